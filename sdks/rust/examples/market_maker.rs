@@ -66,9 +66,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         println!(
             "Cycle: buy@{} sell@{} qty={}",
-            market.format_price(scaled_buy_price),
-            market.format_price(scaled_sell_price),
-            market.format_quantity(scaled_quantity),
+            market.format_price(scaled_buy_price)?,
+            market.format_price(scaled_sell_price)?,
+            market.format_quantity(scaled_quantity)?,
         );
 
         let mut builder = client.actions_for(config.market_pair).await?;
@@ -1,8 +1,7 @@
 use o2_sdk::crypto::*;
 /// Portfolio monitoring example: streams balances and orders via WebSocket,
 /// displays formatted portfolio state and P&L from trade history.
-use o2_sdk::*;
-use tokio_stream::StreamExt;
+use o2_sdk::prelude::*;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -1,8 +1,7 @@
 use o2_sdk::crypto::*;
 /// Taker bot example: monitors depth via WebSocket and executes when price
 /// crosses a configurable threshold.
-use o2_sdk::*;
-use tokio_stream::StreamExt;
+use o2_sdk::prelude::*;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -57,7 +56,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 if ask_price == 0 {
                     continue;
                 }
-                let ask_human = market.format_price(ask_price);
+                let ask_human = market.format_price(ask_price)?;
 
                 if ask_human <= buy_below_price {
                     println!("Target price hit! Best ask: {ask_human}");
@@ -0,0 +1,551 @@
+//! Many trade accounts behind one client.
+//!
+//! An [`AccountSet`] holds a labelled owner wallet per trade account and
+//! drives them all from one [`O2Client`]: setting each account up, opening a
+//! session and [`NonceManager`] for each, submitting batches as a named
+//! account, and summing balances into a [`Portfolio`]. Bulk steps run the
+//! accounts concurrently, each on its own client sharing the connection pool,
+//! carry on past an account that fails and report it as an [`AccountFailure`].
+//!
+//! Each account has its own [`RateLimit`], so one busy account cannot use up
+//! the request budget of the others, and a slow one does not hold the others
+//! back. Every client call made for an account counts as one request against
+//! its limit, and waits until the limit allows it.
+//!
+//! ```rust,ignore
+//! let mut desk = AccountSet::new().with_rate_limit(RateLimit::new(10, Duration::from_secs(1)));
+//! for (i, wallet) in wallets.into_iter().enumerate() {
+//!     desk.add(format!("mm-{i}"), wallet)?;
+//! }
+//! desk.setup(&client).await;
+//! desk.open_sessions(&client, &["fFUEL/fUSDC"], Duration::from_secs(3600)).await;
+//! desk.batch_actions(&mut client, "mm-0", "fFUEL/fUSDC", actions, true).await?;
+//! let portfolio = desk.portfolio(&client).await;
+//! println!("fUSDC across the desk: {:?}", portfolio.totals.get("fUSDC"));
+//! ```
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+
+use crate::client::O2Client;
+use crate::crypto::SignableWallet;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, BalanceResponse, IntoMarketSymbol, Session, SessionActionsResponse, TradeAccountId,
+};
+use crate::nonce::NonceManager;
+use crate::runtime;
+use crate::trace::debug;
+
+/// At most `max_requests` requests in any `per` window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Allow `max_requests` requests per `per`.
+    pub fn new(max_requests: u32, per: Duration) -> Self {
+        Self { max_requests, per }
+    }
+}
+
+/// Sliding-window limiter for one account.
+#[derive(Debug, Clone, Default)]
+struct Limiter {
+    limit: Option<RateLimit>,
+    sent: VecDeque<Instant>,
+}
+
+impl Limiter {
+    fn new(limit: Option<RateLimit>) -> Self {
+        Self {
+            limit,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Take a slot at `now`, or return how long to wait for one.
+    fn try_acquire(&mut self, now: Instant) -> Option<Duration> {
+        let limit = self.limit?;
+        while self
+            .sent
+            .front()
+            .is_some_and(|&at| now.saturating_duration_since(at) >= limit.per)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() < limit.max_requests.max(1) as usize {
+            self.sent.push_back(now);
+            return None;
+        }
+        let oldest = *self.sent.front()?;
+        Some((oldest + limit.per).saturating_duration_since(now))
+    }
+
+    async fn acquire(&mut self) {
+        while let Some(wait) = self.try_acquire(runtime::now()) {
+            runtime::sleep(wait).await;
+        }
+    }
+}
+
+/// One account of an [`AccountSet`].
+pub struct ManagedAccount<W> {
+    label: String,
+    wallet: W,
+    trade_account_id: Option<TradeAccountId>,
+    session: Option<Session>,
+    nonces: Option<NonceManager>,
+    limiter: Limiter,
+}
+
+impl<W> ManagedAccount<W> {
+    /// The label the account was added under.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The owner wallet.
+    pub fn wallet(&self) -> &W {
+        &self.wallet
+    }
+
+    /// The trade account, once set up.
+    pub fn trade_account_id(&self) -> Option<&TradeAccountId> {
+        self.trade_account_id.as_ref()
+    }
+
+    /// The session opened by [`AccountSet::open_sessions`].
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// The nonce manager that batches for this account go through. Share it
+    /// with other tasks submitting for the account.
+    pub fn nonces(&self) -> Option<&NonceManager> {
+        self.nonces.as_ref()
+    }
+}
+
+impl<W> std::fmt::Debug for ManagedAccount<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManagedAccount")
+            .field("label", &self.label)
+            .field("trade_account_id", &self.trade_account_id)
+            .field("has_session", &self.session.is_some())
+            .finish()
+    }
+}
+
+/// An account that failed a bulk step of an [`AccountSet`].
+#[derive(Debug)]
+pub struct AccountFailure {
+    pub label: String,
+    pub error: O2Error,
+}
+
+/// Sum of one asset across the accounts of a [`Portfolio`] (chain integers).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssetTotal {
+    pub total_locked: u128,
+    pub total_unlocked: u128,
+    pub trading_account_balance: u128,
+}
+
+/// Balances of every set-up account in an [`AccountSet`].
+#[derive(Debug, Default)]
+pub struct Portfolio {
+    /// Each account's balances by label, keyed by asset symbol.
+    pub accounts: BTreeMap<String, HashMap<String, BalanceResponse>>,
+    /// Per-asset sums over `accounts`.
+    pub totals: BTreeMap<String, AssetTotal>,
+    /// Accounts whose balances could not be fetched; they are not in the totals.
+    pub failures: Vec<AccountFailure>,
+}
+
+impl Portfolio {
+    fn add(&mut self, label: String, balances: HashMap<String, BalanceResponse>) {
+        for (asset, balance) in &balances {
+            let total = self.totals.entry(asset.clone()).or_default();
+            total.total_locked = total.total_locked.saturating_add(balance.total_locked);
+            total.total_unlocked = total.total_unlocked.saturating_add(balance.total_unlocked);
+            total.trading_account_balance = total
+                .trading_account_balance
+                .saturating_add(balance.trading_account_balance);
+        }
+        self.accounts.insert(label, balances);
+    }
+}
+
+/// Labelled trade accounts driven from one [`O2Client`].
+pub struct AccountSet<W> {
+    accounts: Vec<ManagedAccount<W>>,
+    rate_limit: Option<RateLimit>,
+}
+
+impl<W> Default for AccountSet<W> {
+    fn default() -> Self {
+        Self {
+            accounts: Vec::new(),
+            rate_limit: None,
+        }
+    }
+}
+
+impl<W> std::fmt::Debug for AccountSet<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountSet")
+            .field("accounts", &self.accounts)
+            .field("rate_limit", &self.rate_limit)
+            .finish()
+    }
+}
+
+impl<W: SignableWallet> AccountSet<W> {
+    /// An empty set without rate limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit each account, including ones already added, to `limit`.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        for account in &mut self.accounts {
+            account.limiter = Limiter::new(Some(limit));
+        }
+        self
+    }
+
+    /// Add the account owned by `wallet` under `label`.
+    pub fn add(&mut self, label: impl Into<String>, wallet: W) -> Result<(), O2Error> {
+        let label = label.into();
+        if self.get(&label).is_some() {
+            return Err(O2Error::InvalidRequest(format!(
+                "account {label} is already in the set"
+            )));
+        }
+        self.accounts.push(ManagedAccount {
+            label,
+            wallet,
+            trade_account_id: None,
+            session: None,
+            nonces: None,
+            limiter: Limiter::new(self.rate_limit),
+        });
+        Ok(())
+    }
+
+    /// Number of accounts.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// True if the set has no accounts.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// The account added under `label`.
+    pub fn get(&self, label: &str) -> Option<&ManagedAccount<W>> {
+        self.accounts.iter().find(|a| a.label == label)
+    }
+
+    /// Accounts in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &ManagedAccount<W>> + '_ {
+        self.accounts.iter()
+    }
+
+    fn get_mut(&mut self, label: &str) -> Result<&mut ManagedAccount<W>, O2Error> {
+        self.accounts
+            .iter_mut()
+            .find(|a| a.label == label)
+            .ok_or_else(|| O2Error::InvalidRequest(format!("no account {label} in the set")))
+    }
+
+    /// Run [`O2Client::setup_account`] for every account not set up yet.
+    ///
+    /// Accounts are set up concurrently, each on its own client sharing
+    /// `client`'s connection pool.
+    pub async fn setup(&mut self, client: &O2Client) -> Vec<AccountFailure> {
+        debug!("accounts.setup accounts={}", self.accounts.len());
+        let setups = self
+            .accounts
+            .iter_mut()
+            .filter(|account| account.trade_account_id.is_none())
+            .map(|account| {
+                let mut client = client.worker();
+                async move {
+                    account.limiter.acquire().await;
+                    let error = match client.setup_account(&account.wallet).await {
+                        Ok(resp) => match resp.trade_account_id {
+                            Some(id) => {
+                                account.trade_account_id = Some(id);
+                                return None;
+                            }
+                            None => O2Error::Other("setup returned no trade account id".into()),
+                        },
+                        Err(error) => error,
+                    };
+                    Some(AccountFailure {
+                        label: account.label.clone(),
+                        error,
+                    })
+                }
+            });
+        join_all(setups).await.into_iter().flatten().collect()
+    }
+
+    /// Open a session on `markets` for every set-up account, replacing any
+    /// earlier one, with a fresh [`NonceManager`] seeded from it.
+    ///
+    /// Sessions are opened concurrently, as in [`AccountSet::setup`].
+    pub async fn open_sessions<S: AsRef<str>>(
+        &mut self,
+        client: &O2Client,
+        markets: &[S],
+        ttl: Duration,
+    ) -> Vec<AccountFailure> {
+        debug!(
+            "accounts.open_sessions accounts={} markets={}",
+            self.accounts.len(),
+            markets.len()
+        );
+        let opens = self.accounts.iter_mut().map(|account| {
+            let mut client = client.worker();
+            async move {
+                if account.trade_account_id.is_none() {
+                    return Some(AccountFailure {
+                        label: account.label.clone(),
+                        error: O2Error::AccountNotFound(format!(
+                            "account {} is not set up",
+                            account.label
+                        )),
+                    });
+                }
+                account.limiter.acquire().await;
+                match client.create_session(&account.wallet, markets, ttl).await {
+                    Ok(session) => {
+                        account.nonces = Some(NonceManager::new(&session));
+                        account.session = Some(session);
+                        None
+                    }
+                    Err(error) => Some(AccountFailure {
+                        label: account.label.clone(),
+                        error,
+                    }),
+                }
+            }
+        });
+        join_all(opens).await.into_iter().flatten().collect()
+    }
+
+    /// Submit a batch on `market` as the account under `label`, through its
+    /// nonce manager. See [`O2Client::batch_actions`].
+    ///
+    /// To submit for several accounts at once, do it from separate tasks with
+    /// each account's [`session`](ManagedAccount::session) and
+    /// [`nonces`](ManagedAccount::nonces).
+    pub async fn batch_actions<M: IntoMarketSymbol>(
+        &mut self,
+        client: &mut O2Client,
+        label: &str,
+        market: M,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        let market = market.into_market_symbol()?;
+        debug!(
+            "accounts.batch_actions account={} market={} actions={}",
+            label,
+            market,
+            actions.len()
+        );
+        let account = self.get_mut(label)?;
+        let (Some(session), Some(nonces)) = (account.session.as_mut(), account.nonces.as_ref())
+        else {
+            return Err(O2Error::InvalidSession(format!(
+                "account {label} has no open session"
+            )));
+        };
+        account.limiter.acquire().await;
+        nonces
+            .batch_actions(client, session, market, actions, collect_orders)
+            .await
+    }
+
+    /// Fetch the balances of every set-up account.
+    ///
+    /// Accounts are read concurrently, as in [`AccountSet::setup`].
+    pub async fn portfolio(&mut self, client: &O2Client) -> Portfolio {
+        debug!("accounts.portfolio accounts={}", self.accounts.len());
+        let reads = self.accounts.iter_mut().filter_map(|account| {
+            let trade_account_id = account.trade_account_id.clone()?;
+            let mut client = client.worker();
+            Some(async move {
+                account.limiter.acquire().await;
+                let balances = client.get_balances(&trade_account_id).await;
+                (account.label.clone(), balances)
+            })
+        });
+        let mut portfolio = Portfolio::default();
+        for (label, balances) in join_all(reads).await {
+            match balances {
+                Ok(balances) => portfolio.add(label, balances),
+                Err(error) => portfolio.failures.push(AccountFailure { label, error }),
+            }
+        }
+        portfolio
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, Market, MarketAsset, MarketId, MarketsResponse};
+    use crate::test_util::mock_transport::MockTransport;
+
+    #[test]
+    fn limiter_allows_a_window_of_requests() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(Some(RateLimit::new(2, Duration::from_secs(1))));
+        assert_eq!(limiter.try_acquire(start), None);
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_millis(400)),
+            None
+        );
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_millis(500)),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(1)), None);
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_secs(1)),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(Limiter::new(None).try_acquire(start), None);
+    }
+
+    #[test]
+    fn portfolio_sums_assets_across_accounts() {
+        let balance = |locked: u128, unlocked: u128| -> BalanceResponse {
+            serde_json::from_value(serde_json::json!({
+                "order_books": {},
+                "total_locked": locked.to_string(),
+                "total_unlocked": unlocked.to_string(),
+                "trading_account_balance": unlocked.to_string(),
+            }))
+            .unwrap()
+        };
+        let mut portfolio = Portfolio::default();
+        portfolio.add(
+            "a".into(),
+            HashMap::from([
+                ("fUSDC".into(), balance(1, 10)),
+                ("fFUEL".into(), balance(0, 5)),
+            ]),
+        );
+        portfolio.add(
+            "b".into(),
+            HashMap::from([("fUSDC".into(), balance(2, 20))]),
+        );
+
+        assert_eq!(portfolio.accounts.len(), 2);
+        assert_eq!(
+            portfolio.totals["fUSDC"],
+            AssetTotal {
+                total_locked: 3,
+                total_unlocked: 30,
+                trading_account_balance: 30,
+            }
+        );
+        assert_eq!(portfolio.totals["fFUEL"].total_unlocked, 5);
+    }
+
+    #[test]
+    fn labels_are_unique() {
+        let mut set = AccountSet::new();
+        set.add("a", crate::crypto::generate_keypair().unwrap())
+            .unwrap();
+        assert!(set
+            .add("a", crate::crypto::generate_keypair().unwrap())
+            .is_err());
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.get("a").unwrap().label(), "a");
+        assert!(set.get("a").unwrap().trade_account_id().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn portfolio_reads_accounts_concurrently() {
+        let hex = |n: u64| format!("0x{n:064x}");
+        let markets = MarketsResponse {
+            books_registry_id: ContractId::new(hex(6)),
+            books_whitelist_id: None,
+            books_blacklist_id: None,
+            accounts_registry_id: ContractId::new(hex(2)),
+            trade_account_oracle_id: ContractId::new(hex(7)),
+            fast_bridge_asset_registry_contract_id: None,
+            chain_id: "0x0".into(),
+            base_asset_id: AssetId::new(hex(8)),
+            markets: vec![Market {
+                contract_id: ContractId::new("0xb00c"),
+                market_id: MarketId::new("0xaa"),
+                whitelist_id: None,
+                blacklist_id: None,
+                maker_fee: 0,
+                taker_fee: 0,
+                min_order: 0,
+                dust: 0,
+                price_window: 0,
+                base: MarketAsset {
+                    symbol: "fFUEL".into(),
+                    asset: AssetId::new(hex(3)),
+                    decimals: 9,
+                    max_precision: 3,
+                },
+                quote: MarketAsset {
+                    symbol: "fUSDC".into(),
+                    asset: AssetId::new(hex(4)),
+                    decimals: 6,
+                    max_precision: 4,
+                },
+            }],
+        };
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/markets", serde_json::to_value(markets).unwrap());
+        mock.fixture(
+            "GET",
+            "/v1/balance",
+            serde_json::json!({
+                "order_books": {},
+                "total_locked": "0",
+                "total_unlocked": "1",
+                "trading_account_balance": "1",
+            }),
+        );
+        mock.set_latency(Duration::from_secs(1));
+        let mut set = AccountSet::new().with_rate_limit(RateLimit::new(1, Duration::from_secs(60)));
+        for i in 0..4 {
+            set.add(
+                format!("mm-{i}"),
+                crate::crypto::generate_keypair().unwrap(),
+            )
+            .unwrap();
+        }
+        for (i, account) in set.accounts.iter_mut().enumerate() {
+            account.trade_account_id = Some(TradeAccountId::new(hex(0x10 + i as u64)));
+        }
+
+        let started = tokio::time::Instant::now();
+        let portfolio = set.portfolio(&mock.client()).await;
+
+        assert!(portfolio.failures.is_empty(), "{:?}", portfolio.failures);
+        assert_eq!(portfolio.accounts.len(), 4);
+        assert_eq!(portfolio.totals["fUSDC"].total_unlocked, 4);
+        // Each account reads the markets, the asset list and its balances, one
+        // round trip each; one account after another would take twelve.
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,258 @@
+//! Allocation counting for hot paths. Requires the `alloc-audit` feature.
+//!
+//! With the feature on, the SDK counts the heap allocations made by each
+//! [`O2Client::batch_actions`](crate::O2Client::batch_actions) call (site
+//! `"batch_actions"`, including request building and response parsing on the
+//! calling task) and by parsing each depth update off the WebSocket (site
+//! `"depth_update"`). [`report`] lists per-call figures and
+//! [`assert_max_allocs`] fails a test when a site regresses, so allocation
+//! work can be verified and kept.
+//!
+//! Counts come from [`CountingAllocator`], which the test binary must install
+//! as its global allocator. Allocations are counted on the thread that makes
+//! them, and a measured future only counts while it is being polled, so work
+//! on other tasks is not attributed to it.
+//!
+//! ```rust,ignore
+//! #[global_allocator]
+//! static ALLOC: o2_sdk::alloc_audit::CountingAllocator = o2_sdk::alloc_audit::CountingAllocator;
+//!
+//! client.batch_actions(&mut session, "fFUEL/fUSDC", actions, false).await?;
+//! o2_sdk::alloc_audit::assert_max_allocs("batch_actions", 400);
+//! ```
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::{Add, Sub};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::task::{Context, Poll};
+
+thread_local! {
+    static THREAD_ALLOCS: Cell<AllocStats> = const { Cell::new(AllocStats::ZERO) };
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// A global allocator that counts allocations per thread, then defers to
+/// [`System`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingAllocator;
+
+// Implementing `GlobalAlloc` is unsafe by definition; every method forwards
+// the caller's contract to `System` unchanged.
+#[allow(unsafe_code)]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+fn count(bytes: usize) {
+    INSTALLED.store(true, Ordering::Relaxed);
+    // `try_with` fails only while the thread is being torn down.
+    let _ = THREAD_ALLOCS.try_with(|c| {
+        let mut stats = c.get();
+        stats.allocs += 1;
+        stats.bytes += bytes as u64;
+        c.set(stats);
+    });
+}
+
+fn thread_stats() -> AllocStats {
+    THREAD_ALLOCS
+        .try_with(Cell::get)
+        .unwrap_or(AllocStats::ZERO)
+}
+
+/// True once [`CountingAllocator`] has served an allocation, i.e. it is the
+/// global allocator.
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Allocations made, counting reallocations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocs: u64,
+    pub bytes: u64,
+}
+
+impl AllocStats {
+    const ZERO: AllocStats = AllocStats {
+        allocs: 0,
+        bytes: 0,
+    };
+}
+
+impl Add for AllocStats {
+    type Output = AllocStats;
+    fn add(self, rhs: AllocStats) -> AllocStats {
+        AllocStats {
+            allocs: self.allocs + rhs.allocs,
+            bytes: self.bytes + rhs.bytes,
+        }
+    }
+}
+
+impl Sub for AllocStats {
+    type Output = AllocStats;
+    fn sub(self, rhs: AllocStats) -> AllocStats {
+        AllocStats {
+            allocs: self.allocs.saturating_sub(rhs.allocs),
+            bytes: self.bytes.saturating_sub(rhs.bytes),
+        }
+    }
+}
+
+/// Run `f` and count the allocations it makes on this thread.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    let before = thread_stats();
+    let result = f();
+    (result, thread_stats() - before)
+}
+
+/// Wrap `future` so its output comes with the allocations made while it was
+/// polled. Boxing the future happens before counting starts.
+pub fn measure_future<F: Future>(future: F) -> Measured<F> {
+    Measured {
+        inner: Box::pin(future),
+        stats: AllocStats::ZERO,
+    }
+}
+
+/// A future counting its own allocations. See [`measure_future`].
+pub struct Measured<F> {
+    inner: Pin<Box<F>>,
+    stats: AllocStats,
+}
+
+impl<F: Future> Future for Measured<F> {
+    type Output = (F::Output, AllocStats);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let before = thread_stats();
+        let poll = self.inner.as_mut().poll(cx);
+        let spent = thread_stats() - before;
+        self.stats = self.stats + spent;
+        poll.map(|output| (output, self.stats))
+    }
+}
+
+/// Per-call allocation figures of one site.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SiteReport {
+    pub site: String,
+    pub calls: u64,
+    pub total: AllocStats,
+    /// The most allocations any single call made.
+    pub max_allocs: u64,
+    /// The allocations of the latest call.
+    pub last: AllocStats,
+}
+
+impl SiteReport {
+    /// Mean allocations per call.
+    pub fn mean_allocs(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.total.allocs as f64 / self.calls as f64
+        }
+    }
+}
+
+fn sites() -> &'static Mutex<BTreeMap<String, SiteReport>> {
+    static SITES: OnceLock<Mutex<BTreeMap<String, SiteReport>>> = OnceLock::new();
+    SITES.get_or_init(Default::default)
+}
+
+/// Add one call of `site` that made `stats` allocations.
+pub fn record(site: &str, stats: AllocStats) {
+    let mut sites = sites().lock().unwrap_or_else(|p| p.into_inner());
+    let report = sites.entry(site.to_string()).or_insert_with(|| SiteReport {
+        site: site.to_string(),
+        ..SiteReport::default()
+    });
+    report.calls += 1;
+    report.total = report.total + stats;
+    report.max_allocs = report.max_allocs.max(stats.allocs);
+    report.last = stats;
+}
+
+/// [`measure`] `f` and [`record`] it as a call of `site`.
+pub fn track<R>(site: &str, f: impl FnOnce() -> R) -> R {
+    let (result, stats) = measure(f);
+    record(site, stats);
+    result
+}
+
+/// [`measure_future`] `future` and [`record`] it as a call of `site`.
+pub async fn track_future<F: Future>(site: &str, future: F) -> F::Output {
+    let (output, stats) = measure_future(future).await;
+    record(site, stats);
+    output
+}
+
+/// Figures for every site called so far, by site name.
+pub fn report() -> Vec<SiteReport> {
+    sites()
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// Figures for one site, if it has been called.
+pub fn site_report(site: &str) -> Option<SiteReport> {
+    sites()
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .get(site)
+        .cloned()
+}
+
+/// Forget every recorded call.
+pub fn reset() {
+    sites().lock().unwrap_or_else(|p| p.into_inner()).clear();
+}
+
+/// Panic unless every recorded call of `site` made at most `max` allocations.
+///
+/// Also panics if [`CountingAllocator`] is not installed or `site` was never
+/// called, so a misconfigured test cannot pass by counting nothing.
+pub fn assert_max_allocs(site: &str, max: u64) {
+    assert!(
+        is_installed(),
+        "alloc_audit: CountingAllocator is not the global allocator"
+    );
+    let report =
+        site_report(site).unwrap_or_else(|| panic!("alloc_audit: site {site:?} was never called"));
+    assert!(
+        report.max_allocs <= max,
+        "alloc_audit: {site} made up to {} allocations per call (limit {max}; {} calls, mean {:.1}, last {} allocs / {} bytes)",
+        report.max_allocs,
+        report.calls,
+        report.mean_allocs(),
+        report.last.allocs,
+        report.last.bytes,
+    );
+}
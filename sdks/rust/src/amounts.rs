@@ -0,0 +1,509 @@
+//! Base and quote amounts whose units are checked by the compiler.
+//!
+//! A market's quantities are in base units and its prices and notionals in
+//! quote units, but they are all just decimals, so nothing stops a base
+//! quantity from being multiplied by a quote balance. [`BaseAmount`],
+//! [`QuoteAmount`] and [`UnitPrice`] carry a market tag type `M` and only
+//! define the arithmetic that makes sense:
+//!
+//! | Expression | Result |
+//! |------------|--------|
+//! | `BaseAmount<M> + BaseAmount<M>` | `BaseAmount<M>` |
+//! | `QuoteAmount<M> + QuoteAmount<M>` | `QuoteAmount<M>` |
+//! | `BaseAmount<M> * UnitPrice<M>` (either order) | `QuoteAmount<M>` |
+//! | `QuoteAmount<M>.checked_div_price(&UnitPrice<M>)` | `BaseAmount<M>` |
+//! | `x.checked_sub(y)` on base or quote | same type, error if negative |
+//! | `x.scale(factor)` on base or quote | same type, e.g. for fee rates |
+//!
+//! Anything else, such as base × quote or amounts of two tags, does not
+//! compile. The tag is any type you declare for a market and bind once with
+//! [`Market::typed`]; use one tag per market. Amounts are also bound to the
+//! market at runtime like [`Price`] and [`Quantity`], so
+//! [`O2Client::create_order`](crate::O2Client::create_order) accepts
+//! [`UnitPrice`] and [`BaseAmount`] directly and rejects them for another market.
+//!
+//! ```rust,ignore
+//! enum FuelUsdc {}
+//!
+//! let fuel = client.get_market("FUEL/USDC").await?.typed::<FuelUsdc>();
+//! let size = fuel.base("250")?;
+//! let price = fuel.price("0.0213")?;
+//! let notional = size.clone() * price.clone();           // QuoteAmount<FuelUsdc>
+//! let budget = fuel.quote_from_chain(balance.total_unlocked)?;
+//! if notional <= budget {
+//!     client.create_order(&mut session, "FUEL/USDC", Side::Buy, price, size, OrderType::Spot, true, true).await?;
+//! }
+//! ```
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul};
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{Market, MarketId, OrderPriceInput, OrderQuantityInput, Price, Quantity};
+
+/// Ties amounts to a market type without owning an `M` (and without requiring
+/// `M` to be `Send`, `Sync` or inhabited).
+type Tag<M> = PhantomData<fn() -> M>;
+
+/// Round `value` down to `places` decimal places.
+fn truncate(value: UnsignedDecimal, places: u32) -> UnsignedDecimal {
+    UnsignedDecimal::new(
+        value
+            .into_inner()
+            .round_dp_with_strategy(places, RoundingStrategy::ToZero),
+    )
+    .expect("truncating a non-negative value stays non-negative")
+}
+
+/// The market parameters an amount was created against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Binding {
+    market_id: MarketId,
+    base_decimals: u32,
+    base_max_precision: u32,
+    quote_decimals: u32,
+    quote_max_precision: u32,
+}
+
+impl Binding {
+    fn of(market: &Market) -> Self {
+        Self {
+            market_id: market.market_id.clone(),
+            base_decimals: market.base.decimals,
+            base_max_precision: market.base.max_precision,
+            quote_decimals: market.quote.decimals,
+            quote_max_precision: market.quote.max_precision,
+        }
+    }
+}
+
+fn from_chain(chain_value: u128, decimals: u32) -> Result<UnsignedDecimal, O2Error> {
+    let raw = i128::try_from(chain_value)
+        .ok()
+        .and_then(|v| Decimal::try_from_i128_with_scale(v, decimals).ok())
+        .ok_or_else(|| {
+            O2Error::Other(format!(
+                "Cannot represent {chain_value} with {decimals} decimals"
+            ))
+        })?;
+    UnsignedDecimal::new(raw.normalize())
+}
+
+// ---------------------------------------------------------------------------
+// Typed market
+// ---------------------------------------------------------------------------
+
+/// A [`Market`] bound to the tag type `M`; the source of all `M` amounts.
+pub struct TypedMarket<M> {
+    market: Market,
+    _tag: Tag<M>,
+}
+
+impl Market {
+    /// Bind this market to the tag type `M`. See [`crate::amounts`].
+    pub fn typed<M>(&self) -> TypedMarket<M> {
+        TypedMarket {
+            market: self.clone(),
+            _tag: PhantomData,
+        }
+    }
+}
+
+impl<M> TypedMarket<M> {
+    /// The underlying market.
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// A base quantity from a decimal string, validated like [`Market::quantity`].
+    pub fn base(&self, value: &str) -> Result<BaseAmount<M>, O2Error> {
+        self.base_from_decimal(self.market.quantity(value)?.value())
+    }
+
+    /// A base quantity, validated like [`Market::quantity_from_decimal`].
+    pub fn base_from_decimal(&self, value: UnsignedDecimal) -> Result<BaseAmount<M>, O2Error> {
+        let quantity = self.market.quantity_from_decimal(value)?;
+        Ok(BaseAmount::bound(
+            quantity.value(),
+            Binding::of(&self.market),
+        ))
+    }
+
+    /// A base quantity from chain units, such as a balance, rounded down to
+    /// the market's quantity precision.
+    pub fn base_from_chain(&self, chain_value: u128) -> Result<BaseAmount<M>, O2Error> {
+        let value = from_chain(chain_value, self.market.base.decimals)?;
+        self.base_from_decimal(truncate(value, self.market.base.max_precision))
+    }
+
+    /// A unit price from a decimal string, validated like [`Market::price`].
+    pub fn price(&self, value: &str) -> Result<UnitPrice<M>, O2Error> {
+        self.price_from_decimal(self.market.price(value)?.value())
+    }
+
+    /// A unit price, validated like [`Market::price_from_decimal`].
+    pub fn price_from_decimal(&self, value: UnsignedDecimal) -> Result<UnitPrice<M>, O2Error> {
+        let price = self.market.price_from_decimal(value)?;
+        Ok(UnitPrice::bound(price.value(), Binding::of(&self.market)))
+    }
+
+    /// A unit price from chain units, as in order books and orders.
+    pub fn price_from_chain(&self, chain_value: u64) -> Result<UnitPrice<M>, O2Error> {
+        self.price_from_decimal(self.market.format_price(chain_value)?)
+    }
+
+    /// A quote amount. Unlike prices it may have any number of decimals.
+    pub fn quote_from_decimal(&self, value: UnsignedDecimal) -> QuoteAmount<M> {
+        QuoteAmount::bound(value, Binding::of(&self.market))
+    }
+
+    /// A quote amount from a decimal string.
+    pub fn quote(&self, value: &str) -> Result<QuoteAmount<M>, O2Error> {
+        Ok(self.quote_from_decimal(value.parse()?))
+    }
+
+    /// A quote amount from chain units, such as a balance.
+    pub fn quote_from_chain(&self, chain_value: u128) -> Result<QuoteAmount<M>, O2Error> {
+        Ok(self.quote_from_decimal(from_chain(chain_value, self.market.quote.decimals)?))
+    }
+}
+
+impl<M> Clone for TypedMarket<M> {
+    fn clone(&self) -> Self {
+        self.market.typed()
+    }
+}
+
+impl<M> fmt::Debug for TypedMarket<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedMarket")
+            .field("tag", &std::any::type_name::<M>())
+            .field("market_id", &self.market.market_id)
+            .finish()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Amounts
+// ---------------------------------------------------------------------------
+
+/// A quantity of market `M`'s base asset, within the market's precision.
+pub struct BaseAmount<M> {
+    value: UnsignedDecimal,
+    binding: Binding,
+    _tag: Tag<M>,
+}
+
+/// Price of one unit of market `M`'s base asset, in its quote asset.
+pub struct UnitPrice<M> {
+    value: UnsignedDecimal,
+    binding: Binding,
+    _tag: Tag<M>,
+}
+
+/// An amount of market `M`'s quote asset, such as a notional, fee or balance.
+pub struct QuoteAmount<M> {
+    value: UnsignedDecimal,
+    binding: Binding,
+    _tag: Tag<M>,
+}
+
+impl<M> BaseAmount<M> {
+    /// The amount as an order [`Quantity`].
+    pub fn to_quantity(&self) -> Quantity {
+        Quantity {
+            value: self.value,
+            market_id: self.binding.market_id.clone(),
+            base_decimals: self.binding.base_decimals,
+            base_max_precision: self.binding.base_max_precision,
+        }
+    }
+
+    /// `self - other`, or an error if `other` is larger.
+    pub fn checked_sub(&self, other: &Self) -> Result<Self, O2Error> {
+        Ok(self.with_value(self.value.try_sub(other.value)?))
+    }
+
+    /// `self * factor`, rounded down to the market's quantity precision.
+    pub fn scale(&self, factor: UnsignedDecimal) -> Self {
+        self.with_value(truncate(
+            self.value * factor,
+            self.binding.base_max_precision,
+        ))
+    }
+}
+
+impl<M> UnitPrice<M> {
+    /// The price as an order [`Price`].
+    pub fn to_price(&self) -> Price {
+        Price {
+            value: self.value,
+            market_id: self.binding.market_id.clone(),
+            quote_decimals: self.binding.quote_decimals,
+            quote_max_precision: self.binding.quote_max_precision,
+        }
+    }
+}
+
+impl<M> QuoteAmount<M> {
+    /// The amount in chain units of the quote asset, rounded down.
+    pub fn to_chain(&self) -> Result<u128, O2Error> {
+        let decimals = self.binding.quote_decimals;
+        10i128
+            .checked_pow(decimals)
+            .and_then(|factor| Decimal::try_from_i128_with_scale(factor, 0).ok())
+            .and_then(|factor| self.value.inner().checked_mul(factor))
+            .and_then(|scaled| scaled.floor().to_u128())
+            .ok_or_else(|| {
+                O2Error::Other(format!(
+                    "Cannot scale quote amount {} to {decimals} decimals",
+                    self.value
+                ))
+            })
+    }
+
+    /// `self - other`, or an error if `other` is larger.
+    pub fn checked_sub(&self, other: &Self) -> Result<Self, O2Error> {
+        Ok(self.with_value(self.value.try_sub(other.value)?))
+    }
+
+    /// `self * factor`, e.g. a fee rate.
+    pub fn scale(&self, factor: UnsignedDecimal) -> Self {
+        self.with_value(self.value * factor)
+    }
+
+    /// How much base this buys at `price`, rounded down to the market's
+    /// quantity precision. Fails for a zero price.
+    pub fn checked_div_price(&self, price: &UnitPrice<M>) -> Result<BaseAmount<M>, O2Error> {
+        let base = self
+            .value
+            .inner()
+            .checked_div(*price.value.inner())
+            .ok_or_else(|| O2Error::InvalidOrderParams("Price cannot be zero".into()))?;
+        let base = truncate(UnsignedDecimal::new(base)?, self.binding.base_max_precision);
+        Ok(BaseAmount::bound(base, self.binding.clone()))
+    }
+}
+
+impl<M> Add for BaseAmount<M> {
+    type Output = BaseAmount<M>;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.with_value(self.value + rhs.value)
+    }
+}
+
+impl<M> Add for QuoteAmount<M> {
+    type Output = QuoteAmount<M>;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.with_value(self.value + rhs.value)
+    }
+}
+
+impl<M> Mul<UnitPrice<M>> for BaseAmount<M> {
+    type Output = QuoteAmount<M>;
+    fn mul(self, rhs: UnitPrice<M>) -> Self::Output {
+        QuoteAmount::bound(self.value * rhs.value, self.binding)
+    }
+}
+
+impl<M> Mul<BaseAmount<M>> for UnitPrice<M> {
+    type Output = QuoteAmount<M>;
+    fn mul(self, rhs: BaseAmount<M>) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl<M> TryFrom<BaseAmount<M>> for OrderQuantityInput {
+    type Error = O2Error;
+    fn try_from(value: BaseAmount<M>) -> Result<Self, Self::Error> {
+        Ok(Self::Checked(value.to_quantity()))
+    }
+}
+
+impl<M> TryFrom<UnitPrice<M>> for OrderPriceInput {
+    type Error = O2Error;
+    fn try_from(value: UnitPrice<M>) -> Result<Self, Self::Error> {
+        Ok(Self::Checked(value.to_price()))
+    }
+}
+
+macro_rules! amount_impls {
+    ($ty:ident) => {
+        impl<M> $ty<M> {
+            fn bound(value: UnsignedDecimal, binding: Binding) -> Self {
+                Self {
+                    value,
+                    binding,
+                    _tag: PhantomData,
+                }
+            }
+
+            fn with_value(&self, value: UnsignedDecimal) -> Self {
+                Self::bound(value, self.binding.clone())
+            }
+
+            /// Human-readable decimal value.
+            pub fn value(&self) -> UnsignedDecimal {
+                self.value
+            }
+
+            /// Market this value is bound to.
+            pub fn market_id(&self) -> &MarketId {
+                &self.binding.market_id
+            }
+        }
+
+        impl<M> Clone for $ty<M> {
+            fn clone(&self) -> Self {
+                self.with_value(self.value)
+            }
+        }
+
+        impl<M> PartialEq for $ty<M> {
+            fn eq(&self, other: &Self) -> bool {
+                self.binding == other.binding && self.value == other.value
+            }
+        }
+
+        /// Values bound to different markets are unordered.
+        impl<M> PartialOrd for $ty<M> {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                (self.binding == other.binding).then(|| self.value.cmp(&other.value))
+            }
+        }
+
+        impl<M> fmt::Display for $ty<M> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.value.fmt(f)
+            }
+        }
+
+        impl<M> fmt::Debug for $ty<M> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($ty))
+                    .field("value", &self.value)
+                    .field("market_id", &self.binding.market_id)
+                    .field("tag", &std::any::type_name::<M>())
+                    .finish()
+            }
+        }
+    };
+}
+
+amount_impls!(BaseAmount);
+amount_impls!(UnitPrice);
+amount_impls!(QuoteAmount);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, MarketAsset};
+
+    enum FuelUsdc {}
+
+    fn market(market_id: &str) -> Market {
+        Market {
+            contract_id: ContractId::new("0x01"),
+            market_id: MarketId::new(market_id),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 0,
+            dust: 0,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "FUEL".to_string(),
+                asset: AssetId::new("0xbase"),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "USDC".to_string(),
+                asset: AssetId::new("0xquote"),
+                decimals: 6,
+                max_precision: 4,
+            },
+        }
+    }
+
+    fn dec(s: &str) -> UnsignedDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn legal_arithmetic_keeps_units() {
+        let fuel = market("0xaa").typed::<FuelUsdc>();
+        let size = fuel.base("250.5").unwrap() + fuel.base("0.25").unwrap();
+        assert_eq!(size.value(), dec("250.75"));
+        let price = fuel.price("0.0213").unwrap();
+        let notional: QuoteAmount<FuelUsdc> = size.clone() * price.clone();
+        assert_eq!(notional.value(), dec("5.340975"));
+        assert_eq!(notional.to_chain().unwrap(), 5_340_975);
+        assert_eq!(
+            (price.clone() * size.clone()).value(),
+            notional.value(),
+            "multiplication commutes"
+        );
+
+        let fee = notional.scale(dec("0.001"));
+        assert_eq!(fee.value(), dec("0.005340975"));
+        assert_eq!(fee.to_chain().unwrap(), 5_340);
+
+        // Division rounds down to the base precision.
+        let budget = fuel.quote("10").unwrap();
+        assert_eq!(
+            budget.checked_div_price(&price).unwrap().value(),
+            dec("469.483")
+        );
+        assert!(budget.checked_div_price(&fuel.price("0").unwrap()).is_err());
+
+        assert!(notional < budget);
+        assert!(size.checked_sub(&fuel.base("300").unwrap()).is_err());
+        assert_eq!(
+            size.scale(dec("0.3333")).value(),
+            dec("83.574"),
+            "scaled base is truncated to max_precision"
+        );
+    }
+
+    #[test]
+    fn chain_values_convert_at_market_decimals() {
+        let fuel = market("0xaa").typed::<FuelUsdc>();
+        assert_eq!(
+            fuel.quote_from_chain(12_500_000).unwrap().value(),
+            dec("12.5")
+        );
+        assert_eq!(
+            fuel.base_from_chain(1_234_567_891).unwrap().value(),
+            dec("1.234")
+        );
+        assert_eq!(
+            fuel.price_from_chain(21_300).unwrap().value(),
+            dec("0.0213")
+        );
+        assert!(fuel.base("0.0001").is_err(), "precision is validated");
+    }
+
+    #[test]
+    fn amounts_stay_bound_to_their_market() {
+        let a = market("0xaa");
+        let b = market("0xbb");
+        let size = a.typed::<FuelUsdc>().base("1").unwrap();
+        assert!(a.validate_quantity_binding(&size.to_quantity()).is_ok());
+        assert!(b.validate_quantity_binding(&size.to_quantity()).is_err());
+
+        let other = b.typed::<FuelUsdc>().base("1").unwrap();
+        assert_ne!(size, other);
+        assert_eq!(size.partial_cmp(&other), None);
+        assert!(matches!(
+            OrderQuantityInput::try_from(size).unwrap(),
+            OrderQuantityInput::Checked(_)
+        ));
+    }
+}
@@ -0,0 +1,440 @@
+//! VWAP and TWAP reference prices from trades and bars.
+//!
+//! [`vwap`] and [`twap`] compute one value over a slice of fetched
+//! [`Trade`]s or [`Bar`]s. [`RollingVwap`] and [`RollingTwap`] keep one
+//! trailing window and update per trade or bar, and [`LiveReferencePrice`]
+//! feeds both from the trade stream on a background task. Prices are
+//! chain-scaled integers rounded to the nearest unit, so they plug straight
+//! into a [`Quoter`](crate::Quoter) as its reference price:
+//!
+//! ```rust,ignore
+//! let reference = client
+//!     .live_reference_price("fFUEL/fUSDC", Duration::from_secs(300))
+//!     .await?;
+//! let mut versions = reference.subscribe();
+//! while versions.changed().await.is_ok() {
+//!     if let Some(price) = reference.vwap() {
+//!         let plan = quoter.plan(&mine.read(), price)?;
+//!         // submit plan.cancels and plan.places
+//!     }
+//! }
+//! ```
+//!
+//! Windows trail the newest trade or bar seen, except a TWAP's, which ends at
+//! the time it is read: the last price is held until then.
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::sync::watch;
+
+use crate::models::{Bar, Trade, TradeUpdate};
+use crate::runtime::{self, Task};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+fn millis(ts: Timestamp) -> u64 {
+    u64::try_from(ts.as_unix_millis()).unwrap_or(u64::MAX)
+}
+
+fn window_millis(window: Duration) -> u64 {
+    u64::try_from(window.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// `sum / weight` rounded to the nearest unit.
+fn rounded(sum: u128, weight: u128) -> Option<u64> {
+    if weight == 0 {
+        return None;
+    }
+    u64::try_from((sum + weight / 2) / weight).ok()
+}
+
+/// Volume-weighted average price of the trades within `window` of the newest
+/// one. `None` without trades or volume.
+pub fn vwap(trades: &[Trade], window: Duration) -> Option<u64> {
+    let newest = trades.iter().map(|t| millis(t.timestamp)).max()?;
+    let start = newest.saturating_sub(window_millis(window));
+    let (notional, volume) =
+        trades
+            .iter()
+            .filter(|t| millis(t.timestamp) >= start)
+            .fold((0u128, 0u128), |(n, v), t| {
+                (
+                    n.saturating_add(t.price as u128 * t.quantity as u128),
+                    v + t.quantity as u128,
+                )
+            });
+    rounded(notional, volume)
+}
+
+/// Time-weighted average close of the bars opened within `window` of the
+/// newest one. Bars of one resolution cover equal time, so each close weighs
+/// the same; pass bars of a single resolution. `None` without bars.
+pub fn twap(bars: &[Bar], window: Duration) -> Option<u64> {
+    let newest = bars.iter().map(|b| millis(b.timestamp)).max()?;
+    let start = newest.saturating_sub(window_millis(window));
+    let (sum, count) = bars
+        .iter()
+        .filter(|b| millis(b.timestamp) >= start)
+        .fold((0u128, 0u128), |(s, c), b| (s + b.close as u128, c + 1));
+    rounded(sum, count)
+}
+
+/// VWAP over a trailing window, updated trade by trade.
+///
+/// Feed trades oldest first, as streams and
+/// [`TradeHistory`](crate::TradeHistory) deliver them. Memory is bounded by
+/// the trades inside one window.
+#[derive(Debug, Clone)]
+pub struct RollingVwap {
+    window: u64,
+    trades: VecDeque<(u64, u128, u64)>,
+    notional: u128,
+    volume: u128,
+}
+
+impl RollingVwap {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window_millis(window),
+            trades: VecDeque::new(),
+            notional: 0,
+            volume: 0,
+        }
+    }
+
+    /// Fold in a trade, dropping those that fall out of the window.
+    pub fn update(&mut self, trade: &Trade) {
+        let at = millis(trade.timestamp);
+        let newest = self.trades.back().map_or(at, |&(t, _, _)| t.max(at));
+        let start = newest.saturating_sub(self.window);
+        if at < start {
+            return;
+        }
+        let notional = trade.price as u128 * trade.quantity as u128;
+        self.trades.push_back((at, notional, trade.quantity));
+        self.notional = self.notional.saturating_add(notional);
+        self.volume += trade.quantity as u128;
+        while let Some(&(t, n, q)) = self.trades.front() {
+            if t >= start {
+                break;
+            }
+            self.trades.pop_front();
+            self.notional -= n;
+            self.volume -= q as u128;
+        }
+    }
+
+    /// The VWAP of the window; `None` before any volume.
+    pub fn value(&self) -> Option<u64> {
+        rounded(self.notional, self.volume)
+    }
+
+    /// Base volume traded in the window.
+    pub fn volume(&self) -> u128 {
+        self.volume
+    }
+}
+
+/// TWAP over a trailing window, from trade prices or bar closes.
+///
+/// Each price holds from its timestamp until the next one; a bar's close is
+/// placed at the bar's open time, so it holds for the bar's duration. Feed
+/// samples oldest first.
+#[derive(Debug, Clone)]
+pub struct RollingTwap {
+    window: u64,
+    samples: VecDeque<(u64, u64)>,
+}
+
+impl RollingTwap {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window_millis(window),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record `price` as the price from `at` on.
+    pub fn update(&mut self, at: Timestamp, price: u64) {
+        let at = millis(at);
+        if let Some(&(last, _)) = self.samples.back() {
+            if at < last {
+                return;
+            }
+        }
+        self.samples.push_back((at, price));
+        // Keep the sample in effect at the window's start.
+        let start = at.saturating_sub(self.window);
+        while self.samples.len() > 1 && self.samples[1].0 <= start {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn update_trade(&mut self, trade: &Trade) {
+        self.update(trade.timestamp, trade.price);
+    }
+
+    pub fn update_bar(&mut self, bar: &Bar) {
+        self.update(bar.timestamp, bar.close);
+    }
+
+    /// The TWAP of the window ending at `now`; `None` before any sample at
+    /// or before `now`. At the instant of the first sample, its price.
+    pub fn value_at(&self, now: Timestamp) -> Option<u64> {
+        let now = millis(now);
+        let start = now.saturating_sub(self.window);
+        let mut sum = 0u128;
+        let mut span = 0u128;
+        let mut current = None;
+        for (i, &(at, price)) in self.samples.iter().enumerate() {
+            if at > now {
+                break;
+            }
+            current = Some(price);
+            let until = self
+                .samples
+                .get(i + 1)
+                .map_or(now, |&(next, _)| next.min(now));
+            let from = at.max(start);
+            if until > from {
+                let held = (until - from) as u128;
+                sum += price as u128 * held;
+                span += held;
+            }
+        }
+        rounded(sum, span).or(current)
+    }
+
+    /// [`value_at`](Self::value_at) the current time.
+    pub fn value(&self) -> Option<u64> {
+        self.value_at(Timestamp::now())
+    }
+}
+
+#[derive(Debug)]
+struct ReferenceState {
+    vwap: RollingVwap,
+    twap: RollingTwap,
+    last: Option<Trade>,
+}
+
+/// VWAP and TWAP of one market over a trailing window, maintained from a
+/// trade stream on a background task.
+///
+/// Each [`TradeUpdate`] is applied oldest trade first, including the recent
+/// trades the server sends on subscribe. The task stops when the stream ends
+/// and is aborted when the `LiveReferencePrice` is dropped.
+pub struct LiveReferencePrice {
+    state: Arc<RwLock<ReferenceState>>,
+    version_rx: watch::Receiver<u64>,
+    last_error: Arc<RwLock<Option<String>>>,
+    task: Task<()>,
+}
+
+impl LiveReferencePrice {
+    /// Start tracking prices over `window` from `stream`.
+    pub fn spawn(window: Duration, mut stream: TypedStream<TradeUpdate>) -> Self {
+        let state = Arc::new(RwLock::new(ReferenceState {
+            vwap: RollingVwap::new(window),
+            twap: RollingTwap::new(window),
+            last: None,
+        }));
+        let last_error = Arc::new(RwLock::new(None));
+        let (version_tx, version_rx) = watch::channel(0u64);
+
+        let task_state = state.clone();
+        let task_error = last_error.clone();
+        let task = runtime::spawn(async move {
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(update) => {
+                        if update.trades.is_empty() {
+                            continue;
+                        }
+                        let mut trades = update.trades;
+                        trades.sort_by_key(|t| t.timestamp);
+                        {
+                            let mut state = task_state.write().unwrap_or_else(|p| p.into_inner());
+                            for trade in &trades {
+                                state.vwap.update(trade);
+                                state.twap.update_trade(trade);
+                            }
+                            state.last = trades.pop();
+                        }
+                        version_tx.send_modify(|v| *v = v.wrapping_add(1));
+                    }
+                    Err(e) => {
+                        debug!("analytics.reference stream_error error={}", e);
+                        *task_error.write().unwrap_or_else(|p| p.into_inner()) =
+                            Some(e.to_string());
+                    }
+                }
+            }
+            debug!("analytics.reference stream_ended");
+        });
+
+        Self {
+            state,
+            version_rx,
+            last_error,
+            task,
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, ReferenceState> {
+        self.state.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// See [`RollingVwap::value`].
+    pub fn vwap(&self) -> Option<u64> {
+        self.read().vwap.value()
+    }
+
+    /// See [`RollingTwap::value`].
+    pub fn twap(&self) -> Option<u64> {
+        self.read().twap.value()
+    }
+
+    /// Base volume traded in the window.
+    pub fn volume(&self) -> u128 {
+        self.read().vwap.volume()
+    }
+
+    /// The most recent trade received.
+    pub fn last_trade(&self) -> Option<Trade> {
+        self.read().last.clone()
+    }
+
+    /// A receiver whose value increments each time trades are applied.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version_rx.clone()
+    }
+
+    /// The most recent stream error, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+
+    /// True while the background task is still consuming the stream.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for LiveReferencePrice {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MarketId;
+
+    fn trade(millis: u64, price: u64, quantity: u64) -> Trade {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": format!("0x{millis:064x}"),
+            "side": "Buy",
+            "total": "0",
+            "quantity": quantity.to_string(),
+            "price": price.to_string(),
+            "timestamp": millis,
+        }))
+        .unwrap()
+    }
+
+    fn bar(millis: u64, close: u64) -> Bar {
+        serde_json::from_value(serde_json::json!({
+            "open": "0", "high": "0", "low": "0", "close": close.to_string(),
+            "buy_volume": "0", "sell_volume": "0", "timestamp": millis,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn slice_vwap_and_twap_use_the_trailing_window() {
+        let trades = [trade(0, 50, 10), trade(1_000, 100, 1), trade(2_000, 110, 3)];
+        assert_eq!(vwap(&trades, Duration::from_secs(1)), Some(108));
+        assert_eq!(vwap(&trades, Duration::from_secs(5)), Some(66));
+        assert_eq!(vwap(&[], Duration::from_secs(1)), None);
+
+        let bars = [bar(0, 10), bar(60_000, 20), bar(120_000, 31)];
+        assert_eq!(twap(&bars, Duration::from_secs(60)), Some(26));
+        assert_eq!(twap(&bars, Duration::from_secs(600)), Some(20));
+    }
+
+    #[test]
+    fn rolling_windows_evict_and_hold_prices() {
+        let mut vwap = RollingVwap::new(Duration::from_secs(1));
+        assert_eq!(vwap.value(), None);
+        vwap.update(&trade(0, 50, 10));
+        vwap.update(&trade(1_000, 100, 1));
+        vwap.update(&trade(2_000, 110, 3));
+        assert_eq!((vwap.value(), vwap.volume()), (Some(108), 4));
+
+        let mut twap = RollingTwap::new(Duration::from_secs(10));
+        twap.update_trade(&trade(0, 100, 1));
+        assert_eq!(twap.value_at(Timestamp::from_unix_millis(0)), Some(100));
+        twap.update_trade(&trade(5_000, 200, 1));
+        // 100 for 5s, 200 for 5s.
+        assert_eq!(
+            twap.value_at(Timestamp::from_unix_millis(10_000)),
+            Some(150)
+        );
+        // Window [5s, 15s]: 200 throughout.
+        assert_eq!(
+            twap.value_at(Timestamp::from_unix_millis(15_000)),
+            Some(200)
+        );
+        twap.update_bar(&bar(20_000, 300));
+        assert_eq!(
+            twap.value_at(Timestamp::from_unix_millis(25_000)),
+            Some(250)
+        );
+        assert_eq!(twap.samples.len(), 2, "samples before the window dropped");
+    }
+
+    #[tokio::test]
+    async fn live_reference_price_follows_the_trade_stream() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let reference =
+            LiveReferencePrice::spawn(Duration::from_secs(60), TypedStream::from_receiver(rx));
+        let mut versions = reference.subscribe();
+        let now = millis(Timestamp::now());
+        let update = |trades: Vec<Trade>| TradeUpdate {
+            action: "subscribe_trades".into(),
+            trades,
+            market_id: MarketId::new("0xabc"),
+            onchain_timestamp: None,
+            seen_timestamp: Timestamp::now(),
+        };
+        // Newest first, as in the subscribe snapshot.
+        tx.send(Ok(update(vec![
+            trade(now - 1_000, 120, 1),
+            trade(now - 2_000, 100, 3),
+        ])))
+        .unwrap();
+        versions.changed().await.unwrap();
+        assert_eq!(reference.vwap(), Some(105));
+        assert_eq!(reference.last_trade().unwrap().price, 120);
+        assert!(reference.twap().is_some_and(|p| (100..=120).contains(&p)));
+
+        tx.send(Err(crate::errors::O2Error::WebSocketDisconnected(
+            "gone".into(),
+        )))
+        .unwrap();
+        drop(tx);
+        while reference.is_running() {
+            tokio::task::yield_now().await;
+        }
+        assert!(reference.last_error().is_some());
+        assert_eq!(reference.volume(), 4);
+    }
+}
@@ -1,39 +1,337 @@
 /// REST API client for O2 Exchange.
 ///
 /// Typed wrappers for every REST endpoint from the O2 API reference.
-/// Uses reqwest for HTTP with JSON support.
+/// Uses reqwest for HTTP with JSON support, sent through a [`Transport`]. Read
+/// endpoints are retried on transient failures according to a [`RetryPolicy`].
 use std::any::type_name;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use log::debug;
-use reqwest::Client;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde_json::json;
 
+use crate::capabilities::{is_missing_route, Capabilities, CapabilityCache, Endpoint};
 use crate::config::NetworkConfig;
-use crate::errors::O2Error;
+use crate::errors::{ApiErrorCode, AuthFailure, O2Error};
+use crate::failover::{EndpointHealth, Endpoints, FailoverTransport};
 use crate::models::*;
+use crate::retry::{self, RetryPolicy};
+use crate::setup_guard;
+use crate::timestamp::Timestamp;
+use crate::trace::{self, debug};
+use crate::transport::Transport;
 
 /// Low-level REST API client for the O2 Exchange.
 #[derive(Debug, Clone)]
 pub struct O2Api {
+    /// Builds requests; sending goes through `transport`.
     client: Client,
+    transport: Arc<dyn Transport>,
     config: NetworkConfig,
+    retry: RetryPolicy,
+    clock: ServerClock,
+    capabilities: CapabilityCache,
+    endpoints: Endpoints,
 }
 
-impl O2Api {
-    /// Create a new API client with the given network configuration.
+/// Default limit on establishing a TCP/TLS connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default limit on waiting for the next chunk of a response.
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Page size of open-order reads; the most the API returns at once.
+const OPEN_ORDERS_PAGE: u32 = 200;
+
+/// Builder for [`O2Api`] with control over the underlying HTTP client.
+///
+/// ```rust,ignore
+/// let api = O2Api::builder(NetworkConfig::from_network(Network::Testnet))
+///     .connect_timeout(Duration::from_secs(3))
+///     .timeout(Duration::from_secs(10))
+///     .user_agent("my-bot/1.0")
+///     .build()?;
+/// let client = O2Client::with_api(api);
+/// ```
+#[derive(Debug)]
+pub struct O2ApiBuilder {
+    config: NetworkConfig,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    timeout: Option<Duration>,
+    user_agent: String,
+    proxy: Option<reqwest::Proxy>,
+    client: Option<Client>,
+    transport: Option<Arc<dyn Transport>>,
+    retry: RetryPolicy,
+}
+
+impl O2ApiBuilder {
+    /// Start from the defaults: [`DEFAULT_CONNECT_TIMEOUT`],
+    /// [`DEFAULT_READ_TIMEOUT`], no overall timeout, the SDK user agent, proxies
+    /// from the environment and the default [`RetryPolicy`].
     pub fn new(config: NetworkConfig) -> Self {
         Self {
-            client: Client::new(),
             config,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            timeout: None,
+            user_agent: concat!("o2-sdk-rust/", env!("CARGO_PKG_VERSION")).to_string(),
+            proxy: None,
+            client: None,
+            transport: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Limit on establishing a connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Limit on each wait for response data. Resets whenever bytes arrive, so
+    /// it catches hung connections without capping slow but live downloads.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Limit on a whole request, from connecting until the body is read.
+    /// Each retry attempt gets the full amount.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// `User-Agent` header sent with every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Route requests through `proxy`.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Use a preconfigured HTTP client. Its own settings apply; the timeout,
+    /// user agent and proxy options of this builder are ignored.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Send requests through `transport` instead of over HTTP. The timeout,
+    /// user agent and proxy options apply only if the transport honours them;
+    /// the retry policy still applies.
+    pub fn transport(mut self, transport: impl Transport) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Retry policy for read (`GET`) requests. See [`RetryPolicy`].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Build the API client.
+    ///
+    /// Fails if the HTTP client cannot be constructed, e.g. for an invalid
+    /// user agent.
+    pub fn build(self) -> Result<O2Api, O2Error> {
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder()
+                    .connect_timeout(self.connect_timeout)
+                    .read_timeout(self.read_timeout)
+                    .user_agent(self.user_agent);
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build()?
+            }
+        };
+        let transport = self.transport.unwrap_or_else(|| Arc::new(client.clone()));
+        let endpoints = Endpoints::new(
+            &self.config.api_base,
+            &self.config.api_base_fallbacks,
+            self.config.failover.clone(),
+        );
+        let transport: Arc<dyn Transport> = if endpoints.len() > 1 {
+            Arc::new(FailoverTransport {
+                inner: transport,
+                endpoints: endpoints.clone(),
+            })
+        } else {
+            transport
+        };
+        Ok(O2Api {
+            client,
+            transport,
+            config: self.config,
+            retry: self.retry,
+            clock: ServerClock::default(),
+            capabilities: CapabilityCache::default(),
+            endpoints,
+        })
+    }
+}
+
+impl O2Api {
+    /// Create a new API client with the given network configuration and the
+    /// [`O2ApiBuilder`] defaults.
+    ///
+    /// # Panics
+    ///
+    /// If the TLS backend cannot be initialized, as with `reqwest::Client::new`.
+    pub fn new(config: NetworkConfig) -> Self {
+        O2ApiBuilder::new(config)
+            .build()
+            .expect("default HTTP client configuration is valid")
+    }
+
+    /// Create an API client that sends every request through `transport`,
+    /// e.g. a mock serving canned responses in tests.
+    ///
+    /// # Panics
+    ///
+    /// As [`O2Api::new`].
+    pub fn with_transport(config: NetworkConfig, transport: impl Transport) -> Self {
+        O2ApiBuilder::new(config)
+            .transport(transport)
+            .build()
+            .expect("default HTTP client configuration is valid")
+    }
+
+    /// Start building an API client with custom HTTP settings.
+    pub fn builder(config: NetworkConfig) -> O2ApiBuilder {
+        O2ApiBuilder::new(config)
+    }
+
+    /// The network configuration this client talks to.
+    pub fn config(&self) -> &NetworkConfig {
+        &self.config
+    }
+
+    /// The REST base requests currently go to: `api_base`, or one of
+    /// `api_base_fallbacks` while earlier bases are failing. See
+    /// [`crate::failover`].
+    pub fn active_endpoint(&self) -> String {
+        self.endpoints.active()
+    }
+
+    /// Health of `api_base` and each of its fallbacks, in order.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.health()
+    }
+
+    /// Use `policy` for read (`GET`) requests. See [`RetryPolicy`].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Replace the retry policy for read (`GET`) requests.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// The retry policy for read (`GET`) requests.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Current time on the exchange's clock, estimated from the `Date` header
+    /// of the latest response (one-second resolution). Falls back to the local
+    /// clock before any response has been seen.
+    pub fn server_time(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    /// Which endpoints the server supports, probing every read endpoint whose
+    /// support is not known yet.
+    ///
+    /// Write endpoints are never probed; they are reported once used. Results
+    /// are cached and shared with clones of this client. A probe that cannot
+    /// reach the server leaves its endpoint [`Unknown`](crate::capabilities::Support::Unknown).
+    pub async fn capabilities(&self) -> Capabilities {
+        if self.config.faucet_url.is_none() {
+            self.capabilities.record(Endpoint::Faucet, false);
         }
+        let probes = Endpoint::ALL
+            .into_iter()
+            .filter(|e| e.is_read() && self.capabilities.get(*e).is_none())
+            .map(|endpoint| self.probe(endpoint));
+        futures_util::future::join_all(probes).await;
+        self.capabilities.snapshot()
+    }
+
+    /// What is known so far about which endpoints the server supports, without
+    /// sending any requests.
+    pub fn known_capabilities(&self) -> Capabilities {
+        self.capabilities.snapshot()
+    }
+
+    /// Whether `endpoint` is known to be supported (`None` if not yet known).
+    pub fn supports(&self, endpoint: Endpoint) -> Option<bool> {
+        self.capabilities.get(endpoint)
+    }
+
+    /// Send a parameterless request to a read endpoint and record whether the
+    /// route exists. Missing parameters get an API error from a live route.
+    async fn probe(&self, endpoint: Endpoint) {
+        let url = format!("{}{}", self.config.api_base, endpoint.path());
+        let resp = match self.send_retrying(self.client.get(&url)).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                debug!("api.probe endpoint={} error={}", endpoint, e);
+                return;
+            }
+        };
+        let status = resp.status();
+        let Ok(text) = resp.text().await else {
+            return;
+        };
+        let supported = !is_missing_route(status, &text);
+        debug!(
+            "api.probe endpoint={} status={} supported={}",
+            endpoint, status, supported
+        );
+        self.capabilities.record(endpoint, supported);
+    }
+
+    /// Send a request once.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        trace::http(request.build()?, |request| self.transport.send(request)).await
+    }
+
+    /// Send a read request, retrying transient failures.
+    async fn send_retrying(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        trace::http(request.build()?, |request| {
+            retry::send_retrying(&*self.transport, request, &self.retry)
+        })
+        .await
     }
 
     /// Parse an API response, detecting error codes and returning typed errors.
     async fn parse_response<T: serde::de::DeserializeOwned>(
         &self,
+        endpoint: Endpoint,
         response: reqwest::Response,
     ) -> Result<T, O2Error> {
         let status = response.status();
+        let retry_after = retry_after_from_headers(response.headers(), SystemTime::now());
+        self.clock.observe(response.headers(), SystemTime::now());
         let text = response.text().await?;
         let target_type = type_name::<T>();
         debug!(
@@ -43,11 +341,58 @@ impl O2Api {
             text.len()
         );
 
+        if is_missing_route(status, &text) {
+            debug!(
+                "api.parse_response unsupported endpoint={} status={}",
+                endpoint, status
+            );
+            self.capabilities.record(endpoint, false);
+            return Err(O2Error::Unsupported(endpoint));
+        }
+        self.capabilities.record(endpoint, true);
+
         if !status.is_success() {
             debug!(
                 "api.parse_response non_success status={} body={}",
                 status, text
             );
+            // A 503 that carries an API error code is an API error, not
+            // throttling; keep its code. A 429 is throttling either way, but
+            // keeps the code too.
+            let api_code = serde_json::from_str::<serde_json::Value>(&text)
+                .ok()
+                .and_then(|err| err.get("code").and_then(|c| c.as_u64()));
+            if status == StatusCode::TOO_MANY_REQUESTS
+                || (status == StatusCode::SERVICE_UNAVAILABLE && api_code.is_none())
+            {
+                let message = serde_json::from_str::<serde_json::Value>(&text)
+                    .ok()
+                    .and_then(|err| {
+                        err.get("message")
+                            .or_else(|| err.get("error"))
+                            .and_then(|m| m.as_str())
+                            .map(str::to_string)
+                    })
+                    .unwrap_or_else(|| format!("HTTP {}: {}", status, text));
+                debug!(
+                    "api.parse_response rate_limited status={} retry_after={:?}",
+                    status, retry_after
+                );
+                return Err(O2Error::RateLimited {
+                    message,
+                    retry_after,
+                    code: api_code.and_then(|code| ApiErrorCode::from_u32(code as u32)),
+                });
+            }
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                if let Some(err) = unauthorized(endpoint, status, &text) {
+                    debug!(
+                        "api.parse_response unauthorized endpoint={} status={}",
+                        endpoint, status
+                    );
+                    return Err(err);
+                }
+            }
             // Try to parse as API error
             if let Ok(err) = serde_json::from_str::<serde_json::Value>(&text) {
                 if let Some(code) = err.get("code").and_then(|c| c.as_u64()) {
@@ -135,34 +480,36 @@ impl O2Api {
     pub async fn get_markets(&self) -> Result<MarketsResponse, O2Error> {
         debug!("api.get_markets");
         let url = format!("{}/v1/markets", self.config.api_base);
-        let resp = self.client.get(&url).send().await?;
-        self.parse_response(resp).await
+        let resp = self.send_retrying(self.client.get(&url)).await?;
+        self.parse_response(Endpoint::Markets, resp).await
     }
 
     /// GET /v1/markets/summary - 24-hour market statistics.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_market_summary", level = "debug", skip_all, fields(market_id = %market_id))
+    )]
     pub async fn get_market_summary(&self, market_id: &str) -> Result<Vec<MarketSummary>, O2Error> {
         debug!("api.get_market_summary market_id={}", market_id);
         let url = format!("{}/v1/markets/summary", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("market_id", market_id)])
-            .send()
+            .send_retrying(self.client.get(&url).query(&[("market_id", market_id)]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::MarketSummary, resp).await
     }
 
     /// GET /v1/markets/ticker - Real-time ticker data.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_market_ticker", level = "debug", skip_all, fields(market_id = %market_id))
+    )]
     pub async fn get_market_ticker(&self, market_id: &str) -> Result<Vec<MarketTicker>, O2Error> {
         debug!("api.get_market_ticker market_id={}", market_id);
         let url = format!("{}/v1/markets/ticker", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("market_id", market_id)])
-            .send()
+            .send_retrying(self.client.get(&url).query(&[("market_id", market_id)]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::MarketTicker, resp).await
     }
 
     // -----------------------------------------------------------------------
@@ -170,6 +517,10 @@ impl O2Api {
     // -----------------------------------------------------------------------
 
     /// GET /v1/depth - Order book depth.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_depth", level = "debug", skip_all, fields(market_id = %market_id))
+    )]
     pub async fn get_depth(
         &self,
         market_id: &str,
@@ -190,17 +541,16 @@ impl O2Api {
             pairs.push(("limit", lim.to_string()));
         }
         let resp = self
-            .client
-            .get(&url)
-            .query(
-                &pairs
-                    .iter()
-                    .map(|(k, v)| (*k, v.as_str()))
-                    .collect::<Vec<_>>(),
+            .send_retrying(
+                self.client.get(&url).query(
+                    &pairs
+                        .iter()
+                        .map(|(k, v)| (*k, v.as_str()))
+                        .collect::<Vec<_>>(),
+                ),
             )
-            .send()
             .await?;
-        let val: serde_json::Value = self.parse_response(resp).await?;
+        let val: serde_json::Value = self.parse_response(Endpoint::Depth, resp).await?;
         // API wraps depth in "orders" or "view" field; unwrap it
         let depth = val
             .get("orders")
@@ -222,6 +572,10 @@ impl O2Api {
     // -----------------------------------------------------------------------
 
     /// GET /v1/trades - Recent trade history.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_trades", level = "debug", skip_all, fields(market_id = %market_id))
+    )]
     pub async fn get_trades(
         &self,
         market_id: &str,
@@ -252,11 +606,17 @@ impl O2Api {
         if let Some(c) = contract {
             query.push(("contract", c));
         }
-        let resp = self.client.get(&url).query(&query).send().await?;
-        self.parse_response(resp).await
+        let resp = self
+            .send_retrying(self.client.get(&url).query(&query))
+            .await?;
+        self.parse_response(Endpoint::Trades, resp).await
     }
 
     /// GET /v1/trades_by_account - Trades by account.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_trades_by_account", level = "debug", skip_all, fields(market_id = %market_id, trade_account_id = %contract))
+    )]
     pub async fn get_trades_by_account(
         &self,
         market_id: &str,
@@ -285,8 +645,10 @@ impl O2Api {
         if let Some(tid) = start_trade_id {
             query.push(("start_trade_id", tid));
         }
-        let resp = self.client.get(&url).query(&query).send().await?;
-        self.parse_response(resp).await
+        let resp = self
+            .send_retrying(self.client.get(&url).query(&query))
+            .await?;
+        self.parse_response(Endpoint::TradesByAccount, resp).await
     }
 
     /// Valid bar resolutions accepted by the API.
@@ -300,6 +662,10 @@ impl O2Api {
     /// `from_ts` and `to_ts` are in **milliseconds** (not seconds).
     /// `resolution` must be one of: `1s`, `1m`, `2m`, `3m`, `5m`, `15m`, `30m`,
     /// `1h`, `2h`, `4h`, `6h`, `8h`, `12h`, `1d`, `3d`, `1w`, `1M`, `3M`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_bars", level = "debug", skip_all, fields(market_id = %market_id))
+    )]
     pub async fn get_bars(
         &self,
         market_id: &str,
@@ -321,17 +687,14 @@ impl O2Api {
         let from_ts_str = from_ts.to_string();
         let to_ts_str = to_ts.to_string();
         let resp = self
-            .client
-            .get(&url)
-            .query(&[
+            .send_retrying(self.client.get(&url).query(&[
                 ("market_id", market_id),
                 ("from", from_ts_str.as_str()),
                 ("to", to_ts_str.as_str()),
                 ("resolution", resolution),
-            ])
-            .send()
+            ]))
             .await?;
-        let val: serde_json::Value = self.parse_response(resp).await?;
+        let val: serde_json::Value = self.parse_response(Endpoint::Bars, resp).await?;
         let bars_val = val.get("bars").unwrap_or(&val);
         serde_json::from_value(bars_val.clone())
             .map_err(|e| O2Error::JsonError(format!("Failed to parse bars: {e}")))
@@ -353,14 +716,18 @@ impl O2Api {
                 "Address": owner_address
             }
         });
-        let resp = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-        self.parse_response(resp).await
+        setup_guard::guarded(Endpoint::CreateAccount, async {
+            let resp = self
+                .send(
+                    self.client
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .json(&body),
+                )
+                .await?;
+            self.parse_response(Endpoint::CreateAccount, resp).await
+        })
+        .await
     }
 
     /// GET /v1/accounts - Get account info by owner address.
@@ -368,15 +735,16 @@ impl O2Api {
         debug!("api.get_account_by_owner owner={}", owner);
         let url = format!("{}/v1/accounts", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("owner", owner)])
-            .send()
+            .send_retrying(self.client.get(&url).query(&[("owner", owner)]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::Account, resp).await
     }
 
     /// GET /v1/accounts - Get account info by trade_account_id.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_account_by_id", level = "debug", skip_all, fields(trade_account_id = %trade_account_id))
+    )]
     pub async fn get_account_by_id(
         &self,
         trade_account_id: &str,
@@ -387,12 +755,13 @@ impl O2Api {
         );
         let url = format!("{}/v1/accounts", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("trade_account_id", trade_account_id)])
-            .send()
+            .send_retrying(
+                self.client
+                    .get(&url)
+                    .query(&[("trade_account_id", trade_account_id)]),
+            )
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::Account, resp).await
     }
 
     /// GET /v1/balance - Get asset balance.
@@ -414,8 +783,10 @@ impl O2Api {
         if let Some(a) = address {
             query.push(("address", a));
         }
-        let resp = self.client.get(&url).query(&query).send().await?;
-        self.parse_response(resp).await
+        let resp = self
+            .send_retrying(self.client.get(&url).query(&query))
+            .await?;
+        self.parse_response(Endpoint::Balance, resp).await
     }
 
     // -----------------------------------------------------------------------
@@ -424,6 +795,10 @@ impl O2Api {
 
     /// GET /v1/orders - Get order history.
     #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_orders", level = "debug", skip_all, fields(market_id = %market_id, trade_account_id = %contract))
+    )]
     pub async fn get_orders(
         &self,
         market_id: &str,
@@ -457,11 +832,67 @@ impl O2Api {
         if let Some(oid) = start_order_id {
             query.push(("start_order_id", oid));
         }
-        let resp = self.client.get(&url).query(&query).send().await?;
-        self.parse_response(resp).await
+        let resp = self
+            .send_retrying(self.client.get(&url).query(&query))
+            .await?;
+        self.parse_response(Endpoint::Orders, resp).await
+    }
+
+    /// Every open order of a trade account on one market, read a page at a
+    /// time. Each page starts after the `(timestamp, order_id)` of the last
+    /// order of the one before.
+    pub(crate) async fn all_open_orders(
+        &self,
+        market_id: &MarketId,
+        trade_account_id: &TradeAccountId,
+    ) -> Result<Vec<Order>, O2Error> {
+        let mut orders = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cursor: Option<(u64, OrderId)> = None;
+        loop {
+            let page = self
+                .get_orders(
+                    market_id.as_str(),
+                    trade_account_id.as_str(),
+                    "desc",
+                    OPEN_ORDERS_PAGE,
+                    Some(true),
+                    cursor.as_ref().map(|(timestamp, _)| *timestamp),
+                    cursor.as_ref().map(|(_, order_id)| order_id.as_str()),
+                )
+                .await?
+                .orders;
+            let full = page.len() >= OPEN_ORDERS_PAGE as usize;
+            let last = page.last().cloned();
+            let before = orders.len();
+            orders.extend(
+                page.into_iter()
+                    .filter(|order| seen.insert(order.order_id.clone())),
+            );
+            // A short page is the last one; a page of repeats means the
+            // cursor did not move.
+            let Some(last) = last.filter(|_| full && orders.len() > before) else {
+                return Ok(orders);
+            };
+            let timestamp = last
+                .timestamp
+                .as_ref()
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()))
+                .ok_or_else(|| {
+                    O2Error::Other(format!(
+                        "Order {} has no timestamp to read the next page of open orders from",
+                        last.order_id
+                    ))
+                })?;
+            cursor = Some((timestamp, last.order_id));
+        }
     }
 
     /// GET /v1/order - Get a single order.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.get_order", level = "debug", skip_all, fields(market_id = %market_id, order_id = %order_id))
+    )]
     pub async fn get_order(&self, market_id: &str, order_id: &str) -> Result<Order, O2Error> {
         debug!(
             "api.get_order market_id={} order_id={}",
@@ -469,12 +900,13 @@ impl O2Api {
         );
         let url = format!("{}/v1/order", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("market_id", market_id), ("order_id", order_id)])
-            .send()
+            .send_retrying(
+                self.client
+                    .get(&url)
+                    .query(&[("market_id", market_id), ("order_id", order_id)]),
+            )
             .await?;
-        let val: serde_json::Value = self.parse_response(resp).await?;
+        let val: serde_json::Value = self.parse_response(Endpoint::Order, resp).await?;
         // API wraps order in an "order" key
         let order_val = val.get("order").unwrap_or(&val);
         serde_json::from_value(order_val.clone())
@@ -486,6 +918,10 @@ impl O2Api {
     // -----------------------------------------------------------------------
 
     /// PUT /v1/session - Create or update a trading session.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.create_session", level = "debug", skip_all, fields(trade_account_id = %request.contract_id, nonce = %request.nonce, tx_id = tracing::field::Empty))
+    )]
     pub async fn create_session(
         &self,
         owner_id: &str,
@@ -497,17 +933,24 @@ impl O2Api {
         );
         let url = format!("{}/v1/session", self.config.api_base);
         let resp = self
-            .client
-            .put(&url)
-            .header("Content-Type", "application/json")
-            .header("O2-Owner-Id", owner_id)
-            .json(request)
-            .send()
+            .send(
+                self.client
+                    .put(&url)
+                    .header("Content-Type", "application/json")
+                    .header("O2-Owner-Id", owner_id)
+                    .json(request),
+            )
             .await?;
-        self.parse_response(resp).await
+        let session: SessionResponse = self.parse_response(Endpoint::Session, resp).await?;
+        trace::record("tx_id", &session.tx_id);
+        Ok(session)
     }
 
     /// POST /v1/session/actions - Execute trading actions.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.submit_actions", level = "debug", skip_all, fields(trade_account_id = %request.trade_account_id, nonce = %request.nonce, tx_id = tracing::field::Empty))
+    )]
     pub(crate) async fn submit_actions(
         &self,
         owner_id: &str,
@@ -522,16 +965,17 @@ impl O2Api {
         );
         let url = format!("{}/v1/session/actions", self.config.api_base);
         let resp = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("O2-Owner-Id", owner_id)
-            .json(request)
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("O2-Owner-Id", owner_id)
+                    .json(request),
+            )
             .await?;
         // Reuse standard status/error handling first; this ensures non-2xx
         // responses are mapped consistently with the rest of the SDK.
-        let val: serde_json::Value = self.parse_response(resp).await?;
+        let val: serde_json::Value = self.parse_response(Endpoint::SessionActions, resp).await?;
 
         // Parse as Value first for robustness, then extract fields.
         // The Order struct can have unexpected field types across API versions,
@@ -555,11 +999,16 @@ impl O2Api {
             message,
             reason,
             receipts,
+            annotations: None,
+            dry_run: None,
         };
 
         // Check for errors
         if parsed.is_success() {
             debug!("api.submit_actions parsed=success tx_id={:?}", parsed.tx_id);
+            if let Some(tx_id) = &parsed.tx_id {
+                trace::record("tx_id", tx_id);
+            }
             Ok(parsed)
         } else if parsed.is_preflight_error() {
             let code = parsed.code.unwrap_or(0);
@@ -598,6 +1047,10 @@ impl O2Api {
     // -----------------------------------------------------------------------
 
     /// POST /v1/accounts/withdraw - Withdraw assets.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.withdraw", level = "debug", skip_all, fields(trade_account_id = %request.trade_account_id, nonce = %request.nonce, tx_id = tracing::field::Empty))
+    )]
     pub async fn withdraw(
         &self,
         owner_id: &str,
@@ -609,14 +1062,19 @@ impl O2Api {
         );
         let url = format!("{}/v1/accounts/withdraw", self.config.api_base);
         let resp = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("O2-Owner-Id", owner_id)
-            .json(request)
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("O2-Owner-Id", owner_id)
+                    .json(request),
+            )
             .await?;
-        self.parse_response(resp).await
+        let withdrawal: WithdrawResponse = self.parse_response(Endpoint::Withdraw, resp).await?;
+        if let Some(tx_id) = &withdrawal.tx_id {
+            trace::record("tx_id", tx_id);
+        }
+        Ok(withdrawal)
     }
 
     // -----------------------------------------------------------------------
@@ -624,6 +1082,10 @@ impl O2Api {
     // -----------------------------------------------------------------------
 
     /// POST /analytics/v1/whitelist - Whitelist a trading account.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "api.whitelist_account", level = "debug", skip_all, fields(trade_account_id = %trade_account_id))
+    )]
     pub async fn whitelist_account(
         &self,
         trade_account_id: &str,
@@ -636,14 +1098,18 @@ impl O2Api {
         let body = WhitelistRequest {
             trade_account: trade_account_id.to_string(),
         };
-        let resp = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-        self.parse_response(resp).await
+        setup_guard::guarded(Endpoint::Whitelist, async {
+            let resp = self
+                .send(
+                    self.client
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .json(&body),
+                )
+                .await?;
+            self.parse_response(Endpoint::Whitelist, resp).await
+        })
+        .await
     }
 
     /// GET /analytics/v1/referral/code-info - Look up referral code.
@@ -651,12 +1117,9 @@ impl O2Api {
         debug!("api.get_referral_info code={}", code);
         let url = format!("{}/analytics/v1/referral/code-info", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("code", code)])
-            .send()
+            .send_retrying(self.client.get(&url).query(&[("code", code)]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::ReferralInfo, resp).await
     }
 
     // -----------------------------------------------------------------------
@@ -667,8 +1130,8 @@ impl O2Api {
     pub async fn get_aggregated_assets(&self) -> Result<AggregatedAssets, O2Error> {
         debug!("api.get_aggregated_assets");
         let url = format!("{}/v1/aggregated/assets", self.config.api_base);
-        let resp = self.client.get(&url).send().await?;
-        self.parse_response(resp).await
+        let resp = self.send_retrying(self.client.get(&url)).await?;
+        self.parse_response(Endpoint::AggregatedAssets, resp).await
     }
 
     /// GET /v1/aggregated/orderbook - Order book depth by pair name.
@@ -686,16 +1149,14 @@ impl O2Api {
         let depth_str = depth.to_string();
         let level_str = level.to_string();
         let resp = self
-            .client
-            .get(&url)
-            .query(&[
+            .send_retrying(self.client.get(&url).query(&[
                 ("market_pair", market_pair),
                 ("depth", depth_str.as_str()),
                 ("level", level_str.as_str()),
-            ])
-            .send()
+            ]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::AggregatedOrderbook, resp)
+            .await
     }
 
     /// GET /v1/aggregated/coingecko/orderbook - CoinGecko orderbook depth by ticker ID.
@@ -711,36 +1172,39 @@ impl O2Api {
         let url = format!("{}/v1/aggregated/coingecko/orderbook", self.config.api_base);
         let depth_str = depth.to_string();
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("ticker_id", ticker_id), ("depth", depth_str.as_str())])
-            .send()
+            .send_retrying(
+                self.client
+                    .get(&url)
+                    .query(&[("ticker_id", ticker_id), ("depth", depth_str.as_str())]),
+            )
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::AggregatedCoingeckoOrderbook, resp)
+            .await
     }
 
     /// GET /v1/aggregated/summary - 24-hour stats for all pairs.
     pub async fn get_aggregated_summary(&self) -> Result<Vec<PairSummary>, O2Error> {
         debug!("api.get_aggregated_summary");
         let url = format!("{}/v1/aggregated/summary", self.config.api_base);
-        let resp = self.client.get(&url).send().await?;
-        self.parse_response(resp).await
+        let resp = self.send_retrying(self.client.get(&url)).await?;
+        self.parse_response(Endpoint::AggregatedSummary, resp).await
     }
 
     /// GET /v1/aggregated/ticker - Real-time ticker for all pairs.
     pub async fn get_aggregated_ticker(&self) -> Result<AggregatedTicker, O2Error> {
         debug!("api.get_aggregated_ticker");
         let url = format!("{}/v1/aggregated/ticker", self.config.api_base);
-        let resp = self.client.get(&url).send().await?;
-        self.parse_response(resp).await
+        let resp = self.send_retrying(self.client.get(&url)).await?;
+        self.parse_response(Endpoint::AggregatedTicker, resp).await
     }
 
     /// GET /v1/aggregated/coingecko/tickers - CoinGecko ticker format.
     pub async fn get_aggregated_coingecko_tickers(&self) -> Result<Vec<PairTicker>, O2Error> {
         debug!("api.get_aggregated_coingecko_tickers");
         let url = format!("{}/v1/aggregated/coingecko/tickers", self.config.api_base);
-        let resp = self.client.get(&url).send().await?;
-        self.parse_response(resp).await
+        let resp = self.send_retrying(self.client.get(&url)).await?;
+        self.parse_response(Endpoint::AggregatedCoingeckoTickers, resp)
+            .await
     }
 
     /// GET /v1/aggregated/trades - Recent trades for a pair.
@@ -751,12 +1215,9 @@ impl O2Api {
         debug!("api.get_aggregated_trades market_pair={}", market_pair);
         let url = format!("{}/v1/aggregated/trades", self.config.api_base);
         let resp = self
-            .client
-            .get(&url)
-            .query(&[("market_pair", market_pair)])
-            .send()
+            .send_retrying(self.client.get(&url).query(&[("market_pair", market_pair)]))
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::AggregatedTrades, resp).await
     }
 
     // -----------------------------------------------------------------------
@@ -770,17 +1231,21 @@ impl O2Api {
             .config
             .faucet_url
             .as_ref()
-            .ok_or_else(|| O2Error::Other("Faucet not available on this network".into()))?;
+            .ok_or(O2Error::Unsupported(Endpoint::Faucet))?;
 
         let body = json!({ "address": address });
-        let resp = self
-            .client
-            .post(faucet_url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-        self.parse_response(resp).await
+        setup_guard::guarded(Endpoint::Faucet, async {
+            let resp = self
+                .send(
+                    self.client
+                        .post(faucet_url)
+                        .header("Content-Type", "application/json")
+                        .json(&body),
+                )
+                .await?;
+            self.parse_response(Endpoint::Faucet, resp).await
+        })
+        .await
     }
 
     /// Mint tokens directly to a trading account contract via the faucet (testnet/devnet only).
@@ -790,16 +1255,567 @@ impl O2Api {
             .config
             .faucet_url
             .as_ref()
-            .ok_or_else(|| O2Error::Other("Faucet not available on this network".into()))?;
+            .ok_or(O2Error::Unsupported(Endpoint::Faucet))?;
 
         let body = json!({ "contract": contract_id });
         let resp = self
-            .client
-            .post(faucet_url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
+            .send(
+                self.client
+                    .post(faucet_url)
+                    .header("Content-Type", "application/json")
+                    .json(&body),
+            )
             .await?;
-        self.parse_response(resp).await
+        self.parse_response(Endpoint::Faucet, resp).await
+    }
+
+    // -----------------------------------------------------------------------
+    // Fuel node
+    // -----------------------------------------------------------------------
+
+    /// Status of a transaction, from the network's Fuel GraphQL node
+    /// ([`NetworkConfig::fuel_rpc`]).
+    pub async fn get_transaction_status(&self, tx_id: &str) -> Result<TxStatus, O2Error> {
+        debug!("api.get_transaction_status tx_id={}", tx_id);
+        let body = json!({
+            "query": TX_STATUS_QUERY,
+            "variables": { "id": tx_id },
+        });
+        let resp = self
+            .send(
+                self.client
+                    .post(&self.config.fuel_rpc)
+                    .header("Content-Type", "application/json")
+                    .json(&body),
+            )
+            .await?;
+        let status = resp.status();
+        let retry_after = retry_after_from_headers(resp.headers(), SystemTime::now());
+        let text = resp.text().await?;
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            return Err(O2Error::RateLimited {
+                message: format!("Fuel node HTTP {}: {}", status, text),
+                retry_after,
+                code: None,
+            });
+        }
+        if !status.is_success() {
+            return Err(O2Error::HttpError(format!(
+                "Fuel node HTTP {}: {}",
+                status, text
+            )));
+        }
+        parse_transaction_status(&text)
+    }
+}
+
+const TX_STATUS_QUERY: &str = "query($id: TransactionId!) { transaction(id: $id) { status { \
+    __typename \
+    ... on SqueezedOutStatus { reason } \
+    ... on SuccessStatus { block { height } totalFee receipts { receiptType id pc is reason ra rb result data } } \
+    ... on FailureStatus { block { height } reason totalFee receipts { receiptType id pc is reason ra rb result data } } \
+    } } }";
+
+fn parse_transaction_status(text: &str) -> Result<TxStatus, O2Error> {
+    let value: serde_json::Value = serde_json::from_str(text)?;
+    if let Some(message) = value
+        .get("errors")
+        .and_then(|e| e.get(0))
+        .and_then(|e| e.get("message"))
+        .and_then(|m| m.as_str())
+    {
+        return Err(O2Error::Other(format!("Fuel node: {}", message)));
+    }
+    match value.pointer("/data/transaction/status") {
+        None | Some(serde_json::Value::Null) => Ok(TxStatus::NotFound),
+        Some(status) => {
+            let status: GraphqlTxStatus = serde_json::from_value(status.clone())
+                .map_err(|e| O2Error::JsonError(format!("transaction status: {}", e)))?;
+            Ok(status.into())
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Server clock
+// ---------------------------------------------------------------------------
+
+/// The local clock moved by `offset_ms`, or unmoved for `i64::MIN`.
+fn shifted(offset_ms: i64) -> Timestamp {
+    let local = Timestamp::now();
+    match offset_ms {
+        i64::MIN => local,
+        offset if offset >= 0 => local + Duration::from_millis(offset as u64),
+        offset => local - Duration::from_millis(offset.unsigned_abs()),
+    }
+}
+
+/// Offset of the server's clock from the local one, shared between clones.
+#[derive(Debug, Clone)]
+struct ServerClock {
+    /// Server minus local, in milliseconds. `i64::MIN` until a `Date` header is seen.
+    offset_ms: Arc<AtomicI64>,
+}
+
+impl Default for ServerClock {
+    fn default() -> Self {
+        Self {
+            offset_ms: Arc::new(AtomicI64::new(i64::MIN)),
+        }
+    }
+}
+
+impl ServerClock {
+    fn observe(&self, headers: &HeaderMap, now: SystemTime) {
+        let Some(date) = headers
+            .get("date")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok())
+        else {
+            return;
+        };
+        let server = Timestamp::from_system_time(date).as_unix_millis() as i64;
+        let local = Timestamp::from_system_time(now).as_unix_millis() as i64;
+        self.offset_ms.store(server - local, Ordering::Relaxed);
+    }
+
+    fn now(&self) -> Timestamp {
+        shifted(self.offset_ms.load(Ordering::Relaxed))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Rate-limit hints
+// ---------------------------------------------------------------------------
+
+/// Values above this are treated as Unix timestamps rather than relative seconds
+/// in `*RateLimit-Reset` headers.
+const RESET_EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// Extract the server's requested back-off from response headers.
+///
+/// Prefers `Retry-After` (delta-seconds or HTTP-date), then falls back to
+/// `RateLimit-Reset` / `X-RateLimit-Reset` (relative seconds or Unix timestamp).
+pub(crate) fn retry_after_from_headers(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header_str = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+    };
+
+    if let Some(value) = header_str("retry-after") {
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        if let Ok(at) = httpdate::parse_http_date(value) {
+            return Some(at.duration_since(now).unwrap_or(Duration::ZERO));
+        }
+    }
+
+    for name in ["ratelimit-reset", "x-ratelimit-reset"] {
+        let Some(secs) = header_str(name).and_then(|v| v.parse::<u64>().ok()) else {
+            continue;
+        };
+        if secs < RESET_EPOCH_THRESHOLD {
+            return Some(Duration::from_secs(secs));
+        }
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        return Some(at.duration_since(now).unwrap_or(Duration::ZERO));
+    }
+
+    None
+}
+
+// ---------------------------------------------------------------------------
+// Refused requests
+// ---------------------------------------------------------------------------
+
+/// Classify a 401/403 response. `None` when the body carries an API error
+/// code, which takes precedence.
+fn unauthorized(endpoint: Endpoint, status: StatusCode, text: &str) -> Option<O2Error> {
+    let body = serde_json::from_str::<serde_json::Value>(text).ok();
+    if body.as_ref().and_then(|b| b.get("code")).is_some() {
+        return None;
+    }
+    let message = body
+        .as_ref()
+        .and_then(|b| b.get("message").or_else(|| b.get("error")))
+        .and_then(|m| m.as_str())
+        .unwrap_or(text)
+        .trim();
+    let message = if message.is_empty() {
+        status.to_string()
+    } else {
+        format!("HTTP {}: {}", status.as_u16(), message)
+    };
+    // The testnet whitelist and the faucet admit only some accounts; other
+    // refusals are about who is asking.
+    let gated = matches!(endpoint, Endpoint::Whitelist | Endpoint::Faucet)
+        || message.to_ascii_lowercase().contains("whitelist");
+    let (reason, advice) = if gated {
+        (
+            AuthFailure::NetworkGated,
+            "this network does not admit the account to this endpoint; \
+             check that the network requires it and the account is whitelisted",
+        )
+    } else {
+        (
+            AuthFailure::Credentials,
+            "the server rejected the request's credentials; \
+             check the API key or proxy authentication",
+        )
+    };
+    Some(O2Error::Unauthorized {
+        endpoint,
+        reason,
+        hint: format!("{message} ({advice})"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn server_clock_tracks_date_header() {
+        let clock = ServerClock::default();
+        let now = SystemTime::now();
+        let before = clock.now();
+        assert!(
+            before.saturating_duration_since(Timestamp::from_system_time(now))
+                < Duration::from_secs(1)
+        );
+
+        let ahead = httpdate::fmt_http_date(now + Duration::from_secs(3600));
+        clock.observe(&headers(&[("date", &ahead)]), now);
+        let skew = clock.now().saturating_duration_since(Timestamp::now());
+        assert!(
+            skew > Duration::from_secs(3598) && skew <= Duration::from_secs(3600),
+            "{skew:?}"
+        );
+
+        let behind = httpdate::fmt_http_date(now - Duration::from_secs(60));
+        clock.observe(&headers(&[("date", &behind)]), now);
+        let skew = Timestamp::now().saturating_duration_since(clock.now());
+        assert!(
+            skew >= Duration::from_secs(60) && skew < Duration::from_secs(62),
+            "{skew:?}"
+        );
+    }
+
+    #[test]
+    fn good_til_time_expiries_follow_each_clients_clock() {
+        let price: crate::UnsignedDecimal = "1".parse().unwrap();
+        let now = SystemTime::now();
+        let (ahead, local) = (ServerClock::default(), ServerClock::default());
+        let date = httpdate::fmt_http_date(now + Duration::from_secs(3600));
+        ahead.observe(&headers(&[("date", &date)]), now);
+
+        let expiry = |clock: &ServerClock| match OrderType::limit_good_for(
+            price,
+            Duration::from_secs(60),
+            clock.now(),
+        ) {
+            Ok(OrderType::Limit { timestamp, .. }) => timestamp,
+            other => panic!("unexpected {other:?}"),
+        };
+        // The other client's skew does not leak into this one's expiries.
+        let gap = expiry(&ahead) - expiry(&local);
+        assert!((3598..=3601).contains(&gap), "{gap}");
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let h = headers(&[("retry-after", "7")]);
+        assert_eq!(
+            retry_after_from_headers(&h, SystemTime::now()),
+            Some(Duration::from_secs(7))
+        );
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let at = httpdate::fmt_http_date(now + Duration::from_secs(30));
+        let h = headers(&[("retry-after", &at)]);
+        assert_eq!(
+            retry_after_from_headers(&h, now),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn retry_after_date_in_past_is_zero() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let at = httpdate::fmt_http_date(now - Duration::from_secs(30));
+        let h = headers(&[("retry-after", &at)]);
+        assert_eq!(retry_after_from_headers(&h, now), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rate_limit_reset_relative_and_epoch() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let h = headers(&[("x-ratelimit-reset", "12")]);
+        assert_eq!(
+            retry_after_from_headers(&h, now),
+            Some(Duration::from_secs(12))
+        );
+
+        let h = headers(&[("ratelimit-reset", "1700000045")]);
+        assert_eq!(
+            retry_after_from_headers(&h, now),
+            Some(Duration::from_secs(45))
+        );
+    }
+
+    #[test]
+    fn retry_after_takes_precedence_and_garbage_is_ignored() {
+        let now = SystemTime::now();
+        let h = headers(&[("retry-after", "3"), ("x-ratelimit-reset", "60")]);
+        assert_eq!(
+            retry_after_from_headers(&h, now),
+            Some(Duration::from_secs(3))
+        );
+
+        let h = headers(&[("retry-after", "soon")]);
+        assert_eq!(retry_after_from_headers(&h, now), None);
+    }
+
+    fn local_config(api_base: String) -> NetworkConfig {
+        NetworkConfig {
+            api_base,
+            ws_url: "ws://127.0.0.1:9".into(),
+            fuel_rpc: "http://127.0.0.1:9".into(),
+            faucet_url: None,
+            whitelist_required: false,
+            chain_id: None,
+            api_base_fallbacks: Vec::new(),
+            ws_url_fallbacks: Vec::new(),
+            failover: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn read_timeout_bounds_a_hung_server() {
+        use tokio::io::AsyncReadExt;
+
+        // Accepts and reads the request, then never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sent, mut request) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let _ = sent.send(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                drop(stream);
+            }
+        });
+
+        let api = O2Api::builder(local_config(format!("http://{addr}")))
+            .read_timeout(Duration::from_millis(100))
+            .user_agent("test-agent/1.0")
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+        let started = std::time::Instant::now();
+        let err = api.get_markets().await.unwrap_err();
+        assert!(matches!(err, O2Error::HttpError(_)), "{err:?}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let request = request.recv().await.unwrap();
+        assert!(request.contains("user-agent: test-agent/1.0"), "{request}");
+    }
+
+    /// Serves `/v1/aggregated/*` as a missing route, `/v1/order` as a coded
+    /// API error and everything else as a bad request from a live route.
+    async fn partial_server() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split(' ').nth(1).unwrap_or("/");
+                let (status, body) = if path.starts_with("/v1/aggregated/") {
+                    ("404 Not Found", "Not Found")
+                } else if path.starts_with("/v1/order?") {
+                    (
+                        "404 Not Found",
+                        r#"{"code":3000,"message":"Order not found"}"#,
+                    )
+                } else {
+                    ("400 Bad Request", r#"{"message":"missing parameter"}"#)
+                };
+                let resp = format!(
+                    "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(resp.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn missing_routes_are_unsupported_and_reported() {
+        let api = O2Api::builder(local_config(partial_server().await))
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+
+        let err = api.get_aggregated_ticker().await.unwrap_err();
+        assert!(
+            matches!(err, O2Error::Unsupported(Endpoint::AggregatedTicker)),
+            "{err:?}"
+        );
+        let err = api.get_order("0x01", "0x02").await.unwrap_err();
+        assert!(matches!(err, O2Error::OrderNotFound(_)), "{err:?}");
+        let err = api.mint_to_address("0x01").await.unwrap_err();
+        assert!(
+            matches!(err, O2Error::Unsupported(Endpoint::Faucet)),
+            "{err:?}"
+        );
+
+        let known = api.known_capabilities();
+        assert_eq!(known.supports(Endpoint::AggregatedTicker), Some(false));
+        assert_eq!(known.supports(Endpoint::Order), Some(true));
+        assert_eq!(known.supports(Endpoint::Markets), None);
+
+        let caps = api.clone().capabilities().await;
+        assert_eq!(api.supports(Endpoint::Markets), Some(true));
+        assert_eq!(caps.supports(Endpoint::AggregatedAssets), Some(false));
+        assert_eq!(caps.supports(Endpoint::Faucet), Some(false));
+        assert_eq!(caps.supports(Endpoint::SessionActions), None);
+        assert_eq!(caps.unsupported().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn reads_fail_over_to_a_fallback_base() {
+        let mut config = local_config("http://127.0.0.1:9".into());
+        config.api_base_fallbacks = vec![partial_server().await];
+        config.failover.max_failures = 2;
+        let api = O2Api::builder(config.clone())
+            .retry_policy(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap();
+
+        // Two refused attempts on the primary, then the retry lands on the
+        // fallback, which answers.
+        let err = api.get_order("0x01", "0x02").await.unwrap_err();
+        assert!(matches!(err, O2Error::OrderNotFound(_)), "{err:?}");
+        assert_eq!(api.active_endpoint(), config.api_base_fallbacks[0]);
+        let health = api.endpoint_health();
+        assert_eq!(health[0].url, config.api_base);
+        assert!(!health[0].active && health[0].cooling_down.is_some());
+        assert!(health[1].active);
+    }
+
+    #[test]
+    fn refused_requests_are_classified() {
+        let err = unauthorized(
+            Endpoint::Whitelist,
+            StatusCode::FORBIDDEN,
+            r#"{"message":"forbidden"}"#,
+        )
+        .unwrap();
+        let O2Error::Unauthorized {
+            endpoint,
+            reason,
+            hint,
+        } = err
+        else {
+            panic!("{err:?}");
+        };
+        assert_eq!(endpoint, Endpoint::Whitelist);
+        assert_eq!(reason, AuthFailure::NetworkGated);
+        assert!(hint.starts_with("HTTP 403: forbidden ("), "{hint}");
+
+        let err = unauthorized(
+            Endpoint::Trades,
+            StatusCode::FORBIDDEN,
+            "account is not whitelisted",
+        );
+        assert!(matches!(
+            err,
+            Some(O2Error::Unauthorized {
+                reason: AuthFailure::NetworkGated,
+                ..
+            })
+        ));
+        let err = unauthorized(Endpoint::AggregatedTicker, StatusCode::UNAUTHORIZED, "");
+        assert!(matches!(
+            err,
+            Some(O2Error::Unauthorized {
+                reason: AuthFailure::Credentials,
+                ..
+            })
+        ));
+        // Coded API errors keep their own variant.
+        let coded = r#"{"code":1004,"message":"Geo restricted"}"#;
+        assert!(unauthorized(Endpoint::Markets, StatusCode::FORBIDDEN, coded).is_none());
+    }
+
+    #[test]
+    fn builder_defaults_and_custom_client() {
+        let config = local_config("http://127.0.0.1:9".into());
+        let api = O2Api::new(config.clone());
+        assert_eq!(api.retry_policy(), &RetryPolicy::default());
+        assert_eq!(api.config().api_base, config.api_base);
+
+        let api = O2Api::builder(config)
+            .client(Client::new())
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+        assert_eq!(api.retry_policy().max_attempts, 1);
+    }
+
+    #[test]
+    fn transaction_status_parses_graphql_payloads() {
+        let status = |body: serde_json::Value| parse_transaction_status(&body.to_string());
+
+        let missing = status(json!({ "data": { "transaction": null } })).unwrap();
+        assert_eq!(missing, TxStatus::NotFound);
+        let pending = status(json!({ "data": { "transaction": { "status": {
+            "__typename": "PreconfirmationSuccessStatus"
+        } } } }))
+        .unwrap();
+        assert_eq!(pending, TxStatus::Submitted);
+        assert!(!pending.is_final());
+
+        let reverted = status(json!({ "data": { "transaction": { "status": {
+            "__typename": "FailureStatus",
+            "block": { "height": "1234" },
+            "reason": "Revert(123)",
+            "totalFee": "17",
+            "receipts": [{ "receiptType": "REVERT", "ra": "123" }]
+        } } } }))
+        .unwrap();
+        assert!(matches!(
+            &reverted,
+            TxStatus::Reverted { block_height: 1234, total_fee: 17, receipts, .. }
+                if receipts[0]["receiptType"] == "REVERT"
+        ));
+        assert!(reverted.is_final());
+
+        let error = status(json!({ "errors": [{ "message": "Invalid value for id" }] }));
+        assert!(matches!(error, Err(O2Error::Other(m)) if m.contains("Invalid value")));
     }
 }
@@ -0,0 +1,209 @@
+//! Asset lookup by symbol or asset id.
+//!
+//! Markets list their assets only as a base and a quote, so finding an
+//! asset's id or decimals means scanning every market. An [`AssetRegistry`]
+//! indexes them once: each asset traded on any market appears once, with the
+//! markets that trade it and, where the aggregated assets endpoint lists it,
+//! its deposit and withdrawal metadata.
+//! [`O2Client::asset`](crate::O2Client::asset) resolves through a registry the
+//! client rebuilds whenever its market list is refreshed.
+//!
+//! ```rust,ignore
+//! let usdc = client.asset("fUSDC").await?;
+//! let amount = usdc.scale_amount(&"12.5".parse()?)?;
+//! println!("{} = {amount} of {}", usdc.symbol, usdc.asset_id);
+//! ```
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    AggregatedAssetInfo, AggregatedAssets, AssetId, Market, MarketAsset, MarketSymbol,
+};
+
+/// One asset, as listed across all markets.
+#[derive(Debug, Clone)]
+pub struct AssetInfo {
+    pub symbol: String,
+    pub asset_id: AssetId,
+    pub decimals: u32,
+    /// Markets that trade the asset, as base or quote.
+    pub markets: Vec<MarketSymbol>,
+    /// Metadata from `/v1/aggregated/assets`, if that endpoint lists the
+    /// symbol.
+    pub aggregated: Option<AggregatedAssetInfo>,
+}
+
+impl AssetInfo {
+    /// Convert a human-readable amount of this asset to its chain integer.
+    /// An amount finer than `decimals` fails with [`O2Error::InvalidAmount`].
+    pub fn scale_amount(&self, amount: &UnsignedDecimal) -> Result<u64, O2Error> {
+        self.as_market_asset().scale_amount(amount)
+    }
+
+    /// Convert a chain integer of this asset to human-readable.
+    pub fn format_amount(&self, chain_value: u128) -> Result<UnsignedDecimal, O2Error> {
+        self.as_market_asset().format_amount(chain_value)
+    }
+
+    fn as_market_asset(&self) -> MarketAsset {
+        MarketAsset {
+            symbol: self.symbol.clone(),
+            asset: self.asset_id.clone(),
+            decimals: self.decimals,
+            max_precision: self.decimals,
+        }
+    }
+}
+
+/// Every asset traded on a set of markets, by symbol and by asset id.
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    assets: Vec<AssetInfo>,
+}
+
+impl AssetRegistry {
+    /// Index the assets of `markets`, in listing order. An asset's symbol and
+    /// decimals are taken from the first market that lists it. `aggregated`
+    /// entries are attached by symbol, case-insensitively.
+    pub fn new(markets: &[Market], aggregated: Option<&AggregatedAssets>) -> Self {
+        let mut assets: Vec<AssetInfo> = Vec::new();
+        for market in markets {
+            let pair = market.symbol_pair();
+            for asset in [&market.base, &market.quote] {
+                match assets.iter_mut().find(|a| a.asset_id == asset.asset) {
+                    Some(known) => known.markets.push(pair.clone()),
+                    None => assets.push(AssetInfo {
+                        symbol: asset.symbol.clone(),
+                        asset_id: asset.asset.clone(),
+                        decimals: asset.decimals,
+                        markets: vec![pair.clone()],
+                        aggregated: aggregated.and_then(|all| {
+                            all.iter()
+                                .find(|(symbol, _)| symbol.eq_ignore_ascii_case(&asset.symbol))
+                                .map(|(_, info)| info.clone())
+                        }),
+                    }),
+                }
+            }
+        }
+        Self { assets }
+    }
+
+    /// The asset with `symbol`, matched case-insensitively.
+    pub fn by_symbol(&self, symbol: &str) -> Option<&AssetInfo> {
+        self.assets
+            .iter()
+            .find(|a| a.symbol.eq_ignore_ascii_case(symbol))
+    }
+
+    /// The asset with id `asset_id`, matched case-insensitively.
+    pub fn by_id(&self, asset_id: &str) -> Option<&AssetInfo> {
+        self.assets
+            .iter()
+            .find(|a| a.asset_id.as_str().eq_ignore_ascii_case(asset_id))
+    }
+
+    /// The asset named by `key`: an asset id if it starts with `0x`, else a
+    /// symbol.
+    pub fn get(&self, key: &str) -> Option<&AssetInfo> {
+        if key.starts_with("0x") || key.starts_with("0X") {
+            self.by_id(key)
+        } else {
+            self.by_symbol(key)
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AssetInfo> {
+        self.assets.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::{ContractId, MarketId};
+
+    fn asset(symbol: &str, id: &str, decimals: u32) -> MarketAsset {
+        MarketAsset {
+            symbol: symbol.to_string(),
+            asset: AssetId::new(id),
+            decimals,
+            max_precision: 2,
+        }
+    }
+
+    fn market(id: &str, base: MarketAsset, quote: MarketAsset) -> Market {
+        Market {
+            contract_id: ContractId::new(id),
+            market_id: MarketId::new(id),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 1,
+            dust: 0,
+            price_window: 0,
+            base,
+            quote,
+        }
+    }
+
+    #[test]
+    fn registry_indexes_each_asset_once() {
+        let markets = [
+            market(
+                "0xa",
+                asset("fETH", "0xeth", 9),
+                asset("fUSDC", "0xusdc", 6),
+            ),
+            market(
+                "0xb",
+                asset("fFUEL", "0xfuel", 9),
+                asset("fUSDC", "0xusdc", 6),
+            ),
+        ];
+        let aggregated: AggregatedAssets = serde_json::from_value(serde_json::json!({
+            "FUSDC": {
+                "name": "USD Coin",
+                "unified_cryptoasset_id": 3408,
+                "can_withdraw": true,
+                "can_deposit": true,
+                "min_withdraw": "1",
+                "min_deposit": "1",
+                "maker_fee": "0",
+                "taker_fee": "0.0002",
+            }
+        }))
+        .unwrap();
+        let registry = AssetRegistry::new(&markets, Some(&aggregated));
+
+        assert_eq!(registry.len(), 3);
+        let usdc = registry.get("fusdc").unwrap();
+        assert_eq!(usdc.asset_id.as_str(), "0xusdc");
+        assert_eq!(usdc.decimals, 6);
+        assert_eq!(
+            usdc.markets,
+            [
+                MarketSymbol::new("fETH/fUSDC"),
+                MarketSymbol::new("fFUEL/fUSDC")
+            ]
+        );
+        assert_eq!(usdc.aggregated.as_ref().unwrap().name, "USD Coin");
+        assert_eq!(
+            usdc.scale_amount(&"12.5".parse().unwrap()).unwrap(),
+            12_500_000
+        );
+
+        assert_eq!(registry.get("0xFUEL").unwrap().symbol, "fFUEL");
+        assert!(registry.get("fETH").unwrap().aggregated.is_none());
+        assert!(registry.get("fBTC").is_none());
+    }
+}
@@ -0,0 +1,686 @@
+//! Replaying historical trades and bars through a strategy.
+//!
+//! A [`Strategy`] reacts to [`MarketData`] by placing and cancelling orders
+//! through a [`Trader`]. Live, feed it events from
+//! [`O2Client::stream_trades`] with a [`LiveTrader`](crate::LiveTrader) or a
+//! [`PaperTrader`](crate::PaperTrader); here, a [`Backtest`] feeds it
+//! historical events with a [`BacktestTrader`] that simulates fills with the
+//! same rules as `PaperTrader`.
+//!
+//! ```rust,ignore
+//! let market = client.get_market("fFUEL/fUSDC").await?;
+//! let from = Timestamp::now() - Duration::from_secs(86_400);
+//! let data = BacktestData::fetch_trades(&mut client, &market.symbol_pair(), from, Timestamp::now()).await?;
+//! let report = Backtest::new(market, data)
+//!     .with_quote_balance("1000".parse()?)
+//!     .run(&mut my_strategy)
+//!     .await?;
+//! println!("pnl {} max drawdown {}", report.stats.pnl, report.stats.max_drawdown);
+//! ```
+//!
+//! There is no historical depth. An order is matched on placement against
+//! the last traded price (or bar close) as if unlimited size were quoted
+//! there on both sides; what rests fills at its own price from later trades,
+//! or from later bars whose low (for buys) or high (for sells) reaches it,
+//! without a volume limit. Events are delivered in timestamp order and
+//! resting orders are matched against each event before the strategy sees
+//! it.
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::BufRead;
+
+use futures_util::StreamExt;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    BalanceResponse, Bar, DepthLevel, DepthSnapshot, Market, MarketSymbol, Order, OrderId,
+    OrderType, Side, Trade, TradeId,
+};
+use crate::orderbook::OrderBook;
+use crate::paper::{Ledger, PaperFill, Trader};
+use crate::preflight::check_order;
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+
+/// One historical or live market data point: a trade or a bar.
+#[derive(Debug, Clone)]
+pub enum MarketData {
+    Trade(Trade),
+    Bar(Bar),
+}
+
+impl MarketData {
+    pub fn timestamp(&self) -> Timestamp {
+        match self {
+            MarketData::Trade(trade) => trade.timestamp,
+            MarketData::Bar(bar) => bar.timestamp,
+        }
+    }
+
+    /// The trade price, or the bar's close, in chain units.
+    pub fn price(&self) -> u64 {
+        match self {
+            MarketData::Trade(trade) => trade.price,
+            MarketData::Bar(bar) => bar.close,
+        }
+    }
+}
+
+/// Trading logic driven by market events.
+pub trait Strategy: Send {
+    /// React to `event` on `market`, trading through `trader`.
+    fn on_event<T: Trader>(
+        &mut self,
+        market: &MarketSymbol,
+        event: &MarketData,
+        trader: &mut T,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send;
+}
+
+/// Historical events for one market, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct BacktestData {
+    events: Vec<MarketData>,
+}
+
+impl BacktestData {
+    pub fn from_trades(trades: Vec<Trade>) -> Self {
+        Self::from_events(trades.into_iter().map(MarketData::Trade).collect())
+    }
+
+    pub fn from_bars(bars: Vec<Bar>) -> Self {
+        Self::from_events(bars.into_iter().map(MarketData::Bar).collect())
+    }
+
+    /// Sort `events` by timestamp, keeping the given order for ties.
+    pub fn from_events(mut events: Vec<MarketData>) -> Self {
+        events.sort_by_key(MarketData::timestamp);
+        Self { events }
+    }
+
+    /// Fetch `market`'s trades from `from` up to and including `to`.
+    pub async fn fetch_trades(
+        client: &mut O2Client,
+        market: &MarketSymbol,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Self, O2Error> {
+        debug!(
+            "backtest.fetch_trades market={} from={} to={}",
+            market, from, to
+        );
+        let mut history = client.trade_history(market, from).await?;
+        let mut trades = Vec::new();
+        while let Some(trade) = history.next().await {
+            let trade = trade?;
+            if trade.timestamp > to {
+                break;
+            }
+            trades.push(trade);
+        }
+        Ok(Self::from_trades(trades))
+    }
+
+    /// Fetch `market`'s bars at `resolution` (e.g. `"1m"`) between `from`
+    /// and `to`.
+    pub async fn fetch_bars(
+        client: &mut O2Client,
+        market: &MarketSymbol,
+        resolution: &str,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Self, O2Error> {
+        let millis = |t: Timestamp| u64::try_from(t.as_unix_millis()).unwrap_or(u64::MAX);
+        let bars = client
+            .get_bars(market, resolution, millis(from), millis(to))
+            .await?;
+        Ok(Self::from_bars(bars))
+    }
+
+    /// Read trades from CSV rows of `timestamp,side,price,quantity`: Unix
+    /// milliseconds, the maker side (`Buy` or `Sell`), and chain integers.
+    /// A header row is skipped. `total` is left at zero.
+    pub fn trades_from_csv(reader: impl BufRead) -> Result<Self, O2Error> {
+        let mut trades = Vec::new();
+        for (line, fields) in csv_rows(reader, 4)? {
+            let side = if fields[1].eq_ignore_ascii_case("buy") {
+                Side::Buy
+            } else if fields[1].eq_ignore_ascii_case("sell") {
+                Side::Sell
+            } else {
+                return Err(csv_error(line, &format!("unknown side {:?}", fields[1])));
+            };
+            trades.push(Trade {
+                trade_id: TradeId::new(line.to_string()),
+                side,
+                total: 0,
+                quantity: csv_int(line, &fields[3])?,
+                price: csv_int(line, &fields[2])?,
+                timestamp: Timestamp::from_unix_millis(csv_int(line, &fields[0])?),
+                trader_side: None,
+                maker: None,
+                taker: None,
+            });
+        }
+        Ok(Self::from_trades(trades))
+    }
+
+    /// Read bars from CSV rows of
+    /// `timestamp,open,high,low,close,buy_volume,sell_volume`: Unix
+    /// milliseconds and chain integers. A header row is skipped.
+    pub fn bars_from_csv(reader: impl BufRead) -> Result<Self, O2Error> {
+        let mut bars = Vec::new();
+        for (line, fields) in csv_rows(reader, 7)? {
+            bars.push(Bar {
+                timestamp: Timestamp::from_unix_millis(csv_int(line, &fields[0])?),
+                open: csv_int(line, &fields[1])?,
+                high: csv_int(line, &fields[2])?,
+                low: csv_int(line, &fields[3])?,
+                close: csv_int(line, &fields[4])?,
+                buy_volume: csv_int(line, &fields[5])?,
+                sell_volume: csv_int(line, &fields[6])?,
+            });
+        }
+        Ok(Self::from_bars(bars))
+    }
+
+    pub fn events(&self) -> &[MarketData] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Non-empty rows of `columns` trimmed fields, with 1-based line numbers. A
+/// first row whose first field is not a number is taken as a header.
+fn csv_rows(reader: impl BufRead, columns: usize) -> Result<Vec<(usize, Vec<String>)>, O2Error> {
+    let mut rows = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.map_err(|e| csv_error(line_no, &e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<String> = line.split(',').map(|f| f.trim().to_string()).collect();
+        if rows.is_empty() && line_no == 1 && fields[0].parse::<u64>().is_err() {
+            continue;
+        }
+        if fields.len() != columns {
+            return Err(csv_error(
+                line_no,
+                &format!("expected {columns} fields, found {}", fields.len()),
+            ));
+        }
+        rows.push((line_no, fields));
+    }
+    Ok(rows)
+}
+
+fn csv_int<T: std::str::FromStr>(line: usize, field: &str) -> Result<T, O2Error> {
+    field
+        .parse()
+        .map_err(|_| csv_error(line, &format!("{field:?} is not an integer")))
+}
+
+fn csv_error(line: usize, why: &str) -> O2Error {
+    O2Error::InvalidRequest(format!("CSV line {line}: {why}"))
+}
+
+/// A [`Trader`] whose orders fill against replayed events. Handed to the
+/// strategy by [`Backtest::run`].
+pub struct BacktestTrader {
+    market: Market,
+    ledger: Ledger,
+    now: Timestamp,
+    last_price: Option<u64>,
+}
+
+impl BacktestTrader {
+    /// Time of the event being replayed.
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// The last trade price or bar close, in chain units.
+    pub fn last_price(&self) -> Option<u64> {
+        self.last_price
+    }
+
+    /// Every fill so far, oldest first.
+    pub fn fills(&self) -> &[PaperFill] {
+        self.ledger.fills()
+    }
+
+    fn check_market(&self, market: &MarketSymbol) -> Result<(), O2Error> {
+        if *market == self.market.symbol_pair() {
+            Ok(())
+        } else {
+            Err(O2Error::MarketNotFound(format!(
+                "Backtest replays {}, not {market}",
+                self.market.symbol_pair()
+            )))
+        }
+    }
+
+    /// Fill resting orders from `event`, then take its price as the last.
+    fn apply(&mut self, event: &MarketData) -> Result<(), O2Error> {
+        self.now = event.timestamp();
+        match event {
+            MarketData::Trade(trade) => self
+                .ledger
+                .apply_trades(&self.market, std::slice::from_ref(trade))?,
+            MarketData::Bar(bar) => {
+                let reach = |price| Trade {
+                    trade_id: TradeId::new("bar"),
+                    side: Side::Buy,
+                    total: 0,
+                    quantity: u64::MAX,
+                    price,
+                    timestamp: bar.timestamp,
+                    trader_side: None,
+                    maker: None,
+                    taker: None,
+                };
+                self.ledger
+                    .apply_trades(&self.market, &[reach(bar.low), reach(bar.high)])?
+            }
+        }
+        self.last_price = Some(event.price());
+        Ok(())
+    }
+
+    /// Quote balance plus base balance at the last price, in quote chain units.
+    fn equity(&self) -> u128 {
+        let base = self.ledger.total(&self.market.base.asset);
+        let quote = self.ledger.total(&self.market.quote.asset);
+        let price = u128::from(self.last_price.unwrap_or(0));
+        quote + base * price / 10u128.pow(self.market.base.decimals)
+    }
+
+    fn place(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> Result<Order, O2Error> {
+        self.check_market(market)?;
+        let order = check_order(&self.market, side, price, quantity, order_type)?;
+        let mut book = OrderBook::new(self.market.market_id.clone());
+        if let Some(price) = self.last_price {
+            let level = vec![DepthLevel {
+                price,
+                quantity: u64::MAX,
+            }];
+            book.apply_snapshot(&DepthSnapshot {
+                bids: level.clone(),
+                asks: level,
+            });
+        }
+        self.ledger.place(&self.market, &book, order, self.now)
+    }
+}
+
+impl Trader for BacktestTrader {
+    fn create_order(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> impl Future<Output = Result<Order, O2Error>> + Send {
+        let result = self.place(market, side, price, quantity, order_type);
+        async move { result }
+    }
+
+    fn cancel_order(
+        &mut self,
+        market: &MarketSymbol,
+        order_id: &OrderId,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send {
+        let result = self
+            .check_market(market)
+            .and_then(|()| self.ledger.cancel(market, Some(order_id)));
+        async move { result.map(|_| ()) }
+    }
+
+    fn cancel_all_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send {
+        let result = self
+            .check_market(market)
+            .and_then(|()| self.ledger.cancel(market, None));
+        async move { result.map(|_| ()) }
+    }
+
+    fn open_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<Vec<Order>, O2Error>> + Send {
+        let result = self
+            .check_market(market)
+            .map(|()| self.ledger.open_orders(market));
+        async move { result }
+    }
+
+    fn balances(
+        &mut self,
+    ) -> impl Future<Output = Result<HashMap<String, BalanceResponse>, O2Error>> + Send {
+        let balances = self.ledger.balances();
+        async move { Ok(balances) }
+    }
+}
+
+/// A strategy run over [`BacktestData`] for one market.
+pub struct Backtest {
+    market: Market,
+    data: BacktestData,
+    base_balance: UnsignedDecimal,
+    quote_balance: UnsignedDecimal,
+}
+
+impl Backtest {
+    /// Replay `data` on `market`, starting with no balances.
+    pub fn new(market: Market, data: BacktestData) -> Self {
+        Self {
+            market,
+            data,
+            base_balance: UnsignedDecimal::ZERO,
+            quote_balance: UnsignedDecimal::ZERO,
+        }
+    }
+
+    /// Start with `amount` of the base asset.
+    pub fn with_base_balance(mut self, amount: UnsignedDecimal) -> Self {
+        self.base_balance = amount;
+        self
+    }
+
+    /// Start with `amount` of the quote asset.
+    pub fn with_quote_balance(mut self, amount: UnsignedDecimal) -> Self {
+        self.quote_balance = amount;
+        self
+    }
+
+    /// Replay every event through `strategy`. An error from the strategy
+    /// stops the run.
+    pub async fn run<S: Strategy>(self, strategy: &mut S) -> Result<BacktestReport, O2Error> {
+        let Backtest {
+            market,
+            data,
+            base_balance,
+            quote_balance,
+        } = self;
+        let symbol = market.symbol_pair();
+        debug!("backtest.run market={} events={}", symbol, data.len());
+
+        let mut ledger = Ledger::default();
+        let base = u128::from(market.base.scale_amount(&base_balance)?);
+        let quote = u128::from(market.quote.scale_amount(&quote_balance)?);
+        ledger.credit(&market.base.asset, &market.base.symbol, base);
+        ledger.credit(&market.quote.asset, &market.quote.symbol, quote);
+        let mut trader = BacktestTrader {
+            market,
+            ledger,
+            now: Timestamp::UNIX_EPOCH,
+            last_price: data.events.first().map(MarketData::price),
+        };
+
+        let starting_equity = trader.equity();
+        let mut curve = Vec::with_capacity(data.len());
+        for event in &data.events {
+            trader.apply(event)?;
+            strategy.on_event(&symbol, event, &mut trader).await?;
+            curve.push((event.timestamp(), trader.equity()));
+        }
+        BacktestReport::new(&trader, data.len(), starting_equity, curve)
+    }
+}
+
+/// Account value at one point in a backtest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EquityPoint {
+    pub timestamp: Timestamp,
+    /// Quote balance plus base balance at the last price, in quote units.
+    pub equity: UnsignedDecimal,
+}
+
+/// Summary of a backtest. Amounts are in quote units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktestStats {
+    pub events: usize,
+    pub fills: usize,
+    pub maker_fills: usize,
+    /// Quote notional traded.
+    pub volume: UnsignedDecimal,
+    pub fees: UnsignedDecimal,
+    /// Equity at the first event's price, before any trading.
+    pub starting_equity: UnsignedDecimal,
+    pub final_equity: UnsignedDecimal,
+    pub pnl: Decimal,
+    /// `pnl` as a fraction of `starting_equity`; zero if that is zero.
+    pub total_return: Decimal,
+    /// Largest fall from a peak of the equity curve, as a fraction of the peak.
+    pub max_drawdown: Decimal,
+}
+
+/// What [`Backtest::run`] produced.
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub fills: Vec<PaperFill>,
+    /// Equity after each event.
+    pub equity_curve: Vec<EquityPoint>,
+    pub stats: BacktestStats,
+}
+
+impl BacktestReport {
+    fn new(
+        trader: &BacktestTrader,
+        events: usize,
+        starting_equity: u128,
+        curve: Vec<(Timestamp, u128)>,
+    ) -> Result<Self, O2Error> {
+        let quote = &trader.market.quote;
+        let base_factor = 10u128.pow(trader.market.base.decimals);
+        let fills = trader.ledger.fills().to_vec();
+
+        let volume: u128 = fills
+            .iter()
+            .map(|f| u128::from(f.price) * u128::from(f.quantity) / base_factor)
+            .sum();
+        let fees: u128 = fills.iter().map(|f| f.fee).sum();
+        let final_equity = curve.last().map_or(starting_equity, |&(_, e)| e);
+
+        let mut peak = starting_equity;
+        let mut max_drawdown = Decimal::ZERO;
+        for &(_, equity) in &curve {
+            peak = peak.max(equity);
+            if peak > 0 {
+                let drawdown = to_decimal(peak - equity)? / to_decimal(peak)?;
+                max_drawdown = max_drawdown.max(drawdown);
+            }
+        }
+
+        let pnl = to_decimal(final_equity)? - to_decimal(starting_equity)?;
+        let total_return = if starting_equity == 0 {
+            Decimal::ZERO
+        } else {
+            pnl / to_decimal(starting_equity)?
+        };
+        let scale = Decimal::from(10u64.pow(quote.decimals));
+        let stats = BacktestStats {
+            events,
+            fills: fills.len(),
+            maker_fills: fills.iter().filter(|f| f.is_maker).count(),
+            volume: quote.format_amount(volume)?,
+            fees: quote.format_amount(fees)?,
+            starting_equity: quote.format_amount(starting_equity)?,
+            final_equity: quote.format_amount(final_equity)?,
+            pnl: pnl / scale,
+            total_return,
+            max_drawdown,
+        };
+        let equity_curve = curve
+            .into_iter()
+            .map(|(timestamp, equity)| {
+                Ok(EquityPoint {
+                    timestamp,
+                    equity: quote.format_amount(equity)?,
+                })
+            })
+            .collect::<Result<_, O2Error>>()?;
+        Ok(Self {
+            fills,
+            equity_curve,
+            stats,
+        })
+    }
+}
+
+fn to_decimal(value: u128) -> Result<Decimal, O2Error> {
+    // `From<u128>` panics past Decimal's 96-bit mantissa.
+    Decimal::from_u128(value)
+        .ok_or_else(|| O2Error::Other(format!("Amount {value} is out of decimal range")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, MarketAsset, MarketId};
+
+    fn market() -> Market {
+        Market {
+            contract_id: ContractId::new("0x01"),
+            market_id: MarketId::new("0x02"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 100,
+            min_order: 1_000_000,
+            dust: 0,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "fFUEL".into(),
+                asset: AssetId::new("0xaa"),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "fUSDC".into(),
+                asset: AssetId::new("0xbb"),
+                decimals: 6,
+                max_precision: 4,
+            },
+        }
+    }
+
+    /// Buys at the first price and offers the purchase slightly higher.
+    struct BuyThenOffer {
+        placed: bool,
+    }
+
+    impl Strategy for BuyThenOffer {
+        async fn on_event<T: Trader>(
+            &mut self,
+            market: &MarketSymbol,
+            _event: &MarketData,
+            trader: &mut T,
+        ) -> Result<(), O2Error> {
+            if std::mem::replace(&mut self.placed, true) {
+                return Ok(());
+            }
+            let quantity: UnsignedDecimal = "10".parse()?;
+            trader
+                .create_order(
+                    market,
+                    Side::Buy,
+                    "0.25".parse()?,
+                    quantity,
+                    OrderType::Spot,
+                )
+                .await?;
+            trader
+                .create_order(
+                    market,
+                    Side::Sell,
+                    "0.28".parse()?,
+                    quantity,
+                    OrderType::Spot,
+                )
+                .await?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn csv_rows_are_parsed_and_sorted() {
+        let csv = "timestamp,side,price,quantity\n2000,Sell,200000,5\n\n1000,buy,250000,7\n";
+        let data = BacktestData::trades_from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(data.len(), 2);
+        let MarketData::Trade(first) = &data.events()[0] else {
+            panic!("expected a trade");
+        };
+        assert_eq!(
+            (first.side, first.price, first.quantity),
+            (Side::Buy, 250_000, 7)
+        );
+
+        let bars = BacktestData::bars_from_csv("1000,1,3,1,2,0,0\n".as_bytes()).unwrap();
+        assert_eq!(bars.events()[0].price(), 2);
+        let err = BacktestData::bars_from_csv("1000,1,2,3\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("CSV line 1"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn run_reports_fills_and_equity() {
+        let data = BacktestData::trades_from_csv(
+            "1000,Buy,250000,1\n2000,Sell,200000,1\n3000,Buy,300000,100000000000\n".as_bytes(),
+        )
+        .unwrap();
+        let report = Backtest::new(market(), data)
+            .with_quote_balance("10".parse().unwrap())
+            .run(&mut BuyThenOffer { placed: false })
+            .await
+            .unwrap();
+
+        // A taker buy at the last price, then the offer filled as maker.
+        let fills: Vec<_> = report
+            .fills
+            .iter()
+            .map(|f| (f.side, f.price, f.is_maker))
+            .collect();
+        assert_eq!(
+            fills,
+            [(Side::Buy, 250_000, false), (Side::Sell, 280_000, true)]
+        );
+        let equity: Vec<String> = report
+            .equity_curve
+            .iter()
+            .map(|p| p.equity.to_string())
+            .collect();
+        assert_eq!(equity, ["9.99975", "9.49975", "10.29975"]);
+
+        let stats = &report.stats;
+        assert_eq!(stats.events, 3);
+        assert_eq!((stats.fills, stats.maker_fills), (2, 1));
+        assert_eq!(stats.volume.to_string(), "5.3");
+        assert_eq!(stats.fees.to_string(), "0.00025");
+        assert_eq!(stats.starting_equity.to_string(), "10");
+        assert_eq!(stats.pnl, "0.29975".parse().unwrap());
+        assert_eq!(stats.total_return, "0.029975".parse().unwrap());
+        assert_eq!(stats.max_drawdown, "0.050025".parse().unwrap());
+    }
+}
@@ -0,0 +1,385 @@
+//! `o2-cli`: a thin command-line front end over the O2 SDK.
+//!
+//! Every command goes through the same client calls and signing paths a
+//! program would use; nothing here talks to the API directly. The private key
+//! is read from `--private-key` or `O2_PRIVATE_KEY` and is never written to
+//! disk. Structured results are printed as JSON so they can be piped into
+//! other tools. `O2_API_BASE`, `O2_WS_URL` and the other
+//! [`NetworkConfig::with_env_overrides`](o2_sdk::NetworkConfig::with_env_overrides)
+//! variables point it at a staging environment or self-hosted gateway.
+//!
+//! ```text
+//! o2-cli wallet new
+//! O2_PRIVATE_KEY=0x... o2-cli account setup
+//! O2_PRIVATE_KEY=0x... o2-cli order place fFUEL/fUSDC buy 0.05 100
+//! o2-cli depth fFUEL/fUSDC --limit 10
+//! o2-cli tail trades fFUEL/fUSDC
+//! ```
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use o2_sdk::crypto::to_hex_string;
+use o2_sdk::prelude::*;
+use o2_sdk::IntoValidId;
+use serde::Serialize;
+
+/// Lifetime of the session each signed command creates.
+const SESSION_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Parser)]
+#[command(
+    name = "o2-cli",
+    version,
+    about = "Command-line client for the O2 Exchange"
+)]
+struct Cli {
+    /// Network to connect to.
+    #[arg(
+        long,
+        value_enum,
+        env = "O2_NETWORK",
+        default_value = "testnet",
+        global = true
+    )]
+    network: NetworkArg,
+
+    /// Owner private key (hex). Required by commands that sign.
+    #[arg(long, env = "O2_PRIVATE_KEY", hide_env_values = true, global = true)]
+    private_key: Option<String>,
+
+    /// Treat the private key as an EVM wallet instead of a Fuel wallet.
+    #[arg(long, global = true)]
+    evm: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum NetworkArg {
+    Testnet,
+    Devnet,
+    Mainnet,
+}
+
+impl From<NetworkArg> for Network {
+    fn from(arg: NetworkArg) -> Self {
+        match arg {
+            NetworkArg::Testnet => Network::Testnet,
+            NetworkArg::Devnet => Network::Devnet,
+            NetworkArg::Mainnet => Network::Mainnet,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate or import a wallet.
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Manage the trading account.
+    #[command(subcommand)]
+    Account(AccountCommand),
+    /// Show trading account balances.
+    Balance,
+    /// Withdraw an asset to the owner wallet.
+    Withdraw {
+        /// Asset symbol, e.g. `fUSDC`.
+        asset: String,
+        /// Amount in human units, or `all` to settle and withdraw everything.
+        amount: String,
+    },
+    /// Place and cancel orders.
+    #[command(subcommand)]
+    Order(OrderCommand),
+    /// Print an order book depth snapshot.
+    Depth {
+        market: String,
+        /// Price precision (1-18).
+        #[arg(long, default_value_t = 1)]
+        precision: u64,
+        /// Levels per side.
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Print recent trades.
+    Trades {
+        market: String,
+        #[arg(long, default_value_t = 20)]
+        count: u32,
+    },
+    /// Follow a WebSocket stream until interrupted.
+    #[command(subcommand)]
+    Tail(TailCommand),
+}
+
+#[derive(Subcommand)]
+enum WalletCommand {
+    /// Generate a new wallet and print its key and address.
+    New,
+    /// Derive the address of an existing private key.
+    Import {
+        /// Private key (hex). Falls back to `--private-key`.
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccountCommand {
+    /// Create the trading account if needed (idempotent).
+    Setup,
+}
+
+#[derive(Subcommand)]
+enum OrderCommand {
+    /// Place an order.
+    Place {
+        market: String,
+        side: SideArg,
+        price: String,
+        quantity: String,
+        #[arg(long = "type", value_enum, default_value = "spot")]
+        order_type: OrderTypeArg,
+    },
+    /// Cancel one order.
+    Cancel { market: String, order_id: String },
+    /// Cancel every open order on a market.
+    CancelAll { market: String },
+}
+
+#[derive(Subcommand)]
+enum TailCommand {
+    /// Depth updates for a market.
+    Depth {
+        market: String,
+        #[arg(long, default_value_t = 1)]
+        precision: u64,
+    },
+    /// Trades on a market.
+    Trades { market: String },
+    /// Order updates for the owner's trading account.
+    Orders,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SideArg {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OrderTypeArg {
+    Spot,
+    Market,
+    FillOrKill,
+    PostOnly,
+}
+
+impl From<SideArg> for Side {
+    fn from(arg: SideArg) -> Self {
+        match arg {
+            SideArg::Buy => Side::Buy,
+            SideArg::Sell => Side::Sell,
+        }
+    }
+}
+
+impl From<OrderTypeArg> for OrderType {
+    fn from(arg: OrderTypeArg) -> Self {
+        match arg {
+            OrderTypeArg::Spot => OrderType::Spot,
+            OrderTypeArg::Market => OrderType::Market,
+            OrderTypeArg::FillOrKill => OrderType::FillOrKill,
+            OrderTypeArg::PostOnly => OrderType::PostOnly,
+        }
+    }
+}
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> CliResult {
+    let config = NetworkConfig::from_network(cli.network.into()).with_env_overrides()?;
+    let mut client = O2Client::with_config(config);
+
+    match cli.command {
+        Command::Wallet(WalletCommand::New) => {
+            let (private_key, address) = if cli.evm {
+                let w = client.generate_evm_wallet()?;
+                (w.private_key, w.b256_address)
+            } else {
+                let w = client.generate_wallet()?;
+                (w.private_key, w.b256_address)
+            };
+            print_json(&serde_json::json!({
+                "private_key": private_key.expose_hex().as_str(),
+                "address": to_hex_string(&address),
+            }))
+        }
+        Command::Wallet(WalletCommand::Import { key }) => {
+            let key = key.or(cli.private_key).ok_or(MISSING_KEY)?;
+            let address = if cli.evm {
+                client.load_evm_wallet(&key)?.b256_address
+            } else {
+                client.load_wallet(&key)?.b256_address
+            };
+            print_json(&serde_json::json!({ "address": to_hex_string(&address) }))
+        }
+        Command::Depth {
+            market,
+            precision,
+            limit,
+        } => print_json(&client.get_depth(market, precision, limit).await?),
+        Command::Trades { market, count } => {
+            print_json(&client.get_trades(market, count, None, None).await?)
+        }
+        Command::Tail(TailCommand::Depth { market, precision }) => {
+            let market = client.get_market(market).await?;
+            let stream = client.stream_depth(&market.market_id, precision).await?;
+            tail(stream).await
+        }
+        Command::Tail(TailCommand::Trades { market }) => {
+            let market = client.get_market(market).await?;
+            let stream = client.stream_trades(&market.market_id).await?;
+            tail(stream).await
+        }
+        signed => {
+            let key = cli.private_key.ok_or(MISSING_KEY)?;
+            if cli.evm {
+                let wallet = client.load_evm_wallet(&key)?;
+                run_signed(&mut client, &wallet, signed).await
+            } else {
+                let wallet = client.load_wallet(&key)?;
+                run_signed(&mut client, &wallet, signed).await
+            }
+        }
+    }
+}
+
+const MISSING_KEY: &str = "a private key is required: pass --private-key or set O2_PRIVATE_KEY";
+
+/// Commands that act on behalf of the owner wallet.
+async fn run_signed<W: SignableWallet>(
+    client: &mut O2Client,
+    wallet: &W,
+    command: Command,
+) -> CliResult {
+    let owner = to_hex_string(wallet.b256_address());
+
+    match command {
+        Command::Account(AccountCommand::Setup) => {
+            let account = client.setup_account(wallet).await?;
+            print_json(&serde_json::json!({
+                "owner": owner,
+                "trade_account_id": account.trade_account_id,
+            }))
+        }
+        Command::Balance => print_json(&client.get_balances_by_owner(owner.as_str()).await?),
+        Command::Withdraw { asset, amount } => {
+            let markets: Vec<MarketSymbol> = client
+                .get_markets()
+                .await?
+                .iter()
+                .map(|m| m.symbol_pair())
+                .collect();
+            let mut session = client.create_session(wallet, &markets, SESSION_TTL).await?;
+            if amount.eq_ignore_ascii_case("all") {
+                match client.withdraw_all(wallet, &mut session, &asset).await? {
+                    Some(resp) => print_json(&resp),
+                    None => {
+                        eprintln!("nothing to withdraw");
+                        Ok(())
+                    }
+                }
+            } else {
+                let amount: UnsignedDecimal = amount.parse()?;
+                print_json(
+                    &client
+                        .withdraw_human(wallet, &session, &asset, amount)
+                        .await?,
+                )
+            }
+        }
+        Command::Order(order) => {
+            let market = match &order {
+                OrderCommand::Place { market, .. }
+                | OrderCommand::Cancel { market, .. }
+                | OrderCommand::CancelAll { market } => client.get_market(market).await?,
+            };
+            let symbol = market.symbol_pair();
+            let mut session = client
+                .create_session(wallet, &[&symbol], SESSION_TTL)
+                .await?;
+            match order {
+                OrderCommand::Place {
+                    side,
+                    price,
+                    quantity,
+                    order_type,
+                    ..
+                } => {
+                    let resp = client
+                        .create_order(
+                            &mut session,
+                            &symbol,
+                            side.into(),
+                            price.as_str(),
+                            quantity.as_str(),
+                            order_type.into(),
+                            true,
+                            true,
+                        )
+                        .await?;
+                    print_json(&resp)
+                }
+                OrderCommand::Cancel { order_id, .. } => {
+                    let order_id: OrderId = order_id.into_valid()?;
+                    print_json(
+                        &client
+                            .cancel_order(&mut session, &order_id, &symbol)
+                            .await?,
+                    )
+                }
+                OrderCommand::CancelAll { .. } => {
+                    print_json(&client.cancel_all_orders(&mut session, &symbol).await?)
+                }
+            }
+        }
+        Command::Tail(TailCommand::Orders) => {
+            let trade_account_id = client.trade_account_for_owner(owner.as_str()).await?;
+            let stream = client
+                .stream_orders(&[Identity::ContractId(trade_account_id.to_string())])
+                .await?;
+            tail(stream).await
+        }
+        Command::Wallet(_) | Command::Depth { .. } | Command::Trades { .. } | Command::Tail(_) => {
+            unreachable!("handled without a wallet")
+        }
+    }
+}
+
+/// Print each stream item as one JSON line until the stream ends or Ctrl-C.
+async fn tail<T: Serialize>(mut stream: TypedStream<T>) -> CliResult {
+    loop {
+        tokio::select! {
+            item = stream.next() => match item {
+                Some(update) => println!("{}", serde_json::to_string(&update?)?),
+                None => return Ok(()),
+            },
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> CliResult {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,448 @@
+//! OHLCV bars built locally from the trade stream.
+//!
+//! [`Candles`] folds trades into bars of one resolution, seeded with history
+//! from [`O2Client::get_bars`](crate::O2Client::get_bars). [`Aggregator`] drives
+//! it from a [`TypedStream<TradeUpdate>`] and yields a [`CandleEvent`] each time
+//! the live bar changes or a bar closes, so charting and signal code does not
+//! have to poll `get_bars` or repeat the server's bucketing.
+//!
+//! Bars follow the server's layout: prices are chain-scaled, volumes are base
+//! quantities, and a bar's `timestamp` is the start of its bucket. Buckets are
+//! aligned to the Unix epoch, and weeks start on Monday. Calendar-month
+//! resolutions (`1M`, `3M`) are not aggregated. A bar only closes when a trade
+//! lands in a later bucket, and buckets without trades produce no bar.
+//!
+//! `buy_volume` counts trades the taker bought, i.e. whose maker side is
+//! `Sell`; `sell_volume` counts the rest.
+//!
+//! ```rust,ignore
+//! let mut candles = client.candles("fFUEL/fUSDC", "1m", Duration::from_secs(3600)).await?;
+//! while let Some(event) = candles.next().await {
+//!     if let CandleEvent::Closed(bar) = event? {
+//!         println!("{} close {}", bar.timestamp, bar.close);
+//!     }
+//! }
+//! ```
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt};
+
+use crate::errors::O2Error;
+use crate::models::{Bar, MarketId, Side, Trade, TradeId, TradeUpdate};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// Closed bars kept by default.
+pub const DEFAULT_HISTORY_LIMIT: usize = 1_000;
+
+/// Trade IDs remembered to drop trades that are delivered twice, e.g. when the
+/// stream resubscribes after a reconnect.
+const SEEN_TRADES: usize = 1_024;
+
+/// Monday 1970-01-05, the first week boundary after the epoch.
+const WEEK_OFFSET_MS: u64 = 4 * 86_400_000;
+
+/// Bucket length in milliseconds for a resolution string.
+fn resolution_millis(resolution: &str) -> Result<u64, O2Error> {
+    const MINUTE: u64 = 60_000;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    Ok(match resolution {
+        "1s" => 1_000,
+        "1m" => MINUTE,
+        "2m" => 2 * MINUTE,
+        "3m" => 3 * MINUTE,
+        "5m" => 5 * MINUTE,
+        "15m" => 15 * MINUTE,
+        "30m" => 30 * MINUTE,
+        "1h" => HOUR,
+        "2h" => 2 * HOUR,
+        "4h" => 4 * HOUR,
+        "6h" => 6 * HOUR,
+        "8h" => 8 * HOUR,
+        "12h" => 12 * HOUR,
+        "1d" => DAY,
+        "3d" => 3 * DAY,
+        "1w" => 7 * DAY,
+        "1M" | "3M" => {
+            return Err(O2Error::InvalidRequest(format!(
+                "Calendar-month resolution \"{resolution}\" cannot be aggregated locally"
+            )))
+        }
+        _ => {
+            return Err(O2Error::InvalidRequest(format!(
+                "Invalid bar resolution \"{resolution}\""
+            )))
+        }
+    })
+}
+
+/// A change to the bars.
+#[derive(Debug, Clone)]
+pub enum CandleEvent {
+    /// The live bar, after trades were added to it.
+    Updated(Bar),
+    /// A bar that will not change again, because a trade opened a later one.
+    Closed(Bar),
+}
+
+/// Bars of one resolution built from trades.
+#[derive(Debug, Clone)]
+pub struct Candles {
+    resolution: String,
+    period_ms: u64,
+    offset_ms: u64,
+    history: VecDeque<Bar>,
+    history_limit: usize,
+    current: Option<Bar>,
+    last_trade_at: Option<Timestamp>,
+    cutoff: Option<Timestamp>,
+    seen: HashSet<TradeId>,
+    seen_order: VecDeque<TradeId>,
+}
+
+impl Candles {
+    /// Fails with [`O2Error::InvalidRequest`] for an unknown or calendar-month
+    /// resolution.
+    pub fn new(resolution: &str) -> Result<Self, O2Error> {
+        let period_ms = resolution_millis(resolution)?;
+        Ok(Self {
+            resolution: resolution.to_string(),
+            period_ms,
+            offset_ms: if resolution == "1w" {
+                WEEK_OFFSET_MS
+            } else {
+                0
+            },
+            history: VecDeque::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            current: None,
+            last_trade_at: None,
+            cutoff: None,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+        })
+    }
+
+    /// Keep at most `limit` closed bars instead of [`DEFAULT_HISTORY_LIMIT`].
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.set_history_limit(limit);
+        self
+    }
+
+    pub(crate) fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.trim_history();
+    }
+
+    /// The resolution string, e.g. `"1m"`.
+    pub fn resolution(&self) -> &str {
+        &self.resolution
+    }
+
+    /// Length of one bar.
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms)
+    }
+
+    /// Start of the bucket containing `at`.
+    pub fn bucket_start(&self, at: Timestamp) -> Timestamp {
+        let ms = u64::try_from(at.as_unix_millis()).unwrap_or(u64::MAX);
+        let start = ms.saturating_sub(self.offset_ms) / self.period_ms * self.period_ms;
+        Timestamp::from_unix_millis(start + self.offset_ms.min(ms))
+    }
+
+    /// Replace the bars with `bars` from `get_bars`, covering trades up to
+    /// `cutoff`. The newest bar becomes the live one; stream trades at or
+    /// before `cutoff` are assumed to be in the history already and ignored.
+    pub fn seed(&mut self, bars: &[Bar], cutoff: Timestamp) {
+        let mut bars = bars.to_vec();
+        bars.sort_by_key(|b| b.timestamp);
+        self.current = bars.pop();
+        self.history = bars.into();
+        self.trim_history();
+        self.cutoff = Some(cutoff);
+        self.last_trade_at = Some(cutoff);
+    }
+
+    /// The live bar, if any.
+    pub fn current(&self) -> Option<&Bar> {
+        self.current.as_ref()
+    }
+
+    /// Closed bars, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Bar> + '_ {
+        self.history.iter()
+    }
+
+    /// Fold the trades of `update` in, oldest first. Returns the bars that
+    /// closed, then the live bar if it changed.
+    pub fn apply(&mut self, update: &TradeUpdate) -> Vec<CandleEvent> {
+        let mut trades: Vec<&Trade> = update.trades.iter().collect();
+        trades.sort_by_key(|t| t.timestamp);
+        let mut events = Vec::new();
+        let mut updated = false;
+        for trade in trades {
+            match self.apply_trade(trade) {
+                Some(Some(closed)) => {
+                    events.push(CandleEvent::Closed(closed));
+                    updated = true;
+                }
+                Some(None) => updated = true,
+                None => {}
+            }
+        }
+        if updated {
+            if let Some(bar) = &self.current {
+                events.push(CandleEvent::Updated(bar.clone()));
+            }
+        }
+        events
+    }
+
+    /// `None` if the trade was dropped, otherwise the bar it closed, if any.
+    fn apply_trade(&mut self, trade: &Trade) -> Option<Option<Bar>> {
+        if self.cutoff.is_some_and(|cutoff| trade.timestamp <= cutoff)
+            || !self.remember(&trade.trade_id)
+        {
+            return None;
+        }
+        let bucket = self.bucket_start(trade.timestamp);
+        let quantity = trade.quantity as u128;
+        let (buy, sell) = match trade.side {
+            Side::Sell => (quantity, 0),
+            Side::Buy => (0, quantity),
+        };
+        match &mut self.current {
+            Some(bar) if bar.timestamp == bucket => {
+                bar.high = bar.high.max(trade.price);
+                bar.low = bar.low.min(trade.price);
+                if !self
+                    .last_trade_at
+                    .is_some_and(|last| trade.timestamp < last)
+                {
+                    bar.close = trade.price;
+                }
+                bar.buy_volume += buy;
+                bar.sell_volume += sell;
+                self.last_trade_at = self.last_trade_at.max(Some(trade.timestamp));
+                Some(None)
+            }
+            Some(bar) if bar.timestamp > bucket => {
+                debug!(
+                    "candles.late_trade trade_id={} bucket={} current={}",
+                    trade.trade_id, bucket, bar.timestamp
+                );
+                None
+            }
+            _ => {
+                let opened = Bar {
+                    open: trade.price,
+                    high: trade.price,
+                    low: trade.price,
+                    close: trade.price,
+                    buy_volume: buy,
+                    sell_volume: sell,
+                    timestamp: bucket,
+                };
+                self.last_trade_at = Some(trade.timestamp);
+                let closed = self.current.replace(opened);
+                if let Some(bar) = &closed {
+                    self.history.push_back(bar.clone());
+                    self.trim_history();
+                }
+                Some(closed)
+            }
+        }
+    }
+
+    /// False if `trade_id` was already applied.
+    fn remember(&mut self, trade_id: &TradeId) -> bool {
+        if !self.seen.insert(trade_id.clone()) {
+            return false;
+        }
+        self.seen_order.push_back(trade_id.clone());
+        if self.seen_order.len() > SEEN_TRADES {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    fn trim_history(&mut self) {
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// A trade stream folded into [`CandleEvent`]s.
+///
+/// Updates for other markets are skipped, since the shared WebSocket fans
+/// trade messages out to every trade subscriber. Errors are passed through.
+pub struct Aggregator {
+    market_id: MarketId,
+    stream: TypedStream<TradeUpdate>,
+    candles: Candles,
+    pending: VecDeque<CandleEvent>,
+}
+
+impl Aggregator {
+    /// Aggregate `stream` for `market_id` into `candles`.
+    pub fn new(market_id: MarketId, candles: Candles, stream: TypedStream<TradeUpdate>) -> Self {
+        Self {
+            market_id,
+            stream,
+            candles,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The bars built so far.
+    pub fn candles(&self) -> &Candles {
+        &self.candles
+    }
+
+    pub(crate) fn candles_mut(&mut self) -> &mut Candles {
+        &mut self.candles
+    }
+}
+
+impl Stream for Aggregator {
+    type Item = Result<CandleEvent, O2Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(update))) => {
+                    if update.market_id == this.market_id {
+                        this.pending.extend(this.candles.apply(&update));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(id: &str, side: &str, price: u64, quantity: u64, at_ms: u64) -> Trade {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": id,
+            "side": side,
+            "total": "0",
+            "quantity": quantity.to_string(),
+            "price": price.to_string(),
+            "timestamp": at_ms,
+        }))
+        .unwrap()
+    }
+
+    fn update(trades: Vec<Trade>) -> TradeUpdate {
+        TradeUpdate {
+            action: "subscribe_trades".into(),
+            trades,
+            market_id: MarketId::new("0xaa"),
+            onchain_timestamp: None,
+            seen_timestamp: Timestamp::now(),
+        }
+    }
+
+    #[test]
+    fn folds_trades_into_bars_and_closes_on_the_next_bucket() {
+        let mut candles = Candles::new("1m").unwrap();
+        // Newest first, as the server sends them.
+        let events = candles.apply(&update(vec![
+            trade("0x03", "Buy", 90, 1, 50_000),
+            trade("0x02", "Sell", 120, 2, 30_000),
+            trade("0x01", "Sell", 100, 3, 10_000),
+        ]));
+        assert_eq!(events.len(), 1);
+        let bar = candles.current().unwrap();
+        assert_eq!((bar.open, bar.high, bar.low, bar.close), (100, 120, 90, 90));
+        assert_eq!((bar.buy_volume, bar.sell_volume), (5, 1));
+        assert_eq!(bar.timestamp, Timestamp::from_unix_millis(0));
+
+        // A duplicate is dropped; a trade in the next minute closes the bar.
+        assert!(candles
+            .apply(&update(vec![trade("0x03", "Buy", 90, 1, 50_000)]))
+            .is_empty());
+        let events = candles.apply(&update(vec![trade("0x04", "Buy", 95, 1, 61_000)]));
+        assert!(matches!(&events[0], CandleEvent::Closed(bar) if bar.close == 90));
+        assert!(matches!(&events[1], CandleEvent::Updated(bar) if bar.open == 95));
+        assert_eq!(candles.history().count(), 1);
+
+        // Too late for the closed bar.
+        assert!(candles
+            .apply(&update(vec![trade("0x05", "Buy", 1, 1, 59_000)]))
+            .is_empty());
+    }
+
+    #[test]
+    fn seeding_skips_trades_already_in_history() {
+        let seeded = Bar {
+            open: 100,
+            high: 110,
+            low: 100,
+            close: 105,
+            buy_volume: 7,
+            sell_volume: 0,
+            timestamp: Timestamp::from_unix_millis(120_000),
+        };
+        let older = Bar {
+            timestamp: Timestamp::from_unix_millis(60_000),
+            ..seeded.clone()
+        };
+        let mut candles = Candles::new("1m").unwrap().with_history_limit(5);
+        candles.seed(&[seeded, older], Timestamp::from_unix_millis(150_000));
+        assert_eq!(candles.history().count(), 1);
+
+        candles.apply(&update(vec![
+            trade("0x01", "Sell", 200, 9, 140_000),
+            trade("0x02", "Sell", 101, 1, 160_000),
+        ]));
+        let bar = candles.current().unwrap();
+        assert_eq!((bar.high, bar.close, bar.buy_volume), (110, 101, 8));
+
+        assert!(Candles::new("1M").is_err());
+        let weekly = Candles::new("1w").unwrap();
+        // Thursday 1970-01-08 belongs to the week starting Monday 1970-01-05.
+        assert_eq!(
+            weekly.bucket_start(Timestamp::from_unix_millis(7 * 86_400_000)),
+            Timestamp::from_unix_millis(4 * 86_400_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregator_yields_events_for_its_market_only() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut aggregator = Aggregator::new(
+            MarketId::new("0xaa"),
+            Candles::new("1s").unwrap(),
+            TypedStream::from_receiver(rx),
+        );
+        let mut other = update(vec![trade("0x01", "Buy", 1, 1, 0)]);
+        other.market_id = MarketId::new("0xbb");
+        tx.send(Ok(other)).unwrap();
+        tx.send(Ok(update(vec![trade("0x02", "Buy", 5, 1, 0)])))
+            .unwrap();
+        drop(tx);
+        let event = aggregator.next().await.unwrap().unwrap();
+        assert!(matches!(event, CandleEvent::Updated(bar) if bar.open == 5));
+        assert!(aggregator.next().await.is_none());
+    }
+}
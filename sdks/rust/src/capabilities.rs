@@ -0,0 +1,311 @@
+//! Which REST endpoints a deployment actually serves.
+//!
+//! Not every O2 deployment exposes every endpoint; analytics, aggregated and
+//! faucet routes in particular vary between networks. [`O2Api`](crate::api::O2Api)
+//! records what it learns from each response, so a missing route surfaces as
+//! [`O2Error::Unsupported`](crate::O2Error::Unsupported) rather than a generic
+//! `HttpError`. [`O2Api::capabilities`](crate::api::O2Api::capabilities) probes
+//! the read endpoints that have not been used yet and returns the full report.
+//!
+//! ```rust,ignore
+//! let caps = client.api.capabilities().await;
+//! println!("{caps}");
+//! if caps.supports(Endpoint::AggregatedTicker) == Some(false) {
+//!     // fall back to per-market tickers
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use reqwest::StatusCode;
+use serde::{Serialize, Serializer};
+
+/// A REST endpoint of the O2 API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Endpoint {
+    Markets,
+    MarketSummary,
+    MarketTicker,
+    Depth,
+    Trades,
+    TradesByAccount,
+    Bars,
+    CreateAccount,
+    Account,
+    Balance,
+    Orders,
+    Order,
+    Session,
+    SessionActions,
+    Withdraw,
+    Whitelist,
+    ReferralInfo,
+    AggregatedAssets,
+    AggregatedOrderbook,
+    AggregatedCoingeckoOrderbook,
+    AggregatedSummary,
+    AggregatedTicker,
+    AggregatedCoingeckoTickers,
+    AggregatedTrades,
+    /// The network's faucet, which lives outside the API base URL.
+    Faucet,
+}
+
+impl Endpoint {
+    /// Every endpoint, in reference order.
+    pub const ALL: [Endpoint; 25] = [
+        Endpoint::Markets,
+        Endpoint::MarketSummary,
+        Endpoint::MarketTicker,
+        Endpoint::Depth,
+        Endpoint::Trades,
+        Endpoint::TradesByAccount,
+        Endpoint::Bars,
+        Endpoint::CreateAccount,
+        Endpoint::Account,
+        Endpoint::Balance,
+        Endpoint::Orders,
+        Endpoint::Order,
+        Endpoint::Session,
+        Endpoint::SessionActions,
+        Endpoint::Withdraw,
+        Endpoint::Whitelist,
+        Endpoint::ReferralInfo,
+        Endpoint::AggregatedAssets,
+        Endpoint::AggregatedOrderbook,
+        Endpoint::AggregatedCoingeckoOrderbook,
+        Endpoint::AggregatedSummary,
+        Endpoint::AggregatedTicker,
+        Endpoint::AggregatedCoingeckoTickers,
+        Endpoint::AggregatedTrades,
+        Endpoint::Faucet,
+    ];
+
+    /// HTTP method.
+    pub fn method(self) -> &'static str {
+        match self {
+            Endpoint::CreateAccount
+            | Endpoint::SessionActions
+            | Endpoint::Withdraw
+            | Endpoint::Whitelist
+            | Endpoint::Faucet => "POST",
+            Endpoint::Session => "PUT",
+            _ => "GET",
+        }
+    }
+
+    /// Path below the API base URL. The faucet has its own URL, so its path is empty.
+    pub fn path(self) -> &'static str {
+        match self {
+            Endpoint::Markets => "/v1/markets",
+            Endpoint::MarketSummary => "/v1/markets/summary",
+            Endpoint::MarketTicker => "/v1/markets/ticker",
+            Endpoint::Depth => "/v1/depth",
+            Endpoint::Trades => "/v1/trades",
+            Endpoint::TradesByAccount => "/v1/trades_by_account",
+            Endpoint::Bars => "/v1/bars",
+            Endpoint::CreateAccount | Endpoint::Account => "/v1/accounts",
+            Endpoint::Balance => "/v1/balance",
+            Endpoint::Orders => "/v1/orders",
+            Endpoint::Order => "/v1/order",
+            Endpoint::Session => "/v1/session",
+            Endpoint::SessionActions => "/v1/session/actions",
+            Endpoint::Withdraw => "/v1/accounts/withdraw",
+            Endpoint::Whitelist => "/analytics/v1/whitelist",
+            Endpoint::ReferralInfo => "/analytics/v1/referral/code-info",
+            Endpoint::AggregatedAssets => "/v1/aggregated/assets",
+            Endpoint::AggregatedOrderbook => "/v1/aggregated/orderbook",
+            Endpoint::AggregatedCoingeckoOrderbook => "/v1/aggregated/coingecko/orderbook",
+            Endpoint::AggregatedSummary => "/v1/aggregated/summary",
+            Endpoint::AggregatedTicker => "/v1/aggregated/ticker",
+            Endpoint::AggregatedCoingeckoTickers => "/v1/aggregated/coingecko/tickers",
+            Endpoint::AggregatedTrades => "/v1/aggregated/trades",
+            Endpoint::Faucet => "",
+        }
+    }
+
+    /// Whether the endpoint only reads, and so can be probed safely.
+    pub fn is_read(self) -> bool {
+        self.method() == "GET"
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Faucet => write!(f, "POST <faucet>"),
+            _ => write!(f, "{} {}", self.method(), self.path()),
+        }
+    }
+}
+
+/// Serializes as its `Display` form, e.g. `"GET /v1/markets"`.
+impl Serialize for Endpoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// What is known about one endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    /// The server handled a request to it, successfully or not.
+    Supported,
+    /// The server has no such route.
+    Unsupported,
+    /// Not used or probed yet, or the probe could not reach the server.
+    Unknown,
+}
+
+/// Per-endpoint coverage report for the connected server.
+///
+/// `Display` renders one line per endpoint, suitable for logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    endpoints: Vec<(Endpoint, Support)>,
+}
+
+impl Capabilities {
+    /// What is known about `endpoint`.
+    pub fn get(&self, endpoint: Endpoint) -> Support {
+        self.endpoints
+            .iter()
+            .find(|(e, _)| *e == endpoint)
+            .map_or(Support::Unknown, |(_, s)| *s)
+    }
+
+    /// `Some(true)`/`Some(false)` once known, `None` while unknown.
+    pub fn supports(&self, endpoint: Endpoint) -> Option<bool> {
+        match self.get(endpoint) {
+            Support::Supported => Some(true),
+            Support::Unsupported => Some(false),
+            Support::Unknown => None,
+        }
+    }
+
+    /// Every endpoint with what is known about it, in reference order.
+    pub fn iter(&self) -> impl Iterator<Item = (Endpoint, Support)> + '_ {
+        self.endpoints.iter().copied()
+    }
+
+    /// Endpoints the server is known not to serve.
+    pub fn unsupported(&self) -> Vec<Endpoint> {
+        self.iter()
+            .filter(|(_, s)| *s == Support::Unsupported)
+            .map(|(e, _)| e)
+            .collect()
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (endpoint, support) in self.iter() {
+            let mark = match support {
+                Support::Supported => "ok",
+                Support::Unsupported => "missing",
+                Support::Unknown => "unknown",
+            };
+            writeln!(f, "{mark:<8} {endpoint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a response means the route does not exist, as opposed to an API
+/// error from a route that does. API errors carry a JSON body with a `code` or
+/// `message`; a missing route gets the server's bare 404/405 or a 501.
+pub(crate) fn is_missing_route(status: StatusCode, body: &str) -> bool {
+    match status {
+        StatusCode::NOT_IMPLEMENTED => true,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => {
+            let api_error = serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .is_some_and(|v| {
+                    v.get("code").is_some()
+                        || v.get("message").is_some()
+                        || v.get("error").is_some()
+                });
+            !api_error
+        }
+        _ => false,
+    }
+}
+
+/// What an [`O2Api`](crate::api::O2Api) and its clones have learned so far.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CapabilityCache {
+    known: Arc<Mutex<HashMap<Endpoint, bool>>>,
+}
+
+impl CapabilityCache {
+    pub(crate) fn record(&self, endpoint: Endpoint, supported: bool) {
+        self.known
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(endpoint, supported);
+    }
+
+    pub(crate) fn get(&self, endpoint: Endpoint) -> Option<bool> {
+        self.known
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .get(&endpoint)
+            .copied()
+    }
+
+    pub(crate) fn snapshot(&self) -> Capabilities {
+        let known = self.known.lock().unwrap_or_else(|p| p.into_inner());
+        let endpoints = Endpoint::ALL
+            .iter()
+            .map(|e| {
+                let support = match known.get(e) {
+                    Some(true) => Support::Supported,
+                    Some(false) => Support::Unsupported,
+                    None => Support::Unknown,
+                };
+                (*e, support)
+            })
+            .collect();
+        Capabilities { endpoints }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinguishes_missing_routes_from_api_errors() {
+        assert!(is_missing_route(StatusCode::NOT_FOUND, ""));
+        assert!(is_missing_route(StatusCode::NOT_FOUND, "Not Found"));
+        assert!(is_missing_route(StatusCode::METHOD_NOT_ALLOWED, ""));
+        assert!(is_missing_route(StatusCode::NOT_IMPLEMENTED, "{}"));
+        assert!(!is_missing_route(
+            StatusCode::NOT_FOUND,
+            r#"{"code":3000,"message":"Order not found"}"#
+        ));
+        assert!(!is_missing_route(
+            StatusCode::NOT_FOUND,
+            r#"{"message":"no such account"}"#
+        ));
+        assert!(!is_missing_route(StatusCode::BAD_REQUEST, ""));
+    }
+
+    #[test]
+    fn snapshot_covers_every_endpoint() {
+        let cache = CapabilityCache::default();
+        cache.record(Endpoint::Markets, true);
+        cache.record(Endpoint::AggregatedTicker, false);
+        let caps = cache.clone().snapshot();
+        assert_eq!(caps.iter().count(), Endpoint::ALL.len());
+        assert_eq!(caps.supports(Endpoint::Markets), Some(true));
+        assert_eq!(caps.supports(Endpoint::AggregatedTicker), Some(false));
+        assert_eq!(caps.get(Endpoint::Depth), Support::Unknown);
+        assert_eq!(caps.unsupported(), vec![Endpoint::AggregatedTicker]);
+        assert!(caps
+            .to_string()
+            .contains("missing  GET /v1/aggregated/ticker"));
+    }
+}
@@ -2,24 +2,60 @@
 ///
 /// This is the primary entry point for SDK users. It handles wallet management,
 /// account lifecycle, session management, order placement, and WebSocket streaming.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use log::debug;
+use futures_util::StreamExt;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 
+use crate::analytics::LiveReferencePrice;
 use crate::api::O2Api;
+use crate::assets::{AssetInfo, AssetRegistry};
+use crate::candles::{Aggregator, Candles};
+use crate::compatibility::{CompatibilityReport, COMPATIBILITY};
 use crate::config::{Network, NetworkConfig};
 use crate::crypto::SignableWallet;
 use crate::crypto::{
     generate_evm_keypair, generate_keypair, load_evm_wallet, load_wallet, parse_hex_32, raw_sign,
-    to_hex_string, EvmWallet, Wallet,
+    to_hex_string, verify_personal_sign, EvmWallet, PrivateKey, Wallet,
 };
+use crate::deadman::{DeadmanConfig, DeadmanSwitch};
+use crate::decimal::UnsignedDecimal;
 use crate::encoding::{
-    build_actions_signing_bytes, build_session_signing_bytes, build_withdraw_signing_bytes, CallArg,
+    build_actions_signing_bytes, build_session_signing_bytes, build_withdraw_signing_bytes,
+    CallArg, IdentityKind,
 };
 use crate::errors::O2Error;
+use crate::events::{AccountEvent, AccountStreams};
+use crate::execution::iceberg::{
+    IcebergConfig, IcebergOrder, IcebergState, Worker as IcebergWorker,
+};
+use crate::execution::oco::{OcoConfig, OcoLeg, OcoLink, OcoPair, Worker as OcoWorker};
+use crate::failover::EndpointHealth;
+use crate::feed_monitor::{FeedMonitor, FeedMonitorConfig};
+use crate::fees::{FeeOverrides, FeeRates};
+use crate::hedger::{HedgeConfig, Hedger, O2HedgeVenue};
+use crate::history::TradeHistory;
+use crate::indicators::{IndicatorStream, Indicators};
+use crate::market_maker::{QuotePlan, Quoter};
+use crate::market_watch::{InactiveMarkets, MarketWatcher, MarketWatcherConfig};
 use crate::models::*;
-use crate::websocket::{DepthPrecision, TypedStream};
+use crate::my_book::LiveMyBook;
+use crate::nonce::NonceManager;
+use crate::orderbook::{BboStream, LiveOrderBook};
+use crate::ownership::OrderOwnership;
+use crate::preflight::{check_order, OrderValidationError, ValidatedOrder};
+use crate::reconcile::{Expected, ReconcileReport};
+use crate::recorder::Recorder;
+use crate::retry::RetryPolicy;
+use crate::runtime;
+use crate::settlement::{Settlement, SettlementPolicy};
+use crate::strategy::{LiveStrategy, RunnerConfig, StrategyRunner, Worker as StrategyWorker};
+use crate::timestamp::Timestamp;
+use crate::trace::{self, debug, warn};
+use crate::triggers::{TriggerBook, TriggerConfig, TriggerEngine, Worker as TriggerWorker};
+use crate::websocket::{ActiveStream, DepthPrecision, FeedStats, TypedStream};
 
 /// Strategy for refreshing market metadata.
 #[derive(Debug, Clone, Copy)]
@@ -28,6 +64,24 @@ pub enum MetadataPolicy {
     OptimisticTtl(Duration),
     /// Always refresh metadata before reads that depend on market config.
     StrictFresh,
+    /// Serve cached metadata immediately; once it is older than `ttl`, refresh
+    /// it in the background and swap the new copy in on a later read.
+    ///
+    /// Only the first read (empty cache) waits for the network. A failed
+    /// background refresh keeps the old copy and is retried on the next read.
+    StaleWhileRevalidate(Duration),
+}
+
+/// Whether the client submits signed action batches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientMode {
+    /// Submit batches to the exchange.
+    #[default]
+    Live,
+    /// Resolve, scale, encode and sign batches, then return them in
+    /// [`SessionActionsResponse::dry_run`] instead of submitting. The session
+    /// nonce is not advanced, so consecutive dry-run batches share a nonce.
+    DryRun,
 }
 
 impl Default for MetadataPolicy {
@@ -36,6 +90,147 @@ impl Default for MetadataPolicy {
     }
 }
 
+/// Options for [`O2Client::wait_for_fill_with`].
+#[derive(Debug, Clone)]
+pub struct FillWaitOptions {
+    /// Give up after this long.
+    pub timeout: Duration,
+    /// Interval between REST checks, used alongside (or instead of) the order stream.
+    pub poll_interval: Duration,
+    /// Resolve early once this fraction of the order quantity has filled. Must
+    /// be in (0, 1].
+    pub min_fill_fraction: Option<UnsignedDecimal>,
+}
+
+impl Default for FillWaitOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(2),
+            min_fill_fraction: None,
+        }
+    }
+}
+
+/// How a wait on an order resolved.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum FillOutcome {
+    /// The order filled completely. See [`Order::status`].
+    Filled(Order),
+    /// The fill crossed `min_fill_fraction` while the order is still open.
+    PartiallyFilled(Order),
+    /// The order closed without filling completely (cancelled or expired). It
+    /// may carry a partial fill.
+    Cancelled(Order),
+    /// The timeout elapsed. Carries the last observed state, if any.
+    TimedOut(Option<Order>),
+}
+
+impl FillOutcome {
+    /// Classify an order state, returning `None` if the wait should continue.
+    fn from_order(order: &Order, min_fill_fraction: Option<&UnsignedDecimal>) -> Option<Self> {
+        match order.status() {
+            OrderStatus::Filled => return Some(Self::Filled(order.clone())),
+            OrderStatus::Cancelled | OrderStatus::Expired => {
+                return Some(Self::Cancelled(order.clone()))
+            }
+            _ => {}
+        }
+        let fraction = min_fill_fraction?;
+        let filled = order.quantity_fill.unwrap_or(0);
+        if filled > 0 && Decimal::from(filled) >= Decimal::from(order.quantity) * *fraction.inner()
+        {
+            return Some(Self::PartiallyFilled(order.clone()));
+        }
+        None
+    }
+}
+
+/// Balances and open orders of one trade account as of a single account state.
+///
+/// Returned by [`O2Client::consistent_account_view`].
+#[derive(Debug, Clone)]
+pub struct AccountView {
+    pub trade_account_id: TradeAccountId,
+    /// Account nonce, unchanged from before the first read to after the last.
+    pub nonce: u64,
+    /// On-chain modification timestamp of the account, likewise unchanged.
+    pub last_modification: u64,
+    /// Balances keyed by asset symbol, as from [`O2Client::get_balances`].
+    pub balances: HashMap<String, BalanceResponse>,
+    /// Open orders keyed by market symbol. Markets without open orders are omitted.
+    pub open_orders: HashMap<MarketSymbol, Vec<Order>>,
+    /// Rounds of reads it took to get an unchanged state (1 if the first held).
+    pub attempts: u32,
+}
+
+/// Outcome of [`O2Client::replace_order`].
+#[derive(Debug, Clone)]
+pub struct ReplacedOrder {
+    /// Response of the batch that cancelled the old order and created the new one.
+    pub response: SessionActionsResponse,
+    /// The old order as read back after the batch, showing whether it filled
+    /// before the cancel landed. `None` if it could not be read.
+    pub cancelled: Option<Order>,
+    /// Id of the replacement order. `None` if the response did not include it.
+    pub new_order_id: Option<OrderId>,
+}
+
+/// What [`O2Client::cancel_everything`] did on one of the session's markets.
+#[derive(Debug)]
+pub struct MarketCancelSummary {
+    pub contract_id: ContractId,
+    /// `None` if the contract is not in the current market list.
+    pub market: Option<MarketSymbol>,
+    /// Open orders found on the market.
+    pub open_orders: usize,
+    /// Orders cancelled by batches that succeeded.
+    pub cancelled: usize,
+    /// The first failure on this market: looking it up, listing its orders,
+    /// or a batch carrying its cancels.
+    pub error: Option<O2Error>,
+}
+
+impl MarketCancelSummary {
+    /// True if every open order found was cancelled.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.cancelled == self.open_orders
+    }
+}
+
+/// A step of [`O2Client::setup_account_with_progress`].
+///
+/// Funding and whitelisting failures do not fail the setup. A refusal is an
+/// [`O2Error::Unauthorized`] whose `reason` tells a network that gates the
+/// account (e.g. the testnet whitelist) from rejected credentials.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SetupProgress {
+    /// The owner already had a trade account.
+    AccountFound(TradeAccountId),
+    AccountCreated(TradeAccountId),
+    /// The faucet minted test assets to the account.
+    Funded,
+    /// No faucet on this network, or the account already holds assets.
+    FundingSkipped,
+    FundingFailed(O2Error),
+    Whitelisted,
+    /// The network does not require whitelisting.
+    WhitelistSkipped,
+    WhitelistFailed(O2Error),
+}
+
+/// Rounds of reads [`O2Client::consistent_account_view`] tries before giving up.
+const ACCOUNT_VIEW_ATTEMPTS: u32 = 5;
+
+/// How often [`O2Client::wait_for_tx`] asks the Fuel node, about once a block.
+const TX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most balance requests [`O2Client::get_balances`] keeps in flight at once.
+/// The API has no multi-asset balance endpoint.
+const BALANCE_FETCH_CONCURRENCY: usize = 4;
+
 /// Validate that a REST depth precision value is within the supported range (1–18).
 fn validate_depth_precision(precision: u64) -> Result<(), O2Error> {
     if !(1..=18).contains(&precision) {
@@ -48,14 +243,68 @@ fn validate_depth_precision(precision: u64) -> Result<(), O2Error> {
     Ok(())
 }
 
+/// Split `market_actions` into consecutive batches of at most `max` actions,
+/// keeping action order and regrouping each batch by market.
+pub(crate) fn split_action_batches<M: Clone>(
+    market_actions: &[(M, Vec<Action>)],
+    max: usize,
+) -> Vec<Vec<(M, Vec<Action>)>> {
+    let mut batches: Vec<Vec<(M, Vec<Action>)>> = Vec::new();
+    let mut size = max;
+    for (market, actions) in market_actions {
+        let mut fresh_market = true;
+        for action in actions {
+            if size == max {
+                batches.push(Vec::new());
+                size = 0;
+                fresh_market = true;
+            }
+            let batch = batches.last_mut().expect("batch pushed above");
+            if fresh_market {
+                batch.push((market.clone(), Vec::new()));
+                fresh_market = false;
+            }
+            batch
+                .last_mut()
+                .expect("market pushed above")
+                .1
+                .push(action.clone());
+            size += 1;
+        }
+    }
+    batches
+}
+
+/// Cache key for an owner address: lowercase, `0x`-prefixed.
+fn owner_key(owner: &OwnerAddress) -> String {
+    let hex = owner.as_str();
+    let hex = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    format!("0x{}", hex.to_ascii_lowercase())
+}
+
 /// The high-level O2 Exchange client.
 pub struct O2Client {
     pub api: O2Api,
     pub config: NetworkConfig,
     markets_cache: Option<MarketsResponse>,
     markets_cache_at: Option<Instant>,
+    markets_revalidation: Option<runtime::Task<Result<MarketsResponse, O2Error>>>,
+    /// Built from the market list cached at the paired instant.
+    asset_registry: Option<(Option<Instant>, AssetRegistry)>,
     metadata_policy: MetadataPolicy,
+    mode: ClientMode,
     ws: tokio::sync::Mutex<Option<crate::websocket::O2WebSocket>>,
+    order_ownership: OrderOwnership,
+    fee_overrides: FeeOverrides,
+    owner_accounts: HashMap<String, TradeAccountId>,
+    mid_annotations: bool,
+    inactive_markets: InactiveMarkets,
+    settlement: Settlement,
+    #[cfg(feature = "journal")]
+    journal: Option<crate::journal::Journal>,
 }
 
 /// Builder for composing a batch of actions against a single market.
@@ -199,22 +448,30 @@ impl O2Client {
         }
     }
 
-    async fn retry_whitelist_account(&self, trade_account_id: &str) -> bool {
+    /// `Ok(false)` if the network does not require whitelisting.
+    async fn retry_whitelist_account(&self, trade_account_id: &str) -> Result<bool, O2Error> {
         debug!("client.retry_whitelist_account trade_account_id={trade_account_id}");
         // Whitelist is network-gated, not hostname-gated.
         if !self.should_whitelist_account() {
             debug!("client.retry_whitelist_account skipped (non-testnet)");
-            return true;
+            return Ok(false);
         }
 
         let delays_secs = [0u64, 2, 5];
-        let mut last_error = String::new();
+        let mut attempts = 0;
+        let mut last_error = None;
+        let mut server_hint: Option<std::time::Duration> = None;
 
         for (idx, delay) in delays_secs.iter().enumerate() {
-            if *delay > 0 {
-                tokio::time::sleep(std::time::Duration::from_secs(*delay)).await;
+            // A server Retry-After hint replaces the fixed schedule for the next attempt.
+            let wait = server_hint
+                .take()
+                .unwrap_or(std::time::Duration::from_secs(*delay));
+            if idx > 0 && !wait.is_zero() {
+                runtime::sleep(wait).await;
             }
 
+            attempts = idx + 1;
             match self.api.whitelist_account(trade_account_id).await {
                 Ok(_) => {
                     debug!(
@@ -222,55 +479,71 @@ impl O2Client {
                         idx + 1,
                         trade_account_id
                     );
-                    return true;
+                    return Ok(true);
                 }
                 Err(e) => {
-                    last_error = e.to_string();
-                    if idx < delays_secs.len() - 1 {
+                    server_hint = e.retry_after();
+                    // A refusal will not change on retry, and an open circuit
+                    // is there to stop retries.
+                    let refused = matches!(
+                        e,
+                        O2Error::Unauthorized { .. } | O2Error::CircuitOpen { .. }
+                    );
+                    if idx < delays_secs.len() - 1 && !refused {
                         eprintln!(
                             "whitelist_account attempt {} failed for {}: {} (retrying)",
                             idx + 1,
                             trade_account_id,
-                            last_error
+                            e
                         );
                     }
+                    last_error = Some(e);
+                    if refused {
+                        break;
+                    }
                 }
             }
         }
 
+        let last_error = last_error.expect("at least one whitelist attempt");
         eprintln!(
             "whitelist_account failed after {} attempts for {}: {}",
-            delays_secs.len(),
-            trade_account_id,
-            last_error
+            attempts, trade_account_id, last_error
         );
-        false
+        Err(last_error)
     }
 
-    async fn retry_mint_to_contract(&self, trade_account_id: &str) -> bool {
+    /// `Ok(false)` if the network has no faucet.
+    async fn retry_mint_to_contract(&self, trade_account_id: &str) -> Result<bool, O2Error> {
         debug!("client.retry_mint_to_contract trade_account_id={trade_account_id}");
         // Faucet currently exists only on non-mainnet configs.
         if self.config.faucet_url.is_none() {
             debug!("client.retry_mint_to_contract skipped (no faucet url)");
-            return true;
+            return Ok(false);
         }
 
         // Attempt immediately, then retry with cooldown-aware waits.
         let attempts = 4usize;
         let mut last_error = String::new();
+        let mut last_err = None;
+        let mut server_hint: Option<std::time::Duration> = None;
 
         for idx in 0..attempts {
             if idx > 0 {
-                let lower = last_error.to_ascii_lowercase();
-                let wait_secs = if lower.contains("cooldown")
-                    || lower.contains("rate limit")
-                    || lower.contains("too many")
-                {
-                    65
-                } else {
-                    5
-                };
-                tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
+                // Prefer the server's Retry-After hint over guessing from the message.
+                let wait = server_hint.take().unwrap_or_else(|| {
+                    let lower = last_error.to_ascii_lowercase();
+                    let wait_secs = if lower.contains("cooldown")
+                        || lower.contains("rate limit")
+                        || lower.contains("too many")
+                    {
+                        65
+                    } else {
+                        5
+                    };
+                    std::time::Duration::from_secs(wait_secs)
+                });
+                runtime::sleep(wait).await;
             }
 
             match self.api.mint_to_contract(trade_account_id).await {
@@ -280,12 +553,13 @@ impl O2Client {
                         idx + 1,
                         trade_account_id
                     );
-                    return true;
+                    return Ok(true);
                 }
                 Ok(resp) => {
                     last_error = resp
                         .error
                         .unwrap_or_else(|| "faucet returned an unknown error".to_string());
+                    last_err = Some(O2Error::Other(format!("Faucet error: {last_error}")));
                     if idx < attempts - 1 {
                         eprintln!(
                             "mint_to_contract attempt {} returned error for {}: {} (retrying)",
@@ -296,7 +570,18 @@ impl O2Client {
                     }
                 }
                 Err(e) => {
+                    server_hint = e.retry_after();
                     last_error = e.to_string();
+                    // A refusal will not change on retry, and an open circuit
+                    // is there to stop retries.
+                    let refused = matches!(
+                        e,
+                        O2Error::Unauthorized { .. } | O2Error::CircuitOpen { .. }
+                    );
+                    last_err = Some(e);
+                    if refused {
+                        break;
+                    }
                     if idx < attempts - 1 {
                         eprintln!(
                             "mint_to_contract attempt {} failed for {}: {} (retrying)",
@@ -310,10 +595,10 @@ impl O2Client {
         }
 
         eprintln!(
-            "mint_to_contract failed after {} attempts for {}: {}",
-            attempts, trade_account_id, last_error
+            "mint_to_contract failed for {}: {}",
+            trade_account_id, last_error
         );
-        false
+        Err(last_err.expect("at least one mint attempt"))
     }
 
     async fn should_faucet_account(&mut self, trade_account_id: &str) -> bool {
@@ -345,27 +630,56 @@ impl O2Client {
 
     /// Create a new O2Client for the given network.
     pub fn new(network: Network) -> Self {
-        let config = NetworkConfig::from_network(network);
-        Self {
-            api: O2Api::new(config.clone()),
-            config,
-            markets_cache: None,
-            markets_cache_at: None,
-            metadata_policy: MetadataPolicy::default(),
-            ws: tokio::sync::Mutex::new(None),
-        }
+        Self::with_config(NetworkConfig::from_network(network))
     }
 
     /// Create a new O2Client with a custom configuration.
+    ///
+    /// REST requests use the [`O2ApiBuilder`](crate::api::O2ApiBuilder)
+    /// defaults; to change timeouts, the user agent or the HTTP client, build
+    /// an [`O2Api`] and use [`O2Client::with_api`].
     pub fn with_config(config: NetworkConfig) -> Self {
+        Self::with_api(O2Api::new(config))
+    }
+
+    /// Create a new O2Client around a configured REST client. The network
+    /// configuration is taken from `api`.
+    pub fn with_api(api: O2Api) -> Self {
         Self {
-            api: O2Api::new(config.clone()),
-            config,
+            config: api.config().clone(),
+            api,
             markets_cache: None,
             markets_cache_at: None,
+            markets_revalidation: None,
+            asset_registry: None,
             metadata_policy: MetadataPolicy::default(),
+            mode: ClientMode::default(),
             ws: tokio::sync::Mutex::new(None),
+            order_ownership: OrderOwnership::new(),
+            fee_overrides: FeeOverrides::new(),
+            owner_accounts: HashMap::new(),
+            mid_annotations: false,
+            inactive_markets: InactiveMarkets::default(),
+            settlement: Settlement::default(),
+            #[cfg(feature = "journal")]
+            journal: None,
+        }
+    }
+
+    /// A client for a task run alongside this one: the same REST client and
+    /// connection pool, metadata policy, mode and journal, starting from a
+    /// copy of this client's market list.
+    pub(crate) fn worker(&self) -> O2Client {
+        let mut client = O2Client::with_api(self.api.clone());
+        client.metadata_policy = self.metadata_policy;
+        client.mode = self.mode;
+        client.markets_cache = self.markets_cache.clone();
+        client.markets_cache_at = self.markets_cache_at;
+        #[cfg(feature = "journal")]
+        {
+            client.journal = self.journal.clone();
         }
+        client
     }
 
     /// Configure how market metadata should be refreshed.
@@ -373,6 +687,71 @@ impl O2Client {
         self.metadata_policy = policy;
     }
 
+    /// Switch between submitting action batches and only building them. See
+    /// [`ClientMode::DryRun`].
+    pub fn set_mode(&mut self, mode: ClientMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> ClientMode {
+        self.mode
+    }
+
+    /// Configure retries for REST read requests. See [`RetryPolicy`].
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.api.set_retry_policy(policy);
+    }
+
+    /// Look up each market's mid before submitting orders, so
+    /// [`ActionAnnotations`] carry distances from mid. Off by default, as it
+    /// costs a depth request per market per batch.
+    pub fn set_mid_annotations(&mut self, enabled: bool) {
+        self.mid_annotations = enabled;
+    }
+
+    /// Configure account-level fee rates. See [`crate::fees`].
+    pub fn set_fee_overrides(&mut self, overrides: FeeOverrides) {
+        self.fee_overrides = overrides;
+    }
+
+    /// Append `SettleBalance` to outgoing batches according to `policy`. See
+    /// [`crate::settlement`].
+    pub fn set_settlement_policy(&mut self, policy: SettlementPolicy) {
+        self.settlement.set_policy(policy);
+    }
+
+    /// The policy set with [`O2Client::set_settlement_policy`].
+    pub fn settlement_policy(&self) -> &SettlementPolicy {
+        self.settlement.policy()
+    }
+
+    /// Record submitted batches, fetched balances and fetched account trades
+    /// in `journal`. See [`crate::journal`].
+    #[cfg(feature = "journal")]
+    pub fn set_journal(&mut self, journal: crate::journal::Journal) {
+        self.journal = Some(journal);
+    }
+
+    #[cfg(feature = "journal")]
+    pub fn journal(&self) -> Option<&crate::journal::Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Write to the journal, if one is set. A failed write is logged rather
+    /// than failing the call being journaled.
+    #[cfg(feature = "journal")]
+    fn journal_write<T>(
+        &self,
+        what: &str,
+        write: impl FnOnce(&crate::journal::Journal) -> Result<T, O2Error>,
+    ) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = write(journal) {
+                debug!("client.journal {} error={}", what, e);
+            }
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Wallet Management
     // -----------------------------------------------------------------------
@@ -392,15 +771,26 @@ impl O2Client {
     /// Load a Fuel-native wallet from a private key hex string.
     pub fn load_wallet(&self, private_key_hex: &str) -> Result<Wallet, O2Error> {
         debug!("client.load_wallet");
-        let key = parse_hex_32(private_key_hex)?;
-        load_wallet(&key)
+        let key = PrivateKey::from_hex(private_key_hex)?;
+        load_wallet(key.expose_secret())
     }
 
     /// Load an EVM wallet from a private key hex string.
     pub fn load_evm_wallet(&self, private_key_hex: &str) -> Result<EvmWallet, O2Error> {
         debug!("client.load_evm_wallet");
-        let key = parse_hex_32(private_key_hex)?;
-        load_evm_wallet(&key)
+        let key = PrivateKey::from_hex(private_key_hex)?;
+        load_evm_wallet(key.expose_secret())
+    }
+
+    /// Compare the connected server against [`COMPATIBILITY`](crate::COMPATIBILITY).
+    ///
+    /// Probes the read endpoints not used yet (see
+    /// [`O2Api::capabilities`](crate::api::O2Api::capabilities)); write
+    /// endpoints are only verified once this client has used them.
+    pub async fn check_compatibility(&self) -> CompatibilityReport {
+        debug!("client.check_compatibility");
+        let capabilities = self.api.capabilities().await;
+        CompatibilityReport::from_capabilities(&COMPATIBILITY, &capabilities)
     }
 
     // -----------------------------------------------------------------------
@@ -411,20 +801,69 @@ impl O2Client {
     pub async fn fetch_markets(&mut self) -> Result<&MarketsResponse, O2Error> {
         debug!("client.fetch_markets");
         let resp = self.api.get_markets().await?;
+        // A background refresh started earlier must not overwrite this one.
+        if let Some(pending) = self.markets_revalidation.take() {
+            pending.abort();
+        }
         self.markets_cache = Some(resp);
         self.markets_cache_at = Some(Instant::now());
         Ok(self.markets_cache.as_ref().unwrap())
     }
 
+    /// Serve `markets` from the cache from now on, without refreshing.
+    pub(crate) fn pin_markets(&mut self, markets: MarketsResponse) {
+        self.markets_cache = Some(markets);
+        self.markets_cache_at = Some(Instant::now());
+        self.metadata_policy = MetadataPolicy::OptimisticTtl(Duration::MAX);
+    }
+
     /// Get cached markets, fetching if needed.
     async fn ensure_markets(&mut self) -> Result<&MarketsResponse, O2Error> {
+        self.adopt_revalidated_markets().await;
         if self.should_refresh_markets() {
             debug!("client.ensure_markets refreshing cache");
             self.fetch_markets().await?;
+        } else if self.should_revalidate_markets() {
+            debug!("client.ensure_markets revalidating cache in background");
+            let api = self.api.clone();
+            self.markets_revalidation =
+                Some(runtime::spawn(async move { api.get_markets().await }));
         }
         Ok(self.markets_cache.as_ref().unwrap())
     }
 
+    /// Swap in the result of a finished background refresh, if any.
+    async fn adopt_revalidated_markets(&mut self) {
+        if !self
+            .markets_revalidation
+            .as_ref()
+            .is_some_and(|pending| pending.is_finished())
+        {
+            return;
+        }
+        let Some(pending) = self.markets_revalidation.take() else {
+            return;
+        };
+        match pending.await {
+            Ok(Ok(resp)) => {
+                self.markets_cache = Some(resp);
+                self.markets_cache_at = Some(Instant::now());
+            }
+            Ok(Err(e)) => debug!(
+                "client.adopt_revalidated_markets refresh_failed error={}",
+                e
+            ),
+            Err(e) => debug!("client.adopt_revalidated_markets task_failed error={}", e),
+        }
+    }
+
+    fn is_markets_cache_expired(&self, ttl: Duration) -> bool {
+        match self.markets_cache_at {
+            None => true,
+            Some(fetched_at) => fetched_at.elapsed() >= ttl,
+        }
+    }
+
     fn should_refresh_markets(&self) -> bool {
         if self.markets_cache.is_none() {
             return true;
@@ -432,10 +871,17 @@ impl O2Client {
 
         match self.metadata_policy {
             MetadataPolicy::StrictFresh => true,
-            MetadataPolicy::OptimisticTtl(ttl) => match self.markets_cache_at {
-                None => true,
-                Some(fetched_at) => fetched_at.elapsed() >= ttl,
-            },
+            MetadataPolicy::OptimisticTtl(ttl) => self.is_markets_cache_expired(ttl),
+            MetadataPolicy::StaleWhileRevalidate(_) => false,
+        }
+    }
+
+    fn should_revalidate_markets(&self) -> bool {
+        match self.metadata_policy {
+            MetadataPolicy::StaleWhileRevalidate(ttl) => {
+                self.markets_revalidation.is_none() && self.is_markets_cache_expired(ttl)
+            }
+            _ => false,
         }
     }
 
@@ -480,8 +926,110 @@ impl O2Client {
         )))
     }
 
+    /// Every asset traded on a listed market, by symbol and asset id.
+    ///
+    /// Built from the cached market list, plus `/v1/aggregated/assets`
+    /// metadata where the deployment serves it, and rebuilt whenever the
+    /// market list is refreshed.
+    pub async fn assets(&mut self) -> Result<&AssetRegistry, O2Error> {
+        debug!("client.assets");
+        self.ensure_markets().await?;
+        let built_for = self.markets_cache_at;
+        if !self
+            .asset_registry
+            .as_ref()
+            .is_some_and(|(at, _)| *at == built_for)
+        {
+            let aggregated = match self.api.get_aggregated_assets().await {
+                Ok(aggregated) => Some(aggregated),
+                Err(e) => {
+                    debug!("client.assets aggregated_unavailable error={}", e);
+                    None
+                }
+            };
+            let markets = &self.markets_cache.as_ref().unwrap().markets;
+            self.asset_registry =
+                Some((built_for, AssetRegistry::new(markets, aggregated.as_ref())));
+        }
+        Ok(&self.asset_registry.as_ref().unwrap().1)
+    }
+
+    /// Look up an asset by symbol (e.g. `"fUSDC"`, case-insensitive) or by
+    /// `0x` asset id. Fails with [`O2Error::InvalidRequest`] if no listed
+    /// market trades it.
+    pub async fn asset(&mut self, symbol_or_id: &str) -> Result<AssetInfo, O2Error> {
+        debug!("client.asset key={}", symbol_or_id);
+        self.assets()
+            .await?
+            .get(symbol_or_id)
+            .cloned()
+            .ok_or_else(|| {
+                O2Error::InvalidRequest(format!(
+                    "No listed market trades an asset \"{symbol_or_id}\""
+                ))
+            })
+    }
+
+    /// The registry contract ids the API reports in its market list.
+    ///
+    /// The SDK does not query the registry contracts, so it cannot list every
+    /// registered trade account; to find the account an owner registered, use
+    /// [`O2Client::trade_account_for_owner`].
+    pub async fn registry_ids(&mut self) -> Result<RegistryIds, O2Error> {
+        debug!("client.registry_ids");
+        Ok(self.ensure_markets().await?.registry_ids())
+    }
+
+    /// Look up the order book the API lists for `market`.
+    pub async fn listed_book<M>(&mut self, market: M) -> Result<ListedBook, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        debug!("client.listed_book market_id={}", market.market_id);
+        let resp = self.ensure_markets().await?;
+        resp.listed_book(&market.market_id).ok_or_else(|| {
+            O2Error::MarketNotFound(format!("No market found for id: {}", market.market_id))
+        })
+    }
+
+    /// Look up which listed market, if any, trades on the `contract_id` order book.
+    pub async fn listed_book_by_contract(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> Result<Option<ListedBook>, O2Error> {
+        debug!("client.listed_book_by_contract contract_id={contract_id}");
+        let resp = self.ensure_markets().await?;
+        Ok(resp.listed_book_by_contract(contract_id))
+    }
+
+    /// The fee rates `trade_account_id` pays on `market`.
+    ///
+    /// O2 does not report per-account fees, so these are the configured
+    /// [`FeeOverrides`] where one applies and the market's defaults otherwise.
+    pub async fn my_fees<M>(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+        market: M,
+    ) -> Result<FeeRates, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let trade_account_id = trade_account_id.into_valid()?;
+        let market = self.get_market(market).await?;
+        let rates = self.fee_overrides.resolve(&trade_account_id, &market);
+        debug!(
+            "client.my_fees trade_account_id={} market_id={} maker={} taker={} source={:?}",
+            trade_account_id, market.market_id, rates.maker, rates.taker, rates.source
+        );
+        Ok(rates)
+    }
+
     /// Get the chain_id from cached markets.
     async fn get_chain_id(&mut self) -> Result<u64, O2Error> {
+        if let Some(chain_id) = self.config.chain_id {
+            return Ok(chain_id);
+        }
         let resp = self.ensure_markets().await?;
         let chain_id_hex = resp.chain_id.as_str();
         let stripped = chain_id_hex.strip_prefix("0x").unwrap_or(chain_id_hex);
@@ -500,33 +1048,65 @@ impl O2Client {
         &mut self,
         wallet: &W,
     ) -> Result<AccountResponse, O2Error> {
+        self.setup_account_with_progress(wallet, |_| {}).await
+    }
+
+    /// [`setup_account`](Self::setup_account), reporting each step to
+    /// `on_progress` as it completes.
+    pub async fn setup_account_with_progress<W, F>(
+        &mut self,
+        wallet: &W,
+        mut on_progress: F,
+    ) -> Result<AccountResponse, O2Error>
+    where
+        W: SignableWallet,
+        F: FnMut(SetupProgress),
+    {
         debug!("client.setup_account");
         let owner_hex = to_hex_string(wallet.b256_address());
 
         // 1. Check if account already exists
         let existing = self.api.get_account_by_owner(&owner_hex).await?;
-        let trade_account_id = if existing.trade_account_id.is_some() {
-            existing.trade_account_id.clone().unwrap()
+        let trade_account_id = if let Some(id) = existing.trade_account_id {
+            on_progress(SetupProgress::AccountFound(id.clone()));
+            id
         } else {
             // 2. Create account
             let created = self.api.create_account(&owner_hex).await?;
+            on_progress(SetupProgress::AccountCreated(
+                created.trade_account_id.clone(),
+            ));
             created.trade_account_id
         };
 
         // 3. Mint via faucet only when the account currently has no balances.
         if self.should_faucet_account(trade_account_id.as_str()).await {
-            let _ = self.retry_mint_to_contract(trade_account_id.as_str()).await;
+            on_progress(
+                match self.retry_mint_to_contract(trade_account_id.as_str()).await {
+                    Ok(true) => SetupProgress::Funded,
+                    Ok(false) => SetupProgress::FundingSkipped,
+                    Err(e) => SetupProgress::FundingFailed(e),
+                },
+            );
         } else {
             debug!(
                 "client.setup_account skipping_faucet trade_account_id={} (non-zero balance detected)",
                 trade_account_id
             );
+            on_progress(SetupProgress::FundingSkipped);
         }
 
         // 4. Whitelist account (testnet-only, non-fatal; retry for transient failures)
-        let _ = self
-            .retry_whitelist_account(trade_account_id.as_str())
-            .await;
+        on_progress(
+            match self
+                .retry_whitelist_account(trade_account_id.as_str())
+                .await
+            {
+                Ok(true) => SetupProgress::Whitelisted,
+                Ok(false) => SetupProgress::WhitelistSkipped,
+                Err(e) => SetupProgress::WhitelistFailed(e),
+            },
+        );
 
         // 5. Return current account state
         self.api.get_account_by_id(trade_account_id.as_str()).await
@@ -560,6 +1140,56 @@ impl O2Client {
         market_names: &[S],
         ttl: Duration,
     ) -> Result<Session, O2Error> {
+        let expiry = Self::session_expiry(ttl)?;
+        self.create_session_until(owner, market_names, expiry).await
+    }
+
+    /// Create a trading session that expires at an absolute UNIX timestamp.
+    ///
+    /// Works with both [`Wallet`] (Fuel-native) and [`EvmWallet`].
+    pub async fn create_session_until<W: SignableWallet, S: AsRef<str>>(
+        &mut self,
+        owner: &W,
+        market_names: &[S],
+        expiry_unix_secs: u64,
+    ) -> Result<Session, O2Error> {
+        debug!(
+            "client.create_session_until markets={} expiry_unix_secs={}",
+            market_names.len(),
+            expiry_unix_secs
+        );
+        let mut sessions = self
+            .create_sessions_until(owner, &[market_names], expiry_unix_secs)
+            .await?;
+        Ok(sessions.remove(0))
+    }
+
+    /// Create one session per entry of `market_sets`, all expiring after `ttl`.
+    ///
+    /// Market metadata, the chain ID and the owner's account and nonce are
+    /// looked up once, and every session is signed before any is submitted.
+    /// Each session consumes the next account nonce, so they are submitted in
+    /// nonce order, and every returned session continues from the nonce left
+    /// after the last one. Fails on the first rejected session; the sessions
+    /// before it were created but are not returned.
+    ///
+    /// Works with both [`Wallet`] (Fuel-native) and [`EvmWallet`].
+    pub async fn create_sessions<W, V, S>(
+        &mut self,
+        owner: &W,
+        market_sets: &[V],
+        ttl: Duration,
+    ) -> Result<Vec<Session>, O2Error>
+    where
+        W: SignableWallet,
+        V: AsRef<[S]>,
+        S: AsRef<str>,
+    {
+        let expiry = Self::session_expiry(ttl)?;
+        self.create_sessions_until(owner, market_sets, expiry).await
+    }
+
+    fn session_expiry(ttl: Duration) -> Result<u64, O2Error> {
         let ttl_secs = ttl.as_secs();
         if ttl_secs == 0 {
             return Err(O2Error::InvalidSession(
@@ -571,36 +1201,39 @@ impl O2Client {
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let expiry = now
-            .checked_add(ttl_secs)
-            .ok_or_else(|| O2Error::InvalidSession("Session TTL overflow".into()))?;
-
-        self.create_session_until(owner, market_names, expiry).await
+        now.checked_add(ttl_secs)
+            .ok_or_else(|| O2Error::InvalidSession("Session TTL overflow".into()))
     }
 
-    /// Create a trading session that expires at an absolute UNIX timestamp.
-    ///
-    /// Works with both [`Wallet`] (Fuel-native) and [`EvmWallet`].
-    pub async fn create_session_until<W: SignableWallet, S: AsRef<str>>(
+    async fn create_sessions_until<W, V, S>(
         &mut self,
         owner: &W,
-        market_names: &[S],
+        market_sets: &[V],
         expiry_unix_secs: u64,
-    ) -> Result<Session, O2Error> {
+    ) -> Result<Vec<Session>, O2Error>
+    where
+        W: SignableWallet,
+        V: AsRef<[S]>,
+        S: AsRef<str>,
+    {
         debug!(
-            "client.create_session_until markets={} expiry_unix_secs={}",
-            market_names.len(),
+            "client.create_sessions sessions={} expiry_unix_secs={}",
+            market_sets.len(),
             expiry_unix_secs
         );
+        if market_sets.is_empty() {
+            return Ok(Vec::new());
+        }
         let owner_hex = to_hex_string(owner.b256_address());
 
         // Resolve market names to contract_ids
-        let mut contract_ids_hex = Vec::new();
-        let mut contract_ids_bytes = Vec::new();
-        for name in market_names {
-            let market = self.get_market(name.as_ref()).await?;
-            contract_ids_hex.push(market.contract_id.clone());
-            contract_ids_bytes.push(parse_hex_32(&market.contract_id)?);
+        let mut contract_id_sets = Vec::with_capacity(market_sets.len());
+        for names in market_sets {
+            let mut contract_ids = Vec::new();
+            for name in names.as_ref() {
+                contract_ids.push(self.get_market(name.as_ref()).await?.contract_id);
+            }
+            contract_id_sets.push(contract_ids);
         }
 
         let chain_id = self.get_chain_id().await?;
@@ -612,48 +1245,65 @@ impl O2Client {
             .clone()
             .ok_or_else(|| O2Error::AccountNotFound("No trade_account_id found".into()))?;
 
-        let nonce = Self::parse_account_nonce(
+        let first_nonce = Self::parse_account_nonce(
             account.trade_account.as_ref().map(|ta| ta.nonce),
             "create_session account response",
         )?;
+        let next_nonce = first_nonce + market_sets.len() as u64;
+
+        // Sign every session up front, one nonce each
+        let mut signed = Vec::with_capacity(contract_id_sets.len());
+        for (nonce, contract_ids) in (first_nonce..).zip(contract_id_sets) {
+            let contract_ids_bytes = contract_ids
+                .iter()
+                .map(|id| parse_hex_32(id))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Generate session keypair
+            let session_wallet = generate_keypair()?;
+
+            // Build signing bytes
+            let signing_bytes = build_session_signing_bytes(
+                nonce,
+                chain_id,
+                &session_wallet.b256_address,
+                &contract_ids_bytes,
+                expiry_unix_secs,
+            );
 
-        // Generate session keypair
-        let session_wallet = generate_keypair()?;
-
-        // Build signing bytes
-        let signing_bytes = build_session_signing_bytes(
-            nonce,
-            chain_id,
-            &session_wallet.b256_address,
-            &contract_ids_bytes,
-            expiry_unix_secs,
-        );
-
-        // Sign with owner wallet (dispatches to Fuel or EVM personal_sign)
-        let signature = owner.personal_sign(&signing_bytes)?;
-        let sig_hex = to_hex_string(&signature);
-
-        // Submit session
-        let request = SessionRequest {
-            contract_id: trade_account_id.clone(),
-            session_id: Identity::Address(to_hex_string(&session_wallet.b256_address)),
-            signature: Signature::Secp256k1(sig_hex),
-            contract_ids: contract_ids_hex.clone(),
-            nonce: nonce.to_string(),
-            expiry: expiry_unix_secs.to_string(),
-        };
-
-        let _resp = self.api.create_session(&owner_hex, &request).await?;
+            // Sign with owner wallet (dispatches to Fuel or EVM personal_sign)
+            let signature = owner.personal_sign(&signing_bytes)?;
+            verify_personal_sign(&signing_bytes, &signature, owner.b256_address())?;
+            let sig_hex = to_hex_string(&signature);
+
+            let request = SessionRequest {
+                contract_id: trade_account_id.clone(),
+                session_id: Identity::Address(to_hex_string(&session_wallet.b256_address)),
+                signature: Signature::Secp256k1(sig_hex),
+                contract_ids: contract_ids.clone(),
+                nonce: nonce.to_string(),
+                expiry: expiry_unix_secs.to_string(),
+            };
+            let session = Session {
+                owner_address: *owner.b256_address(),
+                session_private_key: session_wallet.private_key.clone(),
+                session_address: session_wallet.b256_address,
+                trade_account_id: trade_account_id.clone(),
+                contract_ids,
+                inactive_contract_ids: Vec::new(),
+                expiry: expiry_unix_secs,
+                nonce: next_nonce,
+            };
+            signed.push((request, session));
+        }
 
-        Ok(Session {
-            owner_address: *owner.b256_address(),
-            session_private_key: session_wallet.private_key,
-            session_address: session_wallet.b256_address,
-            trade_account_id,
-            contract_ids: contract_ids_hex,
-            expiry: expiry_unix_secs,
-            nonce: nonce + 1,
-        })
+        // Submit in nonce order; a later nonce is rejected until the earlier one lands
+        let mut sessions = Vec::with_capacity(signed.len());
+        for (request, session) in signed {
+            self.api.create_session(&owner_hex, &request).await?;
+            sessions.push(session);
+        }
+        Ok(sessions)
     }
 
     // -----------------------------------------------------------------------
@@ -716,22 +1366,7 @@ impl O2Client {
             market_name, settle_first, collect_orders
         );
         let market = self.get_market(&market_name).await?;
-
-        let price = match price.try_into()? {
-            OrderPriceInput::Unchecked(v) => v,
-            OrderPriceInput::Checked(v) => {
-                market.validate_price_binding(&v)?;
-                v.value()
-            }
-        };
-
-        let quantity = match quantity.try_into()? {
-            OrderQuantityInput::Unchecked(v) => v,
-            OrderQuantityInput::Checked(v) => {
-                market.validate_quantity_binding(&v)?;
-                v.value()
-            }
-        };
+        let (price, quantity) = Self::resolve_order_inputs(&market, price, quantity)?;
 
         let mut actions = Vec::new();
         if settle_first {
@@ -747,11 +1382,283 @@ impl O2Client {
             .await
     }
 
-    /// Cancel an order by order_id.
-    pub async fn cancel_order<M>(
+    /// Check an order against the market's rules without submitting it.
+    ///
+    /// Runs the scaling, [`Market::adjust_quantity`], minimum order and dust
+    /// checks that submission would, and reports every violation with
+    /// amounts in human units. No nonce is used. See
+    /// [`preflight`](crate::preflight).
+    pub async fn validate_order<M, P, Q>(
         &mut self,
-        session: &mut Session,
-        order_id: &OrderId,
+        market_name: M,
+        side: Side,
+        price: P,
+        quantity: Q,
+        order_type: OrderType,
+    ) -> Result<ValidatedOrder, OrderValidationError>
+    where
+        M: IntoMarketSymbol,
+        P: TryInto<OrderPriceInput, Error = O2Error>,
+        Q: TryInto<OrderQuantityInput, Error = O2Error>,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        debug!(
+            "client.validate_order market={} side={}",
+            market_name,
+            side.as_str()
+        );
+        let market = self.get_market(&market_name).await?;
+        check_order(&market, side, price, quantity, order_type)
+    }
+
+    /// Place an order sized by quote notional, e.g. "buy with 100 USDC".
+    ///
+    /// The price is the best level from the order book: the opposite side for
+    /// orders that take liquidity, the order's own side for `PostOnly`. The
+    /// base quantity is `quote_amount / price`, rounded down to the base
+    /// precision and through [`Market::adjust_quantity`]; see
+    /// [`Market::quantity_for_quote`]. Fails with
+    /// [`O2Error::InvalidOrderParams`] if that side of the book is empty or the
+    /// result is below the market's `min_order`. Settles first and collects the
+    /// created order.
+    pub async fn create_order_by_quote_amount<M>(
+        &mut self,
+        session: &mut Session,
+        market_name: M,
+        side: Side,
+        quote_amount: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        debug!(
+            "client.create_order_by_quote_amount market={} side={} quote_amount={}",
+            market_name,
+            side.as_str(),
+            quote_amount
+        );
+        let market = self.get_market(&market_name).await?;
+        let depth = self.get_depth(&market_name, 1, Some(1)).await?;
+        // Takers size against the opposite side, PostOnly against its own.
+        let post_only = matches!(order_type, OrderType::PostOnly);
+        let from_bids = (side == Side::Sell) != post_only;
+        let level = if from_bids {
+            depth.bids.first()
+        } else {
+            depth.asks.first()
+        };
+        let price = level.map(|l| l.price).ok_or_else(|| {
+            O2Error::InvalidOrderParams(format!(
+                "No {} in the book to size a {} order against",
+                if from_bids { "bids" } else { "asks" },
+                side.as_str()
+            ))
+        })?;
+
+        let quote_factor = 10u64
+            .checked_pow(market.quote.decimals)
+            .map(Decimal::from)
+            .ok_or_else(|| O2Error::Other("Invalid quote.decimals".into()))?;
+        let quote_chain = quote_amount
+            .inner()
+            .checked_mul(quote_factor)
+            .and_then(|v| v.floor().to_u64())
+            .ok_or_else(|| {
+                O2Error::InvalidOrderParams(format!(
+                    "Quote amount {quote_amount} exceeds u64 range"
+                ))
+            })?;
+        let quantity = market.quantity_for_quote(price, quote_chain)?;
+
+        self.create_order(
+            session,
+            market.symbol_pair(),
+            side,
+            market.format_price(price)?,
+            market.format_quantity(quantity)?,
+            order_type,
+            true,
+            true,
+        )
+        .await
+    }
+
+    /// Place a market order that fills at most `max_slippage_bps` away from
+    /// the current top of book.
+    ///
+    /// Reads the best ask (for a buy) or best bid (for a sell) and submits a
+    /// `BoundedMarket` order whose bounds come from
+    /// [`Market::slippage_bounds`]. The order's price is the worst acceptable
+    /// price. Fails with [`O2Error::InvalidOrderParams`] if that side of the
+    /// book is empty. Settles first and collects the created order.
+    pub async fn market_order_with_slippage<M, Q>(
+        &mut self,
+        session: &mut Session,
+        market_name: M,
+        side: Side,
+        quantity: Q,
+        max_slippage_bps: u32,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol,
+        Q: TryInto<OrderQuantityInput, Error = O2Error>,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        debug!(
+            "client.market_order_with_slippage market={} side={} max_slippage_bps={}",
+            market_name,
+            side.as_str(),
+            max_slippage_bps
+        );
+        let market = self.get_market(&market_name).await?;
+        let depth = self.get_depth(&market_name, 1, Some(1)).await?;
+        let level = match side {
+            Side::Buy => depth.asks.first(),
+            Side::Sell => depth.bids.first(),
+        };
+        let reference = level.map(|l| l.price).ok_or_else(|| {
+            O2Error::InvalidOrderParams(format!(
+                "No liquidity in the book for a market {} order",
+                side.as_str()
+            ))
+        })?;
+        let (min_price, max_price) = market.slippage_bounds(side, reference, max_slippage_bps)?;
+        let worst = match side {
+            Side::Buy => max_price,
+            Side::Sell => min_price,
+        };
+        let order_type = OrderType::BoundedMarket {
+            max_price: market.format_price(max_price)?,
+            min_price: market.format_price(min_price)?,
+        };
+
+        self.create_order(
+            session,
+            market.symbol_pair(),
+            side,
+            market.format_price(worst)?,
+            quantity,
+            order_type,
+            true,
+            true,
+        )
+        .await
+    }
+
+    fn resolve_order_inputs<P, Q>(
+        market: &Market,
+        price: P,
+        quantity: Q,
+    ) -> Result<(UnsignedDecimal, UnsignedDecimal), O2Error>
+    where
+        P: TryInto<OrderPriceInput, Error = O2Error>,
+        Q: TryInto<OrderQuantityInput, Error = O2Error>,
+    {
+        let price = match price.try_into()? {
+            OrderPriceInput::Unchecked(v) => v,
+            OrderPriceInput::Checked(v) => {
+                market.validate_price_binding(&v)?;
+                v.value()
+            }
+        };
+
+        let quantity = match quantity.try_into()? {
+            OrderQuantityInput::Unchecked(v) => v,
+            OrderQuantityInput::Checked(v) => {
+                market.validate_quantity_binding(&v)?;
+                v.value()
+            }
+        };
+        Ok((price, quantity))
+    }
+
+    /// Replace an order: cancel `old_order_id` and place a new order in one
+    /// signed batch.
+    ///
+    /// Both actions land in the same transaction, so either the old order is
+    /// cancelled and the new one placed, or neither happens. The new order
+    /// keeps the old one's side. `new_price` and `new_quantity` accept the
+    /// same inputs as [`O2Client::create_order`]; the quantity is not reduced
+    /// by anything the old order filled.
+    pub async fn replace_order<M, P, Q>(
+        &mut self,
+        session: &mut Session,
+        market_name: M,
+        old_order_id: &OrderId,
+        new_price: P,
+        new_quantity: Q,
+        order_type: OrderType,
+    ) -> Result<ReplacedOrder, O2Error>
+    where
+        M: IntoMarketSymbol,
+        P: TryInto<OrderPriceInput, Error = O2Error>,
+        Q: TryInto<OrderQuantityInput, Error = O2Error>,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        debug!(
+            "client.replace_order market={} order_id={}",
+            market_name, old_order_id
+        );
+        if old_order_id.as_str().trim().is_empty() {
+            return Err(O2Error::InvalidOrderParams(
+                "Order to replace has an empty id".into(),
+            ));
+        }
+        let market = self.get_market(&market_name).await?;
+        let (price, quantity) = Self::resolve_order_inputs(&market, new_price, new_quantity)?;
+        // The new order takes the old one's side.
+        let side = self.get_order(&market_name, old_order_id).await?.side;
+
+        let actions = vec![
+            Action::CancelOrder {
+                order_id: old_order_id.clone(),
+            },
+            Action::CreateOrder {
+                side,
+                price,
+                quantity,
+                order_type,
+            },
+        ];
+        let response = self
+            .batch_actions(session, &market_name, actions, true)
+            .await?;
+        if !response.is_success() {
+            return Ok(ReplacedOrder {
+                response,
+                cancelled: None,
+                new_order_id: None,
+            });
+        }
+
+        let new_order_id = response
+            .orders
+            .iter()
+            .flatten()
+            .map(|o| &o.order_id)
+            .find(|id| *id != old_order_id)
+            .cloned();
+        let cancelled = match self.get_order(&market_name, old_order_id).await {
+            Ok(order) => Some(order),
+            Err(e) => {
+                debug!("client.replace_order refresh_failed error={}", e);
+                None
+            }
+        };
+        Ok(ReplacedOrder {
+            response,
+            cancelled,
+            new_order_id,
+        })
+    }
+
+    /// Cancel an order by order_id.
+    pub async fn cancel_order<M>(
+        &mut self,
+        session: &mut Session,
+        order_id: &OrderId,
         market_name: M,
     ) -> Result<SessionActionsResponse, O2Error>
     where
@@ -774,6 +1681,10 @@ impl O2Client {
     }
 
     /// Cancel all open orders for a market.
+    ///
+    /// Reads every page of the market's open orders and cancels them in
+    /// batches of at most [`MAX_ACTIONS_PER_BATCH`], then reads them again
+    /// until no order it has not cancelled yet is listed.
     pub async fn cancel_all_orders<M>(
         &mut self,
         session: &mut Session,
@@ -786,37 +1697,164 @@ impl O2Client {
         debug!("client.cancel_all_orders market={}", market_name);
         Self::check_session_expiry(session)?;
         let market = self.get_market(&market_name).await?;
-        let orders_resp = self
-            .api
-            .get_orders(
-                market.market_id.as_str(),
-                session.trade_account_id.as_str(),
-                "desc",
-                200,
-                Some(true),
-                None,
-                None,
-            )
-            .await?;
-
-        let orders = orders_resp.orders;
         let mut results = Vec::new();
+        // A cancelled order can stay listed for a moment; it is not cancelled
+        // twice.
+        let mut cancelled = HashSet::new();
+        loop {
+            let orders = self
+                .api
+                .all_open_orders(&market.market_id, &session.trade_account_id)
+                .await?;
+            let open: Vec<OrderId> = orders
+                .into_iter()
+                .map(|order| order.order_id)
+                .filter(|order_id| cancelled.insert(order_id.clone()))
+                .collect();
+            if open.is_empty() {
+                return Ok(results);
+            }
 
-        // Cancel up to 5 orders per batch
-        for chunk in orders.chunks(5) {
-            let actions = Self::build_cancel_actions(chunk.iter().map(|order| &order.order_id));
+            for chunk in open.chunks(MAX_ACTIONS_PER_BATCH) {
+                let actions = Self::build_cancel_actions(chunk);
 
-            if actions.is_empty() {
-                continue;
+                if actions.is_empty() {
+                    continue;
+                }
+
+                let resp = self
+                    .batch_actions(session, &market_name, actions, false)
+                    .await?;
+                results.push(resp);
             }
+        }
+    }
 
-            let resp = self
-                .batch_actions(session, &market_name, actions, false)
-                .await?;
-            results.push(resp);
+    /// Cancel the open orders on every market of `session.contract_ids`.
+    ///
+    /// Lists every page of each market's open orders, then cancels them all in
+    /// cross-market batches of at most [`MAX_ACTIONS_PER_BATCH`] via
+    /// [`batch_actions_multi`](Self::batch_actions_multi), and lists again
+    /// until no order it has not cancelled yet is listed. A market that cannot
+    /// be listed, or a batch that fails, is recorded in its markets' summaries
+    /// and the rest carry on. Delisted markets are reported with
+    /// [`O2Error::MarketInactive`] and not submitted. Returns one summary per
+    /// contract, in session order.
+    pub async fn cancel_everything(
+        &mut self,
+        session: &mut Session,
+    ) -> Result<Vec<MarketCancelSummary>, O2Error> {
+        debug!(
+            "client.cancel_everything markets={}",
+            session.contract_ids.len()
+        );
+        Self::check_session_expiry(session)?;
+        let markets = self.ensure_markets().await?.markets.clone();
+        let mut summaries = Vec::with_capacity(session.contract_ids.len());
+        // The session's listed markets: summary index, market id and symbol.
+        let mut listed: Vec<(usize, MarketId, MarketSymbol)> = Vec::new();
+        for contract_id in &session.contract_ids {
+            let mut summary = MarketCancelSummary {
+                contract_id: contract_id.clone(),
+                market: None,
+                open_orders: 0,
+                cancelled: 0,
+                error: None,
+            };
+            let Some(market) = markets.iter().find(|m| &m.contract_id == contract_id) else {
+                summary.error = Some(O2Error::MarketNotFound(format!(
+                    "No listed market has contract {}",
+                    contract_id
+                )));
+                summaries.push(summary);
+                continue;
+            };
+            let symbol = market.symbol_pair();
+            summary.market = Some(symbol.clone());
+            if self.inactive_markets.by_symbol(&symbol).is_some() {
+                summary.error = Some(O2Error::MarketInactive(format!(
+                    "{} ({}) has been delisted",
+                    symbol, market.market_id
+                )));
+                summaries.push(summary);
+                continue;
+            }
+            listed.push((summaries.len(), market.market_id.clone(), symbol));
+            summaries.push(summary);
         }
 
-        Ok(results)
+        // A cancelled order can stay listed for a moment; it is not cancelled
+        // twice.
+        let mut cancelled = HashSet::new();
+        loop {
+            let mut market_actions: Vec<(MarketSymbol, Vec<Action>)> = Vec::new();
+            for (index, market_id, symbol) in &listed {
+                let summary = &mut summaries[*index];
+                if summary.error.is_some() {
+                    continue;
+                }
+                match self
+                    .api
+                    .all_open_orders(market_id, &session.trade_account_id)
+                    .await
+                {
+                    Ok(orders) => {
+                        let open: Vec<OrderId> = orders
+                            .into_iter()
+                            .map(|order| order.order_id)
+                            .filter(|order_id| cancelled.insert(order_id.clone()))
+                            .collect();
+                        let actions = Self::build_cancel_actions(&open);
+                        summary.open_orders += actions.len();
+                        if !actions.is_empty() {
+                            market_actions.push((symbol.clone(), actions));
+                        }
+                    }
+                    Err(e) => summary.error = Some(e),
+                }
+            }
+            if market_actions.is_empty() {
+                return Ok(summaries);
+            }
+
+            for batch in split_action_batches(&market_actions, MAX_ACTIONS_PER_BATCH) {
+                // The error goes to the batch's first market; the others get its text.
+                let (mut error, failure) =
+                    match self.batch_actions_multi(session, &batch, false).await {
+                        Ok(resp) if resp.is_success() => (None, None),
+                        Ok(resp) => {
+                            let message = format!(
+                                "Cancel batch rejected: {}",
+                                resp.message.as_deref().unwrap_or("no message")
+                            );
+                            (Some(O2Error::Other(message.clone())), Some(message))
+                        }
+                        Err(e) => {
+                            let message = format!("Cancel batch failed: {e}");
+                            (Some(e), Some(message))
+                        }
+                    };
+                for (symbol, actions) in &batch {
+                    let Some(summary) = summaries
+                        .iter_mut()
+                        .find(|s| s.market.as_ref() == Some(symbol))
+                    else {
+                        continue;
+                    };
+                    match &failure {
+                        None => summary.cancelled += actions.len(),
+                        Some(message) if summary.error.is_none() => {
+                            summary.error = Some(
+                                error
+                                    .take()
+                                    .unwrap_or_else(|| O2Error::Other(message.clone())),
+                            );
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
     }
 
     fn build_cancel_actions<'a, I>(order_ids: I) -> Vec<Action>
@@ -861,7 +1899,63 @@ impl O2Client {
             .await
     }
 
+    /// Submit a list of typed actions of any length, across one or more
+    /// markets, as consecutive batches of at most [`MAX_ACTIONS_PER_BATCH`].
+    ///
+    /// Actions keep their order, and a batch may span a market boundary. Each
+    /// batch is signed with the next session nonce. Submission stops at the
+    /// first batch that fails or is rejected, which is reported in
+    /// [`BatchedActionsResponse::error`] alongside the responses of the batches
+    /// before it.
+    pub async fn batch_actions_chunked<M>(
+        &mut self,
+        session: &mut Session,
+        market_actions: &[(M, Vec<Action>)],
+        collect_orders: bool,
+    ) -> BatchedActionsResponse
+    where
+        M: IntoMarketSymbol + Clone,
+    {
+        let batches = split_action_batches(market_actions, MAX_ACTIONS_PER_BATCH);
+        debug!(
+            "client.batch_actions_chunked markets={} batches={}",
+            market_actions.len(),
+            batches.len()
+        );
+        let mut result = BatchedActionsResponse {
+            batches: batches.len(),
+            responses: Vec::with_capacity(batches.len()),
+            error: None,
+        };
+        for batch in batches {
+            match self
+                .batch_actions_multi(session, &batch, collect_orders)
+                .await
+            {
+                Ok(resp) if resp.is_success() => result.responses.push(resp),
+                Ok(resp) => {
+                    result.error = Some(O2Error::Other(format!(
+                        "Batch {} of {} rejected: {}",
+                        result.responses.len() + 1,
+                        result.batches,
+                        resp.message.as_deref().unwrap_or("no message")
+                    )));
+                    break;
+                }
+                Err(e) => {
+                    result.error = Some(e);
+                    break;
+                }
+            }
+        }
+        result
+    }
+
     /// Submit a batch of typed actions across one or more markets.
+    ///
+    /// Fails with [`O2Error::InvalidRequest`] before signing if there are more
+    /// than [`MAX_ACTIONS_PER_BATCH`] actions; use
+    /// [`O2Client::batch_actions_chunked`] for longer lists.
     pub async fn batch_actions_multi<M>(
         &mut self,
         session: &mut Session,
@@ -881,7 +1975,55 @@ impl O2Client {
             total_actions,
             collect_orders
         );
+        if total_actions > MAX_ACTIONS_PER_BATCH {
+            return Err(O2Error::InvalidRequest(format!(
+                "{} actions exceed the limit of {} per batch; use batch_actions_chunked",
+                total_actions, MAX_ACTIONS_PER_BATCH
+            )));
+        }
+        let submit = self.submit_batch(session, market_actions, collect_orders);
+        #[cfg(feature = "alloc-audit")]
+        let submit = crate::alloc_audit::track_future("batch_actions", submit);
+        submit.await
+    }
+
+    /// Encode, sign and submit one batch that is within the size limit.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "client.submit_batch", level = "debug", skip_all, fields(trade_account_id = %session.trade_account_id, nonce = session.nonce, market_id = tracing::field::Empty, tx_id = tracing::field::Empty))
+    )]
+    async fn submit_batch<M>(
+        &mut self,
+        session: &mut Session,
+        market_actions: &[(M, Vec<Action>)],
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol + Clone,
+    {
         Self::check_session_expiry(session)?;
+        for (market_name, _) in market_actions {
+            let market_name = market_name.clone().into_market_symbol()?;
+            if let Some(market) = self.inactive_markets.by_symbol(&market_name) {
+                session.mark_inactive(&market.contract_id);
+                return Err(O2Error::MarketInactive(format!(
+                    "{} ({}) has been delisted",
+                    market_name, market.market_id
+                )));
+            }
+        }
+        if self.settlement.needs_feed(&session.trade_account_id) {
+            let identity = Identity::ContractId(session.trade_account_id.as_str().to_string());
+            match self.stream_balances(&[identity]).await {
+                Ok(stream) => self
+                    .settlement
+                    .follow(session.trade_account_id.clone(), stream),
+                Err(e) => debug!("client.submit_batch settlement_feed_error error={}", e),
+            }
+        }
+        let mut annotations = ActionAnnotations::default();
+        annotations.record_check(PreTradeCheck::SessionExpiry);
+        annotations.record_check(PreTradeCheck::BatchSize);
 
         // Extract accounts_registry_id in a block so the borrow on self ends
         let accounts_registry_id = {
@@ -891,19 +2033,76 @@ impl O2Client {
 
         let mut all_calls: Vec<CallArg> = Vec::new();
         let mut all_market_actions: Vec<MarketActions> = Vec::new();
+        let server_now = self.api.server_time();
+        let mut batch_size: usize = market_actions.iter().map(|(_, a)| a.len()).sum();
+        // Per market: the market, its action count and whether it settles.
+        let mut submitted: Vec<(Market, usize, bool)> = Vec::new();
 
         for (market_name, actions) in market_actions {
             let market_name = market_name.clone().into_market_symbol()?;
             let market = self.get_market(&market_name).await?;
+            if market_actions.len() == 1 {
+                trace::record("market_id", &market.market_id);
+            }
             let mut actions_json: Vec<serde_json::Value> = Vec::new();
-
-            for action in actions {
+            let creates_orders = actions
+                .iter()
+                .any(|a| matches!(a, Action::CreateOrder { .. }));
+            let mid = if self.mid_annotations && creates_orders {
+                self.mid_price(&market_name).await
+            } else {
+                None
+            };
+
+            let mut settles = actions.iter().any(|a| matches!(a, Action::SettleBalance));
+            let auto_settle = !settles
+                && batch_size < MAX_ACTIONS_PER_BATCH
+                && self.settlement.is_due(&market, actions.len());
+            if auto_settle {
+                debug!("client.submit_batch auto_settle market={}", market_name);
+                batch_size += 1;
+                settles = true;
+            }
+            submitted.push((market.clone(), actions.len(), settles));
+
+            for action in actions
+                .iter()
+                .chain(auto_settle.then_some(&Action::SettleBalance))
+            {
+                if let Action::CreateOrder {
+                    order_type: order_type @ OrderType::Limit { .. },
+                    ..
+                } = action
+                {
+                    // Only the good-til-time constructors reject implausible
+                    // timestamps; orders built by hand are submitted as before.
+                    match order_type.validate_timestamp(server_now) {
+                        Ok(()) => annotations.record_check(PreTradeCheck::LimitExpiry),
+                        Err(e) => warn!("client.submit_batch limit_timestamp {}", e),
+                    }
+                }
                 let (call, json) = crate::encoding::action_to_call(
                     action,
                     &market,
                     session.trade_account_id.as_str(),
                     accounts_registry_id.as_ref(),
                 )?;
+                if let Action::CreateOrder {
+                    side,
+                    price,
+                    quantity,
+                    ..
+                } = action
+                {
+                    // Mirrors the scaling in `action_to_call`, which has
+                    // already validated these.
+                    let price = market.scale_price(price)?;
+                    let quantity =
+                        market.adjust_quantity(price, market.scale_quantity(quantity)?)?;
+                    annotations.record_check(PreTradeCheck::MinOrder);
+                    annotations.record_check(PreTradeCheck::FractionalPrice);
+                    annotations.record_order(&market, *side, price, quantity, mid)?;
+                }
                 all_calls.push(call);
                 actions_json.push(json);
             }
@@ -916,7 +2115,7 @@ impl O2Client {
 
         // Sign, submit, manage nonce
         let signing_bytes = build_actions_signing_bytes(session.nonce, &all_calls);
-        let signature = raw_sign(&session.session_private_key, &signing_bytes)?;
+        let signature = raw_sign(session.session_private_key.expose_secret(), &signing_bytes)?;
         let sig_hex = to_hex_string(&signature);
         let owner_hex = to_hex_string(&session.owner_address);
 
@@ -930,9 +2129,54 @@ impl O2Client {
             variable_outputs: None,
         };
 
-        match self.api.submit_actions(&owner_hex, &request).await {
-            Ok(resp) => {
+        if self.mode == ClientMode::DryRun {
+            debug!(
+                "client.submit_batch dry_run nonce={} markets={}",
+                request.nonce,
+                request.actions.len()
+            );
+            return Ok(SessionActionsResponse {
+                tx_id: None,
+                orders: None,
+                code: None,
+                message: None,
+                reason: None,
+                receipts: None,
+                annotations: Some(annotations),
+                dry_run: Some(DryRunBatch {
+                    request,
+                    signing_bytes,
+                    owner_id: owner_hex,
+                }),
+            });
+        }
+
+        let result = self.api.submit_actions(&owner_hex, &request).await;
+        #[cfg(feature = "journal")]
+        self.journal_write("submission", |j| {
+            j.record_submission(&request, result.as_ref())
+        });
+        match result {
+            Ok(mut resp) => {
                 session.nonce += 1;
+                if let Some(tx_id) = &resp.tx_id {
+                    trace::record("tx_id", tx_id);
+                }
+                if resp.is_success() {
+                    for (market, actions, settled) in &submitted {
+                        self.settlement.record(market, *actions, *settled);
+                    }
+                }
+                resp.annotations = Some(annotations);
+                if let Some(orders) = &resp.orders {
+                    self.order_ownership.record(
+                        &session.session_address,
+                        orders.iter().map(|o| o.order_id.clone()),
+                    );
+                    for order in orders {
+                        self.order_ownership.observe(order);
+                    }
+                }
                 Ok(resp)
             }
             Err(e) => {
@@ -943,6 +2187,25 @@ impl O2Client {
         }
     }
 
+    /// Midpoint of the best bid and ask, or `None` if either side is empty
+    /// or the book cannot be read.
+    async fn mid_price(&mut self, market_name: &MarketSymbol) -> Option<u64> {
+        match self.get_depth(market_name, 1, Some(1)).await {
+            Ok(depth) => {
+                let bid = depth.bids.first()?.price as u128;
+                let ask = depth.asks.first()?.price as u128;
+                Some(((bid + ask) / 2) as u64)
+            }
+            Err(e) => {
+                debug!(
+                    "client.mid_price market={} unavailable error={}",
+                    market_name, e
+                );
+                None
+            }
+        }
+    }
+
     /// Settle balance for a market.
     pub async fn settle_balance<M>(
         &mut self,
@@ -959,30 +2222,483 @@ impl O2Client {
     }
 
     // -----------------------------------------------------------------------
-    // Market Data
+    // Dead-man's Switch
     // -----------------------------------------------------------------------
 
-    /// Get order book depth.
-    ///
-    /// # Arguments
-    /// * `market_name` - Market pair string (e.g. `"ETH/USDC"`) or market ID.
-    /// * `precision` - Price grouping level, from `1` (most precise) to `18`
-    ///   (most grouped). At level 1, prices are at or near their exact values.
-    ///   Higher levels round prices into larger buckets — useful for a visual
-    ///   depth chart but too coarse for trading. Same scale as
-    ///   [`stream_depth`][O2Client::stream_depth].
-    /// * `limit` - Maximum number of price levels per side.
+    /// Arm a client-side dead-man's switch for `markets`.
     ///
-    /// # Errors
-    /// Returns [`O2Error::InvalidRequest`] if `precision` is outside 1–18.
-    pub async fn get_depth<M>(
-        &mut self,
-        market_name: M,
-        precision: u64,
-        limit: Option<usize>,
-    ) -> Result<DepthSnapshot, O2Error>
+    /// The switch runs on its own client built from this client's config and
+    /// cancels with `emergency_session`, which should be a separate session from
+    /// the one the strategy trades with. If the shared WebSocket is connected and
+    /// `config.cancel_on_ws_disconnect` is set, losing it for longer than
+    /// `config.disconnect_grace` also fires the switch.
+    /// See [`crate::deadman`].
+    pub async fn arm_deadman_switch<M>(
+        &self,
+        emergency_session: Session,
+        markets: &[M],
+        config: DeadmanConfig,
+    ) -> Result<DeadmanSwitch, O2Error>
     where
-        M: IntoMarketSymbol,
+        M: IntoMarketSymbol + Clone,
+    {
+        let markets = markets
+            .iter()
+            .cloned()
+            .map(IntoMarketSymbol::into_market_symbol)
+            .collect::<Result<Vec<_>, _>>()?;
+        debug!(
+            "client.arm_deadman_switch markets={} timeout_ms={}",
+            markets.len(),
+            config.timeout.as_millis()
+        );
+        let lifecycle = if config.cancel_on_ws_disconnect {
+            let guard = self.ws.lock().await;
+            guard
+                .as_ref()
+                .filter(|ws| !ws.is_terminated())
+                .map(|ws| ws.subscribe_lifecycle())
+        } else {
+            None
+        };
+        let mut dedicated = O2Client::with_api(self.api.clone());
+        dedicated.set_metadata_policy(self.metadata_policy);
+        Ok(DeadmanSwitch::arm(
+            dedicated,
+            emergency_session,
+            markets,
+            config,
+            lifecycle,
+        ))
+    }
+
+    /// Watch the market list for delistings. See [`crate::market_watch`].
+    ///
+    /// Markets the watcher finds delisted are inactive on this client: batches
+    /// touching them fail with [`O2Error::MarketInactive`] before signing.
+    pub async fn watch_markets(
+        &mut self,
+        config: MarketWatcherConfig,
+    ) -> Result<MarketWatcher, O2Error> {
+        debug!(
+            "client.watch_markets poll_interval_ms={} cancel_orders={}",
+            config.poll_interval.as_millis(),
+            config.cancel_orders.is_some()
+        );
+        let known = self.ensure_markets().await?.clone();
+        Ok(MarketWatcher::spawn(
+            self.api.clone(),
+            known,
+            self.inactive_markets.clone(),
+            config,
+        ))
+    }
+
+    /// Markets a [`MarketWatcher`] started from this client found delisted.
+    pub fn inactive_markets(&self) -> Vec<Market> {
+        self.inactive_markets.list()
+    }
+
+    // -----------------------------------------------------------------------
+    // Hedging
+    // -----------------------------------------------------------------------
+
+    /// Hedge fills on `source_market` with taker orders on `hedge_market`.
+    ///
+    /// Fills are read from `session`'s account order stream, and hedges are
+    /// submitted on a dedicated client through `nonces`, which the quoting side
+    /// should share so the two do not collide on nonces. For an external hedge
+    /// venue, use [`Hedger::spawn`] directly. See [`crate::hedger`].
+    pub async fn spawn_hedger<M, N>(
+        &mut self,
+        session: &Session,
+        nonces: &NonceManager,
+        source_market: M,
+        hedge_market: N,
+        config: HedgeConfig,
+    ) -> Result<Hedger, O2Error>
+    where
+        M: IntoMarketSymbol,
+        N: IntoMarketSymbol,
+    {
+        let source_market = source_market.into_market_symbol()?;
+        let hedge_market = hedge_market.into_market_symbol()?;
+        debug!(
+            "client.spawn_hedger source={} hedge={}",
+            source_market, hedge_market
+        );
+        let source = self.get_market(&source_market).await?;
+        // Fail early on an unknown hedge market rather than on the first hedge.
+        self.get_market(&hedge_market).await?;
+        let orders = self
+            .stream_orders(&[Identity::ContractId(
+                session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let mut dedicated = O2Client::with_api(self.api.clone());
+        dedicated.set_metadata_policy(self.metadata_policy);
+        let venue = O2HedgeVenue::new(dedicated, session.clone(), hedge_market)
+            .with_nonce_manager(nonces.clone());
+        Ok(Hedger::spawn(&source, orders, venue, config))
+    }
+
+    // -----------------------------------------------------------------------
+    // One-Cancels-Other
+    // -----------------------------------------------------------------------
+
+    /// Create two linked orders where the first to execute cancels the other.
+    ///
+    /// Resting legs go out in one transaction through `nonces`, which the rest
+    /// of the strategy should share. At most one leg can be a stop
+    /// ([`OcoLeg::stop`]), held by this process until a trade crosses it. The
+    /// link is then enforced on a dedicated client; see
+    /// [`crate::execution::oco`]. The returned [`OcoPair`] reports the link's
+    /// status and can cancel the pair.
+    pub async fn create_oco<M>(
+        &mut self,
+        session: &Session,
+        nonces: &NonceManager,
+        market: M,
+        legs: [OcoLeg; 2],
+        config: OcoConfig,
+    ) -> Result<OcoPair, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        let symbol = market.symbol_pair();
+        debug!("client.create_oco market={}", symbol);
+        let mut actions = Vec::new();
+        let mut stop = None;
+        for leg in &legs {
+            match leg.to_stop(&symbol) {
+                Some(_) if stop.is_some() => {
+                    return Err(O2Error::InvalidOrderParams(
+                        "At most one OCO leg can be a stop".into(),
+                    ))
+                }
+                Some(trigger) => {
+                    trigger.validate()?;
+                    stop = Some(trigger);
+                }
+                None => actions.push(leg.to_action()?),
+            }
+        }
+        // Subscribe first so no execution or trade between placement and spawn
+        // is missed.
+        let orders = self
+            .stream_orders(&[Identity::ContractId(
+                session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let trades = match stop {
+            Some(_) => Some(self.stream_trades(&market.market_id).await?),
+            None => None,
+        };
+        let expected = actions.len();
+        let mut session = session.clone();
+        let resp = nonces
+            .batch_actions(self, &mut session, &symbol, actions, true)
+            .await?;
+        if !resp.is_success() {
+            return Err(O2Error::Other(format!(
+                "OCO placement rejected: {}",
+                resp.message.as_deref().unwrap_or("no message")
+            )));
+        }
+        let placed = resp.orders.unwrap_or_default();
+        let mut link = match (stop, placed.as_slice()) {
+            (Some(stop), [leg]) => OcoLink::with_stop(symbol, leg.order_id.clone(), stop),
+            (None, [first, second]) => {
+                OcoLink::new(symbol, [first.order_id.clone(), second.order_id.clone()])
+            }
+            _ => {
+                return Err(O2Error::Other(format!(
+                    "OCO placement returned {} orders, expected {expected}",
+                    placed.len()
+                )))
+            }
+        };
+        for order in &placed {
+            link.observe(order);
+        }
+        Ok(self.spawn_oco(session, nonces, market, link, orders, trades, config))
+    }
+
+    /// Resume enforcing a link persisted by [`O2Client::create_oco`] with
+    /// [`OcoConfig::persist_path`] set, e.g. after a restart.
+    ///
+    /// Resting legs are re-read over REST before the task starts, so
+    /// executions while nothing was watching are acted on immediately. A stop
+    /// left firing by the restart is settled from its resting leg; see
+    /// [`crate::execution::oco`].
+    pub async fn resume_oco(
+        &mut self,
+        session: &Session,
+        nonces: &NonceManager,
+        path: impl AsRef<std::path::Path>,
+        mut config: OcoConfig,
+    ) -> Result<OcoPair, O2Error> {
+        let path = path.as_ref();
+        let mut link = OcoLink::load(path)?;
+        debug!(
+            "client.resume_oco market={} status={:?}",
+            link.market, link.status
+        );
+        let market = self.get_market(&link.market).await?;
+        let orders = self
+            .stream_orders(&[Identity::ContractId(
+                session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let trades = match &link.stop {
+            Some(stop) if stop.is_pending() => Some(self.stream_trades(&market.market_id).await?),
+            _ => None,
+        };
+        for id in link.legs.clone() {
+            let order = self.get_order(&link.market, &id).await?;
+            link.observe(&order);
+        }
+        link.resolve_stop(false, "interrupted while firing".into());
+        config.persist_path = Some(path.to_path_buf());
+        Ok(self.spawn_oco(
+            session.clone(),
+            nonces,
+            market,
+            link,
+            orders,
+            trades,
+            config,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_oco(
+        &self,
+        session: Session,
+        nonces: &NonceManager,
+        market: Market,
+        link: OcoLink,
+        orders: TypedStream<OrderUpdate>,
+        trades: Option<TypedStream<TradeUpdate>>,
+        config: OcoConfig,
+    ) -> OcoPair {
+        let mut client = O2Client::with_api(self.api.clone());
+        client.set_metadata_policy(self.metadata_policy);
+        let worker = OcoWorker::new(client, session, nonces.clone(), market, link);
+        OcoPair::spawn(worker, orders, trades, config)
+    }
+
+    // -----------------------------------------------------------------------
+    // Triggers
+    // -----------------------------------------------------------------------
+
+    /// Start firing the pending triggers of `book` as prices cross them.
+    ///
+    /// Orders go out through `nonces`, which the rest of the strategy should
+    /// share. Prices are watched on a dedicated client; see
+    /// [`crate::triggers`]. To pick up a book persisted through
+    /// [`TriggerConfig::persist_path`], pass [`TriggerBook::load`] of that
+    /// file.
+    #[doc(alias = "stop_loss")]
+    pub async fn start_triggers(
+        &self,
+        session: &Session,
+        nonces: &NonceManager,
+        book: TriggerBook,
+        config: TriggerConfig,
+    ) -> Result<TriggerEngine, O2Error> {
+        debug!("client.start_triggers pending={}", book.pending().count());
+        let mut client = O2Client::with_api(self.api.clone());
+        client.set_metadata_policy(self.metadata_policy);
+        let worker = TriggerWorker::new(client, session.clone(), nonces.clone(), book);
+        TriggerEngine::start(worker, config).await
+    }
+
+    // -----------------------------------------------------------------------
+    // Strategies
+    // -----------------------------------------------------------------------
+
+    /// Run `strategy` against the configured markets in the background.
+    ///
+    /// Its intents go out through `nonces`, which the rest of the bot should
+    /// share; streams are read on a dedicated client. See
+    /// [`crate::strategy`].
+    pub async fn run_strategy<S>(
+        &self,
+        session: &Session,
+        nonces: &NonceManager,
+        strategy: S,
+        config: RunnerConfig,
+    ) -> Result<StrategyRunner, O2Error>
+    where
+        S: LiveStrategy + 'static,
+    {
+        debug!("client.run_strategy markets={}", config.markets.len());
+        let mut client = O2Client::with_api(self.api.clone());
+        client.set_metadata_policy(self.metadata_policy);
+        let worker = StrategyWorker::new(client, session.clone(), nonces.clone());
+        StrategyRunner::start(worker, strategy, config).await
+    }
+
+    // -----------------------------------------------------------------------
+    // Quoting
+    // -----------------------------------------------------------------------
+
+    /// Submit `plan` in the batches from [`Quoter::batches`], in order.
+    ///
+    /// Stops at the first batch that fails or is rejected; earlier batches
+    /// stay applied, and the next plan against a refreshed [`MyBook`](crate::MyBook)
+    /// picks up from there. See [`crate::market_maker`].
+    pub async fn requote(
+        &mut self,
+        session: &mut Session,
+        quoter: &Quoter,
+        plan: &QuotePlan,
+    ) -> Result<Vec<SessionActionsResponse>, O2Error> {
+        self.requote_inner(session, quoter, plan, None).await
+    }
+
+    /// [`requote`](Self::requote), checking `book` before each batch.
+    ///
+    /// If `plan` was stamped with [`QuotePlan::at_book_version`] and `book` has
+    /// since applied more than [`QuoterConfig::max_book_lag`](crate::QuoterConfig::max_book_lag)
+    /// updates, fails with [`O2Error::StaleBook`] without sending the batch;
+    /// plan again from the current book and retry.
+    pub async fn requote_against(
+        &mut self,
+        session: &mut Session,
+        quoter: &Quoter,
+        plan: &QuotePlan,
+        book: &LiveOrderBook,
+    ) -> Result<Vec<SessionActionsResponse>, O2Error> {
+        self.requote_inner(session, quoter, plan, Some(book)).await
+    }
+
+    async fn requote_inner(
+        &mut self,
+        session: &mut Session,
+        quoter: &Quoter,
+        plan: &QuotePlan,
+        book: Option<&LiveOrderBook>,
+    ) -> Result<Vec<SessionActionsResponse>, O2Error> {
+        let symbol = quoter.market().symbol_pair();
+        debug!(
+            "client.requote market={} cancel={} create={} unchanged={}",
+            symbol,
+            plan.cancel.len(),
+            plan.create.len(),
+            plan.unchanged
+        );
+        let mut responses = Vec::new();
+        for actions in quoter.batches(plan)? {
+            if let Some(book) = book {
+                quoter.check_book_lag(plan, book.version())?;
+            }
+            let resp = self.batch_actions(session, &symbol, actions, true).await?;
+            if !resp.is_success() {
+                return Err(O2Error::Other(format!(
+                    "Requote batch rejected: {}",
+                    resp.message.as_deref().unwrap_or("no message")
+                )));
+            }
+            responses.push(resp);
+        }
+        Ok(responses)
+    }
+
+    // -----------------------------------------------------------------------
+    // Iceberg Orders
+    // -----------------------------------------------------------------------
+
+    /// Work `config.total_quantity` with at most `config.visible_quantity`
+    /// resting at a time.
+    ///
+    /// The first clip is placed before this returns, through `nonces`, which
+    /// the rest of the strategy should share. Later clips are posted on a
+    /// dedicated client as each one fills; see [`crate::execution::iceberg`].
+    pub async fn start_iceberg<M>(
+        &mut self,
+        session: &Session,
+        nonces: &NonceManager,
+        market: M,
+        config: IcebergConfig,
+    ) -> Result<IcebergOrder, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        let symbol = market.symbol_pair();
+        debug!(
+            "client.start_iceberg market={} side={} total={} visible={}",
+            symbol,
+            config.side.as_str(),
+            config.total_quantity,
+            config.visible_quantity
+        );
+        if matches!(
+            config.order_type,
+            OrderType::Market | OrderType::FillOrKill | OrderType::BoundedMarket { .. }
+        ) {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Iceberg clips must rest on the book; {:?} orders do not",
+                config.order_type
+            )));
+        }
+        let total_quantity = market.scale_quantity(&config.total_quantity)?;
+        let visible_quantity = market.scale_quantity(&config.visible_quantity)?;
+        if visible_quantity == 0 || visible_quantity > total_quantity {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Iceberg visible quantity {} must be positive and at most the total {}",
+                config.visible_quantity, config.total_quantity
+            )));
+        }
+        // Subscribe first so no fill between placement and spawn is missed.
+        let orders = self
+            .stream_orders(&[Identity::ContractId(
+                session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let mut client = O2Client::with_api(self.api.clone());
+        client.set_metadata_policy(self.metadata_policy);
+        let mut worker = IcebergWorker {
+            client,
+            session: session.clone(),
+            nonces: nonces.clone(),
+            state: IcebergState::new(symbol, config.side, total_quantity),
+            market,
+            config,
+            visible_quantity,
+        };
+        worker.place_next().await?;
+        Ok(IcebergOrder::spawn(worker, orders))
+    }
+
+    // -----------------------------------------------------------------------
+    // Market Data
+    // -----------------------------------------------------------------------
+
+    /// Get order book depth.
+    ///
+    /// # Arguments
+    /// * `market_name` - Market pair string (e.g. `"ETH/USDC"`) or market ID.
+    /// * `precision` - Price grouping level, from `1` (most precise) to `18`
+    ///   (most grouped). At level 1, prices are at or near their exact values.
+    ///   Higher levels round prices into larger buckets — useful for a visual
+    ///   depth chart but too coarse for trading. Same scale as
+    ///   [`stream_depth`][O2Client::stream_depth].
+    /// * `limit` - Maximum number of price levels per side.
+    ///
+    /// # Errors
+    /// Returns [`O2Error::InvalidRequest`] if `precision` is outside 1–18.
+    pub async fn get_depth<M>(
+        &mut self,
+        market_name: M,
+        precision: u64,
+        limit: Option<usize>,
+    ) -> Result<DepthSnapshot, O2Error>
+    where
+        M: IntoMarketSymbol,
     {
         validate_depth_precision(precision)?;
         let wire_precision = 10u64.pow(precision as u32);
@@ -1048,7 +2764,8 @@ impl O2Client {
             market_name, account, count
         );
         let market = self.get_market(&market_name).await?;
-        self.api
+        let resp = self
+            .api
             .get_trades_by_account(
                 market.market_id.as_str(),
                 account.as_str(),
@@ -1057,7 +2774,63 @@ impl O2Client {
                 start_timestamp,
                 start_trade_id.map(|t| t.as_str()),
             )
-            .await
+            .await?;
+        #[cfg(feature = "journal")]
+        self.journal_write("fills", |j| {
+            j.record_fills(&account, &market.market_id, &resp.trades)
+        });
+        Ok(resp)
+    }
+
+    /// Stream a market's trades from `from` onward, oldest first.
+    ///
+    /// Pages through `GET /v1/trades` as the stream is consumed and ends once it
+    /// catches up with the latest trade. See [`TradeHistory`].
+    pub async fn trade_history<M>(
+        &mut self,
+        market_name: M,
+        from: Timestamp,
+    ) -> Result<TradeHistory, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        debug!("client.trade_history market={} from={}", market_name, from);
+        let market = self.get_market(&market_name).await?;
+        Ok(TradeHistory::new(
+            self.api.clone(),
+            market.market_id,
+            None,
+            from,
+        ))
+    }
+
+    /// Stream an account's trades on a market from `from` onward, oldest first.
+    ///
+    /// Like [`trade_history`](Self::trade_history), but pages
+    /// `GET /v1/trades_by_account`, so each trade carries `trader_side`.
+    pub async fn account_trade_history<M>(
+        &mut self,
+        market_name: M,
+        account: impl IntoValidId<TradeAccountId>,
+        from: Timestamp,
+    ) -> Result<TradeHistory, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let account = account.into_valid()?;
+        let market_name = market_name.into_market_symbol()?;
+        debug!(
+            "client.account_trade_history market={} account={} from={}",
+            market_name, account, from
+        );
+        let market = self.get_market(&market_name).await?;
+        Ok(TradeHistory::new(
+            self.api.clone(),
+            market.market_id,
+            Some(account),
+            from,
+        ))
     }
 
     /// Get OHLCV bars.
@@ -1107,40 +2880,75 @@ impl O2Client {
     // -----------------------------------------------------------------------
 
     /// Get balances for a trading account, keyed by asset symbol.
+    ///
+    /// The API serves one asset per request, so this fetches every listed
+    /// asset with a few requests in flight at a time. To read a single
+    /// asset, [`O2Client::get_balance_for_symbol`] makes one request.
     pub async fn get_balances(
         &mut self,
         trade_account_id: impl IntoValidId<TradeAccountId>,
     ) -> Result<HashMap<String, BalanceResponse>, O2Error> {
+        use futures_util::{StreamExt, TryStreamExt};
+
         let trade_account_id = trade_account_id.into_valid()?;
         debug!("client.get_balances trade_account_id={}", trade_account_id);
-        let markets = self.get_markets().await?;
-        let mut balances = HashMap::new();
-        let mut seen_assets = std::collections::HashSet::new();
-
-        for market in &markets {
-            for (symbol, asset_id) in [
-                (&market.base.symbol, &market.base.asset),
-                (&market.quote.symbol, &market.quote.asset),
-            ] {
-                if seen_assets.insert(asset_id.clone()) {
-                    let bal = self
-                        .api
-                        .get_balance(asset_id.as_str(), Some(trade_account_id.as_str()), None)
-                        .await
-                        .map_err(|e| {
-                            O2Error::Other(format!(
-                                "Failed to fetch balance for asset {} ({}) on account {}: {}",
-                                symbol, asset_id, trade_account_id, e
-                            ))
-                        })?;
-                    balances.insert(symbol.clone(), bal);
-                }
-            }
-        }
-
+        let assets: Vec<AssetInfo> = self.assets().await?.iter().cloned().collect();
+
+        let api = &self.api;
+        let account = &trade_account_id;
+        let balances = futures_util::stream::iter(assets)
+            .map(|asset| async move {
+                let balance = Self::fetch_balance(api, &asset, account).await?;
+                Ok::<_, O2Error>((asset.symbol.clone(), balance))
+            })
+            .buffer_unordered(BALANCE_FETCH_CONCURRENCY)
+            .try_collect()
+            .await?;
+        #[cfg(feature = "journal")]
+        self.journal_write("balances", |j| {
+            j.record_balances(&trade_account_id, &balances)
+        });
         Ok(balances)
     }
 
+    /// Get a trading account's balance of one asset, by symbol (e.g.
+    /// `"fUSDC"`, case-insensitive). Fails with [`O2Error::InvalidRequest`]
+    /// if no listed market trades the asset.
+    pub async fn get_balance_for_symbol(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+        symbol: &str,
+    ) -> Result<BalanceResponse, O2Error> {
+        let trade_account_id = trade_account_id.into_valid()?;
+        debug!(
+            "client.get_balance_for_symbol trade_account_id={} symbol={}",
+            trade_account_id, symbol
+        );
+        let asset = self.asset(symbol).await?;
+        Self::fetch_balance(&self.api, &asset, &trade_account_id).await
+    }
+
+    async fn fetch_balance(
+        api: &O2Api,
+        asset: &AssetInfo,
+        trade_account_id: &TradeAccountId,
+    ) -> Result<BalanceResponse, O2Error> {
+        api.get_balance(
+            asset.asset_id.as_str(),
+            Some(trade_account_id.as_str()),
+            None,
+        )
+        .await
+        .map_err(|e| match e {
+            // Kept typed so the `*_by_owner` reads can drop a stale owner.
+            O2Error::AccountNotFound(_) => e,
+            e => O2Error::Other(format!(
+                "Failed to fetch balance for asset {} ({}) on account {}: {}",
+                asset.symbol, asset.asset_id, trade_account_id, e
+            )),
+        })
+    }
+
     /// Get orders for a trading account in a market.
     ///
     /// Use `start_timestamp` + `start_order_id` for cursor pagination
@@ -1177,6 +2985,107 @@ impl O2Client {
             .await
     }
 
+    /// Resolve the trade account owned by `owner`.
+    ///
+    /// The mapping is cached per client. Reads made through the `*_by_owner`
+    /// methods drop the cached entry when they fail with
+    /// [`O2Error::AccountNotFound`], so the next call looks it up again.
+    pub async fn trade_account_for_owner(
+        &mut self,
+        owner: impl IntoValidId<OwnerAddress>,
+    ) -> Result<TradeAccountId, O2Error> {
+        let owner = owner.into_valid()?;
+        let key = owner_key(&owner);
+        if let Some(trade_account_id) = self.owner_accounts.get(&key) {
+            return Ok(trade_account_id.clone());
+        }
+        debug!("client.trade_account_for_owner resolving owner={}", key);
+        let account = self.api.get_account_by_owner(&key).await?;
+        let trade_account_id = account.trade_account_id.ok_or_else(|| {
+            O2Error::AccountNotFound(format!("No trade account found for owner {}", key))
+        })?;
+        self.owner_accounts.insert(key, trade_account_id.clone());
+        Ok(trade_account_id)
+    }
+
+    fn forget_owner_on_missing<T>(
+        &mut self,
+        owner: &OwnerAddress,
+        result: Result<T, O2Error>,
+    ) -> Result<T, O2Error> {
+        if let Err(O2Error::AccountNotFound(_)) = &result {
+            let key = owner_key(owner);
+            debug!("client.forget_owner_on_missing owner={}", key);
+            self.owner_accounts.remove(&key);
+        }
+        result
+    }
+
+    /// [`O2Client::get_balances`] for the trade account owned by `owner`.
+    pub async fn get_balances_by_owner(
+        &mut self,
+        owner: impl IntoValidId<OwnerAddress>,
+    ) -> Result<HashMap<String, BalanceResponse>, O2Error> {
+        let owner = owner.into_valid()?;
+        let trade_account_id = self.trade_account_for_owner(&owner).await?;
+        let result = self.get_balances(&trade_account_id).await;
+        self.forget_owner_on_missing(&owner, result)
+    }
+
+    /// [`O2Client::get_orders`] for the trade account owned by `owner`.
+    pub async fn get_orders_by_owner<M>(
+        &mut self,
+        market_name: M,
+        owner: impl IntoValidId<OwnerAddress>,
+        is_open: Option<bool>,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_order_id: Option<&OrderId>,
+    ) -> Result<OrdersResponse, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let owner = owner.into_valid()?;
+        let trade_account_id = self.trade_account_for_owner(&owner).await?;
+        let result = self
+            .get_orders(
+                market_name,
+                &trade_account_id,
+                is_open,
+                count,
+                start_timestamp,
+                start_order_id,
+            )
+            .await;
+        self.forget_owner_on_missing(&owner, result)
+    }
+
+    /// [`O2Client::get_account_trades`] for the trade account owned by `owner`.
+    pub async fn get_account_trades_by_owner<M>(
+        &mut self,
+        market_name: M,
+        owner: impl IntoValidId<OwnerAddress>,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_trade_id: Option<&TradeId>,
+    ) -> Result<TradesResponse, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let owner = owner.into_valid()?;
+        let trade_account_id = self.trade_account_for_owner(&owner).await?;
+        let result = self
+            .get_account_trades(
+                market_name,
+                &trade_account_id,
+                count,
+                start_timestamp,
+                start_trade_id,
+            )
+            .await;
+        self.forget_owner_on_missing(&owner, result)
+    }
+
     /// Get a single order.
     pub async fn get_order<M>(
         &mut self,
@@ -1198,6 +3107,297 @@ impl O2Client {
             .await
     }
 
+    /// Read balances and all open orders (across all markets) that belong to
+    /// the same account state.
+    ///
+    /// Separate balance and order reads can straddle a trade and show, say, the
+    /// funds of an order both locked and free. This reads the account's nonce
+    /// and on-chain modification timestamp before and after the other reads and
+    /// starts over if either moved, up to 5 times. The returned view carries the
+    /// nonce it corresponds to.
+    ///
+    /// Fails with [`O2Error::Other`] if the account changed during every round.
+    pub async fn consistent_account_view(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+    ) -> Result<AccountView, O2Error> {
+        let trade_account_id = trade_account_id.into_valid()?;
+        debug!(
+            "client.consistent_account_view trade_account_id={}",
+            trade_account_id
+        );
+        let markets = self.get_markets().await?;
+        for attempt in 1..=ACCOUNT_VIEW_ATTEMPTS {
+            let before = self.account_state_token(&trade_account_id).await?;
+            let balances = self.get_balances(trade_account_id.as_str()).await?;
+            let mut open_orders = HashMap::new();
+            for market in &markets {
+                let orders = self
+                    .api
+                    .all_open_orders(&market.market_id, &trade_account_id)
+                    .await?;
+                if !orders.is_empty() {
+                    open_orders.insert(market.symbol_pair(), orders);
+                }
+            }
+            let after = self.account_state_token(&trade_account_id).await?;
+            if before == after {
+                let (nonce, last_modification) = after;
+                return Ok(AccountView {
+                    trade_account_id,
+                    nonce,
+                    last_modification,
+                    balances,
+                    open_orders,
+                    attempts: attempt,
+                });
+            }
+            debug!(
+                "client.consistent_account_view changed attempt={} before={:?} after={:?}",
+                attempt, before, after
+            );
+        }
+        Err(O2Error::Other(format!(
+            "Account {} changed during each of {} attempts to read a consistent view",
+            trade_account_id, ACCOUNT_VIEW_ATTEMPTS
+        )))
+    }
+
+    /// `(nonce, last_modification)` of a trade account.
+    async fn account_state_token(
+        &self,
+        trade_account_id: &TradeAccountId,
+    ) -> Result<(u64, u64), O2Error> {
+        let account = self
+            .api
+            .get_account_by_id(trade_account_id.as_str())
+            .await?;
+        let trade_account = account.trade_account.ok_or_else(|| {
+            O2Error::AccountNotFound(format!("No trade account {}", trade_account_id))
+        })?;
+        Ok((trade_account.nonce, trade_account.last_modification))
+    }
+
+    /// Wait until an order is filled or cancelled, or `timeout` elapses.
+    ///
+    /// Shorthand for [`O2Client::wait_for_fill_with`] with default options.
+    pub async fn wait_for_fill<M>(
+        &mut self,
+        session: &Session,
+        market_name: M,
+        order_id: &OrderId,
+        timeout: Duration,
+    ) -> Result<FillOutcome, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let options = FillWaitOptions {
+            timeout,
+            ..FillWaitOptions::default()
+        };
+        self.wait_for_fill_with(session, market_name, order_id, options)
+            .await
+    }
+
+    /// Wait until an order is filled, partially filled past a threshold, or cancelled.
+    ///
+    /// Watches the session's order stream and polls the REST order endpoint every
+    /// `poll_interval` as a fallback, so a missed or delayed WebSocket message only
+    /// delays the result. If the stream cannot be opened, polling alone is used.
+    /// A timeout is reported as [`FillOutcome::TimedOut`], not an error, and a
+    /// `min_fill_fraction` outside (0, 1] as [`O2Error::InvalidRequest`].
+    pub async fn wait_for_fill_with<M>(
+        &mut self,
+        session: &Session,
+        market_name: M,
+        order_id: &OrderId,
+        options: FillWaitOptions,
+    ) -> Result<FillOutcome, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        if let Some(fraction) = &options.min_fill_fraction {
+            if *fraction == UnsignedDecimal::ZERO || *fraction > UnsignedDecimal::ONE {
+                return Err(O2Error::InvalidRequest(format!(
+                    "min_fill_fraction {fraction} is outside (0, 1]"
+                )));
+            }
+        }
+        debug!(
+            "client.wait_for_fill market={} order_id={} timeout_ms={}",
+            market_name,
+            order_id,
+            options.timeout.as_millis()
+        );
+        let market = self.get_market(&market_name).await?;
+        let deadline = runtime::now() + options.timeout;
+        let threshold = options.min_fill_fraction.as_ref();
+
+        let identity = Identity::ContractId(session.trade_account_id.to_string());
+        let mut stream = match self.stream_orders(&[identity]).await {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                debug!("client.wait_for_fill stream unavailable, polling only error={e}");
+                None
+            }
+        };
+
+        let mut last_seen: Option<Order> = None;
+        let mut poll = runtime::interval(options.poll_interval);
+
+        loop {
+            tokio::select! {
+                _ = runtime::sleep_until(deadline) => {
+                    return Ok(FillOutcome::TimedOut(last_seen));
+                }
+                item = async { stream.as_mut()?.next().await }, if stream.is_some() => {
+                    match item {
+                        Some(Ok(update)) => {
+                            if let Some(order) =
+                                update.orders.into_iter().find(|o| o.order_id == *order_id)
+                            {
+                                if let Some(outcome) = FillOutcome::from_order(&order, threshold) {
+                                    return Ok(outcome);
+                                }
+                                last_seen = Some(order);
+                            }
+                        }
+                        Some(Err(e)) => {
+                            debug!("client.wait_for_fill stream error={e}");
+                        }
+                        None => stream = None,
+                    }
+                }
+                _ = poll.tick() => {
+                    match self
+                        .api
+                        .get_order(market.market_id.as_str(), order_id.as_str())
+                        .await
+                    {
+                        Ok(order) => {
+                            if let Some(outcome) = FillOutcome::from_order(&order, threshold) {
+                                return Ok(outcome);
+                            }
+                            last_seen = Some(order);
+                        }
+                        // The order may not be indexed yet right after submission.
+                        Err(O2Error::OrderNotFound(_)) => {}
+                        Err(e) if e.is_retryable() => {
+                            debug!("client.wait_for_fill poll error={e}");
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Wait until a transaction is included in a block or dropped, or
+    /// `timeout` elapses.
+    ///
+    /// An action response's `tx_id` means the API accepted the transaction;
+    /// this asks the network's Fuel node ([`NetworkConfig::fuel_rpc`]) whether
+    /// it settled. The status is polled every second. On timeout the last
+    /// observed status is returned, [`TxStatus::Submitted`] or
+    /// [`TxStatus::NotFound`]; check [`TxStatus::is_final`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "client.wait_for_tx", level = "debug", skip_all, fields(tx_id = %tx_id))
+    )]
+    pub async fn wait_for_tx(&self, tx_id: &TxId, timeout: Duration) -> Result<TxStatus, O2Error> {
+        debug!(
+            "client.wait_for_tx tx_id={} timeout_ms={}",
+            tx_id,
+            timeout.as_millis()
+        );
+        let mut last_seen = TxStatus::NotFound;
+        let mut poll = runtime::interval(TX_POLL_INTERVAL);
+        let settled = runtime::timeout(timeout, async {
+            loop {
+                poll.tick().await;
+                match self.api.get_transaction_status(tx_id.as_str()).await {
+                    Ok(status) if status.is_final() => return Ok(status),
+                    Ok(status) => last_seen = status,
+                    Err(e) if e.is_retryable() => {
+                        debug!("client.wait_for_tx poll error={e}");
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+        .await;
+        settled.unwrap_or(Ok(last_seen))
+    }
+
+    // -----------------------------------------------------------------------
+    // Reconciliation
+    // -----------------------------------------------------------------------
+
+    /// Compare `expected` with the account's open orders on each of its
+    /// markets and with its balances. See [`crate::reconcile`].
+    pub async fn reconcile(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+        expected: &Expected,
+    ) -> Result<ReconcileReport, O2Error> {
+        let trade_account_id = trade_account_id.into_valid()?;
+        debug!(
+            "client.reconcile trade_account_id={} markets={} assets={}",
+            trade_account_id,
+            expected.books().len(),
+            expected.balances().len()
+        );
+        let mut open_orders = HashMap::new();
+        for book in expected.books() {
+            let market_id = book.market_id();
+            let orders = self
+                .api
+                .all_open_orders(market_id, &trade_account_id)
+                .await?;
+            open_orders.insert(market_id.clone(), orders);
+        }
+        let balances = self.get_balances(&trade_account_id).await?;
+        let markets = self.get_markets().await?;
+        let report = crate::reconcile::reconcile(expected, &open_orders, &balances, &markets);
+        debug!(
+            "client.reconcile discrepancies={}",
+            report.discrepancies.len()
+        );
+        Ok(report)
+    }
+
+    /// Submit a `SettleBalance` on every market `report` finds unsettled and
+    /// `session` may trade.
+    ///
+    /// Returns `None` if there is nothing to settle.
+    pub async fn heal(
+        &mut self,
+        session: &mut Session,
+        report: &ReconcileReport,
+    ) -> Result<Option<BatchedActionsResponse>, O2Error> {
+        let unsettled = report.unsettled_markets();
+        debug!(
+            "client.heal trade_account_id={} unsettled={}",
+            session.trade_account_id,
+            unsettled.len()
+        );
+        let settles: Vec<(MarketSymbol, Vec<Action>)> = self
+            .get_markets()
+            .await?
+            .iter()
+            .filter(|m| unsettled.contains(&m.market_id))
+            .filter(|m| session.is_active(&m.contract_id))
+            .map(|m| (m.symbol_pair(), vec![Action::SettleBalance]))
+            .collect();
+        if settles.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.batch_actions_chunked(session, &settles, false).await,
+        ))
+    }
+
     // -----------------------------------------------------------------------
     // Nonce Management
     // -----------------------------------------------------------------------
@@ -1230,6 +3430,27 @@ impl O2Client {
         Ok(nonce)
     }
 
+    /// Create a [`NonceManager`] for `session`'s trade account.
+    ///
+    /// The manager is synced from the API and follows the account's nonce stream
+    /// over the shared WebSocket, so it notices nonces consumed elsewhere. Share
+    /// clones of it between tasks that submit for the same account.
+    pub async fn nonce_manager(&self, session: &Session) -> Result<NonceManager, O2Error> {
+        debug!(
+            "client.nonce_manager trade_account_id={}",
+            session.trade_account_id
+        );
+        let manager = NonceManager::unsynced(session.trade_account_id.clone());
+        manager.resync(self).await?;
+        let stream = self
+            .stream_nonce(&[Identity::ContractId(
+                session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        manager.track(stream);
+        Ok(manager)
+    }
+
     // -----------------------------------------------------------------------
     // Withdrawals
     // -----------------------------------------------------------------------
@@ -1263,12 +3484,13 @@ impl O2Client {
         let signing_bytes = build_withdraw_signing_bytes(
             nonce,
             chain_id,
-            0, // Address discriminant
+            IdentityKind::Address,
             &to_address_bytes,
             &asset_id_bytes,
             amount_u64,
         );
         let signature = owner.personal_sign(&signing_bytes)?;
+        verify_personal_sign(&signing_bytes, &signature, owner.b256_address())?;
         let sig_hex = to_hex_string(&signature);
 
         let request = WithdrawRequest {
@@ -1283,6 +3505,90 @@ impl O2Client {
         self.api.withdraw(&owner_hex, &request).await
     }
 
+    /// Withdraw `amount` of the asset with symbol `asset_symbol`, in
+    /// human units, to the owner wallet.
+    ///
+    /// The asset's decimals come from the market list; an amount finer than
+    /// them fails with [`O2Error::InvalidAmount`] before signing.
+    pub async fn withdraw_human<W: SignableWallet>(
+        &mut self,
+        owner: &W,
+        session: &Session,
+        asset_symbol: &str,
+        amount: UnsignedDecimal,
+    ) -> Result<WithdrawResponse, O2Error> {
+        debug!(
+            "client.withdraw_human trade_account_id={} asset={} amount={}",
+            session.trade_account_id, asset_symbol, amount
+        );
+        let asset = self.asset(asset_symbol).await?;
+        let chain_amount = asset.scale_amount(&amount)?;
+        self.withdraw(
+            owner,
+            session,
+            &asset.asset_id,
+            &chain_amount.to_string(),
+            None,
+        )
+        .await
+    }
+
+    /// Withdraw the account's whole balance of `asset_symbol` to the owner
+    /// wallet.
+    ///
+    /// First settles every session market that trades the asset, so funds
+    /// unlocked in its order books are back in the trade account, then
+    /// withdraws the trade account's balance. `Ok(None)` if it is zero.
+    pub async fn withdraw_all<W: SignableWallet>(
+        &mut self,
+        owner: &W,
+        session: &mut Session,
+        asset_symbol: &str,
+    ) -> Result<Option<WithdrawResponse>, O2Error> {
+        debug!(
+            "client.withdraw_all trade_account_id={} asset={}",
+            session.trade_account_id, asset_symbol
+        );
+        let asset = self.asset(asset_symbol).await?;
+        let settles: Vec<(MarketSymbol, Vec<Action>)> = self
+            .get_markets()
+            .await?
+            .iter()
+            .filter(|m| asset.markets.contains(&m.symbol_pair()))
+            .filter(|m| session.is_active(&m.contract_id))
+            .map(|m| (m.symbol_pair(), vec![Action::SettleBalance]))
+            .collect();
+        let settled = self.batch_actions_chunked(session, &settles, false).await;
+        if let Some(e) = settled.error {
+            return Err(e);
+        }
+
+        let balance = self
+            .api
+            .get_balance(
+                asset.asset_id.as_str(),
+                Some(session.trade_account_id.as_str()),
+                None,
+            )
+            .await?;
+        if balance.trading_account_balance == 0 {
+            debug!(
+                "client.withdraw_all nothing to withdraw asset={}",
+                asset_symbol
+            );
+            return Ok(None);
+        }
+        let amount = u64::try_from(balance.trading_account_balance).map_err(|_| {
+            O2Error::InvalidAmount(format!(
+                "{} {} exceeds the largest single withdrawal",
+                balance.trading_account_balance, asset.symbol
+            ))
+        })?;
+        self.withdraw(owner, session, &asset.asset_id, &amount.to_string(), None)
+            .await
+            .map(Some)
+    }
+
     // -----------------------------------------------------------------------
     // WebSocket Streaming (shared connection)
     // -----------------------------------------------------------------------
@@ -1290,48 +3596,314 @@ impl O2Client {
     /// Ensure the shared WebSocket is connected, creating or replacing as needed.
     async fn ensure_ws(
         ws_slot: &mut Option<crate::websocket::O2WebSocket>,
-        ws_url: &str,
+        config: &NetworkConfig,
     ) -> Result<(), O2Error> {
-        debug!("client.ensure_ws url={}", ws_url);
+        debug!("client.ensure_ws url={}", config.ws_url);
         if ws_slot.as_ref().is_some_and(|ws| ws.is_terminated()) {
             *ws_slot = None;
         }
         if ws_slot.is_none() {
-            *ws_slot = Some(crate::websocket::O2WebSocket::connect(ws_url).await?);
+            let ws_config = crate::websocket::WsConfig {
+                fallback_urls: config.ws_url_fallbacks.clone(),
+                failover: config.failover.clone(),
+                ..Default::default()
+            };
+            *ws_slot = Some(
+                crate::websocket::O2WebSocket::connect_with_config(&config.ws_url, ws_config)
+                    .await?,
+            );
         }
         Ok(())
     }
 
-    /// Stream depth updates over a shared WebSocket connection.
+    /// Stream depth updates over a shared WebSocket connection.
+    ///
+    /// # Arguments
+    /// * `market_id` - The market ID (hex string).
+    /// * `precision` - Price grouping level, from `"1"` (most precise) to `"18"`
+    ///   (most grouped). Default `"1"`. At level 1, prices are at or near their
+    ///   exact values. Higher levels round prices into larger buckets. Same
+    ///   scale as [`get_depth`][O2Client::get_depth].
+    ///
+    /// # Errors
+    /// Returns [`O2Error::InvalidRequest`] if `precision` is outside 1–18.
+    pub async fn stream_depth(
+        &self,
+        market_id: impl IntoValidId<MarketId>,
+        precision: u64,
+    ) -> Result<TypedStream<DepthUpdate>, O2Error> {
+        let dp = DepthPrecision::new(precision)?;
+        let market_id = market_id.into_valid()?;
+        debug!(
+            "client.stream_depth market_id={} precision={}",
+            market_id,
+            dp.as_str()
+        );
+        let mut guard = self.ws.lock().await;
+        Self::ensure_ws(&mut guard, &self.config).await?;
+        guard
+            .as_ref()
+            .unwrap()
+            .stream_depth(market_id.as_str(), &dp)
+            .await
+    }
+
+    /// Build live OHLCV bars for a market from its trade stream.
+    ///
+    /// Subscribes to trades first, then seeds the bars with the last
+    /// `lookback` of history from `get_bars`, so no trade falls between the
+    /// two. See [`crate::candles`].
+    pub async fn candles<M>(
+        &mut self,
+        market: M,
+        resolution: &str,
+        lookback: Duration,
+    ) -> Result<Aggregator, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        debug!(
+            "client.candles market={} resolution={} lookback_ms={}",
+            market.market_id,
+            resolution,
+            lookback.as_millis()
+        );
+        let mut candles = Candles::new(resolution)?;
+        let stream = self.stream_trades(&market.market_id).await?;
+        let now = self.api.server_time();
+        let to_ms = u64::try_from(now.as_unix_millis()).unwrap_or(u64::MAX);
+        let from_ms = u64::try_from((now - lookback).as_unix_millis()).unwrap_or(0);
+        let bars = self
+            .api
+            .get_bars(market.market_id.as_str(), from_ms, to_ms, resolution)
+            .await?;
+        candles.seed(&bars, now);
+        Ok(Aggregator::new(market.market_id, candles, stream))
+    }
+
+    /// Stream `indicators` over a market's live bars.
+    ///
+    /// Builds bars as [`candles`](Self::candles) does, warms the indicators up
+    /// with the `lookback` of history, then keeps no closed bars. See
+    /// [`crate::indicators`].
+    pub async fn indicators<M>(
+        &mut self,
+        market: M,
+        resolution: &str,
+        lookback: Duration,
+        indicators: Indicators,
+    ) -> Result<IndicatorStream, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        debug!("client.indicators indicators={:?}", indicators);
+        let aggregator = self.candles(market, resolution, lookback).await?;
+        Ok(IndicatorStream::new(aggregator, indicators))
+    }
+
+    /// Poll a market's ticker every `interval` and stream it as [`TickerUpdate`]s.
+    ///
+    /// O2 has no ticker WebSocket channel, so a background task polls
+    /// `GET /v1/markets/ticker`. The first ticker is yielded right away, then
+    /// one whenever the ticker changes. A failed poll is yielded as an error
+    /// and polling carries on. Dropping the stream stops the task.
+    pub async fn ticker_stream<M>(
+        &mut self,
+        market: M,
+        interval: Duration,
+    ) -> Result<TypedStream<TickerUpdate>, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        if interval.is_zero() {
+            return Err(O2Error::InvalidRequest(
+                "ticker_stream interval must be non-zero".into(),
+            ));
+        }
+        let market = self.get_market(market).await?;
+        debug!(
+            "client.ticker_stream market={} interval_ms={}",
+            market.market_id,
+            interval.as_millis()
+        );
+        let api = self.api.clone();
+        let market_id = market.market_id;
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        runtime::spawn(async move {
+            let mut ticks = runtime::interval(interval);
+            let mut last: Option<MarketTicker> = None;
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => break,
+                    _ = ticks.tick() => {}
+                }
+                let ticker = match api.get_market_ticker(market_id.as_str()).await {
+                    Ok(tickers) => tickers.into_iter().next().ok_or_else(|| {
+                        O2Error::Other("No ticker returned for requested market".into())
+                    }),
+                    Err(e) => Err(e),
+                };
+                let sent = match ticker {
+                    Ok(ticker) if last.as_ref() == Some(&ticker) => Ok(()),
+                    Ok(ticker) => {
+                        last = Some(ticker.clone());
+                        tx.send(Ok(TickerUpdate {
+                            market_id: market_id.clone(),
+                            ticker,
+                            seen_timestamp: Timestamp::now(),
+                        }))
+                    }
+                    Err(e) => {
+                        debug!(
+                            "client.ticker_stream market={} poll failed error={}",
+                            market_id, e
+                        );
+                        tx.send(Err(e))
+                    }
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(TypedStream::from_receiver(rx))
+    }
+
+    /// Stream the account's orders, balances, nonce and its trades on `markets`
+    /// as one stream of [`AccountEvent`]s over the shared WebSocket.
+    ///
+    /// See [`crate::events`] for the filtering, ordering and reconnect
+    /// semantics.
+    pub async fn stream_account_events<S: AsRef<str>>(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+        markets: &[S],
+    ) -> Result<TypedStream<AccountEvent>, O2Error> {
+        let trade_account_id = trade_account_id.into_valid()?;
+        debug!(
+            "client.stream_account_events trade_account_id={} markets={}",
+            trade_account_id,
+            markets.len()
+        );
+        let mut market_ids = Vec::with_capacity(markets.len());
+        for market in markets {
+            market_ids.push(self.get_market(market.as_ref()).await?.market_id);
+        }
+        let identity = [Identity::ContractId(trade_account_id.to_string())];
+        // Subscribe to lifecycle events first so no reconnect goes unseen.
+        let lifecycle = self.subscribe_ws_lifecycle().await?;
+        let mut trades = Vec::with_capacity(market_ids.len());
+        for market_id in &market_ids {
+            trades.push(self.stream_trades(market_id).await?);
+        }
+        let streams = AccountStreams {
+            orders: self.stream_orders(&identity).await?,
+            balances: self.stream_balances(&identity).await?,
+            nonce: self.stream_nonce(&identity).await?,
+            trades,
+            lifecycle,
+        };
+        Ok(crate::events::merge(trade_account_id, streams))
+    }
+
+    /// Subscribe to depth for a market and maintain a local order book from it.
+    ///
+    /// The returned [`LiveOrderBook`] applies snapshots and deltas on a background
+    /// task; see [`crate::orderbook`] for details.
+    pub async fn live_order_book<M>(
+        &mut self,
+        market: M,
+        precision: u64,
+    ) -> Result<LiveOrderBook, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        let stream = self.stream_depth(&market.market_id, precision).await?;
+        Ok(LiveOrderBook::spawn(market.market_id, stream))
+    }
+
+    /// Subscribe to trades on a market and maintain its VWAP and TWAP over a
+    /// trailing `window`, e.g. as a quoting reference price. See
+    /// [`crate::analytics`].
+    pub async fn live_reference_price<M>(
+        &mut self,
+        market: M,
+        window: Duration,
+    ) -> Result<LiveReferencePrice, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        let stream = self.stream_trades(&market.market_id).await?;
+        Ok(LiveReferencePrice::spawn(window, stream))
+    }
+
+    /// Track the account's own resting size at each price level on a market.
+    ///
+    /// Subscribes to the account's order updates and reconciles against the
+    /// REST open orders immediately and then every `reconcile_every`. See
+    /// [`crate::my_book`].
+    pub async fn my_book<M>(
+        &mut self,
+        trade_account_id: impl IntoValidId<TradeAccountId>,
+        market: M,
+        reconcile_every: Duration,
+    ) -> Result<LiveMyBook, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let trade_account_id = trade_account_id.into_valid()?;
+        let market = self.get_market(market).await?;
+        let stream = self
+            .stream_orders(&[Identity::ContractId(trade_account_id.to_string())])
+            .await?;
+        Ok(LiveMyBook::spawn(
+            self.api.clone(),
+            market.market_id,
+            trade_account_id,
+            stream,
+            reconcile_every,
+        ))
+    }
+
+    /// Stream best bid/offer changes for a market.
     ///
-    /// # Arguments
-    /// * `market_id` - The market ID (hex string).
-    /// * `precision` - Price grouping level, from `"1"` (most precise) to `"18"`
-    ///   (most grouped). Default `"1"`. At level 1, prices are at or near their
-    ///   exact values. Higher levels round prices into larger buckets. Same
-    ///   scale as [`get_depth`][O2Client::get_depth].
+    /// Subscribes to full-precision depth and reduces it locally, yielding only
+    /// when the top of book changes. See [`BboStream`].
+    pub async fn stream_bbo<M>(&mut self, market: M) -> Result<BboStream, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market = self.get_market(market).await?;
+        let stream = self.stream_depth(&market.market_id, 1).await?;
+        Ok(BboStream::new(market.market_id, stream))
+    }
+
+    /// Order ids created through this client, keyed by the session that created them.
     ///
-    /// # Errors
-    /// Returns [`O2Error::InvalidRequest`] if `precision` is outside 1–18.
-    pub async fn stream_depth(
+    /// Pass to [`TypedStream::mine_only`] to filter an order stream by session.
+    pub fn order_ownership(&self) -> &OrderOwnership {
+        &self.order_ownership
+    }
+
+    /// Stream order updates for orders created by `session` through this client.
+    ///
+    /// Subscribes to the session's trade account and applies
+    /// [`TypedStream::mine_only`]. Orders are only attributed when submitted with
+    /// `collect_orders = true`.
+    pub async fn stream_session_orders(
         &self,
-        market_id: impl IntoValidId<MarketId>,
-        precision: u64,
-    ) -> Result<TypedStream<DepthUpdate>, O2Error> {
-        let dp = DepthPrecision::new(precision)?;
-        let market_id = market_id.into_valid()?;
+        session: &Session,
+    ) -> Result<TypedStream<OrderUpdate>, O2Error> {
         debug!(
-            "client.stream_depth market_id={} precision={}",
-            market_id,
-            dp.as_str()
+            "client.stream_session_orders trade_account_id={}",
+            session.trade_account_id
         );
-        let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
-        guard
-            .as_ref()
-            .unwrap()
-            .stream_depth(market_id.as_str(), &dp)
-            .await
+        let identity = Identity::ContractId(session.trade_account_id.to_string());
+        let stream = self.stream_orders(&[identity]).await?;
+        Ok(stream.mine_only(&self.order_ownership, session))
     }
 
     /// Stream order updates over a shared WebSocket connection.
@@ -1341,7 +3913,7 @@ impl O2Client {
     ) -> Result<TypedStream<OrderUpdate>, O2Error> {
         debug!("client.stream_orders identities={}", identities.len());
         let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
+        Self::ensure_ws(&mut guard, &self.config).await?;
         guard.as_ref().unwrap().stream_orders(identities).await
     }
 
@@ -1353,7 +3925,7 @@ impl O2Client {
         let market_id = market_id.into_valid()?;
         debug!("client.stream_trades market_id={}", market_id);
         let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
+        Self::ensure_ws(&mut guard, &self.config).await?;
         guard
             .as_ref()
             .unwrap()
@@ -1361,6 +3933,18 @@ impl O2Client {
             .await
     }
 
+    /// Subscribe with a raw message over the shared WebSocket connection.
+    /// See [`O2WebSocket::subscribe_raw`](crate::O2WebSocket::subscribe_raw).
+    pub async fn subscribe_raw(
+        &self,
+        subscription: serde_json::Value,
+    ) -> Result<TypedStream<serde_json::Value>, O2Error> {
+        debug!("client.subscribe_raw subscription={}", subscription);
+        let mut guard = self.ws.lock().await;
+        Self::ensure_ws(&mut guard, &self.config).await?;
+        guard.as_ref().unwrap().subscribe_raw(subscription).await
+    }
+
     /// Stream balance updates over a shared WebSocket connection.
     pub async fn stream_balances(
         &self,
@@ -1368,7 +3952,7 @@ impl O2Client {
     ) -> Result<TypedStream<BalanceUpdate>, O2Error> {
         debug!("client.stream_balances identities={}", identities.len());
         let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
+        Self::ensure_ws(&mut guard, &self.config).await?;
         guard.as_ref().unwrap().stream_balances(identities).await
     }
 
@@ -1379,19 +3963,117 @@ impl O2Client {
     ) -> Result<TypedStream<NonceUpdate>, O2Error> {
         debug!("client.stream_nonce identities={}", identities.len());
         let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
+        Self::ensure_ws(&mut guard, &self.config).await?;
         guard.as_ref().unwrap().stream_nonce(identities).await
     }
 
+    /// Server subscriptions on the shared WebSocket and how many streams share
+    /// each.
+    ///
+    /// Streaming the same feed twice, e.g. calling
+    /// [`stream_depth`](Self::stream_depth) again for a market and precision
+    /// already streamed, joins the existing subscription: the new stream gets
+    /// its own copy of every message without a second subscription on the
+    /// server. A joining depth stream starts from a snapshot of the current
+    /// book. Empty if the WebSocket has not been connected yet.
+    pub async fn active_streams(&self) -> Vec<ActiveStream> {
+        let guard = self.ws.lock().await;
+        match guard.as_ref() {
+            Some(ws) => ws.active_streams().await,
+            None => Vec::new(),
+        }
+    }
+
+    /// Message-gap statistics for depth and trades feeds on the shared WebSocket.
+    ///
+    /// Empty if the WebSocket has not been connected yet.
+    pub async fn feed_stats(&self) -> Vec<FeedStats> {
+        let guard = self.ws.lock().await;
+        match guard.as_ref() {
+            Some(ws) => ws.feed_stats().await,
+            None => Vec::new(),
+        }
+    }
+
+    /// Health of `ws_url` and each of `ws_url_fallbacks`, with the one the
+    /// shared WebSocket is connected to marked active. See
+    /// [`crate::failover`]; `self.api.endpoint_health()` reports the REST
+    /// bases.
+    ///
+    /// Empty if the WebSocket has not been connected yet.
+    pub async fn ws_endpoint_health(&self) -> Vec<EndpointHealth> {
+        let guard = self.ws.lock().await;
+        match guard.as_ref() {
+            Some(ws) => ws.endpoint_health(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Watch depth and trades feeds on the shared WebSocket for stalls.
+    ///
+    /// Stalls are reported as
+    /// [`WsLifecycleEvent::FeedStalled`](crate::websocket::WsLifecycleEvent::FeedStalled) on
+    /// [`O2Client::subscribe_ws_lifecycle`]. The monitor stops when the current
+    /// connection terminates for good. See [`crate::feed_monitor`].
+    pub async fn monitor_feeds(&self, config: FeedMonitorConfig) -> Result<FeedMonitor, O2Error> {
+        debug!(
+            "client.monitor_feeds stall_window_ms={} resubscribe={}",
+            config.stall_window.as_millis(),
+            config.resubscribe
+        );
+        let mut guard = self.ws.lock().await;
+        Self::ensure_ws(&mut guard, &self.config).await?;
+        Ok(guard
+            .as_ref()
+            .unwrap()
+            .monitor_feeds(self.api.clone(), config))
+    }
+
+    /// Record every frame on the shared WebSocket to `recorder`, connecting
+    /// it if needed. A connection opened after this one closes for good is
+    /// not recorded. See [`crate::recorder`].
+    pub async fn record_ws(&self, recorder: Recorder) -> Result<(), O2Error> {
+        debug!("client.record_ws");
+        let mut guard = self.ws.lock().await;
+        Self::ensure_ws(&mut guard, &self.config).await?;
+        guard.as_ref().unwrap().record(recorder).await;
+        Ok(())
+    }
+
     /// Subscribe to shared WebSocket lifecycle events (reconnect/disconnect).
     pub async fn subscribe_ws_lifecycle(
         &self,
     ) -> Result<tokio::sync::broadcast::Receiver<crate::websocket::WsLifecycleEvent>, O2Error> {
         let mut guard = self.ws.lock().await;
-        Self::ensure_ws(&mut guard, &self.config.ws_url).await?;
+        Self::ensure_ws(&mut guard, &self.config).await?;
         Ok(guard.as_ref().unwrap().subscribe_lifecycle())
     }
 
+    /// Drain the shared WebSocket ahead of a planned shutdown, such as a rolling deploy.
+    ///
+    /// See [`O2WebSocket::drain`](crate::websocket::O2WebSocket::drain). While
+    /// draining, `stream_*` calls fail with [`O2Error::SubscriptionError`];
+    /// once the socket has closed, the next one opens a fresh connection.
+    /// An armed [`DeadmanSwitch`] sees the
+    /// [`Draining`](crate::websocket::WsLifecycleEvent::Draining) event and
+    /// stops treating the socket's loss as a reason to fire. Its heartbeat and
+    /// SIGTERM triggers stay armed.
+    ///
+    /// Does nothing if the WebSocket has not been connected.
+    pub async fn drain(&self, deadline: Duration) -> Result<(), O2Error> {
+        debug!("client.drain deadline_ms={}", deadline.as_millis());
+        // Drain without holding the slot, so concurrent subscribers are
+        // rejected by the draining socket rather than queued behind it.
+        let handle = {
+            let guard = self.ws.lock().await;
+            match guard.as_ref() {
+                Some(ws) if !ws.is_terminated() => ws.drain_handle(),
+                _ => return Ok(()),
+            }
+        };
+        handle.drain(deadline).await
+    }
+
     /// Disconnect the shared WebSocket connection and release resources.
     pub async fn disconnect_ws(&self) -> Result<(), O2Error> {
         debug!("client.disconnect_ws");
@@ -1408,14 +4090,27 @@ mod tests {
     use std::time::{Duration, Instant};
 
     use crate::{
+        api::O2Api,
+        capabilities::Endpoint,
         config::{Network, NetworkConfig},
+        decimal::UnsignedDecimal,
+        errors::{AuthFailure, O2Error},
         models::{
-            Action, AssetId, ContractId, Market, MarketAsset, MarketId, MarketsResponse, OrderId,
-            OrderType, Side,
+            Action, AssetId, ContractId, Market, MarketAsset, MarketId, MarketsResponse, Order,
+            OrderId, OrderType, Session, SessionActionsResponse, Side, TradeAccountId,
+        },
+        retry::RetryPolicy,
+        settlement::SettlementPolicy,
+        test_util::{
+            mock_api::MockResponse,
+            mock_transport::{MockTransport, MOCK_API_BASE},
         },
     };
 
-    use super::{MarketActionsBuilder, MetadataPolicy, O2Client};
+    use super::{
+        ClientMode, FillOutcome, FillWaitOptions, MarketActionsBuilder, MetadataPolicy, O2Client,
+        SetupProgress,
+    };
 
     fn dummy_markets_response() -> MarketsResponse {
         MarketsResponse {
@@ -1431,6 +4126,21 @@ mod tests {
         }
     }
 
+    /// A session on trade account `0x01` with a fixed key, no contracts and no
+    /// expiry. `address` fills the session address.
+    fn session(address: u8, nonce: u64) -> Session {
+        Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [address; 32],
+            trade_account_id: TradeAccountId::new("0x01"),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce,
+        }
+    }
+
     fn dummy_market(market_id: &str) -> Market {
         Market {
             contract_id: ContractId::new("0x01"),
@@ -1529,6 +4239,45 @@ mod tests {
         assert!(client.should_refresh_markets());
     }
 
+    #[test]
+    fn metadata_policy_stale_while_revalidate_never_blocks_on_cache() {
+        let mut client = O2Client::new(Network::Testnet);
+        client.metadata_policy = MetadataPolicy::StaleWhileRevalidate(Duration::from_millis(10));
+        assert!(client.should_refresh_markets());
+        client.markets_cache = Some(dummy_markets_response());
+        client.markets_cache_at = Some(Instant::now() - Duration::from_secs(1));
+        assert!(!client.should_refresh_markets());
+        assert!(client.should_revalidate_markets());
+    }
+
+    #[tokio::test]
+    async fn metadata_policy_stale_while_revalidate_serves_stale_then_swaps() {
+        // Nothing is scripted, so the API answers every request with an error.
+        let mut client = MockTransport::new().client();
+        client.set_retry_policy(RetryPolicy::none());
+        client.metadata_policy = MetadataPolicy::StaleWhileRevalidate(Duration::from_millis(10));
+        client.markets_cache = Some(dummy_markets_response());
+        client.markets_cache_at = Some(Instant::now() - Duration::from_secs(1));
+
+        // Stale and the API fails: the cached copy is served anyway.
+        assert!(client.get_markets().await.unwrap().is_empty());
+        let pending = client.markets_revalidation.take().expect("refresh spawned");
+        assert!(pending.await.unwrap().is_err());
+
+        // A finished refresh is swapped in on the next read.
+        let mut fresh = dummy_markets_response();
+        fresh.markets.push(dummy_market("0xaa"));
+        let handle = crate::runtime::spawn(async move { Ok(fresh) });
+        while !handle.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        client.markets_revalidation = Some(handle);
+        let markets = client.get_markets().await.unwrap();
+        assert_eq!(markets.len(), 1);
+        assert!(client.markets_revalidation.is_none());
+        assert!(!client.is_markets_cache_expired(Duration::from_secs(60)));
+    }
+
     #[test]
     fn market_actions_builder_builds_valid_actions() {
         let market = dummy_market("0xmarket_a");
@@ -1604,6 +4353,37 @@ mod tests {
     }
 
     // REST depth precision (1-18)
+    #[test]
+    fn split_action_batches_respects_limit_and_market_order() {
+        let cancels = |n: usize| -> Vec<Action> {
+            (0..n)
+                .map(|i| Action::CancelOrder {
+                    order_id: OrderId::new(format!("0x{i:02x}")),
+                })
+                .collect()
+        };
+        let input = vec![
+            ("A", cancels(3)),
+            ("B", cancels(4)),
+            ("C", Vec::new()),
+            ("D", cancels(6)),
+        ];
+        let batches = super::split_action_batches(&input, 5);
+        let shape: Vec<Vec<(&str, usize)>> = batches
+            .iter()
+            .map(|b| b.iter().map(|(m, a)| (*m, a.len())).collect())
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                vec![("A", 3), ("B", 2)],
+                vec![("B", 2), ("D", 3)],
+                vec![("D", 3)],
+            ]
+        );
+        assert!(super::split_action_batches::<&str>(&[], 5).is_empty());
+    }
+
     #[test]
     fn validate_depth_precision_rejects_0() {
         let err = super::validate_depth_precision(0).unwrap_err();
@@ -1641,4 +4421,890 @@ mod tests {
     fn validate_depth_precision_accepts_10_for_stream() {
         assert!(super::validate_depth_precision(10).is_ok());
     }
+
+    /// Serves a trade account whose nonce moves once (during the first round
+    /// of reads) and then holds, with one open order on every market.
+    /// A client answered by `mock`, with `markets` already cached.
+    fn mock_client(mock: &MockTransport, markets: MarketsResponse) -> O2Client {
+        mock_client_with(mock.config(), mock, markets)
+    }
+
+    /// [`mock_client`] with a network configuration of the test's own.
+    fn mock_client_with(
+        config: NetworkConfig,
+        mock: &MockTransport,
+        markets: MarketsResponse,
+    ) -> O2Client {
+        let mut client = O2Client::with_api(O2Api::with_transport(config, mock.clone()));
+        client.markets_cache = Some(markets);
+        client.markets_cache_at = Some(Instant::now());
+        client
+    }
+
+    fn account_json(nonce: u64) -> serde_json::Value {
+        serde_json::json!({
+            "trade_account_id": "0xacc",
+            "trade_account": {
+                "nonce": nonce.to_string(),
+                "last_modification": 7,
+                "owner": {"Address": "0x01"},
+            },
+            "session": null,
+        })
+    }
+
+    fn balance_json(locked: u64, unlocked: u64) -> serde_json::Value {
+        serde_json::json!({
+            "order_books": {},
+            "total_locked": locked.to_string(),
+            "total_unlocked": unlocked.to_string(),
+            "trading_account_balance": unlocked.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn consistent_account_view_retries_until_nonce_holds() {
+        let mock = MockTransport::new();
+        // The nonce moves once between the first two reads, then holds.
+        mock.script(
+            "GET",
+            "/v1/accounts",
+            [
+                MockResponse::json(account_json(1)),
+                MockResponse::json(account_json(2)),
+            ],
+        );
+        mock.fixture("GET", "/v1/balance", balance_json(5, 10));
+        mock.fixture(
+            "GET",
+            "/v1/orders",
+            serde_json::json!({
+                "identity": {"ContractId": "0xacc"},
+                "market_id": "0xaa",
+                "orders": [{
+                    "order_id": "0x01",
+                    "side": "buy",
+                    "order_type": "Spot",
+                    "quantity": "5",
+                    "price": "1",
+                }],
+            }),
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+
+        let view = client.consistent_account_view("0xacc").await.unwrap();
+        assert_eq!(view.attempts, 2);
+        assert_eq!(view.nonce, 2);
+        assert_eq!(view.last_modification, 7);
+        assert_eq!(view.balances["fETH"].total_unlocked, 10);
+        let orders: Vec<_> = view.open_orders.values().flatten().collect();
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn consistent_account_view_reads_every_page_of_open_orders() {
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/accounts", account_json(1));
+        mock.fixture("GET", "/v1/balance", balance_json(0, 10));
+        mock.script(
+            "GET",
+            "/v1/orders",
+            [
+                MockResponse::json(open_orders_json("0xaa", 200)),
+                MockResponse::json(open_orders_json("0xaa", 3)),
+            ],
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+
+        let view = client.consistent_account_view("0xacc").await.unwrap();
+        let orders: Vec<_> = view.open_orders.values().flatten().collect();
+        assert_eq!(orders.len(), 203);
+        let reads = mock.requests_to("GET", "/v1/orders");
+        assert_eq!(reads.len(), 2);
+        let next = reads[1].query.as_deref().unwrap();
+        assert!(next.contains("start_timestamp=801"), "{next}");
+        assert!(
+            next.contains(&format!("start_order_id=0x{:062x}c7", 200)),
+            "{next}"
+        );
+    }
+
+    #[tokio::test]
+    async fn setup_account_reports_gated_whitelist() {
+        // An existing, funded trade account on a network that refuses
+        // whitelisting.
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/accounts", account_json(0));
+        mock.fixture("GET", "/v1/balance", balance_json(0, 10));
+        mock.script(
+            "POST",
+            "/analytics/v1/whitelist",
+            [MockResponse::status_json(
+                403,
+                serde_json::json!({"message": "Forbidden"}),
+            )],
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let config = NetworkConfig {
+            whitelist_required: true,
+            ..mock.config()
+        };
+        let mut client = mock_client_with(config, &mock, markets);
+
+        let wallet = client.generate_wallet().unwrap();
+        let mut steps = Vec::new();
+        let account = client
+            .setup_account_with_progress(&wallet, |step| steps.push(step))
+            .await
+            .unwrap();
+        assert_eq!(account.trade_account_id.unwrap().as_str(), "0xacc");
+        assert_eq!(steps.len(), 3, "{steps:?}");
+        assert!(matches!(&steps[0], SetupProgress::AccountFound(id) if id.as_str() == "0xacc"));
+        assert!(matches!(steps[1], SetupProgress::FundingSkipped));
+        // A refusal is not retried.
+        match &steps[2] {
+            SetupProgress::WhitelistFailed(O2Error::Unauthorized {
+                endpoint, reason, ..
+            }) => {
+                assert_eq!(*endpoint, Endpoint::Whitelist);
+                assert_eq!(*reason, AuthFailure::NetworkGated);
+            }
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(mock.requests_to("POST", "/analytics/v1/whitelist").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ticker_stream_yields_only_changed_tickers() {
+        use futures_util::StreamExt;
+
+        let ticker = |last: u64| {
+            MockResponse::json(serde_json::json!([{
+                "market_id": "0xaa",
+                "last": last.to_string(),
+                "base_volume": "0",
+                "quote_volume": "0",
+                "timestamp": "1",
+            }]))
+        };
+        let mock = MockTransport::new();
+        mock.script(
+            "GET",
+            "/v1/markets/ticker",
+            [ticker(100), ticker(100), ticker(100), ticker(105)],
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+
+        assert!(client
+            .ticker_stream("fETH/fUSDC", Duration::ZERO)
+            .await
+            .is_err());
+        let mut stream = client
+            .ticker_stream("fETH/fUSDC", Duration::from_millis(5))
+            .await
+            .unwrap();
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.market_id.as_str(), "0xaa");
+        assert_eq!(first.ticker.last, Some(100));
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.ticker.last, Some(105));
+    }
+
+    #[tokio::test]
+    async fn delisted_market_is_inactive_until_relisted() {
+        use crate::market_watch::{MarketEvent, MarketWatcherConfig};
+
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        // The first poll lists no markets, later ones list the market again.
+        let mut delisted = markets.clone();
+        delisted.markets.clear();
+        let mock = MockTransport::new();
+        mock.script(
+            "GET",
+            "/v1/markets",
+            [
+                MockResponse::json(serde_json::to_value(&delisted).unwrap()),
+                MockResponse::json(serde_json::to_value(&markets).unwrap()),
+            ],
+        );
+        let mut client = mock_client(&mock, markets);
+
+        let watcher = client
+            .watch_markets(MarketWatcherConfig {
+                poll_interval: Duration::from_millis(100),
+                cancel_orders: None,
+            })
+            .await
+            .unwrap();
+        let mut events = watcher.subscribe();
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            matches!(&event, MarketEvent::Delisted { market } if market.market_id.as_str() == "0xaa")
+        );
+        assert_eq!(client.inactive_markets().len(), 1);
+
+        let contract = ContractId::new("0x01");
+        let mut session = session(2, 4);
+        session.trade_account_id = TradeAccountId::new("0xacc");
+        session.contract_ids = vec![contract.clone()];
+        let err = client
+            .batch_actions(
+                &mut session,
+                "fETH/fUSDC",
+                vec![Action::CancelOrder {
+                    order_id: OrderId::new("0x01"),
+                }],
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::errors::O2Error::MarketInactive(_)));
+        assert_eq!(session.nonce, 4);
+        assert!(!session.is_active(&contract));
+
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(event, MarketEvent::Relisted { .. }));
+        assert!(client.inactive_markets().is_empty());
+    }
+
+    #[tokio::test]
+    async fn owner_lookup_is_cached_until_account_not_found() {
+        // One owner's trade account; every order read is `AccountNotFound`.
+        let mock = MockTransport::new();
+        mock.fixture(
+            "GET",
+            "/v1/accounts",
+            serde_json::json!({
+                "trade_account_id": "0xacc",
+                "trade_account": null,
+                "session": null,
+            }),
+        );
+        mock.script(
+            "GET",
+            "/v1/orders",
+            [MockResponse::status_json(
+                400,
+                serde_json::json!({"code": 4002, "message": "Account not found"}),
+            )],
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+        let lookups = || mock.requests_to("GET", "/v1/accounts").len();
+
+        let first = client.trade_account_for_owner("0xABCD").await.unwrap();
+        let second = client.trade_account_for_owner("abcd").await.unwrap();
+        assert_eq!(first.as_str(), "0xacc");
+        assert_eq!(first, second);
+        assert_eq!(lookups(), 1);
+
+        let err = client
+            .get_orders_by_owner("fETH/fUSDC", "0xabcd", Some(true), 10, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::O2Error::AccountNotFound(_)));
+        client.trade_account_for_owner("0xabcd").await.unwrap();
+        assert_eq!(lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn owner_lookup_is_forgotten_when_balances_are_not_found() {
+        // One owner's trade account; every balance read is `AccountNotFound`.
+        let mock = MockTransport::new();
+        mock.fixture(
+            "GET",
+            "/v1/accounts",
+            serde_json::json!({
+                "trade_account_id": "0xacc",
+                "trade_account": null,
+                "session": null,
+            }),
+        );
+        mock.script(
+            "GET",
+            "/v1/balance",
+            [MockResponse::status_json(
+                400,
+                serde_json::json!({"code": 4002, "message": "Account not found"}),
+            )],
+        );
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+        let lookups = || mock.requests_to("GET", "/v1/accounts").len();
+
+        let err = client.get_balances_by_owner("0xabcd").await.unwrap_err();
+        assert!(matches!(err, crate::O2Error::AccountNotFound(_)));
+        client.trade_account_for_owner("0xabcd").await.unwrap();
+        assert_eq!(lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn create_sessions_share_one_lookup_and_consecutive_nonces() {
+        // The owner's account is at nonce 7 and every session is accepted.
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/accounts", account_json(7));
+        mock.fixture(
+            "PUT",
+            "/v1/session",
+            serde_json::json!({
+                "tx_id": "0xfeed",
+                "trade_account_id": "0xacc",
+                "contract_ids": [],
+                "session_id": {"Address": "0x02"},
+                "session_expiry": "1",
+            }),
+        );
+        let mut markets = dummy_markets_response();
+        let mut market = dummy_market("0xaa");
+        market.contract_id = ContractId::new(format!("0x{}", "01".repeat(32)));
+        markets.markets.push(market);
+        let mut client = mock_client(&mock, markets);
+        let owner = client.generate_wallet().unwrap();
+
+        let sessions = client
+            .create_sessions(
+                &owner,
+                &[vec!["fETH/fUSDC"], vec!["fETH/fUSDC"], vec![]],
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        let nonces: Vec<_> = mock
+            .requests_to("PUT", "/v1/session")
+            .iter()
+            .map(|r| r.json()["nonce"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(nonces, vec!["7", "8", "9"]);
+        assert_eq!(mock.requests_to("GET", "/v1/accounts").len(), 1);
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions.iter().all(|s| s.nonce == 10));
+        assert_eq!(sessions[0].contract_ids.len(), 1);
+        assert!(sessions[2].contract_ids.is_empty());
+        assert_ne!(sessions[0].session_address, sessions[1].session_address);
+
+        let none: &[Vec<&str>] = &[];
+        assert!(client
+            .create_sessions(&owner, none, Duration::from_secs(60))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Markets with one fully-specified market `fETH/fUSDC` at id `hex(0xaa)`.
+    fn signable_markets() -> MarketsResponse {
+        let mut markets = dummy_markets_response();
+        markets.accounts_registry_id = ContractId::new(hex(2));
+        let mut market = dummy_market(&hex(0xaa));
+        market.contract_id = ContractId::new(hex(1));
+        market.base.asset = AssetId::new(hex(3));
+        market.quote.asset = AssetId::new(hex(4));
+        markets.markets.push(market);
+        markets
+    }
+
+    fn hex(n: u64) -> String {
+        format!("0x{n:064x}")
+    }
+
+    /// Session 2 on trade account `hex(5)` at nonce 4.
+    fn trading_session() -> Session {
+        let mut session = session(2, 4);
+        session.trade_account_id = TradeAccountId::new(hex(5));
+        session
+    }
+
+    #[tokio::test]
+    async fn replace_order_cancels_and_creates_in_one_batch() {
+        let old_id = "0x00000000000000000000000000000000000000000000000000000000000000a1";
+        let new_id = "0x00000000000000000000000000000000000000000000000000000000000000b2";
+        let order = |cancelled: bool| {
+            MockResponse::json(serde_json::json!({
+                "order_id": old_id,
+                "side": "Buy",
+                "order_type": "Spot",
+                "quantity": "2000000000",
+                "quantity_fill": "500000000",
+                "price": "1000000000",
+                "close": cancelled,
+                "cancel": cancelled,
+            }))
+        };
+        let mock = MockTransport::new();
+        // Read once for its side, and once more after the batch.
+        mock.script("GET", "/v1/order", [order(false), order(true)]);
+        mock.fixture(
+            "POST",
+            "/v1/session/actions",
+            serde_json::json!({
+                "tx_id": "0xfeed",
+                "orders": [{
+                    "order_id": new_id,
+                    "side": "Buy",
+                    "order_type": "Spot",
+                    "quantity": "2000000000",
+                    "price": "1100000000",
+                }],
+            }),
+        );
+        let mut client = mock_client(&mock, signable_markets());
+        let mut session = trading_session();
+
+        let replaced = client
+            .replace_order(
+                &mut session,
+                "fETH/fUSDC",
+                &OrderId::new(old_id),
+                "1.1",
+                "2",
+                OrderType::Spot,
+            )
+            .await
+            .unwrap();
+
+        assert!(replaced.response.is_success());
+        assert_eq!(replaced.new_order_id, Some(OrderId::new(new_id)));
+        let cancelled = replaced.cancelled.unwrap();
+        assert!(cancelled.cancel);
+        assert_eq!(cancelled.quantity_fill, Some(500_000_000));
+        assert_eq!(session.nonce, 5);
+
+        let submitted = mock.requests_to("POST", "/v1/session/actions");
+        assert_eq!(submitted.len(), 1);
+        let request = submitted[0].json();
+        let actions = &request["actions"][0]["actions"];
+        assert_eq!(actions[0]["CancelOrder"]["order_id"], old_id);
+        assert_eq!(actions[1]["CreateOrder"]["side"], "Buy");
+        assert_eq!(actions[1]["CreateOrder"]["price"], "1100000000");
+    }
+
+    #[tokio::test]
+    async fn dry_run_builds_and_signs_without_submitting() {
+        // Nothing is scripted, so a submission would fail.
+        let mock = MockTransport::new();
+        let mut client = mock_client(&mock, signable_markets());
+        client.set_mode(ClientMode::DryRun);
+        let mut session = trading_session();
+
+        let resp = client
+            .create_order(
+                &mut session,
+                "fETH/fUSDC",
+                Side::Buy,
+                "1.1",
+                "2",
+                OrderType::Spot,
+                true,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert!(resp.is_success());
+        assert!(resp.tx_id.is_none());
+        let batch = resp.dry_run.unwrap();
+        assert_eq!(batch.request.nonce, "4");
+        assert_eq!(batch.signing_bytes[..8], 4u64.to_be_bytes());
+        let body = serde_json::to_value(&batch.request).unwrap();
+        let actions = &body["actions"][0]["actions"];
+        assert!(actions[0].get("SettleBalance").is_some());
+        assert_eq!(actions[1]["CreateOrder"]["price"], "1100000000");
+        assert_eq!(session.nonce, 4);
+        assert!(mock.requests_to("POST", "/v1/session/actions").is_empty());
+    }
+
+    #[tokio::test]
+    async fn settlement_policy_appends_settle_to_due_batches() {
+        let mock = MockTransport::new();
+        let mut client = mock_client(&mock, signable_markets());
+        client.set_mode(ClientMode::DryRun);
+        client.set_settlement_policy(SettlementPolicy::EveryActions(1));
+        let mut session = trading_session();
+        let cancel = |n: u64| Action::CancelOrder {
+            order_id: OrderId::new(hex(n)),
+        };
+        let actions = |resp: SessionActionsResponse| {
+            let body = serde_json::to_value(&resp.dry_run.unwrap().request).unwrap();
+            body["actions"][0]["actions"].as_array().unwrap().clone()
+        };
+
+        let resp = client
+            .batch_actions(&mut session, "fETH/fUSDC", vec![cancel(7)], false)
+            .await
+            .unwrap();
+        let sent = actions(resp);
+        assert_eq!(sent.len(), 2);
+        assert!(sent[1].get("SettleBalance").is_some());
+
+        // A full batch has no room for the settle.
+        let resp = client
+            .batch_actions(
+                &mut session,
+                "fETH/fUSDC",
+                (7..12).map(cancel).collect(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(actions(resp).len(), 5);
+    }
+
+    #[tokio::test]
+    async fn implausible_limit_timestamps_are_still_submitted() {
+        let mock = MockTransport::new();
+        let mut client = mock_client(&mock, signable_markets());
+        client.set_mode(ClientMode::DryRun);
+        let mut session = trading_session();
+        let limit = |timestamp: u64| Action::CreateOrder {
+            side: Side::Buy,
+            price: "1.1".parse().unwrap(),
+            quantity: "2".parse().unwrap(),
+            order_type: OrderType::Limit {
+                price: "1.1".parse().unwrap(),
+                timestamp,
+            },
+        };
+
+        // Zero, and a timestamp in milliseconds.
+        let resp = client
+            .batch_actions(
+                &mut session,
+                "fETH/fUSDC",
+                vec![limit(0), limit(1_700_000_000_000)],
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(resp.is_success());
+        let body = serde_json::to_value(&resp.dry_run.unwrap().request).unwrap();
+        let actions = body["actions"][0]["actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0]["CreateOrder"]["order_type"]["Limit"][1], "0");
+    }
+
+    /// `count` open orders on `market_id`, with ids unique to the market.
+    fn open_orders_json(market_id: &str, count: usize) -> serde_json::Value {
+        let orders: Vec<_> = (0..count)
+            .map(|i| {
+                serde_json::json!({
+                    "order_id": format!("0x{count:062x}{i:02x}"),
+                    "side": "Buy",
+                    "order_type": "Spot",
+                    "quantity": "1",
+                    "price": "1",
+                    "timestamp": (1_000 - i).to_string(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "identity": { "ContractId": "0x05" },
+            "market_id": market_id,
+            "orders": orders,
+        })
+    }
+
+    #[tokio::test]
+    async fn cancel_all_orders_cancels_past_the_first_page() {
+        let mock = MockTransport::new();
+        let market_id = hex(0xaa);
+        mock.script(
+            "GET",
+            "/v1/orders",
+            [
+                MockResponse::json(open_orders_json(&market_id, 200)),
+                MockResponse::json(open_orders_json(&market_id, 3)),
+                MockResponse::json(open_orders_json(&market_id, 0)),
+            ],
+        );
+        mock.fixture(
+            "POST",
+            "/v1/session/actions",
+            serde_json::json!({ "tx_id": "0xfeed" }),
+        );
+        let mut client = mock_client(&mock, signable_markets());
+        let mut session = trading_session();
+
+        let responses = client
+            .cancel_all_orders(&mut session, "fETH/fUSDC")
+            .await
+            .unwrap();
+
+        // 203 cancels in batches of five, then a listing with nothing left.
+        assert_eq!(responses.len(), 41);
+        let bodies = mock.requests_to("POST", "/v1/session/actions");
+        let cancels: usize = bodies
+            .iter()
+            .map(|b| b.json()["actions"][0]["actions"].as_array().unwrap().len())
+            .sum();
+        assert_eq!(cancels, 203);
+        assert_eq!(mock.requests_to("GET", "/v1/orders").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cancel_everything_batches_across_markets() {
+        let mock = MockTransport::new();
+        mock.fixture(
+            "GET",
+            "/v1/orders?market_id=0xaa",
+            open_orders_json("0xaa", 3),
+        );
+        mock.fixture(
+            "GET",
+            "/v1/orders?market_id=0xbb",
+            open_orders_json("0xbb", 4),
+        );
+        mock.fixture(
+            "POST",
+            "/v1/session/actions",
+            serde_json::json!({ "tx_id": "0xfeed" }),
+        );
+        let mut markets = dummy_markets_response();
+        markets.accounts_registry_id = ContractId::new(hex(2));
+        for (id, contract, base) in [("0xaa", 0xa1, "fETH"), ("0xbb", 0xb1, "fBTC")] {
+            let mut market = dummy_market(id);
+            market.contract_id = ContractId::new(hex(contract));
+            market.base.symbol = base.into();
+            market.base.asset = AssetId::new(hex(contract + 1));
+            market.quote.asset = AssetId::new(hex(4));
+            markets.markets.push(market);
+        }
+        let mut client = mock_client(&mock, markets);
+        let mut session = trading_session();
+        session.contract_ids = vec![
+            ContractId::new(hex(0xa1)),
+            ContractId::new(hex(0xb1)),
+            ContractId::new(hex(0xc1)),
+        ];
+
+        let summaries = client.cancel_everything(&mut session).await.unwrap();
+
+        assert_eq!(summaries.len(), 3);
+        assert!(summaries[0].is_success());
+        assert_eq!((summaries[0].open_orders, summaries[0].cancelled), (3, 3));
+        assert!(summaries[1].is_success());
+        assert_eq!((summaries[1].open_orders, summaries[1].cancelled), (4, 4));
+        assert!(matches!(
+            summaries[2].error,
+            Some(O2Error::MarketNotFound(_))
+        ));
+        assert_eq!(session.nonce, 6);
+
+        // Seven cancels: three and two in the first batch, two in the second.
+        let bodies = mock.requests_to("POST", "/v1/session/actions");
+        assert_eq!(bodies.len(), 2);
+        let sizes: Vec<usize> = bodies[0].json()["actions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["actions"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![3, 2]);
+    }
+
+    #[tokio::test]
+    async fn cancel_everything_cancels_past_the_first_page() {
+        let mock = MockTransport::new();
+        let market_id = hex(0xaa);
+        mock.script(
+            "GET",
+            "/v1/orders",
+            [
+                MockResponse::json(open_orders_json(&market_id, 200)),
+                MockResponse::json(open_orders_json(&market_id, 3)),
+                MockResponse::json(open_orders_json(&market_id, 0)),
+            ],
+        );
+        mock.fixture(
+            "POST",
+            "/v1/session/actions",
+            serde_json::json!({ "tx_id": "0xfeed" }),
+        );
+        let mut client = mock_client(&mock, signable_markets());
+        let mut session = trading_session();
+        session.contract_ids = vec![ContractId::new(hex(1))];
+
+        let summaries = client.cancel_everything(&mut session).await.unwrap();
+
+        assert!(summaries[0].is_success(), "{:?}", summaries[0]);
+        assert_eq!(
+            (summaries[0].open_orders, summaries[0].cancelled),
+            (203, 203)
+        );
+        assert_eq!(mock.requests_to("POST", "/v1/session/actions").len(), 41);
+        assert_eq!(mock.requests_to("GET", "/v1/orders").len(), 3);
+    }
+
+    // wait_for_fill classification
+    fn order_state(quantity: u64, filled: u64, close: bool, cancel: bool) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": "0x01",
+            "side": "buy",
+            "order_type": "Spot",
+            "quantity": quantity.to_string(),
+            "quantity_fill": filled.to_string(),
+            "price": "1",
+            "close": close,
+            "cancel": cancel,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fill_outcome_classifies_terminal_states() {
+        let filled = order_state(10, 10, true, false);
+        assert!(matches!(
+            FillOutcome::from_order(&filled, None),
+            Some(FillOutcome::Filled(_))
+        ));
+        let cancelled = order_state(10, 3, true, true);
+        assert!(matches!(
+            FillOutcome::from_order(&cancelled, None),
+            Some(FillOutcome::Cancelled(_))
+        ));
+        let open = order_state(10, 3, false, false);
+        assert!(FillOutcome::from_order(&open, None).is_none());
+    }
+
+    #[test]
+    fn fill_outcome_respects_partial_threshold() {
+        let half: UnsignedDecimal = "0.5".parse().unwrap();
+        let below = order_state(10, 4, false, false);
+        assert!(FillOutcome::from_order(&below, Some(&half)).is_none());
+        let at = order_state(10, 5, false, false);
+        assert!(matches!(
+            FillOutcome::from_order(&at, Some(&half)),
+            Some(FillOutcome::PartiallyFilled(_))
+        ));
+        let zero = UnsignedDecimal::ZERO;
+        let unfilled = order_state(10, 0, false, false);
+        assert!(FillOutcome::from_order(&unfilled, Some(&zero)).is_none());
+    }
+
+    #[tokio::test]
+    async fn wait_for_fill_rejects_fractions_outside_unit_interval() {
+        let mock = MockTransport::new();
+        let mut markets = dummy_markets_response();
+        markets.markets.push(dummy_market("0xaa"));
+        let mut client = mock_client(&mock, markets);
+        let session = session(2, 4);
+
+        for fraction in ["0", "1.5"] {
+            let options = FillWaitOptions {
+                min_fill_fraction: Some(fraction.parse().unwrap()),
+                ..FillWaitOptions::default()
+            };
+            let err = client
+                .wait_for_fill_with(&session, "fETH/fUSDC", &OrderId::new("0x01"), options)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, O2Error::InvalidRequest(_)), "{err:?}");
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn wait_for_tx_polls_the_fuel_node_until_final() {
+        let status = |status: serde_json::Value| {
+            MockResponse::json(
+                serde_json::json!({ "data": { "transaction": { "status": status } } }),
+            )
+        };
+        let mock = MockTransport::new();
+        mock.script(
+            "POST",
+            "/v1/graphql",
+            [
+                status(serde_json::json!({ "__typename": "SubmittedStatus" })),
+                status(serde_json::json!({
+                    "__typename": "SuccessStatus",
+                    "block": { "height": "42" },
+                    "totalFee": "3",
+                    "receipts": [],
+                })),
+            ],
+        );
+        let config = NetworkConfig {
+            fuel_rpc: format!("{MOCK_API_BASE}/v1/graphql"),
+            ..mock.config()
+        };
+        let client = O2Client::with_api(O2Api::with_transport(config, mock.clone()));
+
+        let status = client
+            .wait_for_tx(&crate::models::TxId::new("0xfeed"), Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            status,
+            crate::models::TxStatus::Success {
+                block_height: 42,
+                total_fee: 3,
+                receipts: serde_json::json!([]),
+            }
+        );
+        assert_eq!(mock.requests_to("POST", "/v1/graphql").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn balances_are_fetched_once_per_asset_or_by_symbol() {
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/balance", balance_json(0, 10));
+        mock.fixture("GET", "/v1/balance?asset_id=0xquote", balance_json(7, 10));
+        let requested = || {
+            mock.requests_to("GET", "/v1/balance")
+                .iter()
+                .map(|r| {
+                    let query = r.query.as_deref().unwrap_or("");
+                    let asset = query.split("asset_id=").nth(1).unwrap_or("");
+                    asset.split('&').next().unwrap_or("").to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut markets = dummy_markets_response();
+        let mut other = dummy_market("0xmarket_b");
+        other.base.symbol = "fFUEL".into();
+        other.base.asset = AssetId::new("0xfuel");
+        markets.markets = vec![dummy_market("0xmarket_a"), other];
+        let mut client = mock_client(&mock, markets);
+
+        let balances = client.get_balances("0xacc").await.unwrap();
+        let mut keys: Vec<_> = balances.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["fETH", "fFUEL", "fUSDC"]);
+        assert_eq!(balances["fUSDC"].total_locked, 7);
+        // The shared quote asset is requested once.
+        assert_eq!(requested().len(), 3);
+
+        let usdc = client
+            .get_balance_for_symbol("0xacc", "fusdc")
+            .await
+            .unwrap();
+        assert_eq!(usdc.total_locked, 7);
+        assert_eq!(requested()[3..], ["0xquote"]);
+
+        let err = client
+            .get_balance_for_symbol("0xacc", "fBTC")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, O2Error::InvalidRequest(_)));
+
+        let fuel = client.asset("0xFUEL").await.unwrap();
+        assert_eq!(fuel.symbol, "fFUEL");
+        assert_eq!(fuel.markets[0].as_str(), "fFUEL/fUSDC");
+    }
 }
@@ -0,0 +1,8 @@
+//! Conversions between this crate's wallets and other Rust SDKs' key and
+//! address types, so keys move between them without a trip through hex.
+//!
+//! - `ethers-compat`: `TryFrom` between ethers' `LocalWallet` and
+//!   [`EvmWallet`](crate::EvmWallet), and `From<&EvmWallet>` for ethers'
+//!   `Address`.
+#[cfg(feature = "ethers-compat")]
+mod ethers;
@@ -0,0 +1,50 @@
+//! ethers' `LocalWallet` and `Address` for [`EvmWallet`].
+use ethers_core::types::Address;
+use ethers_signers::LocalWallet;
+
+use crate::crypto::{load_evm_wallet, EvmWallet};
+use crate::errors::O2Error;
+
+impl TryFrom<&LocalWallet> for EvmWallet {
+    type Error = O2Error;
+
+    fn try_from(wallet: &LocalWallet) -> Result<Self, O2Error> {
+        load_evm_wallet(&wallet.signer().to_bytes().into())
+    }
+}
+
+impl TryFrom<&EvmWallet> for LocalWallet {
+    type Error = O2Error;
+
+    fn try_from(wallet: &EvmWallet) -> Result<Self, O2Error> {
+        LocalWallet::from_bytes(wallet.private_key.expose_secret())
+            .map_err(|e| O2Error::CryptoError(format!("Invalid private key: {e}")))
+    }
+}
+
+impl From<&EvmWallet> for Address {
+    fn from(wallet: &EvmWallet) -> Self {
+        Address::from(wallet.evm_address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_signers::Signer;
+
+    use super::*;
+
+    #[test]
+    fn local_wallet_round_trips() {
+        let ours = load_evm_wallet(&[7u8; 32]).unwrap();
+        let theirs = LocalWallet::try_from(&ours).unwrap();
+        assert_eq!(theirs.address(), Address::from(&ours));
+
+        let back = EvmWallet::try_from(&theirs).unwrap();
+        assert_eq!(
+            back.private_key.expose_secret(),
+            ours.private_key.expose_secret()
+        );
+        assert_eq!(back.b256_address, ours.b256_address);
+    }
+}
@@ -0,0 +1,224 @@
+//! What this SDK build understands, and whether a live server matches it.
+//!
+//! [`COMPATIBILITY`] is a static manifest of the API version, actions, order
+//! types, WebSocket channels and REST endpoints this build speaks. It
+//! serializes to JSON for deploy tooling.
+//! [`O2Client::check_compatibility`](crate::O2Client::check_compatibility)
+//! probes the connected server and returns a [`CompatibilityReport`], so a
+//! deploy can be gated on [`CompatibilityReport::is_compatible`].
+//!
+//! The API has no version endpoint; a server is taken to speak an API version
+//! when it serves that version's routes.
+//!
+//! ```rust,ignore
+//! let report = client.check_compatibility().await;
+//! println!("{report}");
+//! if !report.is_compatible() {
+//!     std::process::exit(1);
+//! }
+//! ```
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::capabilities::{Capabilities, Endpoint, Support};
+
+/// Static description of what an SDK build understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Compatibility {
+    pub sdk_version: &'static str,
+    /// Oldest API version this build can talk to.
+    pub min_api_version: &'static str,
+    /// Newest API version this build can talk to.
+    pub max_api_version: &'static str,
+    /// Session actions this build can encode.
+    pub actions: &'static [&'static str],
+    /// Order types this build can encode.
+    pub order_types: &'static [&'static str],
+    /// WebSocket channels this build can subscribe to.
+    pub ws_channels: &'static [&'static str],
+    /// Endpoints trading cannot work without.
+    pub required_endpoints: &'static [Endpoint],
+    /// Endpoints some features use; everything else degrades without them.
+    pub optional_endpoints: &'static [Endpoint],
+}
+
+/// The manifest of this build.
+pub const COMPATIBILITY: Compatibility = Compatibility {
+    sdk_version: env!("CARGO_PKG_VERSION"),
+    min_api_version: "v1",
+    max_api_version: "v1",
+    actions: &[
+        "CreateOrder",
+        "CancelOrder",
+        "SettleBalance",
+        "RegisterReferer",
+    ],
+    order_types: &[
+        "Spot",
+        "Market",
+        "FillOrKill",
+        "PostOnly",
+        "Limit",
+        "BoundedMarket",
+    ],
+    ws_channels: &["depth", "orders", "trades", "balances", "nonce"],
+    required_endpoints: &[
+        Endpoint::Markets,
+        Endpoint::Depth,
+        Endpoint::CreateAccount,
+        Endpoint::Account,
+        Endpoint::Balance,
+        Endpoint::Orders,
+        Endpoint::Order,
+        Endpoint::Session,
+        Endpoint::SessionActions,
+    ],
+    optional_endpoints: &[
+        Endpoint::MarketSummary,
+        Endpoint::MarketTicker,
+        Endpoint::Trades,
+        Endpoint::TradesByAccount,
+        Endpoint::Bars,
+        Endpoint::Withdraw,
+        Endpoint::Whitelist,
+        Endpoint::ReferralInfo,
+        Endpoint::AggregatedAssets,
+        Endpoint::AggregatedOrderbook,
+        Endpoint::AggregatedCoingeckoOrderbook,
+        Endpoint::AggregatedSummary,
+        Endpoint::AggregatedTicker,
+        Endpoint::AggregatedCoingeckoTickers,
+        Endpoint::AggregatedTrades,
+        Endpoint::Faucet,
+    ],
+};
+
+/// How a live server compares to [`COMPATIBILITY`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompatibilityReport {
+    pub sdk_version: &'static str,
+    /// The API version the server was seen to serve, if established.
+    pub api_version: Option<&'static str>,
+    /// Required endpoints the server does not serve.
+    pub missing_required: Vec<Endpoint>,
+    /// Optional endpoints the server does not serve.
+    pub missing_optional: Vec<Endpoint>,
+    /// Required endpoints that could not be checked: write endpoints that
+    /// have not been used yet, or probes that could not reach the server.
+    pub unverified_required: Vec<Endpoint>,
+}
+
+impl CompatibilityReport {
+    /// Compare `capabilities` of a server against `manifest`.
+    pub fn from_capabilities(manifest: &Compatibility, capabilities: &Capabilities) -> Self {
+        let missing = |endpoints: &[Endpoint]| -> Vec<Endpoint> {
+            endpoints
+                .iter()
+                .copied()
+                .filter(|e| capabilities.get(*e) == Support::Unsupported)
+                .collect()
+        };
+        // Only v1 exists so far; serving its market list is what speaking it means.
+        let api_version = capabilities
+            .supports(Endpoint::Markets)
+            .unwrap_or(false)
+            .then_some(manifest.max_api_version);
+        Self {
+            sdk_version: manifest.sdk_version,
+            api_version,
+            missing_required: missing(manifest.required_endpoints),
+            missing_optional: missing(manifest.optional_endpoints),
+            unverified_required: manifest
+                .required_endpoints
+                .iter()
+                .copied()
+                .filter(|e| capabilities.get(*e) == Support::Unknown)
+                .collect(),
+        }
+    }
+
+    /// True if the server speaks a supported API version and no required
+    /// endpoint is known to be missing. Unverified endpoints do not count
+    /// against it.
+    pub fn is_compatible(&self) -> bool {
+        self.api_version.is_some() && self.missing_required.is_empty()
+    }
+}
+
+impl fmt::Display for CompatibilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.is_compatible() {
+            "compatible"
+        } else {
+            "INCOMPATIBLE"
+        };
+        writeln!(
+            f,
+            "{verdict}: sdk {} api {}",
+            self.sdk_version,
+            self.api_version.unwrap_or("unknown")
+        )?;
+        for (label, endpoints) in [
+            ("missing", &self.missing_required),
+            ("missing (optional)", &self.missing_optional),
+            ("unverified", &self.unverified_required),
+        ] {
+            for endpoint in endpoints {
+                writeln!(f, "{label:<18} {endpoint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::CapabilityCache;
+
+    #[test]
+    fn manifest_covers_every_endpoint_once() {
+        let mut listed: Vec<Endpoint> = COMPATIBILITY
+            .required_endpoints
+            .iter()
+            .chain(COMPATIBILITY.optional_endpoints)
+            .copied()
+            .collect();
+        listed.sort();
+        let mut all = Endpoint::ALL.to_vec();
+        all.sort();
+        assert_eq!(listed, all);
+
+        let json = serde_json::to_value(COMPATIBILITY).unwrap();
+        assert_eq!(json["max_api_version"], "v1");
+        assert_eq!(json["required_endpoints"][0], "GET /v1/markets");
+    }
+
+    #[test]
+    fn report_flags_missing_required_endpoints() {
+        let cache = CapabilityCache::default();
+        cache.record(Endpoint::Markets, true);
+        cache.record(Endpoint::AggregatedTicker, false);
+        let report = CompatibilityReport::from_capabilities(&COMPATIBILITY, &cache.snapshot());
+        assert_eq!(report.api_version, Some("v1"));
+        assert_eq!(report.missing_optional, vec![Endpoint::AggregatedTicker]);
+        assert!(report
+            .unverified_required
+            .contains(&Endpoint::SessionActions));
+        assert!(report.is_compatible());
+
+        cache.record(Endpoint::Orders, false);
+        let report = CompatibilityReport::from_capabilities(&COMPATIBILITY, &cache.snapshot());
+        assert_eq!(report.missing_required, vec![Endpoint::Orders]);
+        assert!(!report.is_compatible());
+        assert!(report.to_string().starts_with("INCOMPATIBLE"));
+
+        let empty = CompatibilityReport::from_capabilities(
+            &COMPATIBILITY,
+            &CapabilityCache::default().snapshot(),
+        );
+        assert_eq!(empty.api_version, None);
+        assert!(!empty.is_compatible());
+    }
+}
@@ -1,4 +1,12 @@
-/// Network configuration for O2 Exchange API endpoints.
+//! Network configuration for O2 Exchange API endpoints.
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::errors::O2Error;
+use crate::failover::FailoverPolicy;
+
 /// Supported O2 Exchange networks.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Network {
@@ -7,6 +15,19 @@ pub enum Network {
     Mainnet,
 }
 
+impl FromStr for Network {
+    type Err = O2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "testnet" => Ok(Self::Testnet),
+            "devnet" => Ok(Self::Devnet),
+            "mainnet" => Ok(Self::Mainnet),
+            _ => Err(O2Error::Other(format!("unknown network: {s}"))),
+        }
+    }
+}
+
 /// Configuration holding API and RPC URLs for a specific network.
 #[derive(Debug, Clone)]
 pub struct NetworkConfig {
@@ -15,6 +36,15 @@ pub struct NetworkConfig {
     pub fuel_rpc: String,
     pub faucet_url: Option<String>,
     pub whitelist_required: bool,
+    /// Chain id to sign for. `None` reads it from the markets response.
+    pub chain_id: Option<u64>,
+    /// REST bases tried in order when `api_base` is failing. See
+    /// [`crate::failover`].
+    pub api_base_fallbacks: Vec<String>,
+    /// WebSocket URLs tried in order when `ws_url` is failing.
+    pub ws_url_fallbacks: Vec<String>,
+    /// When to move between `api_base`, `ws_url` and their fallbacks.
+    pub failover: FailoverPolicy,
 }
 
 impl NetworkConfig {
@@ -26,6 +56,10 @@ impl NetworkConfig {
                 fuel_rpc: "https://testnet.fuel.network/v1/graphql".into(),
                 faucet_url: Some("https://fuel-o2-faucet.vercel.app/api/testnet/mint-v2".into()),
                 whitelist_required: true,
+                chain_id: None,
+                api_base_fallbacks: Vec::new(),
+                ws_url_fallbacks: Vec::new(),
+                failover: FailoverPolicy::default(),
             },
             Network::Devnet => Self {
                 api_base: "https://api.devnet.o2.app".into(),
@@ -33,6 +67,10 @@ impl NetworkConfig {
                 fuel_rpc: "https://devnet.fuel.network/v1/graphql".into(),
                 faucet_url: Some("https://fuel-o2-faucet.vercel.app/api/devnet/mint-v2".into()),
                 whitelist_required: false,
+                chain_id: None,
+                api_base_fallbacks: Vec::new(),
+                ws_url_fallbacks: Vec::new(),
+                failover: FailoverPolicy::default(),
             },
             Network::Mainnet => Self {
                 api_base: "https://api.o2.app".into(),
@@ -40,9 +78,129 @@ impl NetworkConfig {
                 fuel_rpc: "https://mainnet.fuel.network/v1/graphql".into(),
                 faucet_url: None,
                 whitelist_required: false,
+                chain_id: None,
+                api_base_fallbacks: Vec::new(),
+                ws_url_fallbacks: Vec::new(),
+                failover: FailoverPolicy::default(),
             },
         }
     }
+
+    /// Endpoints from `O2_*` environment variables.
+    ///
+    /// `O2_NETWORK` (`testnet`, `devnet` or `mainnet`, default `testnet`)
+    /// picks the base endpoints, which `O2_API_BASE`, `O2_WS_URL`,
+    /// `O2_FUEL_RPC`, `O2_FAUCET_URL` (empty for none),
+    /// `O2_WHITELIST_REQUIRED` and `O2_CHAIN_ID` override, and
+    /// `O2_API_BASE_FALLBACKS` and `O2_WS_URL_FALLBACKS` (comma-separated)
+    /// fill in. Unset variables keep the base value.
+    pub fn from_env() -> Result<Self, O2Error> {
+        Self::from_lookup(|var| std::env::var(var).ok())
+    }
+
+    /// `self` with any `O2_*` endpoint overrides from the environment applied.
+    /// `O2_NETWORK` is ignored; the base is `self`.
+    pub fn with_env_overrides(self) -> Result<Self, O2Error> {
+        let mut values = env_values(|var| std::env::var(var).ok());
+        values.remove("network");
+        self.apply(values, "environment")
+    }
+
+    /// Endpoints from a TOML file, or JSON if `path` ends in `.json`.
+    ///
+    /// The file holds top-level keys named like the fields, plus an optional
+    /// `network` picking the base endpoints (default `testnet`):
+    ///
+    /// ```toml
+    /// network = "devnet"
+    /// api_base = "https://o2.staging.internal"
+    /// ws_url = "wss://o2.staging.internal/v1/ws"
+    /// faucet_url = ""          # no faucet
+    /// whitelist_required = false
+    /// chain_id = 0
+    /// api_base_fallbacks = ["https://o2-eu.staging.internal"]
+    /// ```
+    ///
+    /// Unknown keys are an error, so a misspelt override is not silently
+    /// ignored.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| O2Error::Other(format!("cannot read {}: {e}", path.display())))?;
+        let origin = path.display().to_string();
+        let values = if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            match serde_json::from_str(&text) {
+                Ok(Value::Object(map)) => map,
+                Ok(_) => return Err(O2Error::Other(format!("{origin}: expected a JSON object"))),
+                Err(e) => return Err(O2Error::Other(format!("{origin}: {e}"))),
+            }
+        } else {
+            parse_toml(&text).map_err(|e| O2Error::Other(format!("{origin}: {e}")))?
+        };
+        Self::from_values(values, &origin)
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, O2Error> {
+        Self::from_values(env_values(lookup), "environment")
+    }
+
+    /// The `network` base with the remaining `values` applied.
+    fn from_values(mut values: Map<String, Value>, origin: &str) -> Result<Self, O2Error> {
+        let network = match values.remove("network") {
+            None => Network::Testnet,
+            Some(value) => value
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid(origin, "network", &value))?,
+        };
+        Self::from_network(network).apply(values, origin)
+    }
+
+    fn apply(mut self, values: Map<String, Value>, origin: &str) -> Result<Self, O2Error> {
+        for (key, value) in values {
+            let bad = || invalid(origin, &key, &value);
+            match key.as_str() {
+                "api_base" => self.api_base = value.as_str().ok_or_else(bad)?.to_string(),
+                "ws_url" => self.ws_url = value.as_str().ok_or_else(bad)?.to_string(),
+                "fuel_rpc" => self.fuel_rpc = value.as_str().ok_or_else(bad)?.to_string(),
+                "faucet_url" => {
+                    self.faucet_url = match &value {
+                        Value::Null => None,
+                        _ => Some(value.as_str().ok_or_else(bad)?)
+                            .filter(|url| !url.is_empty())
+                            .map(str::to_string),
+                    }
+                }
+                "whitelist_required" => {
+                    self.whitelist_required = match &value {
+                        Value::Bool(b) => *b,
+                        Value::String(s) => match s.to_ascii_lowercase().as_str() {
+                            "true" | "1" => true,
+                            "false" | "0" => false,
+                            _ => return Err(bad()),
+                        },
+                        _ => return Err(bad()),
+                    }
+                }
+                "chain_id" => {
+                    self.chain_id = match &value {
+                        Value::Null => None,
+                        Value::Number(n) => Some(n.as_u64().ok_or_else(bad)?),
+                        Value::String(s) if s.is_empty() => None,
+                        Value::String(s) => Some(parse_u64(s).ok_or_else(bad)?),
+                        _ => return Err(bad()),
+                    }
+                }
+                "api_base_fallbacks" => self.api_base_fallbacks = urls(&value).ok_or_else(bad)?,
+                "ws_url_fallbacks" => self.ws_url_fallbacks = urls(&value).ok_or_else(bad)?,
+                _ => return Err(O2Error::Other(format!("{origin}: unknown key `{key}`"))),
+            }
+        }
+        Ok(self)
+    }
 }
 
 impl Default for NetworkConfig {
@@ -50,3 +208,249 @@ impl Default for NetworkConfig {
         Self::from_network(Network::Testnet)
     }
 }
+
+/// Keys [`NetworkConfig::from_file`] accepts; the environment variable for
+/// each is `O2_` and the key in upper case.
+const KEYS: [&str; 9] = [
+    "network",
+    "api_base",
+    "ws_url",
+    "fuel_rpc",
+    "faucet_url",
+    "whitelist_required",
+    "chain_id",
+    "api_base_fallbacks",
+    "ws_url_fallbacks",
+];
+
+fn env_values(lookup: impl Fn(&str) -> Option<String>) -> Map<String, Value> {
+    KEYS.iter()
+        .filter_map(|key| {
+            let value = lookup(&format!("O2_{}", key.to_ascii_uppercase()))?;
+            Some((key.to_string(), Value::String(value)))
+        })
+        .collect()
+}
+
+/// An array of URLs, or one comma-separated string.
+fn urls(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Value::String(s) => Some(
+            s.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn invalid(origin: &str, key: &str, value: &Value) -> O2Error {
+    O2Error::Other(format!("{origin}: invalid value for `{key}`: {value}"))
+}
+
+/// A decimal or `0x` hex integer.
+fn parse_u64(s: &str) -> Option<u64> {
+    let s = s.trim().replace('_', "");
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Top-level `key = value` pairs of a TOML document. Only what a network
+/// config needs is understood: strings, booleans, integers, one-line arrays
+/// and comments.
+fn parse_toml(text: &str) -> Result<Map<String, Value>, String> {
+    let mut values = Map::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: &str| format!("line {}: {msg}", n + 1);
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| err("expected `key = value`"))?;
+        let key = key.trim().trim_matches('"');
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(err("expected a bare key"));
+        }
+        let (value, trailing) = toml_value(rest.trim()).ok_or_else(|| err("invalid value"))?;
+        let trailing = trailing.trim();
+        if !(trailing.is_empty() || trailing.starts_with('#')) {
+            return Err(err("unexpected text after value"));
+        }
+        if values.insert(key.to_string(), value).is_some() {
+            return Err(err(&format!("duplicate key `{key}`")));
+        }
+    }
+    Ok(values)
+}
+
+/// The value at the start of `s` and what follows it.
+fn toml_value(s: &str) -> Option<(Value, &str)> {
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Some((Value::Array(items), after));
+            }
+            let (item, after) = toml_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after,
+                None => rest = rest.starts_with(']').then_some(rest)?,
+            }
+        }
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end = rest.find('\'')?;
+        return Some((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Some((Value::String(out), &rest[i + 1..])),
+                '\\' => out.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '"' => '"',
+                    '\\' => '\\',
+                    _ => return None,
+                }),
+                _ => out.push(c),
+            }
+        }
+        return None;
+    }
+    let end = s
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .unwrap_or(s.len());
+    let (token, rest) = s.split_at(end);
+    let value = match token {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::from(parse_u64(token)?),
+    };
+    Some((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn env_overrides_the_named_network() {
+        let vars: HashMap<&str, &str> = [
+            ("O2_NETWORK", "Devnet"),
+            ("O2_API_BASE", "https://o2.staging.internal"),
+            ("O2_FAUCET_URL", ""),
+            ("O2_WHITELIST_REQUIRED", "1"),
+            ("O2_CHAIN_ID", "0x2a"),
+            (
+                "O2_WS_URL_FALLBACKS",
+                "wss://a.example/ws, wss://b.example/ws",
+            ),
+        ]
+        .into();
+        let config =
+            NetworkConfig::from_lookup(|var| vars.get(var).map(|v| v.to_string())).unwrap();
+        let devnet = NetworkConfig::from_network(Network::Devnet);
+        assert_eq!(config.api_base, "https://o2.staging.internal");
+        assert_eq!(config.ws_url, devnet.ws_url);
+        assert_eq!(config.faucet_url, None);
+        assert!(config.whitelist_required);
+        assert_eq!(config.chain_id, Some(42));
+        assert_eq!(
+            config.ws_url_fallbacks,
+            ["wss://a.example/ws", "wss://b.example/ws"]
+        );
+
+        let err = NetworkConfig::from_lookup(|var| {
+            (var == "O2_WHITELIST_REQUIRED").then(|| "maybe".to_string())
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("whitelist_required"), "{err}");
+    }
+
+    #[test]
+    fn toml_and_json_files_give_the_same_config() {
+        let dir = std::env::temp_dir();
+        let toml = dir.join(format!("o2-config-test-{}.toml", std::process::id()));
+        let json = dir.join(format!("o2-config-test-{}.json", std::process::id()));
+        std::fs::write(
+            &toml,
+            r#"
+# staging gateway
+network = "mainnet"
+api_base = "https://gw.example.com"   # self-hosted
+ws_url = 'wss://gw.example.com/v1/ws'
+faucet_url = "https://gw.example.com/faucet"
+whitelist_required = true
+chain_id = 1_000
+api_base_fallbacks = ["https://eu.example.com", 'https://us.example.com', ]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &json,
+            r#"{"network": "mainnet", "api_base": "https://gw.example.com",
+                "ws_url": "wss://gw.example.com/v1/ws",
+                "faucet_url": "https://gw.example.com/faucet",
+                "whitelist_required": true, "chain_id": 1000,
+                "api_base_fallbacks": ["https://eu.example.com", "https://us.example.com"]}"#,
+        )
+        .unwrap();
+
+        let from_toml = NetworkConfig::from_file(&toml).unwrap();
+        let from_json = NetworkConfig::from_file(&json).unwrap();
+        std::fs::remove_file(&toml).unwrap();
+        std::fs::remove_file(&json).unwrap();
+        for config in [from_toml, from_json] {
+            assert_eq!(config.api_base, "https://gw.example.com");
+            assert_eq!(config.ws_url, "wss://gw.example.com/v1/ws");
+            assert_eq!(
+                config.fuel_rpc,
+                NetworkConfig::from_network(Network::Mainnet).fuel_rpc
+            );
+            assert_eq!(
+                config.faucet_url.as_deref(),
+                Some("https://gw.example.com/faucet")
+            );
+            assert!(config.whitelist_required);
+            assert_eq!(config.chain_id, Some(1000));
+            assert_eq!(
+                config.api_base_fallbacks,
+                ["https://eu.example.com", "https://us.example.com"]
+            );
+        }
+    }
+
+    #[test]
+    fn toml_errors_name_the_line() {
+        assert_eq!(
+            parse_toml("api_base = \"x\"\n[gateway]\n").unwrap_err(),
+            "line 2: expected `key = value`"
+        );
+        assert_eq!(
+            parse_toml("chain_id = 1 2\n").unwrap_err(),
+            "line 1: unexpected text after value"
+        );
+        let err =
+            NetworkConfig::from_values(parse_toml("api_url = \"x\"").unwrap(), "cfg").unwrap_err();
+        assert_eq!(err.to_string(), "cfg: unknown key `api_url`");
+    }
+}
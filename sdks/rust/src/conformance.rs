@@ -0,0 +1,496 @@
+//! Golden-vector conformance checks for signing payloads.
+//!
+//! The Rust, TypeScript, and Python SDKs must produce byte-identical signing
+//! payloads for `set_session`, session actions, and withdrawals, or the
+//! contracts reject their signatures. The shared vectors in `conformance/vectors/`
+//! at the repository root pin those layouts: each vector gives the inputs, the
+//! expected signing bytes, and (when a signer key is given) the expected
+//! signature. Signatures are deterministic (RFC 6979), so they can be compared
+//! exactly.
+//!
+//! ```rust,ignore
+//! let vectors = o2_sdk::conformance::load("conformance/vectors")?;
+//! let report = o2_sdk::conformance::run(&vectors);
+//! assert!(report.is_success(), "{report}");
+//! ```
+//!
+//! All `u64` fields accept a JSON number or a decimal/`0x`-hex string; the
+//! shared files use strings so JavaScript readers keep full precision. Byte
+//! fields are `0x`-prefixed hex.
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::crypto::{evm_personal_sign, parse_hex_32, personal_sign, raw_sign, to_hex_string};
+use crate::encoding::{
+    build_actions_signing_bytes, build_session_signing_bytes, build_withdraw_signing_bytes,
+    cancel_order_to_call, create_order_to_call, function_selector, identity_parts,
+    settle_balance_to_call, CallArg, OrderTypeEncoding,
+};
+use crate::errors::O2Error;
+use crate::models::{deserialize_string_or_u64, Identity};
+
+/// Latest vector file format understood by [`load`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A file of conformance vectors.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VectorFile {
+    pub version: u32,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub vectors: Vec<Vector>,
+}
+
+/// One input → expected output case.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Vector {
+    /// Unique, human-readable identifier, e.g. `"actions/create_order/limit"`.
+    pub name: String,
+    pub input: VectorInput,
+    /// Key and scheme to sign with. Without one only the signing bytes are checked.
+    #[serde(default)]
+    pub signer: Option<Signer>,
+    pub expected: Expected,
+}
+
+/// The payload a vector encodes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VectorInput {
+    Session {
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        nonce: u64,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        chain_id: u64,
+        session_address: String,
+        contract_ids: Vec<String>,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        expiry: u64,
+    },
+    Actions {
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        nonce: u64,
+        calls: Vec<VectorCall>,
+    },
+    Withdraw {
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        nonce: u64,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        chain_id: u64,
+        to: Identity,
+        asset_id: String,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        amount: u64,
+    },
+}
+
+/// A contract call within an actions payload.
+///
+/// The typed variants go through the SDK's call builders, so they also cover
+/// call-data encoding and the buy-side quote amount. `raw` pins a [`CallArg`]
+/// directly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum VectorCall {
+    CreateOrder {
+        contract_id: String,
+        /// `"Buy"` or `"Sell"`.
+        side: String,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        price: u64,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        quantity: u64,
+        order_type: VectorOrderType,
+        base_decimals: u32,
+        base_asset: String,
+        quote_asset: String,
+    },
+    CancelOrder {
+        contract_id: String,
+        order_id: String,
+    },
+    SettleBalance {
+        contract_id: String,
+        to: Identity,
+    },
+    Raw {
+        contract_id: String,
+        /// Function name; encoded with [`function_selector`].
+        function: String,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        amount: u64,
+        asset_id: String,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        gas: u64,
+        #[serde(default)]
+        call_data: Option<String>,
+    },
+}
+
+/// Order type of a `create_order` call, in chain units.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VectorOrderType {
+    Spot,
+    Market,
+    FillOrKill,
+    PostOnly,
+    Limit {
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        price: u64,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        timestamp: u64,
+    },
+    BoundedMarket {
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        max_price: u64,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        min_price: u64,
+    },
+}
+
+/// Signing scheme applied to the signing bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignScheme {
+    /// Fuel `personalSign` (owner signatures for sessions and withdrawals).
+    FuelPersonal,
+    /// Ethereum `personal_sign` (EVM owners).
+    EvmPersonal,
+    /// SHA-256 of the bytes, no prefix (session-key signatures for actions).
+    Raw,
+}
+
+/// Key used to produce a vector's signature.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Signer {
+    pub scheme: SignScheme,
+    /// `0x`-prefixed 32-byte secp256k1 private key. Test keys only.
+    pub private_key: String,
+}
+
+/// Expected outputs, as `0x`-prefixed hex.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Expected {
+    pub signing_bytes: String,
+    /// 64-byte Fuel compact signature. Required when `signer` is set.
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// What this SDK produces for a vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub signing_bytes: Vec<u8>,
+    pub signature: Option<[u8; 64]>,
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, O2Error> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    hex::decode(s).map_err(|e| O2Error::CryptoError(format!("Invalid hex: {e}")))
+}
+
+impl VectorOrderType {
+    fn to_encoding(&self) -> OrderTypeEncoding {
+        match *self {
+            VectorOrderType::Spot => OrderTypeEncoding::Spot,
+            VectorOrderType::Market => OrderTypeEncoding::Market,
+            VectorOrderType::FillOrKill => OrderTypeEncoding::FillOrKill,
+            VectorOrderType::PostOnly => OrderTypeEncoding::PostOnly,
+            VectorOrderType::Limit { price, timestamp } => {
+                OrderTypeEncoding::Limit { price, timestamp }
+            }
+            VectorOrderType::BoundedMarket {
+                max_price,
+                min_price,
+            } => OrderTypeEncoding::BoundedMarket {
+                max_price,
+                min_price,
+            },
+        }
+    }
+}
+
+impl VectorCall {
+    fn to_call(&self) -> Result<CallArg, O2Error> {
+        match self {
+            VectorCall::CreateOrder {
+                contract_id,
+                side,
+                price,
+                quantity,
+                order_type,
+                base_decimals,
+                base_asset,
+                quote_asset,
+            } => {
+                if side != "Buy" && side != "Sell" {
+                    return Err(O2Error::InvalidRequest(format!(
+                        "Invalid side {side:?}; expected \"Buy\" or \"Sell\""
+                    )));
+                }
+                Ok(create_order_to_call(
+                    &parse_hex_32(contract_id)?,
+                    side,
+                    *price,
+                    *quantity,
+                    &order_type.to_encoding(),
+                    *base_decimals,
+                    &parse_hex_32(base_asset)?,
+                    &parse_hex_32(quote_asset)?,
+                ))
+            }
+            VectorCall::CancelOrder {
+                contract_id,
+                order_id,
+            } => Ok(cancel_order_to_call(
+                &parse_hex_32(contract_id)?,
+                &parse_hex_32(order_id)?,
+            )),
+            VectorCall::SettleBalance { contract_id, to } => {
+                let (kind, address) = identity_parts(to)?;
+                Ok(settle_balance_to_call(
+                    &parse_hex_32(contract_id)?,
+                    kind,
+                    &address,
+                ))
+            }
+            VectorCall::Raw {
+                contract_id,
+                function,
+                amount,
+                asset_id,
+                gas,
+                call_data,
+            } => Ok(CallArg {
+                contract_id: parse_hex_32(contract_id)?,
+                function_selector: function_selector(function),
+                amount: *amount,
+                asset_id: parse_hex_32(asset_id)?,
+                gas: *gas,
+                call_data: call_data.as_deref().map(parse_hex).transpose()?,
+            }),
+        }
+    }
+}
+
+impl VectorInput {
+    /// The signing bytes this SDK builds for the input.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, O2Error> {
+        match self {
+            VectorInput::Session {
+                nonce,
+                chain_id,
+                session_address,
+                contract_ids,
+                expiry,
+            } => {
+                let contract_ids = contract_ids
+                    .iter()
+                    .map(|c| parse_hex_32(c))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(build_session_signing_bytes(
+                    *nonce,
+                    *chain_id,
+                    &parse_hex_32(session_address)?,
+                    &contract_ids,
+                    *expiry,
+                ))
+            }
+            VectorInput::Actions { nonce, calls } => {
+                let calls = calls
+                    .iter()
+                    .map(VectorCall::to_call)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(build_actions_signing_bytes(*nonce, &calls))
+            }
+            VectorInput::Withdraw {
+                nonce,
+                chain_id,
+                to,
+                asset_id,
+                amount,
+            } => {
+                let (kind, address) = identity_parts(to)?;
+                Ok(build_withdraw_signing_bytes(
+                    *nonce,
+                    *chain_id,
+                    kind,
+                    &address,
+                    &parse_hex_32(asset_id)?,
+                    *amount,
+                ))
+            }
+        }
+    }
+}
+
+impl Signer {
+    /// Sign `message` with this signer's key and scheme.
+    pub fn sign(&self, message: &[u8]) -> Result<[u8; 64], O2Error> {
+        let key = parse_hex_32(&self.private_key)?;
+        match self.scheme {
+            SignScheme::FuelPersonal => personal_sign(&key, message),
+            SignScheme::EvmPersonal => evm_personal_sign(&key, message),
+            SignScheme::Raw => raw_sign(&key, message),
+        }
+    }
+}
+
+impl Vector {
+    /// Compute this SDK's output for the vector's input.
+    pub fn compute(&self) -> Result<Output, O2Error> {
+        let signing_bytes = self.input.signing_bytes()?;
+        let signature = match &self.signer {
+            Some(signer) => Some(signer.sign(&signing_bytes)?),
+            None => None,
+        };
+        Ok(Output {
+            signing_bytes,
+            signature,
+        })
+    }
+
+    /// Compare this SDK's output with the expected one.
+    ///
+    /// Returns a description of the first mismatch.
+    pub fn check(&self) -> Result<(), String> {
+        let output = self
+            .compute()
+            .map_err(|e| format!("failed to compute: {e}"))?;
+        let expected = parse_hex(&self.expected.signing_bytes)
+            .map_err(|e| format!("bad expected.signing_bytes: {e}"))?;
+        if output.signing_bytes != expected {
+            return Err(format!(
+                "signing bytes differ{}\n  expected: {}\n  actual:   {}",
+                first_difference(&expected, &output.signing_bytes),
+                self.expected.signing_bytes,
+                to_hex_string(&output.signing_bytes)
+            ));
+        }
+        match (&output.signature, &self.expected.signature) {
+            (Some(actual), Some(expected)) => {
+                let expected_bytes =
+                    parse_hex(expected).map_err(|e| format!("bad expected.signature: {e}"))?;
+                if actual[..] != expected_bytes[..] {
+                    return Err(format!(
+                        "signature differs\n  expected: {}\n  actual:   {}",
+                        expected,
+                        to_hex_string(actual)
+                    ));
+                }
+            }
+            (Some(_), None) => return Err("signer given without expected.signature".into()),
+            (None, Some(_)) => return Err("expected.signature given without signer".into()),
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+fn first_difference(expected: &[u8], actual: &[u8]) -> String {
+    match expected.iter().zip(actual).position(|(a, b)| a != b) {
+        Some(i) => format!(" at byte {i}"),
+        None => format!(" in length ({} vs {})", expected.len(), actual.len()),
+    }
+}
+
+/// Outcome for one vector.
+#[derive(Debug, Clone)]
+pub struct VectorResult {
+    pub name: String,
+    /// `None` if the vector passed.
+    pub failure: Option<String>,
+}
+
+/// Outcome of [`run`].
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    pub results: Vec<VectorResult>,
+}
+
+impl ConformanceReport {
+    /// True if every vector passed.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.failure.is_none())
+    }
+
+    /// Number of vectors that passed.
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.failure.is_none()).count()
+    }
+
+    /// Vectors that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &VectorResult> {
+        self.results.iter().filter(|r| r.failure.is_some())
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} vectors passed", self.passed(), self.results.len())?;
+        for result in self.failures() {
+            write!(
+                f,
+                "\n{}: {}",
+                result.name,
+                result.failure.as_deref().unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Check every vector against this SDK.
+pub fn run(vectors: &[Vector]) -> ConformanceReport {
+    ConformanceReport {
+        results: vectors
+            .iter()
+            .map(|v| VectorResult {
+                name: v.name.clone(),
+                failure: v.check().err(),
+            })
+            .collect(),
+    }
+}
+
+/// Parse a vector file.
+pub fn from_json(json: &str) -> Result<VectorFile, O2Error> {
+    let file: VectorFile = serde_json::from_str(json)?;
+    if file.version > FORMAT_VERSION {
+        return Err(O2Error::InvalidRequest(format!(
+            "Unsupported conformance vector version {} (latest is {FORMAT_VERSION})",
+            file.version
+        )));
+    }
+    Ok(file)
+}
+
+/// Load vectors from a JSON file, or from every `.json` file in a directory.
+///
+/// Directory entries are read in file-name order.
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Vector>, O2Error> {
+    let path = path.as_ref();
+    let read = |p: &Path| {
+        std::fs::read_to_string(p)
+            .map_err(|e| O2Error::Other(format!("Failed to read {}: {e}", p.display())))
+    };
+    if !path.is_dir() {
+        return Ok(from_json(&read(path)?)?.vectors);
+    }
+    let mut files = std::fs::read_dir(path)
+        .map_err(|e| O2Error::Other(format!("Failed to read {}: {e}", path.display())))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+    let mut vectors = Vec::new();
+    for file in files {
+        let parsed = from_json(&read(&file)?)
+            .map_err(|e| O2Error::JsonError(format!("{}: {e}", file.display())))?;
+        vectors.extend(parsed.vectors);
+    }
+    Ok(vectors)
+}
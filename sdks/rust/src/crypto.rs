@@ -1,18 +1,24 @@
-use secp256k1::ecdsa::RecoverableSignature;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 /// Cryptographic operations for O2 Exchange: key generation, signing, and address derivation.
 ///
 /// Implements:
 /// - Fuel-native key generation (SHA-256 address derivation)
 /// - EVM key generation (keccak256 address derivation)
+/// - Loading either from a BIP-39 mnemonic (see [`crate::mnemonic`])
 /// - personalSign (Fuel prefix + SHA-256)
 /// - rawSign (plain SHA-256)
 /// - evm_personal_sign (Ethereum prefix + keccak256)
 /// - fuel_compact_sign with low-s normalization and recovery ID in MSB of byte 32
+/// - verify_personal_sign, recovering the signer under either personalSign scheme
+/// - PrivateKey, which zeroes key bytes on drop and keeps them out of Debug output
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
 use sha2::{Digest as Sha256Digest, Sha256};
 use sha3::Keccak256;
+use zeroize::Zeroize;
+pub use zeroize::Zeroizing;
 
 use crate::errors::O2Error;
+use crate::mnemonic;
 
 /// Half of the secp256k1 group order, used for low-s normalization.
 const SECP256K1_ORDER_HALF: [u8; 32] = [
@@ -26,10 +32,59 @@ const SECP256K1_ORDER: [u8; 32] = [
     0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x41,
 ];
 
+/// A secp256k1 private key that is zeroed when dropped and redacted from
+/// `Debug` output.
+///
+/// Clones are zeroed independently. [`PrivateKey::expose_secret`] and
+/// [`PrivateKey::expose_hex`] hand out the raw key for callers that need it;
+/// anything copied out of them is no longer covered.
+#[derive(Clone)]
+pub struct PrivateKey([u8; 32]);
+
+impl PrivateKey {
+    /// Take ownership of raw key bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parse a "0x"-prefixed (or bare) 64-character hex key.
+    pub fn from_hex(s: &str) -> Result<Self, O2Error> {
+        parse_hex_32(s).map(Self)
+    }
+
+    /// The raw key bytes.
+    pub fn expose_secret(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The key as a "0x"-prefixed hex string, zeroed when dropped.
+    pub fn expose_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(to_hex_string(&self.0))
+    }
+}
+
+impl From<[u8; 32]> for PrivateKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PrivateKey(<redacted>)")
+    }
+}
+
 /// A Fuel-native wallet with SHA-256 derived B256 address.
 #[derive(Debug, Clone)]
 pub struct Wallet {
-    pub private_key: [u8; 32],
+    pub private_key: PrivateKey,
     pub public_key: [u8; 65],
     pub b256_address: [u8; 32],
 }
@@ -37,7 +92,7 @@ pub struct Wallet {
 /// An EVM-compatible wallet with keccak256 derived address, zero-padded to B256.
 #[derive(Debug, Clone)]
 pub struct EvmWallet {
-    pub private_key: [u8; 32],
+    pub private_key: PrivateKey,
     pub public_key: [u8; 65],
     pub evm_address: [u8; 20],
     pub b256_address: [u8; 32],
@@ -54,7 +109,7 @@ pub fn generate_keypair() -> Result<Wallet, O2Error> {
     let address = Sha256::digest(&pubkey_bytes[1..65]);
 
     Ok(Wallet {
-        private_key: secret_key.secret_bytes(),
+        private_key: PrivateKey(secret_key.secret_bytes()),
         public_key: pubkey_bytes,
         b256_address: address.into(),
     })
@@ -78,7 +133,7 @@ pub fn generate_evm_keypair() -> Result<EvmWallet, O2Error> {
     b256_address[12..32].copy_from_slice(&evm_address);
 
     Ok(EvmWallet {
-        private_key: secret_key.secret_bytes(),
+        private_key: PrivateKey(secret_key.secret_bytes()),
         public_key: pubkey_bytes,
         evm_address,
         b256_address,
@@ -95,7 +150,7 @@ pub fn load_wallet(private_key: &[u8; 32]) -> Result<Wallet, O2Error> {
     let address = Sha256::digest(&pubkey_bytes[1..65]);
 
     Ok(Wallet {
-        private_key: *private_key,
+        private_key: PrivateKey(*private_key),
         public_key: pubkey_bytes,
         b256_address: address.into(),
     })
@@ -117,13 +172,37 @@ pub fn load_evm_wallet(private_key: &[u8; 32]) -> Result<EvmWallet, O2Error> {
     b256_address[12..32].copy_from_slice(&evm_address);
 
     Ok(EvmWallet {
-        private_key: *private_key,
+        private_key: PrivateKey(*private_key),
         public_key: pubkey_bytes,
         evm_address,
         b256_address,
     })
 }
 
+impl Wallet {
+    /// Load a Fuel-native wallet from a BIP-39 phrase and derivation path,
+    /// e.g. [`FUEL_DERIVATION_PATH`](crate::mnemonic::FUEL_DERIVATION_PATH).
+    pub fn from_mnemonic(phrase: &str, derivation_path: &str) -> Result<Self, O2Error> {
+        let seed = Zeroizing::new(mnemonic::mnemonic_to_seed(phrase, "")?);
+        load_wallet(&Zeroizing::new(mnemonic::derive_private_key(
+            seed.as_slice(),
+            derivation_path,
+        )?))
+    }
+}
+
+impl EvmWallet {
+    /// Load an EVM wallet from a BIP-39 phrase and derivation path,
+    /// e.g. [`EVM_DERIVATION_PATH`](crate::mnemonic::EVM_DERIVATION_PATH).
+    pub fn from_mnemonic(phrase: &str, derivation_path: &str) -> Result<Self, O2Error> {
+        let seed = Zeroizing::new(mnemonic::mnemonic_to_seed(phrase, "")?);
+        load_evm_wallet(&Zeroizing::new(mnemonic::derive_private_key(
+            seed.as_slice(),
+            derivation_path,
+        )?))
+    }
+}
+
 /// Compare two 32-byte big-endian numbers: returns true if a > b.
 fn gt_be(a: &[u8; 32], b: &[u8; 32]) -> bool {
     for i in 0..32 {
@@ -188,6 +267,11 @@ pub fn fuel_compact_sign(private_key: &[u8; 32], digest: &[u8; 32]) -> Result<[u
 /// prefix = b"\x19Fuel Signed Message:\n" + str(len(message)) + message
 /// digest = sha256(prefix)
 pub fn personal_sign(private_key: &[u8; 32], message: &[u8]) -> Result<[u8; 64], O2Error> {
+    fuel_compact_sign(private_key, &personal_sign_digest(message))
+}
+
+/// Digest signed by [`personal_sign`].
+fn personal_sign_digest(message: &[u8]) -> [u8; 32] {
     let prefix = b"\x19Fuel Signed Message:\n";
     let length_str = message.len().to_string();
 
@@ -195,9 +279,7 @@ pub fn personal_sign(private_key: &[u8; 32], message: &[u8]) -> Result<[u8; 64],
     hasher.update(prefix);
     hasher.update(length_str.as_bytes());
     hasher.update(message);
-    let digest: [u8; 32] = hasher.finalize().into();
-
-    fuel_compact_sign(private_key, &digest)
+    hasher.finalize().into()
 }
 
 /// Sign using raw SHA-256 hash, no prefix (for session actions).
@@ -211,14 +293,86 @@ pub fn raw_sign(private_key: &[u8; 32], message: &[u8]) -> Result<[u8; 64], O2Er
 /// prefix = "\x19Ethereum Signed Message:\n" + str(len(message))
 /// digest = keccak256(prefix_bytes + message)
 pub fn evm_personal_sign(private_key: &[u8; 32], message: &[u8]) -> Result<[u8; 64], O2Error> {
+    fuel_compact_sign(private_key, &evm_personal_sign_digest(message))
+}
+
+/// Digest signed by [`evm_personal_sign`].
+fn evm_personal_sign_digest(message: &[u8]) -> [u8; 32] {
     let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
 
     let mut hasher = Keccak256::new();
     hasher.update(prefix.as_bytes());
     hasher.update(message);
-    let digest: [u8; 32] = hasher.finalize().into();
+    hasher.finalize().into()
+}
 
-    fuel_compact_sign(private_key, &digest)
+/// Recover the uncompressed public key (65 bytes, 0x04 prefix) that made a
+/// Fuel compact signature over `digest`.
+pub fn recover_fuel_compact(digest: &[u8; 32], signature: &[u8; 64]) -> Result<[u8; 65], O2Error> {
+    let mut compact = *signature;
+    let recovery_id = i32::from(compact[32] >> 7);
+    compact[32] &= 0x7F;
+    let recovery_id = RecoveryId::from_i32(recovery_id)
+        .map_err(|e| O2Error::CryptoError(format!("Invalid recovery id: {e}")))?;
+    let signature = RecoverableSignature::from_compact(&compact, recovery_id)
+        .map_err(|e| O2Error::CryptoError(format!("Invalid signature: {e}")))?;
+    let public_key = Secp256k1::new()
+        .recover_ecdsa(&Message::from_digest(*digest), &signature)
+        .map_err(|e| O2Error::CryptoError(format!("Cannot recover public key: {e}")))?;
+    Ok(public_key.serialize_uncompressed())
+}
+
+/// The personal_sign scheme a signature was made under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersonalSignScheme {
+    /// [`personal_sign`]: Fuel prefix + SHA-256, SHA-256 address.
+    Fuel,
+    /// [`evm_personal_sign`]: Ethereum prefix + keccak256, keccak256 address
+    /// zero-padded to B256.
+    Evm,
+}
+
+/// Check that `signature` is a personal_sign of `message` by the owner whose
+/// B256 address is `expected_address`, under either scheme.
+///
+/// Returns the scheme that matched. On a mismatch the error names the
+/// addresses the signature recovers to, which usually shows whether the
+/// signer hashed the wrong bytes or holds a different key.
+pub fn verify_personal_sign(
+    message: &[u8],
+    signature: &[u8; 64],
+    expected_address: &[u8; 32],
+) -> Result<PersonalSignScheme, O2Error> {
+    let fuel = recover_fuel_compact(&personal_sign_digest(message), signature)
+        .map(|public_key| address_from_pubkey(&public_key));
+    if fuel
+        .as_ref()
+        .is_ok_and(|address| address == expected_address)
+    {
+        return Ok(PersonalSignScheme::Fuel);
+    }
+    let evm =
+        recover_fuel_compact(&evm_personal_sign_digest(message), signature).map(|public_key| {
+            let mut address = [0u8; 32];
+            address[12..].copy_from_slice(&evm_address_from_pubkey(&public_key));
+            address
+        });
+    if evm
+        .as_ref()
+        .is_ok_and(|address| address == expected_address)
+    {
+        return Ok(PersonalSignScheme::Evm);
+    }
+    let describe = |recovered: Result<[u8; 32], O2Error>| match recovered {
+        Ok(address) => to_hex_string(&address),
+        Err(e) => e.to_string(),
+    };
+    Err(O2Error::CryptoError(format!(
+        "Signature does not match owner {}: recovers to {} (Fuel) or {} (EVM)",
+        to_hex_string(expected_address),
+        describe(fuel),
+        describe(evm)
+    )))
 }
 
 /// Trait for wallets that can sign messages for O2 Exchange operations.
@@ -239,7 +393,7 @@ impl SignableWallet for Wallet {
         &self.b256_address
     }
     fn personal_sign(&self, message: &[u8]) -> Result<[u8; 64], O2Error> {
-        personal_sign(&self.private_key, message)
+        personal_sign(self.private_key.expose_secret(), message)
     }
 }
 
@@ -248,7 +402,7 @@ impl SignableWallet for EvmWallet {
         &self.b256_address
     }
     fn personal_sign(&self, message: &[u8]) -> Result<[u8; 64], O2Error> {
-        evm_personal_sign(&self.private_key, message)
+        evm_personal_sign(self.private_key.expose_secret(), message)
     }
 }
 
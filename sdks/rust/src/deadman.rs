@@ -0,0 +1,646 @@
+//! Client-side dead-man's switch.
+//!
+//! O2 has no server-side cancel-on-disconnect. A [`DeadmanSwitch`] emulates it:
+//! the strategy loop must [`feed`](DeadmanSwitch::feed) the switch regularly, and
+//! if it stops doing so (hung task, crashed loop), the switch cancels all open
+//! orders on the configured markets. It also fires when the WebSocket has been
+//! reconnecting for longer than [`DeadmanConfig::disconnect_grace`], on permanent
+//! WebSocket disconnect, and on SIGTERM (Unix only). A WebSocket that is
+//! [drained](crate::O2Client::drain) for a planned shutdown is not a lost
+//! connection: once the switch sees the drain start, it stops watching the
+//! socket and keeps only its heartbeat and SIGTERM triggers.
+//!
+//! Cancellation runs on a dedicated [`O2Client`] with a separate *emergency*
+//! session, so it does not contend with the strategy for the client or its
+//! session. The trade account nonce is shared between sessions, so the switch
+//! refreshes the emergency session's nonce before cancelling. A lost connection
+//! often affects REST too, so markets whose cancel fails are retried for
+//! [`DeadmanConfig::cancel_retry_for`]. With [`DeadmanConfig::revoke_session`]
+//! set, the trading session is then revoked as well.
+//!
+//! The switch fires at most once. When it fires on SIGTERM the default
+//! termination is suppressed; wait on [`DeadmanSwitch::wait`] and exit the
+//! process once the report is available.
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{broadcast, watch, Notify};
+
+use crate::client::O2Client;
+use crate::crypto::SignableWallet;
+use crate::errors::O2Error;
+use crate::models::{MarketSymbol, Session};
+use crate::runtime::{self, Task};
+use crate::trace::{debug, warn};
+use crate::websocket::WsLifecycleEvent;
+
+/// Configuration for a [`DeadmanSwitch`].
+#[derive(Debug, Clone)]
+pub struct DeadmanConfig {
+    /// Fire if the switch has not been fed for this long.
+    pub timeout: Duration,
+    /// How often the heartbeat age is checked.
+    pub check_interval: Duration,
+    /// Fire when the shared WebSocket gives up reconnecting, or has been
+    /// reconnecting for longer than `disconnect_grace`.
+    pub cancel_on_ws_disconnect: bool,
+    /// How long the WebSocket may stay down before the switch fires.
+    pub disconnect_grace: Duration,
+    /// Fire on SIGTERM. Ignored on non-Unix targets.
+    pub cancel_on_sigterm: bool,
+    /// After firing, keep retrying markets whose cancel failed for this long.
+    pub cancel_retry_for: Duration,
+    /// After cancelling, revoke the trading session with the owner's key.
+    pub revoke_session: Option<SessionRevocation>,
+}
+
+impl Default for DeadmanConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            check_interval: Duration::from_secs(1),
+            cancel_on_ws_disconnect: true,
+            disconnect_grace: Duration::from_secs(10),
+            cancel_on_sigterm: true,
+            cancel_retry_for: Duration::from_secs(30),
+            revoke_session: None,
+        }
+    }
+}
+
+/// The owner key used to revoke the trading session when the switch fires.
+///
+/// Revoking submits a new session for the account that is scoped to no
+/// markets and expires immediately, replacing the one the strategy trades
+/// with, so a strategy that comes back after the switch fired cannot resume
+/// quoting until a fresh session is created.
+#[derive(Clone)]
+pub struct SessionRevocation {
+    owner: Arc<dyn SignableWallet + Send + Sync>,
+}
+
+impl SessionRevocation {
+    pub fn new<W: SignableWallet + Send + Sync + 'static>(owner: W) -> Self {
+        Self {
+            owner: Arc::new(owner),
+        }
+    }
+
+    async fn revoke(&self, client: &mut O2Client) -> Result<(), O2Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        client
+            .create_session_until(self, &[] as &[&str], now + 1)
+            .await
+            .map(|_| ())
+    }
+}
+
+impl SignableWallet for SessionRevocation {
+    fn b256_address(&self) -> &[u8; 32] {
+        self.owner.b256_address()
+    }
+
+    fn personal_sign(&self, message: &[u8]) -> Result<[u8; 64], O2Error> {
+        self.owner.personal_sign(message)
+    }
+}
+
+impl fmt::Debug for SessionRevocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionRevocation")
+            .field("owner", &crate::crypto::to_hex_string(self.b256_address()))
+            .finish()
+    }
+}
+
+/// Why the switch fired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeadmanTrigger {
+    /// The heartbeat was not fed within the configured timeout.
+    HeartbeatTimeout { elapsed: Duration },
+    /// The WebSocket disconnected permanently.
+    WebSocketDisconnected { reason: String },
+    /// The WebSocket was reconnecting for longer than the grace period.
+    ConnectivityLost { down_for: Duration },
+    /// The process received SIGTERM.
+    Signal,
+    /// [`DeadmanSwitch::trigger`] was called.
+    Manual,
+}
+
+/// Outcome of a fired switch.
+#[derive(Debug, Clone)]
+pub struct DeadmanReport {
+    pub trigger: DeadmanTrigger,
+    /// Number of cancel batches submitted across all markets.
+    pub cancel_batches: usize,
+    /// Markets whose cancellation failed, with the error message.
+    pub errors: Vec<(MarketSymbol, String)>,
+    /// Outcome of session revocation, if it was configured.
+    pub revocation: Option<Result<(), String>>,
+}
+
+impl DeadmanReport {
+    /// True if every market was cancelled, and the session revoked if
+    /// configured, without error.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && !matches!(self.revocation, Some(Err(_)))
+    }
+}
+
+/// A cloneable handle for feeding a [`DeadmanSwitch`] from other tasks.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(runtime::now())),
+        }
+    }
+
+    /// Record that the strategy is alive.
+    pub fn feed(&self) {
+        *self.last.lock().unwrap_or_else(|p| p.into_inner()) = runtime::now();
+    }
+
+    /// Time since the last feed.
+    pub fn elapsed(&self) -> Duration {
+        let last = *self.last.lock().unwrap_or_else(|p| p.into_inner());
+        runtime::now().saturating_duration_since(last)
+    }
+}
+
+/// A background task that cancels all open orders unless it is fed regularly.
+///
+/// Dropping the switch disarms it without cancelling.
+pub struct DeadmanSwitch {
+    heartbeat: Heartbeat,
+    manual: Arc<Notify>,
+    report_rx: watch::Receiver<Option<DeadmanReport>>,
+    task: Task<()>,
+}
+
+impl DeadmanSwitch {
+    /// Arm a switch that cancels orders on `markets` using `client` and
+    /// `emergency_session` when it fires.
+    ///
+    /// `client` should be dedicated to the switch (see
+    /// [`O2Client::arm_deadman_switch`]). Pass `lifecycle` to fire on lost
+    /// WebSocket connectivity. Must be called from within a Tokio runtime.
+    pub fn arm(
+        client: O2Client,
+        emergency_session: Session,
+        markets: Vec<MarketSymbol>,
+        config: DeadmanConfig,
+        lifecycle: Option<broadcast::Receiver<WsLifecycleEvent>>,
+    ) -> Self {
+        let heartbeat = Heartbeat::new();
+        let manual = Arc::new(Notify::new());
+        let (report_tx, report_rx) = watch::channel(None);
+
+        let task = runtime::spawn(Self::run(
+            client,
+            emergency_session,
+            markets,
+            config,
+            lifecycle,
+            heartbeat.clone(),
+            manual.clone(),
+            report_tx,
+        ));
+
+        Self {
+            heartbeat,
+            manual,
+            report_rx,
+            task,
+        }
+    }
+
+    /// Record that the strategy is alive.
+    pub fn feed(&self) {
+        self.heartbeat.feed();
+    }
+
+    /// A cloneable handle for feeding the switch from another task.
+    pub fn heartbeat(&self) -> Heartbeat {
+        self.heartbeat.clone()
+    }
+
+    /// Fire the switch now.
+    pub fn trigger(&self) {
+        self.manual.notify_one();
+    }
+
+    /// The report, if the switch has fired and finished cancelling.
+    pub fn report(&self) -> Option<DeadmanReport> {
+        self.report_rx.borrow().clone()
+    }
+
+    /// Wait until the switch has fired and finished cancelling.
+    ///
+    /// Returns `None` if the switch task ended without firing.
+    pub async fn wait(&mut self) -> Option<DeadmanReport> {
+        match self.report_rx.wait_for(|r| r.is_some()).await {
+            Ok(report) => report.clone(),
+            Err(_) => None,
+        }
+    }
+
+    /// True while the switch is armed and has not fired.
+    pub fn is_armed(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn run(
+        mut client: O2Client,
+        mut session: Session,
+        markets: Vec<MarketSymbol>,
+        config: DeadmanConfig,
+        mut lifecycle: Option<broadcast::Receiver<WsLifecycleEvent>>,
+        heartbeat: Heartbeat,
+        manual: Arc<Notify>,
+        report_tx: watch::Sender<Option<DeadmanReport>>,
+    ) {
+        let mut ticker = runtime::interval(config.check_interval);
+        let sigterm = wait_for_sigterm();
+        tokio::pin!(sigterm);
+
+        let mut down_since: Option<Instant> = None;
+        let trigger = loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let elapsed = heartbeat.elapsed();
+                    if elapsed >= config.timeout {
+                        break DeadmanTrigger::HeartbeatTimeout { elapsed };
+                    }
+                    if let Some(since) = down_since {
+                        let down_for = runtime::now().saturating_duration_since(since);
+                        if down_for >= config.disconnect_grace {
+                            break DeadmanTrigger::ConnectivityLost { down_for };
+                        }
+                    }
+                }
+                _ = manual.notified() => break DeadmanTrigger::Manual,
+                event = next_connectivity(&mut lifecycle),
+                    if config.cancel_on_ws_disconnect && lifecycle.is_some() => match event {
+                    Connectivity::Down => {
+                        down_since.get_or_insert_with(runtime::now);
+                    }
+                    Connectivity::Up => down_since = None,
+                    Connectivity::Draining => {
+                        debug!("deadman.ws_draining");
+                        down_since = None;
+                        lifecycle = None;
+                    }
+                    Connectivity::Lost(reason) => {
+                        break DeadmanTrigger::WebSocketDisconnected { reason };
+                    }
+                },
+                _ = &mut sigterm, if config.cancel_on_sigterm => break DeadmanTrigger::Signal,
+            }
+        };
+
+        debug!("deadman.fired trigger={:?}", trigger);
+        let mut report = cancel_everything(
+            &mut client,
+            &mut session,
+            &markets,
+            trigger,
+            config.cancel_retry_for,
+        )
+        .await;
+        if let Some(revocation) = &config.revoke_session {
+            let result = revocation.revoke(&mut client).await;
+            debug!("deadman.revoke_session ok={}", result.is_ok());
+            report.revocation = Some(result.map_err(|e| e.to_string()));
+        }
+        if !report.is_clean() {
+            warn!(
+                "deadman.unclean errors={} {:?} revocation={:?}",
+                report.errors.len(),
+                report.errors,
+                report.revocation
+            );
+        }
+        let _ = report_tx.send(Some(report));
+    }
+}
+
+impl Drop for DeadmanSwitch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn cancel_everything(
+    client: &mut O2Client,
+    session: &mut Session,
+    markets: &[MarketSymbol],
+    trigger: DeadmanTrigger,
+    retry_for: Duration,
+) -> DeadmanReport {
+    let mut report = DeadmanReport {
+        trigger,
+        cancel_batches: 0,
+        errors: Vec::new(),
+        revocation: None,
+    };
+    let deadline = runtime::now() + retry_for;
+    let mut delay = Duration::from_millis(250);
+    let mut pending = markets.to_vec();
+    loop {
+        // Other sessions on the account have been advancing the shared nonce.
+        if let Err(e) = client.refresh_nonce(session).await {
+            debug!("deadman.refresh_nonce failed error={}", e);
+        }
+        report.errors.clear();
+        for market in pending {
+            match client.cancel_all_orders(session, &market).await {
+                Ok(responses) => report.cancel_batches += responses.len(),
+                Err(e) => report.errors.push((market, e.to_string())),
+            }
+        }
+        if report.errors.is_empty() || runtime::now() + delay > deadline {
+            return report;
+        }
+        debug!(
+            "deadman.cancel_retry failed_markets={} delay_ms={}",
+            report.errors.len(),
+            delay.as_millis()
+        );
+        runtime::sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(5));
+        pending = report.errors.iter().map(|(m, _)| m.clone()).collect();
+    }
+}
+
+/// A change in WebSocket connectivity.
+enum Connectivity {
+    /// The socket dropped and is reconnecting.
+    Down,
+    /// The socket reconnected.
+    Up,
+    /// The socket gave up, with the reason.
+    Lost(String),
+    /// The socket is being drained for a planned shutdown.
+    Draining,
+}
+
+/// Resolve with the next connectivity change reported by the WebSocket.
+/// Never resolves if there is no receiver or the channel closes.
+async fn next_connectivity(
+    lifecycle: &mut Option<broadcast::Receiver<WsLifecycleEvent>>,
+) -> Connectivity {
+    let Some(rx) = lifecycle.as_mut() else {
+        return std::future::pending().await;
+    };
+    loop {
+        match rx.recv().await {
+            Ok(WsLifecycleEvent::Disconnected {
+                reason,
+                final_: true,
+            }) => return Connectivity::Lost(reason),
+            Ok(WsLifecycleEvent::Disconnected { final_: false, .. })
+            | Ok(WsLifecycleEvent::Reconnecting { .. }) => return Connectivity::Down,
+            Ok(WsLifecycleEvent::Reconnected { .. }) => return Connectivity::Up,
+            Ok(WsLifecycleEvent::Draining { .. }) => return Connectivity::Draining,
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => {
+                *lifecycle = None;
+                return std::future::pending().await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_sigterm() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut stream) => {
+            stream.recv().await;
+        }
+        Err(e) => {
+            debug!("deadman.sigterm_handler_failed error={}", e);
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_sigterm() {
+    std::future::pending::<()>().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NetworkConfig;
+    use crate::models::TradeAccountId;
+
+    fn offline_client() -> O2Client {
+        O2Client::with_config(NetworkConfig {
+            api_base: "http://127.0.0.1:9".into(),
+            ws_url: "ws://127.0.0.1:9".into(),
+            fuel_rpc: "http://127.0.0.1:9".into(),
+            faucet_url: None,
+            whitelist_required: false,
+            chain_id: None,
+            api_base_fallbacks: Vec::new(),
+            ws_url_fallbacks: Vec::new(),
+            failover: Default::default(),
+        })
+    }
+
+    fn session() -> Session {
+        Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [2u8; 32],
+            trade_account_id: TradeAccountId::new("0x01"),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce: 0,
+        }
+    }
+
+    fn config(timeout_ms: u64) -> DeadmanConfig {
+        DeadmanConfig {
+            timeout: Duration::from_millis(timeout_ms),
+            check_interval: Duration::from_millis(10),
+            cancel_on_ws_disconnect: true,
+            disconnect_grace: Duration::from_secs(60),
+            cancel_on_sigterm: false,
+            cancel_retry_for: Duration::ZERO,
+            revoke_session: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn fires_when_not_fed() {
+        let markets = vec![MarketSymbol::new("FUEL/USDC")];
+        let mut switch = DeadmanSwitch::arm(offline_client(), session(), markets, config(50), None);
+
+        let hb = switch.heartbeat();
+        for _ in 0..10 {
+            hb.feed();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(switch.report().is_none(), "fed switch must not fire");
+
+        let report = tokio::time::timeout(Duration::from_secs(5), switch.wait())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            report.trigger,
+            DeadmanTrigger::HeartbeatTimeout { .. }
+        ));
+        // Offline client: the cancel attempt is made and its failure reported.
+        assert_eq!(report.errors.len(), 1);
+        assert!(!switch.is_armed());
+    }
+
+    #[tokio::test]
+    async fn fires_on_manual_trigger_and_final_disconnect() {
+        let mut switch =
+            DeadmanSwitch::arm(offline_client(), session(), vec![], config(60_000), None);
+        switch.trigger();
+        let report = switch.wait().await.unwrap();
+        assert_eq!(report.trigger, DeadmanTrigger::Manual);
+        assert!(report.is_clean());
+
+        let (tx, rx) = broadcast::channel(4);
+        let mut switch = DeadmanSwitch::arm(
+            offline_client(),
+            session(),
+            vec![],
+            config(60_000),
+            Some(rx),
+        );
+        tx.send(WsLifecycleEvent::Reconnecting {
+            attempt: 1,
+            delay: Duration::from_millis(1),
+        })
+        .unwrap();
+        tx.send(WsLifecycleEvent::Disconnected {
+            reason: "gone".into(),
+            final_: true,
+        })
+        .unwrap();
+        let report = switch.wait().await.unwrap();
+        assert_eq!(
+            report.trigger,
+            DeadmanTrigger::WebSocketDisconnected {
+                reason: "gone".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn fires_when_reconnecting_past_grace() {
+        let (tx, rx) = broadcast::channel(8);
+        let grace = DeadmanConfig {
+            disconnect_grace: Duration::from_millis(100),
+            ..config(60_000)
+        };
+        let mut switch = DeadmanSwitch::arm(offline_client(), session(), vec![], grace, Some(rx));
+
+        // A short outage within the grace period does not fire.
+        tx.send(WsLifecycleEvent::Reconnecting {
+            attempt: 1,
+            delay: Duration::from_millis(1),
+        })
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        tx.send(WsLifecycleEvent::Reconnected { attempts: 1 })
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(switch.report().is_none());
+
+        tx.send(WsLifecycleEvent::Reconnecting {
+            attempt: 1,
+            delay: Duration::from_millis(1),
+        })
+        .unwrap();
+        let report = tokio::time::timeout(Duration::from_secs(5), switch.wait())
+            .await
+            .unwrap()
+            .unwrap();
+        match report.trigger {
+            DeadmanTrigger::ConnectivityLost { down_for } => {
+                assert!(down_for >= Duration::from_millis(100))
+            }
+            other => panic!("unexpected trigger {other:?}"),
+        }
+        assert_eq!(report.revocation, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn grace_runs_on_the_runtime_clock() {
+        let (tx, rx) = broadcast::channel(4);
+        let grace = DeadmanConfig {
+            timeout: Duration::from_secs(3600),
+            check_interval: Duration::from_secs(1),
+            disconnect_grace: Duration::from_secs(600),
+            ..config(0)
+        };
+        let mut switch = DeadmanSwitch::arm(offline_client(), session(), vec![], grace, Some(rx));
+        tx.send(WsLifecycleEvent::Reconnecting {
+            attempt: 1,
+            delay: Duration::from_millis(1),
+        })
+        .unwrap();
+
+        // Only virtual time passes.
+        let report = tokio::time::timeout(Duration::from_secs(900), switch.wait())
+            .await
+            .unwrap()
+            .unwrap();
+        match report.trigger {
+            DeadmanTrigger::ConnectivityLost { down_for } => {
+                assert!(down_for >= Duration::from_secs(600))
+            }
+            other => panic!("unexpected trigger {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn drained_socket_does_not_fire() {
+        let (tx, rx) = broadcast::channel(4);
+        let mut switch = DeadmanSwitch::arm(
+            offline_client(),
+            session(),
+            vec![],
+            config(60_000),
+            Some(rx),
+        );
+        tx.send(WsLifecycleEvent::Draining {
+            deadline: Instant::now(),
+        })
+        .unwrap();
+        tx.send(WsLifecycleEvent::Disconnected {
+            reason: "Drained".into(),
+            final_: true,
+        })
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(switch.report().is_none());
+        assert!(switch.is_armed());
+
+        switch.trigger();
+        let report = switch.wait().await.unwrap();
+        assert_eq!(report.trigger, DeadmanTrigger::Manual);
+    }
+}
@@ -9,6 +9,10 @@
 //! - Session signing bytes
 //! - Action signing bytes
 
+use crate::crypto::parse_hex_32;
+use crate::errors::O2Error;
+use crate::models::Identity;
+
 /// Encode a u64 value as 8 bytes big-endian.
 pub fn u64_be(value: u64) -> [u8; 8] {
     value.to_be_bytes()
@@ -24,11 +28,50 @@ pub fn function_selector(name: &str) -> Vec<u8> {
     result
 }
 
+/// Variant of a Fuel Identity enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdentityKind {
+    Address,
+    ContractId,
+}
+
+impl IdentityKind {
+    /// The encoded discriminant: 0 = Address, 1 = ContractId.
+    pub const fn discriminant(self) -> u64 {
+        match self {
+            IdentityKind::Address => 0,
+            IdentityKind::ContractId => 1,
+        }
+    }
+}
+
+impl From<IdentityKind> for u64 {
+    fn from(kind: IdentityKind) -> Self {
+        kind.discriminant()
+    }
+}
+
+impl From<&Identity> for IdentityKind {
+    fn from(identity: &Identity) -> Self {
+        match identity {
+            Identity::Address(_) => IdentityKind::Address,
+            Identity::ContractId(_) => IdentityKind::ContractId,
+        }
+    }
+}
+
+/// Split an Identity into its kind and parsed 32-byte address.
+pub fn identity_parts(identity: &Identity) -> Result<(IdentityKind, [u8; 32]), O2Error> {
+    Ok((
+        IdentityKind::from(identity),
+        parse_hex_32(identity.address_value())?,
+    ))
+}
+
 /// Encode a Fuel Identity enum: u64(discriminant) + 32-byte address.
-/// discriminant: 0 = Address, 1 = ContractId
-pub fn encode_identity(discriminant: u64, address: &[u8; 32]) -> Vec<u8> {
+pub fn encode_identity(kind: IdentityKind, address: &[u8; 32]) -> Vec<u8> {
     let mut result = Vec::with_capacity(40);
-    result.extend_from_slice(&u64_be(discriminant));
+    result.extend_from_slice(&u64_be(kind.discriminant()));
     result.extend_from_slice(address);
     result
 }
@@ -141,10 +184,8 @@ pub fn build_session_signing_bytes(
 
     // Option::Some
     result.extend_from_slice(&u64_be(1));
-    // Identity::Address
-    result.extend_from_slice(&u64_be(0));
-    // Session address
-    result.extend_from_slice(session_address);
+    // Identity::Address + session address
+    result.extend_from_slice(&encode_identity(IdentityKind::Address, session_address));
     // Expiry
     result.extend_from_slice(&u64_be(expiry));
     // Contract IDs vec
@@ -238,10 +279,10 @@ pub fn cancel_order_to_call(contract_id: &[u8; 32], order_id: &[u8; 32]) -> Call
 }
 
 /// Convert a SettleBalance action to a low-level CallArg.
-/// `to` is the destination identity (discriminant, address).
+/// `to` is the destination identity (kind, address).
 pub fn settle_balance_to_call(
     contract_id: &[u8; 32],
-    to_discriminant: u64,
+    to_kind: IdentityKind,
     to_address: &[u8; 32],
 ) -> CallArg {
     CallArg {
@@ -250,7 +291,7 @@ pub fn settle_balance_to_call(
         amount: 0,
         asset_id: [0u8; 32],
         gas: GAS_MAX,
-        call_data: Some(encode_identity(to_discriminant, to_address)),
+        call_data: Some(encode_identity(to_kind, to_address)),
     }
 }
 
@@ -263,7 +304,7 @@ pub fn settle_balance_to_call(
 pub fn build_withdraw_signing_bytes(
     nonce: u64,
     chain_id: u64,
-    to_discriminant: u64,
+    to_kind: IdentityKind,
     to_address: &[u8; 32],
     asset_id: &[u8; 32],
     amount: u64,
@@ -276,7 +317,7 @@ pub fn build_withdraw_signing_bytes(
     result.extend_from_slice(&u64_be(func_name.len() as u64));
     result.extend_from_slice(func_name);
     // to identity
-    result.extend_from_slice(&u64_be(to_discriminant));
+    result.extend_from_slice(&u64_be(to_kind.discriminant()));
     result.extend_from_slice(to_address);
     // asset_id
     result.extend_from_slice(asset_id);
@@ -295,9 +336,8 @@ pub fn action_to_call(
     market: &crate::models::Market,
     trade_account_id: &str,
     accounts_registry_id: Option<&[u8; 32]>,
-) -> Result<(CallArg, serde_json::Value), crate::errors::O2Error> {
-    use crate::crypto::parse_hex_32;
-    use crate::models::{Action, Identity};
+) -> Result<(CallArg, serde_json::Value), O2Error> {
+    use crate::models::Action;
 
     let contract_id = parse_hex_32(&market.contract_id)?;
 
@@ -351,7 +391,11 @@ pub fn action_to_call(
         }
         Action::SettleBalance => {
             let trade_account_bytes = parse_hex_32(trade_account_id)?;
-            let call = settle_balance_to_call(&contract_id, 1, &trade_account_bytes);
+            let call = settle_balance_to_call(
+                &contract_id,
+                IdentityKind::ContractId,
+                &trade_account_bytes,
+            );
             let json = serde_json::json!({
                 "SettleBalance": { "to": { "ContractId": trade_account_id } }
             });
@@ -359,16 +403,10 @@ pub fn action_to_call(
         }
         Action::RegisterReferer { to } => {
             let registry_id = accounts_registry_id.ok_or_else(|| {
-                crate::errors::O2Error::Other(
-                    "accounts_registry_id required for RegisterReferer".into(),
-                )
+                O2Error::Other("accounts_registry_id required for RegisterReferer".into())
             })?;
-            let (disc, addr_hex) = match to {
-                Identity::Address(a) => (0u64, a.as_str()),
-                Identity::ContractId(c) => (1u64, c.as_str()),
-            };
-            let addr_bytes = parse_hex_32(addr_hex)?;
-            let call = register_referer_to_call(registry_id, disc, &addr_bytes);
+            let (kind, addr_bytes) = identity_parts(to)?;
+            let call = register_referer_to_call(registry_id, kind, &addr_bytes);
             let json = serde_json::json!({
                 "RegisterReferer": { "to": serde_json::to_value(to).unwrap_or_default() }
             });
@@ -380,7 +418,7 @@ pub fn action_to_call(
 /// Convert a RegisterReferer action to a low-level CallArg.
 pub fn register_referer_to_call(
     accounts_registry_id: &[u8; 32],
-    referer_discriminant: u64,
+    referer_kind: IdentityKind,
     referer_address: &[u8; 32],
 ) -> CallArg {
     CallArg {
@@ -389,6 +427,6 @@ pub fn register_referer_to_call(
         amount: 0,
         asset_id: [0u8; 32],
         gas: GAS_MAX,
-        call_data: Some(encode_identity(referer_discriminant, referer_address)),
+        call_data: Some(encode_identity(referer_kind, referer_address)),
     }
 }
@@ -2,8 +2,12 @@
 ///
 /// Maps all error codes from the O2 API (Section 8) to typed Rust errors.
 /// Also handles the two distinct error formats for POST /v1/session/actions.
+use std::time::Duration;
+
 use thiserror::Error;
 
+use crate::capabilities::Endpoint;
+
 /// The primary error type for the O2 SDK.
 #[derive(Error, Debug)]
 #[non_exhaustive]
@@ -105,10 +109,57 @@ pub enum O2Error {
         receipts: Option<serde_json::Value>,
     },
 
+    // Throttling (HTTP 429, or 503 without an API code)
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        message: String,
+        /// Server-provided wait before retrying, from `Retry-After` or rate-limit reset headers.
+        retry_after: Option<Duration>,
+        /// The API error code a 429 body carried, such as
+        /// [`ApiErrorCode::RateLimitExceeded`]; also reported by [`O2Error::api_code`].
+        code: Option<ApiErrorCode>,
+    },
+
+    // Missing route (HTTP 404/405 without an API error body, or 501)
+    #[error("Not supported by this server: {0}")]
+    Unsupported(Endpoint),
+
+    // Refused request (HTTP 401/403 without an API error body)
+    #[error("Unauthorized ({endpoint}, {reason}): {hint}")]
+    Unauthorized {
+        endpoint: Endpoint,
+        reason: AuthFailure,
+        /// What the server said, and what to do about it.
+        hint: String,
+    },
+
     // Client-side errors
     #[error("Session expired: {0}")]
     SessionExpired(String),
 
+    #[error("Market inactive: {0}")]
+    MarketInactive(String),
+
+    #[error("Book moved on: quotes computed at version {computed_at}, book now at {current}")]
+    StaleBook { computed_at: u64, current: u64 },
+
+    // Nonce used by another writer (see NonceManager); already re-synced
+    #[error("Nonce contention: nonce {nonce} used elsewhere, account now at {current}: {source}")]
+    NonceContention {
+        nonce: u64,
+        current: u64,
+        /// The stale-nonce revert the submission failed with.
+        #[source]
+        source: Box<O2Error>,
+    },
+
+    // Setup-time endpoint failing repeatedly (see setup_guard); nothing sent
+    #[error("Circuit open for {endpoint}: not retrying for {retry_after:?}")]
+    CircuitOpen {
+        endpoint: Endpoint,
+        retry_after: Duration,
+    },
+
     // Transport errors
     #[error("HTTP error: {0}")]
     HttpError(String),
@@ -134,82 +185,147 @@ pub enum O2Error {
     Other(String),
 }
 
-impl O2Error {
-    /// Create an O2Error from an API error code and message.
-    pub fn from_code(code: u32, message: String) -> Self {
-        match code {
-            1000 => O2Error::InternalError(message),
-            1001 => O2Error::InvalidRequest(message),
-            1002 => O2Error::ParseError(message),
-            1003 => O2Error::RateLimitExceeded(message),
-            1004 => O2Error::GeoRestricted(message),
-            2000 => O2Error::MarketNotFound(message),
-            2001 => O2Error::MarketPaused(message),
-            2002 => O2Error::MarketAlreadyExists(message),
-            3000 => O2Error::OrderNotFound(message),
-            3001 => O2Error::OrderNotActive(message),
-            3002 => O2Error::InvalidOrderParams(message),
-            4000 => O2Error::InvalidSignature(message),
-            4001 => O2Error::InvalidSession(message),
-            4002 => O2Error::AccountNotFound(message),
-            4003 => O2Error::WhitelistNotConfigured(message),
-            5000 => O2Error::TradeNotFound(message),
-            5001 => O2Error::InvalidTradeCount(message),
-            6000 => O2Error::AlreadySubscribed(message),
-            6001 => O2Error::TooManySubscriptions(message),
-            6002 => O2Error::SubscriptionError(message),
-            7000 => O2Error::InvalidAmount(message),
-            7001 => O2Error::InvalidTimeRange(message),
-            7002 => O2Error::InvalidPagination(message),
-            7003 => O2Error::NoActionsProvided(message),
-            7004 => O2Error::TooManyActions(message),
-            8000 => O2Error::BlockNotFound(message),
-            8001 => O2Error::EventsNotFound(message),
-            _ => O2Error::Other(format!("Unknown error code {code}: {message}")),
+/// Why a request was refused with HTTP 401 or 403.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// The network admits only some accounts or callers to this endpoint,
+    /// such as the testnet whitelist or faucet. Retrying will not help;
+    /// the account has to be admitted, or the endpoint is closed on this
+    /// network.
+    NetworkGated,
+    /// The request's credentials were missing or rejected.
+    Credentials,
+}
+
+impl std::fmt::Display for AuthFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthFailure::NetworkGated => "network-gated",
+            AuthFailure::Credentials => "credentials rejected",
+        })
+    }
+}
+
+/// Declares [`ApiErrorCode`] and its mappings to and from [`O2Error`] from one
+/// table, so a code cannot be added to one side and forgotten on the other.
+/// Each entry names both the `ApiErrorCode` and the `O2Error` variant.
+macro_rules! api_error_codes {
+    ($($name:ident = $code:literal,)*) => {
+        /// A documented API error code, as carried by [`O2Error::api_code`].
+        ///
+        /// These are the codes the API returns before a transaction is built
+        /// (preflight errors). Failures that only surface on chain, such as an
+        /// insufficient balance or a stale nonce, arrive as
+        /// [`O2Error::OnChainRevert`] instead; see [`O2Error::revert_reason`].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum ApiErrorCode {
+            $($name,)*
         }
+
+        impl ApiErrorCode {
+            /// Look up a numeric code. Returns `None` for undocumented codes.
+            pub fn from_u32(code: u32) -> Option<Self> {
+                match code {
+                    $($code => Some(ApiErrorCode::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// The numeric code the API sends.
+            pub fn code(self) -> u32 {
+                match self {
+                    $(ApiErrorCode::$name => $code,)*
+                }
+            }
+        }
+
+        impl O2Error {
+            /// Create an O2Error from an API error code and message.
+            pub fn from_code(code: u32, message: String) -> Self {
+                match ApiErrorCode::from_u32(code) {
+                    $(Some(ApiErrorCode::$name) => O2Error::$name(message),)*
+                    None => O2Error::Other(format!("Unknown error code {code}: {message}")),
+                }
+            }
+
+            /// Returns the typed error code if this is a coded API error.
+            pub fn api_code(&self) -> Option<ApiErrorCode> {
+                match self {
+                    $(O2Error::$name(_) => Some(ApiErrorCode::$name),)*
+                    O2Error::RateLimited { code, .. } => *code,
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+api_error_codes! {
+    // General (1xxx)
+    InternalError = 1000,
+    InvalidRequest = 1001,
+    ParseError = 1002,
+    RateLimitExceeded = 1003,
+    GeoRestricted = 1004,
+    // Market (2xxx)
+    MarketNotFound = 2000,
+    MarketPaused = 2001,
+    MarketAlreadyExists = 2002,
+    // Order (3xxx)
+    OrderNotFound = 3000,
+    OrderNotActive = 3001,
+    InvalidOrderParams = 3002,
+    // Account/Session (4xxx)
+    InvalidSignature = 4000,
+    InvalidSession = 4001,
+    AccountNotFound = 4002,
+    WhitelistNotConfigured = 4003,
+    // Trade (5xxx)
+    TradeNotFound = 5000,
+    InvalidTradeCount = 5001,
+    // Subscription (6xxx)
+    AlreadySubscribed = 6000,
+    TooManySubscriptions = 6001,
+    SubscriptionError = 6002,
+    // Validation (7xxx)
+    InvalidAmount = 7000,
+    InvalidTimeRange = 7001,
+    InvalidPagination = 7002,
+    NoActionsProvided = 7003,
+    TooManyActions = 7004,
+    // Block/Events (8xxx)
+    BlockNotFound = 8000,
+    EventsNotFound = 8001,
+}
+
+impl std::fmt::Display for ApiErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?} ({})", self.code())
     }
+}
 
+impl O2Error {
     /// Returns the error code if this is a coded API error.
     pub fn error_code(&self) -> Option<u32> {
-        match self {
-            O2Error::InternalError(_) => Some(1000),
-            O2Error::InvalidRequest(_) => Some(1001),
-            O2Error::ParseError(_) => Some(1002),
-            O2Error::RateLimitExceeded(_) => Some(1003),
-            O2Error::GeoRestricted(_) => Some(1004),
-            O2Error::MarketNotFound(_) => Some(2000),
-            O2Error::MarketPaused(_) => Some(2001),
-            O2Error::MarketAlreadyExists(_) => Some(2002),
-            O2Error::OrderNotFound(_) => Some(3000),
-            O2Error::OrderNotActive(_) => Some(3001),
-            O2Error::InvalidOrderParams(_) => Some(3002),
-            O2Error::InvalidSignature(_) => Some(4000),
-            O2Error::InvalidSession(_) => Some(4001),
-            O2Error::AccountNotFound(_) => Some(4002),
-            O2Error::WhitelistNotConfigured(_) => Some(4003),
-            O2Error::TradeNotFound(_) => Some(5000),
-            O2Error::InvalidTradeCount(_) => Some(5001),
-            O2Error::AlreadySubscribed(_) => Some(6000),
-            O2Error::TooManySubscriptions(_) => Some(6001),
-            O2Error::SubscriptionError(_) => Some(6002),
-            O2Error::InvalidAmount(_) => Some(7000),
-            O2Error::InvalidTimeRange(_) => Some(7001),
-            O2Error::InvalidPagination(_) => Some(7002),
-            O2Error::NoActionsProvided(_) => Some(7003),
-            O2Error::TooManyActions(_) => Some(7004),
-            O2Error::BlockNotFound(_) => Some(8000),
-            O2Error::EventsNotFound(_) => Some(8001),
-            _ => None,
-        }
+        self.api_code().map(ApiErrorCode::code)
     }
 
     /// Returns true if this error suggests retrying with backoff.
     pub fn is_retryable(&self) -> bool {
         matches!(
             self,
-            O2Error::InternalError(_) | O2Error::RateLimitExceeded(_)
+            O2Error::InternalError(_) | O2Error::RateLimitExceeded(_) | O2Error::RateLimited { .. }
         )
     }
+
+    /// Returns the server's requested wait before retrying, if it sent one.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            O2Error::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 impl From<reqwest::Error> for O2Error {
@@ -241,3 +357,36 @@ impl From<std::convert::Infallible> for O2Error {
         unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_codes_round_trip_through_o2_error() {
+        for code in [1000, 1003, 2001, 3002, 4001, 7004, 8001] {
+            let api = ApiErrorCode::from_u32(code).unwrap();
+            assert_eq!(api.code(), code);
+            let err = O2Error::from_code(code, "msg".into());
+            assert_eq!(err.api_code(), Some(api));
+            assert_eq!(err.error_code(), Some(code));
+        }
+        assert!(matches!(
+            O2Error::from_code(2001, "paused".into()),
+            O2Error::MarketPaused(_)
+        ));
+        assert_eq!(
+            ApiErrorCode::MarketPaused.to_string(),
+            "MarketPaused (2001)"
+        );
+    }
+
+    #[test]
+    fn undocumented_codes_have_no_api_code() {
+        assert_eq!(ApiErrorCode::from_u32(9999), None);
+        let err = O2Error::from_code(9999, "new".into());
+        assert!(matches!(err, O2Error::Other(_)));
+        assert_eq!(err.api_code(), None);
+        assert_eq!(O2Error::SessionExpired("x".into()).api_code(), None);
+    }
+}
@@ -0,0 +1,284 @@
+//! Everything that happens to one trade account, as a single stream.
+//!
+//! A bot that follows its orders, fills, balances and nonce otherwise holds
+//! four streams and `select!`s over them, each with its own view of a
+//! reconnect. [`O2Client::stream_account_events`](crate::O2Client::stream_account_events)
+//! subscribes to all four for one account and yields [`AccountEvent`]s:
+//!
+//! - every update is narrowed to the account: orders, balances and trades of
+//!   other identities sharing the connection are dropped, and an update with
+//!   nothing left is skipped;
+//! - updates that are ready together are yielded in `seen_timestamp` order;
+//! - a reconnect is reported once, as [`AccountEvent::Reconnected`], rather
+//!   than per stream;
+//! - a lost connection ends the stream after a single error.
+//!
+//! ```rust,ignore
+//! let mut events = client
+//!     .stream_account_events(&session.trade_account_id, &["fFUEL/fUSDC"])
+//!     .await?;
+//! while let Some(event) = events.next().await {
+//!     match event? {
+//!         AccountEvent::Trades(update) => { /* fills */ }
+//!         AccountEvent::Reconnected { .. } => { /* refresh from REST */ }
+//!         other => log::debug!("{:?} at {}", other, other.seen_timestamp()),
+//!     }
+//! }
+//! ```
+use futures_util::future::FutureExt;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::errors::O2Error;
+use crate::models::{
+    BalanceUpdate, Identity, NonceUpdate, OrderUpdate, TradeAccountId, TradeUpdate,
+};
+use crate::runtime;
+use crate::timestamp::Timestamp;
+use crate::websocket::{TypedStream, WsLifecycleEvent};
+
+/// An update for one trade account.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum AccountEvent {
+    /// The account's orders that changed.
+    Orders(OrderUpdate),
+    /// Trades on a watched market in which the account was maker or taker.
+    Trades(TradeUpdate),
+    /// The account's balances that changed.
+    Balances(BalanceUpdate),
+    Nonce(NonceUpdate),
+    /// The connection was re-established and the subscriptions re-sent.
+    /// Updates sent while it was down are not replayed; refresh from REST.
+    Reconnected {
+        seen_timestamp: Timestamp,
+    },
+}
+
+impl AccountEvent {
+    /// When the SDK received the update.
+    pub fn seen_timestamp(&self) -> Timestamp {
+        match self {
+            AccountEvent::Orders(u) => u.seen_timestamp,
+            AccountEvent::Trades(u) => u.seen_timestamp,
+            AccountEvent::Balances(u) => u.seen_timestamp,
+            AccountEvent::Nonce(u) => u.seen_timestamp,
+            AccountEvent::Reconnected { seen_timestamp } => *seen_timestamp,
+        }
+    }
+
+    /// When the change happened on chain, if the server said.
+    pub fn onchain_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            AccountEvent::Orders(u) => u.onchain_timestamp,
+            AccountEvent::Trades(u) => u.onchain_timestamp,
+            AccountEvent::Balances(u) => u.onchain_timestamp,
+            AccountEvent::Nonce(u) => u.onchain_timestamp,
+            AccountEvent::Reconnected { .. } => None,
+        }
+    }
+}
+
+/// The account's streams, subscribed on one connection.
+pub(crate) struct AccountStreams {
+    pub(crate) orders: TypedStream<OrderUpdate>,
+    pub(crate) balances: TypedStream<BalanceUpdate>,
+    pub(crate) nonce: TypedStream<NonceUpdate>,
+    pub(crate) trades: Vec<TypedStream<TradeUpdate>>,
+    pub(crate) lifecycle: broadcast::Receiver<WsLifecycleEvent>,
+}
+
+type Item = Result<AccountEvent, O2Error>;
+
+/// Merge `streams` into one stream of `trade_account_id`'s events, on a
+/// background task that stops when the returned stream is dropped.
+pub(crate) fn merge(
+    trade_account_id: TradeAccountId,
+    streams: AccountStreams,
+) -> TypedStream<AccountEvent> {
+    let AccountStreams {
+        orders,
+        balances,
+        nonce,
+        trades,
+        mut lifecycle,
+    } = streams;
+    let account = trade_account_id.as_str().to_string();
+    let mut sources: Vec<BoxStream<'static, Item>> = vec![
+        orders.map(|r| r.map(AccountEvent::Orders)).boxed(),
+        balances.map(|r| r.map(AccountEvent::Balances)).boxed(),
+        nonce.map(|r| r.map(AccountEvent::Nonce)).boxed(),
+    ];
+    sources.extend(
+        trades
+            .into_iter()
+            .map(|s| s.map(|r| r.map(AccountEvent::Trades)).boxed()),
+    );
+    let mut merged = stream::select_all(sources)
+        .filter_map(move |item| std::future::ready(narrow(item, &account)));
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    runtime::spawn(async move {
+        let mut lifecycle_open = true;
+        loop {
+            tokio::select! {
+                _ = tx.closed() => return,
+                item = merged.next() => {
+                    let Some(item) = item else { return };
+                    let mut batch = vec![item];
+                    while let Some(Some(item)) = merged.next().now_or_never() {
+                        batch.push(item);
+                    }
+                    // Errors last, so the updates that made it are not lost.
+                    batch.sort_by_key(|r| match r {
+                        Ok(event) => (false, Some(event.seen_timestamp())),
+                        Err(_) => (true, None),
+                    });
+                    for item in batch {
+                        let failed = item.is_err();
+                        if tx.send(item).is_err() || failed {
+                            return;
+                        }
+                    }
+                }
+                event = lifecycle.recv(), if lifecycle_open => match event {
+                    Ok(WsLifecycleEvent::Reconnected { .. }) => {
+                        let event = AccountEvent::Reconnected {
+                            seen_timestamp: Timestamp::now(),
+                        };
+                        if tx.send(Ok(event)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => lifecycle_open = false,
+                    _ => {}
+                },
+            }
+        }
+    });
+    TypedStream::from_receiver(rx)
+}
+
+/// Keep only what concerns `account`; `None` if nothing does.
+fn narrow(item: Item, account: &str) -> Option<Item> {
+    let is_account = |identity: &Identity| identity.address_value().eq_ignore_ascii_case(account);
+    let event = match item {
+        Ok(event) => event,
+        Err(e) => return Some(Err(e)),
+    };
+    let keep = match event {
+        AccountEvent::Orders(mut update) => {
+            // Orders without an account are the subscription's own.
+            update
+                .orders
+                .retain(|o| o.account.as_ref().map_or(true, is_account));
+            (!update.orders.is_empty()).then_some(AccountEvent::Orders(update))
+        }
+        AccountEvent::Trades(mut update) => {
+            update.trades.retain(|t| {
+                t.maker.as_ref().is_some_and(is_account) || t.taker.as_ref().is_some_and(is_account)
+            });
+            (!update.trades.is_empty()).then_some(AccountEvent::Trades(update))
+        }
+        AccountEvent::Balances(mut update) => {
+            update.balance.retain(|b| is_account(&b.identity));
+            (!update.balance.is_empty()).then_some(AccountEvent::Balances(update))
+        }
+        AccountEvent::Nonce(update) => update
+            .contract_id
+            .as_str()
+            .eq_ignore_ascii_case(account)
+            .then_some(AccountEvent::Nonce(update)),
+        event @ AccountEvent::Reconnected { .. } => Some(event),
+    };
+    keep.map(Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const T0: u64 = 1_700_000_000_000;
+
+    fn stream<T>() -> (mpsc::UnboundedSender<Result<T, O2Error>>, TypedStream<T>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, TypedStream::from_receiver(rx))
+    }
+
+    fn trade(maker: &str, seen: u64) -> TradeUpdate {
+        serde_json::from_value(json!({
+            "action": "subscribe_trades",
+            "market_id": "0xaa",
+            "seen_timestamp": seen,
+            "trades": [{
+                "trade_id": "1",
+                "side": "buy",
+                "total": "10",
+                "quantity": "5",
+                "price": "2",
+                "timestamp": seen,
+                "maker": {"ContractId": maker},
+                "taker": {"ContractId": "0xother"},
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merges_the_accounts_updates_in_seen_order() {
+        let (orders_tx, orders) = stream();
+        let (balances_tx, balances) = stream();
+        let (nonce_tx, nonce) = stream();
+        let (trades_tx, trades) = stream();
+        let (lifecycle_tx, lifecycle) = broadcast::channel(4);
+
+        nonce_tx
+            .send(Ok(serde_json::from_value(json!({
+                "action": "subscribe_nonce",
+                "contract_id": "0xACC",
+                "nonce": "7",
+                "seen_timestamp": T0 + 30,
+            }))
+            .unwrap()))
+            .unwrap();
+        trades_tx.send(Ok(trade("0xother", T0 + 10))).unwrap();
+        trades_tx.send(Ok(trade("0xacc", T0 + 20))).unwrap();
+        balances_tx
+            .send(Ok(serde_json::from_value(json!({
+                "action": "subscribe_balances",
+                "seen_timestamp": T0 + 5,
+                "balance": [],
+            }))
+            .unwrap()))
+            .unwrap();
+
+        let mut events = merge(
+            TradeAccountId::new("0xacc"),
+            AccountStreams {
+                orders,
+                balances,
+                nonce,
+                trades: vec![trades],
+                lifecycle,
+            },
+        );
+        let first = events.next().await.unwrap().unwrap();
+        let second = events.next().await.unwrap().unwrap();
+        assert!(matches!(&first, AccountEvent::Trades(u) if u.trades.len() == 1));
+        assert_eq!(first.seen_timestamp(), Timestamp::from_unix_millis(T0 + 20));
+        assert!(matches!(second, AccountEvent::Nonce(ref u) if u.nonce == 7));
+
+        lifecycle_tx
+            .send(WsLifecycleEvent::Reconnected { attempts: 1 })
+            .unwrap();
+        let event = events.next().await.unwrap().unwrap();
+        assert!(matches!(event, AccountEvent::Reconnected { .. }));
+
+        orders_tx
+            .send(Err(O2Error::WebSocketDisconnected("gone".into())))
+            .unwrap();
+        assert!(events.next().await.unwrap().is_err());
+        assert!(events.next().await.is_none());
+    }
+}
@@ -0,0 +1,8 @@
+//! Order types O2 has no native support for, emulated client-side.
+//!
+//! - [`iceberg`]: works a large order in small visible clips, posting the next
+//!   clip as each one fills.
+//! - [`oco`]: links two orders so that the first to execute cancels the other,
+//!   optionally with a stop held by this process as one leg.
+pub mod iceberg;
+pub mod oco;
@@ -0,0 +1,466 @@
+//! Iceberg orders.
+//!
+//! O2 has no native iceberg order. An [`IcebergOrder`] emulates one: only a
+//! *clip* of at most [`IcebergConfig::visible_quantity`] rests on the book, and
+//! once it fills completely a background task posts the next clip at the same
+//! price, until [`IcebergConfig::total_quantity`] has executed. Fills are read
+//! from the account's order stream, with a REST poll of the resting clip as a
+//! fallback for missed messages.
+//!
+//! A clip that is closed without filling completely by anyone but the iceberg
+//! (an external cancel, say) ends it as [`IcebergStatus::Cancelled`]: re-posting
+//! over someone else's decision is never the right call.
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::{oneshot, watch};
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, Market, MarketSymbol, Order, OrderId, OrderStatus, OrderType, OrderUpdate, Session,
+    Side,
+};
+use crate::nonce::NonceManager;
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// Configuration for an [`IcebergOrder`].
+#[derive(Debug, Clone)]
+pub struct IcebergConfig {
+    pub side: Side,
+    pub price: UnsignedDecimal,
+    /// Quantity to execute across all clips.
+    pub total_quantity: UnsignedDecimal,
+    /// Largest quantity resting at any time.
+    pub visible_quantity: UnsignedDecimal,
+    /// Order type of each clip; must rest on the book.
+    pub order_type: OrderType,
+    /// How often the resting clip is re-read over REST, and how long to wait
+    /// before retrying a failed placement.
+    pub poll_interval: Duration,
+}
+
+impl IcebergConfig {
+    /// `Spot` clips of `visible_quantity` until `total_quantity` executes,
+    /// polled every 5s.
+    pub fn new(
+        side: Side,
+        price: UnsignedDecimal,
+        total_quantity: UnsignedDecimal,
+        visible_quantity: UnsignedDecimal,
+    ) -> Self {
+        Self {
+            side,
+            price,
+            total_quantity,
+            visible_quantity,
+            order_type: OrderType::Spot,
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Use `order_type` instead of `Spot`, e.g. `PostOnly`.
+    pub fn with_order_type(mut self, order_type: OrderType) -> Self {
+        self.order_type = order_type;
+        self
+    }
+}
+
+/// Where an [`IcebergOrder`] is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcebergStatus {
+    /// A clip is resting, or the next one is being posted.
+    Working,
+    /// The total quantity executed.
+    Completed,
+    /// Stopped early by [`IcebergOrder::cancel`] or by a clip closed elsewhere.
+    Cancelled,
+}
+
+/// Progress and aggregate fill stats of an [`IcebergOrder`].
+///
+/// Quantities are chain-scaled base units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcebergState {
+    pub market: MarketSymbol,
+    pub side: Side,
+    pub total_quantity: u64,
+    /// Executed across all clips.
+    pub filled_quantity: u64,
+    /// Clips placed so far, including the resting one.
+    pub clips_posted: u32,
+    /// Clips that filled completely.
+    pub clips_filled: u32,
+    /// The clip currently on the book.
+    pub active_clip: Option<OrderId>,
+    pub status: IcebergStatus,
+    /// Most recent placement, cancel or poll error, cleared by the next success.
+    pub last_error: Option<String>,
+    active_fill: u64,
+}
+
+impl IcebergState {
+    /// A fresh iceberg with nothing posted.
+    pub fn new(market: MarketSymbol, side: Side, total_quantity: u64) -> Self {
+        Self {
+            market,
+            side,
+            total_quantity,
+            filled_quantity: 0,
+            clips_posted: 0,
+            clips_filled: 0,
+            active_clip: None,
+            status: IcebergStatus::Working,
+            last_error: None,
+            active_fill: 0,
+        }
+    }
+
+    /// Quantity still to execute.
+    pub fn remaining(&self) -> u64 {
+        self.total_quantity.saturating_sub(self.filled_quantity)
+    }
+
+    /// True while clips are still being worked.
+    pub fn is_working(&self) -> bool {
+        self.status == IcebergStatus::Working
+    }
+
+    /// Size of the clip to post now, if none is resting.
+    pub fn next_clip(&self, visible_quantity: u64) -> Option<u64> {
+        if !self.is_working() || self.active_clip.is_some() {
+            return None;
+        }
+        Some(visible_quantity.min(self.remaining())).filter(|q| *q > 0)
+    }
+
+    /// Record a freshly placed clip, as returned by the placement.
+    pub fn record_clip(&mut self, order: &Order) {
+        self.active_clip = Some(order.order_id.clone());
+        self.active_fill = 0;
+        self.clips_posted += 1;
+        self.last_error = None;
+        self.observe(order);
+    }
+
+    /// Apply an order update. Returns true if the state changed.
+    pub fn observe(&mut self, order: &Order) -> bool {
+        if self.active_clip.as_ref() != Some(&order.order_id) {
+            return false;
+        }
+        let status = order.status();
+        let fill = if status == OrderStatus::Filled {
+            order.quantity_fill.unwrap_or(order.quantity)
+        } else {
+            order.quantity_fill.unwrap_or(0)
+        };
+        let delta = fill.saturating_sub(self.active_fill);
+        self.active_fill += delta;
+        self.filled_quantity += delta;
+        let mut changed = delta > 0;
+        if status.is_terminal() {
+            self.active_clip = None;
+            if status == OrderStatus::Filled {
+                self.clips_filled += 1;
+            } else {
+                self.status = IcebergStatus::Cancelled;
+            }
+            changed = true;
+        }
+        if self.is_working() && self.active_clip.is_none() && self.remaining() == 0 {
+            self.status = IcebergStatus::Completed;
+        }
+        changed
+    }
+}
+
+/// A background task working an [`IcebergState`].
+///
+/// Dropping the handle stops the task; the resting clip stays on the book.
+pub struct IcebergOrder {
+    state_rx: watch::Receiver<IcebergState>,
+    cancel_tx: Option<oneshot::Sender<()>>,
+    task: Task<()>,
+}
+
+impl IcebergOrder {
+    pub(crate) fn spawn(worker: Worker, orders: TypedStream<OrderUpdate>) -> Self {
+        let (state_tx, state_rx) = watch::channel(worker.state.clone());
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let task = runtime::spawn(run(worker, orders, cancel_rx, state_tx));
+        Self {
+            state_rx,
+            cancel_tx: Some(cancel_tx),
+            task,
+        }
+    }
+
+    /// Current progress.
+    pub fn state(&self) -> IcebergState {
+        self.state_rx.borrow().clone()
+    }
+
+    /// Receive every change to the progress.
+    pub fn subscribe(&self) -> watch::Receiver<IcebergState> {
+        self.state_rx.clone()
+    }
+
+    /// Wait until the iceberg completes or is cancelled, or the task stops.
+    pub async fn wait(&self) -> IcebergState {
+        let mut rx = self.state_rx.clone();
+        let done = rx
+            .wait_for(|state| !state.is_working())
+            .await
+            .map(|state| state.clone());
+        done.unwrap_or_else(|_| self.state())
+    }
+
+    /// True until the iceberg is done, the order stream ends, or it is stopped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop posting clips. The resting clip is left on the book.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+
+    /// Cancel the resting clip and stop posting clips. Returns the final state.
+    pub async fn cancel(mut self) -> IcebergState {
+        if let Some(cancel_tx) = self.cancel_tx.take() {
+            let _ = cancel_tx.send(());
+        }
+        let _ = (&mut self.task).await;
+        self.state()
+    }
+}
+
+impl Drop for IcebergOrder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The client side of an [`IcebergOrder`]: posts, re-reads and cancels clips.
+pub(crate) struct Worker {
+    pub(crate) client: O2Client,
+    pub(crate) session: Session,
+    pub(crate) nonces: NonceManager,
+    pub(crate) market: Market,
+    pub(crate) config: IcebergConfig,
+    pub(crate) visible_quantity: u64,
+    pub(crate) state: IcebergState,
+}
+
+impl Worker {
+    /// Post the next clip, if one is due.
+    pub(crate) async fn place_next(&mut self) -> Result<(), O2Error> {
+        let Some(quantity) = self.state.next_clip(self.visible_quantity) else {
+            return Ok(());
+        };
+        debug!(
+            "iceberg.place market={} quantity={} remaining={}",
+            self.state.market,
+            quantity,
+            self.state.remaining()
+        );
+        let actions = vec![Action::CreateOrder {
+            side: self.config.side,
+            price: self.config.price,
+            quantity: self.market.format_quantity(quantity)?,
+            order_type: self.config.order_type.clone(),
+        }];
+        let resp = self
+            .nonces
+            .batch_actions(
+                &mut self.client,
+                &mut self.session,
+                &self.state.market,
+                actions,
+                true,
+            )
+            .await?;
+        if !resp.is_success() {
+            return Err(O2Error::Other(format!(
+                "Iceberg clip rejected: {}",
+                resp.message.as_deref().unwrap_or("no message")
+            )));
+        }
+        let order = resp
+            .orders
+            .as_ref()
+            .and_then(|orders| orders.first())
+            .ok_or_else(|| O2Error::Other("Iceberg clip placement returned no order".into()))?;
+        self.state.record_clip(order);
+        Ok(())
+    }
+
+    /// Re-read the resting clip over REST.
+    async fn poll(&mut self) -> bool {
+        let Some(id) = self.state.active_clip.clone() else {
+            return false;
+        };
+        match self.client.get_order(&self.state.market, &id).await {
+            Ok(order) => self.state.observe(&order),
+            Err(e) => {
+                debug!("iceberg.poll_failed order_id={} error={}", id, e);
+                self.state.last_error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Cancel the resting clip and end the iceberg.
+    async fn cancel(&mut self) {
+        if let Some(id) = self.state.active_clip.clone() {
+            debug!("iceberg.cancel order_id={}", id);
+            let result = self
+                .nonces
+                .batch_actions(
+                    &mut self.client,
+                    &mut self.session,
+                    &self.state.market,
+                    vec![Action::CancelOrder {
+                        order_id: id.clone(),
+                    }],
+                    false,
+                )
+                .await;
+            self.state.last_error = match result {
+                Ok(resp) if resp.is_success() => None,
+                Ok(resp) => Some(resp.message.unwrap_or_else(|| "no message".into())),
+                Err(e) => Some(e.to_string()),
+            };
+            // Pick up any fill that beat the cancel.
+            match self.client.get_order(&self.state.market, &id).await {
+                Ok(order) => {
+                    self.state.observe(&order);
+                }
+                Err(e) => debug!("iceberg.cancel_refresh_failed error={}", e),
+            }
+        }
+        if self.state.is_working() {
+            self.state.status = IcebergStatus::Cancelled;
+        }
+    }
+}
+
+async fn run(
+    mut worker: Worker,
+    mut orders: TypedStream<OrderUpdate>,
+    mut cancel_rx: oneshot::Receiver<()>,
+    state_tx: watch::Sender<IcebergState>,
+) {
+    let _ = state_tx.send(worker.state.clone());
+    let poll_interval = worker.config.poll_interval;
+    let mut ticker = runtime::interval(poll_interval);
+    let mut retry_place_at: Option<Instant> = None;
+    while worker.state.is_working() {
+        if worker.state.next_clip(worker.visible_quantity).is_some()
+            && retry_place_at.map_or(true, |at| runtime::now() >= at)
+        {
+            if let Err(e) = worker.place_next().await {
+                debug!("iceberg.place_failed error={}", e);
+                worker.state.last_error = Some(e.to_string());
+                retry_place_at = Some(runtime::now() + poll_interval);
+            } else {
+                retry_place_at = None;
+            }
+            let _ = state_tx.send(worker.state.clone());
+            continue;
+        }
+
+        tokio::select! {
+            _ = &mut cancel_rx => {
+                worker.cancel().await;
+                break;
+            }
+            item = orders.next() => match item {
+                None => break,
+                Some(Err(e)) => debug!("iceberg.run stream_error={}", e),
+                Some(Ok(update)) => {
+                    let mut changed = false;
+                    for order in &update.orders {
+                        changed |= worker.state.observe(order);
+                    }
+                    if changed {
+                        let _ = state_tx.send(worker.state.clone());
+                    }
+                }
+            },
+            _ = ticker.tick() => {
+                if worker.poll().await {
+                    let _ = state_tx.send(worker.state.clone());
+                }
+            }
+        }
+    }
+    debug!(
+        "iceberg.run finished status={:?} filled={} clips={}",
+        worker.state.status, worker.state.filled_quantity, worker.state.clips_posted
+    );
+    let _ = state_tx.send(worker.state.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: &str, quantity: u64, filled: Option<u64>, close: bool, cancel: bool) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": "Buy",
+            "order_type": "Spot",
+            "quantity": quantity.to_string(),
+            "quantity_fill": filled.map(|f| f.to_string()),
+            "price": "1",
+            "close": close,
+            "cancel": cancel,
+        }))
+        .unwrap()
+    }
+
+    fn state() -> IcebergState {
+        IcebergState::new(MarketSymbol::new("fFUEL/fUSDC"), Side::Buy, 100)
+    }
+
+    #[test]
+    fn reposts_after_each_filled_clip_until_complete() {
+        let mut state = state();
+        assert_eq!(state.next_clip(40), Some(40));
+        state.record_clip(&order("0x01", 40, Some(0), false, false));
+        assert_eq!(state.next_clip(40), None);
+
+        assert!(!state.observe(&order("0x99", 40, Some(40), true, false)));
+        assert!(state.observe(&order("0x01", 40, Some(15), false, false)));
+        assert!(!state.observe(&order("0x01", 40, Some(15), false, false)));
+        assert!(state.observe(&order("0x01", 40, Some(40), true, false)));
+        assert_eq!(state.filled_quantity, 40);
+        assert_eq!(state.next_clip(40), Some(40));
+
+        state.record_clip(&order("0x02", 40, None, true, false));
+        assert_eq!(state.filled_quantity, 80);
+        assert_eq!(state.next_clip(40), Some(20));
+
+        state.record_clip(&order("0x03", 20, Some(20), true, false));
+        assert_eq!(state.status, IcebergStatus::Completed);
+        assert_eq!(state.clips_posted, 3);
+        assert_eq!(state.clips_filled, 3);
+        assert_eq!(state.remaining(), 0);
+        assert_eq!(state.next_clip(40), None);
+    }
+
+    #[test]
+    fn external_cancel_ends_the_iceberg() {
+        let mut state = state();
+        state.record_clip(&order("0x01", 40, Some(10), false, false));
+        assert!(state.observe(&order("0x01", 40, Some(10), true, true)));
+        assert_eq!(state.status, IcebergStatus::Cancelled);
+        assert_eq!(state.filled_quantity, 10);
+        assert_eq!(state.clips_filled, 0);
+        assert_eq!(state.next_clip(40), None);
+    }
+}
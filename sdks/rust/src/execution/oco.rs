@@ -0,0 +1,1194 @@
+//! One-cancels-other order pairs.
+//!
+//! O2 has no native OCO order. An [`OcoPair`] emulates one from two legs: two
+//! orders resting on the book, or one resting order and a stop held by this
+//! process, typically a take-profit limit paired with a stop-loss.
+//!
+//! Resting legs are placed in a single transaction, and a background task
+//! cancels the survivor as soon as either leg executes (fully or partly) or is
+//! closed. Executions are read from the account's order stream, with a REST
+//! poll of the legs as a fallback for missed messages.
+//!
+//! A stop leg ([`OcoLeg::stop`]) is a [`Trigger`] that never reaches the
+//! book: it watches the market's trades, with a REST poll of the ticker as a
+//! fallback, and is simply dropped if the resting leg executes first. When a
+//! trade crosses it, its order and the cancel of the resting leg go out in one
+//! transaction, so they land or fail together. Like any trigger it fires at
+//! most once: if that transaction fails while the resting leg is still open,
+//! the link ends [`OcoStatus::Failed`] and the resting leg is left as it is.
+//!
+//! The two legs can race: the survivor may fill before its cancel lands. Cancels
+//! are idempotent — a cancel that fails because the order is already closed is
+//! resolved by re-reading the order — and [`OcoLink::filled`] lists every leg
+//! that executed, so a double execution is visible rather than silent.
+//!
+//! [`OcoPair::cancel`] withdraws the whole pair: open legs are cancelled and
+//! the link ends [`OcoStatus::Cancelled`], or [`OcoStatus::Done`] if a leg
+//! executed first.
+//!
+//! With [`OcoConfig::persist_path`] set, the link is written to disk on every
+//! change, and [`O2Client::resume_oco`](crate::O2Client::resume_oco) picks it
+//! up again after a restart.
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures_util::stream::SelectAll;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, Market, MarketSymbol, Order, OrderId, OrderStatus, OrderType, OrderUpdate, Session,
+    Side, TradeUpdate,
+};
+use crate::nonce::NonceManager;
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::triggers::{Trigger, TriggerCondition, TriggerEvent, TriggerOrder, TriggerStatus};
+use crate::websocket::TypedStream;
+
+/// Configuration for an [`OcoPair`].
+#[derive(Debug, Clone)]
+pub struct OcoConfig {
+    /// How often the legs are re-read over REST (and a stop leg's ticker), and
+    /// how long to wait before retrying a failed cancel.
+    pub poll_interval: Duration,
+    /// Write the link to this file on every change.
+    pub persist_path: Option<PathBuf>,
+}
+
+impl Default for OcoConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            persist_path: None,
+        }
+    }
+}
+
+/// One leg of an OCO pair. At most one leg of a pair can be a stop.
+#[derive(Debug, Clone)]
+pub enum OcoLeg {
+    /// An order resting on the book, e.g. a take-profit limit.
+    Resting {
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    },
+    /// An order held by this process until a trade crosses `condition`, e.g.
+    /// a stop-loss. See [`crate::triggers`].
+    Stop {
+        side: Side,
+        quantity: UnsignedDecimal,
+        condition: TriggerCondition,
+        order: TriggerOrder,
+    },
+}
+
+impl OcoLeg {
+    /// A `Spot` order resting on the book.
+    pub fn new(side: Side, price: UnsignedDecimal, quantity: UnsignedDecimal) -> Self {
+        Self::Resting {
+            side,
+            price,
+            quantity,
+            order_type: OrderType::Spot,
+        }
+    }
+
+    /// A stop submitting `order` once a trade crosses `condition`.
+    pub fn stop(
+        side: Side,
+        quantity: UnsignedDecimal,
+        condition: TriggerCondition,
+        order: TriggerOrder,
+    ) -> Self {
+        Self::Stop {
+            side,
+            quantity,
+            condition,
+            order,
+        }
+    }
+
+    /// Rest with `order_type` instead of `Spot`, e.g. `PostOnly`. Has no
+    /// effect on a stop leg.
+    pub fn with_order_type(mut self, order_type: OrderType) -> Self {
+        if let Self::Resting {
+            order_type: resting,
+            ..
+        } = &mut self
+        {
+            *resting = order_type;
+        }
+        self
+    }
+
+    /// The trigger behind a stop leg; `None` for a resting leg.
+    pub(crate) fn to_stop(&self, market: &MarketSymbol) -> Option<Trigger> {
+        match self {
+            Self::Resting { .. } => None,
+            Self::Stop {
+                side,
+                quantity,
+                condition,
+                order,
+            } => Some(Trigger::new(
+                "oco-stop",
+                market.clone(),
+                *side,
+                *quantity,
+                condition.clone(),
+                order.clone(),
+            )),
+        }
+    }
+
+    /// The order a resting leg places.
+    pub(crate) fn to_action(&self) -> Result<Action, O2Error> {
+        let Self::Resting {
+            side,
+            price,
+            quantity,
+            order_type,
+        } = self
+        else {
+            return Err(O2Error::InvalidOrderParams(
+                "OCO stop legs are not placed on the book".into(),
+            ));
+        };
+        if matches!(
+            order_type,
+            OrderType::Market | OrderType::FillOrKill | OrderType::BoundedMarket { .. }
+        ) {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "OCO resting legs must rest on the book; {order_type:?} orders do not, \
+                 use OcoLeg::stop for them"
+            )));
+        }
+        Ok(Action::CreateOrder {
+            side: *side,
+            price: *price,
+            quantity: *quantity,
+            order_type: order_type.clone(),
+        })
+    }
+}
+
+/// Where an [`OcoLink`] is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OcoStatus {
+    /// Both legs are resting untouched, or waiting for the stop.
+    Active,
+    /// `trigger` executed or was closed; the other leg is being cancelled.
+    Unwinding { trigger: OrderId },
+    /// The other leg is closed, or the stop fired and closed the resting leg.
+    /// A partly filled trigger keeps resting.
+    Done,
+    /// The pair was cancelled through [`OcoPair::cancel`] before either leg
+    /// executed.
+    Cancelled,
+    /// The stop's transaction failed while the resting leg was open. The
+    /// stop is not retried and the resting leg is left on the book.
+    Failed { reason: String },
+}
+
+/// Two orders linked so that the first to execute cancels the other.
+///
+/// This is the persisted state of an [`OcoPair`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OcoLink {
+    pub market: MarketSymbol,
+    /// The legs resting on the book: two, or one beside [`OcoLink::stop`].
+    pub legs: Vec<OrderId>,
+    /// The stop leg, if the pair has one.
+    #[serde(default)]
+    pub stop: Option<Trigger>,
+    pub status: OcoStatus,
+    /// Legs that executed any quantity, in the order that was observed. Two
+    /// entries means the survivor filled before its cancel landed. A fired
+    /// stop's order is listed once it executed.
+    #[serde(default)]
+    pub filled: Vec<OrderId>,
+    /// Resting legs known to be closed: filled, cancelled or expired.
+    #[serde(default)]
+    pub closed: Vec<OrderId>,
+    /// Most recent cancel or poll error, cleared by the next success.
+    #[serde(skip)]
+    pub last_error: Option<String>,
+}
+
+impl OcoLink {
+    /// A fresh link between two resting orders.
+    pub fn new(market: MarketSymbol, legs: [OrderId; 2]) -> Self {
+        Self {
+            market,
+            legs: legs.into(),
+            stop: None,
+            status: OcoStatus::Active,
+            filled: Vec::new(),
+            closed: Vec::new(),
+            last_error: None,
+        }
+    }
+
+    /// A fresh link between a resting order and a pending stop.
+    pub fn with_stop(market: MarketSymbol, leg: OrderId, stop: Trigger) -> Self {
+        Self {
+            market,
+            legs: vec![leg],
+            stop: Some(stop),
+            status: OcoStatus::Active,
+            filled: Vec::new(),
+            closed: Vec::new(),
+            last_error: None,
+        }
+    }
+
+    /// Read a link written by an [`OcoPair`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            O2Error::Other(format!("Failed to read OCO link {}: {e}", path.display()))
+        })?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Write the link atomically (temporary file, then rename).
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), O2Error> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                O2Error::Other(format!("Failed to write OCO link {}: {e}", path.display()))
+            })
+    }
+
+    /// True once the leg that did not trigger is closed, the pair was
+    /// cancelled, or its stop failed.
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.status,
+            OcoStatus::Done | OcoStatus::Cancelled | OcoStatus::Failed { .. }
+        )
+    }
+
+    /// Legs not known to be closed.
+    fn open_legs(&self) -> Vec<OrderId> {
+        self.legs
+            .iter()
+            .filter(|id| !self.closed.contains(id))
+            .cloned()
+            .collect()
+    }
+
+    /// True while the stop leg can still fire.
+    fn stop_pending(&self) -> bool {
+        self.stop.as_ref().is_some_and(Trigger::is_pending)
+    }
+
+    /// The price that fired the stop, while its transaction is unresolved.
+    fn stop_firing(&self) -> Option<UnsignedDecimal> {
+        match self.stop.as_ref()?.status {
+            TriggerStatus::Firing { price } => Some(price),
+            _ => None,
+        }
+    }
+
+    /// Withdraw a pending stop. It never left this process, so this is
+    /// immediate.
+    fn withdraw_stop(&mut self) {
+        if let Some(stop) = self.stop.as_mut().filter(|stop| stop.is_pending()) {
+            stop.status = TriggerStatus::Cancelled;
+        }
+    }
+
+    /// After cancelling the pair: `Cancelled` if every leg is closed and none
+    /// executed. Returns true if every leg is closed.
+    fn settle_cancel(&mut self) -> bool {
+        self.withdraw_stop();
+        if !self.open_legs().is_empty() || self.stop_firing().is_some() {
+            return false;
+        }
+        if self.filled.is_empty() {
+            self.status = OcoStatus::Cancelled;
+        }
+        true
+    }
+
+    /// True if both legs executed.
+    pub fn both_filled(&self) -> bool {
+        self.filled.len() == 2
+    }
+
+    /// The leg that still has to be cancelled, while unwinding.
+    pub fn survivor(&self) -> Option<&OrderId> {
+        match &self.status {
+            OcoStatus::Unwinding { trigger } => self
+                .legs
+                .iter()
+                .find(|id| *id != trigger && !self.closed.contains(id)),
+            _ => None,
+        }
+    }
+
+    /// Apply an order update. Returns true if the link changed.
+    pub fn observe(&mut self, order: &Order) -> bool {
+        if !self.legs.contains(&order.order_id) || self.is_done() {
+            return false;
+        }
+        let status = order.status();
+        let mut changed = false;
+        if (order.quantity_fill.unwrap_or(0) > 0 || status == OrderStatus::Filled)
+            && !self.filled.contains(&order.order_id)
+        {
+            self.filled.push(order.order_id.clone());
+            changed = true;
+        }
+        if status.is_terminal() {
+            changed |= self.mark_closed(&order.order_id);
+        }
+        self.advance() || changed
+    }
+
+    /// Record that a leg is closed. Returns true if it was not already.
+    fn mark_closed(&mut self, id: &OrderId) -> bool {
+        if self.closed.contains(id) {
+            return false;
+        }
+        self.closed.push(id.clone());
+        self.advance();
+        true
+    }
+
+    fn advance(&mut self) -> bool {
+        let before = self.status.clone();
+        if self.status == OcoStatus::Active {
+            if let Some(trigger) = self.filled.first().or(self.closed.first()) {
+                self.status = OcoStatus::Unwinding {
+                    trigger: trigger.clone(),
+                };
+            }
+        }
+        if let OcoStatus::Unwinding { trigger } = &self.status {
+            let other_closed = match &self.stop {
+                // A firing stop is settled by `resolve_stop` once its
+                // transaction's outcome is known.
+                Some(_) => self.stop_firing().is_none(),
+                None => self.closed.iter().any(|id| id != trigger),
+            };
+            if other_closed {
+                self.status = OcoStatus::Done;
+            }
+        }
+        if self.status != OcoStatus::Active {
+            self.withdraw_stop();
+        }
+        self.status != before
+    }
+
+    /// The stop fired: its order was placed and the resting leg cancelled in
+    /// one transaction. `order` is the stop's order as returned.
+    fn stop_fired(&mut self, order: Option<&Order>) {
+        let Some(price) = self.stop_firing() else {
+            return;
+        };
+        for id in self.open_legs() {
+            self.closed.push(id);
+        }
+        if let Some(order) = order {
+            if order.quantity_fill.unwrap_or(0) > 0 || order.status() == OrderStatus::Filled {
+                self.filled.push(order.order_id.clone());
+            }
+        }
+        if let Some(stop) = &mut self.stop {
+            stop.status = TriggerStatus::Fired {
+                price,
+                order_id: order.map(|o| o.order_id.clone()),
+            };
+        }
+        self.status = OcoStatus::Done;
+    }
+
+    /// Settle a stop whose transaction failed, or whose outcome was lost, from
+    /// the resting leg as last observed. `rejected` says the transaction
+    /// certainly did not land.
+    ///
+    /// The transaction also cancels the resting leg, so it did not land while
+    /// that leg is open: the stop and the link then fail. If the leg executed,
+    /// it beat the stop, which is dropped. If it closed without executing, the
+    /// stop fired unless the transaction was rejected.
+    pub(crate) fn resolve_stop(&mut self, rejected: bool, reason: String) {
+        let Some(price) = self.stop_firing() else {
+            return;
+        };
+        let executed = self.legs.iter().any(|id| self.filled.contains(id));
+        let closed = self.open_legs().is_empty();
+        let status = if executed || (closed && rejected) {
+            TriggerStatus::Cancelled
+        } else if closed {
+            TriggerStatus::Fired {
+                price,
+                order_id: None,
+            }
+        } else {
+            TriggerStatus::Failed {
+                reason: reason.clone(),
+            }
+        };
+        self.status = match status {
+            TriggerStatus::Failed { .. } => OcoStatus::Failed { reason },
+            _ => OcoStatus::Done,
+        };
+        if let Some(stop) = &mut self.stop {
+            stop.status = status;
+        }
+    }
+}
+
+/// A background task enforcing an [`OcoLink`].
+///
+/// Dropping the pair stops the task; resting legs stay on the book, a pending
+/// stop stops being watched, and the link can be resumed from its persisted
+/// file.
+pub struct OcoPair {
+    link_rx: watch::Receiver<OcoLink>,
+    cancel_tx: watch::Sender<bool>,
+    task: Task<()>,
+}
+
+impl OcoPair {
+    pub(crate) fn spawn(
+        worker: Worker,
+        orders: TypedStream<OrderUpdate>,
+        trades: Option<TypedStream<TradeUpdate>>,
+        config: OcoConfig,
+    ) -> Self {
+        let (link_tx, link_rx) = watch::channel(worker.link.clone());
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let publisher = Publisher {
+            path: config.persist_path.clone(),
+            link_tx,
+        };
+        let trades = trades.into_iter().collect();
+        let task = runtime::spawn(run(worker, orders, trades, config, publisher, cancel_rx));
+        Self {
+            link_rx,
+            cancel_tx,
+            task,
+        }
+    }
+
+    /// Current state of the link.
+    pub fn link(&self) -> OcoLink {
+        self.link_rx.borrow().clone()
+    }
+
+    /// Where the link is in its lifecycle.
+    pub fn status(&self) -> OcoStatus {
+        self.link_rx.borrow().status.clone()
+    }
+
+    /// Withdraw a pending stop, cancel every open leg and wait until they are
+    /// closed, retrying failed cancels every poll interval.
+    ///
+    /// Ends [`OcoStatus::Cancelled`] if no leg had executed, or
+    /// [`OcoStatus::Done`] with the executions in [`OcoLink::filled`] if one
+    /// beat the cancel. Returns the link as it stands if the task has already
+    /// stopped.
+    pub async fn cancel(&self) -> OcoLink {
+        debug!("oco.cancel_requested");
+        let _ = self.cancel_tx.send(true);
+        self.wait().await
+    }
+
+    /// Receive every change to the link.
+    pub fn subscribe(&self) -> watch::Receiver<OcoLink> {
+        self.link_rx.clone()
+    }
+
+    /// Wait until the link is done, or the task stops.
+    pub async fn wait(&self) -> OcoLink {
+        let mut rx = self.link_rx.clone();
+        let done = rx.wait_for(OcoLink::is_done).await.map(|link| link.clone());
+        done.unwrap_or_else(|_| self.link())
+    }
+
+    /// True until the link is done, the order stream ends, or the pair is stopped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop enforcing the link. The legs are left as they are.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for OcoPair {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The client side of an [`OcoPair`]: reads legs, fires the stop and cancels
+/// the survivor.
+pub(crate) struct Worker {
+    pub(crate) client: O2Client,
+    pub(crate) session: Session,
+    pub(crate) nonces: NonceManager,
+    /// The pair's market, for pricing the stop's order.
+    pub(crate) market: Market,
+    pub(crate) link: OcoLink,
+    /// A failed stop transaction whose resting leg could not be re-read yet:
+    /// whether it was rejected, and why it failed.
+    unresolved: Option<(bool, String)>,
+}
+
+impl Worker {
+    pub(crate) fn new(
+        client: O2Client,
+        session: Session,
+        nonces: NonceManager,
+        market: Market,
+        link: OcoLink,
+    ) -> Self {
+        Self {
+            client,
+            session,
+            nonces,
+            market,
+            link,
+            unresolved: None,
+        }
+    }
+
+    /// Re-read every open leg over REST, and the last price if the stop is
+    /// pending.
+    async fn poll(&mut self, publisher: &Publisher) -> bool {
+        let mut changed = false;
+        for id in self.link.open_legs() {
+            match self.client.get_order(&self.link.market, &id).await {
+                Ok(order) => changed |= self.link.observe(&order),
+                Err(e) => {
+                    debug!("oco.poll_failed order_id={} error={}", id, e);
+                    self.link.last_error = Some(e.to_string());
+                }
+            }
+        }
+        if self.link.stop_pending() {
+            match self.client.get_ticker(&self.link.market).await {
+                Ok(ticker) => {
+                    if let Some(last) = ticker.last {
+                        changed |= self.observe_trade(last, publisher).await;
+                    }
+                }
+                Err(e) => debug!("oco.poll_failed ticker error={}", e),
+            }
+        }
+        changed
+    }
+
+    /// Apply a trade at `chain_price` to the stop, firing it if crossed.
+    /// Returns true if the link changed.
+    async fn observe_trade(&mut self, chain_price: u64, publisher: &Publisher) -> bool {
+        let Some(stop) = self.link.stop.as_mut() else {
+            return false;
+        };
+        let price = match self.market.format_price(chain_price) {
+            Ok(price) => price,
+            Err(e) => {
+                debug!("oco.observe bad_price={} error={}", chain_price, e);
+                return false;
+            }
+        };
+        let events = stop.observe(price);
+        if events
+            .iter()
+            .any(|event| matches!(event, TriggerEvent::Triggered { .. }))
+        {
+            stop.status = TriggerStatus::Firing { price };
+            self.fire(chain_price, publisher).await;
+        }
+        !events.is_empty()
+    }
+
+    /// Submit the stop's order together with the cancel of the resting leg.
+    async fn fire(&mut self, chain_price: u64, publisher: &Publisher) {
+        let Some(stop) = self.link.stop.clone() else {
+            return;
+        };
+        debug!("oco.fire market={} status={:?}", stop.market, stop.status);
+        if let Err(e) = publisher.publish(&self.link) {
+            self.link
+                .resolve_stop(true, format!("not fired, state could not be saved: {e}"));
+            return;
+        }
+        let action = match stop.to_action(&self.market, chain_price) {
+            Ok(action) => action,
+            Err(e) => {
+                self.link.resolve_stop(true, e.to_string());
+                return;
+            }
+        };
+        let mut actions: Vec<Action> = self
+            .link
+            .open_legs()
+            .into_iter()
+            .map(|order_id| Action::CancelOrder { order_id })
+            .collect();
+        actions.push(action);
+        let result = self
+            .nonces
+            .batch_actions(
+                &mut self.client,
+                &mut self.session,
+                &self.link.market,
+                actions,
+                true,
+            )
+            .await;
+        match result {
+            Ok(resp) if resp.is_success() => {
+                let orders = resp.orders.unwrap_or_default();
+                let order = orders
+                    .iter()
+                    .find(|o| !self.link.legs.contains(&o.order_id));
+                debug!("oco.fired order_id={:?}", order.map(|o| o.order_id.clone()));
+                self.link.stop_fired(order);
+                self.link.last_error = None;
+            }
+            Ok(resp) => {
+                let reason = resp.message.unwrap_or_else(|| "no message".into());
+                self.resolve(true, reason).await;
+            }
+            // Only a revert is known not to have landed.
+            Err(e) => {
+                self.resolve(e.revert_reason().is_some(), e.to_string())
+                    .await
+            }
+        }
+    }
+
+    /// Re-read the resting leg and settle a stop whose transaction failed;
+    /// see [`OcoLink::resolve_stop`]. Retried on the next poll if the leg
+    /// cannot be read.
+    async fn resolve(&mut self, rejected: bool, reason: String) {
+        debug!("oco.fire_failed reason={}", reason);
+        for id in self.link.open_legs() {
+            match self.client.get_order(&self.link.market, &id).await {
+                Ok(order) => {
+                    self.link.observe(&order);
+                }
+                Err(e) => {
+                    debug!("oco.resolve_failed order_id={} error={}", id, e);
+                    self.link.last_error = Some(e.to_string());
+                    self.unresolved = Some((rejected, reason));
+                    return;
+                }
+            }
+        }
+        self.link.resolve_stop(rejected, reason);
+        self.link.last_error = None;
+    }
+
+    /// Cancel `survivor`. Returns true once it is known to be closed.
+    async fn cancel(&mut self, survivor: &OrderId) -> bool {
+        debug!("oco.cancel order_id={}", survivor);
+        let result = self
+            .nonces
+            .batch_actions(
+                &mut self.client,
+                &mut self.session,
+                &self.link.market,
+                vec![Action::CancelOrder {
+                    order_id: survivor.clone(),
+                }],
+                false,
+            )
+            .await;
+        let error = match result {
+            Ok(resp) if resp.is_success() => None,
+            Ok(resp) => Some(resp.message.unwrap_or_else(|| "no message".into())),
+            Err(e) => Some(e.to_string()),
+        };
+        // Whether the cancel landed or not, the order itself says what happened,
+        // including any fill that beat the cancel.
+        match self.client.get_order(&self.link.market, survivor).await {
+            Ok(order) => {
+                self.link.observe(&order);
+            }
+            Err(e) => debug!("oco.cancel_refresh_failed error={}", e),
+        }
+        if error.is_none() {
+            self.link.mark_closed(survivor);
+        }
+        let closed = self.link.closed.contains(survivor);
+        self.link.last_error = if closed { None } else { error };
+        closed
+    }
+
+    /// Withdraw the stop and cancel every open leg. Returns true once every
+    /// leg is known to be closed.
+    async fn cancel_all(&mut self) -> bool {
+        self.link.withdraw_stop();
+        let open = self.link.open_legs();
+        debug!("oco.cancel_all open={}", open.len());
+        let mut error = None;
+        if !open.is_empty() {
+            let actions = open
+                .iter()
+                .map(|id| Action::CancelOrder {
+                    order_id: id.clone(),
+                })
+                .collect();
+            let result = self
+                .nonces
+                .batch_actions(
+                    &mut self.client,
+                    &mut self.session,
+                    &self.link.market,
+                    actions,
+                    false,
+                )
+                .await;
+            error = match result {
+                Ok(resp) if resp.is_success() => None,
+                Ok(resp) => Some(resp.message.unwrap_or_else(|| "no message".into())),
+                Err(e) => Some(e.to_string()),
+            };
+            for id in &open {
+                match self.client.get_order(&self.link.market, id).await {
+                    Ok(order) => {
+                        self.link.observe(&order);
+                    }
+                    Err(e) => debug!("oco.cancel_refresh_failed error={}", e),
+                }
+                if error.is_none() {
+                    self.link.mark_closed(id);
+                }
+            }
+        }
+        let closed = self.link.settle_cancel();
+        self.link.last_error = if closed { None } else { error };
+        closed
+    }
+}
+
+/// Where changes go: the link to its watch channel and file, if configured.
+struct Publisher {
+    path: Option<PathBuf>,
+    link_tx: watch::Sender<OcoLink>,
+}
+
+impl Publisher {
+    /// Publish `link`. Fails only if it could not be written to disk.
+    fn publish(&self, link: &OcoLink) -> Result<(), O2Error> {
+        let _ = self.link_tx.send(link.clone());
+        match &self.path {
+            Some(path) => link.save(path),
+            None => Ok(()),
+        }
+    }
+
+    fn publish_logged(&self, link: &OcoLink) {
+        if let Err(e) = self.publish(link) {
+            debug!("oco.persist_failed error={}", e);
+        }
+    }
+}
+
+async fn run(
+    mut worker: Worker,
+    mut orders: TypedStream<OrderUpdate>,
+    mut trades: SelectAll<TypedStream<TradeUpdate>>,
+    config: OcoConfig,
+    publisher: Publisher,
+    mut cancel_rx: watch::Receiver<bool>,
+) {
+    publisher.publish_logged(&worker.link);
+
+    let mut ticker = runtime::interval(config.poll_interval);
+    let mut retry_cancel_at: Option<Instant> = None;
+    while !worker.link.is_done() {
+        // A stop that may have fired is settled before anything else touches
+        // the resting leg.
+        if let Some((rejected, reason)) = worker.unresolved.take() {
+            ticker.tick().await;
+            worker.resolve(rejected, reason).await;
+            publisher.publish_logged(&worker.link);
+            continue;
+        }
+        if *cancel_rx.borrow() {
+            if retry_cancel_at.map_or(true, |at| runtime::now() >= at) {
+                let closed = worker.cancel_all().await;
+                retry_cancel_at = (!closed).then(|| runtime::now() + config.poll_interval);
+                publisher.publish_logged(&worker.link);
+                continue;
+            }
+        } else if let Some(survivor) = worker.link.survivor().cloned() {
+            if retry_cancel_at.map_or(true, |at| runtime::now() >= at) {
+                let closed = worker.cancel(&survivor).await;
+                retry_cancel_at = (!closed).then(|| runtime::now() + config.poll_interval);
+                publisher.publish_logged(&worker.link);
+                continue;
+            }
+        }
+
+        let changed = tokio::select! {
+            item = orders.next() => match item {
+                None => break,
+                Some(Err(e)) => {
+                    debug!("oco.run stream_error={}", e);
+                    false
+                }
+                Some(Ok(update)) => {
+                    let mut changed = false;
+                    for order in &update.orders {
+                        changed |= worker.link.observe(order);
+                    }
+                    changed
+                }
+            },
+            Some(item) = trades.next() => match item {
+                Err(e) => {
+                    debug!("oco.run trade_stream_error={}", e);
+                    false
+                }
+                Ok(update) => {
+                    let mut changed = false;
+                    for trade in &update.trades {
+                        changed |= worker.observe_trade(trade.price, &publisher).await;
+                    }
+                    changed
+                }
+            },
+            _ = ticker.tick() => worker.poll(&publisher).await,
+            Ok(()) = cancel_rx.changed() => false,
+        };
+        if changed {
+            publisher.publish_logged(&worker.link);
+        }
+    }
+    debug!(
+        "oco.run finished status={:?} filled={}",
+        worker.link.status,
+        worker.link.filled.len()
+    );
+    publisher.publish_logged(&worker.link);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        AssetId, ContractId, MarketAsset, MarketId, MarketsResponse, TradeAccountId,
+    };
+    use crate::test_util::{mock_api::MockResponse, mock_transport::MockTransport};
+
+    fn order(id: &str, filled: u64, close: bool, cancel: bool) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": "Sell",
+            "order_type": "Spot",
+            "quantity": "100",
+            "quantity_fill": filled.to_string(),
+            "price": "1",
+            "close": close,
+            "cancel": cancel,
+        }))
+        .unwrap()
+    }
+
+    fn link() -> OcoLink {
+        OcoLink::new(
+            MarketSymbol::new("fFUEL/fUSDC"),
+            [OrderId::new("0x01"), OrderId::new("0x02")],
+        )
+    }
+
+    fn dec(s: &str) -> UnsignedDecimal {
+        s.parse().unwrap()
+    }
+
+    fn hex(n: u64) -> String {
+        format!("0x{n:064x}")
+    }
+
+    /// Resting take-profit `hex(1)` beside a sell stop-loss at 0.95 with a 5%
+    /// bounded market order.
+    fn stop_link() -> OcoLink {
+        let market = MarketSymbol::new("fFUEL/fUSDC");
+        let stop = OcoLeg::stop(
+            Side::Sell,
+            dec("100"),
+            TriggerCondition::AtOrBelow(dec("0.95")),
+            TriggerOrder::BoundedMarket {
+                max_slippage_bps: 500,
+            },
+        )
+        .to_stop(&market)
+        .unwrap();
+        OcoLink::with_stop(market, OrderId::new(hex(1)), stop)
+    }
+
+    fn firing(mut link: OcoLink) -> OcoLink {
+        link.stop.as_mut().unwrap().status = TriggerStatus::Firing { price: dec("0.9") };
+        link
+    }
+
+    fn stop_status(link: &OcoLink) -> &TriggerStatus {
+        &link.stop.as_ref().unwrap().status
+    }
+
+    #[test]
+    fn fill_unwinds_then_cancel_completes() {
+        let mut link = link();
+        assert!(!link.observe(&order("0x01", 0, false, false)));
+        assert!(!link.observe(&order("0x99", 100, true, false)));
+        assert_eq!(link.survivor(), None);
+
+        assert!(link.observe(&order("0x01", 40, false, false)));
+        assert_eq!(
+            link.status,
+            OcoStatus::Unwinding {
+                trigger: OrderId::new("0x01")
+            }
+        );
+        assert_eq!(link.survivor(), Some(&OrderId::new("0x02")));
+
+        // The partly filled trigger keeps resting; only the other leg is cancelled.
+        link.observe(&order("0x02", 0, true, true));
+        assert!(link.is_done());
+        assert_eq!(link.survivor(), None);
+        assert!(!link.both_filled());
+    }
+
+    #[test]
+    fn external_cancel_unwinds_and_races_are_recorded() {
+        let mut link = link();
+        link.observe(&order("0x02", 0, true, true));
+        assert_eq!(link.survivor(), Some(&OrderId::new("0x01")));
+        assert!(link.filled.is_empty());
+
+        let mut link = self::link();
+        link.observe(&order("0x01", 100, true, false));
+        link.observe(&order("0x02", 30, false, false));
+        assert!(!link.is_done());
+        link.observe(&order("0x02", 30, true, true));
+        assert!(link.is_done());
+        assert!(link.both_filled());
+    }
+
+    #[test]
+    fn cancelling_the_pair_ends_cancelled_unless_a_leg_executed() {
+        let mut link = link();
+        assert!(!link.settle_cancel());
+        link.mark_closed(&OrderId::new("0x01"));
+        link.mark_closed(&OrderId::new("0x02"));
+        assert!(link.settle_cancel());
+        assert_eq!(link.status, OcoStatus::Cancelled);
+        assert!(link.is_done());
+        assert!(!link.observe(&order("0x01", 100, true, false)));
+
+        let mut link = self::link();
+        link.observe(&order("0x01", 20, true, true));
+        link.observe(&order("0x02", 0, true, true));
+        assert!(link.settle_cancel());
+        assert_eq!(link.status, OcoStatus::Done);
+        assert_eq!(link.filled, [OrderId::new("0x01")]);
+    }
+
+    #[test]
+    fn persists_round_trip() {
+        let mut link = link();
+        link.observe(&order("0x01", 10, false, false));
+        let path = std::env::temp_dir().join(format!("o2-oco-test-{}.json", std::process::id()));
+        link.save(&path).unwrap();
+        let loaded = OcoLink::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, link);
+    }
+    #[test]
+    fn only_resting_legs_are_placed() {
+        let stop = OcoLeg::stop(
+            Side::Sell,
+            dec("1"),
+            TriggerCondition::AtOrBelow(dec("0.95")),
+            TriggerOrder::Market { price: dec("0.9") },
+        );
+        assert!(stop.to_action().is_err());
+        let market =
+            OcoLeg::new(Side::Sell, dec("1.1"), dec("1")).with_order_type(OrderType::Market);
+        assert!(market.to_action().is_err());
+        assert!(market.to_stop(&MarketSymbol::new("fFUEL/fUSDC")).is_none());
+    }
+
+    #[test]
+    fn stop_is_dropped_when_the_resting_leg_executes() {
+        let mut link = stop_link();
+        assert!(link.observe(&order(&hex(1), 30, false, false)));
+        assert_eq!(link.status, OcoStatus::Done);
+        assert_eq!(stop_status(&link), &TriggerStatus::Cancelled);
+        assert_eq!(link.survivor(), None);
+
+        let mut link = stop_link();
+        assert!(!link.settle_cancel());
+        assert_eq!(stop_status(&link), &TriggerStatus::Cancelled);
+        link.mark_closed(&OrderId::new(hex(1)));
+        assert!(link.settle_cancel());
+        assert_eq!(link.status, OcoStatus::Cancelled);
+    }
+
+    #[test]
+    fn failed_stop_is_settled_from_the_resting_leg() {
+        // Still open: the stop's transaction did not land.
+        let mut link = firing(stop_link());
+        link.resolve_stop(false, "timed out".into());
+        assert_eq!(
+            link.status,
+            OcoStatus::Failed {
+                reason: "timed out".into()
+            }
+        );
+        assert!(link.is_done());
+
+        // The take-profit executed first; the stop is dropped.
+        let mut link = firing(stop_link());
+        link.observe(&order(&hex(1), 100, true, false));
+        assert!(!link.is_done());
+        link.resolve_stop(true, "reverted".into());
+        assert_eq!(link.status, OcoStatus::Done);
+        assert_eq!(stop_status(&link), &TriggerStatus::Cancelled);
+
+        // Closed without a fill after an unknown outcome: the stop's cancel landed.
+        let mut link = firing(stop_link());
+        link.observe(&order(&hex(1), 0, true, true));
+        link.resolve_stop(false, "timed out".into());
+        assert_eq!(link.status, OcoStatus::Done);
+        assert_eq!(
+            stop_status(&link),
+            &TriggerStatus::Fired {
+                price: dec("0.9"),
+                order_id: None
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_fires_with_the_cancel_of_the_resting_leg() {
+        let market = Market {
+            contract_id: ContractId::new(hex(1)),
+            market_id: MarketId::new(hex(0xaa)),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 0,
+            dust: 0,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "fFUEL".into(),
+                asset: AssetId::new(hex(3)),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "fUSDC".into(),
+                asset: AssetId::new(hex(4)),
+                decimals: 6,
+                max_precision: 4,
+            },
+        };
+        let markets = MarketsResponse {
+            books_registry_id: ContractId::new(hex(6)),
+            books_whitelist_id: None,
+            books_blacklist_id: None,
+            accounts_registry_id: ContractId::new(hex(2)),
+            trade_account_oracle_id: ContractId::new(hex(7)),
+            fast_bridge_asset_registry_contract_id: None,
+            chain_id: "0x0".into(),
+            base_asset_id: AssetId::new(hex(8)),
+            markets: vec![market.clone()],
+        };
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/markets", serde_json::to_value(markets).unwrap());
+        mock.script(
+            "POST",
+            "/v1/session/actions",
+            [MockResponse::json(serde_json::json!({
+                "tx_id": "0xfeed",
+                "orders": [{
+                    "order_id": hex(0x0b),
+                    "side": "Sell",
+                    "order_type": "Spot",
+                    "quantity": "100000000000",
+                    "quantity_fill": "100000000000",
+                    "price": "855000",
+                    "close": true,
+                }],
+            }))],
+        );
+        let session = Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [2u8; 32],
+            trade_account_id: TradeAccountId::new(hex(5)),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce: 3,
+        };
+        let nonces = NonceManager::new(&session);
+        let mut worker = Worker::new(mock.client(), session, nonces, market, stop_link());
+        let publisher = Publisher {
+            path: None,
+            link_tx: watch::channel(worker.link.clone()).0,
+        };
+
+        // Above the stop nothing is submitted.
+        assert!(!worker.observe_trade(960_000, &publisher).await);
+        assert!(mock.requests_to("POST", "/v1/session/actions").is_empty());
+
+        assert!(worker.observe_trade(900_000, &publisher).await);
+        let submitted = mock.requests_to("POST", "/v1/session/actions");
+        assert_eq!(submitted.len(), 1);
+        let request = submitted[0].json();
+        let actions = &request["actions"][0]["actions"];
+        assert_eq!(actions[0]["CancelOrder"]["order_id"], hex(1));
+        assert_eq!(actions[1]["CreateOrder"]["side"], "Sell");
+
+        let link = &worker.link;
+        assert_eq!(link.status, OcoStatus::Done);
+        assert_eq!(link.closed, [OrderId::new(hex(1))]);
+        assert_eq!(link.filled, [OrderId::new(hex(0x0b))]);
+        assert_eq!(
+            stop_status(link),
+            &TriggerStatus::Fired {
+                price: dec("0.9"),
+                order_id: Some(OrderId::new(hex(0x0b)))
+            }
+        );
+        // A stop fires at most once.
+        assert!(!worker.observe_trade(800_000, &publisher).await);
+        assert_eq!(mock.requests_to("POST", "/v1/session/actions").len(), 1);
+    }
+
+    #[test]
+    fn stop_links_persist_round_trip() {
+        let link = firing(stop_link());
+        let path =
+            std::env::temp_dir().join(format!("o2-oco-stop-test-{}.json", std::process::id()));
+        link.save(&path).unwrap();
+        let loaded = OcoLink::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, link);
+    }
+}
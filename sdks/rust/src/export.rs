@@ -0,0 +1,558 @@
+//! Exporting market data to CSV, or Parquet with the `parquet` feature.
+//!
+//! [`Exporter`] writes [`Record`]s, one or more rows each, under a fixed
+//! typed schema: [`Bar`]s, [`Trade`]s and [`DepthSample`]s (order book
+//! snapshots, one row per level). It takes records one at a time, from a
+//! slice, or from a stream such as
+//! [`O2Client::trade_history`](crate::O2Client::trade_history), so a long
+//! history is written as it is fetched rather than held in memory:
+//!
+//! ```rust,ignore
+//! let mut out = Exporter::<Trade>::create("trades.csv", Format::Csv)?;
+//! out.write_stream(client.trade_history("fFUEL/fUSDC", from).await?).await?;
+//! out.finish()?;
+//!
+//! let bars = client.get_bars("fFUEL/fUSDC", "1h", from_ts, to_ts).await?;
+//! let mut out = Exporter::<Bar>::create("bars.parquet", Format::Parquet)?;
+//! out.write_all(&bars)?;
+//! out.finish()?;
+//! ```
+//!
+//! Prices and quantities are the chain-scaled integers of the models; times
+//! are Unix milliseconds in CSV and UTC millisecond timestamps in Parquet.
+//! 128-bit amounts are `DECIMAL(38, 0)` in Parquet.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use futures_util::{Stream, StreamExt};
+
+use crate::errors::O2Error;
+use crate::models::{Bar, DepthSnapshot, Trade};
+use crate::orderbook::OrderBook;
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+
+/// The type of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Milliseconds since the Unix epoch.
+    Timestamp,
+    UInt64,
+    /// Amounts that may exceed `u64`.
+    UInt128,
+    Utf8,
+}
+
+/// A named, typed column of an export schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+}
+
+const fn column(name: &'static str, kind: ColumnType) -> Column {
+    Column { name, kind }
+}
+
+/// One value of a row, matching its column's [`ColumnType`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    Timestamp(Timestamp),
+    UInt64(u64),
+    UInt128(u128),
+    Utf8(String),
+}
+
+impl Cell {
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Timestamp(ts) => ts.as_unix_millis().to_string(),
+            Cell::UInt64(v) => v.to_string(),
+            Cell::UInt128(v) => v.to_string(),
+            Cell::Utf8(s) => s.clone(),
+        }
+    }
+}
+
+/// A model that can be exported as rows of a fixed schema.
+pub trait Record {
+    /// The columns of each row, in order.
+    fn schema() -> &'static [Column];
+
+    /// Append this record's rows to `out`.
+    fn rows(&self, out: &mut Vec<Vec<Cell>>);
+}
+
+impl Record for Bar {
+    fn schema() -> &'static [Column] {
+        const SCHEMA: &[Column] = &[
+            column("timestamp", ColumnType::Timestamp),
+            column("open", ColumnType::UInt64),
+            column("high", ColumnType::UInt64),
+            column("low", ColumnType::UInt64),
+            column("close", ColumnType::UInt64),
+            column("buy_volume", ColumnType::UInt128),
+            column("sell_volume", ColumnType::UInt128),
+        ];
+        SCHEMA
+    }
+
+    fn rows(&self, out: &mut Vec<Vec<Cell>>) {
+        out.push(vec![
+            Cell::Timestamp(self.timestamp),
+            Cell::UInt64(self.open),
+            Cell::UInt64(self.high),
+            Cell::UInt64(self.low),
+            Cell::UInt64(self.close),
+            Cell::UInt128(self.buy_volume),
+            Cell::UInt128(self.sell_volume),
+        ]);
+    }
+}
+
+impl Record for Trade {
+    fn schema() -> &'static [Column] {
+        const SCHEMA: &[Column] = &[
+            column("timestamp", ColumnType::Timestamp),
+            column("trade_id", ColumnType::Utf8),
+            column("side", ColumnType::Utf8),
+            column("price", ColumnType::UInt64),
+            column("quantity", ColumnType::UInt64),
+            column("total", ColumnType::UInt128),
+        ];
+        SCHEMA
+    }
+
+    fn rows(&self, out: &mut Vec<Vec<Cell>>) {
+        out.push(vec![
+            Cell::Timestamp(self.timestamp),
+            Cell::Utf8(self.trade_id.as_str().to_string()),
+            Cell::Utf8(self.side.as_str().to_string()),
+            Cell::UInt64(self.price),
+            Cell::UInt64(self.quantity),
+            Cell::UInt128(self.total),
+        ]);
+    }
+}
+
+/// An order book snapshot taken at a known time, exported as one row per
+/// level: bids best first, then asks best first.
+#[derive(Debug, Clone)]
+pub struct DepthSample {
+    pub timestamp: Timestamp,
+    pub depth: DepthSnapshot,
+}
+
+impl DepthSample {
+    /// `depth` as of now, e.g. fresh from
+    /// [`get_depth`](crate::O2Client::get_depth).
+    pub fn now(depth: DepthSnapshot) -> Self {
+        Self {
+            timestamp: Timestamp::now(),
+            depth,
+        }
+    }
+
+    /// The top `levels` of each side of `book`, as of now.
+    pub fn from_book(book: &OrderBook, levels: usize) -> Self {
+        Self::now(book.to_snapshot(levels))
+    }
+}
+
+impl Record for DepthSample {
+    fn schema() -> &'static [Column] {
+        const SCHEMA: &[Column] = &[
+            column("timestamp", ColumnType::Timestamp),
+            column("side", ColumnType::Utf8),
+            column("level", ColumnType::UInt64),
+            column("price", ColumnType::UInt64),
+            column("quantity", ColumnType::UInt64),
+        ];
+        SCHEMA
+    }
+
+    fn rows(&self, out: &mut Vec<Vec<Cell>>) {
+        let sides = [("bid", &self.depth.bids), ("ask", &self.depth.asks)];
+        for (side, levels) in sides {
+            for (i, level) in levels.iter().enumerate() {
+                out.push(vec![
+                    Cell::Timestamp(self.timestamp),
+                    Cell::Utf8(side.to_string()),
+                    Cell::UInt64(i as u64),
+                    Cell::UInt64(level.price),
+                    Cell::UInt64(level.quantity),
+                ]);
+            }
+        }
+    }
+}
+
+/// Output file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+enum Sink {
+    Csv(csv::Writer<Box<dyn Write + Send>>),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet_sink::ParquetSink),
+}
+
+fn export_error(e: impl std::fmt::Display) -> O2Error {
+    O2Error::Other(format!("export failed: {e}"))
+}
+
+/// Writes records of one type to CSV or Parquet. Call
+/// [`finish`](Self::finish) when done: a Parquet file is unreadable without
+/// its footer.
+pub struct Exporter<R: Record> {
+    sink: Sink,
+    rows: Vec<Vec<Cell>>,
+    written: u64,
+    _record: PhantomData<fn(&R)>,
+}
+
+impl<R: Record> Exporter<R> {
+    /// Export to a new file at `path`, replacing any existing one.
+    pub fn create(path: impl AsRef<Path>, format: Format) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| O2Error::Other(format!("cannot create export {}: {e}", path.display())))?;
+        let out = BufWriter::new(file);
+        match format {
+            Format::Csv => Self::csv(out),
+            #[cfg(feature = "parquet")]
+            Format::Parquet => Self::parquet(out),
+        }
+    }
+
+    /// Export CSV with a header row to `out`.
+    pub fn csv(out: impl Write + Send + 'static) -> Result<Self, O2Error> {
+        let mut writer = csv::Writer::from_writer(Box::new(out) as Box<dyn Write + Send>);
+        writer
+            .write_record(R::schema().iter().map(|c| c.name))
+            .map_err(export_error)?;
+        Ok(Self::with_sink(Sink::Csv(writer)))
+    }
+
+    /// Export Snappy-compressed Parquet to `out`, in row groups of up to
+    /// 8192 rows.
+    #[cfg(feature = "parquet")]
+    pub fn parquet(out: impl Write + Send + 'static) -> Result<Self, O2Error> {
+        let sink = parquet_sink::ParquetSink::new(Box::new(out), R::schema())?;
+        Ok(Self::with_sink(Sink::Parquet(sink)))
+    }
+
+    fn with_sink(sink: Sink) -> Self {
+        Self {
+            sink,
+            rows: Vec::new(),
+            written: 0,
+            _record: PhantomData,
+        }
+    }
+
+    /// Write one record.
+    pub fn write(&mut self, record: &R) -> Result<(), O2Error> {
+        record.rows(&mut self.rows);
+        self.written += self.rows.len() as u64;
+        match &mut self.sink {
+            Sink::Csv(writer) => {
+                for row in self.rows.drain(..) {
+                    writer
+                        .write_record(row.iter().map(Cell::to_csv))
+                        .map_err(export_error)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(sink) => sink.push(self.rows.drain(..)),
+        }
+    }
+
+    /// Write every record in `records`.
+    pub fn write_all<'a>(&mut self, records: impl IntoIterator<Item = &'a R>) -> Result<(), O2Error>
+    where
+        R: 'a,
+    {
+        records.into_iter().try_for_each(|r| self.write(r))
+    }
+
+    /// Write records from `stream` until it ends. Stops at the first error,
+    /// keeping what was written before it.
+    pub async fn write_stream<S>(&mut self, stream: S) -> Result<(), O2Error>
+    where
+        S: Stream<Item = Result<R, O2Error>>,
+    {
+        let mut stream = std::pin::pin!(stream);
+        while let Some(record) = stream.next().await {
+            self.write(&record?)?;
+        }
+        Ok(())
+    }
+
+    /// Flush and close the output. Returns the number of rows written.
+    pub fn finish(self) -> Result<u64, O2Error> {
+        match self.sink {
+            Sink::Csv(mut writer) => writer.flush().map_err(export_error)?,
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(sink) => sink.close()?,
+        }
+        debug!("export.finish rows={}", self.written);
+        Ok(self.written)
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use arrow_array::builder::{
+        Decimal128Builder, StringBuilder, TimestampMillisecondBuilder, UInt64Builder,
+    };
+    use arrow_array::{ArrayRef, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+
+    use super::{export_error, Cell, Column, ColumnType};
+    use crate::errors::O2Error;
+
+    /// Rows buffered per Parquet row group.
+    const ROW_GROUP: usize = 8192;
+
+    /// Largest value of a `DECIMAL(38, 0)`.
+    const DECIMAL_MAX: u128 = 10u128.pow(38) - 1;
+
+    pub(super) struct ParquetSink {
+        schema: SchemaRef,
+        writer: ArrowWriter<Box<dyn Write + Send>>,
+        buffer: Vec<Vec<Cell>>,
+    }
+
+    fn data_type(kind: ColumnType) -> DataType {
+        match kind {
+            ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            ColumnType::UInt64 => DataType::UInt64,
+            ColumnType::UInt128 => DataType::Decimal128(38, 0),
+            ColumnType::Utf8 => DataType::Utf8,
+        }
+    }
+
+    impl ParquetSink {
+        pub(super) fn new(out: Box<dyn Write + Send>, columns: &[Column]) -> Result<Self, O2Error> {
+            let fields: Vec<Field> = columns
+                .iter()
+                .map(|c| Field::new(c.name, data_type(c.kind), false))
+                .collect();
+            let schema = Arc::new(Schema::new(fields));
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let writer =
+                ArrowWriter::try_new(out, schema.clone(), Some(props)).map_err(export_error)?;
+            Ok(Self {
+                schema,
+                writer,
+                buffer: Vec::new(),
+            })
+        }
+
+        /// Buffer `rows`, writing a row group once enough are waiting.
+        pub(super) fn push(
+            &mut self,
+            rows: impl Iterator<Item = Vec<Cell>>,
+        ) -> Result<(), O2Error> {
+            self.buffer.extend(rows);
+            if self.buffer.len() >= ROW_GROUP {
+                let rows = std::mem::take(&mut self.buffer);
+                self.write(rows)?;
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, rows: Vec<Vec<Cell>>) -> Result<(), O2Error> {
+            let mut columns: Vec<ArrayRef> = Vec::with_capacity(self.schema.fields().len());
+            for (i, field) in self.schema.fields().iter().enumerate() {
+                let cells = rows.iter().map(|row| &row[i]);
+                let array: ArrayRef = match field.data_type() {
+                    DataType::Timestamp(..) => {
+                        let mut b = TimestampMillisecondBuilder::with_capacity(rows.len());
+                        for cell in cells {
+                            if let Cell::Timestamp(ts) = cell {
+                                b.append_value(
+                                    i64::try_from(ts.as_unix_millis()).unwrap_or(i64::MAX),
+                                );
+                            }
+                        }
+                        Arc::new(b.finish().with_timezone("UTC"))
+                    }
+                    DataType::UInt64 => {
+                        let mut b = UInt64Builder::with_capacity(rows.len());
+                        for cell in cells {
+                            if let Cell::UInt64(v) = cell {
+                                b.append_value(*v);
+                            }
+                        }
+                        Arc::new(b.finish())
+                    }
+                    DataType::Decimal128(..) => {
+                        let mut b = Decimal128Builder::with_capacity(rows.len());
+                        for cell in cells {
+                            if let Cell::UInt128(v) = cell {
+                                if *v > DECIMAL_MAX {
+                                    return Err(export_error(format!(
+                                        "{} value {v} exceeds DECIMAL(38, 0)",
+                                        field.name()
+                                    )));
+                                }
+                                b.append_value(*v as i128);
+                            }
+                        }
+                        Arc::new(
+                            b.finish()
+                                .with_precision_and_scale(38, 0)
+                                .map_err(export_error)?,
+                        )
+                    }
+                    _ => {
+                        let mut b = StringBuilder::new();
+                        for cell in cells {
+                            if let Cell::Utf8(s) = cell {
+                                b.append_value(s);
+                            }
+                        }
+                        Arc::new(b.finish())
+                    }
+                };
+                columns.push(array);
+            }
+            let batch = RecordBatch::try_new(self.schema.clone(), columns).map_err(export_error)?;
+            self.writer.write(&batch).map_err(export_error)?;
+            self.writer.flush().map_err(export_error)
+        }
+
+        pub(super) fn close(mut self) -> Result<(), O2Error> {
+            if !self.buffer.is_empty() {
+                let rows = std::mem::take(&mut self.buffer);
+                self.write(rows)?;
+            }
+            self.writer.close().map(|_| ()).map_err(export_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::models::DepthLevel;
+
+    /// A writer whose output the test can read after the exporter owns it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trade(millis: u64, price: u64) -> Trade {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": "0x01",
+            "side": "Sell",
+            "total": "340282366920938463463374607431768211455",
+            "quantity": "2",
+            "price": price.to_string(),
+            "timestamp": millis,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn csv_has_a_header_and_one_row_per_record_or_level() {
+        let out = Shared::default();
+        let mut trades = Exporter::<Trade>::csv(out.clone()).unwrap();
+        trades.write(&trade(1_000, 100)).unwrap();
+        let rest = futures_util::stream::iter([Ok(trade(2_000, 101))]);
+        trades.write_stream(rest).await.unwrap();
+        assert_eq!(trades.finish().unwrap(), 2);
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            text,
+            "timestamp,trade_id,side,price,quantity,total\n\
+             1000,0x01,Sell,100,2,340282366920938463463374607431768211455\n\
+             2000,0x01,Sell,101,2,340282366920938463463374607431768211455\n"
+        );
+
+        let out = Shared::default();
+        let mut depth = Exporter::<DepthSample>::csv(out.clone()).unwrap();
+        let level = |price, quantity| DepthLevel { price, quantity };
+        depth
+            .write(&DepthSample {
+                timestamp: Timestamp::from_unix_millis(5),
+                depth: DepthSnapshot {
+                    bids: vec![level(99, 1), level(98, 2)],
+                    asks: vec![level(101, 3)],
+                },
+            })
+            .unwrap();
+        assert_eq!(depth.finish().unwrap(), 3);
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert!(text.ends_with("5,bid,1,98,2\n5,ask,0,101,3\n"), "{text}");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trips_with_typed_columns() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Decimal128Type, TimestampMillisecondType, UInt64Type};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let bar = |millis: u64, close: u64| -> Bar {
+            serde_json::from_value(serde_json::json!({
+                "open": "1", "high": "2", "low": "1", "close": close.to_string(),
+                "buy_volume": "7", "sell_volume": "8", "timestamp": millis,
+            }))
+            .unwrap()
+        };
+        let path =
+            std::env::temp_dir().join(format!("o2-export-test-{}.parquet", std::process::id()));
+        let mut bars = Exporter::<Bar>::create(&path, Format::Parquet).unwrap();
+        bars.write_all(&[bar(60_000, 10), bar(120_000, 11)])
+            .unwrap();
+        assert_eq!(bars.finish().unwrap(), 2);
+
+        let file = File::open(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.into_iter().next().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let times = batch.column(0).as_primitive::<TimestampMillisecondType>();
+        assert_eq!(times.value(1), 120_000);
+        let close = batch.column(4).as_primitive::<UInt64Type>();
+        assert_eq!(close.value(0), 10);
+        let volume = batch.column(5).as_primitive::<Decimal128Type>();
+        assert_eq!(volume.value(1), 7);
+
+        let mut wide = Exporter::<Trade>::parquet(Shared::default()).unwrap();
+        wide.write(&trade(1, 1)).unwrap();
+        assert!(wide.finish().is_err(), "u128::MAX exceeds DECIMAL(38, 0)");
+    }
+}
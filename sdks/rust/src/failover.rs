@@ -0,0 +1,329 @@
+//! Failover across redundant API and WebSocket endpoints.
+//!
+//! A [`NetworkConfig`](crate::NetworkConfig) lists fallback REST bases in
+//! `api_base_fallbacks` and fallback WebSocket URLs in `ws_url_fallbacks`,
+//! tried in order after `api_base` and `ws_url`. Requests go to the first
+//! endpoint that is not cooling down. Once the active endpoint fails
+//! [`FailoverPolicy::max_failures`] times in a row it cools down for
+//! [`FailoverPolicy::cooldown`] and traffic moves to the next one; an earlier
+//! endpoint takes over again once its cooldown ends, so a recovered primary is
+//! back in use without a restart.
+//!
+//! For REST, a failure is a connection error, a timeout or a 502, 503 or 504
+//! response; retries of a read follow the switch, so a read can finish on a
+//! fallback. For the WebSocket, a failure is a connect attempt that does not
+//! succeed. The first connect tries each URL once in order.
+//!
+//! ```rust,ignore
+//! let mut config = NetworkConfig::from_network(Network::Mainnet);
+//! config.api_base_fallbacks = vec!["https://eu.gateway.example.com".into()];
+//! config.ws_url_fallbacks = vec!["wss://eu.gateway.example.com/v1/ws".into()];
+//! let client = O2Client::with_config(config);
+//! println!("{}", client.api.active_endpoint());
+//! ```
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::{Request, Response, StatusCode};
+
+use crate::runtime;
+use crate::trace::debug;
+use crate::transport::Transport;
+
+/// When traffic moves off an endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    /// Consecutive failures of the active endpoint before moving to the next
+    /// (default: 3).
+    pub max_failures: u32,
+    /// How long a failed endpoint is passed over before it is tried again
+    /// (default: 30s).
+    pub cooldown: Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How one endpoint has been doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Whether new requests go here.
+    pub active: bool,
+    /// Failures since the last success or cooldown.
+    pub consecutive_failures: u32,
+    /// Time left before the endpoint is tried again, if it is cooling down.
+    pub cooling_down: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    failures: u32,
+    down_until: Option<Instant>,
+}
+
+impl Endpoint {
+    fn is_up(&self, now: Instant) -> bool {
+        self.down_until.map_or(true, |until| until <= now)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    endpoints: Vec<Endpoint>,
+    active: usize,
+    policy: FailoverPolicy,
+}
+
+impl State {
+    /// The first endpoint that is up, or the current one if none is.
+    fn route(&mut self, now: Instant) -> usize {
+        if let Some(i) = self.endpoints.iter().position(|e| e.is_up(now)) {
+            if i != self.active {
+                debug!(
+                    "failover.route from={} to={}",
+                    self.endpoints[self.active].url, self.endpoints[i].url
+                );
+                self.active = i;
+            }
+        }
+        self.active
+    }
+
+    /// Take `index` out of rotation for the cooldown.
+    fn cool_down(&mut self, index: usize, now: Instant) {
+        let endpoint = &mut self.endpoints[index];
+        endpoint.failures = 0;
+        endpoint.down_until = Some(now + self.policy.cooldown);
+        if index == self.active {
+            let next = match self.endpoints.iter().position(|e| e.is_up(now)) {
+                Some(next) => next,
+                None => (index + 1) % self.endpoints.len(),
+            };
+            debug!(
+                "failover.switch from={} to={}",
+                self.endpoints[index].url, self.endpoints[next].url
+            );
+            self.active = next;
+        }
+    }
+}
+
+/// An ordered list of interchangeable endpoints and their health. Clones
+/// share state.
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoints {
+    state: Arc<Mutex<State>>,
+}
+
+impl Endpoints {
+    /// `primary` followed by `fallbacks`, skipping duplicates.
+    pub(crate) fn new(primary: &str, fallbacks: &[String], policy: FailoverPolicy) -> Self {
+        let mut endpoints: Vec<Endpoint> = Vec::with_capacity(1 + fallbacks.len());
+        for url in std::iter::once(primary).chain(fallbacks.iter().map(String::as_str)) {
+            let url = url.trim_end_matches('/');
+            if !endpoints.iter().any(|e| e.url == url) {
+                endpoints.push(Endpoint {
+                    url: url.to_string(),
+                    failures: 0,
+                    down_until: None,
+                });
+            }
+        }
+        Self {
+            state: Arc::new(Mutex::new(State {
+                endpoints,
+                active: 0,
+                policy,
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().endpoints.len()
+    }
+
+    /// Where new requests should go.
+    pub(crate) fn active(&self) -> String {
+        let mut state = self.lock();
+        let active = state.route(runtime::now());
+        state.endpoints[active].url.clone()
+    }
+
+    /// Record the outcome of a request to `url`, which must be one of the
+    /// endpoints; other URLs are ignored.
+    pub(crate) fn record(&self, url: &str, ok: bool) {
+        let mut state = self.lock();
+        let Some(index) = state.endpoints.iter().position(|e| e.url == url) else {
+            return;
+        };
+        let now = runtime::now();
+        let max_failures = state.policy.max_failures.max(1);
+        let endpoint = &mut state.endpoints[index];
+        if ok {
+            endpoint.failures = 0;
+            endpoint.down_until = None;
+            return;
+        }
+        endpoint.failures += 1;
+        debug!(
+            "failover.failure url={} consecutive={}",
+            endpoint.url, endpoint.failures
+        );
+        if endpoint.failures >= max_failures {
+            state.cool_down(index, now);
+        }
+    }
+
+    /// Take `url` out of rotation at once, as after a failed first connect.
+    pub(crate) fn skip(&self, url: &str) {
+        let mut state = self.lock();
+        if let Some(index) = state.endpoints.iter().position(|e| e.url == url) {
+            state.cool_down(index, runtime::now());
+        }
+    }
+
+    /// The active endpoint and `url` moved onto it, if `url` is on one of the
+    /// endpoints.
+    fn reroute(&self, url: &str) -> Option<(String, String)> {
+        let mut state = self.lock();
+        let active = state.route(runtime::now());
+        let target = &state.endpoints[active].url;
+        let rest = state
+            .endpoints
+            .iter()
+            .find_map(|e| path_after(url, &e.url))?;
+        Some((target.clone(), format!("{target}{rest}")))
+    }
+
+    pub(crate) fn health(&self) -> Vec<EndpointHealth> {
+        let mut state = self.lock();
+        let now = runtime::now();
+        let active = state.route(now);
+        state
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, e)| EndpointHealth {
+                url: e.url.clone(),
+                active: i == active,
+                consecutive_failures: e.failures,
+                cooling_down: e
+                    .down_until
+                    .filter(|&until| until > now)
+                    .map(|until| until - now),
+            })
+            .collect()
+    }
+}
+
+/// What follows `base` in `url`, if `url` is on `base`.
+fn path_after<'a>(url: &'a str, base: &str) -> Option<&'a str> {
+    let rest = url.strip_prefix(base)?;
+    (rest.is_empty() || rest.starts_with(['/', '?'])).then_some(rest)
+}
+
+/// Sends each request to the active endpoint and records how it went.
+pub(crate) struct FailoverTransport {
+    pub(crate) inner: Arc<dyn Transport>,
+    pub(crate) endpoints: Endpoints,
+}
+
+impl Transport for FailoverTransport {
+    fn send(&self, mut request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+        async move {
+            let Some((base, url)) = self.endpoints.reroute(request.url().as_str()) else {
+                return self.inner.send(request).await;
+            };
+            if url != request.url().as_str() {
+                match url.parse() {
+                    Ok(url) => *request.url_mut() = url,
+                    Err(e) => debug!("failover.reroute_failed url={} error={}", url, e),
+                }
+            }
+            let result = self.inner.send(request).await;
+            let ok = match &result {
+                Ok(resp) => !matches!(
+                    resp.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(e) => !(e.is_connect() || e.is_timeout() || e.is_request()),
+            };
+            self.endpoints.record(&base, ok);
+            result
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Endpoints {
+        Endpoints::new(
+            "https://a.example/",
+            &["https://b.example".into(), "https://a.example".into()],
+            FailoverPolicy {
+                max_failures: 2,
+                cooldown: Duration::from_millis(50),
+            },
+        )
+    }
+
+    #[test]
+    fn repeated_failures_move_to_the_next_endpoint() {
+        let endpoints = endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints.active(), "https://a.example");
+
+        endpoints.record("https://a.example", false);
+        endpoints.record("https://a.example", true);
+        endpoints.record("https://a.example", false);
+        assert_eq!(endpoints.active(), "https://a.example");
+        endpoints.record("https://a.example", false);
+        assert_eq!(endpoints.active(), "https://b.example");
+
+        let health = endpoints.health();
+        assert!(!health[0].active && health[0].cooling_down.is_some());
+        assert!(health[1].active && health[1].cooling_down.is_none());
+
+        // The primary takes over again once its cooldown ends.
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(endpoints.active(), "https://a.example");
+    }
+
+    #[test]
+    fn requests_are_rerouted_to_the_active_base() {
+        let endpoints = endpoints();
+        endpoints.skip("https://a.example");
+        assert_eq!(
+            endpoints.reroute("https://a.example/v1/markets?x=1"),
+            Some((
+                "https://b.example".to_string(),
+                "https://b.example/v1/markets?x=1".to_string()
+            ))
+        );
+        assert_eq!(endpoints.reroute("https://faucet.example/mint"), None);
+        assert_eq!(endpoints.reroute("https://a.example.org/v1"), None);
+
+        // With every endpoint down, traffic keeps rotating.
+        endpoints.skip("https://b.example");
+        assert_eq!(endpoints.active(), "https://a.example");
+    }
+}
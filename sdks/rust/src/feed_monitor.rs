@@ -0,0 +1,152 @@
+//! Stall detection for depth and trade feeds.
+//!
+//! A subscription can go quiet while the socket stays connected and keeps
+//! answering pings, which the reconnect logic cannot see. The
+//! [`O2WebSocket`](crate::websocket::O2WebSocket) records per-feed message gaps
+//! (see [`FeedStats`]); a [`FeedMonitor`] checks them periodically and, when a
+//! feed has been silent for longer than [`FeedMonitorConfig::stall_window`],
+//! confirms over REST that the market has actually traded since the feed's last
+//! message. If so it emits [`WsLifecycleEvent::FeedStalled`] and, optionally,
+//! re-subscribes just that feed.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::api::O2Api;
+use crate::runtime::{self, Task};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::{FeedHandle, FeedKind, FeedStats, WsLifecycleEvent};
+
+/// Configuration for a [`FeedMonitor`].
+#[derive(Debug, Clone)]
+pub struct FeedMonitorConfig {
+    /// A feed silent for longer than this is a stall candidate.
+    pub stall_window: Duration,
+    /// How often feeds are checked.
+    pub check_interval: Duration,
+    /// Only report a stall if REST shows a trade after the feed's last message.
+    /// Disable for markets where silence alone is abnormal.
+    pub require_rest_activity: bool,
+    /// Re-subscribe a stalled feed. Retried at most once per `stall_window`.
+    pub resubscribe: bool,
+}
+
+impl Default for FeedMonitorConfig {
+    fn default() -> Self {
+        Self {
+            stall_window: Duration::from_secs(30),
+            check_interval: Duration::from_secs(5),
+            require_rest_activity: true,
+            resubscribe: true,
+        }
+    }
+}
+
+/// Background task that reports stalled feeds on the WebSocket lifecycle channel.
+///
+/// Dropping the monitor stops it.
+pub struct FeedMonitor {
+    task: Task<()>,
+}
+
+impl FeedMonitor {
+    pub(crate) fn spawn(feeds: FeedHandle, api: O2Api, config: FeedMonitorConfig) -> Self {
+        let task = runtime::spawn(run(feeds, api, config));
+        Self { task }
+    }
+
+    /// True until the WebSocket terminates or the monitor is dropped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for FeedMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// When a feed was last reported, and its message count at that time.
+struct Flagged {
+    at: Instant,
+    messages: u64,
+}
+
+async fn run(feeds: FeedHandle, api: O2Api, config: FeedMonitorConfig) {
+    let mut flagged: HashMap<(FeedKind, String), Flagged> = HashMap::new();
+    let mut ticker = runtime::interval(config.check_interval);
+    loop {
+        ticker.tick().await;
+        if feeds.is_terminated() {
+            break;
+        }
+        let stats = feeds.stats().await;
+        flagged.retain(|key, _| {
+            stats
+                .iter()
+                .any(|s| s.kind == key.0 && s.market_id == key.1)
+        });
+        for feed in stats {
+            let key = (feed.kind, feed.market_id.clone());
+            if let Some(prev) = flagged.get(&key) {
+                if prev.messages == feed.messages && prev.at.elapsed() < config.stall_window {
+                    continue;
+                }
+            }
+            let silent_for = feed.silent_for();
+            if silent_for < config.stall_window {
+                flagged.remove(&key);
+                continue;
+            }
+            if config.require_rest_activity && !traded_since(&api, &feed, silent_for).await {
+                continue;
+            }
+            debug!(
+                "feed_monitor.stalled kind={:?} market_id={} silent_ms={}",
+                feed.kind,
+                feed.market_id,
+                silent_for.as_millis()
+            );
+            feeds.emit(WsLifecycleEvent::FeedStalled {
+                kind: feed.kind,
+                market_id: feed.market_id.clone(),
+                silent_for,
+            });
+            if config.resubscribe {
+                match feeds.resubscribe(feed.kind, &feed.market_id).await {
+                    Ok(()) => feeds.emit(WsLifecycleEvent::FeedResubscribed {
+                        kind: feed.kind,
+                        market_id: feed.market_id.clone(),
+                    }),
+                    Err(e) => debug!("feed_monitor.resubscribe_failed error={}", e),
+                }
+            }
+            flagged.insert(
+                key,
+                Flagged {
+                    at: runtime::now(),
+                    messages: feed.messages,
+                },
+            );
+        }
+    }
+}
+
+/// True if the market's most recent trade is newer than the feed's last message.
+async fn traded_since(api: &O2Api, feed: &FeedStats, silent_for: Duration) -> bool {
+    let since = Timestamp::now() - silent_for;
+    match api
+        .get_trades(&feed.market_id, "desc", 1, None, None, None)
+        .await
+    {
+        Ok(resp) => resp.trades.first().is_some_and(|t| t.timestamp > since),
+        Err(e) => {
+            debug!(
+                "feed_monitor.rest_check_failed market_id={} error={}",
+                feed.market_id, e
+            );
+            false
+        }
+    }
+}
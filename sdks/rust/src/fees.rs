@@ -0,0 +1,320 @@
+//! Maker and taker fee rates, with account-level overrides.
+//!
+//! Every [`Market`] carries the venue's default fees. An account on a fee tier
+//! or rebate programme pays something else, and O2 has no endpoint that reports
+//! an account's own rates yet, so they are configured as [`FeeOverrides`] on the
+//! client. [`O2Client::my_fees`](crate::O2Client::my_fees) resolves the rates an
+//! account actually pays on a market: a market-specific override first, then an
+//! account-wide one, then the market's defaults. Anything that estimates fees
+//! should go through it rather than read `Market::maker_fee` directly.
+//!
+//! ```rust,ignore
+//! client.set_fee_overrides(
+//!     FeeOverrides::new().account(account_id.clone(), FeeRates::new(0, 150)),
+//! );
+//! let fees = client.my_fees(&account_id, "fFUEL/fUSDC").await?;
+//! println!("taker rate {}", fees.taker_rate());
+//! ```
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{Market, MarketId, Side, TradeAccountId};
+
+/// Fee values are fractions of this: `1_000_000` is 100%, `200` is 0.02%.
+pub const FEE_SCALE: u64 = 1_000_000;
+
+/// Where a set of [`FeeRates`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSource {
+    /// The market's default fees.
+    Market,
+    /// A configured account-level override.
+    Override,
+}
+
+/// Maker and taker fees in the exchange's units (see [`FEE_SCALE`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeRates {
+    pub maker: u64,
+    pub taker: u64,
+    pub source: FeeSource,
+}
+
+impl FeeRates {
+    /// Override rates in the exchange's units, e.g. `FeeRates::new(0, 150)`
+    /// for no maker fee and a 0.015% taker fee.
+    pub fn new(maker: u64, taker: u64) -> Self {
+        Self {
+            maker,
+            taker,
+            source: FeeSource::Override,
+        }
+    }
+
+    /// The market's default fees.
+    pub fn from_market(market: &Market) -> Self {
+        Self {
+            maker: market.maker_fee,
+            taker: market.taker_fee,
+            source: FeeSource::Market,
+        }
+    }
+
+    /// The maker fee as a fraction, e.g. `0.0002`.
+    pub fn maker_rate(&self) -> UnsignedDecimal {
+        to_rate(self.maker)
+    }
+
+    /// The taker fee as a fraction, e.g. `0.0002`.
+    pub fn taker_rate(&self) -> UnsignedDecimal {
+        to_rate(self.taker)
+    }
+
+    /// The maker or taker fee as a fraction.
+    pub fn rate(&self, is_maker: bool) -> UnsignedDecimal {
+        if is_maker {
+            self.maker_rate()
+        } else {
+            self.taker_rate()
+        }
+    }
+
+    /// The fee on `quantity` at `price` on `market`, in quote units.
+    ///
+    /// The exchange charges fees on the quote notional in chain units:
+    /// `notional * fee / FEE_SCALE`, rounded up. Price and quantity are
+    /// truncated to the market's precision first, as when an order is placed.
+    pub fn fee_for(
+        &self,
+        market: &Market,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+        is_maker: bool,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        let (_, fee) = self.chain_fee(market, price, quantity, is_maker)?;
+        format_quote(market, fee)
+    }
+
+    /// Quote units a fill of `quantity` at `price` nets after fees: what a
+    /// sell receives, or what a buy pays. A sell whose fee exceeds its
+    /// notional nets zero.
+    pub fn net_proceeds(
+        &self,
+        market: &Market,
+        side: Side,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+        is_maker: bool,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        let (notional, fee) = self.chain_fee(market, price, quantity, is_maker)?;
+        let net = match side {
+            Side::Buy => notional + fee,
+            Side::Sell => notional.saturating_sub(fee),
+        };
+        format_quote(market, net)
+    }
+
+    /// `(notional, fee)` in quote chain units.
+    fn chain_fee(
+        &self,
+        market: &Market,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+        is_maker: bool,
+    ) -> Result<(u128, u128), O2Error> {
+        let price = market.scale_price(price)?;
+        let quantity = market.scale_quantity(quantity)?;
+        self.chain_fee_units(market, price, quantity, is_maker)
+    }
+
+    /// `(notional, fee)` in quote chain units for a fill already in chain units.
+    pub(crate) fn chain_fee_units(
+        &self,
+        market: &Market,
+        price: u64,
+        quantity: u64,
+        is_maker: bool,
+    ) -> Result<(u128, u128), O2Error> {
+        let (price, quantity) = (price as u128, quantity as u128);
+        let base_factor = 10u128.checked_pow(market.base.decimals).ok_or_else(|| {
+            O2Error::Other(format!(
+                "Invalid base.decimals: 10^{} overflows u128",
+                market.base.decimals
+            ))
+        })?;
+        let notional = price * quantity / base_factor;
+        let fee = if is_maker { self.maker } else { self.taker } as u128;
+        Ok((notional, (notional * fee).div_ceil(FEE_SCALE as u128)))
+    }
+}
+
+fn format_quote(market: &Market, chain_value: u128) -> Result<UnsignedDecimal, O2Error> {
+    let chain_value = u64::try_from(chain_value)
+        .map_err(|_| O2Error::Other("Quote amount exceeds u64 range".into()))?;
+    // Prices and quote amounts share the quote asset's decimals.
+    market.format_price(chain_value)
+}
+
+fn to_rate(fee: u64) -> UnsignedDecimal {
+    UnsignedDecimal::new(Decimal::from(fee) / Decimal::from(FEE_SCALE))
+        .expect("fee rates are non-negative")
+}
+
+/// Per-account fee rates that replace the markets' defaults.
+#[derive(Debug, Clone, Default)]
+pub struct FeeOverrides {
+    accounts: HashMap<TradeAccountId, FeeRates>,
+    markets: HashMap<(TradeAccountId, MarketId), FeeRates>,
+}
+
+impl FeeOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `rates` for `account` on every market without a market-specific override.
+    pub fn account(mut self, account: TradeAccountId, rates: FeeRates) -> Self {
+        self.accounts.insert(account, override_rates(rates));
+        self
+    }
+
+    /// Use `rates` for `account` on one market.
+    pub fn market(mut self, account: TradeAccountId, market_id: MarketId, rates: FeeRates) -> Self {
+        self.markets
+            .insert((account, market_id), override_rates(rates));
+        self
+    }
+
+    /// The rates `account` pays on `market`: a market-specific override, then
+    /// an account-wide one, then the market's defaults.
+    pub fn resolve(&self, account: &TradeAccountId, market: &Market) -> FeeRates {
+        self.markets
+            .get(&(account.clone(), market.market_id.clone()))
+            .or_else(|| self.accounts.get(account))
+            .copied()
+            .unwrap_or_else(|| FeeRates::from_market(market))
+    }
+
+    /// True if no overrides are configured.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.markets.is_empty()
+    }
+}
+
+fn override_rates(rates: FeeRates) -> FeeRates {
+    FeeRates {
+        source: FeeSource::Override,
+        ..rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(id: &str, maker: u64, taker: u64) -> Market {
+        serde_json::from_value(serde_json::json!({
+            "contract_id": "0x01",
+            "market_id": id,
+            "whitelist_id": null,
+            "blacklist_id": null,
+            "maker_fee": maker.to_string(),
+            "taker_fee": taker.to_string(),
+            "min_order": "0",
+            "dust": "0",
+            "price_window": "0",
+            "base": { "symbol": "fFUEL", "asset": "0x02", "decimals": 9, "max_precision": 3 },
+            "quote": { "symbol": "fUSDC", "asset": "0x03", "decimals": 9, "max_precision": 9 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_market_fees() {
+        let alice = TradeAccountId::new("0xa1");
+        let bob = TradeAccountId::new("0xb0");
+        let fuel = market("0xaa", 100, 200);
+        let eth = market("0xbb", 100, 200);
+        let overrides = FeeOverrides::new()
+            .account(alice.clone(), FeeRates::new(0, 150))
+            .market(alice.clone(), eth.market_id.clone(), FeeRates::new(0, 50));
+
+        let rates = overrides.resolve(&alice, &fuel);
+        assert_eq!((rates.maker, rates.taker), (0, 150));
+        assert_eq!(rates.source, FeeSource::Override);
+        assert_eq!(overrides.resolve(&alice, &eth).taker, 50);
+
+        let rates = overrides.resolve(&bob, &fuel);
+        assert_eq!(rates, FeeRates::from_market(&fuel));
+        assert_eq!(rates.source, FeeSource::Market);
+        assert_eq!(rates.maker_rate().to_string(), "0.0001");
+        assert_eq!(rates.rate(false).to_string(), "0.0002");
+    }
+
+    #[test]
+    fn fees_round_up_on_quote_notional() {
+        let fuel = market("0xaa", 100, 200);
+        let price = "0.5".parse().unwrap();
+        let quantity = "3".parse().unwrap();
+        let defaults = FeeRates::from_market(&fuel);
+        // Notional 1.5; taker 0.02% is 0.0003, maker 0.01% is 0.00015.
+        assert_eq!(
+            fuel.taker_fee_for(&defaults, &price, &quantity)
+                .unwrap()
+                .to_string(),
+            "0.0003"
+        );
+        assert_eq!(
+            fuel.maker_fee_for(&defaults, &price, &quantity)
+                .unwrap()
+                .to_string(),
+            "0.00015"
+        );
+        assert_eq!(
+            fuel.net_proceeds(&defaults, Side::Sell, &price, &quantity, false)
+                .unwrap()
+                .to_string(),
+            "1.4997"
+        );
+        assert_eq!(
+            fuel.net_proceeds(&defaults, Side::Buy, &price, &quantity, true)
+                .unwrap()
+                .to_string(),
+            "1.50015"
+        );
+
+        // A notional below one chain unit carries no fee; one chain unit of
+        // notional owes 0.0002 of a unit, which rounds up to a whole one.
+        let dust = "0.000000001".parse().unwrap();
+        let tiny = "0.001".parse().unwrap();
+        assert_eq!(
+            fuel.taker_fee_for(&defaults, &dust, &tiny)
+                .unwrap()
+                .to_string(),
+            "0"
+        );
+        let one = "1".parse().unwrap();
+        assert_eq!(
+            fuel.taker_fee_for(&defaults, &dust, &one)
+                .unwrap()
+                .to_string(),
+            "0.000000001"
+        );
+        assert_eq!(
+            FeeRates::new(0, 0)
+                .fee_for(&fuel, &price, &quantity, false)
+                .unwrap()
+                .to_string(),
+            "0"
+        );
+        assert_eq!(
+            fuel.taker_fee_for(&FeeRates::new(0, 100), &price, &quantity)
+                .unwrap()
+                .to_string(),
+            "0.00015"
+        );
+    }
+}
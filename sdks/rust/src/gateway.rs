@@ -0,0 +1,248 @@
+//! Traits over the market data reads and order submission of [`O2Client`].
+//!
+//! Code that takes `&mut impl OrderGateway` instead of `&mut O2Client` can be
+//! unit tested against an in-memory exchange such as
+//! `o2_sdk::testing::MockExchange` (with the `test-util` feature). The
+//! methods mirror the client's inherent methods of the same name, with the
+//! market as a [`MarketSymbol`] and prices and quantities as
+//! [`UnsignedDecimal`], as [`Action::CreateOrder`] takes them.
+//!
+//! ```rust,ignore
+//! async fn join_best_bid(
+//!     gateway: &mut (impl MarketDataProvider + OrderGateway),
+//!     session: &mut Session,
+//!     market: &MarketSymbol,
+//!     quantity: UnsignedDecimal,
+//! ) -> Result<SessionActionsResponse, O2Error> {
+//!     let info = gateway.get_market(market).await?;
+//!     let depth = gateway.get_depth(market, 1, Some(1)).await?;
+//!     let bid = depth.bids.first().ok_or_else(|| O2Error::Other("empty book".into()))?;
+//!     let price = info.format_price(bid.price)?;
+//!     gateway
+//!         .create_order(session, market, Side::Buy, price, quantity, OrderType::PostOnly, true, true)
+//!         .await
+//! }
+//! ```
+use std::collections::HashMap;
+use std::future::Future;
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, BalanceResponse, Bar, DepthSnapshot, Market, MarketSymbol, OrderId, OrderType,
+    OrdersResponse, Session, SessionActionsResponse, Side, TradeAccountId, TradeId, TradesResponse,
+};
+
+/// Market metadata and public market data.
+pub trait MarketDataProvider: Send {
+    fn get_markets(&mut self) -> impl Future<Output = Result<Vec<Market>, O2Error>> + Send;
+
+    fn get_market(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<Market, O2Error>> + Send;
+
+    /// See [`O2Client::get_depth`].
+    fn get_depth(
+        &mut self,
+        market: &MarketSymbol,
+        precision: u64,
+        limit: Option<usize>,
+    ) -> impl Future<Output = Result<DepthSnapshot, O2Error>> + Send;
+
+    /// See [`O2Client::get_trades`].
+    fn get_trades(
+        &mut self,
+        market: &MarketSymbol,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_trade_id: Option<&TradeId>,
+    ) -> impl Future<Output = Result<TradesResponse, O2Error>> + Send;
+
+    /// See [`O2Client::get_bars`]; `from_ts` and `to_ts` are milliseconds.
+    fn get_bars(
+        &mut self,
+        market: &MarketSymbol,
+        resolution: &str,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> impl Future<Output = Result<Vec<Bar>, O2Error>> + Send;
+}
+
+/// Order submission and the account reads that go with it.
+pub trait OrderGateway: Send {
+    /// See [`O2Client::create_order`].
+    #[allow(clippy::too_many_arguments)]
+    fn create_order(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+        settle_first: bool,
+        collect_orders: bool,
+    ) -> impl Future<Output = Result<SessionActionsResponse, O2Error>> + Send;
+
+    fn cancel_order(
+        &mut self,
+        session: &mut Session,
+        order_id: &OrderId,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<SessionActionsResponse, O2Error>> + Send;
+
+    fn cancel_all_orders(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<Vec<SessionActionsResponse>, O2Error>> + Send;
+
+    /// See [`O2Client::batch_actions`].
+    fn batch_actions(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> impl Future<Output = Result<SessionActionsResponse, O2Error>> + Send;
+
+    /// See [`O2Client::get_orders`].
+    fn get_orders(
+        &mut self,
+        market: &MarketSymbol,
+        trade_account_id: &TradeAccountId,
+        is_open: Option<bool>,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_order_id: Option<&OrderId>,
+    ) -> impl Future<Output = Result<OrdersResponse, O2Error>> + Send;
+
+    /// Balances keyed by asset symbol.
+    fn get_balances(
+        &mut self,
+        trade_account_id: &TradeAccountId,
+    ) -> impl Future<Output = Result<HashMap<String, BalanceResponse>, O2Error>> + Send;
+}
+
+impl MarketDataProvider for O2Client {
+    async fn get_markets(&mut self) -> Result<Vec<Market>, O2Error> {
+        O2Client::get_markets(self).await
+    }
+
+    async fn get_market(&mut self, market: &MarketSymbol) -> Result<Market, O2Error> {
+        O2Client::get_market(self, market).await
+    }
+
+    async fn get_depth(
+        &mut self,
+        market: &MarketSymbol,
+        precision: u64,
+        limit: Option<usize>,
+    ) -> Result<DepthSnapshot, O2Error> {
+        O2Client::get_depth(self, market, precision, limit).await
+    }
+
+    async fn get_trades(
+        &mut self,
+        market: &MarketSymbol,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_trade_id: Option<&TradeId>,
+    ) -> Result<TradesResponse, O2Error> {
+        O2Client::get_trades(self, market, count, start_timestamp, start_trade_id).await
+    }
+
+    async fn get_bars(
+        &mut self,
+        market: &MarketSymbol,
+        resolution: &str,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> Result<Vec<Bar>, O2Error> {
+        O2Client::get_bars(self, market, resolution, from_ts, to_ts).await
+    }
+}
+
+impl OrderGateway for O2Client {
+    async fn create_order(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+        settle_first: bool,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        O2Client::create_order(
+            self,
+            session,
+            market,
+            side,
+            price,
+            quantity,
+            order_type,
+            settle_first,
+            collect_orders,
+        )
+        .await
+    }
+
+    async fn cancel_order(
+        &mut self,
+        session: &mut Session,
+        order_id: &OrderId,
+        market: &MarketSymbol,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        O2Client::cancel_order(self, session, order_id, market).await
+    }
+
+    async fn cancel_all_orders(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+    ) -> Result<Vec<SessionActionsResponse>, O2Error> {
+        O2Client::cancel_all_orders(self, session, market).await
+    }
+
+    async fn batch_actions(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        O2Client::batch_actions(self, session, market, actions, collect_orders).await
+    }
+
+    async fn get_orders(
+        &mut self,
+        market: &MarketSymbol,
+        trade_account_id: &TradeAccountId,
+        is_open: Option<bool>,
+        count: u32,
+        start_timestamp: Option<u64>,
+        start_order_id: Option<&OrderId>,
+    ) -> Result<OrdersResponse, O2Error> {
+        O2Client::get_orders(
+            self,
+            market,
+            trade_account_id,
+            is_open,
+            count,
+            start_timestamp,
+            start_order_id,
+        )
+        .await
+    }
+
+    async fn get_balances(
+        &mut self,
+        trade_account_id: &TradeAccountId,
+    ) -> Result<HashMap<String, BalanceResponse>, O2Error> {
+        O2Client::get_balances(self, trade_account_id).await
+    }
+}
@@ -0,0 +1,528 @@
+//! Inventory hedging against a reference market.
+//!
+//! A [`Hedger`] watches fills on a *source* market (typically the one being
+//! quoted) and offsets the resulting inventory on a *hedge* venue. The venue is
+//! anything implementing [`HedgeVenue`]: [`O2HedgeVenue`] hedges on another O2
+//! market, and an adapter for an external exchange can be plugged in the same way.
+//!
+//! Fills are derived from the account's order stream by tracking how far each
+//! order's `quantity_fill` has advanced. Hedging is driven by net exposure
+//! (source position plus hedge position, in base units): once its magnitude
+//! reaches [`HedgeConfig::threshold`], an offsetting order of at most
+//! [`HedgeConfig::max_order_size`] is sent, then no further hedge is sent for
+//! [`HedgeConfig::cooldown`].
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use rust_decimal::{Decimal, RoundingStrategy};
+use tokio::sync::watch;
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, Market, MarketId, MarketSymbol, Order, OrderId, OrderType, OrderUpdate, Session, Side,
+};
+use crate::nonce::NonceManager;
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// Configuration for a [`Hedger`].
+#[derive(Debug, Clone)]
+pub struct HedgeConfig {
+    /// Hedge once absolute net exposure reaches this many base units.
+    pub threshold: UnsignedDecimal,
+    /// Largest single hedge order, in base units.
+    pub max_order_size: UnsignedDecimal,
+    /// Minimum time between hedge orders.
+    pub cooldown: Duration,
+}
+
+impl HedgeConfig {
+    /// Hedge at `threshold`, in orders of at most `max_order_size`, with a 1s cooldown.
+    pub fn new(threshold: UnsignedDecimal, max_order_size: UnsignedDecimal) -> Self {
+        Self {
+            threshold,
+            max_order_size,
+            cooldown: Duration::from_secs(1),
+        }
+    }
+}
+
+/// An offsetting order requested from a [`HedgeVenue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HedgeOrder {
+    pub side: Side,
+    /// Base units.
+    pub quantity: UnsignedDecimal,
+}
+
+/// A place where hedge orders can be executed.
+pub trait HedgeVenue: Send + 'static {
+    /// Execute `order` and return the base quantity actually filled.
+    ///
+    /// Returning less than `order.quantity` leaves the remainder as exposure to
+    /// be retried after the cooldown.
+    fn hedge(
+        &mut self,
+        order: HedgeOrder,
+    ) -> impl Future<Output = Result<UnsignedDecimal, O2Error>> + Send;
+}
+
+/// Hedges with taker orders on an O2 market.
+///
+/// Each hedge crosses the current best level by up to `max_slippage` (a
+/// fraction, default 0.5%) as a market order. If the account is also traded from
+/// other tasks, route submissions through a shared [`NonceManager`].
+pub struct O2HedgeVenue {
+    client: O2Client,
+    session: Session,
+    market: MarketSymbol,
+    max_slippage: UnsignedDecimal,
+    nonces: Option<NonceManager>,
+}
+
+impl O2HedgeVenue {
+    pub fn new(client: O2Client, session: Session, market: MarketSymbol) -> Self {
+        Self {
+            client,
+            session,
+            market,
+            max_slippage: UnsignedDecimal::new(Decimal::new(5, 3)).unwrap_or(UnsignedDecimal::ZERO),
+            nonces: None,
+        }
+    }
+
+    /// Set the maximum price distance from the best level, as a fraction.
+    pub fn with_max_slippage(mut self, max_slippage: UnsignedDecimal) -> Self {
+        self.max_slippage = max_slippage;
+        self
+    }
+
+    /// Submit through `nonces` instead of signing with the session nonce directly.
+    pub fn with_nonce_manager(mut self, nonces: NonceManager) -> Self {
+        self.nonces = Some(nonces);
+        self
+    }
+
+    async fn place(&mut self, order: HedgeOrder) -> Result<UnsignedDecimal, O2Error> {
+        let market = self.client.get_market(&self.market).await?;
+        let depth = self.client.get_depth(&self.market, 1, Some(1)).await?;
+        let best = match order.side {
+            Side::Buy => depth.asks.first(),
+            Side::Sell => depth.bids.first(),
+        }
+        .ok_or_else(|| {
+            O2Error::InvalidOrderParams(format!("No liquidity to hedge on {}", self.market))
+        })?;
+
+        let best = *market.format_price(best.price)?.inner();
+        let slippage = *self.max_slippage.inner();
+        let (limit, strategy) = match order.side {
+            Side::Buy => (
+                best * (Decimal::ONE + slippage),
+                RoundingStrategy::ToPositiveInfinity,
+            ),
+            Side::Sell => (
+                best * (Decimal::ONE - slippage).max(Decimal::ZERO),
+                RoundingStrategy::ToZero,
+            ),
+        };
+        let price = UnsignedDecimal::new(
+            limit.round_dp_with_strategy(market.quote.max_precision, strategy),
+        )?;
+        let quantity = UnsignedDecimal::new(
+            order
+                .quantity
+                .inner()
+                .round_dp_with_strategy(market.base.max_precision, RoundingStrategy::ToZero),
+        )?;
+        if quantity.inner().is_zero() {
+            return Ok(UnsignedDecimal::ZERO);
+        }
+
+        let actions = vec![Action::CreateOrder {
+            side: order.side,
+            price,
+            quantity,
+            order_type: OrderType::Market,
+        }];
+        let resp = match &self.nonces {
+            Some(nonces) => {
+                nonces
+                    .batch_actions(
+                        &mut self.client,
+                        &mut self.session,
+                        &self.market,
+                        actions,
+                        true,
+                    )
+                    .await?
+            }
+            None => {
+                self.client
+                    .batch_actions(&mut self.session, &self.market, actions, true)
+                    .await?
+            }
+        };
+        if !resp.is_success() {
+            return Err(O2Error::Other(format!(
+                "Hedge order rejected: {}",
+                resp.message.as_deref().unwrap_or("no message")
+            )));
+        }
+        // Fall back to the requested size if the response carries no fill info.
+        match resp
+            .orders
+            .as_ref()
+            .and_then(|orders| orders.first())
+            .and_then(|o| o.quantity_fill)
+        {
+            Some(filled) => market.format_quantity(filled),
+            None => Ok(quantity),
+        }
+    }
+}
+
+impl HedgeVenue for O2HedgeVenue {
+    fn hedge(
+        &mut self,
+        order: HedgeOrder,
+    ) -> impl Future<Output = Result<UnsignedDecimal, O2Error>> + Send {
+        self.place(order)
+    }
+}
+
+/// Snapshot of a [`Hedger`]'s inventory, in base units.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HedgerState {
+    /// Net filled position on the source market.
+    pub source_position: Decimal,
+    /// Net position taken on the hedge venue.
+    pub hedge_position: Decimal,
+    /// Hedge orders sent.
+    pub hedges_sent: u64,
+    /// Most recent venue error, cleared by the next successful hedge.
+    pub last_error: Option<String>,
+}
+
+impl HedgerState {
+    /// Unhedged inventory: source position plus hedge position.
+    pub fn exposure(&self) -> Decimal {
+        self.source_position + self.hedge_position
+    }
+}
+
+/// Pure position bookkeeping behind a [`Hedger`].
+#[derive(Debug)]
+struct Inventory {
+    market_id: MarketId,
+    base_decimals: u32,
+    fills_seen: HashMap<OrderId, u64>,
+    state: HedgerState,
+    next_hedge_at: Option<Instant>,
+}
+
+impl Inventory {
+    fn new(source: &Market) -> Self {
+        Self {
+            market_id: source.market_id.clone(),
+            base_decimals: source.base.decimals,
+            fills_seen: HashMap::new(),
+            state: HedgerState::default(),
+            next_hedge_at: None,
+        }
+    }
+
+    /// Apply an order update; returns true if the position changed.
+    fn apply(&mut self, order: &Order) -> bool {
+        if order.market_id.as_ref() != Some(&self.market_id) {
+            return false;
+        }
+        let filled = order.quantity_fill.unwrap_or(0);
+        let seen = self.fills_seen.entry(order.order_id.clone()).or_insert(0);
+        let delta = filled.saturating_sub(*seen);
+        *seen = (*seen).max(filled);
+        if order.close || order.cancel {
+            self.fills_seen.remove(&order.order_id);
+        }
+        if delta == 0 {
+            return false;
+        }
+        let delta = Decimal::from_i128_with_scale(delta as i128, self.base_decimals);
+        match order.side {
+            Side::Buy => self.state.source_position += delta,
+            Side::Sell => self.state.source_position -= delta,
+        }
+        true
+    }
+
+    /// The hedge to send now, if exposure is over threshold and not cooling down.
+    fn next_hedge(&self, config: &HedgeConfig, now: Instant) -> Option<HedgeOrder> {
+        if self.next_hedge_at.is_some_and(|at| now < at) {
+            return None;
+        }
+        let exposure = self.state.exposure();
+        let size = exposure.abs();
+        if size.is_zero() || size < *config.threshold.inner() {
+            return None;
+        }
+        let side = if exposure.is_sign_positive() {
+            Side::Sell
+        } else {
+            Side::Buy
+        };
+        let quantity = UnsignedDecimal::new(size.min(*config.max_order_size.inner())).ok()?;
+        Some(HedgeOrder { side, quantity })
+    }
+
+    fn record_hedge(
+        &mut self,
+        side: Side,
+        result: Result<UnsignedDecimal, O2Error>,
+        config: &HedgeConfig,
+        now: Instant,
+    ) {
+        self.state.hedges_sent += 1;
+        self.next_hedge_at = Some(now + config.cooldown);
+        match result {
+            Ok(filled) => {
+                match side {
+                    Side::Buy => self.state.hedge_position += *filled.inner(),
+                    Side::Sell => self.state.hedge_position -= *filled.inner(),
+                }
+                self.state.last_error = None;
+            }
+            Err(e) => self.state.last_error = Some(e.to_string()),
+        }
+    }
+}
+
+/// A background task that keeps source-market inventory hedged.
+///
+/// Dropping the hedger stops it.
+pub struct Hedger {
+    state_rx: watch::Receiver<HedgerState>,
+    task: Task<()>,
+}
+
+impl Hedger {
+    /// Start hedging fills on `source` seen in `orders` through `venue`.
+    ///
+    /// `orders` should be the account's order stream (see
+    /// [`O2Client::stream_orders`]); updates for other markets are ignored.
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn<V: HedgeVenue>(
+        source: &Market,
+        orders: TypedStream<OrderUpdate>,
+        venue: V,
+        config: HedgeConfig,
+    ) -> Self {
+        let inventory = Inventory::new(source);
+        let (state_tx, state_rx) = watch::channel(HedgerState::default());
+        let task = runtime::spawn(run(inventory, orders, venue, config, state_tx));
+        Self { state_rx, task }
+    }
+
+    /// Current inventory.
+    pub fn state(&self) -> HedgerState {
+        self.state_rx.borrow().clone()
+    }
+
+    /// Receive every inventory change.
+    pub fn subscribe(&self) -> watch::Receiver<HedgerState> {
+        self.state_rx.clone()
+    }
+
+    /// True until the order stream ends or the hedger is stopped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop hedging. Any in-flight hedge order is abandoned, not cancelled.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for Hedger {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run<V: HedgeVenue>(
+    mut inventory: Inventory,
+    mut orders: TypedStream<OrderUpdate>,
+    mut venue: V,
+    config: HedgeConfig,
+    state_tx: watch::Sender<HedgerState>,
+) {
+    loop {
+        let now = runtime::now();
+        if let Some(order) = inventory.next_hedge(&config, now) {
+            debug!(
+                "hedger.hedge side={:?} quantity={} exposure={}",
+                order.side,
+                order.quantity,
+                inventory.state.exposure()
+            );
+            let side = order.side;
+            let result = venue.hedge(order).await;
+            inventory.record_hedge(side, result, &config, runtime::now());
+            let _ = state_tx.send(inventory.state.clone());
+            continue;
+        }
+
+        let cooldown_end = inventory.next_hedge_at.filter(|at| *at > now);
+        tokio::select! {
+            item = orders.next() => match item {
+                None => break,
+                Some(Err(e)) => debug!("hedger.run stream_error={}", e),
+                Some(Ok(update)) => {
+                    let mut changed = false;
+                    for order in &update.orders {
+                        changed |= inventory.apply(order);
+                    }
+                    if changed {
+                        let _ = state_tx.send(inventory.state.clone());
+                    }
+                }
+            },
+            _ = runtime::sleep_until(cooldown_end.unwrap_or(now)), if cooldown_end.is_some() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc;
+
+    fn market() -> Market {
+        serde_json::from_value(serde_json::json!({
+            "contract_id": "0x01",
+            "market_id": "0xaa",
+            "whitelist_id": null,
+            "blacklist_id": null,
+            "maker_fee": "0",
+            "taker_fee": "0",
+            "min_order": "0",
+            "dust": "0",
+            "price_window": "0",
+            "base": { "symbol": "fFUEL", "asset": "0x02", "decimals": 9, "max_precision": 3 },
+            "quote": { "symbol": "fUSDC", "asset": "0x03", "decimals": 9, "max_precision": 3 },
+        }))
+        .unwrap()
+    }
+
+    fn order(id: &str, market: &str, side: &str, filled: u64) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": side,
+            "order_type": "Spot",
+            "quantity": "10000000000",
+            "quantity_fill": filled.to_string(),
+            "price": "1",
+            "market_id": market,
+        }))
+        .unwrap()
+    }
+
+    fn units(v: &str) -> UnsignedDecimal {
+        v.parse().unwrap()
+    }
+
+    #[test]
+    fn tracks_fill_deltas_per_order() {
+        let mut inv = Inventory::new(&market());
+        assert!(inv.apply(&order("0x01", "0xaa", "buy", 1_000_000_000)));
+        assert!(!inv.apply(&order("0x01", "0xaa", "buy", 1_000_000_000)));
+        assert!(inv.apply(&order("0x01", "0xaa", "buy", 3_000_000_000)));
+        assert!(inv.apply(&order("0x02", "0xaa", "sell", 500_000_000)));
+        assert!(!inv.apply(&order("0x03", "0xbb", "buy", 9_000_000_000)));
+        assert_eq!(inv.state.source_position, Decimal::new(25, 1));
+    }
+
+    #[test]
+    fn hedges_over_threshold_with_size_cap_and_cooldown() {
+        let config = HedgeConfig {
+            threshold: units("1"),
+            max_order_size: units("2"),
+            cooldown: Duration::from_secs(5),
+        };
+        let mut inv = Inventory::new(&market());
+        let now = Instant::now();
+        inv.apply(&order("0x01", "0xaa", "buy", 500_000_000));
+        assert_eq!(inv.next_hedge(&config, now), None);
+
+        inv.apply(&order("0x01", "0xaa", "buy", 3_000_000_000));
+        let hedge = inv.next_hedge(&config, now).unwrap();
+        assert_eq!(hedge.side, Side::Sell);
+        assert_eq!(hedge.quantity, units("2"));
+
+        inv.record_hedge(Side::Sell, Ok(units("2")), &config, now);
+        assert_eq!(inv.state.exposure(), Decimal::ONE);
+        assert_eq!(inv.next_hedge(&config, now + Duration::from_secs(1)), None);
+        let hedge = inv
+            .next_hedge(&config, now + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(hedge.quantity, units("1"));
+    }
+
+    struct RecordingVenue(Arc<Mutex<Vec<HedgeOrder>>>);
+
+    impl HedgeVenue for RecordingVenue {
+        fn hedge(
+            &mut self,
+            order: HedgeOrder,
+        ) -> impl Future<Output = Result<UnsignedDecimal, O2Error>> + Send {
+            let quantity = order.quantity;
+            self.0.lock().unwrap().push(order);
+            async move { Ok(quantity) }
+        }
+    }
+
+    #[tokio::test]
+    async fn hedger_offsets_streamed_fills() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::unbounded_channel();
+        let hedger = Hedger::spawn(
+            &market(),
+            TypedStream::from_receiver(rx),
+            RecordingVenue(sent.clone()),
+            HedgeConfig::new(units("1"), units("10")),
+        );
+        let mut state = hedger.subscribe();
+
+        tx.send(Ok(OrderUpdate {
+            action: "subscribe_orders".into(),
+            orders: vec![order("0x01", "0xaa", "sell", 1_500_000_000)],
+            onchain_timestamp: None,
+            seen_timestamp: crate::timestamp::Timestamp::UNIX_EPOCH,
+        }))
+        .unwrap();
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            state.wait_for(|s| s.hedges_sent == 1),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let s = hedger.state();
+        assert_eq!(s.exposure(), Decimal::ZERO);
+        assert_eq!(s.hedge_position, Decimal::new(15, 1));
+        assert_eq!(
+            sent.lock().unwrap().as_slice(),
+            &[HedgeOrder {
+                side: Side::Buy,
+                quantity: units("1.5")
+            }]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Paginated trade history.
+//!
+//! `GET /v1/trades` returns at most 50 trades per call, paged by a
+//! `(start_timestamp, start_trade_id)` cursor. [`TradeHistory`] walks those pages
+//! oldest-first from a start time and yields individual trades, fetching the
+//! next page only when the previous one has been consumed. Create one with
+//! [`O2Client::trade_history`](crate::O2Client::trade_history) or
+//! [`O2Client::account_trade_history`](crate::O2Client::account_trade_history).
+//!
+//! ```rust,ignore
+//! use futures_util::StreamExt;
+//!
+//! let mut trades = client.trade_history("fFUEL/fUSDC", Timestamp::now() - day).await?;
+//! while let Some(trade) = trades.next().await {
+//!     let trade = trade?;
+//!     println!("{} {} @ {}", trade.timestamp, trade.quantity, trade.price);
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt};
+
+use crate::api::O2Api;
+use crate::errors::O2Error;
+use crate::models::{MarketId, Trade, TradeAccountId};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+
+/// Largest page the trades endpoints return.
+const PAGE_SIZE: u32 = 50;
+
+/// Trade id that sorts before every real one, for the initial cursor.
+const ZERO_TRADE_ID: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Cursor bookkeeping, separate from I/O.
+#[derive(Debug)]
+struct Pager {
+    cursor_ts: u64,
+    cursor_id: String,
+    buffer: VecDeque<Trade>,
+    done: bool,
+}
+
+impl Pager {
+    fn new(from: Timestamp) -> Self {
+        Self {
+            cursor_ts: u64::try_from(from.as_unix_millis()).unwrap_or(u64::MAX),
+            cursor_id: ZERO_TRADE_ID.to_string(),
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Take in a page fetched at the current cursor.
+    fn accept(&mut self, page: Vec<Trade>) {
+        let short = page.len() < PAGE_SIZE as usize;
+        let mut fresh = 0;
+        for trade in page {
+            let ts = u64::try_from(trade.timestamp.as_unix_millis()).unwrap_or(u64::MAX);
+            // The cursor trade itself may be returned again.
+            if ts < self.cursor_ts || trade.trade_id.as_str() == self.cursor_id {
+                continue;
+            }
+            self.cursor_ts = ts;
+            self.cursor_id = trade.trade_id.as_str().to_string();
+            self.buffer.push_back(trade);
+            fresh += 1;
+        }
+        // A full page with nothing new would otherwise be requested forever.
+        if short || fresh == 0 {
+            self.done = true;
+        }
+    }
+}
+
+/// Where the pages come from.
+struct Source {
+    api: O2Api,
+    market_id: MarketId,
+    account: Option<TradeAccountId>,
+}
+
+impl Source {
+    async fn fetch(&self, pager: &Pager) -> Result<Vec<Trade>, O2Error> {
+        debug!(
+            "history.fetch market_id={} account={:?} cursor_ts={}",
+            self.market_id, self.account, pager.cursor_ts
+        );
+        let resp = match &self.account {
+            Some(account) => {
+                self.api
+                    .get_trades_by_account(
+                        self.market_id.as_str(),
+                        account.as_str(),
+                        "asc",
+                        PAGE_SIZE,
+                        Some(pager.cursor_ts),
+                        Some(&pager.cursor_id),
+                    )
+                    .await?
+            }
+            None => {
+                self.api
+                    .get_trades(
+                        self.market_id.as_str(),
+                        "asc",
+                        PAGE_SIZE,
+                        Some(pager.cursor_ts),
+                        Some(&pager.cursor_id),
+                        None,
+                    )
+                    .await?
+            }
+        };
+        Ok(resp.trades)
+    }
+}
+
+/// Trades from a start time onward, oldest first.
+///
+/// Ends once it catches up with the most recent trade; to stop at an end time,
+/// use `take_while` on the trade timestamp. An error ends the stream after it is
+/// yielded. To resume, start a new history from the last trade's timestamp
+/// (trades at exactly that millisecond will be repeated).
+pub struct TradeHistory {
+    inner: BoxStream<'static, Result<Trade, O2Error>>,
+}
+
+impl TradeHistory {
+    pub(crate) fn new(
+        api: O2Api,
+        market_id: MarketId,
+        account: Option<TradeAccountId>,
+        from: Timestamp,
+    ) -> Self {
+        let source = Source {
+            api,
+            market_id,
+            account,
+        };
+        let pager = Pager::new(from);
+        let inner =
+            futures_util::stream::unfold((source, pager), |(source, mut pager)| async move {
+                loop {
+                    if let Some(trade) = pager.buffer.pop_front() {
+                        return Some((Ok(trade), (source, pager)));
+                    }
+                    if pager.done {
+                        return None;
+                    }
+                    match source.fetch(&pager).await {
+                        Ok(page) => pager.accept(page),
+                        Err(e) => {
+                            pager.done = true;
+                            return Some((Err(e), (source, pager)));
+                        }
+                    }
+                }
+            })
+            .boxed();
+        Self { inner }
+    }
+}
+
+impl Stream for TradeHistory {
+    type Item = Result<Trade, O2Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(id: u8, millis: u64) -> Trade {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": format!("0x{id:064x}"),
+            "side": "Buy",
+            "total": "0",
+            "quantity": "1",
+            "price": "1",
+            "timestamp": millis,
+        }))
+        .unwrap()
+    }
+
+    fn times(pager: &Pager) -> Vec<u64> {
+        pager
+            .buffer
+            .iter()
+            .map(|t| t.timestamp.as_unix_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn advances_cursor_and_skips_repeated_cursor_trade() {
+        let mut pager = Pager::new(Timestamp::from_unix_millis(100));
+        pager.accept(
+            (0..PAGE_SIZE as u8)
+                .map(|i| trade(i + 1, 100 + i as u64))
+                .collect(),
+        );
+        assert!(!pager.done);
+        assert_eq!(pager.cursor_ts, 149);
+        assert_eq!(pager.buffer.len(), PAGE_SIZE as usize);
+
+        pager.buffer.clear();
+        pager.accept(vec![trade(50, 149), trade(51, 150)]);
+        assert_eq!(times(&pager), vec![150]);
+        assert!(pager.done, "short page ends the history");
+    }
+
+    #[test]
+    fn full_page_of_repeats_ends_history() {
+        let mut pager = Pager::new(Timestamp::from_unix_millis(500));
+        pager.accept((0..PAGE_SIZE as u8).map(|i| trade(i + 1, 1)).collect());
+        assert!(pager.buffer.is_empty());
+        assert!(pager.done);
+    }
+}
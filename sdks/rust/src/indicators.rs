@@ -0,0 +1,570 @@
+//! Technical indicators updated bar by bar from the live candle stream.
+//!
+//! Each [`Indicator`] folds closed bars into a running value in constant
+//! memory ([`RollingHigh`] and [`RollingLow`] keep at most one window), so a
+//! long-running strategy neither holds its bar history nor refetches
+//! `get_bars` every tick. [`Indicators`] is a named set of them, and
+//! [`IndicatorStream`] drives one from an [`Aggregator`]: it warms the set up
+//! from the aggregator's seeded history, drops that history, and yields an
+//! [`IndicatorSnapshot`] for every [`CandleEvent`].
+//!
+//! Only closed bars advance an indicator. Snapshots of the live bar carry the
+//! values as if it closed now, so they move with it without being committed.
+//! Values are `f64` in the bars' chain-scaled units.
+//!
+//! ```rust,ignore
+//! let indicators = Indicators::new()
+//!     .with("ema20", Ema::new(20))
+//!     .with("atr14", Atr::new(14))
+//!     .with("vwap", Vwap::anchored(Duration::from_secs(86_400)))
+//!     .with("high50", RollingHigh::new(50));
+//! let mut stream = client
+//!     .indicators("fFUEL/fUSDC", "1m", Duration::from_secs(3600), indicators)
+//!     .await?;
+//! while let Some(snapshot) = stream.next().await {
+//!     let snapshot = snapshot?;
+//!     if let (Some(ema), Some(atr)) = (snapshot.get("ema20"), snapshot.get("atr14")) {
+//!         println!("{} ema {ema:.0} atr {atr:.0}", snapshot.bar.timestamp);
+//!     }
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt};
+
+use crate::candles::{Aggregator, CandleEvent, Candles};
+use crate::errors::O2Error;
+use crate::models::Bar;
+
+/// A value computed incrementally from closed bars.
+pub trait Indicator: Send {
+    /// Fold in a closed bar.
+    fn update(&mut self, bar: &Bar);
+
+    /// The value after the bars folded in so far; `None` while warming up.
+    fn value(&self) -> Option<f64>;
+
+    /// The value if `live` closed now, without folding it in.
+    fn peek(&self, live: &Bar) -> Option<f64>;
+}
+
+/// Running sum for an average over the first `period` samples.
+#[derive(Debug, Clone, Copy, Default)]
+struct Warmup {
+    count: usize,
+    sum: f64,
+}
+
+impl Warmup {
+    /// The average once `sample` completes `period` samples.
+    fn average_with(&self, sample: f64, period: usize) -> Option<f64> {
+        (self.count + 1 == period).then(|| (self.sum + sample) / period as f64)
+    }
+}
+
+/// Exponential moving average of closes, seeded with the simple average of
+/// the first `period` of them.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    warmup: Warmup,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// An EMA with smoothing `2 / (period + 1)`. A `period` of 0 is taken as 1.
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            alpha: 2.0 / (period as f64 + 1.0),
+            warmup: Warmup::default(),
+            value: None,
+        }
+    }
+
+    fn next(&self, close: f64) -> Option<f64> {
+        match self.value {
+            Some(prev) => Some(prev + self.alpha * (close - prev)),
+            None => self.warmup.average_with(close, self.period),
+        }
+    }
+}
+
+impl Indicator for Ema {
+    fn update(&mut self, bar: &Bar) {
+        let close = bar.close as f64;
+        self.value = self.next(close);
+        if self.value.is_none() {
+            self.warmup.count += 1;
+            self.warmup.sum += close;
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.value
+    }
+
+    fn peek(&self, live: &Bar) -> Option<f64> {
+        self.next(live.close as f64)
+    }
+}
+
+/// Average true range with Wilder's smoothing, seeded with the simple
+/// average of the first `period` true ranges.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    warmup: Warmup,
+    value: Option<f64>,
+}
+
+impl Atr {
+    /// A `period` of 0 is taken as 1.
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            prev_close: None,
+            warmup: Warmup::default(),
+            value: None,
+        }
+    }
+
+    fn true_range(&self, bar: &Bar) -> f64 {
+        let (high, low) = (bar.high as f64, bar.low as f64);
+        match self.prev_close {
+            Some(close) => (high - low)
+                .max((high - close).abs())
+                .max((low - close).abs()),
+            None => high - low,
+        }
+    }
+
+    fn next(&self, bar: &Bar) -> Option<f64> {
+        let tr = self.true_range(bar);
+        match self.value {
+            Some(prev) => Some((prev * (self.period - 1) as f64 + tr) / self.period as f64),
+            None => self.warmup.average_with(tr, self.period),
+        }
+    }
+}
+
+impl Indicator for Atr {
+    fn update(&mut self, bar: &Bar) {
+        let next = self.next(bar);
+        if next.is_none() {
+            self.warmup.count += 1;
+            self.warmup.sum += self.true_range(bar);
+        }
+        self.value = next;
+        self.prev_close = Some(bar.close as f64);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.value
+    }
+
+    fn peek(&self, live: &Bar) -> Option<f64> {
+        self.next(live)
+    }
+}
+
+/// Volume-weighted average of each bar's typical price,
+/// `(high + low + close) / 3`.
+#[derive(Debug, Clone)]
+pub struct Vwap {
+    anchor_ms: Option<u64>,
+    anchor: Option<u64>,
+    weighted: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    /// A VWAP over every bar folded in.
+    pub fn new() -> Self {
+        Self {
+            anchor_ms: None,
+            anchor: None,
+            weighted: 0.0,
+            volume: 0.0,
+        }
+    }
+
+    /// A VWAP that restarts at every multiple of `period` since the Unix
+    /// epoch, e.g. daily at 00:00 UTC.
+    pub fn anchored(period: Duration) -> Self {
+        Self {
+            anchor_ms: Some((period.as_millis() as u64).max(1)),
+            ..Self::new()
+        }
+    }
+
+    fn anchor_of(&self, bar: &Bar) -> Option<u64> {
+        let ms = u64::try_from(bar.timestamp.as_unix_millis()).unwrap_or(u64::MAX);
+        self.anchor_ms.map(|period| ms / period)
+    }
+
+    /// Running sums with `bar` added.
+    fn sums_with(&self, bar: &Bar) -> (f64, f64) {
+        let (weighted, volume) = if self.anchor_of(bar) == self.anchor {
+            (self.weighted, self.volume)
+        } else {
+            (0.0, 0.0)
+        };
+        let typical = (bar.high as f64 + bar.low as f64 + bar.close as f64) / 3.0;
+        let bar_volume = (bar.buy_volume + bar.sell_volume) as f64;
+        (weighted + typical * bar_volume, volume + bar_volume)
+    }
+}
+
+impl Default for Vwap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Indicator for Vwap {
+    fn update(&mut self, bar: &Bar) {
+        (self.weighted, self.volume) = self.sums_with(bar);
+        self.anchor = self.anchor_of(bar);
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.volume > 0.0).then(|| self.weighted / self.volume)
+    }
+
+    fn peek(&self, live: &Bar) -> Option<f64> {
+        let (weighted, volume) = self.sums_with(live);
+        (volume > 0.0).then(|| weighted / volume)
+    }
+}
+
+/// Extreme of one price over the last `period` bars, kept as a monotonic
+/// queue of at most `period` candidates.
+#[derive(Debug, Clone)]
+struct RollingExtreme {
+    period: u64,
+    seen: u64,
+    /// `(bar index, price)`, best first.
+    candidates: VecDeque<(u64, u64)>,
+}
+
+impl RollingExtreme {
+    fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1) as u64,
+            seen: 0,
+            candidates: VecDeque::new(),
+        }
+    }
+
+    fn update(&mut self, price: u64, better: fn(u64, u64) -> bool) {
+        while self
+            .candidates
+            .back()
+            .is_some_and(|&(_, p)| !better(p, price))
+        {
+            self.candidates.pop_back();
+        }
+        self.candidates.push_back((self.seen, price));
+        self.seen += 1;
+        while self
+            .candidates
+            .front()
+            .is_some_and(|&(i, _)| i + self.period < self.seen)
+        {
+            self.candidates.pop_front();
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.seen >= self.period)
+            .then(|| self.candidates.front().map(|&(_, p)| p as f64))
+            .flatten()
+    }
+
+    fn peek(&self, price: u64, better: fn(u64, u64) -> bool) -> Option<f64> {
+        if self.seen + 1 < self.period {
+            return None;
+        }
+        // The live bar pushes out the oldest one in the window.
+        let oldest = self.seen + 1 - self.period;
+        let kept = self
+            .candidates
+            .iter()
+            .find(|&&(i, _)| i >= oldest)
+            .map(|&(_, p)| p);
+        Some(match kept {
+            Some(p) if better(p, price) => p,
+            _ => price,
+        } as f64)
+    }
+}
+
+/// Highest high over the last `period` bars.
+#[derive(Debug, Clone)]
+pub struct RollingHigh(RollingExtreme);
+
+impl RollingHigh {
+    /// A `period` of 0 is taken as 1.
+    pub fn new(period: usize) -> Self {
+        Self(RollingExtreme::new(period))
+    }
+}
+
+impl Indicator for RollingHigh {
+    fn update(&mut self, bar: &Bar) {
+        self.0.update(bar.high, |a, b| a > b);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.0.value()
+    }
+
+    fn peek(&self, live: &Bar) -> Option<f64> {
+        self.0.peek(live.high, |a, b| a > b)
+    }
+}
+
+/// Lowest low over the last `period` bars.
+#[derive(Debug, Clone)]
+pub struct RollingLow(RollingExtreme);
+
+impl RollingLow {
+    /// A `period` of 0 is taken as 1.
+    pub fn new(period: usize) -> Self {
+        Self(RollingExtreme::new(period))
+    }
+}
+
+impl Indicator for RollingLow {
+    fn update(&mut self, bar: &Bar) {
+        self.0.update(bar.low, |a, b| a < b);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.0.value()
+    }
+
+    fn peek(&self, live: &Bar) -> Option<f64> {
+        self.0.peek(live.low, |a, b| a < b)
+    }
+}
+
+/// Indicator values at one bar.
+#[derive(Debug, Clone)]
+pub struct IndicatorSnapshot {
+    /// The bar the values include.
+    pub bar: Bar,
+    /// False for the live bar, whose values are provisional.
+    pub closed: bool,
+    /// Each indicator's name and value, in the order they were added.
+    pub values: Vec<(Arc<str>, Option<f64>)>,
+}
+
+impl IndicatorSnapshot {
+    /// The named indicator's value; `None` if it is unknown or warming up.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .and_then(|(_, v)| *v)
+    }
+}
+
+/// A named set of indicators fed the same bars.
+#[derive(Default)]
+pub struct Indicators {
+    entries: Vec<(Arc<str>, Box<dyn Indicator>)>,
+}
+
+impl Indicators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `indicator` under `name`, replacing any with the same name.
+    pub fn with(mut self, name: &str, indicator: impl Indicator + 'static) -> Self {
+        self.entries.retain(|(n, _)| &**n != name);
+        self.entries.push((name.into(), Box::new(indicator)));
+        self
+    }
+
+    /// The named indicator's value over the closed bars so far.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .and_then(|(_, i)| i.value())
+    }
+
+    /// Fold in a closed bar.
+    pub fn update(&mut self, bar: &Bar) {
+        for (_, indicator) in &mut self.entries {
+            indicator.update(bar);
+        }
+    }
+
+    /// Fold in `event` if it closed a bar, and return the values at its bar.
+    pub fn apply(&mut self, event: &CandleEvent) -> IndicatorSnapshot {
+        match event {
+            CandleEvent::Closed(bar) => {
+                self.update(bar);
+                IndicatorSnapshot {
+                    bar: bar.clone(),
+                    closed: true,
+                    values: self
+                        .entries
+                        .iter()
+                        .map(|(n, i)| (n.clone(), i.value()))
+                        .collect(),
+                }
+            }
+            CandleEvent::Updated(bar) => IndicatorSnapshot {
+                bar: bar.clone(),
+                closed: false,
+                values: self
+                    .entries
+                    .iter()
+                    .map(|(n, i)| (n.clone(), i.peek(bar)))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for Indicators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(n, i)| (n, i.value())))
+            .finish()
+    }
+}
+
+/// A candle stream folded into [`IndicatorSnapshot`]s.
+///
+/// Created with [`O2Client::indicators`](crate::O2Client::indicators), or from
+/// any [`Aggregator`]. Errors are passed through.
+pub struct IndicatorStream {
+    aggregator: Aggregator,
+    indicators: Indicators,
+}
+
+impl IndicatorStream {
+    /// Warm `indicators` up with the closed bars `aggregator` was seeded
+    /// with, then drop them; from here on it keeps no closed bars.
+    pub fn new(mut aggregator: Aggregator, mut indicators: Indicators) -> Self {
+        let candles: &mut Candles = aggregator.candles_mut();
+        for bar in candles.history() {
+            indicators.update(bar);
+        }
+        candles.set_history_limit(0);
+        Self {
+            aggregator,
+            indicators,
+        }
+    }
+
+    /// The indicators, as of the last closed bar.
+    pub fn indicators(&self) -> &Indicators {
+        &self.indicators
+    }
+
+    /// The live bar, if any.
+    pub fn current(&self) -> Option<&Bar> {
+        self.aggregator.candles().current()
+    }
+}
+
+impl Stream for IndicatorStream {
+    type Item = Result<IndicatorSnapshot, O2Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        this.aggregator
+            .poll_next_unpin(cx)
+            .map(|event| event.map(|event| event.map(|e| this.indicators.apply(&e))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::Timestamp;
+
+    fn bar(minute: u64, high: u64, low: u64, close: u64, volume: u128) -> Bar {
+        Bar {
+            open: close,
+            high,
+            low,
+            close,
+            buy_volume: volume,
+            sell_volume: 0,
+            timestamp: Timestamp::from_unix_millis(minute * 60_000),
+        }
+    }
+
+    #[test]
+    fn ema_and_atr_warm_up_then_smooth() {
+        let mut ema = Ema::new(3);
+        let mut atr = Atr::new(2);
+        for (i, close) in [10, 20, 30].into_iter().enumerate() {
+            assert_eq!(ema.value(), None);
+            let b = bar(i as u64, close + 5, close - 5, close, 1);
+            ema.update(&b);
+            atr.update(&b);
+        }
+        assert_eq!(ema.value(), Some(20.0));
+        // True ranges 10, 15, 15: seeded with 12.5, then (12.5 + 15) / 2.
+        assert_eq!(atr.value(), Some(13.75));
+
+        let live = bar(3, 40, 40, 40, 1);
+        assert_eq!(ema.peek(&live), Some(30.0));
+        assert_eq!(ema.value(), Some(20.0));
+    }
+
+    #[test]
+    fn vwap_restarts_at_its_anchor() {
+        let mut vwap = Vwap::anchored(Duration::from_secs(120));
+        vwap.update(&bar(0, 30, 30, 30, 1));
+        vwap.update(&bar(1, 60, 60, 60, 2));
+        assert_eq!(vwap.value(), Some(50.0));
+        assert_eq!(vwap.peek(&bar(2, 90, 90, 90, 1)), Some(90.0));
+    }
+
+    #[test]
+    fn rolling_extremes_forget_bars_outside_the_window() {
+        let mut high = RollingHigh::new(3);
+        let mut low = RollingLow::new(3);
+        for (i, price) in [50, 40, 30, 20].into_iter().enumerate() {
+            let b = bar(i as u64, price, price, price, 1);
+            high.update(&b);
+            low.update(&b);
+        }
+        assert_eq!(high.value(), Some(40.0));
+        assert_eq!(low.value(), Some(20.0));
+        // The live bar would push out the 40.
+        assert_eq!(high.peek(&bar(4, 25, 25, 25, 1)), Some(30.0));
+        assert_eq!(low.peek(&bar(4, 25, 25, 25, 1)), Some(20.0));
+    }
+
+    #[test]
+    fn snapshots_commit_closed_bars_only() {
+        let mut indicators = Indicators::new().with("ema", Ema::new(1));
+        let live = indicators.apply(&CandleEvent::Updated(bar(0, 10, 10, 10, 1)));
+        assert!(!live.closed);
+        assert_eq!(live.get("ema"), Some(10.0));
+        assert_eq!(indicators.get("ema"), None);
+
+        let closed = indicators.apply(&CandleEvent::Closed(bar(0, 12, 12, 12, 1)));
+        assert!(closed.closed);
+        assert_eq!(indicators.get("ema"), Some(12.0));
+        assert_eq!(closed.get("missing"), None);
+    }
+}
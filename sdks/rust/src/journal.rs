@@ -0,0 +1,621 @@
+//! A local SQLite audit trail of what a bot submitted and what it got back.
+//!
+//! Requires the `journal` feature. A [`Journal`] keeps, per trade account:
+//!
+//! - every submitted action batch, with the signed request and either the
+//!   server's response or the error,
+//! - fills, one row per trade, ignoring repeats,
+//! - balance snapshots.
+//!
+//! Attach one with [`O2Client::set_journal`](crate::O2Client::set_journal) and
+//! the client records batches it submits, balances it fetches with
+//! [`get_balances`](crate::O2Client::get_balances) and fills it fetches with
+//! [`get_account_trades`](crate::O2Client::get_account_trades). Fills from a
+//! stream or [`TradeHistory`](crate::TradeHistory) go in with
+//! [`Journal::record_fills`]. A failed write by the client is logged and does
+//! not fail the call that triggered it.
+//!
+//! ```rust,ignore
+//! let journal = Journal::open("bot-journal.sqlite")?;
+//! client.set_journal(journal.clone());
+//!
+//! // Reconcile against the exchange's view of the last hour.
+//! let trades = client.get_account_trades("fFUEL/fUSDC", &account, 50, None, None).await?;
+//! let missing = journal.missing_fills(&account, &market_id, &trades.trades)?;
+//! let failed = journal
+//!     .submissions(&account, Timestamp::now() - hour)?
+//!     .into_iter()
+//!     .filter(|s| !s.is_success());
+//! ```
+//!
+//! Amounts are stored as decimal text of the chain integers, and times as
+//! Unix milliseconds. Writes are synchronous and short; the database is
+//! opened in WAL mode so another process can read it while the bot runs.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::errors::O2Error;
+use crate::models::{
+    BalanceResponse, MarketId, SessionActionsRequest, SessionActionsResponse, Side, Trade,
+    TradeAccountId, TradeId, TraderSide, TxId,
+};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS submissions (
+    id INTEGER PRIMARY KEY,
+    trade_account_id TEXT NOT NULL,
+    nonce INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    request TEXT NOT NULL,
+    response TEXT,
+    tx_id TEXT,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS submissions_by_account
+    ON submissions (trade_account_id, recorded_at);
+CREATE TABLE IF NOT EXISTS fills (
+    trade_account_id TEXT NOT NULL,
+    market_id TEXT NOT NULL,
+    trade_id TEXT NOT NULL,
+    side TEXT NOT NULL,
+    trader_side TEXT,
+    price TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    total TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    PRIMARY KEY (trade_account_id, market_id, trade_id)
+);
+CREATE TABLE IF NOT EXISTS balances (
+    trade_account_id TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    total_locked TEXT NOT NULL,
+    total_unlocked TEXT NOT NULL,
+    trading_account_balance TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS balances_by_account
+    ON balances (trade_account_id, asset, recorded_at);
+";
+
+fn journal_error(e: impl std::fmt::Display) -> O2Error {
+    O2Error::Other(format!("journal: {e}"))
+}
+
+fn millis(ts: Timestamp) -> i64 {
+    i64::try_from(ts.as_unix_millis()).unwrap_or(i64::MAX)
+}
+
+fn timestamp(millis: i64) -> Timestamp {
+    Timestamp::from_unix_millis(u64::try_from(millis).unwrap_or(0))
+}
+
+/// Parse a stored decimal amount.
+fn amount<T: std::str::FromStr>(row: &Row<'_>, column: &str) -> rusqlite::Result<T> {
+    let text: String = row.get(column)?;
+    text.parse().map_err(|_| {
+        rusqlite::Error::InvalidColumnType(0, column.to_string(), rusqlite::types::Type::Text)
+    })
+}
+
+/// A submitted action batch and its outcome.
+#[derive(Debug, Clone)]
+pub struct JournalSubmission {
+    pub id: i64,
+    pub trade_account_id: TradeAccountId,
+    pub nonce: u64,
+    pub recorded_at: Timestamp,
+    /// The request as sent, signature included.
+    pub request: SessionActionsRequest,
+    /// The server's response, when one arrived.
+    pub response: Option<SessionActionsResponse>,
+    pub tx_id: Option<TxId>,
+    /// Why the submission failed, if it did.
+    pub error: Option<String>,
+}
+
+impl JournalSubmission {
+    /// True if the server accepted the batch with a transaction.
+    pub fn is_success(&self) -> bool {
+        self.tx_id.is_some()
+    }
+}
+
+/// A fill of one of the account's orders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalFill {
+    pub trade_account_id: TradeAccountId,
+    pub market_id: MarketId,
+    pub trade_id: TradeId,
+    /// The maker's order side, as on [`Trade::side`].
+    pub side: Side,
+    pub trader_side: Option<TraderSide>,
+    pub price: u64,
+    pub quantity: u64,
+    pub total: u128,
+    pub timestamp: Timestamp,
+    pub recorded_at: Timestamp,
+}
+
+/// One asset's balance at the time of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalBalance {
+    pub asset: String,
+    pub recorded_at: Timestamp,
+    pub total_locked: u128,
+    pub total_unlocked: u128,
+    pub trading_account_balance: u128,
+}
+
+/// A SQLite journal. Clones share the connection.
+#[derive(Clone)]
+pub struct Journal {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Journal {
+    /// Open the journal at `path`, creating the file and tables if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let conn = Connection::open(path).map_err(journal_error)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(journal_error)?;
+        Self::init(conn)
+    }
+
+    /// A journal that lives only as long as the process, for tests.
+    pub fn in_memory() -> Result<Self, O2Error> {
+        Self::init(Connection::open_in_memory().map_err(journal_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, O2Error> {
+        conn.execute_batch(SCHEMA).map_err(journal_error)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Record a submitted batch and its response or error. Returns the
+    /// submission's id.
+    pub fn record_submission(
+        &self,
+        request: &SessionActionsRequest,
+        outcome: Result<&SessionActionsResponse, &O2Error>,
+    ) -> Result<i64, O2Error> {
+        let (response, tx_id, error) = match outcome {
+            Ok(resp) => (
+                Some(serde_json::to_string(resp)?),
+                resp.tx_id.as_ref().map(|t| t.to_string()),
+                (!resp.is_success()).then(|| {
+                    resp.message
+                        .clone()
+                        .unwrap_or_else(|| "rejected without a transaction".into())
+                }),
+            ),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO submissions
+                (trade_account_id, nonce, recorded_at, request, response, tx_id, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                request.trade_account_id.as_str(),
+                request.nonce.parse::<i64>().unwrap_or(-1),
+                millis(Timestamp::now()),
+                serde_json::to_string(request)?,
+                response,
+                tx_id,
+                error,
+            ],
+        )
+        .map_err(journal_error)?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Record the account's trades on a market. Trades already journaled are
+    /// skipped; returns how many were new.
+    pub fn record_fills(
+        &self,
+        trade_account_id: &TradeAccountId,
+        market_id: &MarketId,
+        trades: &[Trade],
+    ) -> Result<usize, O2Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(journal_error)?;
+        let mut added = 0;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO fills
+                        (trade_account_id, market_id, trade_id, side, trader_side,
+                         price, quantity, total, timestamp, recorded_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )
+                .map_err(journal_error)?;
+            let now = millis(Timestamp::now());
+            for trade in trades {
+                let trader_side = trade
+                    .trader_side
+                    .map(|s| serde_json::to_value(s).map(|v| v.as_str().map(str::to_string)))
+                    .transpose()?
+                    .flatten();
+                added += insert
+                    .execute(params![
+                        trade_account_id.as_str(),
+                        market_id.as_str(),
+                        trade.trade_id.as_str(),
+                        trade.side.as_str(),
+                        trader_side,
+                        trade.price.to_string(),
+                        trade.quantity.to_string(),
+                        trade.total.to_string(),
+                        millis(trade.timestamp),
+                        now,
+                    ])
+                    .map_err(journal_error)?;
+            }
+        }
+        tx.commit().map_err(journal_error)?;
+        Ok(added)
+    }
+
+    /// Record a balance snapshot, keyed by asset symbol as returned by
+    /// [`get_balances`](crate::O2Client::get_balances).
+    pub fn record_balances(
+        &self,
+        trade_account_id: &TradeAccountId,
+        balances: &HashMap<String, BalanceResponse>,
+    ) -> Result<(), O2Error> {
+        let mut conn = self.conn();
+        let tx = conn.transaction().map_err(journal_error)?;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO balances
+                        (trade_account_id, asset, recorded_at,
+                         total_locked, total_unlocked, trading_account_balance)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(journal_error)?;
+            let now = millis(Timestamp::now());
+            for (asset, balance) in balances {
+                insert
+                    .execute(params![
+                        trade_account_id.as_str(),
+                        asset,
+                        now,
+                        balance.total_locked.to_string(),
+                        balance.total_unlocked.to_string(),
+                        balance.trading_account_balance.to_string(),
+                    ])
+                    .map_err(journal_error)?;
+            }
+        }
+        tx.commit().map_err(journal_error)
+    }
+
+    /// The account's submissions recorded at or after `since`, oldest first.
+    pub fn submissions(
+        &self,
+        trade_account_id: &TradeAccountId,
+        since: Timestamp,
+    ) -> Result<Vec<JournalSubmission>, O2Error> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT id, trade_account_id, nonce, recorded_at, request, response, tx_id, error
+                 FROM submissions
+                 WHERE trade_account_id = ?1 AND recorded_at >= ?2
+                 ORDER BY id",
+            )
+            .map_err(journal_error)?;
+        let rows = query
+            .query_map(params![trade_account_id.as_str(), millis(since)], |row| {
+                Ok((
+                    row.get::<_, i64>("id")?,
+                    row.get::<_, i64>("nonce")?,
+                    row.get::<_, i64>("recorded_at")?,
+                    row.get::<_, String>("request")?,
+                    row.get::<_, Option<String>>("response")?,
+                    row.get::<_, Option<String>>("tx_id")?,
+                    row.get::<_, Option<String>>("error")?,
+                ))
+            })
+            .map_err(journal_error)?;
+        let mut submissions = Vec::new();
+        for row in rows {
+            let (id, nonce, recorded_at, request, response, tx_id, error) =
+                row.map_err(journal_error)?;
+            submissions.push(JournalSubmission {
+                id,
+                trade_account_id: trade_account_id.clone(),
+                nonce: u64::try_from(nonce).unwrap_or_default(),
+                recorded_at: timestamp(recorded_at),
+                request: serde_json::from_str(&request)?,
+                response: response.as_deref().map(serde_json::from_str).transpose()?,
+                tx_id: tx_id.map(TxId::new),
+                error,
+            });
+        }
+        Ok(submissions)
+    }
+
+    /// The account's fills at or after `since`, on one market or all of
+    /// them, oldest first.
+    pub fn fills(
+        &self,
+        trade_account_id: &TradeAccountId,
+        market_id: Option<&MarketId>,
+        since: Timestamp,
+    ) -> Result<Vec<JournalFill>, O2Error> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT * FROM fills
+                 WHERE trade_account_id = ?1 AND (?2 IS NULL OR market_id = ?2)
+                   AND timestamp >= ?3
+                 ORDER BY timestamp, trade_id",
+            )
+            .map_err(journal_error)?;
+        let rows = query
+            .query_map(
+                params![
+                    trade_account_id.as_str(),
+                    market_id.map(|m| m.as_str()),
+                    millis(since)
+                ],
+                |row| {
+                    let side: String = row.get("side")?;
+                    let trader_side: Option<String> = row.get("trader_side")?;
+                    Ok(JournalFill {
+                        trade_account_id: trade_account_id.clone(),
+                        market_id: MarketId::new(row.get::<_, String>("market_id")?),
+                        trade_id: TradeId::new(row.get::<_, String>("trade_id")?),
+                        side: if side == "Buy" { Side::Buy } else { Side::Sell },
+                        trader_side: trader_side.and_then(|s| {
+                            serde_json::from_value(serde_json::Value::String(s)).ok()
+                        }),
+                        price: amount(row, "price")?,
+                        quantity: amount(row, "quantity")?,
+                        total: amount(row, "total")?,
+                        timestamp: timestamp(row.get("timestamp")?),
+                        recorded_at: timestamp(row.get("recorded_at")?),
+                    })
+                },
+            )
+            .map_err(journal_error)?;
+        rows.collect::<Result<_, _>>().map_err(journal_error)
+    }
+
+    /// The most recent snapshot of each asset's balance.
+    pub fn latest_balances(
+        &self,
+        trade_account_id: &TradeAccountId,
+    ) -> Result<HashMap<String, JournalBalance>, O2Error> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT asset, recorded_at, total_locked, total_unlocked, trading_account_balance
+                 FROM balances AS b
+                 WHERE trade_account_id = ?1 AND rowid = (
+                     SELECT rowid FROM balances
+                     WHERE trade_account_id = b.trade_account_id AND asset = b.asset
+                     ORDER BY recorded_at DESC, rowid DESC LIMIT 1
+                 )",
+            )
+            .map_err(journal_error)?;
+        let rows = query
+            .query_map(params![trade_account_id.as_str()], |row| {
+                Ok(JournalBalance {
+                    asset: row.get("asset")?,
+                    recorded_at: timestamp(row.get("recorded_at")?),
+                    total_locked: amount(row, "total_locked")?,
+                    total_unlocked: amount(row, "total_unlocked")?,
+                    trading_account_balance: amount(row, "trading_account_balance")?,
+                })
+            })
+            .map_err(journal_error)?;
+        rows.map(|row| row.map(|b| (b.asset.clone(), b)))
+            .collect::<Result<_, _>>()
+            .map_err(journal_error)
+    }
+
+    /// The trades in `exchange_trades`, as reported by the exchange for the
+    /// account on `market_id`, that the journal has no fill for.
+    pub fn missing_fills<'a>(
+        &self,
+        trade_account_id: &TradeAccountId,
+        market_id: &MarketId,
+        exchange_trades: &'a [Trade],
+    ) -> Result<Vec<&'a Trade>, O2Error> {
+        let conn = self.conn();
+        let mut query = conn
+            .prepare_cached(
+                "SELECT 1 FROM fills
+                 WHERE trade_account_id = ?1 AND market_id = ?2 AND trade_id = ?3",
+            )
+            .map_err(journal_error)?;
+        let mut missing = Vec::new();
+        for trade in exchange_trades {
+            let known = query
+                .query_row(
+                    params![
+                        trade_account_id.as_str(),
+                        market_id.as_str(),
+                        trade.trade_id.as_str()
+                    ],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(journal_error)?;
+            if known.is_none() {
+                missing.push(trade);
+            }
+        }
+        debug!(
+            "journal.missing_fills market_id={} checked={} missing={}",
+            market_id,
+            exchange_trades.len(),
+            missing.len()
+        );
+        Ok(missing)
+    }
+}
+
+impl std::fmt::Debug for Journal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Journal")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn account() -> TradeAccountId {
+        TradeAccountId::new(format!("0x{:064x}", 1))
+    }
+
+    fn request(nonce: u64) -> SessionActionsRequest {
+        SessionActionsRequest {
+            actions: Vec::new(),
+            signature: crate::models::Signature::Secp256k1("0xsig".into()),
+            nonce: nonce.to_string(),
+            trade_account_id: account(),
+            session_id: crate::models::Identity::Address("0x02".into()),
+            collect_orders: None,
+            variable_outputs: None,
+        }
+    }
+
+    fn trade(id: u8, millis: u64) -> Trade {
+        serde_json::from_value(json!({
+            "trade_id": format!("0x{id:02x}"),
+            "side": "Sell",
+            "total": "340282366920938463463374607431768211455",
+            "quantity": "2",
+            "price": "100",
+            "timestamp": millis,
+            "trader_side": "taker",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn submissions_keep_request_response_and_errors() {
+        let journal = Journal::in_memory().unwrap();
+        let accepted: SessionActionsResponse =
+            serde_json::from_value(json!({"tx_id": "0xabc", "orders": []})).unwrap();
+        journal
+            .record_submission(&request(7), Ok(&accepted))
+            .unwrap();
+        let err = O2Error::InvalidSession("expired".into());
+        journal.record_submission(&request(8), Err(&err)).unwrap();
+
+        let all = journal
+            .submissions(&account(), Timestamp::from_unix_millis(0))
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].is_success());
+        assert_eq!((all[0].nonce, all[0].request.nonce.as_str()), (7, "7"));
+        assert_eq!(all[0].tx_id.as_ref().unwrap().as_str(), "0xabc");
+        assert!(all[0].response.is_some());
+        assert!(!all[1].is_success());
+        assert!(all[1].error.as_deref().unwrap().contains("expired"));
+        let other = TradeAccountId::new(format!("0x{:064x}", 2));
+        assert!(journal
+            .submissions(&other, Timestamp::from_unix_millis(0))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn fills_are_deduplicated_and_reconciled() {
+        let journal = Journal::in_memory().unwrap();
+        let market = MarketId::new("0xaa");
+        assert_eq!(
+            journal
+                .record_fills(&account(), &market, &[trade(1, 1_000), trade(2, 2_000)])
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            journal
+                .record_fills(&account(), &market, &[trade(2, 2_000)])
+                .unwrap(),
+            0
+        );
+
+        let fills = journal
+            .fills(
+                &account(),
+                Some(&market),
+                Timestamp::from_unix_millis(1_500),
+            )
+            .unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].trade_id.as_str(), "0x02");
+        assert_eq!(
+            (fills[0].side, fills[0].trader_side),
+            (Side::Sell, Some(TraderSide::Taker))
+        );
+        assert_eq!(fills[0].total, u128::MAX);
+        assert_eq!(
+            journal
+                .fills(&account(), None, Timestamp::from_unix_millis(0))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let exchange = [trade(2, 2_000), trade(3, 3_000)];
+        let missing = journal
+            .missing_fills(&account(), &market, &exchange)
+            .unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].trade_id.as_str(), "0x03");
+    }
+
+    #[test]
+    fn latest_balances_takes_the_newest_snapshot_per_asset() {
+        let journal = Journal::in_memory().unwrap();
+        let balance = |unlocked: u128| -> BalanceResponse {
+            serde_json::from_value(json!({
+                "order_books": {},
+                "total_locked": "5",
+                "total_unlocked": unlocked.to_string(),
+                "trading_account_balance": "1",
+            }))
+            .unwrap()
+        };
+        let snapshot = |pairs: &[(&str, u128)]| {
+            pairs
+                .iter()
+                .map(|(asset, unlocked)| (asset.to_string(), balance(*unlocked)))
+                .collect::<HashMap<_, _>>()
+        };
+        journal
+            .record_balances(&account(), &snapshot(&[("fUSDC", 10), ("fFUEL", 3)]))
+            .unwrap();
+        journal
+            .record_balances(&account(), &snapshot(&[("fUSDC", 20)]))
+            .unwrap();
+
+        let latest = journal.latest_balances(&account()).unwrap();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest["fUSDC"].total_unlocked, 20);
+        assert_eq!(latest["fFUEL"].total_unlocked, 3);
+        assert_eq!(latest["fUSDC"].total_locked, 5);
+    }
+}
@@ -8,7 +8,10 @@
 //! - High-level workflow client: [`O2Client`]
 //! - Typed REST API access: [`api::O2Api`]
 //! - Typed WebSocket streams: [`TypedStream`]
+//! - Local order book maintenance: [`LiveOrderBook`], with a top-of-book-only [`BboStream`]
 //! - Strong domain models for markets, balances, orders, and sessions
+//! - Unit-safe timestamps: [`Timestamp`] (with `chrono` conversions behind the `chrono` feature)
+//! - A curated [`prelude`] of the common types plus `StreamExt`
 //!
 //! # Quick Start
 //!
@@ -156,8 +159,15 @@
 //! All fallible operations return [`O2Error`]. Match specific variants for robust handling:
 //!
 //! - Preflight/API validation failures (`code`/`message` style errors)
-//! - On-chain revert failures (`OnChainRevert`)
+//! - On-chain revert failures (`OnChainRevert`); [`O2Error::revert_reason`] decodes
+//!   the contract error, see [`receipts`]
 //! - Transport/serialization failures (`HttpError`, `JsonError`, etc.)
+//! - Endpoints the connected deployment does not serve (`Unsupported`); see
+//!   [`capabilities`]
+//!
+//! REST reads are retried on transient failures (5xx, rate limits, timeouts,
+//! dropped connections) before an error is returned; see [`RetryPolicy`] and
+//! [`O2Client::set_retry_policy`].
 //!
 //! See [`guides::error_handling`] for recovery patterns.
 //!
@@ -171,26 +181,139 @@
 //! - [`guides::websocket_streams`] — Real-time data with `TypedStream`
 //! - [`guides::error_handling`] — Error types and recovery patterns
 //! - [`guides::external_signers`] — Integrating KMS/HSM via the `SignableWallet` trait
+pub mod accounts;
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
+pub mod amounts;
+pub mod analytics;
 pub mod api;
+pub mod assets;
+pub mod backtest;
+pub mod candles;
+pub mod capabilities;
 pub mod client;
+#[cfg(feature = "ethers-compat")]
+pub mod compat;
+pub mod compatibility;
 pub mod config;
+#[cfg(feature = "low-level")]
+pub mod conformance;
 pub mod crypto;
+pub mod deadman;
 pub mod decimal;
+#[cfg(feature = "low-level")]
 pub mod encoding;
+// The client signs with it either way; some helpers are only for callers.
+#[cfg(not(feature = "low-level"))]
+#[allow(dead_code)]
+mod encoding;
 pub mod errors;
+pub mod events;
+pub mod execution;
+pub mod export;
+pub mod failover;
+pub mod feed_monitor;
+pub mod fees;
+pub mod gateway;
 pub mod guides;
+pub mod hedger;
+pub mod history;
+pub mod indicators;
+#[cfg(feature = "journal")]
+pub mod journal;
+pub mod market_maker;
+pub mod market_watch;
+pub mod mnemonic;
 pub mod models;
+pub mod my_book;
+pub mod nonce;
 mod onchain_revert;
+pub mod orderbook;
+pub mod ownership;
+pub mod paper;
+pub mod preflight;
+pub mod prelude;
+pub mod receipts;
+pub mod reconcile;
+pub mod recorder;
+pub mod retry;
+pub mod runtime;
+pub mod scheduler;
+pub mod settlement;
+pub mod setup_guard;
+pub mod strategy;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod timestamp;
+mod trace;
+pub mod transport;
+pub mod triggers;
 pub mod websocket;
 
 // Re-export primary types for convenience.
-pub use client::{MarketActionsBuilder, MetadataPolicy, O2Client};
+pub use accounts::{AccountFailure, AccountSet, AssetTotal, ManagedAccount, Portfolio, RateLimit};
+pub use amounts::{BaseAmount, QuoteAmount, TypedMarket, UnitPrice};
+pub use analytics::{LiveReferencePrice, RollingTwap, RollingVwap};
+pub use assets::{AssetInfo, AssetRegistry};
+pub use backtest::{
+    Backtest, BacktestData, BacktestReport, BacktestStats, BacktestTrader, EquityPoint, MarketData,
+    Strategy,
+};
+pub use candles::{Aggregator, CandleEvent, Candles};
+pub use capabilities::{Capabilities, Endpoint};
+pub use client::{
+    AccountView, ClientMode, FillOutcome, FillWaitOptions, MarketActionsBuilder,
+    MarketCancelSummary, MetadataPolicy, O2Client, ReplacedOrder, SetupProgress,
+};
+pub use compatibility::{Compatibility, CompatibilityReport, COMPATIBILITY};
 pub use config::{Network, NetworkConfig};
 pub use crypto::{EvmWallet, SignableWallet, Wallet};
+pub use deadman::{DeadmanConfig, DeadmanSwitch, SessionRevocation};
 pub use decimal::UnsignedDecimal;
-pub use errors::O2Error;
+pub use errors::{ApiErrorCode, AuthFailure, O2Error};
+pub use events::AccountEvent;
+pub use execution::iceberg::{IcebergConfig, IcebergOrder, IcebergState, IcebergStatus};
+pub use execution::oco::{OcoConfig, OcoLeg, OcoLink, OcoPair, OcoStatus};
+pub use export::Exporter;
+pub use failover::{EndpointHealth, FailoverPolicy};
+pub use feed_monitor::{FeedMonitor, FeedMonitorConfig};
+pub use fees::{FeeOverrides, FeeRates, FeeSource};
+pub use gateway::{MarketDataProvider, OrderGateway};
+pub use hedger::{HedgeConfig, HedgeVenue, Hedger};
+pub use history::TradeHistory;
+pub use indicators::{IndicatorSnapshot, IndicatorStream, Indicators};
+#[cfg(feature = "journal")]
+pub use journal::Journal;
+pub use market_maker::{Quote, QuotePlan, Quoter, QuoterConfig};
+pub use market_watch::{MarketEvent, MarketWatcher, MarketWatcherConfig};
 pub use models::*;
 pub use models::{
     Action, AssetId, MarketId, MarketSymbol, OrderId, OrderType, Side, TradeAccountId,
 };
-pub use websocket::{DepthPrecision, O2WebSocket, TypedStream, WsConfig, WsLifecycleEvent};
+pub use my_book::{LevelDiff, LiveMyBook, MyBook, MyOrder};
+pub use nonce::{ContentionStats, NonceLock, NonceManager};
+pub use orderbook::{Bbo, BboStream, LiveOrderBook, MarketImpact, OrderBook};
+pub use ownership::OrderOwnership;
+pub use paper::{LiveTrader, PaperFill, PaperTrader, Trader};
+pub use preflight::{OrderValidationError, OrderViolation, ValidatedOrder};
+pub use receipts::{O2RevertReason, Receipt};
+pub use reconcile::{Discrepancy, Expected, ReconcileReport};
+pub use recorder::{Recorder, ReplaySpeed, ReplayWebSocket};
+pub use retry::{RetryOn, RetryPolicy};
+pub use runtime::{Runtime, TokioRuntime};
+pub use scheduler::{JobEvent, Schedule, Scheduler, SchedulerHandle};
+pub use settlement::SettlementPolicy;
+pub use setup_guard::{CircuitState, SetupGuardConfig};
+pub use strategy::{Fill, Intent, LiveStrategy, RunnerConfig, RunnerStats, StrategyRunner};
+pub use timestamp::Timestamp;
+pub use transport::Transport;
+pub use triggers::{
+    TrailDistance, TrailingStop, Trigger, TriggerBook, TriggerCondition, TriggerConfig,
+    TriggerEngine, TriggerEvent, TriggerOrder, TriggerStatus,
+};
+pub use websocket::{
+    ActiveStream, DepthPrecision, FeedKind, FeedStats, O2WebSocket, SubscriptionHandle,
+    TypedStream, WsConfig, WsLifecycleEvent,
+};
@@ -0,0 +1,402 @@
+//! Layered two-sided quoting.
+//!
+//! A [`Quoter`] turns a reference price into [`QuoterConfig::levels`] bids and
+//! asks, each one [`QuoterConfig::level_spacing_bps`] further out than the
+//! last, and diffs them against the account's resting orders in a [`MyBook`].
+//! The resulting [`QuotePlan`] only touches levels that moved or changed size,
+//! and [`Quoter::batches`] packs it into batches of at most
+//! [`MAX_ACTIONS_PER_BATCH`] actions, cancels first, so each batch replaces
+//! quotes rather than briefly doubling them.
+//!
+//! Prices and sizes are chain-scaled integers, matching [`MyBook`].
+//!
+//! During bursts the book can move between computing a plan and sending its
+//! last batch. Stamp the plan with the [`LiveOrderBook`](crate::LiveOrderBook)
+//! version it was computed from and set [`QuoterConfig::max_book_lag`];
+//! [`O2Client::requote_against`](crate::O2Client::requote_against) then fails
+//! with [`O2Error::StaleBook`] instead of sending a batch once the book has
+//! applied more updates than that, so the caller can plan again.
+//!
+//! ```rust,ignore
+//! let quoter = Quoter::new(market, QuoterConfig::new(3, 20, 10, "100".parse()?))?;
+//! let mine = client.my_book(&session.trade_account_id, "fFUEL/fUSDC", Duration::from_secs(30)).await?;
+//! loop {
+//!     let plan = quoter.plan(&mine.read(), reference_price)?;
+//!     client.requote(&mut session, &quoter, &plan).await?;
+//! }
+//! ```
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{Action, Market, OrderId, OrderType, Side, MAX_ACTIONS_PER_BATCH};
+use crate::my_book::MyBook;
+
+/// Basis points in a whole.
+const BPS: u128 = 10_000;
+
+/// Configuration for a [`Quoter`].
+#[derive(Debug, Clone)]
+pub struct QuoterConfig {
+    /// Quotes per side.
+    pub levels: usize,
+    /// Distance of the innermost bid and ask from the reference price, in
+    /// basis points.
+    pub spread_bps: u32,
+    /// Additional distance of each further level, in basis points.
+    pub level_spacing_bps: u32,
+    /// Base quantity quoted at each level.
+    pub quantity: UnsignedDecimal,
+    /// Order type of each quote; must rest on the book.
+    pub order_type: OrderType,
+    /// Book updates a stamped plan may lag behind before its remaining
+    /// batches are withheld. `None` never withholds.
+    pub max_book_lag: Option<u64>,
+}
+
+impl QuoterConfig {
+    /// `PostOnly` quotes of `quantity` on `levels` levels per side.
+    pub fn new(
+        levels: usize,
+        spread_bps: u32,
+        level_spacing_bps: u32,
+        quantity: UnsignedDecimal,
+    ) -> Self {
+        Self {
+            levels,
+            spread_bps,
+            level_spacing_bps,
+            quantity,
+            order_type: OrderType::PostOnly,
+            max_book_lag: None,
+        }
+    }
+
+    /// Use `order_type` instead of `PostOnly`.
+    pub fn with_order_type(mut self, order_type: OrderType) -> Self {
+        self.order_type = order_type;
+        self
+    }
+
+    /// Withhold batches of a plan once the book has applied more than
+    /// `updates` updates since it was computed.
+    pub fn with_max_book_lag(mut self, updates: u64) -> Self {
+        self.max_book_lag = Some(updates);
+        self
+    }
+}
+
+/// One desired quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    pub side: Side,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+/// The cancels and creates that move the resting quotes to the desired ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuotePlan {
+    pub cancel: Vec<OrderId>,
+    pub create: Vec<Quote>,
+    /// Levels already resting at exactly the desired size.
+    pub unchanged: usize,
+    /// Version of the book the plan was computed from, if stamped.
+    pub book_version: Option<u64>,
+}
+
+impl QuotePlan {
+    /// True if the resting quotes already match.
+    pub fn is_empty(&self) -> bool {
+        self.cancel.is_empty() && self.create.is_empty()
+    }
+
+    /// Record that the plan was computed from the book at `version`, as read
+    /// from [`LiveOrderBook::version`](crate::LiveOrderBook::version).
+    pub fn at_book_version(mut self, version: u64) -> Self {
+        self.book_version = Some(version);
+        self
+    }
+}
+
+/// Computes layered quotes around a reference price for one market.
+#[derive(Debug, Clone)]
+pub struct Quoter {
+    market: Market,
+    config: QuoterConfig,
+    quantity: u64,
+    tick: u64,
+}
+
+impl Quoter {
+    /// Fails with [`O2Error::InvalidOrderParams`] if there are no levels, the
+    /// quantity scales to zero, or the order type cannot rest on the book.
+    pub fn new(market: Market, config: QuoterConfig) -> Result<Self, O2Error> {
+        if config.levels == 0 {
+            return Err(O2Error::InvalidOrderParams(
+                "Quoter needs at least one level".into(),
+            ));
+        }
+        if matches!(
+            config.order_type,
+            OrderType::Market | OrderType::FillOrKill | OrderType::BoundedMarket { .. }
+        ) {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Quotes must rest on the book; {:?} orders do not",
+                config.order_type
+            )));
+        }
+        let quantity = market.scale_quantity(&config.quantity)?;
+        if quantity == 0 {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Quote quantity {} is below the market's base precision",
+                config.quantity
+            )));
+        }
+        let tick = market.price_tick()?;
+        Ok(Self {
+            market,
+            config,
+            quantity,
+            tick,
+        })
+    }
+
+    /// The market being quoted.
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// The quoting configuration.
+    pub fn config(&self) -> &QuoterConfig {
+        &self.config
+    }
+
+    /// Desired quotes around `reference_price`, innermost first on each side.
+    ///
+    /// Bids round down and asks round up to the price tick, so a level is never
+    /// closer to the reference than configured. Bids that would reach zero are
+    /// dropped, and each size is trimmed by [`Market::adjust_quantity`].
+    pub fn quotes(&self, reference_price: u64) -> Result<Vec<Quote>, O2Error> {
+        if reference_price == 0 {
+            return Err(O2Error::InvalidOrderParams(
+                "Reference price cannot be zero".into(),
+            ));
+        }
+        let reference = reference_price as u128;
+        let tick = self.tick as u128;
+        let mut quotes = Vec::with_capacity(self.config.levels * 2);
+        for level in 0..self.config.levels as u128 {
+            let offset =
+                self.config.spread_bps as u128 + level * self.config.level_spacing_bps as u128;
+            let bid = reference * BPS.saturating_sub(offset) / BPS / tick * tick;
+            if bid > 0 {
+                quotes.push(self.quote(Side::Buy, bid as u64)?);
+            }
+            let ask = (reference * (BPS + offset)).div_ceil(BPS).div_ceil(tick) * tick;
+            let ask = u64::try_from(ask)
+                .map_err(|_| O2Error::InvalidOrderParams("Quote price exceeds u64 range".into()))?;
+            quotes.push(self.quote(Side::Sell, ask)?);
+        }
+        Ok(quotes)
+    }
+
+    fn quote(&self, side: Side, price: u64) -> Result<Quote, O2Error> {
+        let quantity = self.market.adjust_quantity(price, self.quantity)?;
+        Ok(Quote {
+            side,
+            price,
+            quantity,
+        })
+    }
+
+    /// What to cancel and create so `book` rests exactly the quotes for
+    /// `reference_price`. See [`MyBook::diff`].
+    pub fn plan(&self, book: &MyBook, reference_price: u64) -> Result<QuotePlan, O2Error> {
+        let quotes = self.quotes(reference_price)?;
+        let mut plan = QuotePlan::default();
+        for side in [Side::Buy, Side::Sell] {
+            let targets: Vec<(u64, u64)> = quotes
+                .iter()
+                .filter(|q| q.side == side)
+                .map(|q| (q.price, q.quantity))
+                .collect();
+            let diff = book.diff(side, &targets);
+            plan.cancel.extend(diff.cancel);
+            plan.create
+                .extend(diff.create.into_iter().map(|(price, quantity)| Quote {
+                    side,
+                    price,
+                    quantity,
+                }));
+            plan.unchanged += diff.unchanged;
+        }
+        Ok(plan)
+    }
+
+    /// Fails with [`O2Error::StaleBook`] if `plan` is stamped and the book,
+    /// now at `current_version`, has moved more than
+    /// [`QuoterConfig::max_book_lag`] updates past it.
+    pub fn check_book_lag(&self, plan: &QuotePlan, current_version: u64) -> Result<(), O2Error> {
+        let (Some(computed_at), Some(max_lag)) = (plan.book_version, self.config.max_book_lag)
+        else {
+            return Ok(());
+        };
+        if current_version.wrapping_sub(computed_at) > max_lag {
+            return Err(O2Error::StaleBook {
+                computed_at,
+                current: current_version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Pack `plan` into batches of at most [`MAX_ACTIONS_PER_BATCH`] actions,
+    /// all cancels before any create.
+    pub fn batches(&self, plan: &QuotePlan) -> Result<Vec<Vec<Action>>, O2Error> {
+        let mut actions = Vec::with_capacity(plan.cancel.len() + plan.create.len());
+        actions.extend(plan.cancel.iter().map(|order_id| Action::CancelOrder {
+            order_id: order_id.clone(),
+        }));
+        for quote in &plan.create {
+            actions.push(Action::CreateOrder {
+                side: quote.side,
+                price: self.market.format_price(quote.price)?,
+                quantity: self.market.format_quantity(quote.quantity)?,
+                order_type: self.config.order_type.clone(),
+            });
+        }
+        Ok(actions
+            .chunks(MAX_ACTIONS_PER_BATCH)
+            .map(<[Action]>::to_vec)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MarketId, Order};
+
+    fn market() -> Market {
+        serde_json::from_value(serde_json::json!({
+            "contract_id": "0x01",
+            "market_id": "0xaa",
+            "whitelist_id": null,
+            "blacklist_id": null,
+            "maker_fee": "0",
+            "taker_fee": "0",
+            "min_order": "0",
+            "dust": "0",
+            "price_window": "0",
+            "base": { "symbol": "fFUEL", "asset": "0x02", "decimals": 9, "max_precision": 3 },
+            "quote": { "symbol": "fUSDC", "asset": "0x03", "decimals": 9, "max_precision": 3 },
+        }))
+        .unwrap()
+    }
+
+    fn resting(id: &str, side: &str, price: u64, quantity: u64) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": side,
+            "order_type": "PostOnly",
+            "quantity": quantity.to_string(),
+            "price": price.to_string(),
+            "market_id": "0xaa",
+        }))
+        .unwrap()
+    }
+
+    fn quoter(levels: usize) -> Quoter {
+        Quoter::new(
+            market(),
+            QuoterConfig::new(levels, 50, 100, "2".parse().unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn layers_quotes_outward_on_the_tick() {
+        // 1.0 with 50bps then 150bps: bids 0.995 / 0.985, asks 1.005 / 1.015.
+        let quotes = quoter(2).quotes(1_000_000_000).unwrap();
+        let prices: Vec<(Side, u64)> = quotes.iter().map(|q| (q.side, q.price)).collect();
+        assert_eq!(
+            prices,
+            vec![
+                (Side::Buy, 995_000_000),
+                (Side::Sell, 1_005_000_000),
+                (Side::Buy, 985_000_000),
+                (Side::Sell, 1_015_000_000),
+            ]
+        );
+        assert!(quotes.iter().all(|q| q.quantity == 2_000_000_000));
+
+        // 0.1234 with 50bps: bid 0.122783 rounds down, ask 0.124017 rounds up.
+        let quotes = quoter(1).quotes(123_400_000).unwrap();
+        assert_eq!(quotes[0].price, 122_000_000);
+        assert_eq!(quotes[1].price, 125_000_000);
+        assert!(quoter(1).quotes(0).is_err());
+    }
+
+    #[test]
+    fn plans_only_the_levels_that_moved() {
+        let quoter = quoter(2);
+        let mut book = MyBook::new(MarketId::new("0xaa"));
+        book.reconcile(&[
+            resting("0x01", "Buy", 995_000_000, 2_000_000_000),
+            resting("0x02", "Buy", 985_000_000, 2_000_000_000),
+            resting("0x03", "Sell", 1_005_000_000, 2_000_000_000),
+            resting("0x04", "Sell", 1_010_000_000, 2_000_000_000),
+        ]);
+        let plan = quoter.plan(&book, 1_000_000_000).unwrap();
+        assert_eq!(plan.unchanged, 3);
+        assert_eq!(plan.cancel, vec![OrderId::new("0x04")]);
+        assert_eq!(
+            plan.create,
+            vec![Quote {
+                side: Side::Sell,
+                price: 1_015_000_000,
+                quantity: 2_000_000_000
+            }]
+        );
+        assert!(!plan.is_empty());
+    }
+
+    #[test]
+    fn batches_respect_the_action_limit_with_cancels_first() {
+        let quoter = quoter(3);
+        let mut book = MyBook::new(MarketId::new("0xaa"));
+        book.reconcile(&[
+            resting("0x01", "Buy", 900_000_000, 1),
+            resting("0x02", "Sell", 1_100_000_000, 1),
+        ]);
+        let plan = quoter.plan(&book, 1_000_000_000).unwrap();
+        assert_eq!((plan.cancel.len(), plan.create.len()), (2, 6));
+        let batches = quoter.batches(&plan).unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![5, 3]);
+        assert!(matches!(batches[0][0], Action::CancelOrder { .. }));
+        assert!(matches!(batches[0][1], Action::CancelOrder { .. }));
+        assert!(matches!(batches[0][2], Action::CreateOrder { .. }));
+        assert!(quoter.batches(&QuotePlan::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn withholds_plans_computed_from_a_stale_book() {
+        let lenient = quoter(1);
+        let strict = Quoter::new(
+            market(),
+            QuoterConfig::new(1, 50, 100, "2".parse().unwrap()).with_max_book_lag(2),
+        )
+        .unwrap();
+        let plan = QuotePlan::default().at_book_version(u64::MAX);
+        // The counter wraps, so 1 is two updates on.
+        assert!(strict.check_book_lag(&plan, 1).is_ok());
+        assert!(matches!(
+            strict.check_book_lag(&plan, 2),
+            Err(O2Error::StaleBook {
+                computed_at: u64::MAX,
+                current: 2
+            })
+        ));
+        assert!(lenient.check_book_lag(&plan, 2).is_ok());
+        assert!(strict.check_book_lag(&QuotePlan::default(), 2).is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+//! Detection of markets that are delisted mid-session.
+//!
+//! O2 does not report a market status; a delisted market drops out of
+//! `GET /v1/markets`, after which submissions to it fail with opaque reverts
+//! and its streams go quiet. A [`MarketWatcher`] polls the market list and,
+//! when a market disappears:
+//!
+//! - marks it inactive on the [`O2Client`](crate::O2Client) that started the
+//!   watcher, which from then on fails submissions to it with
+//!   [`O2Error::MarketInactive`](crate::O2Error::MarketInactive) before
+//!   signing, and records its contract in
+//!   [`Session::inactive_contract_ids`] of the session used;
+//! - emits [`MarketEvent::Delisted`] so strategies can rebalance;
+//! - with [`MarketWatcherConfig::cancel_orders`] set, cancels the account's
+//!   resting orders on it with that separate session.
+//!
+//! A market that comes back is reactivated and reported as
+//! [`MarketEvent::Relisted`]. A market the server reports as paused is not in
+//! this list; submissions to it fail with
+//! [`O2Error::MarketPaused`](crate::O2Error::MarketPaused).
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use crate::api::O2Api;
+use crate::client::O2Client;
+use crate::models::{Market, MarketId, MarketSymbol, MarketsResponse, Session};
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+
+/// Configuration for a [`MarketWatcher`].
+#[derive(Debug, Clone)]
+pub struct MarketWatcherConfig {
+    /// How often the market list is polled.
+    pub poll_interval: Duration,
+    /// Cancel the account's resting orders on a delisted market with this
+    /// session. Use a separate session from the one the strategy trades with;
+    /// its nonce is refreshed before cancelling.
+    pub cancel_orders: Option<Session>,
+}
+
+impl Default for MarketWatcherConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            cancel_orders: None,
+        }
+    }
+}
+
+/// A change in a market's listing.
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    /// The market left the market list. It is inactive from now on.
+    Delisted { market: Market },
+    /// A delisted market is listed again and active.
+    Relisted { market: Market },
+    /// Resting orders on a delisted market were cancelled.
+    OrdersCancelled {
+        market_id: MarketId,
+        /// Number of cancel batches submitted.
+        batches: usize,
+    },
+    /// Cancelling resting orders on a delisted market failed.
+    CancelFailed { market_id: MarketId, error: String },
+}
+
+/// Delisted markets, shared between a client and its watchers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InactiveMarkets(Arc<RwLock<HashMap<MarketId, Market>>>);
+
+impl InactiveMarkets {
+    fn insert(&self, market: Market) {
+        self.0
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(market.market_id.clone(), market);
+    }
+
+    fn remove(&self, market_id: &MarketId) -> bool {
+        self.0
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .remove(market_id)
+            .is_some()
+    }
+
+    /// The inactive market with this symbol pair, if any.
+    pub(crate) fn by_symbol(&self, symbol: &MarketSymbol) -> Option<Market> {
+        self.0
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .find(|m| &m.symbol_pair() == symbol)
+            .cloned()
+    }
+
+    pub(crate) fn list(&self) -> Vec<Market> {
+        self.0
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Background task that watches the market list for delistings.
+///
+/// Dropping the watcher stops it; markets it marked inactive stay inactive.
+pub struct MarketWatcher {
+    events: broadcast::Sender<MarketEvent>,
+    inactive: InactiveMarkets,
+    task: Task<()>,
+}
+
+impl MarketWatcher {
+    pub(crate) fn spawn(
+        api: O2Api,
+        known: MarketsResponse,
+        inactive: InactiveMarkets,
+        config: MarketWatcherConfig,
+    ) -> Self {
+        let (events, _) = broadcast::channel(64);
+        let task = runtime::spawn(run(api, known, inactive.clone(), config, events.clone()));
+        Self {
+            events,
+            inactive,
+            task,
+        }
+    }
+
+    /// Receive listing changes from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<MarketEvent> {
+        self.events.subscribe()
+    }
+
+    /// Markets currently known to be delisted.
+    pub fn inactive_markets(&self) -> Vec<Market> {
+        self.inactive.list()
+    }
+
+    /// True until the watcher is dropped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for MarketWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(
+    api: O2Api,
+    mut known: MarketsResponse,
+    inactive: InactiveMarkets,
+    mut config: MarketWatcherConfig,
+    events: broadcast::Sender<MarketEvent>,
+) {
+    let mut ticker = runtime::interval(config.poll_interval);
+    loop {
+        ticker.tick().await;
+        let fresh = match api.get_markets().await {
+            Ok(fresh) => fresh,
+            Err(e) => {
+                debug!("market_watch.poll_failed error={}", e);
+                continue;
+            }
+        };
+        for market in &fresh.markets {
+            if inactive.remove(&market.market_id) {
+                debug!("market_watch.relisted market_id={}", market.market_id);
+                let _ = events.send(MarketEvent::Relisted {
+                    market: market.clone(),
+                });
+            }
+        }
+        let delisted: Vec<Market> = known
+            .markets
+            .iter()
+            .filter(|m| !fresh.markets.iter().any(|f| f.market_id == m.market_id))
+            .cloned()
+            .collect();
+        for market in delisted {
+            debug!("market_watch.delisted market_id={}", market.market_id);
+            inactive.insert(market.clone());
+            let _ = events.send(MarketEvent::Delisted {
+                market: market.clone(),
+            });
+            if let Some(session) = config.cancel_orders.as_mut() {
+                let event = cancel_resting(&api, &known, session, &market).await;
+                let _ = events.send(event);
+            }
+        }
+        known = fresh;
+    }
+}
+
+/// Cancel the account's open orders on `market`, which is no longer listed.
+async fn cancel_resting(
+    api: &O2Api,
+    listed: &MarketsResponse,
+    session: &mut Session,
+    market: &Market,
+) -> MarketEvent {
+    // The live list no longer has the market, so resolve it from the last
+    // list that did.
+    let mut client = O2Client::with_api(api.clone());
+    client.pin_markets(listed.clone());
+    if let Err(e) = client.refresh_nonce(session).await {
+        debug!("market_watch.refresh_nonce failed error={}", e);
+    }
+    match client
+        .cancel_all_orders(session, market.symbol_pair())
+        .await
+    {
+        Ok(responses) => MarketEvent::OrdersCancelled {
+            market_id: market.market_id.clone(),
+            batches: responses.len(),
+        },
+        Err(e) => MarketEvent::CancelFailed {
+            market_id: market.market_id.clone(),
+            error: e.to_string(),
+        },
+    }
+}
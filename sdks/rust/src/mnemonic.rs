@@ -0,0 +1,287 @@
+//! BIP-39 mnemonics and BIP-32 key derivation.
+//!
+//! Lets wallets be backed up as seed phrases and shared with browser wallets.
+//! A phrase is turned into a 64-byte seed (BIP-39, PBKDF2-HMAC-SHA512) and a
+//! private key is derived from the seed along a path such as
+//! [`FUEL_DERIVATION_PATH`] or [`EVM_DERIVATION_PATH`] (BIP-32/BIP-44).
+//! [`Wallet::from_mnemonic`](crate::Wallet::from_mnemonic) and
+//! [`EvmWallet::from_mnemonic`](crate::EvmWallet::from_mnemonic) combine the two.
+//!
+//! Only the English wordlist is supported. Phrases are matched
+//! case-insensitively with any whitespace between words. Passphrases must be
+//! ASCII, since Unicode normalization is not applied.
+use std::sync::OnceLock;
+
+use rand::RngCore;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::errors::O2Error;
+
+/// BIP-44 path used by Fuel wallets for the first account.
+pub const FUEL_DERIVATION_PATH: &str = "m/44'/1179993420'/0'/0/0";
+
+/// BIP-44 path used by Ethereum wallets for the first address.
+pub const EVM_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// The Fuel wallet path for account `index` (`m/44'/1179993420'/{index}'/0/0`).
+pub fn fuel_derivation_path(index: u32) -> String {
+    format!("m/44'/1179993420'/{index}'/0/0")
+}
+
+/// The Ethereum wallet path for address `index` (`m/44'/60'/0'/0/{index}`).
+pub fn evm_derivation_path(index: u32) -> String {
+    format!("m/44'/60'/0'/0/{index}")
+}
+
+const ENGLISH: &str = include_str!("bip39_english.txt");
+
+const HARDENED: u32 = 0x8000_0000;
+
+const PBKDF2_ROUNDS: u32 = 2048;
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| ENGLISH.lines().collect())
+}
+
+fn word_index(word: &str) -> Option<u16> {
+    // The list is sorted.
+    wordlist().binary_search(&word).ok().map(|i| i as u16)
+}
+
+/// Generate a random English mnemonic of 12, 15, 18, 21 or 24 words.
+pub fn generate_mnemonic(word_count: usize) -> Result<String, O2Error> {
+    if !matches!(word_count, 12 | 15 | 18 | 21 | 24) {
+        return Err(O2Error::CryptoError(format!(
+            "Mnemonic must have 12, 15, 18, 21 or 24 words, not {word_count}"
+        )));
+    }
+    let mut entropy = vec![0u8; word_count * 4 / 3];
+    rand::thread_rng().fill_bytes(&mut entropy);
+    entropy_to_mnemonic(&entropy)
+}
+
+/// Encode 16–32 bytes of entropy (a multiple of 4) as a mnemonic.
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> Result<String, O2Error> {
+    if !(16..=32).contains(&entropy.len()) || entropy.len() % 4 != 0 {
+        return Err(O2Error::CryptoError(format!(
+            "Mnemonic entropy must be 16, 20, 24, 28 or 32 bytes, not {}",
+            entropy.len()
+        )));
+    }
+    let checksum = Sha256::digest(entropy);
+    let total_bits = entropy.len() * 8 + entropy.len() / 4;
+    let bit = |i: usize| {
+        let byte = if i < entropy.len() * 8 {
+            entropy[i / 8]
+        } else {
+            checksum[(i - entropy.len() * 8) / 8]
+        };
+        (byte >> (7 - i % 8)) & 1
+    };
+    let words = wordlist();
+    Ok((0..total_bits / 11)
+        .map(|w| {
+            let index = (0..11).fold(0usize, |acc, b| (acc << 1) | bit(w * 11 + b) as usize);
+            words[index]
+        })
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Check a phrase's words and checksum, returning its entropy.
+pub fn mnemonic_to_entropy(phrase: &str) -> Result<Vec<u8>, O2Error> {
+    let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+    if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+        return Err(O2Error::CryptoError(format!(
+            "Mnemonic must have 12, 15, 18, 21 or 24 words, got {}",
+            words.len()
+        )));
+    }
+    let mut bits = Vec::with_capacity(words.len() * 11);
+    for (position, word) in words.iter().enumerate() {
+        let index = word_index(word).ok_or_else(|| {
+            O2Error::CryptoError(format!(
+                "Word {} of the mnemonic is not in the BIP-39 English wordlist",
+                position + 1
+            ))
+        })?;
+        bits.extend((0..11).rev().map(|b| (index >> b) & 1 == 1));
+    }
+    let entropy_bits = bits.len() * 32 / 33;
+    let entropy: Vec<u8> = bits[..entropy_bits]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &b| (acc << 1) | b as u8))
+        .collect();
+    let checksum = Sha256::digest(&entropy);
+    let expected =
+        (0..bits.len() - entropy_bits).map(|i| (checksum[i / 8] >> (7 - i % 8)) & 1 == 1);
+    if !expected.eq(bits[entropy_bits..].iter().copied()) {
+        return Err(O2Error::CryptoError("Invalid mnemonic checksum".into()));
+    }
+    Ok(entropy)
+}
+
+/// Derive the 64-byte BIP-39 seed from a phrase and optional passphrase.
+///
+/// The phrase is validated first, so a mistyped word or a wrong word order is
+/// an error rather than a different wallet.
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], O2Error> {
+    mnemonic_to_entropy(phrase)?;
+    if !passphrase.is_ascii() {
+        return Err(O2Error::CryptoError(
+            "Mnemonic passphrase must be ASCII".into(),
+        ));
+    }
+    let normalized = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let salt = format!("mnemonic{passphrase}");
+    Ok(pbkdf2_sha512(
+        normalized.as_bytes(),
+        salt.as_bytes(),
+        PBKDF2_ROUNDS,
+    ))
+}
+
+/// Derive a private key from a seed along a BIP-32 path like `m/44'/60'/0'/0/0`.
+///
+/// Hardened components may be written `44'` or `44h`.
+pub fn derive_private_key(seed: &[u8], path: &str) -> Result<[u8; 32], O2Error> {
+    let indices = parse_path(path)?;
+    let secp = Secp256k1::new();
+    let master = hmac_sha512(b"Bitcoin seed", &[seed]);
+    let (mut key, mut chain_code) = split_key(&master)?;
+    for index in indices {
+        let mut data = Vec::with_capacity(37);
+        if index >= HARDENED {
+            data.push(0);
+            data.extend_from_slice(&key.secret_bytes());
+        } else {
+            data.extend_from_slice(&PublicKey::from_secret_key(&secp, &key).serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+        let out = hmac_sha512(&chain_code, &[&data]);
+        let tweak: [u8; 32] = out[..32].try_into().expect("32-byte half");
+        let tweak = Scalar::from_be_bytes(tweak)
+            .map_err(|_| O2Error::CryptoError(format!("Unusable child key at {path}")))?;
+        key = key
+            .add_tweak(&tweak)
+            .map_err(|_| O2Error::CryptoError(format!("Unusable child key at {path}")))?;
+        chain_code.copy_from_slice(&out[32..]);
+    }
+    Ok(key.secret_bytes())
+}
+
+fn split_key(out: &[u8; 64]) -> Result<(SecretKey, [u8; 32]), O2Error> {
+    let key = SecretKey::from_slice(&out[..32])
+        .map_err(|e| O2Error::CryptoError(format!("Unusable master key: {e}")))?;
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&out[32..]);
+    Ok((key, chain_code))
+}
+
+fn parse_path(path: &str) -> Result<Vec<u32>, O2Error> {
+    let invalid = || O2Error::CryptoError(format!("Invalid derivation path: {path:?}"));
+    let mut parts = path.trim().split('/');
+    if parts.next() != Some("m") {
+        return Err(invalid());
+    }
+    parts
+        .map(|part| {
+            let (digits, hardened) = match part.strip_suffix(['\'', 'h', 'H']) {
+                Some(digits) => (digits, true),
+                None => (part, false),
+            };
+            let index: u32 = digits.parse().map_err(|_| invalid())?;
+            if index >= HARDENED {
+                return Err(invalid());
+            }
+            Ok(if hardened { index | HARDENED } else { index })
+        })
+        .collect()
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    const BLOCK: usize = 128;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..64].copy_from_slice(&Sha512::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha512::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in data {
+        inner.update(part);
+    }
+    let mut outer = Sha512::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// PBKDF2-HMAC-SHA512 producing a single 64-byte block.
+fn pbkdf2_sha512(password: &[u8], salt: &[u8], rounds: u32) -> [u8; 64] {
+    let mut u = hmac_sha512(password, &[salt, &1u32.to_be_bytes()]);
+    let mut out = u;
+    for _ in 1..rounds {
+        u = hmac_sha512(password, &[&u]);
+        out.iter_mut().zip(u.iter()).for_each(|(o, b)| *o ^= b);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wordlist_is_the_bip39_english_list() {
+        let digest = Sha256::digest(ENGLISH.as_bytes());
+        assert_eq!(
+            hex::encode(digest),
+            "2f5eed53a4727b4bf8880d8f3f199efc90e58503646d9ff8eff3a2ed3b24dbda"
+        );
+        assert_eq!(wordlist().len(), 2048);
+    }
+
+    #[test]
+    fn hmac_matches_rfc4231() {
+        // RFC 4231 test case 2.
+        let out = hmac_sha512(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            hex::encode(out),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+
+    #[test]
+    fn parses_paths() {
+        assert_eq!(
+            parse_path("m/44'/60h/0/1").unwrap(),
+            vec![44 | HARDENED, 60 | HARDENED, 0, 1]
+        );
+        assert_eq!(parse_path("m").unwrap(), Vec::<u32>::new());
+        assert!(parse_path("44'/60'").is_err());
+        assert!(parse_path("m/x").is_err());
+        assert!(parse_path("m/2147483648").is_err());
+    }
+
+    #[test]
+    fn entropy_must_be_a_bip39_length() {
+        for len in [16, 20, 24, 28, 32] {
+            let phrase = entropy_to_mnemonic(&vec![0u8; len]).unwrap();
+            assert_eq!(phrase.split(' ').count(), len * 3 / 4);
+        }
+        for len in [0, 4, 12, 17, 30, 36] {
+            assert!(matches!(
+                entropy_to_mnemonic(&vec![0u8; len]),
+                Err(O2Error::CryptoError(_))
+            ));
+        }
+    }
+}
@@ -2,13 +2,17 @@
 ///
 /// All models use serde for JSON serialization/deserialization.
 /// String fields are used for large numeric values to avoid precision loss.
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
+use crate::crypto::PrivateKey;
 use crate::decimal::UnsignedDecimal;
 use crate::errors::O2Error;
+use crate::fees::FeeRates;
+use crate::timestamp::Timestamp;
 
 macro_rules! newtype_id {
     ($(#[$meta:meta])* $name:ident) => {
@@ -252,6 +256,10 @@ hex_id!(
     /// A hex asset ID.
     AssetId
 );
+hex_id!(
+    /// A hex owner address (the wallet's b256 address).
+    OwnerAddress
+);
 
 fn normalize_hex_prefixed(s: String) -> String {
     if s.starts_with("0x") || s.starts_with("0X") || s.is_empty() {
@@ -317,7 +325,7 @@ impl<'de> Deserialize<'de> for TxId {
 }
 
 /// Deserialize a value that may be a JSON number or a string containing a number.
-fn deserialize_string_or_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+pub(crate) fn deserialize_string_or_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -450,6 +458,94 @@ where
     }
 }
 
+/// A decimal value from the aggregated endpoints: a price, volume or minimum.
+///
+/// `f64` by default. With the `lossless-amounts` feature it is an exact
+/// [`Decimal`], so volumes beyond 2^53 or with many significant digits keep
+/// every digit. Values sent as strings or integers are exact; fractional JSON
+/// numbers are read through `f64` by `serde_json` and keep its shortest
+/// round-trip digits.
+#[cfg(not(feature = "lossless-amounts"))]
+pub type AggregatedAmount = f64;
+/// A decimal value from the aggregated endpoints: a price, volume or minimum.
+///
+/// An exact [`Decimal`] because the `lossless-amounts` feature is enabled;
+/// `f64` without it. Values sent as strings or integers are exact; fractional
+/// JSON numbers are read through `f64` by `serde_json` and keep its shortest
+/// round-trip digits.
+#[cfg(feature = "lossless-amounts")]
+pub type AggregatedAmount = Decimal;
+
+#[cfg(not(feature = "lossless-amounts"))]
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<AggregatedAmount, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_string_or_f64(deserializer)
+}
+
+#[cfg(feature = "lossless-amounts")]
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<AggregatedAmount, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_string_or_decimal(deserializer)
+}
+
+/// Deserialize `[[price, volume], ...]` levels of [`AggregatedAmount`]s.
+fn deserialize_amount_levels<'de, D>(
+    deserializer: D,
+) -> Result<Vec<[AggregatedAmount; 2]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Amount(#[serde(deserialize_with = "deserialize_amount")] AggregatedAmount);
+
+    let levels: Vec<[Amount; 2]> = Vec::deserialize(deserializer)?;
+    Ok(levels.into_iter().map(|[a, b]| [a.0, b.0]).collect())
+}
+
+/// Deserialize a value that may be a JSON number or a string, as an exact `Decimal`.
+#[cfg(feature = "lossless-amounts")]
+fn deserialize_string_or_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de;
+
+    fn parse<E: de::Error>(v: &str) -> Result<Decimal, E> {
+        Decimal::from_str(v)
+            .or_else(|_| Decimal::from_scientific(v))
+            .map_err(de::Error::custom)
+    }
+
+    struct StringOrDecimal;
+    impl<'de> de::Visitor<'de> for StringOrDecimal {
+        type Value = Decimal;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a number or a string containing a decimal")
+        }
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+            Ok(Decimal::from(v))
+        }
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+            Ok(Decimal::from(v))
+        }
+        fn visit_u128<E: de::Error>(self, v: u128) -> Result<Decimal, E> {
+            let v = i128::try_from(v).map_err(de::Error::custom)?;
+            Decimal::try_from_i128_with_scale(v, 0).map_err(de::Error::custom)
+        }
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+            parse(&v.to_string())
+        }
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+            parse(v)
+        }
+    }
+    deserializer.deserialize_any(StringOrDecimal)
+}
+
 // ---------------------------------------------------------------------------
 // Public trading enums
 // ---------------------------------------------------------------------------
@@ -494,6 +590,28 @@ impl<'de> Deserialize<'de> for Side {
     }
 }
 
+/// How [`Market::round_price`] and [`Market::round_quantity`] snap a value
+/// to the market's step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    /// Toward zero: never more than the value given.
+    Down,
+    /// Away from zero: never less than the value given.
+    Up,
+    /// To the closest step, with halves rounded up.
+    Nearest,
+}
+
+impl RoundingMode {
+    fn strategy(self) -> rust_decimal::RoundingStrategy {
+        match self {
+            RoundingMode::Down => rust_decimal::RoundingStrategy::ToZero,
+            RoundingMode::Up => rust_decimal::RoundingStrategy::AwayFromZero,
+            RoundingMode::Nearest => rust_decimal::RoundingStrategy::MidpointAwayFromZero,
+        }
+    }
+}
+
 /// High-level order type with associated data.
 ///
 /// Used in `create_order` and `Action::CreateOrder` to provide compile-time
@@ -505,6 +623,8 @@ pub enum OrderType {
     Market,
     FillOrKill,
     PostOnly,
+    /// `timestamp` is the time-in-force timestamp in **Unix seconds**. Prefer
+    /// [`OrderType::limit`] or [`OrderType::limit_in`], which take typed times.
     Limit {
         price: UnsignedDecimal,
         timestamp: u64,
@@ -515,6 +635,9 @@ pub enum OrderType {
     },
 }
 
+/// Most actions the exchange accepts in one signed batch.
+pub const MAX_ACTIONS_PER_BATCH: usize = 5;
+
 /// High-level action for use with `batch_actions`.
 ///
 /// Converts to the low-level `CallArg` and JSON representations internally.
@@ -538,10 +661,10 @@ pub enum Action {
 /// A market-bound human-readable order price.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Price {
-    value: UnsignedDecimal,
-    market_id: MarketId,
-    quote_decimals: u32,
-    quote_max_precision: u32,
+    pub(crate) value: UnsignedDecimal,
+    pub(crate) market_id: MarketId,
+    pub(crate) quote_decimals: u32,
+    pub(crate) quote_max_precision: u32,
 }
 
 impl Price {
@@ -554,6 +677,22 @@ impl Price {
     pub fn market_id(&self) -> &MarketId {
         &self.market_id
     }
+
+    /// Bind this price to a refreshed snapshot of its market.
+    ///
+    /// Use after a metadata refresh changes the quote asset's decimals or
+    /// precision, which makes the old binding stale. The value is checked
+    /// against the new precision, never rounded; a price the market can no
+    /// longer represent is an error saying which limit changed.
+    pub fn rebind(&self, market: &Market) -> Result<Price, O2Error> {
+        market.check_same_market(Some(&self.market_id), "Price")?;
+        market.price_from_decimal(self.value).map_err(|e| {
+            O2Error::InvalidOrderParams(format!(
+                "Cannot rebind price {}: {e} (quote max_precision was {}, now {})",
+                self.value, self.quote_max_precision, market.quote.max_precision
+            ))
+        })
+    }
 }
 
 impl std::fmt::Display for Price {
@@ -565,10 +704,10 @@ impl std::fmt::Display for Price {
 /// A market-bound human-readable order quantity.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Quantity {
-    value: UnsignedDecimal,
-    market_id: MarketId,
-    base_decimals: u32,
-    base_max_precision: u32,
+    pub(crate) value: UnsignedDecimal,
+    pub(crate) market_id: MarketId,
+    pub(crate) base_decimals: u32,
+    pub(crate) base_max_precision: u32,
 }
 
 impl Quantity {
@@ -581,6 +720,18 @@ impl Quantity {
     pub fn market_id(&self) -> &MarketId {
         &self.market_id
     }
+
+    /// Bind this quantity to a refreshed snapshot of its market. See
+    /// [`Price::rebind`].
+    pub fn rebind(&self, market: &Market) -> Result<Quantity, O2Error> {
+        market.check_same_market(Some(&self.market_id), "Quantity")?;
+        market.quantity_from_decimal(self.value).map_err(|e| {
+            O2Error::InvalidOrderParams(format!(
+                "Cannot rebind quantity {}: {e} (base max_precision was {}, now {})",
+                self.value, self.base_max_precision, market.base.max_precision
+            ))
+        })
+    }
 }
 
 impl std::fmt::Display for Quantity {
@@ -663,7 +814,108 @@ impl TryFrom<String> for OrderQuantityInput {
     }
 }
 
+/// How far a `Limit` timestamp may be from the current time before
+/// [`OrderType::validate_timestamp`] rejects it.
+pub const LIMIT_TIMESTAMP_WINDOW: std::time::Duration =
+    std::time::Duration::from_secs(365 * 24 * 60 * 60);
+
 impl OrderType {
+    /// A `Limit` order with time-in-force timestamp `at`.
+    pub fn limit(price: UnsignedDecimal, at: Timestamp) -> Self {
+        OrderType::Limit {
+            price,
+            timestamp: at.as_unix_secs(),
+        }
+    }
+
+    /// A `Limit` order with a time-in-force timestamp `offset` from now.
+    ///
+    /// Uses the local clock; pass [`Duration::ZERO`](std::time::Duration::ZERO)
+    /// for "now".
+    pub fn limit_in(price: UnsignedDecimal, offset: std::time::Duration) -> Self {
+        Self::limit(price, Timestamp::now() + offset)
+    }
+
+    /// A good-til-time `Limit` order that expires `good_for` after `now` on the
+    /// exchange's clock. Pass the clock of the client that will submit the
+    /// order, [`O2Api::server_time`](crate::api::O2Api::server_time), so the
+    /// expiry is not thrown off by local clock skew.
+    ///
+    /// Fails if the expiry falls in the current second or is more than
+    /// [`LIMIT_TIMESTAMP_WINDOW`] away. The market's `price_window` bounds
+    /// prices, not expiries, so it does not apply.
+    pub fn limit_good_for(
+        price: UnsignedDecimal,
+        good_for: std::time::Duration,
+        now: Timestamp,
+    ) -> Result<Self, O2Error> {
+        Self::good_til(price, now + good_for, now)
+    }
+
+    /// A good-til-time `Limit` order that expires at `until`, checked against
+    /// the exchange's clock `now` as in [`OrderType::limit_good_for`].
+    #[cfg(feature = "chrono")]
+    pub fn limit_until(
+        price: UnsignedDecimal,
+        until: chrono::DateTime<chrono::Utc>,
+        now: Timestamp,
+    ) -> Result<Self, O2Error> {
+        Self::good_til(price, until.into(), now)
+    }
+
+    /// A `Limit` order expiring at `until`, which must be after `now` and
+    /// within [`LIMIT_TIMESTAMP_WINDOW`] of it. Expiries have one-second
+    /// resolution.
+    fn good_til(price: UnsignedDecimal, until: Timestamp, now: Timestamp) -> Result<Self, O2Error> {
+        if until.as_unix_secs() <= now.as_unix_secs() {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Limit expiry {} is not after the exchange's current time {}",
+                until.as_unix_secs(),
+                now.as_unix_secs()
+            )));
+        }
+        let order_type = Self::limit(price, until);
+        order_type.validate_timestamp(now)?;
+        Ok(order_type)
+    }
+
+    /// Check that a `Limit` timestamp is plausible Unix seconds relative to `now`.
+    ///
+    /// Rejects values whose magnitude indicates milliseconds, microseconds, or
+    /// nanoseconds, and values more than [`LIMIT_TIMESTAMP_WINDOW`] away from
+    /// `now`. A timestamp of 0 and other order types always pass.
+    /// [`O2Client::batch_actions`](crate::O2Client::batch_actions) calls this
+    /// with the exchange's clock before signing and logs a warning for a
+    /// rejected timestamp, but still submits the order.
+    pub fn validate_timestamp(&self, now: Timestamp) -> Result<(), O2Error> {
+        let OrderType::Limit { timestamp, .. } = self else {
+            return Ok(());
+        };
+        if *timestamp == 0 {
+            return Ok(());
+        }
+        let unit = Timestamp::inferred_unit(*timestamp as u128);
+        if unit != "seconds" {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Limit timestamp {timestamp} looks like Unix {unit}; expected Unix seconds \
+                 (now is {})",
+                now.as_unix_secs()
+            )));
+        }
+        let distance = now
+            .saturating_duration_since(Timestamp::from_unix_secs(*timestamp))
+            .max(Timestamp::from_unix_secs(*timestamp).saturating_duration_since(now));
+        if distance > LIMIT_TIMESTAMP_WINDOW {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Limit timestamp {timestamp} is {} days from now ({}); expected Unix seconds \
+                 near the current time",
+                distance.as_secs() / 86_400,
+                now.as_unix_secs()
+            )));
+        }
+        Ok(())
+    }
+
     /// Convert to the low-level `OrderTypeEncoding` and JSON representation
     /// used by the encoding and API layers.
     pub fn to_encoding(
@@ -746,6 +998,27 @@ pub struct MarketAsset {
     pub max_precision: u32,
 }
 
+impl MarketAsset {
+    /// Convert a human-readable amount of this asset to its chain integer,
+    /// e.g. for a withdrawal. Unlike order quantities it is not truncated to
+    /// `max_precision`; an amount finer than `decimals` fails with
+    /// [`O2Error::InvalidAmount`].
+    pub fn scale_amount(&self, amount: &UnsignedDecimal) -> Result<u64, O2Error> {
+        if amount.inner().normalize().scale() > self.decimals {
+            return Err(O2Error::InvalidAmount(format!(
+                "{amount} {} has more than {} decimals",
+                self.symbol, self.decimals
+            )));
+        }
+        Market::scale_human_value(amount, self.decimals, self.decimals, "decimals", "amount")
+    }
+
+    /// Convert a chain integer of this asset to human-readable.
+    pub fn format_amount(&self, chain_value: u128) -> Result<UnsignedDecimal, O2Error> {
+        Market::format_chain_value(chain_value, self.decimals, "decimals")
+    }
+}
+
 /// A trading market.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Market {
@@ -828,28 +1101,49 @@ impl Market {
     }
 
     /// Validate that a `Price` wrapper is compatible with this market.
+    ///
+    /// The error names the constraint that failed: a different market, or a
+    /// change in the quote asset's decimals or max precision since the price
+    /// was built. The latter can be fixed with [`Price::rebind`].
     pub fn validate_price_binding(&self, price: &Price) -> Result<(), O2Error> {
-        if price.market_id != self.market_id
-            || price.quote_decimals != self.quote.decimals
-            || price.quote_max_precision != self.quote.max_precision
-        {
-            return Err(O2Error::Other(format!(
-                "Price wrapper is stale or bound to a different market (expected {}, got {})",
-                self.market_id, price.market_id
-            )));
-        }
-        Ok(())
+        self.check_same_market(Some(&price.market_id), "Price")?;
+        Self::check_unchanged(
+            "Price",
+            "quote decimals",
+            price.quote_decimals,
+            self.quote.decimals,
+        )?;
+        Self::check_unchanged(
+            "Price",
+            "quote max_precision",
+            price.quote_max_precision,
+            self.quote.max_precision,
+        )
     }
 
-    /// Validate that a `Quantity` wrapper is compatible with this market.
+    /// Validate that a `Quantity` wrapper is compatible with this market. See
+    /// [`Market::validate_price_binding`].
     pub fn validate_quantity_binding(&self, quantity: &Quantity) -> Result<(), O2Error> {
-        if quantity.market_id != self.market_id
-            || quantity.base_decimals != self.base.decimals
-            || quantity.base_max_precision != self.base.max_precision
-        {
+        self.check_same_market(Some(&quantity.market_id), "Quantity")?;
+        Self::check_unchanged(
+            "Quantity",
+            "base decimals",
+            quantity.base_decimals,
+            self.base.decimals,
+        )?;
+        Self::check_unchanged(
+            "Quantity",
+            "base max_precision",
+            quantity.base_max_precision,
+            self.base.max_precision,
+        )
+    }
+
+    fn check_unchanged(what: &str, field: &str, bound: u32, current: u32) -> Result<(), O2Error> {
+        if bound != current {
             return Err(O2Error::Other(format!(
-                "Quantity wrapper is stale or bound to a different market (expected {}, got {})",
-                self.market_id, quantity.market_id
+                "{what} is stale: {field} changed from {bound} to {current} since it was built; \
+                 call {what}::rebind with the refreshed market"
             )));
         }
         Ok(())
@@ -880,56 +1174,184 @@ impl Market {
         Self::checked_pow_u64(decimals - max_precision, field)
     }
 
+    /// Interpret a chain integer as a decimal with `decimals` fractional digits.
+    fn format_chain_value(
+        chain_value: impl Into<u128>,
+        decimals: u32,
+        field: &str,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        let chain_value: u128 = chain_value.into();
+        let d = i128::try_from(chain_value)
+            .map_err(|e| e.to_string())
+            .and_then(|v| Decimal::try_from_i128_with_scale(v, decimals).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                O2Error::Other(format!(
+                    "Invalid {field}: cannot represent {chain_value} with {decimals} decimals: {e}"
+                ))
+            })?;
+        UnsignedDecimal::new(d.normalize())
+    }
+
+    fn format_quote_value(&self, chain_value: impl Into<u128>) -> Result<UnsignedDecimal, O2Error> {
+        Self::format_chain_value(chain_value, self.quote.decimals, "quote.decimals")
+    }
+
+    fn format_base_value(&self, chain_value: impl Into<u128>) -> Result<UnsignedDecimal, O2Error> {
+        Self::format_chain_value(chain_value, self.base.decimals, "base.decimals")
+    }
+
+    /// Fails with [`O2Error::InvalidRequest`] if `market_id` is known and is
+    /// not this market.
+    fn check_same_market(&self, market_id: Option<&MarketId>, what: &str) -> Result<(), O2Error> {
+        match market_id {
+            Some(id) if *id != self.market_id => Err(O2Error::InvalidRequest(format!(
+                "{what} belongs to market {id}, not {}",
+                self.market_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Scale a human value into chain units and truncate it to `max_precision`.
+    fn scale_human_value(
+        human_value: &UnsignedDecimal,
+        decimals: u32,
+        max_precision: u32,
+        field: &str,
+        label: &str,
+    ) -> Result<u64, O2Error> {
+        let truncate_factor = Self::checked_truncate_factor(decimals, max_precision, field)?;
+        let factor = Decimal::from(Self::checked_pow_u64(decimals, field)?);
+        let scaled = human_value
+            .inner()
+            .checked_mul(factor)
+            .ok_or_else(|| {
+                O2Error::Other(format!(
+                    "Failed to scale {label} '{human_value}': overflow at {decimals} decimals"
+                ))
+            })?
+            .floor()
+            .to_u64()
+            .ok_or_else(|| {
+                O2Error::Other(format!("Failed to scale {label} '{human_value}' into u64"))
+            })?;
+        Ok((scaled / truncate_factor) * truncate_factor)
+    }
+
     /// Convert a chain-scaled price to human-readable.
-    pub fn format_price(&self, chain_value: u64) -> UnsignedDecimal {
-        let factor = 10u64.pow(self.quote.decimals);
-        let d = Decimal::from(chain_value) / Decimal::from(factor);
-        UnsignedDecimal::new(d).unwrap()
+    ///
+    /// Fails if the market's quote decimals exceed what `Decimal` can represent.
+    pub fn format_price(&self, chain_value: u64) -> Result<UnsignedDecimal, O2Error> {
+        self.format_quote_value(chain_value)
     }
 
     /// Convert a human-readable price to chain-scaled integer, truncated to max_precision.
+    ///
+    /// Truncation rounds down, which for a sell can quote below the price you
+    /// meant; use [`Market::round_price`] to choose the direction.
     pub fn scale_price(&self, human_value: &UnsignedDecimal) -> Result<u64, O2Error> {
-        let factor_u64 = Self::checked_pow_u64(self.quote.decimals, "quote.decimals")?;
-        let factor = Decimal::from(factor_u64);
-        let scaled_str = (*human_value.inner() * factor).floor().to_string();
-        let scaled = scaled_str.parse::<u64>().map_err(|e| {
-            O2Error::Other(format!(
-                "Failed to scale price '{}' into u64: {e}",
-                human_value
-            ))
-        })?;
-        let truncate_factor = Self::checked_truncate_factor(
+        Self::scale_human_value(
+            human_value,
             self.quote.decimals,
             self.quote.max_precision,
             "quote precision",
-        )?;
-        Ok((scaled / truncate_factor) * truncate_factor)
+            "price",
+        )
     }
 
-    /// Convert a chain-scaled quantity to human-readable.
-    pub fn format_quantity(&self, chain_value: u64) -> UnsignedDecimal {
-        let factor = 10u64.pow(self.base.decimals);
-        let d = Decimal::from(chain_value) / Decimal::from(factor);
-        UnsignedDecimal::new(d).unwrap()
+    /// Smallest price step, chain-scaled: `10^(quote.decimals - quote.max_precision)`.
+    pub fn price_tick(&self) -> Result<u64, O2Error> {
+        Self::checked_truncate_factor(
+            self.quote.decimals,
+            self.quote.max_precision,
+            "quote precision",
+        )
     }
 
-    /// Convert a human-readable quantity to chain-scaled integer, truncated to max_precision.
-    pub fn scale_quantity(&self, human_value: &UnsignedDecimal) -> Result<u64, O2Error> {
-        let factor_u64 = Self::checked_pow_u64(self.base.decimals, "base.decimals")?;
-        let factor = Decimal::from(factor_u64);
-        let scaled_str = (*human_value.inner() * factor).floor().to_string();
-        let scaled = scaled_str.parse::<u64>().map_err(|e| {
+    /// Smallest price step in human units: `10^-quote.max_precision`.
+    pub fn tick_size(&self) -> Result<UnsignedDecimal, O2Error> {
+        Self::step_size(
+            self.quote.decimals,
+            self.quote.max_precision,
+            "quote precision",
+        )
+    }
+
+    /// Smallest quantity step in human units: `10^-base.max_precision`.
+    pub fn lot_size(&self) -> Result<UnsignedDecimal, O2Error> {
+        Self::step_size(
+            self.base.decimals,
+            self.base.max_precision,
+            "base precision",
+        )
+    }
+
+    fn step_size(
+        decimals: u32,
+        max_precision: u32,
+        field: &str,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        Self::checked_truncate_factor(decimals, max_precision, field)?;
+        let step = Decimal::try_new(1, max_precision).map_err(|e| {
             O2Error::Other(format!(
-                "Failed to scale quantity '{}' into u64: {e}",
-                human_value
+                "Invalid {field}: cannot represent 10^-{max_precision}: {e}"
             ))
         })?;
-        let truncate_factor = Self::checked_truncate_factor(
+        UnsignedDecimal::new(step)
+    }
+
+    /// Round `value` to a multiple of [`tick_size`](Self::tick_size) and bind
+    /// it to this market. A sell rounded [`Up`](RoundingMode::Up) never asks
+    /// less than `value`; a buy rounded [`Down`](RoundingMode::Down) never
+    /// bids more.
+    pub fn round_price(
+        &self,
+        value: UnsignedDecimal,
+        mode: RoundingMode,
+    ) -> Result<Price, O2Error> {
+        self.price_from_decimal(Self::round_to_step(value, self.quote.max_precision, mode)?)
+    }
+
+    /// Round `value` to a multiple of [`lot_size`](Self::lot_size) and bind it
+    /// to this market.
+    pub fn round_quantity(
+        &self,
+        value: UnsignedDecimal,
+        mode: RoundingMode,
+    ) -> Result<Quantity, O2Error> {
+        self.quantity_from_decimal(Self::round_to_step(value, self.base.max_precision, mode)?)
+    }
+
+    fn round_to_step(
+        value: UnsignedDecimal,
+        max_precision: u32,
+        mode: RoundingMode,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        UnsignedDecimal::new(
+            value
+                .inner()
+                .round_dp_with_strategy(max_precision, mode.strategy()),
+        )
+    }
+
+    /// Convert a chain-scaled quantity to human-readable.
+    ///
+    /// Fails if the market's base decimals exceed what `Decimal` can represent.
+    pub fn format_quantity(&self, chain_value: u64) -> Result<UnsignedDecimal, O2Error> {
+        self.format_base_value(chain_value)
+    }
+
+    /// Convert a human-readable quantity to chain-scaled integer, truncated to max_precision.
+    ///
+    /// Use [`Market::round_quantity`] to choose the rounding direction.
+    pub fn scale_quantity(&self, human_value: &UnsignedDecimal) -> Result<u64, O2Error> {
+        Self::scale_human_value(
+            human_value,
             self.base.decimals,
             self.base.max_precision,
             "base precision",
-        )?;
-        Ok((scaled / truncate_factor) * truncate_factor)
+            "quantity",
+        )
     }
 
     /// The symbol pair, e.g. "FUEL/USDC".
@@ -961,6 +1383,105 @@ impl Market {
         Ok(adjusted as u64)
     }
 
+    /// The base quantity that `quote_amount` buys at `price`, both chain-scaled.
+    ///
+    /// Rounds down to the base precision, then through [`Market::adjust_quantity`],
+    /// and fails if the result is below `min_order` or still fractional.
+    pub fn quantity_for_quote(&self, price: u64, quote_amount: u64) -> Result<u64, O2Error> {
+        if price == 0 {
+            return Err(O2Error::InvalidOrderParams(
+                "Price cannot be zero when sizing by quote amount".into(),
+            ));
+        }
+        let base_factor = Self::checked_pow_u128(self.base.decimals, "base.decimals")?;
+        let truncate_factor = Self::checked_truncate_factor(
+            self.base.decimals,
+            self.base.max_precision,
+            "base precision",
+        )?;
+        let quantity =
+            u64::try_from(quote_amount as u128 * base_factor / price as u128).map_err(|_| {
+                O2Error::InvalidOrderParams("Quantity for quote amount exceeds u64 range".into())
+            })?;
+        let quantity = self.adjust_quantity(price, quantity / truncate_factor * truncate_factor)?;
+        let quantity = quantity / truncate_factor * truncate_factor;
+        self.validate_order(price, quantity)?;
+        Ok(quantity)
+    }
+
+    /// `(min_price, max_price)` bounds, chain-scaled, for a `BoundedMarket`
+    /// order that accepts at most `max_slippage_bps` of slippage from
+    /// `reference_price`.
+    ///
+    /// Only the adverse side is bounded: a buy is capped above the reference
+    /// and a sell floored below it. Bounds are rounded inward to the quote
+    /// precision.
+    pub fn slippage_bounds(
+        &self,
+        side: Side,
+        reference_price: u64,
+        max_slippage_bps: u32,
+    ) -> Result<(u64, u64), O2Error> {
+        if reference_price == 0 {
+            return Err(O2Error::InvalidOrderParams(
+                "Reference price cannot be zero".into(),
+            ));
+        }
+        let tick = Self::checked_truncate_factor(
+            self.quote.decimals,
+            self.quote.max_precision,
+            "quote precision",
+        )? as u128;
+        let reference = reference_price as u128;
+        let bps = max_slippage_bps as u128;
+        let ceiling = u64::MAX as u128 / tick * tick;
+        match side {
+            Side::Buy => {
+                let max = reference * (10_000 + bps) / 10_000 / tick * tick;
+                Ok((0, max.min(ceiling) as u64))
+            }
+            Side::Sell => {
+                let min = (reference * 10_000u128.saturating_sub(bps)).div_ceil(10_000);
+                Ok(((min.div_ceil(tick) * tick) as u64, ceiling as u64))
+            }
+        }
+    }
+
+    /// The maker fee at `fees` on `quantity` at `price`, in quote units. Pass
+    /// the account's rates from [`O2Client::my_fees`](crate::O2Client::my_fees);
+    /// see [`FeeRates::fee_for`](crate::fees::FeeRates::fee_for).
+    pub fn maker_fee_for(
+        &self,
+        fees: &FeeRates,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        fees.fee_for(self, price, quantity, true)
+    }
+
+    /// The taker fee at `fees` on `quantity` at `price`, in quote units.
+    pub fn taker_fee_for(
+        &self,
+        fees: &FeeRates,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        fees.fee_for(self, price, quantity, false)
+    }
+
+    /// Quote units a fill nets after fees at `fees`. See
+    /// [`FeeRates::net_proceeds`](crate::fees::FeeRates::net_proceeds).
+    pub fn net_proceeds(
+        &self,
+        fees: &FeeRates,
+        side: Side,
+        price: &UnsignedDecimal,
+        quantity: &UnsignedDecimal,
+        is_maker: bool,
+    ) -> Result<UnsignedDecimal, O2Error> {
+        fees.net_proceeds(self, side, price, quantity, is_maker)
+    }
+
     /// Validate that a price*quantity satisfies min_order and FractionalPrice constraints.
     pub fn validate_order(&self, price: u64, quantity: u64) -> Result<(), O2Error> {
         let base_factor = Self::checked_pow_u128(self.base.decimals, "base.decimals")?;
@@ -996,6 +1517,78 @@ pub struct MarketsResponse {
     pub markets: Vec<Market>,
 }
 
+impl MarketsResponse {
+    /// The registry and access-control contract ids this deployment reports.
+    pub fn registry_ids(&self) -> RegistryIds {
+        RegistryIds {
+            books_registry_id: self.books_registry_id.clone(),
+            books_whitelist_id: self.books_whitelist_id.clone(),
+            books_blacklist_id: self.books_blacklist_id.clone(),
+            accounts_registry_id: self.accounts_registry_id.clone(),
+            trade_account_oracle_id: self.trade_account_oracle_id.clone(),
+        }
+    }
+
+    /// The listed market `market_id` and its order book, if the API lists it.
+    pub fn listed_book(&self, market_id: &MarketId) -> Option<ListedBook> {
+        self.markets
+            .iter()
+            .find(|m| m.market_id == *market_id)
+            .map(|m| ListedBook::new(&self.books_registry_id, m))
+    }
+
+    /// The listed market whose order book contract is `contract_id`.
+    pub fn listed_book_by_contract(&self, contract_id: &ContractId) -> Option<ListedBook> {
+        self.markets
+            .iter()
+            .find(|m| m.contract_id == *contract_id)
+            .map(|m| ListedBook::new(&self.books_registry_id, m))
+    }
+}
+
+/// Registry contract ids as reported by GET /v1/markets.
+///
+/// These are the ids the API is configured with; the SDK does not read the
+/// registry contracts themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryIds {
+    /// Maps market ids to their order book contracts.
+    pub books_registry_id: ContractId,
+    pub books_whitelist_id: Option<ContractId>,
+    pub books_blacklist_id: Option<ContractId>,
+    /// Maps owners to their trade account contracts.
+    pub accounts_registry_id: ContractId,
+    pub trade_account_oracle_id: ContractId,
+}
+
+/// An order book as listed by GET /v1/markets.
+///
+/// This is the API's view of the books registry, not a read of the registry
+/// contract: a book registered on chain but not yet listed will not appear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedBook {
+    pub registry_id: ContractId,
+    pub market_id: MarketId,
+    /// The order book contract registered for the market.
+    pub contract_id: ContractId,
+    pub symbol: MarketSymbol,
+    pub whitelist_id: Option<ContractId>,
+    pub blacklist_id: Option<ContractId>,
+}
+
+impl ListedBook {
+    fn new(registry_id: &ContractId, market: &Market) -> Self {
+        Self {
+            registry_id: registry_id.clone(),
+            market_id: market.market_id.clone(),
+            contract_id: market.contract_id.clone(),
+            symbol: market.symbol_pair(),
+            whitelist_id: market.whitelist_id.clone(),
+            blacklist_id: market.blacklist_id.clone(),
+        }
+    }
+}
+
 /// Market summary from GET /v1/markets/summary.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketSummary {
@@ -1013,7 +1606,7 @@ pub struct MarketSummary {
 }
 
 /// Market ticker from GET /v1/markets/ticker.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MarketTicker {
     pub market_id: MarketId,
     #[serde(default, deserialize_with = "deserialize_optional_u64")]
@@ -1050,6 +1643,16 @@ pub struct MarketTicker {
     pub timestamp: u128,
 }
 
+/// A market's ticker as yielded by
+/// [`O2Client::ticker_stream`](crate::O2Client::ticker_stream).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickerUpdate {
+    pub market_id: MarketId,
+    pub ticker: MarketTicker,
+    /// When the SDK received the ticker.
+    pub seen_timestamp: Timestamp,
+}
+
 // ---------------------------------------------------------------------------
 // Account
 // ---------------------------------------------------------------------------
@@ -1085,6 +1688,13 @@ pub struct SessionInfo {
     pub contract_ids: Vec<ContractId>,
 }
 
+impl SessionInfo {
+    /// Session expiry (`expiry` is in seconds since epoch).
+    pub fn expires_at(&self) -> Timestamp {
+        Timestamp::from_unix_secs(self.expiry)
+    }
+}
+
 /// Response from POST /v1/accounts (create account).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateAccountResponse {
@@ -1119,18 +1729,56 @@ pub struct SessionResponse {
     pub session_expiry: u64,
 }
 
+impl SessionResponse {
+    /// Session expiry (`session_expiry` is in seconds since epoch).
+    pub fn expires_at(&self) -> Timestamp {
+        Timestamp::from_unix_secs(self.session_expiry)
+    }
+}
+
 /// Local session state tracked by the client.
 #[derive(Debug, Clone)]
 pub struct Session {
     pub owner_address: [u8; 32],
-    pub session_private_key: [u8; 32],
+    /// Zeroed when the session is dropped; redacted from `Debug` output.
+    pub session_private_key: PrivateKey,
     pub session_address: [u8; 32],
     pub trade_account_id: TradeAccountId,
     pub contract_ids: Vec<ContractId>,
+    /// Contracts of `contract_ids` whose market has been delisted. See
+    /// [`crate::market_watch`].
+    pub inactive_contract_ids: Vec<ContractId>,
     pub expiry: u64,
     pub nonce: u64,
 }
 
+impl Session {
+    /// True if the session covers `contract_id` and its market is not known
+    /// to be delisted.
+    pub fn is_active(&self, contract_id: &ContractId) -> bool {
+        self.contract_ids.contains(contract_id) && !self.inactive_contract_ids.contains(contract_id)
+    }
+
+    /// Record that the market of `contract_id` is no longer trading.
+    pub(crate) fn mark_inactive(&mut self, contract_id: &ContractId) {
+        if self.contract_ids.contains(contract_id)
+            && !self.inactive_contract_ids.contains(contract_id)
+        {
+            self.inactive_contract_ids.push(contract_id.clone());
+        }
+    }
+
+    /// Session expiry (`expiry` is in seconds since epoch).
+    pub fn expires_at(&self) -> Timestamp {
+        Timestamp::from_unix_secs(self.expiry)
+    }
+
+    /// True if the session has expired as of `now`.
+    pub fn is_expired_at(&self, now: Timestamp) -> bool {
+        now >= self.expires_at()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Orders
 // ---------------------------------------------------------------------------
@@ -1177,6 +1825,252 @@ pub struct Order {
     pub fills: Option<Vec<serde_json::Value>>,
 }
 
+/// Lifecycle state of an [`Order`], derived from its flags and fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderStatus {
+    /// Resting with nothing filled.
+    Open,
+    /// Resting with part of the quantity filled.
+    PartiallyFilled,
+    /// Closed with the full quantity filled.
+    Filled,
+    /// Closed before filling completely, by the owner or because an
+    /// immediate-or-cancel style order could not fill.
+    Cancelled,
+    /// A `Limit` order closed by the exchange after its time-in-force
+    /// timestamp without filling completely.
+    Expired,
+}
+
+impl OrderStatus {
+    /// True for `Open` and `PartiallyFilled`.
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::Open | OrderStatus::PartiallyFilled)
+    }
+
+    /// True for `Filled`, `Cancelled`, and `Expired`.
+    pub fn is_terminal(&self) -> bool {
+        !self.is_open()
+    }
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            OrderStatus::Open => "open",
+            OrderStatus::PartiallyFilled => "partially_filled",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Expired => "expired",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Typed view of an order's `order_type` as returned by the API.
+///
+/// Prices are chain integers. Unlike [`OrderType`] (used when placing orders),
+/// this is parsed from responses and keeps unrecognised shapes in `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderTypeInfo {
+    Spot,
+    Market,
+    FillOrKill,
+    PostOnly,
+    /// `timestamp` is the time-in-force timestamp (sent as Unix seconds).
+    Limit {
+        price: u64,
+        timestamp: Timestamp,
+    },
+    BoundedMarket {
+        max_price: u64,
+        min_price: u64,
+    },
+    Unknown(serde_json::Value),
+}
+
+impl OrderTypeInfo {
+    /// Parse the API's `order_type` JSON.
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        Self::parse(value).unwrap_or_else(|| OrderTypeInfo::Unknown(value.clone()))
+    }
+
+    fn parse(value: &serde_json::Value) -> Option<Self> {
+        use serde_json::Value;
+
+        fn int(v: &Value) -> Option<u64> {
+            match v {
+                Value::String(s) => s.parse().ok(),
+                Value::Number(n) => n.as_u64(),
+                _ => None,
+            }
+        }
+
+        let (name, body) = match value {
+            Value::String(name) => (name.as_str(), None),
+            Value::Object(map) if map.len() == 1 => {
+                let (name, body) = map.iter().next()?;
+                (name.as_str(), Some(body))
+            }
+            _ => return None,
+        };
+        match (name.to_ascii_lowercase().as_str(), body) {
+            ("spot", _) => Some(OrderTypeInfo::Spot),
+            ("market", _) => Some(OrderTypeInfo::Market),
+            ("fillorkill", _) => Some(OrderTypeInfo::FillOrKill),
+            ("postonly", _) => Some(OrderTypeInfo::PostOnly),
+            ("limit", Some(body)) => {
+                let (price, timestamp) = match body {
+                    Value::Array(items) if items.len() == 2 => (int(&items[0])?, int(&items[1])?),
+                    Value::Object(fields) => {
+                        (int(fields.get("price")?)?, int(fields.get("timestamp")?)?)
+                    }
+                    _ => return None,
+                };
+                Some(OrderTypeInfo::Limit {
+                    price,
+                    timestamp: Timestamp::from_unix_secs(timestamp),
+                })
+            }
+            ("boundedmarket", Some(body)) => {
+                let (max_price, min_price) = match body {
+                    Value::Array(items) if items.len() == 2 => (int(&items[0])?, int(&items[1])?),
+                    Value::Object(fields) => (
+                        int(fields.get("max_price")?)?,
+                        int(fields.get("min_price")?)?,
+                    ),
+                    _ => return None,
+                };
+                Some(OrderTypeInfo::BoundedMarket {
+                    max_price,
+                    min_price,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Order {
+    /// A newly placed, unfilled order that exists only in this process, for
+    /// the simulated venues.
+    pub(crate) fn local(
+        order_id: OrderId,
+        market: &Market,
+        side: Side,
+        order_type: &OrderType,
+        price: u64,
+        quantity: u64,
+        placed_at: Timestamp,
+    ) -> Result<Self, O2Error> {
+        Ok(Order {
+            order_id,
+            side,
+            order_type: order_type.to_encoding(market)?.1,
+            quantity,
+            quantity_fill: Some(0),
+            price,
+            price_fill: None,
+            timestamp: Some(serde_json::Value::from(
+                u64::try_from(placed_at.as_unix_millis()).unwrap_or(u64::MAX),
+            )),
+            close: false,
+            partially_filled: false,
+            cancel: false,
+            desired_quantity: None,
+            base_decimals: Some(market.base.decimals),
+            account: None,
+            fill: None,
+            order_tx_history: None,
+            market_id: Some(market.market_id.clone()),
+            owner: None,
+            history: None,
+            fills: None,
+        })
+    }
+
+    /// This order scaled by `market`'s decimals.
+    ///
+    /// Fails with [`O2Error::InvalidRequest`] if the order names a different
+    /// market.
+    pub fn human(&self, market: &Market) -> Result<HumanOrder, O2Error> {
+        market.check_same_market(self.market_id.as_ref(), "Order")?;
+        Ok(HumanOrder {
+            order_id: self.order_id.clone(),
+            side: self.side,
+            price: market.format_price(self.price)?,
+            quantity: market.format_quantity(self.quantity)?,
+            quantity_fill: self
+                .quantity_fill
+                .map(|q| market.format_quantity(q))
+                .transpose()?,
+            price_fill: self
+                .price_fill
+                .map(|p| market.format_price(p))
+                .transpose()?,
+        })
+    }
+
+    /// Order creation time, if the API reported one.
+    ///
+    /// The raw `timestamp` field is untyped and its unit is inferred; see
+    /// [`Timestamp::from_unix_auto`].
+    pub fn created_at(&self) -> Option<Timestamp> {
+        self.timestamp
+            .as_ref()
+            .and_then(|v| Timestamp::deserialize(v).ok())
+    }
+
+    /// Typed `order_type`.
+    pub fn order_type_info(&self) -> OrderTypeInfo {
+        OrderTypeInfo::from_json(&self.order_type)
+    }
+
+    /// True if the full quantity has been filled.
+    pub fn is_fully_filled(&self) -> bool {
+        self.quantity > 0 && self.quantity_fill.unwrap_or(0) >= self.quantity
+    }
+
+    /// Lifecycle state as of now. See [`Order::status_at`].
+    pub fn status(&self) -> OrderStatus {
+        self.status_at(Timestamp::now())
+    }
+
+    /// Lifecycle state as of `now`.
+    ///
+    /// A full fill wins over the close/cancel flags. A `Limit` order that was
+    /// closed by the exchange (not cancelled) without filling, with its
+    /// time-in-force timestamp at or before `now`, is `Expired`. Resting orders
+    /// are never reported as expired; the exchange decides when they close.
+    pub fn status_at(&self, now: Timestamp) -> OrderStatus {
+        if self.is_fully_filled() {
+            return OrderStatus::Filled;
+        }
+        if self.cancel {
+            return OrderStatus::Cancelled;
+        }
+        if self.close {
+            if let OrderTypeInfo::Limit { timestamp, .. } = self.order_type_info() {
+                if timestamp <= now && self.quantity_fill.is_some() {
+                    return OrderStatus::Expired;
+                }
+            }
+            // `close` without `cancel` and without a fill amount is how the API
+            // reports fills when `quantity_fill` is absent.
+            return if self.quantity_fill.is_none() {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::Cancelled
+            };
+        }
+        if self.partially_filled || self.quantity_fill.unwrap_or(0) > 0 {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Open
+        }
+    }
+}
+
 /// Response from GET /v1/orders.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrdersResponse {
@@ -1218,9 +2112,9 @@ pub struct Trade {
     pub quantity: u64,
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub price: u64,
-    /// Trade execution timestamp (milliseconds since epoch).
-    #[serde(deserialize_with = "deserialize_string_or_u128")]
-    pub timestamp: u128,
+    /// Trade execution time (sent as milliseconds since epoch).
+    #[serde(with = "crate::timestamp::unix_millis")]
+    pub timestamp: Timestamp,
     /// The querying account's role. Only present on account-scoped trade queries.
     #[serde(default)]
     pub trader_side: Option<TraderSide>,
@@ -1351,8 +2245,10 @@ pub struct DepthUpdate {
     #[serde(alias = "view")]
     pub view: Option<DepthSnapshot>,
     pub market_id: MarketId,
-    pub onchain_timestamp: Option<String>,
-    pub seen_timestamp: Option<String>,
+    #[serde(default)]
+    pub onchain_timestamp: Option<Timestamp>,
+    #[serde(default)]
+    pub seen_timestamp: Option<Timestamp>,
 }
 
 // ---------------------------------------------------------------------------
@@ -1374,8 +2270,96 @@ pub struct Bar {
     pub buy_volume: u128,
     #[serde(deserialize_with = "deserialize_string_or_u128")]
     pub sell_volume: u128,
-    #[serde(deserialize_with = "deserialize_string_or_u128")]
-    pub timestamp: u128,
+    /// Bar open time (sent as milliseconds since epoch).
+    #[serde(with = "crate::timestamp::unix_millis")]
+    pub timestamp: Timestamp,
+}
+
+// ---------------------------------------------------------------------------
+// Human-readable views
+// ---------------------------------------------------------------------------
+
+/// An [`Order`] with prices and quantities as decimals. See [`Order::human`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanOrder {
+    pub order_id: OrderId,
+    pub side: Side,
+    pub price: UnsignedDecimal,
+    pub quantity: UnsignedDecimal,
+    pub quantity_fill: Option<UnsignedDecimal>,
+    pub price_fill: Option<UnsignedDecimal>,
+}
+
+/// A [`Trade`] with its price and amounts as decimals. See [`Trade::human`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanTrade {
+    pub trade_id: TradeId,
+    /// The maker's order side.
+    pub side: Side,
+    pub price: UnsignedDecimal,
+    pub quantity: UnsignedDecimal,
+    /// Trade value in quote units.
+    pub total: UnsignedDecimal,
+    pub timestamp: Timestamp,
+}
+
+/// A [`DepthLevel`] as decimals. See [`DepthLevel::human`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanDepthLevel {
+    pub price: UnsignedDecimal,
+    pub quantity: UnsignedDecimal,
+}
+
+/// A [`Bar`] as decimals. See [`Bar::human`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanBar {
+    pub open: UnsignedDecimal,
+    pub high: UnsignedDecimal,
+    pub low: UnsignedDecimal,
+    pub close: UnsignedDecimal,
+    /// Volumes in base units.
+    pub buy_volume: UnsignedDecimal,
+    pub sell_volume: UnsignedDecimal,
+    pub timestamp: Timestamp,
+}
+
+impl Trade {
+    /// This trade scaled by `market`'s decimals.
+    pub fn human(&self, market: &Market) -> Result<HumanTrade, O2Error> {
+        Ok(HumanTrade {
+            trade_id: self.trade_id.clone(),
+            side: self.side,
+            price: market.format_price(self.price)?,
+            quantity: market.format_quantity(self.quantity)?,
+            total: market.format_quote_value(self.total)?,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+impl DepthLevel {
+    /// This level scaled by `market`'s decimals.
+    pub fn human(&self, market: &Market) -> Result<HumanDepthLevel, O2Error> {
+        Ok(HumanDepthLevel {
+            price: market.format_price(self.price)?,
+            quantity: market.format_quantity(self.quantity)?,
+        })
+    }
+}
+
+impl Bar {
+    /// This bar scaled by `market`'s decimals.
+    pub fn human(&self, market: &Market) -> Result<HumanBar, O2Error> {
+        Ok(HumanBar {
+            open: market.format_price(self.open)?,
+            high: market.format_price(self.high)?,
+            low: market.format_price(self.low)?,
+            close: market.format_price(self.close)?,
+            buy_volume: market.format_base_value(self.buy_volume)?,
+            sell_volume: market.format_base_value(self.sell_volume)?,
+            timestamp: self.timestamp,
+        })
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -1427,14 +2411,14 @@ pub enum ActionItem {
 
 /// A market-grouped set of actions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct MarketActions {
+pub struct MarketActions {
     pub market_id: MarketId,
     pub actions: Vec<serde_json::Value>,
 }
 
 /// Request body for POST /v1/session/actions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct SessionActionsRequest {
+pub struct SessionActionsRequest {
     pub actions: Vec<MarketActions>,
     pub signature: Signature,
     pub nonce: String,
@@ -1456,12 +2440,21 @@ pub struct SessionActionsResponse {
     pub message: Option<String>,
     pub reason: Option<String>,
     pub receipts: Option<serde_json::Value>,
+    /// Computed by the client for a batch it submitted; never sent by the
+    /// server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ActionAnnotations>,
+    /// Set instead of `tx_id` when the client is in
+    /// [`ClientMode::DryRun`](crate::ClientMode::DryRun).
+    #[serde(skip)]
+    pub dry_run: Option<DryRunBatch>,
 }
 
 impl SessionActionsResponse {
-    /// Returns true if the response indicates success (has tx_id).
+    /// Returns true if the response indicates success (has tx_id), or the
+    /// batch was built and signed in dry-run mode.
     pub fn is_success(&self) -> bool {
-        self.tx_id.is_some()
+        self.tx_id.is_some() || self.dry_run.is_some()
     }
 
     /// Returns true if this is a pre-flight validation error (has code field).
@@ -1475,6 +2468,276 @@ impl SessionActionsResponse {
     }
 }
 
+/// A batch built and signed in [`ClientMode::DryRun`](crate::ClientMode::DryRun)
+/// but not submitted.
+#[derive(Debug, Clone)]
+pub struct DryRunBatch {
+    /// The body that would have been posted to `/v1/session/actions`.
+    pub request: SessionActionsRequest,
+    /// The bytes the session key signed: the nonce followed by the encoded
+    /// calls.
+    pub signing_bytes: Vec<u8>,
+    /// The `O2-Owner-Id` header that would have been sent.
+    pub owner_id: String,
+}
+
+/// Status of a transaction on the Fuel chain, from the network's GraphQL node.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TxStatus {
+    /// The node has not seen the transaction.
+    NotFound,
+    /// In the transaction pool, or preconfirmed, but not yet in a block.
+    Submitted,
+    /// Dropped from the pool without being included. Nothing was executed.
+    Squeezed { reason: String },
+    /// Included in a block and executed.
+    Success {
+        block_height: u64,
+        total_fee: u64,
+        receipts: serde_json::Value,
+    },
+    /// Included in a block, but reverted. The fee was still charged.
+    Reverted {
+        block_height: u64,
+        reason: String,
+        total_fee: u64,
+        receipts: serde_json::Value,
+    },
+}
+
+impl TxStatus {
+    /// Returns true once the status can no longer change.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            Self::Squeezed { .. } | Self::Success { .. } | Self::Reverted { .. }
+        )
+    }
+}
+
+/// `status` of a transaction as returned by the Fuel GraphQL API.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "__typename")]
+pub(crate) enum GraphqlTxStatus {
+    SqueezedOutStatus {
+        reason: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SuccessStatus {
+        block: GraphqlBlock,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        total_fee: u64,
+        receipts: serde_json::Value,
+    },
+    #[serde(rename_all = "camelCase")]
+    FailureStatus {
+        block: GraphqlBlock,
+        reason: String,
+        #[serde(deserialize_with = "deserialize_string_or_u64")]
+        total_fee: u64,
+        receipts: serde_json::Value,
+    },
+    /// Submitted, or preconfirmed by the block producer.
+    #[serde(other)]
+    Pending,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GraphqlBlock {
+    #[serde(deserialize_with = "deserialize_string_or_u64")]
+    height: u64,
+}
+
+impl From<GraphqlTxStatus> for TxStatus {
+    fn from(status: GraphqlTxStatus) -> Self {
+        match status {
+            GraphqlTxStatus::SqueezedOutStatus { reason } => Self::Squeezed { reason },
+            GraphqlTxStatus::SuccessStatus {
+                block,
+                total_fee,
+                receipts,
+            } => Self::Success {
+                block_height: block.height,
+                total_fee,
+                receipts,
+            },
+            GraphqlTxStatus::FailureStatus {
+                block,
+                reason,
+                total_fee,
+                receipts,
+            } => Self::Reverted {
+                block_height: block.height,
+                reason,
+                total_fee,
+                receipts,
+            },
+            GraphqlTxStatus::Pending => Self::Submitted,
+        }
+    }
+}
+
+/// A check the client ran on a batch before signing it. The server's own
+/// checks, such as the price window, are not evaluated client-side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PreTradeCheck {
+    /// The session had not expired.
+    SessionExpiry,
+    /// The batch held at most [`MAX_ACTIONS_PER_BATCH`] actions.
+    BatchSize,
+    /// Every `Limit` expiry was a plausible unix timestamp.
+    LimitExpiry,
+    /// Every order's quote value met the market's `min_order`.
+    MinOrder,
+    /// Every `price * quantity` was a whole number of quote units.
+    FractionalPrice,
+}
+
+/// One order of a submitted batch, as encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderAnnotation {
+    pub market_id: MarketId,
+    pub side: Side,
+    /// Chain-scaled price.
+    pub price: u64,
+    /// Chain-scaled base quantity, after [`Market::adjust_quantity`].
+    pub quantity: u64,
+    /// `price * quantity` in quote chain units.
+    pub notional: u128,
+    /// Mid price of the book when the batch was built, if known.
+    pub mid: Option<u64>,
+    /// Signed distance of `price` from `mid` in basis points; positive is
+    /// above the mid.
+    pub distance_from_mid_bps: Option<i64>,
+}
+
+/// Funds an asset's created orders lock up, in chain units.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceImpact {
+    pub asset_id: AssetId,
+    pub symbol: String,
+    /// Quote notional of buys or base quantity of sells. Fees are not
+    /// included, and funds released by cancels are unknown until they settle.
+    pub locked: u128,
+}
+
+/// Client-side annotations of a submitted batch, for post-trade analytics.
+///
+/// Set on [`SessionActionsResponse::annotations`] by
+/// [`O2Client::batch_actions_multi`](crate::O2Client::batch_actions_multi) and
+/// everything built on it. Mids are only looked up when enabled with
+/// [`O2Client::set_mid_annotations`](crate::O2Client::set_mid_annotations).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionAnnotations {
+    /// Created orders, in submission order.
+    pub orders: Vec<OrderAnnotation>,
+    /// Projected balance impact per asset, in first-seen order.
+    pub balance_impact: Vec<BalanceImpact>,
+    /// Checks the client ran before signing.
+    pub checks: Vec<PreTradeCheck>,
+}
+
+impl ActionAnnotations {
+    /// Total quote notional submitted on `market_id`.
+    pub fn notional(&self, market_id: &MarketId) -> u128 {
+        self.orders
+            .iter()
+            .filter(|o| &o.market_id == market_id)
+            .map(|o| o.notional)
+            .sum()
+    }
+
+    pub(crate) fn record_check(&mut self, check: PreTradeCheck) {
+        if !self.checks.contains(&check) {
+            self.checks.push(check);
+        }
+    }
+
+    pub(crate) fn record_order(
+        &mut self,
+        market: &Market,
+        side: Side,
+        price: u64,
+        quantity: u64,
+        mid: Option<u64>,
+    ) -> Result<(), O2Error> {
+        let base_factor = Market::checked_pow_u128(market.base.decimals, "base.decimals")?;
+        let notional = price as u128 * quantity as u128 / base_factor;
+        let distance_from_mid_bps = mid.filter(|m| *m > 0).map(|m| {
+            ((price as i128 - m as i128) * 10_000 / m as i128)
+                .clamp(i64::MIN as i128, i64::MAX as i128) as i64
+        });
+        self.orders.push(OrderAnnotation {
+            market_id: market.market_id.clone(),
+            side,
+            price,
+            quantity,
+            notional,
+            mid,
+            distance_from_mid_bps,
+        });
+        let (asset, locked) = match side {
+            Side::Buy => (&market.quote, notional),
+            Side::Sell => (&market.base, quantity as u128),
+        };
+        match self
+            .balance_impact
+            .iter_mut()
+            .find(|b| b.asset_id == asset.asset)
+        {
+            Some(impact) => impact.locked += locked,
+            None => self.balance_impact.push(BalanceImpact {
+                asset_id: asset.asset.clone(),
+                symbol: asset.symbol.clone(),
+                locked,
+            }),
+        }
+        Ok(())
+    }
+}
+
+/// Combined result of an action list submitted as several batches by
+/// [`O2Client::batch_actions_chunked`](crate::O2Client::batch_actions_chunked).
+#[derive(Debug)]
+pub struct BatchedActionsResponse {
+    /// Number of batches the actions were split into.
+    pub batches: usize,
+    /// Responses of the batches that were submitted, in order.
+    pub responses: Vec<SessionActionsResponse>,
+    /// What stopped submission, if anything. It belongs to batch
+    /// `responses.len()`; that batch and every later one were not applied.
+    pub error: Option<O2Error>,
+}
+
+impl BatchedActionsResponse {
+    /// True if every batch was submitted and succeeded.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+            && self.responses.len() == self.batches
+            && self
+                .responses
+                .iter()
+                .all(SessionActionsResponse::is_success)
+    }
+
+    /// Transaction ids of the submitted batches, in order.
+    pub fn tx_ids(&self) -> Vec<&TxId> {
+        self.responses
+            .iter()
+            .filter_map(|r| r.tx_id.as_ref())
+            .collect()
+    }
+
+    /// Orders collected across all submitted batches, in order.
+    pub fn orders(&self) -> Vec<&Order> {
+        self.responses
+            .iter()
+            .flat_map(|r| r.orders.iter().flatten())
+            .collect()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Withdraw
 // ---------------------------------------------------------------------------
@@ -1556,10 +2819,10 @@ pub struct AggregatedAssetInfo {
     pub unified_cryptoasset_id: u64,
     pub can_withdraw: bool,
     pub can_deposit: bool,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub min_withdraw: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub min_deposit: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub min_withdraw: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub min_deposit: AggregatedAmount,
     #[serde(deserialize_with = "deserialize_string_or_f64")]
     pub maker_fee: f64,
     #[serde(deserialize_with = "deserialize_string_or_f64")]
@@ -1574,8 +2837,10 @@ pub type AggregatedAssets = BTreeMap<String, AggregatedAssetInfo>;
 pub struct AggregatedOrderbook {
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub timestamp: u64,
-    pub bids: Vec<[f64; 2]>,
-    pub asks: Vec<[f64; 2]>,
+    #[serde(deserialize_with = "deserialize_amount_levels")]
+    pub bids: Vec<[AggregatedAmount; 2]>,
+    #[serde(deserialize_with = "deserialize_amount_levels")]
+    pub asks: Vec<[AggregatedAmount; 2]>,
 }
 
 /// CoinGecko aggregated orderbook from GET /v1/aggregated/coingecko/orderbook.
@@ -1584,8 +2849,10 @@ pub struct CoingeckoAggregatedOrderbook {
     pub ticker_id: String,
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub timestamp: u64,
-    pub bids: Vec<[f64; 2]>,
-    pub asks: Vec<[f64; 2]>,
+    #[serde(deserialize_with = "deserialize_amount_levels")]
+    pub bids: Vec<[AggregatedAmount; 2]>,
+    #[serde(deserialize_with = "deserialize_amount_levels")]
+    pub asks: Vec<[AggregatedAmount; 2]>,
 }
 
 /// Pair summary from GET /v1/aggregated/summary.
@@ -1594,33 +2861,33 @@ pub struct PairSummary {
     pub trading_pairs: String,
     pub base_currency: String,
     pub quote_currency: String,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub last_price: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub lowest_ask: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub highest_bid: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub base_volume: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub quote_volume: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub last_price: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub lowest_ask: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub highest_bid: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub base_volume: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub quote_volume: AggregatedAmount,
     #[serde(deserialize_with = "deserialize_string_or_f64")]
     pub price_change_percent_24h: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub highest_price_24h: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub lowest_price_24h: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub highest_price_24h: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub lowest_price_24h: AggregatedAmount,
 }
 
 /// Aggregated ticker value from GET /v1/aggregated/ticker.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AggregatedTickerData {
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub last_price: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub base_volume: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub quote_volume: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub last_price: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub base_volume: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub quote_volume: AggregatedAmount,
 }
 
 /// Pair-keyed map from GET /v1/aggregated/ticker.
@@ -1632,20 +2899,20 @@ pub struct PairTicker {
     pub ticker_id: String,
     pub base_currency: String,
     pub target_currency: String,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub last_price: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub base_volume: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub target_volume: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub bid: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub ask: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub high: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub low: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub last_price: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub base_volume: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub target_volume: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub bid: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub ask: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub high: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub low: AggregatedAmount,
 }
 
 /// Trade from GET /v1/aggregated/trades.
@@ -1653,12 +2920,12 @@ pub struct PairTicker {
 pub struct AggregatedTrade {
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub trade_id: u64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub price: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub base_volume: f64,
-    #[serde(deserialize_with = "deserialize_string_or_f64")]
-    pub quote_volume: f64,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub price: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub base_volume: AggregatedAmount,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub quote_volume: AggregatedAmount,
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub timestamp: u64,
     #[serde(rename = "type")]
@@ -1675,8 +2942,9 @@ pub struct OrderUpdate {
     pub action: String,
     #[serde(default)]
     pub orders: Vec<Order>,
-    pub onchain_timestamp: Option<String>,
-    pub seen_timestamp: String,
+    #[serde(default)]
+    pub onchain_timestamp: Option<Timestamp>,
+    pub seen_timestamp: Timestamp,
 }
 
 /// WebSocket trade update.
@@ -1686,8 +2954,9 @@ pub struct TradeUpdate {
     #[serde(default)]
     pub trades: Vec<Trade>,
     pub market_id: MarketId,
-    pub onchain_timestamp: Option<String>,
-    pub seen_timestamp: String,
+    #[serde(default)]
+    pub onchain_timestamp: Option<Timestamp>,
+    pub seen_timestamp: Timestamp,
 }
 
 /// WebSocket balance entry.
@@ -1710,8 +2979,9 @@ pub struct BalanceUpdate {
     pub action: String,
     #[serde(default)]
     pub balance: Vec<BalanceEntry>,
-    pub onchain_timestamp: Option<String>,
-    pub seen_timestamp: String,
+    #[serde(default)]
+    pub onchain_timestamp: Option<Timestamp>,
+    pub seen_timestamp: Timestamp,
 }
 
 /// WebSocket nonce update.
@@ -1721,8 +2991,9 @@ pub struct NonceUpdate {
     pub contract_id: TradeAccountId,
     #[serde(deserialize_with = "deserialize_string_or_u64")]
     pub nonce: u64,
-    pub onchain_timestamp: Option<String>,
-    pub seen_timestamp: String,
+    #[serde(default)]
+    pub onchain_timestamp: Option<Timestamp>,
+    pub seen_timestamp: Timestamp,
 }
 
 /// Generic WebSocket message for initial parsing.
@@ -1778,6 +3049,192 @@ mod tests {
         }
     }
 
+    #[test]
+    fn prices_and_quantities_round_to_market_steps() {
+        let market = sample_market();
+        let d = |s: &str| s.parse::<UnsignedDecimal>().unwrap();
+        assert_eq!(market.tick_size().unwrap(), d("0.0001"));
+        assert_eq!(market.lot_size().unwrap(), d("0.001"));
+
+        let price = d("1.23456");
+        assert_eq!(
+            market
+                .round_price(price, RoundingMode::Down)
+                .unwrap()
+                .value(),
+            d("1.2345")
+        );
+        assert_eq!(
+            market.round_price(price, RoundingMode::Up).unwrap().value(),
+            d("1.2346")
+        );
+        assert_eq!(
+            market
+                .round_price(d("1.23454"), RoundingMode::Nearest)
+                .unwrap()
+                .value(),
+            d("1.2345")
+        );
+        assert_eq!(
+            market
+                .round_quantity(d("2.0005"), RoundingMode::Nearest)
+                .unwrap()
+                .value(),
+            d("2.001")
+        );
+        // Already on a step: unchanged in every mode.
+        assert_eq!(
+            market
+                .round_quantity(d("2.5"), RoundingMode::Up)
+                .unwrap()
+                .value(),
+            d("2.5")
+        );
+    }
+
+    #[test]
+    fn action_annotations_sum_notional_and_locked_funds() {
+        let market = sample_market();
+        let mut annotations = ActionAnnotations::default();
+        // Buy 2 at 1.5 against a 1.6 mid, sell 3 at 2.0 with no mid.
+        annotations
+            .record_order(
+                &market,
+                Side::Buy,
+                1_500_000_000,
+                2_000_000_000,
+                Some(1_600_000_000),
+            )
+            .unwrap();
+        annotations
+            .record_order(&market, Side::Sell, 2_000_000_000, 3_000_000_000, None)
+            .unwrap();
+        annotations
+            .record_order(&market, Side::Buy, 1_000_000_000, 1_000_000_000, None)
+            .unwrap();
+        annotations.record_check(PreTradeCheck::MinOrder);
+        annotations.record_check(PreTradeCheck::MinOrder);
+
+        assert_eq!(annotations.orders[0].notional, 3_000_000_000);
+        assert_eq!(annotations.orders[0].distance_from_mid_bps, Some(-625));
+        assert_eq!(annotations.orders[1].distance_from_mid_bps, None);
+        assert_eq!(annotations.notional(&market.market_id), 10_000_000_000);
+        let locked: Vec<(&str, u128)> = annotations
+            .balance_impact
+            .iter()
+            .map(|b| (b.symbol.as_str(), b.locked))
+            .collect();
+        assert_eq!(
+            locked,
+            vec![("QUOTE", 4_000_000_000), ("BASE", 3_000_000_000)]
+        );
+        assert_eq!(annotations.checks, vec![PreTradeCheck::MinOrder]);
+    }
+
+    #[test]
+    fn asset_amounts_scale_by_full_decimals() {
+        let base = sample_market().base;
+        let amount: UnsignedDecimal = "1.234567891".parse().unwrap();
+        // Finer than max_precision (3) but within decimals (9).
+        assert_eq!(base.scale_amount(&amount).unwrap(), 1_234_567_891);
+        assert_eq!(base.format_amount(1_234_567_891).unwrap(), amount);
+        let too_fine: UnsignedDecimal = "0.0000000001".parse().unwrap();
+        assert!(matches!(
+            base.scale_amount(&too_fine),
+            Err(O2Error::InvalidAmount(_))
+        ));
+    }
+
+    #[test]
+    fn listed_books_resolve_by_market_and_contract() {
+        let market = sample_market();
+        let markets = MarketsResponse {
+            books_registry_id: ContractId::new("0x01"),
+            books_whitelist_id: None,
+            books_blacklist_id: None,
+            accounts_registry_id: ContractId::new("0x02"),
+            trade_account_oracle_id: ContractId::new("0x03"),
+            fast_bridge_asset_registry_contract_id: None,
+            chain_id: "0x0".to_string(),
+            base_asset_id: AssetId::new("0x04"),
+            markets: vec![market.clone()],
+        };
+
+        assert_eq!(
+            markets.registry_ids().accounts_registry_id,
+            ContractId::new("0x02")
+        );
+        let book = markets.listed_book(&market.market_id).unwrap();
+        assert_eq!(book.registry_id, ContractId::new("0x01"));
+        assert_eq!(book.contract_id, market.contract_id);
+        assert_eq!(book.symbol, MarketSymbol::new("BASE/QUOTE"));
+        assert_eq!(
+            markets.listed_book_by_contract(&market.contract_id),
+            Some(book)
+        );
+        assert!(markets.listed_book(&MarketId::new("0x09")).is_none());
+    }
+
+    #[test]
+    fn human_views_scale_by_market_decimals() {
+        let market = sample_market();
+        let order: Order = serde_json::from_value(serde_json::json!({
+            "order_id": "0x01",
+            "side": "Buy",
+            "order_type": "Spot",
+            "quantity": "2500000000",
+            "quantity_fill": "500000000",
+            "price": "1250000000",
+            "market_id": market.market_id,
+        }))
+        .unwrap();
+        let human = order.human(&market).unwrap();
+        assert_eq!(human.price.to_string(), "1.25");
+        assert_eq!(human.quantity.to_string(), "2.5");
+        assert_eq!(human.quantity_fill.unwrap().to_string(), "0.5");
+        assert_eq!(human.price_fill, None);
+
+        let mut other = order.clone();
+        other.market_id = Some(MarketId::new("0xbb"));
+        assert!(matches!(
+            other.human(&market),
+            Err(O2Error::InvalidRequest(_))
+        ));
+
+        let trade: Trade = serde_json::from_value(serde_json::json!({
+            "trade_id": "0x02",
+            "side": "Sell",
+            "total": "3125000000",
+            "quantity": "2500000000",
+            "price": "1250000000",
+            "timestamp": 1_700_000_000_000u64,
+        }))
+        .unwrap();
+        assert_eq!(trade.human(&market).unwrap().total.to_string(), "3.125");
+
+        let bar: Bar = serde_json::from_value(serde_json::json!({
+            "open": "1000000000",
+            "high": "1500000000",
+            "low": "900000000",
+            "close": "1100000000",
+            "buy_volume": "40000000000000000000",
+            "sell_volume": "0",
+            "timestamp": 1_700_000_000_000u64,
+        }))
+        .unwrap();
+        let human = bar.human(&market).unwrap();
+        assert_eq!(human.low.to_string(), "0.9");
+        assert_eq!(human.buy_volume.to_string(), "40000000000");
+
+        let level = DepthLevel {
+            price: 1,
+            quantity: 1_000,
+        };
+        let human = level.human(&market).unwrap();
+        assert_eq!(human.price.to_string(), "0.000000001");
+        assert_eq!(human.quantity.to_string(), "0.000001");
+    }
+
     #[test]
     fn market_price_accepts_valid_precision() {
         let market = sample_market();
@@ -1819,7 +3276,9 @@ mod tests {
         let err = market_b
             .validate_quantity_binding(&quantity)
             .expect_err("cross-market quantity must be rejected");
-        assert!(format!("{err}").contains("stale or bound to a different market"));
+        assert!(matches!(err, O2Error::InvalidRequest(_)));
+        assert!(format!("{err}").contains("belongs to market 0x"));
+        assert!(quantity.rebind(&market_b).is_err());
     }
 
     #[test]
@@ -1832,6 +3291,245 @@ mod tests {
         let err = market_b
             .validate_price_binding(&price)
             .expect_err("precision drift should be rejected");
-        assert!(format!("{err}").contains("stale or bound to a different market"));
+        assert!(format!("{err}").contains("quote max_precision changed from 4 to 5"));
+
+        let rebound = price.rebind(&market_b).expect("rebind to finer precision");
+        assert_eq!(rebound.value(), price.value());
+        market_b
+            .validate_price_binding(&rebound)
+            .expect("rebound price should match");
+    }
+
+    #[test]
+    fn market_rebind_reports_lost_precision_and_decimals_change() {
+        let market_a = sample_market();
+        let mut market_b = sample_market();
+        market_b.quote.max_precision = 2;
+        market_b.base.decimals += 3;
+
+        let price = market_a.price("1.2345").expect("price should be valid");
+        let err = price
+            .rebind(&market_b)
+            .expect_err("price no longer representable");
+        assert!(matches!(err, O2Error::InvalidOrderParams(_)));
+        assert!(format!("{err}").contains("quote max_precision was 4, now 2"));
+
+        let quantity = market_a.quantity("1.5").expect("quantity should be valid");
+        let err = market_b
+            .validate_quantity_binding(&quantity)
+            .expect_err("decimals change should be rejected");
+        assert!(format!("{err}").contains("base decimals changed"));
+        let rebound = quantity
+            .rebind(&market_b)
+            .expect("rebind after decimals change");
+        assert_eq!(rebound.base_decimals, market_b.base.decimals);
+    }
+
+    fn order_with(
+        order_type: serde_json::Value,
+        filled: Option<u64>,
+        close: bool,
+        cancel: bool,
+    ) -> Order {
+        let mut json = serde_json::json!({
+            "order_id": "0x01",
+            "side": "buy",
+            "order_type": order_type,
+            "quantity": "100",
+            "price": "1",
+            "close": close,
+            "cancel": cancel,
+        });
+        if let Some(filled) = filled {
+            json["quantity_fill"] = serde_json::json!(filled.to_string());
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn order_type_info_parses_api_shapes() {
+        assert_eq!(
+            OrderTypeInfo::from_json(&serde_json::json!("Spot")),
+            OrderTypeInfo::Spot
+        );
+        assert_eq!(
+            OrderTypeInfo::from_json(&serde_json::json!({ "Limit": ["25000", "1700000000"] })),
+            OrderTypeInfo::Limit {
+                price: 25_000,
+                timestamp: Timestamp::from_unix_secs(1_700_000_000),
+            }
+        );
+        assert_eq!(
+            OrderTypeInfo::from_json(
+                &serde_json::json!({ "BoundedMarket": { "max_price": "9", "min_price": 1 } })
+            ),
+            OrderTypeInfo::BoundedMarket {
+                max_price: 9,
+                min_price: 1,
+            }
+        );
+        let odd = serde_json::json!({ "Twap": {} });
+        assert_eq!(OrderTypeInfo::from_json(&odd), OrderTypeInfo::Unknown(odd));
+    }
+
+    #[test]
+    fn order_status_from_flags_and_fill() {
+        let now = Timestamp::from_unix_secs(2_000);
+        let spot = serde_json::json!("Spot");
+        let status =
+            |filled, close, cancel| order_with(spot.clone(), filled, close, cancel).status_at(now);
+        assert_eq!(status(Some(0), false, false), OrderStatus::Open);
+        assert_eq!(status(Some(40), false, false), OrderStatus::PartiallyFilled);
+        assert_eq!(status(Some(100), true, false), OrderStatus::Filled);
+        assert_eq!(status(None, true, false), OrderStatus::Filled);
+        assert_eq!(status(Some(40), true, false), OrderStatus::Cancelled);
+        assert_eq!(status(Some(40), true, true), OrderStatus::Cancelled);
+        assert_eq!(status(Some(100), true, true), OrderStatus::Filled);
+        assert!(OrderStatus::PartiallyFilled.is_open());
+    }
+
+    #[test]
+    fn order_status_expired_only_once_closed() {
+        let limit = |ts: u64| serde_json::json!({ "Limit": ["1", ts.to_string()] });
+        let now = Timestamp::from_unix_secs(2_000);
+        assert_eq!(
+            order_with(limit(1_000), Some(10), true, false).status_at(now),
+            OrderStatus::Expired
+        );
+        assert_eq!(
+            order_with(limit(1_000), Some(10), false, false).status_at(now),
+            OrderStatus::PartiallyFilled
+        );
+        assert_eq!(
+            order_with(limit(3_000), Some(10), true, false).status_at(now),
+            OrderStatus::Cancelled
+        );
+        assert_eq!(
+            order_with(limit(1_000), Some(0), true, true).status_at(now),
+            OrderStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn limit_constructors_use_unix_seconds() {
+        let price: UnsignedDecimal = "1".parse().unwrap();
+        let at = Timestamp::from_unix_millis(1_700_000_000_999);
+        match OrderType::limit(price, at) {
+            OrderType::Limit { timestamp, .. } => assert_eq!(timestamp, 1_700_000_000),
+            other => panic!("unexpected {other:?}"),
+        }
+        let in_a_minute = OrderType::limit_in(price, std::time::Duration::from_secs(60));
+        assert!(in_a_minute.validate_timestamp(Timestamp::now()).is_ok());
+    }
+
+    #[test]
+    fn good_til_time_expiries_must_be_ahead_and_near() {
+        let price: UnsignedDecimal = "1".parse().unwrap();
+        let now = Timestamp::from_unix_millis(1_700_000_000_400);
+        match OrderType::good_til(price, now + std::time::Duration::from_secs(90), now) {
+            Ok(OrderType::Limit { timestamp, .. }) => assert_eq!(timestamp, 1_700_000_090),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let err = OrderType::good_til(price, now + std::time::Duration::from_millis(500), now)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not after"), "{err}");
+        let err = OrderType::good_til(price, now + LIMIT_TIMESTAMP_WINDOW * 2, now)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("days from now"), "{err}");
+
+        match OrderType::limit_good_for(price, std::time::Duration::from_secs(60), now) {
+            Ok(OrderType::Limit { timestamp, .. }) => assert_eq!(timestamp, 1_700_000_060),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(OrderType::limit_good_for(price, std::time::Duration::ZERO, now).is_err());
+        #[cfg(feature = "chrono")]
+        assert!(
+            OrderType::limit_until(price, Timestamp::from_unix_secs(1).to_datetime(), now).is_err()
+        );
+    }
+
+    #[test]
+    fn limit_timestamp_validation_rejects_wrong_units_and_far_values() {
+        let now = Timestamp::from_unix_secs(1_700_000_000);
+        let limit = |timestamp: u64| OrderType::Limit {
+            price: "1".parse().unwrap(),
+            timestamp,
+        };
+        assert!(limit(1_700_000_000).validate_timestamp(now).is_ok());
+        assert!(limit(1_700_000_000 + 30 * 86_400)
+            .validate_timestamp(now)
+            .is_ok());
+
+        let err = limit(1_700_000_000_000)
+            .validate_timestamp(now)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("milliseconds"), "{err}");
+        let err = limit(1_700_000_000_000_000_000)
+            .validate_timestamp(now)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("nanoseconds"), "{err}");
+        let err = limit(1_700_000_000 - 400 * 86_400)
+            .validate_timestamp(now)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("days from now"), "{err}");
+        assert!(limit(0).validate_timestamp(now).is_ok());
+
+        assert!(OrderType::Spot.validate_timestamp(now).is_ok());
+    }
+
+    #[test]
+    fn u128_volumes_beyond_f64_precision_are_exact() {
+        // 2^53 + 1 is the first integer f64 cannot represent.
+        for volume in [
+            serde_json::json!("9007199254740993"),
+            serde_json::json!(9007199254740993u64),
+        ] {
+            let summary: MarketSummary = serde_json::from_value(serde_json::json!({
+                "market_id": "0x01",
+                "volume_24h": volume,
+                "change_24h": "0",
+            }))
+            .unwrap();
+            assert_eq!(summary.volume_24h, 9_007_199_254_740_993);
+        }
+        let summary: MarketSummary = serde_json::from_value(serde_json::json!({
+            "market_id": "0x01",
+            "volume_24h": "340282366920938463463374607431768211455",
+            "change_24h": 0,
+        }))
+        .unwrap();
+        assert_eq!(summary.volume_24h, u128::MAX);
+    }
+
+    #[cfg(feature = "lossless-amounts")]
+    #[test]
+    fn aggregated_amounts_are_lossless() {
+        let trade: AggregatedTrade = serde_json::from_value(serde_json::json!({
+            "trade_id": "1",
+            "price": "0.000000123456789012",
+            "base_volume": "9007199254740993.123456789",
+            "quote_volume": 9007199254740993u64,
+            "timestamp": 1,
+            "type": "buy",
+        }))
+        .unwrap();
+        assert_eq!(trade.price.to_string(), "0.000000123456789012");
+        assert_eq!(trade.base_volume.to_string(), "9007199254740993.123456789");
+        assert_eq!(trade.quote_volume, Decimal::from(9_007_199_254_740_993u64));
+
+        let book: AggregatedOrderbook = serde_json::from_value(serde_json::json!({
+            "timestamp": 1,
+            "bids": [["0.5", "9007199254740993"]],
+            "asks": [[0.25, 1e3]],
+        }))
+        .unwrap();
+        assert_eq!(book.bids[0][1], Decimal::from(9_007_199_254_740_993u64));
+        assert_eq!(book.asks[0], [Decimal::new(25, 2), Decimal::from(1000)]);
     }
 }
@@ -0,0 +1,515 @@
+//! The account's own resting size at each price level.
+//!
+//! [`MyBook`] tracks one account's open orders on one market, keyed by
+//! `(side, price)`, so a quoting bot can ask how much it has resting at a
+//! price without walking its open orders. It is fed from order stream updates
+//! and periodically replaced by a REST snapshot of the open orders, which
+//! repairs anything the stream missed across a reconnect.
+//! [`MyBook::diff`] compares the resting levels against the levels a bot wants
+//! and returns only the cancels and creates needed to get there, so levels that
+//! already match are left alone. [`LiveMyBook`] does the bookkeeping on a
+//! background task.
+//!
+//! Prices and sizes are chain-scaled integers, matching [`Order`].
+//!
+//! ```rust,ignore
+//! let mine = client.my_book(&session.trade_account_id, "fFUEL/fUSDC", Duration::from_secs(30)).await?;
+//! let diff = mine.read().diff(Side::Buy, &[(bid, size)]);
+//! for order_id in &diff.cancel { /* cancel */ }
+//! for &(price, quantity) in &diff.create { /* create */ }
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::sync::watch;
+
+use crate::api::O2Api;
+use crate::errors::O2Error;
+use crate::models::{MarketId, Order, OrderId, OrderUpdate, Side, TradeAccountId};
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+// ---------------------------------------------------------------------------
+// MyBook
+// ---------------------------------------------------------------------------
+
+/// One of the account's resting orders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MyOrder {
+    pub order_id: OrderId,
+    pub side: Side,
+    pub price: u64,
+    /// Unfilled quantity.
+    pub remaining: u64,
+}
+
+/// The cancels and creates that move one side of a [`MyBook`] to a target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelDiff {
+    /// Orders to cancel: every order at a level that is dropped or resized.
+    pub cancel: Vec<OrderId>,
+    /// `(price, quantity)` orders to create.
+    pub create: Vec<(u64, u64)>,
+    /// Target levels already resting at exactly the wanted size.
+    pub unchanged: usize,
+}
+
+impl LevelDiff {
+    /// True if the side already matches the target.
+    pub fn is_empty(&self) -> bool {
+        self.cancel.is_empty() && self.create.is_empty()
+    }
+}
+
+/// An account's open orders on a single market, aggregated by price level.
+///
+/// Updates for other markets are ignored, since the order stream carries every
+/// market the account trades. Orders that are filled, cancelled or closed are
+/// removed.
+#[derive(Debug, Clone)]
+pub struct MyBook {
+    market_id: MarketId,
+    orders: HashMap<OrderId, MyOrder>,
+    bids: BTreeMap<u64, u64>,
+    asks: BTreeMap<u64, u64>,
+}
+
+impl MyBook {
+    /// Create an empty book for `market_id`.
+    pub fn new(market_id: MarketId) -> Self {
+        Self {
+            market_id,
+            orders: HashMap::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    /// The market this book tracks.
+    pub fn market_id(&self) -> &MarketId {
+        &self.market_id
+    }
+
+    /// Apply every order in a stream update. Returns true if anything changed.
+    pub fn apply(&mut self, update: &OrderUpdate) -> bool {
+        let mut changed = false;
+        for order in &update.orders {
+            changed |= self.apply_order(order);
+        }
+        changed
+    }
+
+    /// Apply the latest state of one order. Returns true if anything changed.
+    ///
+    /// An order without a `market_id` is only accepted if it is already tracked.
+    pub fn apply_order(&mut self, order: &Order) -> bool {
+        match &order.market_id {
+            Some(market_id) if *market_id != self.market_id => return false,
+            None if !self.orders.contains_key(&order.order_id) => return false,
+            _ => {}
+        }
+        let next = resting(order);
+        if self.orders.get(&order.order_id) == next.as_ref() {
+            return false;
+        }
+        self.remove(&order.order_id);
+        if let Some(next) = next {
+            self.insert(next);
+        }
+        true
+    }
+
+    /// Replace the book with a REST snapshot of the account's open orders on
+    /// this market. The snapshot must hold every page: an order missing from
+    /// it is dropped from the book. Returns true if anything changed.
+    pub fn reconcile(&mut self, open_orders: &[Order]) -> bool {
+        let next: HashMap<OrderId, MyOrder> = open_orders
+            .iter()
+            .filter(|o| o.market_id.as_ref().map_or(true, |m| *m == self.market_id))
+            .filter_map(resting)
+            .map(|o| (o.order_id.clone(), o))
+            .collect();
+        if next == self.orders {
+            return false;
+        }
+        self.clear();
+        for order in next.into_values() {
+            self.insert(order);
+        }
+        true
+    }
+
+    /// Remove every order.
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Total resting size at `price` on `side`, zero if none.
+    pub fn size_at(&self, side: Side, price: u64) -> u64 {
+        self.levels_for(side).get(&price).copied().unwrap_or(0)
+    }
+
+    /// The orders resting at `price` on `side`.
+    pub fn orders_at(&self, side: Side, price: u64) -> Vec<&MyOrder> {
+        self.orders
+            .values()
+            .filter(|o| o.side == side && o.price == price)
+            .collect()
+    }
+
+    /// `(price, size)` levels on `side`, best first.
+    pub fn levels(&self, side: Side) -> Vec<(u64, u64)> {
+        let levels = self.levels_for(side).iter().map(|(&p, &q)| (p, q));
+        match side {
+            Side::Buy => levels.rev().collect(),
+            Side::Sell => levels.collect(),
+        }
+    }
+
+    /// Total resting size on `side`.
+    pub fn total(&self, side: Side) -> u64 {
+        self.levels_for(side).values().sum()
+    }
+
+    /// A tracked order by id.
+    pub fn order(&self, order_id: &OrderId) -> Option<&MyOrder> {
+        self.orders.get(order_id)
+    }
+
+    /// Every tracked order, in no particular order.
+    pub fn orders(&self) -> impl Iterator<Item = &MyOrder> + '_ {
+        self.orders.values()
+    }
+
+    /// Number of resting orders.
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// True if nothing is resting.
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// What to cancel and create so `side` rests exactly `targets`, given as
+    /// `(price, size)` pairs. A target with size zero, or a resting level
+    /// without a target, is cancelled. A level whose total size differs from
+    /// its target is requoted as a whole; one that matches is left alone.
+    pub fn diff(&self, side: Side, targets: &[(u64, u64)]) -> LevelDiff {
+        let mut wanted: BTreeMap<u64, u64> = BTreeMap::new();
+        for &(price, size) in targets {
+            *wanted.entry(price).or_insert(0) += size;
+        }
+
+        let mut diff = LevelDiff::default();
+        for (&price, &size) in self.levels_for(side) {
+            if wanted.get(&price) == Some(&size) {
+                wanted.remove(&price);
+                diff.unchanged += 1;
+                continue;
+            }
+            let mut ids: Vec<OrderId> = self
+                .orders_at(side, price)
+                .into_iter()
+                .map(|o| o.order_id.clone())
+                .collect();
+            ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+            diff.cancel.extend(ids);
+        }
+        diff.create = wanted.into_iter().filter(|&(_, size)| size > 0).collect();
+        diff
+    }
+
+    fn levels_for(&self, side: Side) -> &BTreeMap<u64, u64> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    fn insert(&mut self, order: MyOrder) {
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        *levels.entry(order.price).or_insert(0) += order.remaining;
+        self.orders.insert(order.order_id.clone(), order);
+    }
+
+    fn remove(&mut self, order_id: &OrderId) {
+        let Some(order) = self.orders.remove(order_id) else {
+            return;
+        };
+        let levels = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(size) = levels.get_mut(&order.price) {
+            *size = size.saturating_sub(order.remaining);
+            if *size == 0 {
+                levels.remove(&order.price);
+            }
+        }
+    }
+}
+
+/// The resting part of `order`, or `None` if nothing of it rests on the book.
+fn resting(order: &Order) -> Option<MyOrder> {
+    if order.status().is_terminal() {
+        return None;
+    }
+    let remaining = order
+        .quantity
+        .saturating_sub(order.quantity_fill.unwrap_or(0));
+    (remaining > 0).then(|| MyOrder {
+        order_id: order.order_id.clone(),
+        side: order.side,
+        price: order.price,
+        remaining,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// LiveMyBook
+// ---------------------------------------------------------------------------
+
+/// A [`MyBook`] kept up to date by a background task.
+///
+/// The task applies order stream updates as they arrive and replaces the book
+/// with a REST snapshot of every page of the open orders on start and every
+/// `reconcile_every`. A snapshot taken while a stream update is in flight can
+/// briefly disagree with it; the next update or reconcile settles it.
+/// [`LiveMyBook::subscribe`] yields a version counter that bumps on every
+/// change. The task stops when the stream ends and is aborted when the
+/// `LiveMyBook` is dropped.
+pub struct LiveMyBook {
+    book: Arc<RwLock<MyBook>>,
+    version_rx: watch::Receiver<u64>,
+    last_error: Arc<RwLock<Option<String>>>,
+    task: Task<()>,
+}
+
+impl LiveMyBook {
+    /// Start maintaining `trade_account_id`'s book on `market_id` from
+    /// `stream`, reconciling through `api`.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(
+        api: O2Api,
+        market_id: MarketId,
+        trade_account_id: TradeAccountId,
+        mut stream: TypedStream<OrderUpdate>,
+        reconcile_every: Duration,
+    ) -> Self {
+        let book = Arc::new(RwLock::new(MyBook::new(market_id.clone())));
+        let last_error = Arc::new(RwLock::new(None));
+        let (version_tx, version_rx) = watch::channel(0u64);
+
+        let task_book = book.clone();
+        let task_error = last_error.clone();
+        let task = runtime::spawn(async move {
+            let mut reconcile = runtime::interval(reconcile_every);
+            loop {
+                tokio::select! {
+                    item = stream.next() => match item {
+                        Some(Ok(update)) => {
+                            if write_lock(&task_book).apply(&update) {
+                                version_tx.send_modify(|v| *v = v.wrapping_add(1));
+                            }
+                        }
+                        Some(Err(e)) => {
+                            debug!("my_book.live stream_error error={}", e);
+                            if matches!(e, O2Error::WebSocketDisconnected(_)) {
+                                reconcile.reset_immediately();
+                            }
+                            set_error(&task_error, e.to_string());
+                        }
+                        None => break,
+                    },
+                    _ = reconcile.tick() => {
+                        let result = api.all_open_orders(&market_id, &trade_account_id).await;
+                        match result {
+                            Ok(orders) => {
+                                if write_lock(&task_book).reconcile(&orders) {
+                                    debug!(
+                                        "my_book.live reconciled market_id={} orders={}",
+                                        market_id,
+                                        orders.len()
+                                    );
+                                    version_tx.send_modify(|v| *v = v.wrapping_add(1));
+                                }
+                            }
+                            Err(e) => {
+                                debug!("my_book.live reconcile_error error={}", e);
+                                set_error(&task_error, e.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            debug!("my_book.live stream_ended");
+        });
+
+        Self {
+            book,
+            version_rx,
+            last_error,
+            task,
+        }
+    }
+
+    /// Borrow the current book. Hold the guard briefly; updates block while it lives.
+    pub fn read(&self) -> RwLockReadGuard<'_, MyBook> {
+        self.book.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Clone the current book.
+    pub fn snapshot(&self) -> MyBook {
+        self.read().clone()
+    }
+
+    /// Total resting size at `price` on `side`.
+    pub fn size_at(&self, side: Side, price: u64) -> u64 {
+        self.read().size_at(side, price)
+    }
+
+    /// See [`MyBook::diff`].
+    pub fn diff(&self, side: Side, targets: &[(u64, u64)]) -> LevelDiff {
+        self.read().diff(side, targets)
+    }
+
+    /// A receiver whose value increments each time the book changes.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version_rx.clone()
+    }
+
+    /// The most recent stream or reconcile error, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+
+    /// True while the background task is still running.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for LiveMyBook {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn write_lock(book: &RwLock<MyBook>) -> std::sync::RwLockWriteGuard<'_, MyBook> {
+    book.write().unwrap_or_else(|p| p.into_inner())
+}
+
+fn set_error(slot: &RwLock<Option<String>>, error: String) {
+    *slot.write().unwrap_or_else(|p| p.into_inner()) = Some(error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mock_api::MockResponse, mock_transport::MockTransport};
+    use crate::websocket::TypedStream;
+
+    fn order(id: &str, market: &str, side: &str, price: u64, qty: u64, filled: u64) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": side,
+            "order_type": "Spot",
+            "quantity": qty.to_string(),
+            "quantity_fill": filled.to_string(),
+            "price": price.to_string(),
+            "market_id": market,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn tracks_size_per_level_and_diffs_targets() {
+        let mut book = MyBook::new(MarketId::new("0xaa"));
+        assert!(book.apply_order(&order("0x01", "0xaa", "Buy", 100, 10, 0)));
+        assert!(book.apply_order(&order("0x02", "0xaa", "Buy", 100, 5, 0)));
+        assert!(book.apply_order(&order("0x03", "0xaa", "Buy", 99, 7, 0)));
+        assert!(book.apply_order(&order("0x04", "0xaa", "Sell", 101, 4, 0)));
+        assert!(!book.apply_order(&order("0x05", "0xbb", "Buy", 100, 9, 0)));
+        assert!(!book.apply_order(&order("0x01", "0xaa", "Buy", 100, 10, 0)));
+        assert_eq!(book.size_at(Side::Buy, 100), 15);
+        assert_eq!(book.levels(Side::Buy), vec![(100, 15), (99, 7)]);
+
+        // Partial fill shrinks the level; a full fill removes the order.
+        assert!(book.apply_order(&order("0x01", "0xaa", "Buy", 100, 10, 6)));
+        assert_eq!(book.size_at(Side::Buy, 100), 9);
+        assert!(book.apply_order(&order("0x04", "0xaa", "Sell", 101, 4, 4)));
+        assert_eq!(book.total(Side::Sell), 0);
+        assert_eq!(book.len(), 3);
+
+        let diff = book.diff(Side::Buy, &[(100, 9), (99, 3), (98, 2)]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.cancel, vec![OrderId::new("0x03")]);
+        assert_eq!(diff.create, vec![(98, 2), (99, 3)]);
+        assert!(book.diff(Side::Buy, &[(100, 9), (99, 7)]).is_empty());
+
+        // A REST snapshot replaces the whole book.
+        assert!(book.reconcile(&[order("0x06", "0xaa", "Sell", 102, 3, 0)]));
+        assert_eq!(book.levels(Side::Buy), vec![]);
+        assert_eq!(book.levels(Side::Sell), vec![(102, 3)]);
+        assert!(!book.reconcile(&[order("0x06", "0xaa", "Sell", 102, 3, 0)]));
+    }
+
+    #[tokio::test]
+    async fn live_book_reconciles_every_page_of_open_orders() {
+        // Orders 0..n on market 0xaa, newest first, with the cursor fields.
+        let page = |from: usize, to: usize| {
+            let orders: Vec<_> = (from..to)
+                .map(|i| {
+                    serde_json::json!({
+                        "order_id": format!("0x{i:064x}"),
+                        "side": "Buy",
+                        "order_type": "Spot",
+                        "quantity": "1",
+                        "price": (1_000 + i).to_string(),
+                        "market_id": "0xaa",
+                        "timestamp": (10_000 - i).to_string(),
+                    })
+                })
+                .collect();
+            MockResponse::json(serde_json::json!({
+                "identity": { "ContractId": "0xacc" },
+                "market_id": "0xaa",
+                "orders": orders,
+            }))
+        };
+        let mock = MockTransport::new();
+        mock.script("GET", "/v1/orders", [page(0, 200), page(200, 203)]);
+        let (_tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let live = LiveMyBook::spawn(
+            mock.api(),
+            MarketId::new("0xaa"),
+            TradeAccountId::new("0xacc"),
+            TypedStream::from_receiver(rx),
+            Duration::from_secs(3600),
+        );
+        let mut version = live.subscribe();
+        tokio::time::timeout(Duration::from_secs(5), version.wait_for(|v| *v > 0))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(live.read().len(), 203);
+        assert_eq!(live.size_at(Side::Buy, 1_000), 1);
+        assert_eq!(live.size_at(Side::Buy, 1_202), 1);
+        assert_eq!(mock.requests_to("GET", "/v1/orders").len(), 2);
+    }
+}
@@ -0,0 +1,821 @@
+//! Local nonce sequencing for concurrent submitters.
+//!
+//! Every action batch is signed over the trade account's current nonce, and the
+//! nonce is shared by all sessions on the account. [`O2Client::batch_actions`]
+//! bumps `session.nonce` optimistically, so two tasks submitting with clones of
+//! the same session sign over the same nonce and one of them is rejected.
+//!
+//! A [`NonceManager`] owns the nonce for one trade account. Submissions made
+//! through it are queued and sent one at a time (in arrival order), each signed
+//! over the locally assigned nonce. When fed a `stream_nonce` subscription it
+//! also tracks the confirmed on-chain nonce and re-syncs if another writer
+//! (a different process, the web UI) has advanced it.
+//!
+//! ```rust,ignore
+//! let nonces = client.nonce_manager(&session).await?;
+//!
+//! // In each task, with its own client and session clone:
+//! let nonces = nonces.clone();
+//! nonces.batch_actions(&mut client, &mut session, "fFUEL/fUSDC", actions, true).await?;
+//! ```
+//!
+//! When several processes trade one account, each has its own manager and their
+//! nonces still collide. A manager counts the nonces other writers consume in
+//! [`NonceManager::contention`], and reports a submission that was rejected on
+//! chain because another writer had used its nonce as
+//! [`O2Error::NonceContention`]. To stop the collisions, give every process's
+//! manager the same [`NonceLock`], e.g. one backed by Redis or a database row;
+//! submissions then take turns across processes:
+//!
+//! ```rust,ignore
+//! let nonces = client.nonce_manager(&session).await?.with_lock(RedisNonceLock::new(redis));
+//! ```
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+
+use crate::client::O2Client;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, IntoMarketSymbol, NonceUpdate, Session, SessionActionsResponse, TradeAccountId,
+};
+use crate::receipts::O2RevertReason;
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+#[derive(Debug, Default)]
+struct NonceState {
+    /// Nonce the next submission will sign over. `None` until first synced.
+    next: Option<u64>,
+    /// Latest nonce reported by the nonce stream.
+    confirmed: Option<u64>,
+    /// Set when the stream reports a nonce ahead of `next`.
+    diverged: bool,
+    /// Set while a submission signed over `next` is in flight, so the stream
+    /// confirming it is not taken for another writer.
+    in_flight: bool,
+    resyncs: u64,
+    contention: ContentionStats,
+}
+
+/// How often other writers got in the way of a [`NonceManager`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentionStats {
+    /// Times the nonce stream reported a nonce ahead of the local sequence.
+    pub external_advances: u64,
+    /// Nonces the stream showed other writers consuming.
+    pub external_nonces: u64,
+    /// Submissions that failed because another writer had used their nonce.
+    pub contended_failures: u64,
+}
+
+/// A lock shared by every process submitting for a trade account.
+///
+/// While a [`NonceManager`] holds it, it re-syncs the nonce, signs and submits,
+/// so processes sharing the lock never sign over the same nonce. A process can
+/// die while holding the lock, so implementations should let it expire. If a
+/// submission is dropped while holding the lock, `unlock` runs on a spawned
+/// task.
+pub trait NonceLock: Send + Sync + 'static {
+    /// Wait until this process holds the lock for `trade_account_id`.
+    fn lock<'a>(
+        &'a self,
+        trade_account_id: &'a TradeAccountId,
+    ) -> BoxFuture<'a, Result<(), O2Error>>;
+
+    /// Release the lock taken by [`NonceLock::lock`].
+    fn unlock<'a>(&'a self, trade_account_id: &'a TradeAccountId) -> BoxFuture<'a, ()>;
+}
+
+#[derive(Debug)]
+struct Inner {
+    trade_account_id: TradeAccountId,
+    state: Mutex<NonceState>,
+    /// Held for the duration of a submission; waiters queue in FIFO order.
+    submit: tokio::sync::Mutex<()>,
+    queued: AtomicUsize,
+    tracker: Mutex<Option<Task<()>>>,
+    lock: Mutex<Option<Arc<dyn NonceLock>>>,
+}
+
+impl std::fmt::Debug for dyn NonceLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NonceLock")
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(task) = self
+            .tracker
+            .get_mut()
+            .unwrap_or_else(|p| p.into_inner())
+            .take()
+        {
+            task.abort();
+        }
+    }
+}
+
+/// Counts a submission in [`NonceManager::queued`] until dropped, so a
+/// cancelled submission leaves the count too.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn enter(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks a submission in flight until dropped. A submission dropped before it
+/// [finished](InFlight::finish) may or may not have used its nonce, so the
+/// next one re-syncs first.
+struct InFlight<'a> {
+    state: &'a Mutex<NonceState>,
+    finished: bool,
+}
+
+impl<'a> InFlight<'a> {
+    fn start(state: &'a Mutex<NonceState>) -> Self {
+        state.lock().unwrap_or_else(|p| p.into_inner()).in_flight = true;
+        Self {
+            state,
+            finished: false,
+        }
+    }
+
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        state.in_flight = false;
+        if !self.finished {
+            state.diverged = true;
+        }
+    }
+}
+
+/// Holds a [`NonceLock`] until [released](Held::release) or dropped. A
+/// submission dropped while holding it releases it on a background task, as
+/// `unlock` cannot be awaited in `drop`.
+struct Held {
+    lock: Option<Arc<dyn NonceLock>>,
+    trade_account_id: TradeAccountId,
+}
+
+impl Held {
+    async fn release(mut self) {
+        if let Some(lock) = self.lock.take() {
+            lock.unlock(&self.trade_account_id).await;
+        }
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        if let Some(lock) = self.lock.take() {
+            let trade_account_id = self.trade_account_id.clone();
+            debug!(
+                "nonce.lock released_on_drop trade_account_id={}",
+                trade_account_id
+            );
+            drop(runtime::spawn(async move {
+                lock.unlock(&trade_account_id).await;
+            }));
+        }
+    }
+}
+
+/// Serializes action submissions for one trade account and assigns nonces locally.
+///
+/// Cloning is cheap; clones share the same sequence and queue.
+#[derive(Debug, Clone)]
+pub struct NonceManager {
+    inner: Arc<Inner>,
+}
+
+impl NonceManager {
+    /// Create a manager for `session`'s trade account, seeded with `session.nonce`.
+    pub fn new(session: &Session) -> Self {
+        let manager = Self::unsynced(session.trade_account_id.clone());
+        manager.lock_state().next = Some(session.nonce);
+        manager
+    }
+
+    /// Create a manager that fetches the nonce from the API before its first submission.
+    pub fn unsynced(trade_account_id: TradeAccountId) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                trade_account_id,
+                state: Mutex::new(NonceState::default()),
+                submit: tokio::sync::Mutex::new(()),
+                queued: AtomicUsize::new(0),
+                tracker: Mutex::new(None),
+                lock: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Take `lock` around every submission, so managers in other processes
+    /// sharing it take turns with this one. Applies to all clones.
+    ///
+    /// Each submission then re-syncs the nonce from the API first, since
+    /// another process may have used it since this one's last turn.
+    pub fn with_lock(self, lock: impl NonceLock) -> Self {
+        *self.inner.lock.lock().unwrap_or_else(|p| p.into_inner()) = Some(Arc::new(lock));
+        self
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, NonceState> {
+        self.inner.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// The trade account this manager sequences.
+    pub fn trade_account_id(&self) -> &TradeAccountId {
+        &self.inner.trade_account_id
+    }
+
+    /// Nonce the next submission will use, if synced.
+    pub fn next_nonce(&self) -> Option<u64> {
+        self.lock_state().next
+    }
+
+    /// Latest nonce confirmed by the nonce stream, if tracking.
+    pub fn confirmed_nonce(&self) -> Option<u64> {
+        self.lock_state().confirmed
+    }
+
+    /// Number of submissions sent but not yet confirmed by the nonce stream.
+    ///
+    /// Always zero when not tracking a stream.
+    pub fn unconfirmed(&self) -> u64 {
+        let state = self.lock_state();
+        match (state.next, state.confirmed) {
+            (Some(next), Some(confirmed)) => next.saturating_sub(confirmed),
+            _ => 0,
+        }
+    }
+
+    /// Number of submissions waiting for their turn, including the one in flight.
+    pub fn queued(&self) -> usize {
+        self.inner.queued.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the local nonce was replaced from the API or stream.
+    pub fn resyncs(&self) -> u64 {
+        self.lock_state().resyncs
+    }
+
+    /// Nonce use by other writers seen so far.
+    pub fn contention(&self) -> ContentionStats {
+        self.lock_state().contention
+    }
+
+    /// Follow a nonce stream for this trade account.
+    ///
+    /// Replaces any stream previously passed in. If the stream reports a nonce
+    /// ahead of the local sequence, the next submission re-syncs from the API
+    /// first. See [`O2Client::nonce_manager`] for a manager that is already
+    /// tracking.
+    pub fn track(&self, stream: TypedStream<NonceUpdate>) {
+        let weak = Arc::downgrade(&self.inner);
+        let mut stream = stream;
+        let task = runtime::spawn(async move {
+            while let Some(item) = stream.next().await {
+                let Some(inner) = weak.upgrade() else {
+                    break;
+                };
+                let update = match item {
+                    Ok(update) => update,
+                    Err(e) => {
+                        debug!("nonce.track stream_error={}", e);
+                        continue;
+                    }
+                };
+                if update.contract_id != inner.trade_account_id {
+                    continue;
+                }
+                NonceManager { inner }.observe(update.nonce);
+            }
+        });
+        let previous = self
+            .inner
+            .tracker
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .replace(task);
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Record a nonce reported by the exchange.
+    fn observe(&self, nonce: u64) {
+        let mut state = self.lock_state();
+        let previous = state.confirmed;
+        state.confirmed = Some(previous.map_or(nonce, |c| c.max(nonce)));
+        let expected = state.next.map(|next| next + u64::from(state.in_flight));
+        if let Some(next) = expected.filter(|next| nonce > *next) {
+            debug!(
+                "nonce.observe diverged trade_account_id={} local={} remote={}",
+                self.inner.trade_account_id, next, nonce
+            );
+            state.diverged = true;
+            let seen = previous.map_or(next, |c| c.max(next));
+            state.contention.external_advances += 1;
+            state.contention.external_nonces += nonce.saturating_sub(seen);
+        }
+    }
+
+    /// Replace the local nonce with the one reported by the API.
+    ///
+    /// Waits for any in-flight submission to finish first.
+    pub async fn resync(&self, client: &O2Client) -> Result<u64, O2Error> {
+        let _turn = self.inner.submit.lock().await;
+        self.resync_locked(client).await
+    }
+
+    async fn resync_locked(&self, client: &O2Client) -> Result<u64, O2Error> {
+        let nonce = client
+            .get_nonce(self.inner.trade_account_id.as_str())
+            .await?;
+        let mut state = self.lock_state();
+        if state.next != Some(nonce) {
+            state.resyncs += 1;
+        }
+        state.next = Some(nonce);
+        state.diverged = false;
+        Ok(nonce)
+    }
+
+    /// Submit a batch of actions for a single market through the queue.
+    ///
+    /// See [`O2Client::batch_actions`].
+    pub async fn batch_actions<M>(
+        &self,
+        client: &mut O2Client,
+        session: &mut Session,
+        market_name: M,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol,
+    {
+        let market_name = market_name.into_market_symbol()?;
+        self.batch_actions_multi(client, session, &[(market_name, actions)], collect_orders)
+            .await
+    }
+
+    /// Submit a batch of actions across markets through the queue.
+    ///
+    /// The session's nonce is overwritten with the managed one before signing,
+    /// and reflects the managed sequence when this returns. `session` must belong
+    /// to this manager's trade account.
+    pub async fn batch_actions_multi<M>(
+        &self,
+        client: &mut O2Client,
+        session: &mut Session,
+        market_actions: &[(M, Vec<Action>)],
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol + Clone,
+    {
+        if session.trade_account_id != self.inner.trade_account_id {
+            return Err(O2Error::InvalidSession(format!(
+                "Session trade account {} does not match nonce manager account {}",
+                session.trade_account_id, self.inner.trade_account_id
+            )));
+        }
+
+        let _queued = Queued::enter(&self.inner.queued);
+        let _turn = self.inner.submit.lock().await;
+
+        let lock = self
+            .inner
+            .lock
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone();
+        let Some(lock) = lock else {
+            return self
+                .submit_locked(client, session, market_actions, collect_orders, false)
+                .await;
+        };
+        lock.lock(&self.inner.trade_account_id).await?;
+        let held = Held {
+            lock: Some(lock),
+            trade_account_id: self.inner.trade_account_id.clone(),
+        };
+        let result = self
+            .submit_locked(client, session, market_actions, collect_orders, true)
+            .await;
+        held.release().await;
+        result
+    }
+
+    /// Submit once this manager's turn has come; `always_sync` re-syncs first.
+    async fn submit_locked<M>(
+        &self,
+        client: &mut O2Client,
+        session: &mut Session,
+        market_actions: &[(M, Vec<Action>)],
+        collect_orders: bool,
+        always_sync: bool,
+    ) -> Result<SessionActionsResponse, O2Error>
+    where
+        M: IntoMarketSymbol + Clone,
+    {
+        let needs_sync = always_sync || {
+            let state = self.lock_state();
+            state.next.is_none() || state.diverged
+        };
+        if needs_sync {
+            self.resync_locked(client).await?;
+        }
+
+        let nonce = self.lock_state().next.unwrap_or_default();
+        debug!(
+            "nonce.batch_actions_multi trade_account_id={} nonce={}",
+            self.inner.trade_account_id, nonce
+        );
+        session.nonce = nonce;
+        let in_flight = InFlight::start(&self.inner.state);
+        // batch_actions_multi advances the nonce on success and re-fetches it on
+        // failure, so the session holds the right next value either way.
+        let result = client
+            .batch_actions_multi(session, market_actions, collect_orders)
+            .await;
+        in_flight.finish();
+        let mut state = self.lock_state();
+        if result.is_err() && session.nonce != nonce + 1 {
+            state.resyncs += 1;
+        }
+        state.next = Some(session.nonce);
+        // Our own failed batch consumes at most one nonce. Beyond that, or once
+        // the stream has shown another writer, a stale-nonce revert means the
+        // nonce went elsewhere. Any other failure may have landed, so it is
+        // returned as is.
+        match result {
+            Err(e)
+                if e.revert_reason() == Some(O2RevertReason::InvalidNonce)
+                    && (session.nonce > nonce + 1 || state.diverged) =>
+            {
+                debug!(
+                    "nonce.batch_actions_multi contention trade_account_id={} nonce={} current={}",
+                    self.inner.trade_account_id, nonce, session.nonce
+                );
+                state.contention.contended_failures += 1;
+                Err(O2Error::NonceContention {
+                    nonce,
+                    current: session.nonce,
+                    source: Box::new(e),
+                })
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, Market, MarketAsset, MarketId, MarketsResponse};
+    use crate::test_util::{mock_api::MockResponse, mock_transport::MockTransport};
+    use tokio::sync::mpsc;
+
+    fn session(nonce: u64) -> Session {
+        Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [2u8; 32],
+            trade_account_id: TradeAccountId::new("0x01"),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce,
+        }
+    }
+
+    fn nonce_update(account: &str, nonce: u64) -> NonceUpdate {
+        NonceUpdate {
+            action: "subscribe_nonce".into(),
+            contract_id: TradeAccountId::new(account),
+            nonce,
+            onchain_timestamp: None,
+            seen_timestamp: crate::timestamp::Timestamp::UNIX_EPOCH,
+        }
+    }
+
+    /// Counts how often it was taken and how many holders it has.
+    #[derive(Default)]
+    struct CountingLock {
+        held: Arc<AtomicUsize>,
+        taken: Arc<AtomicUsize>,
+    }
+    impl NonceLock for CountingLock {
+        fn lock<'a>(&'a self, _: &'a TradeAccountId) -> BoxFuture<'a, Result<(), O2Error>> {
+            Box::pin(async move {
+                self.held.fetch_add(1, Ordering::SeqCst);
+                self.taken.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        }
+        fn unlock<'a>(&'a self, _: &'a TradeAccountId) -> BoxFuture<'a, ()> {
+            Box::pin(async move {
+                self.held.fetch_sub(1, Ordering::SeqCst);
+            })
+        }
+    }
+
+    #[test]
+    fn seeds_from_session() {
+        let manager = NonceManager::new(&session(7));
+        assert_eq!(manager.next_nonce(), Some(7));
+        assert_eq!(manager.confirmed_nonce(), None);
+        assert_eq!(manager.unconfirmed(), 0);
+        assert!(NonceManager::unsynced(TradeAccountId::new("0x01"))
+            .next_nonce()
+            .is_none());
+    }
+
+    #[test]
+    fn observe_flags_divergence_only_when_remote_is_ahead() {
+        let manager = NonceManager::new(&session(10));
+        manager.observe(8);
+        assert_eq!(manager.unconfirmed(), 2);
+        assert!(!manager.lock_state().diverged);
+        manager.observe(12);
+        assert!(manager.lock_state().diverged);
+        // Confirmed nonce never goes backwards.
+        manager.observe(9);
+        assert_eq!(manager.confirmed_nonce(), Some(12));
+    }
+
+    #[test]
+    fn observe_counts_nonces_used_by_other_writers() {
+        let manager = NonceManager::new(&session(10));
+        // The stream confirming our own in-flight submission is not contention.
+        manager.lock_state().in_flight = true;
+        manager.observe(11);
+        assert_eq!(manager.contention(), ContentionStats::default());
+        manager.lock_state().in_flight = false;
+
+        manager.observe(13);
+        manager.observe(14);
+        assert_eq!(
+            manager.contention(),
+            ContentionStats {
+                external_advances: 2,
+                external_nonces: 3,
+                contended_failures: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn lock_is_released_when_submission_fails() {
+        let lock = CountingLock::default();
+        let (held, taken) = (lock.held.clone(), lock.taken.clone());
+        let manager = NonceManager::new(&session(3)).with_lock(lock);
+        let mut client = O2Client::with_config(crate::config::NetworkConfig {
+            api_base: "http://127.0.0.1:9".into(),
+            ws_url: "ws://127.0.0.1:9".into(),
+            fuel_rpc: "http://127.0.0.1:9".into(),
+            faucet_url: None,
+            whitelist_required: false,
+            chain_id: None,
+            api_base_fallbacks: Vec::new(),
+            ws_url_fallbacks: Vec::new(),
+            failover: Default::default(),
+        });
+        // Under a lock the nonce is re-synced first, which fails here.
+        let result = manager
+            .batch_actions(&mut client, &mut session(3), "fFUEL/fUSDC", vec![], false)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(taken.load(Ordering::SeqCst), 1);
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn track_ignores_other_accounts() {
+        let manager = NonceManager::new(&session(3));
+        let (tx, rx) = mpsc::unbounded_channel();
+        manager.track(TypedStream::from_receiver(rx));
+        tx.send(Ok(nonce_update("0x02", 50))).unwrap();
+        tx.send(Ok(nonce_update("0x01", 3))).unwrap();
+        drop(tx);
+        for _ in 0..50 {
+            if manager.confirmed_nonce().is_some() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(manager.confirmed_nonce(), Some(3));
+        assert!(!manager.lock_state().diverged);
+    }
+
+    #[tokio::test]
+    async fn rejects_session_from_other_account() {
+        let manager = NonceManager::unsynced(TradeAccountId::new("0x02"));
+        let mut client = MockTransport::new().client();
+        let mut s = session(0);
+        let err = manager
+            .batch_actions(&mut client, &mut s, "fFUEL/fUSDC", vec![], false)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, O2Error::InvalidSession(_)));
+        assert_eq!(manager.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_submissions_leave_the_queue() {
+        let manager = NonceManager::unsynced(TradeAccountId::new("0x01"));
+        let mut client = MockTransport::new().client();
+        let mut s = session(0);
+
+        // Another submission holds the turn; this one gives up waiting.
+        let turn = manager.inner.submit.lock().await;
+        let waiting = manager.batch_actions(&mut client, &mut s, "fFUEL/fUSDC", vec![], false);
+        let timed_out = tokio::time::timeout(std::time::Duration::from_millis(10), waiting).await;
+        assert!(timed_out.is_err());
+        assert_eq!(manager.queued(), 0);
+        drop(turn);
+    }
+
+    fn hex(n: u64) -> String {
+        format!("0x{n:064x}")
+    }
+
+    /// Market `fFUEL/fUSDC` and trade account `hex(5)` at `account_nonce`.
+    fn mock_account(account_nonce: &str) -> MockTransport {
+        let markets = MarketsResponse {
+            books_registry_id: ContractId::new(hex(6)),
+            books_whitelist_id: None,
+            books_blacklist_id: None,
+            accounts_registry_id: ContractId::new(hex(2)),
+            trade_account_oracle_id: ContractId::new(hex(7)),
+            fast_bridge_asset_registry_contract_id: None,
+            chain_id: "0x0".into(),
+            base_asset_id: AssetId::new(hex(8)),
+            markets: vec![Market {
+                contract_id: ContractId::new(hex(1)),
+                market_id: MarketId::new(hex(0xaa)),
+                whitelist_id: None,
+                blacklist_id: None,
+                maker_fee: 0,
+                taker_fee: 0,
+                min_order: 0,
+                dust: 0,
+                price_window: 0,
+                base: MarketAsset {
+                    symbol: "fFUEL".into(),
+                    asset: AssetId::new(hex(3)),
+                    decimals: 9,
+                    max_precision: 3,
+                },
+                quote: MarketAsset {
+                    symbol: "fUSDC".into(),
+                    asset: AssetId::new(hex(4)),
+                    decimals: 6,
+                    max_precision: 4,
+                },
+            }],
+        };
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/markets", serde_json::to_value(markets).unwrap());
+        mock.fixture(
+            "GET",
+            "/v1/accounts",
+            serde_json::json!({
+                "trade_account_id": hex(5),
+                "trade_account": { "nonce": account_nonce, "owner": { "Address": hex(9) } },
+                "session": null,
+            }),
+        );
+        mock
+    }
+
+    /// A session on the mocked trade account.
+    fn mock_session(nonce: u64) -> Session {
+        let mut s = session(nonce);
+        s.trade_account_id = TradeAccountId::new(hex(5));
+        s
+    }
+
+    fn cancel() -> Vec<Action> {
+        vec![Action::CancelOrder {
+            order_id: crate::models::OrderId::new(hex(0x0d)),
+        }]
+    }
+
+    #[tokio::test]
+    async fn dropped_submission_releases_the_lock_and_resyncs() {
+        let mock = mock_account("3");
+        // The first submission never gets an answer.
+        mock.script(
+            "POST",
+            "/v1/session/actions",
+            [
+                MockResponse::hang(),
+                MockResponse::json(serde_json::json!({ "tx_id": "0xfeed" })),
+            ],
+        );
+        let mut client = mock.client();
+        let mut s = mock_session(3);
+        let lock = CountingLock::default();
+        let (held, taken) = (lock.held.clone(), lock.taken.clone());
+        let manager = NonceManager::new(&s).with_lock(lock);
+
+        let pending = manager.batch_actions(&mut client, &mut s, "fFUEL/fUSDC", cancel(), false);
+        let timed_out = tokio::time::timeout(std::time::Duration::from_millis(20), pending).await;
+        assert!(timed_out.is_err());
+        assert_eq!(mock.requests_to("POST", "/v1/session/actions").len(), 1);
+        {
+            let state = manager.lock_state();
+            assert!(!state.in_flight);
+            assert!(state.diverged);
+        }
+        for _ in 0..50 {
+            if held.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+
+        let resp = manager
+            .batch_actions(&mut client, &mut s, "fFUEL/fUSDC", cancel(), false)
+            .await
+            .unwrap();
+        assert!(resp.is_success());
+        assert_eq!(taken.load(Ordering::SeqCst), 2);
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+        assert_eq!(manager.next_nonce(), Some(4));
+        assert!(!manager.lock_state().diverged);
+    }
+
+    #[tokio::test]
+    async fn only_stale_nonce_reverts_are_contention() {
+        // Another writer has moved the account from nonce 3 to 6.
+        let mock = mock_account("6");
+        mock.script(
+            "POST",
+            "/v1/session/actions",
+            [
+                MockResponse::status_json(502, serde_json::json!({ "error": "bad gateway" })),
+                MockResponse::json(serde_json::json!({
+                    "message": "Failed to process transaction",
+                    "reason": "NonceError::InvalidNonce",
+                })),
+            ],
+        );
+        let mut client = mock.client();
+
+        // A failure that may have landed is returned as is.
+        let mut s = mock_session(3);
+        let manager = NonceManager::new(&s);
+        let err = manager
+            .batch_actions(&mut client, &mut s, "fFUEL/fUSDC", cancel(), false)
+            .await
+            .unwrap_err();
+        assert!(!matches!(err, O2Error::NonceContention { .. }), "{err:?}");
+        assert_eq!(manager.contention().contended_failures, 0);
+
+        // A stale-nonce revert did not land, and keeps the revert as its source.
+        let mut s = mock_session(3);
+        let manager = NonceManager::new(&s);
+        let err = manager
+            .batch_actions(&mut client, &mut s, "fFUEL/fUSDC", cancel(), false)
+            .await
+            .unwrap_err();
+        let O2Error::NonceContention {
+            nonce,
+            current,
+            source,
+        } = err
+        else {
+            panic!("expected contention, got {err:?}");
+        };
+        assert_eq!((nonce, current), (3, 6));
+        assert_eq!(source.revert_reason(), Some(O2RevertReason::InvalidNonce));
+        assert_eq!(manager.contention().contended_failures, 1);
+        assert_eq!(manager.next_nonce(), Some(6));
+    }
+}
@@ -186,6 +186,36 @@ fn variant_to_qualified(variant: &str) -> Option<String> {
     None
 }
 
+/// The ABI error `(fully-qualified enum name, variant)` logged with `log_id`
+/// and variant index `discriminant`.
+pub(crate) fn abi_error(log_id: u64, discriminant: u64) -> Option<(&'static str, &'static str)> {
+    let (_, enum_name, variants) = ABI_ERROR_ENUMS.iter().find(|(id, _, _)| *id == log_id)?;
+    let (variant, _) = variants.get(usize::try_from(discriminant).ok()?)?;
+    Some((enum_name, variant))
+}
+
+/// The ABI error a reason string starts with, either `EnumShortName::Variant`
+/// as produced by [`augment_revert_reason`] or a bare variant name.
+pub(crate) fn abi_error_by_name(text: &str) -> Option<(&'static str, &'static str)> {
+    let name = text.split_whitespace().next()?;
+    let (short, variant) = match name.rsplit_once("::") {
+        Some((path, variant)) => (Some(path.rsplit("::").next().unwrap_or(path)), variant),
+        None => (None, name),
+    };
+    ABI_ERROR_ENUMS
+        .iter()
+        .find_map(|&(_, enum_name, variants)| {
+            let enum_short = enum_name.rsplit("::").next().unwrap_or(enum_name);
+            if short.is_some_and(|s| s != enum_short) {
+                return None;
+            }
+            variants
+                .iter()
+                .find(|(v, _)| *v == variant)
+                .map(|(v, _)| (enum_name, *v))
+        })
+}
+
 // ---------------------------------------------------------------------------
 // Extraction helpers
 // ---------------------------------------------------------------------------
@@ -0,0 +1,723 @@
+//! Local order book maintained from depth stream updates.
+//!
+//! [`OrderBook`] is a plain data structure that applies `view` snapshots and
+//! `changes` deltas from [`DepthUpdate`] messages. [`LiveOrderBook`] drives an
+//! `OrderBook` from a [`TypedStream<DepthUpdate>`] on a background task so bots
+//! can read the current top of book without merging updates by hand.
+//! [`BboStream`] reduces the same updates to best bid/offer changes for
+//! consumers that only need the top of book.
+//!
+//! For execution logic, [`OrderBook`] also derives the volume-weighted mid,
+//! the bid/ask imbalance over the top levels, the depth available up to a
+//! limit price and the [`MarketImpact`] of taking a given quantity.
+//!
+//! Prices and quantities are chain-scaled integers, matching [`DepthLevel`].
+//! Use [`Market::format_price`](crate::Market::format_price) /
+//! [`Market::format_quantity`](crate::Market::format_quantity) to convert.
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::task::{Context, Poll};
+
+use futures_util::{Stream, StreamExt};
+use rust_decimal::Decimal;
+use tokio::sync::watch;
+
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{DepthLevel, DepthSnapshot, DepthUpdate, MarketId, Side};
+use crate::runtime::{self, Task};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+// ---------------------------------------------------------------------------
+// OrderBook
+// ---------------------------------------------------------------------------
+
+/// An in-memory order book for a single market.
+///
+/// A `view` in an update replaces the whole book; `changes` are applied as
+/// per-level deltas where a zero quantity removes the level. Updates for other
+/// markets are ignored, since the shared WebSocket fans depth messages out to
+/// every depth subscriber.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    market_id: MarketId,
+    bids: BTreeMap<u64, u64>,
+    asks: BTreeMap<u64, u64>,
+    synced: bool,
+    last_onchain_timestamp: Option<Timestamp>,
+}
+
+impl OrderBook {
+    /// Create an empty, unsynced book for `market_id`.
+    pub fn new(market_id: MarketId) -> Self {
+        Self {
+            market_id,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            synced: false,
+            last_onchain_timestamp: None,
+        }
+    }
+
+    /// The market this book tracks.
+    pub fn market_id(&self) -> &MarketId {
+        &self.market_id
+    }
+
+    /// True once a full snapshot has been applied.
+    ///
+    /// Deltas received before the first snapshot are still applied, but the
+    /// book may be missing levels until then.
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// On-chain timestamp of the most recent applied update, if the server sent one.
+    pub fn last_onchain_timestamp(&self) -> Option<Timestamp> {
+        self.last_onchain_timestamp
+    }
+
+    /// Apply a depth update. Returns `false` if it belongs to another market.
+    pub fn apply(&mut self, update: &DepthUpdate) -> bool {
+        if !update
+            .market_id
+            .as_str()
+            .eq_ignore_ascii_case(self.market_id.as_str())
+        {
+            return false;
+        }
+        if let Some(view) = &update.view {
+            self.apply_snapshot(view);
+        }
+        if let Some(changes) = &update.changes {
+            self.apply_changes(changes);
+        }
+        if update.onchain_timestamp.is_some() {
+            self.last_onchain_timestamp = update.onchain_timestamp;
+        }
+        true
+    }
+
+    /// Replace the book contents with a full snapshot.
+    pub fn apply_snapshot(&mut self, snapshot: &DepthSnapshot) {
+        self.bids.clear();
+        self.asks.clear();
+        Self::merge_levels(&mut self.bids, &snapshot.bids);
+        Self::merge_levels(&mut self.asks, &snapshot.asks);
+        self.synced = true;
+    }
+
+    /// Apply per-level deltas. A zero quantity removes the level.
+    pub fn apply_changes(&mut self, changes: &DepthSnapshot) {
+        Self::merge_levels(&mut self.bids, &changes.bids);
+        Self::merge_levels(&mut self.asks, &changes.asks);
+    }
+
+    /// Drop all levels and mark the book as needing a fresh snapshot.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.synced = false;
+    }
+
+    fn merge_levels(side: &mut BTreeMap<u64, u64>, levels: &[DepthLevel]) {
+        for level in levels {
+            if level.quantity == 0 {
+                side.remove(&level.price);
+            } else {
+                side.insert(level.price, level.quantity);
+            }
+        }
+    }
+
+    /// Highest bid level.
+    pub fn best_bid(&self) -> Option<DepthLevel> {
+        self.bids.iter().next_back().map(Self::level)
+    }
+
+    /// Lowest ask level.
+    pub fn best_ask(&self) -> Option<DepthLevel> {
+        self.asks.iter().next().map(Self::level)
+    }
+
+    /// Midpoint of best bid and best ask, in chain units.
+    ///
+    /// Returned as a decimal because the midpoint of two ticks can fall on a half unit.
+    pub fn mid_price(&self) -> Option<UnsignedDecimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let sum = Decimal::from(bid.price) + Decimal::from(ask.price);
+        UnsignedDecimal::new(sum / Decimal::TWO).ok()
+    }
+
+    /// Best ask minus best bid, in chain units. `None` if either side is empty
+    /// or the book is crossed.
+    pub fn spread(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        ask.price.checked_sub(bid.price)
+    }
+
+    /// Bid levels from best (highest) to worst.
+    pub fn bids(&self) -> impl Iterator<Item = DepthLevel> + '_ {
+        self.bids.iter().rev().map(Self::level)
+    }
+
+    /// Ask levels from best (lowest) to worst.
+    pub fn asks(&self) -> impl Iterator<Item = DepthLevel> + '_ {
+        self.asks.iter().map(Self::level)
+    }
+
+    /// Number of bid levels.
+    pub fn bid_depth(&self) -> usize {
+        self.bids.len()
+    }
+
+    /// Number of ask levels.
+    pub fn ask_depth(&self) -> usize {
+        self.asks.len()
+    }
+
+    /// Best bid and offer, stamped with the latest on-chain timestamp (or the
+    /// local clock if the server has not sent one).
+    pub fn bbo(&self) -> Bbo {
+        let bid = self.best_bid();
+        let ask = self.best_ask();
+        Bbo {
+            bid: bid.as_ref().map(|l| l.price),
+            bid_size: bid.map_or(0, |l| l.quantity),
+            ask: ask.as_ref().map(|l| l.price),
+            ask_size: ask.map_or(0, |l| l.quantity),
+            ts: self.last_onchain_timestamp.unwrap_or_else(Timestamp::now),
+        }
+    }
+
+    /// Copy the top `levels` of each side into a [`DepthSnapshot`].
+    pub fn to_snapshot(&self, levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: self.bids().take(levels).collect(),
+            asks: self.asks().take(levels).collect(),
+        }
+    }
+
+    /// Midpoint weighted by the opposite side's size at the top of book (the
+    /// micro-price): `(bid * ask_size + ask * bid_size) / (bid_size + ask_size)`.
+    /// Leans toward the side more likely to trade through next.
+    pub fn weighted_mid_price(&self) -> Option<UnsignedDecimal> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let weighted =
+            bid.price as u128 * ask.quantity as u128 + ask.price as u128 * bid.quantity as u128;
+        let size = bid.quantity as u128 + ask.quantity as u128;
+        UnsignedDecimal::new(ratio(weighted, size)).ok()
+    }
+
+    /// `(bid volume - ask volume) / (bid volume + ask volume)` over the top
+    /// `levels` of each side, from -1 (only asks) to 1 (only bids). `None` if
+    /// both are empty.
+    pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
+        let bids: u128 = self.bids().take(levels).map(|l| l.quantity as u128).sum();
+        let asks: u128 = self.asks().take(levels).map(|l| l.quantity as u128).sum();
+        if bids + asks == 0 {
+            return None;
+        }
+        Some((Decimal::from(bids) - Decimal::from(asks)) / Decimal::from(bids + asks))
+    }
+
+    /// Quantity a `side` order limited at `price` could take right now: asks at
+    /// or below `price` for a buy, bids at or above it for a sell.
+    pub fn depth_to_price(&self, side: Side, price: u64) -> u128 {
+        let levels: Box<dyn Iterator<Item = (&u64, &u64)>> = match side {
+            Side::Buy => Box::new(self.asks.range(..=price)),
+            Side::Sell => Box::new(self.bids.range(price..)),
+        };
+        levels.map(|(_, quantity)| *quantity as u128).sum()
+    }
+
+    /// What taking `quantity` with a `side` order would cost, walking the
+    /// opposite side from the best level. `None` if that side is empty or
+    /// `quantity` is zero.
+    pub fn market_impact(&self, side: Side, quantity: u64) -> Option<MarketImpact> {
+        if quantity == 0 {
+            return None;
+        }
+        let mut levels: Box<dyn Iterator<Item = DepthLevel> + '_> = match side {
+            Side::Buy => Box::new(self.asks()),
+            Side::Sell => Box::new(self.bids()),
+        };
+        let best = levels.next()?;
+        let mut impact = MarketImpact {
+            side,
+            requested: quantity,
+            filled: 0,
+            levels: 0,
+            best_price: best.price,
+            worst_price: best.price,
+            average_price: UnsignedDecimal::ZERO,
+            slippage_bps: Decimal::ZERO,
+        };
+        let mut notional: u128 = 0;
+        for level in std::iter::once(best).chain(levels) {
+            let take = level.quantity.min(quantity - impact.filled);
+            notional += level.price as u128 * take as u128;
+            impact.filled += take;
+            impact.levels += 1;
+            impact.worst_price = level.price;
+            if impact.filled == quantity {
+                break;
+            }
+        }
+        let average = ratio(notional, impact.filled as u128);
+        impact.average_price = UnsignedDecimal::new(average).ok()?;
+        let best = Decimal::from(impact.best_price);
+        if !best.is_zero() {
+            let adverse = match side {
+                Side::Buy => average - best,
+                Side::Sell => best - average,
+            };
+            impact.slippage_bps = adverse / best * Decimal::from(10_000);
+        }
+        Some(impact)
+    }
+
+    fn level((price, quantity): (&u64, &u64)) -> DepthLevel {
+        DepthLevel {
+            price: *price,
+            quantity: *quantity,
+        }
+    }
+}
+
+/// `numerator / denominator` for a quotient that fits in a `u64`, without
+/// overflowing `Decimal` on the intermediate products.
+fn ratio(numerator: u128, denominator: u128) -> Decimal {
+    let whole = Decimal::from((numerator / denominator) as u64);
+    whole + Decimal::from(numerator % denominator) / Decimal::from(denominator)
+}
+
+/// The result of walking the book with [`OrderBook::market_impact`]. Prices
+/// are in chain units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketImpact {
+    pub side: Side,
+    pub requested: u64,
+    /// Quantity the book can fill; less than `requested` if it runs out.
+    pub filled: u64,
+    /// Price levels touched.
+    pub levels: usize,
+    pub best_price: u64,
+    /// Price of the last level touched, the limit a sweep would need.
+    pub worst_price: u64,
+    /// Volume-weighted average fill price.
+    pub average_price: UnsignedDecimal,
+    /// How much worse `average_price` is than `best_price`, in basis points.
+    pub slippage_bps: Decimal,
+}
+
+impl MarketImpact {
+    /// Whether the book holds the whole `requested` quantity.
+    pub fn is_complete(&self) -> bool {
+        self.filled == self.requested
+    }
+}
+
+// ---------------------------------------------------------------------------
+// LiveOrderBook
+// ---------------------------------------------------------------------------
+
+/// An [`OrderBook`] kept up to date by a background task reading a depth stream.
+///
+/// Read the book with [`LiveOrderBook::read`] or the top-of-book shortcuts.
+/// [`LiveOrderBook::subscribe`] yields a version counter that bumps on every
+/// applied update. After a reconnect the server re-sends a `view` snapshot on
+/// resubscribe, which resets the book. The task stops when the stream ends and
+/// is aborted when the `LiveOrderBook` is dropped.
+pub struct LiveOrderBook {
+    book: Arc<RwLock<OrderBook>>,
+    version_rx: watch::Receiver<u64>,
+    last_error: Arc<RwLock<Option<String>>>,
+    task: Task<()>,
+}
+
+impl LiveOrderBook {
+    /// Start maintaining a book for `market_id` from `stream`.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(market_id: MarketId, mut stream: TypedStream<DepthUpdate>) -> Self {
+        let book = Arc::new(RwLock::new(OrderBook::new(market_id)));
+        let last_error = Arc::new(RwLock::new(None));
+        let (version_tx, version_rx) = watch::channel(0u64);
+
+        let task_book = book.clone();
+        let task_error = last_error.clone();
+        let task = runtime::spawn(async move {
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(update) => {
+                        let applied = write_lock(&task_book).apply(&update);
+                        if applied {
+                            version_tx.send_modify(|v| *v = v.wrapping_add(1));
+                        }
+                    }
+                    Err(e) => {
+                        debug!("orderbook.live stream_error error={}", e);
+                        if matches!(e, O2Error::WebSocketDisconnected(_)) {
+                            write_lock(&task_book).clear();
+                            version_tx.send_modify(|v| *v = v.wrapping_add(1));
+                        }
+                        *task_error.write().unwrap_or_else(|p| p.into_inner()) =
+                            Some(e.to_string());
+                    }
+                }
+            }
+            debug!("orderbook.live stream_ended");
+        });
+
+        Self {
+            book,
+            version_rx,
+            last_error,
+            task,
+        }
+    }
+
+    /// Borrow the current book. Hold the guard briefly; updates block while it lives.
+    pub fn read(&self) -> RwLockReadGuard<'_, OrderBook> {
+        self.book.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Clone the current book.
+    pub fn snapshot(&self) -> OrderBook {
+        self.read().clone()
+    }
+
+    /// Highest bid level.
+    pub fn best_bid(&self) -> Option<DepthLevel> {
+        self.read().best_bid()
+    }
+
+    /// Lowest ask level.
+    pub fn best_ask(&self) -> Option<DepthLevel> {
+        self.read().best_ask()
+    }
+
+    /// Midpoint of best bid and best ask, in chain units.
+    pub fn mid_price(&self) -> Option<UnsignedDecimal> {
+        self.read().mid_price()
+    }
+
+    /// Best ask minus best bid, in chain units.
+    pub fn spread(&self) -> Option<u64> {
+        self.read().spread()
+    }
+
+    /// See [`OrderBook::weighted_mid_price`].
+    pub fn weighted_mid_price(&self) -> Option<UnsignedDecimal> {
+        self.read().weighted_mid_price()
+    }
+
+    /// See [`OrderBook::imbalance`].
+    pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
+        self.read().imbalance(levels)
+    }
+
+    /// See [`OrderBook::depth_to_price`].
+    pub fn depth_to_price(&self, side: Side, price: u64) -> u128 {
+        self.read().depth_to_price(side, price)
+    }
+
+    /// See [`OrderBook::market_impact`].
+    pub fn market_impact(&self, side: Side, quantity: u64) -> Option<MarketImpact> {
+        self.read().market_impact(side, quantity)
+    }
+
+    /// True once a full snapshot has been applied.
+    pub fn is_synced(&self) -> bool {
+        self.read().is_synced()
+    }
+
+    /// A receiver whose value increments each time the book changes.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version_rx.clone()
+    }
+
+    /// The current value of the [`subscribe`](Self::subscribe) counter.
+    pub fn version(&self) -> u64 {
+        *self.version_rx.borrow()
+    }
+
+    /// The most recent stream error, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone()
+    }
+
+    /// True while the background task is still consuming the stream.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for LiveOrderBook {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// BBO
+// ---------------------------------------------------------------------------
+
+/// Best bid and offer. Prices and sizes are chain-scaled integers; a missing
+/// side has no price and a size of zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bbo {
+    pub bid: Option<u64>,
+    pub bid_size: u64,
+    pub ask: Option<u64>,
+    pub ask_size: u64,
+    pub ts: Timestamp,
+}
+
+impl Bbo {
+    /// True if prices and sizes match, ignoring the timestamp.
+    pub fn same_top(&self, other: &Bbo) -> bool {
+        (self.bid, self.bid_size, self.ask, self.ask_size)
+            == (other.bid, other.bid_size, other.ask, other.ask_size)
+    }
+}
+
+/// A depth stream reduced to best bid/offer changes.
+///
+/// The full book is kept locally, but an item is only yielded once the first
+/// snapshot has arrived and then whenever the top of book changes, so updates
+/// deeper in the book never wake the consumer. Errors are passed through; a
+/// disconnect clears the book, and the next snapshot is yielded even if it
+/// matches the last BBO.
+pub struct BboStream {
+    stream: TypedStream<DepthUpdate>,
+    book: OrderBook,
+    last: Option<Bbo>,
+}
+
+impl BboStream {
+    /// Reduce `stream` to BBO changes for `market_id`.
+    pub fn new(market_id: MarketId, stream: TypedStream<DepthUpdate>) -> Self {
+        Self {
+            stream,
+            book: OrderBook::new(market_id),
+            last: None,
+        }
+    }
+
+    /// The most recently yielded BBO.
+    pub fn last(&self) -> Option<Bbo> {
+        self.last
+    }
+}
+
+impl Stream for BboStream {
+    type Item = Result<Bbo, O2Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(update))) => {
+                    if !this.book.apply(&update) || !this.book.is_synced() {
+                        continue;
+                    }
+                    let bbo = this.book.bbo();
+                    if this.last.is_some_and(|last| last.same_top(&bbo)) {
+                        continue;
+                    }
+                    this.last = Some(bbo);
+                    return Poll::Ready(Some(Ok(bbo)));
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    if matches!(e, O2Error::WebSocketDisconnected(_)) {
+                        this.book.clear();
+                        this.last = None;
+                    }
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+fn write_lock(book: &RwLock<OrderBook>) -> std::sync::RwLockWriteGuard<'_, OrderBook> {
+    book.write().unwrap_or_else(|p| p.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(pairs: &[(u64, u64)]) -> Vec<DepthLevel> {
+        pairs
+            .iter()
+            .map(|&(price, quantity)| DepthLevel { price, quantity })
+            .collect()
+    }
+
+    /// (bids, asks) as (price, quantity) pairs.
+    type Sides<'a> = (&'a [(u64, u64)], &'a [(u64, u64)]);
+
+    fn update(market: &str, view: Option<Sides>, changes: Option<Sides>) -> DepthUpdate {
+        let to_snapshot = |(b, a): Sides| DepthSnapshot {
+            bids: levels(b),
+            asks: levels(a),
+        };
+        DepthUpdate {
+            action: "subscribe_depth_update".into(),
+            changes: changes.map(to_snapshot),
+            view: view.map(to_snapshot),
+            market_id: MarketId::new(market),
+            onchain_timestamp: None,
+            seen_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn snapshot_then_deltas() {
+        let mut book = OrderBook::new(MarketId::new("0xabc"));
+        assert!(!book.is_synced());
+
+        book.apply(&update(
+            "0xabc",
+            Some((&[(100, 5), (99, 3)], &[(101, 2), (103, 7)])),
+            None,
+        ));
+        assert!(book.is_synced());
+        assert_eq!(book.best_bid().unwrap().price, 100);
+        assert_eq!(book.best_ask().unwrap().price, 101);
+        assert_eq!(book.spread(), Some(1));
+        assert_eq!(book.mid_price().unwrap(), "100.5".parse().unwrap());
+
+        // Remove best bid, add a better ask, update a quantity.
+        book.apply(&update(
+            "0xabc",
+            None,
+            Some((&[(100, 0), (99, 4)], &[(102, 1)])),
+        ));
+        assert_eq!(book.best_bid().unwrap().price, 99);
+        assert_eq!(book.best_bid().unwrap().quantity, 4);
+        let asks: Vec<u64> = book.asks().map(|l| l.price).collect();
+        assert_eq!(asks, vec![101, 102, 103]);
+    }
+
+    #[test]
+    fn view_replaces_book() {
+        let mut book = OrderBook::new(MarketId::new("0xabc"));
+        book.apply(&update("0xabc", Some((&[(100, 5)], &[(101, 2)])), None));
+        book.apply(&update("0xabc", Some((&[(90, 1)], &[])), None));
+        let bids: Vec<u64> = book.bids().map(|l| l.price).collect();
+        assert_eq!(bids, vec![90]);
+        assert!(book.best_ask().is_none());
+        assert!(book.mid_price().is_none());
+    }
+
+    #[test]
+    fn ignores_other_markets() {
+        let mut book = OrderBook::new(MarketId::new("0xABC"));
+        assert!(!book.apply(&update("0xdef", Some((&[(1, 1)], &[])), None)));
+        assert!(book.best_bid().is_none());
+        assert!(book.apply(&update("0xabc", Some((&[(1, 1)], &[])), None)));
+    }
+
+    #[test]
+    fn bids_iterate_descending() {
+        let mut book = OrderBook::new(MarketId::new("0xabc"));
+        book.apply(&update(
+            "0xabc",
+            Some((&[(97, 1), (100, 1), (98, 1)], &[])),
+            None,
+        ));
+        let snapshot = book.to_snapshot(2);
+        let prices: Vec<u64> = snapshot.bids.iter().map(|l| l.price).collect();
+        assert_eq!(prices, vec![100, 98]);
+    }
+
+    #[test]
+    fn microstructure_analytics() {
+        let mut book = OrderBook::new(MarketId::new("0xabc"));
+        book.apply(&update(
+            "0xabc",
+            Some((&[(100, 30), (99, 20)], &[(102, 10), (103, 20), (105, 50)])),
+            None,
+        ));
+        // Heavier bid size pulls the weighted mid toward the ask.
+        assert_eq!(book.weighted_mid_price().unwrap(), "101.5".parse().unwrap());
+        assert_eq!(book.imbalance(1).unwrap(), "0.5".parse().unwrap());
+        assert_eq!(book.imbalance(2).unwrap(), "0.25".parse().unwrap());
+        assert_eq!(book.depth_to_price(Side::Buy, 103), 30);
+        assert_eq!(book.depth_to_price(Side::Sell, 99), 50);
+        assert_eq!(book.depth_to_price(Side::Sell, 101), 0);
+
+        let buy = book.market_impact(Side::Buy, 25).unwrap();
+        assert!(buy.is_complete());
+        assert_eq!((buy.levels, buy.best_price, buy.worst_price), (2, 102, 103));
+        // (10 * 102 + 15 * 103) / 25
+        assert_eq!(buy.average_price, "102.6".parse().unwrap());
+        assert_eq!(
+            buy.slippage_bps.round_dp(4),
+            "58.8235".parse::<Decimal>().unwrap()
+        );
+
+        let sell = book.market_impact(Side::Sell, 100).unwrap();
+        assert!(!sell.is_complete());
+        assert_eq!((sell.filled, sell.worst_price), (50, 99));
+        assert!(sell.slippage_bps > Decimal::ZERO);
+        assert!(book.market_impact(Side::Buy, 0).is_none());
+        assert!(OrderBook::new(MarketId::new("0xabc"))
+            .market_impact(Side::Buy, 1)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn bbo_stream_emits_only_top_changes() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut bbo = BboStream::new(MarketId::new("0xabc"), TypedStream::from_receiver(rx));
+
+        // Deltas before the first snapshot are not emitted.
+        tx.send(Ok(update("0xabc", None, Some((&[(99, 1)], &[])))))
+            .unwrap();
+        tx.send(Ok(update(
+            "0xabc",
+            Some((&[(100, 5), (99, 3)], &[(101, 2)])),
+            None,
+        )))
+        .unwrap();
+        // Deeper level and other market: no change at the top.
+        tx.send(Ok(update("0xabc", None, Some((&[(98, 9)], &[])))))
+            .unwrap();
+        tx.send(Ok(update("0xdef", Some((&[(500, 1)], &[])), None)))
+            .unwrap();
+        // Best ask size changes.
+        tx.send(Ok(update("0xabc", None, Some((&[], &[(101, 4)])))))
+            .unwrap();
+        tx.send(Err(O2Error::WebSocketDisconnected("gone".into())))
+            .unwrap();
+        tx.send(Ok(update("0xabc", Some((&[(100, 5)], &[(101, 4)])), None)))
+            .unwrap();
+        drop(tx);
+
+        let first = bbo.next().await.unwrap().unwrap();
+        assert_eq!(
+            (first.bid, first.bid_size, first.ask, first.ask_size),
+            (Some(100), 5, Some(101), 2)
+        );
+        let second = bbo.next().await.unwrap().unwrap();
+        assert_eq!((second.ask, second.ask_size), (Some(101), 4));
+        assert!(bbo.next().await.unwrap().is_err());
+        // Same top as before the disconnect, still re-emitted after resync.
+        let resynced = bbo.next().await.unwrap().unwrap();
+        assert!(resynced.same_top(&second));
+        assert!(bbo.next().await.is_none());
+    }
+}
@@ -0,0 +1,380 @@
+//! Session-level order attribution.
+//!
+//! The O2 API attributes orders to a trade account, not to the session that
+//! placed them, so `stream_orders` for an account also delivers orders placed by
+//! other sessions (another bot, the web UI). [`OrderOwnership`] records the order
+//! ids returned from actions submitted through an [`O2Client`](crate::O2Client),
+//! and [`TypedStream::mine_only`] uses it to filter an order stream down to a
+//! single session's orders.
+//!
+//! Only orders submitted with `collect_orders = true` are recorded, since the
+//! order ids are otherwise not returned.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use tokio::sync::{mpsc, Notify};
+
+use crate::crypto::to_hex_string;
+use crate::models::{Order, OrderId, OrderUpdate, Session};
+use crate::runtime;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// How long an order update with an unrecognised id is held back before being
+/// dropped. Covers the window where the WebSocket reports a new order before the
+/// submitting REST call has returned its id.
+const UNMATCHED_GRACE: Duration = Duration::from_secs(5);
+
+/// How long a closed order stays attributed, so every filtered stream still
+/// sees its final update.
+const CLOSED_RETENTION: Duration = Duration::from_secs(60);
+
+/// Upper bound on tracked orders. Orders that close without being seen on a
+/// filtered stream are never marked closed; past this bound the oldest tenth
+/// is dropped.
+const MAX_TRACKED: usize = 100_000;
+
+#[derive(Debug)]
+struct Entry {
+    session_hex: String,
+    recorded_at: Instant,
+    closed_at: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Orders {
+    by_id: HashMap<OrderId, Entry>,
+    last_sweep: Option<Instant>,
+}
+
+impl Orders {
+    /// Drop orders closed for longer than [`CLOSED_RETENTION`], at most once
+    /// per retention period, then enforce [`MAX_TRACKED`].
+    fn sweep(&mut self, now: Instant) {
+        let due = self.last_sweep.map_or(true, |last| {
+            now.saturating_duration_since(last) >= CLOSED_RETENTION
+        });
+        if due {
+            self.last_sweep = Some(now);
+            self.by_id.retain(|_, entry| {
+                entry.closed_at.map_or(true, |at| {
+                    now.saturating_duration_since(at) < CLOSED_RETENTION
+                })
+            });
+        }
+        if self.by_id.len() > MAX_TRACKED {
+            let mut recorded: Vec<Instant> =
+                self.by_id.values().map(|entry| entry.recorded_at).collect();
+            recorded.sort_unstable();
+            let cutoff = recorded[self.by_id.len() - MAX_TRACKED * 9 / 10];
+            self.by_id.retain(|_, entry| entry.recorded_at >= cutoff);
+            debug!(
+                "ownership.sweep evicted oldest orders remaining={}",
+                self.by_id.len()
+            );
+        }
+    }
+}
+
+/// Shared map from order id to the session address that created it.
+///
+/// Orders leave the map a minute after a filtered stream (or the submit
+/// response) reports them closed. Cloning is cheap; clones share the same map.
+#[derive(Debug, Clone, Default)]
+pub struct OrderOwnership {
+    orders: Arc<Mutex<Orders>>,
+    recorded: Arc<Notify>,
+}
+
+impl OrderOwnership {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Orders> {
+        self.orders.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Attribute `order_ids` to the session with address `session_address`.
+    pub fn record<I>(&self, session_address: &[u8; 32], order_ids: I)
+    where
+        I: IntoIterator<Item = OrderId>,
+    {
+        let session_hex = to_hex_string(session_address);
+        let now = runtime::now();
+        {
+            let mut orders = self.lock();
+            for order_id in order_ids {
+                orders.by_id.insert(
+                    order_id,
+                    Entry {
+                        session_hex: session_hex.clone(),
+                        recorded_at: now,
+                        closed_at: None,
+                    },
+                );
+            }
+            orders.sweep(now);
+        }
+        self.recorded.notify_waiters();
+    }
+
+    /// Note the latest state of `order`. A tracked order that is closed stays
+    /// attributed for a short while, then is forgotten.
+    pub fn observe(&self, order: &Order) {
+        if !order.status().is_terminal() {
+            return;
+        }
+        let now = runtime::now();
+        let mut orders = self.lock();
+        if let Some(entry) = orders.by_id.get_mut(&order.order_id) {
+            entry.closed_at.get_or_insert(now);
+        }
+        orders.sweep(now);
+    }
+
+    /// The hex session address that created `order_id`, if known.
+    pub fn session_of(&self, order_id: &OrderId) -> Option<String> {
+        self.lock()
+            .by_id
+            .get(order_id)
+            .map(|entry| entry.session_hex.clone())
+    }
+
+    /// True if `order_id` was created by `session`.
+    pub fn is_owned_by(&self, order_id: &OrderId, session: &Session) -> bool {
+        self.session_of(order_id).as_deref() == Some(&to_hex_string(&session.session_address))
+    }
+
+    /// Stop tracking an order (e.g. once it is closed).
+    pub fn forget(&self, order_id: &OrderId) {
+        self.lock().by_id.remove(order_id);
+    }
+
+    /// Number of tracked orders, including recently closed ones.
+    pub fn len(&self) -> usize {
+        self.lock().by_id.len()
+    }
+
+    /// True if no orders are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.lock().by_id.is_empty()
+    }
+}
+
+enum Attribution {
+    Mine,
+    Other,
+    Unknown,
+}
+
+fn attribute(ownership: &OrderOwnership, order: &Order, session_hex: &str) -> Attribution {
+    match ownership.session_of(&order.order_id) {
+        Some(owner) if owner == session_hex => Attribution::Mine,
+        Some(_) => Attribution::Other,
+        None => Attribution::Unknown,
+    }
+}
+
+impl TypedStream<OrderUpdate> {
+    /// Filter this stream to orders created by `session` through this SDK.
+    ///
+    /// Orders from other sessions on the same account are dropped. Orders whose
+    /// ids have not been recorded yet are held for a short grace period in case
+    /// the submitting call is still in flight, then dropped. Because of that hold,
+    /// updates may be delivered slightly out of arrival order. Updates whose order
+    /// list becomes empty after filtering are not delivered. Errors pass through.
+    pub fn mine_only(
+        self,
+        ownership: &OrderOwnership,
+        session: &Session,
+    ) -> TypedStream<OrderUpdate> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let ownership = ownership.clone();
+        let session_hex = to_hex_string(&session.session_address);
+        let mut inner = self;
+
+        runtime::spawn(async move {
+            // (deadline, update containing only unattributed orders)
+            let mut pending: Vec<(Instant, OrderUpdate)> = Vec::new();
+            loop {
+                let next_deadline = pending.iter().map(|(d, _)| *d).min();
+                let recorded = ownership.recorded.notified();
+                tokio::select! {
+                    item = inner.next() => match item {
+                        None => break,
+                        Some(Err(e)) => {
+                            if tx.send(Err(e)).is_err() {
+                                break;
+                            }
+                        }
+                        Some(Ok(update)) => {
+                            pending.push((runtime::now() + UNMATCHED_GRACE, update));
+                        }
+                    },
+                    _ = recorded => {}
+                    _ = runtime::sleep_until(next_deadline.unwrap_or_else(runtime::now)),
+                        if next_deadline.is_some() => {}
+                }
+
+                let now = runtime::now();
+                let mut still_pending = Vec::with_capacity(pending.len());
+                for (deadline, mut update) in pending.drain(..) {
+                    let mut mine = Vec::new();
+                    let mut unknown = Vec::new();
+                    for order in update.orders.drain(..) {
+                        match attribute(&ownership, &order, &session_hex) {
+                            Attribution::Mine => {
+                                ownership.observe(&order);
+                                mine.push(order);
+                            }
+                            Attribution::Other => ownership.observe(&order),
+                            Attribution::Unknown => unknown.push(order),
+                        }
+                    }
+                    if !mine.is_empty() {
+                        let mut out = update.clone();
+                        out.orders = mine;
+                        if tx.send(Ok(out)).is_err() {
+                            return;
+                        }
+                    }
+                    if !unknown.is_empty() {
+                        if deadline > now {
+                            update.orders = unknown;
+                            still_pending.push((deadline, update));
+                        } else {
+                            debug!(
+                                "ownership.mine_only dropped unattributed orders count={}",
+                                unknown.len()
+                            );
+                        }
+                    }
+                }
+                pending = still_pending;
+
+                if tx.is_closed() {
+                    break;
+                }
+            }
+        });
+
+        TypedStream::from_receiver(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeAccountId;
+    use crate::timestamp::Timestamp;
+
+    fn session(address_byte: u8) -> Session {
+        Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [address_byte; 32],
+            trade_account_id: TradeAccountId::new("0x01"),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce: 0,
+        }
+    }
+
+    fn order(id: &str) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": "buy",
+            "order_type": "Spot",
+            "quantity": "1",
+            "price": "1",
+        }))
+        .unwrap()
+    }
+
+    fn update(ids: &[&str]) -> OrderUpdate {
+        OrderUpdate {
+            action: "subscribe_orders".into(),
+            orders: ids.iter().map(|id| order(id)).collect(),
+            onchain_timestamp: None,
+            seen_timestamp: Timestamp::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn records_and_attributes_orders() {
+        let ownership = OrderOwnership::new();
+        let a = session(0xaa);
+        let b = session(0xbb);
+        ownership.record(&a.session_address, [OrderId::new("0x01")]);
+        assert!(ownership.is_owned_by(&OrderId::new("0x01"), &a));
+        assert!(!ownership.is_owned_by(&OrderId::new("0x01"), &b));
+        ownership.forget(&OrderId::new("0x01"));
+        assert!(ownership.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_orders_are_forgotten_after_retention() {
+        let ownership = OrderOwnership::new();
+        let me = session(0xaa);
+        ownership.record(
+            &me.session_address,
+            [OrderId::new("0x01"), OrderId::new("0x02")],
+        );
+        let mut closed = order("0x01");
+        closed.close = true;
+        closed.cancel = true;
+        ownership.observe(&closed);
+        assert!(ownership.is_owned_by(&OrderId::new("0x01"), &me));
+
+        tokio::time::sleep(CLOSED_RETENTION + Duration::from_secs(1)).await;
+        ownership.record(&me.session_address, [OrderId::new("0x03")]);
+        assert_eq!(ownership.session_of(&OrderId::new("0x01")), None);
+        assert!(ownership.is_owned_by(&OrderId::new("0x02"), &me));
+        assert_eq!(ownership.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn mine_only_filters_and_waits_for_late_registration() {
+        let ownership = OrderOwnership::new();
+        let me = session(0xaa);
+        let other = session(0xbb);
+        ownership.record(&me.session_address, [OrderId::new("0x01")]);
+        ownership.record(&other.session_address, [OrderId::new("0x02")]);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut mine = TypedStream::from_receiver(rx).mine_only(&ownership, &me);
+
+        tx.send(Ok(update(&["0x01", "0x02", "0x03"]))).unwrap();
+        let first = mine.next().await.unwrap().unwrap();
+        let ids: Vec<&str> = first.orders.iter().map(|o| o.order_id.as_str()).collect();
+        assert_eq!(ids, vec!["0x01"]);
+
+        // 0x03 shows up on the stream before its submit call returned.
+        ownership.record(&me.session_address, [OrderId::new("0x03")]);
+        let late = tokio::time::timeout(Duration::from_secs(1), mine.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(late.orders[0].order_id.as_str(), "0x03");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mine_only_drops_unknown_after_grace() {
+        let ownership = OrderOwnership::new();
+        let me = session(0xaa);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut mine = TypedStream::from_receiver(rx).mine_only(&ownership, &me);
+
+        tx.send(Ok(update(&["0x09"]))).unwrap();
+        tokio::time::sleep(UNMATCHED_GRACE + Duration::from_secs(1)).await;
+        ownership.record(&me.session_address, [OrderId::new("0x09")]);
+        drop(tx);
+        assert!(mine.next().await.is_none());
+    }
+}
@@ -0,0 +1,960 @@
+//! Paper trading against live market data.
+//!
+//! [`Trader`] is the order-management surface of [`O2Client`]: place, cancel,
+//! list open orders, read balances. [`LiveTrader`] implements it by submitting
+//! through a client and session; [`PaperTrader`] implements it locally, so a
+//! strategy written against `Trader` can run on mainnet data without
+//! submitting anything.
+//!
+//! A `PaperTrader` subscribes to depth and trades for each market it trades
+//! on. A new order is checked with [`check_order`], then takes liquidity from
+//! the current book at each level's price, paying the taker fee. Whatever
+//! remains of a `Spot`, `PostOnly` or `Limit` order rests; the remainder of
+//! `Market` and `BoundedMarket` orders is cancelled, and a `FillOrKill` order
+//! that cannot fill completely is rejected. A resting order fills, at its own
+//! price and paying the maker fee, from public trades printed at or through
+//! that price after it was placed. Fees are the markets' defaults, or an
+//! account's own rates (see [`crate::fees`]) with
+//! [`PaperTrader::with_account`].
+//!
+//! ```rust,ignore
+//! let mut paper = PaperTrader::new(client);
+//! paper.deposit("fUSDC", "1000".parse()?).await?;
+//! let market = MarketSymbol::new("fFUEL/fUSDC");
+//! let order = paper
+//!     .create_order(&market, Side::Buy, "0.025".parse()?, "5000".parse()?, OrderType::Spot)
+//!     .await?;
+//! println!("{} {:?}", order.order_id, order.status());
+//! ```
+//!
+//! The simulation is optimistic in two ways: paper fills do not remove
+//! liquidity from the book, so orders placed back to back can take the same
+//! level, and queue position is not modelled, so a resting order fills from
+//! the first trade at its price. `Limit` orders rest like `Spot` orders; their
+//! time-in-force is not enforced.
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::{FutureExt, StreamExt};
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::fees::FeeRates;
+use crate::models::{
+    AssetId, BalanceResponse, DepthLevel, Market, MarketAsset, MarketId, MarketSymbol, Order,
+    OrderId, OrderType, Session, Side, Trade, TradeAccountId, TradeUpdate,
+};
+use crate::orderbook::{LiveOrderBook, OrderBook};
+use crate::preflight::{check_order, ValidatedOrder};
+use crate::runtime;
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// How long [`PaperTrader::track`] waits for a market's first depth snapshot.
+const BOOK_SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Open orders listed by [`LiveTrader::open_orders`].
+const OPEN_ORDERS_LIMIT: u32 = 200;
+
+/// Placing and cancelling orders, and reading the balances they draw on.
+///
+/// Prices and quantities are human-readable, as for
+/// [`O2Client::create_order`]. Balances are keyed by asset symbol and amounts
+/// are chain integers, as from [`O2Client::get_balances`].
+pub trait Trader: Send {
+    /// Place an order and return it as placed, including any immediate fill.
+    fn create_order(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> impl Future<Output = Result<Order, O2Error>> + Send;
+
+    fn cancel_order(
+        &mut self,
+        market: &MarketSymbol,
+        order_id: &OrderId,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send;
+
+    fn cancel_all_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send;
+
+    fn open_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<Vec<Order>, O2Error>> + Send;
+
+    fn balances(
+        &mut self,
+    ) -> impl Future<Output = Result<HashMap<String, BalanceResponse>, O2Error>> + Send;
+}
+
+/// A [`Trader`] that submits to the exchange.
+///
+/// Orders are placed with `settle_first` and `collect_orders` set, so the
+/// placed order comes back in the response. In
+/// [`ClientMode::DryRun`](crate::ClientMode::DryRun) there is no order to
+/// return and `create_order` fails.
+pub struct LiveTrader {
+    pub client: O2Client,
+    pub session: Session,
+}
+
+impl LiveTrader {
+    pub fn new(client: O2Client, session: Session) -> Self {
+        Self { client, session }
+    }
+}
+
+impl Trader for LiveTrader {
+    async fn create_order(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> Result<Order, O2Error> {
+        let resp = self
+            .client
+            .create_order(
+                &mut self.session,
+                market,
+                side,
+                price,
+                quantity,
+                order_type,
+                true,
+                true,
+            )
+            .await?;
+        resp.orders
+            .and_then(|orders| orders.into_iter().next())
+            .ok_or_else(|| O2Error::Other(format!("No order returned for {market}")))
+    }
+
+    async fn cancel_order(
+        &mut self,
+        market: &MarketSymbol,
+        order_id: &OrderId,
+    ) -> Result<(), O2Error> {
+        self.client
+            .cancel_order(&mut self.session, order_id, market)
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&mut self, market: &MarketSymbol) -> Result<(), O2Error> {
+        self.client
+            .cancel_all_orders(&mut self.session, market)
+            .await?;
+        Ok(())
+    }
+
+    async fn open_orders(&mut self, market: &MarketSymbol) -> Result<Vec<Order>, O2Error> {
+        let account = self.session.trade_account_id.clone();
+        let resp = self
+            .client
+            .get_orders(market, account, Some(true), OPEN_ORDERS_LIMIT, None, None)
+            .await?;
+        Ok(resp.orders)
+    }
+
+    fn balances(
+        &mut self,
+    ) -> impl Future<Output = Result<HashMap<String, BalanceResponse>, O2Error>> + Send {
+        self.client
+            .get_balances(self.session.trade_account_id.clone())
+    }
+}
+
+/// A fill simulated by a [`PaperTrader`]. Amounts are chain integers; `fee` is
+/// in the quote asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaperFill {
+    pub order_id: OrderId,
+    pub market: MarketSymbol,
+    pub side: Side,
+    pub price: u64,
+    pub quantity: u64,
+    pub fee: u128,
+    pub is_maker: bool,
+    pub timestamp: Timestamp,
+}
+
+/// A [`Trader`] that fills orders locally against live depth and trades.
+///
+/// Balances start empty; fund them with [`PaperTrader::deposit`]. Resting
+/// orders are matched against trades received since the last call, at the
+/// start of every `Trader` method.
+pub struct PaperTrader {
+    client: O2Client,
+    /// Whose fee rates paper fills pay, if not the markets' defaults.
+    account: Option<TradeAccountId>,
+    feeds: HashMap<MarketSymbol, Feed>,
+    ledger: Ledger,
+}
+
+struct Feed {
+    market: Market,
+    book: LiveOrderBook,
+    trades: TypedStream<TradeUpdate>,
+}
+
+impl PaperTrader {
+    /// Trade on paper with market data from `client`.
+    pub fn new(client: O2Client) -> Self {
+        Self {
+            client,
+            account: None,
+            feeds: HashMap::new(),
+            ledger: Ledger::default(),
+        }
+    }
+
+    /// Pay the fees `account` pays, as resolved by
+    /// [`O2Client::my_fees`], instead of the markets' defaults.
+    pub fn with_account(mut self, account: TradeAccountId) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    /// Credit `amount` of the asset named by `symbol_or_id` to the paper
+    /// balances.
+    pub async fn deposit(
+        &mut self,
+        symbol_or_id: &str,
+        amount: UnsignedDecimal,
+    ) -> Result<(), O2Error> {
+        let asset = self.client.asset(symbol_or_id).await?;
+        let chain_amount = asset.scale_amount(&amount)?;
+        debug!("paper.deposit asset={} amount={}", asset.symbol, amount);
+        self.ledger
+            .credit(&asset.asset_id, &asset.symbol, u128::from(chain_amount));
+        Ok(())
+    }
+
+    /// Subscribe to depth and trades for `market` and wait for its first
+    /// depth snapshot. Called on the first order for a market; call it ahead
+    /// of time to avoid the wait.
+    pub async fn track(&mut self, market: &MarketSymbol) -> Result<(), O2Error> {
+        if self.feeds.contains_key(market) {
+            return Ok(());
+        }
+        debug!("paper.track market={}", market);
+        let info = self.client.get_market(market).await?;
+        if let Some(account) = &self.account {
+            let fees = self.client.my_fees(account.clone(), market).await?;
+            self.ledger.set_fees(&info, fees);
+        }
+        let book = self.client.live_order_book(market, 1).await?;
+        let trades = self.client.stream_trades(&info.market_id).await?;
+
+        let mut version = book.subscribe();
+        let _ = runtime::timeout(BOOK_SYNC_TIMEOUT, async {
+            while !book.is_synced() {
+                if version.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+        .await;
+        if !book.is_synced() {
+            return Err(O2Error::Other(format!(
+                "No depth snapshot for {market} within {BOOK_SYNC_TIMEOUT:?}"
+            )));
+        }
+        self.feeds.insert(
+            market.clone(),
+            Feed {
+                market: info,
+                book,
+                trades,
+            },
+        );
+        Ok(())
+    }
+
+    /// Every fill so far, oldest first.
+    pub fn fills(&self) -> &[PaperFill] {
+        self.ledger.fills()
+    }
+
+    /// Match resting orders against trades received since the last sync.
+    fn sync(&mut self) -> Result<(), O2Error> {
+        for feed in self.feeds.values_mut() {
+            while let Some(Some(item)) = feed.trades.next().now_or_never() {
+                match item {
+                    Ok(update) => self.ledger.apply_trades(&feed.market, &update.trades)?,
+                    Err(e) => debug!(
+                        "paper.sync stream_error market={} error={}",
+                        feed.market.symbol_pair(),
+                        e
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn place(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> Result<Order, O2Error> {
+        self.track(market).await?;
+        self.sync()?;
+        let feed = &self.feeds[market];
+        let order = check_order(&feed.market, side, price, quantity, order_type)?;
+        let book = feed.book.snapshot();
+        self.ledger
+            .place(&feed.market, &book, order, Timestamp::now())
+    }
+}
+
+impl Trader for PaperTrader {
+    fn create_order(
+        &mut self,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> impl Future<Output = Result<Order, O2Error>> + Send {
+        self.place(market, side, price, quantity, order_type)
+    }
+
+    fn cancel_order(
+        &mut self,
+        market: &MarketSymbol,
+        order_id: &OrderId,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send {
+        let result = self
+            .sync()
+            .and_then(|()| self.ledger.cancel(market, Some(order_id)));
+        async move { result.map(|_| ()) }
+    }
+
+    fn cancel_all_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<(), O2Error>> + Send {
+        let result = self.sync().and_then(|()| self.ledger.cancel(market, None));
+        async move { result.map(|_| ()) }
+    }
+
+    fn open_orders(
+        &mut self,
+        market: &MarketSymbol,
+    ) -> impl Future<Output = Result<Vec<Order>, O2Error>> + Send {
+        let result = self.sync().map(|()| self.ledger.open_orders(market));
+        async move { result }
+    }
+
+    fn balances(
+        &mut self,
+    ) -> impl Future<Output = Result<HashMap<String, BalanceResponse>, O2Error>> + Send {
+        let result = self.sync().map(|()| self.ledger.balances());
+        async move { result }
+    }
+}
+
+/// Simulated balances, orders and fills, independent of any feed.
+///
+/// Orders are placed and filled at times the caller supplies, so the same
+/// ledger serves live paper trading and replays.
+#[derive(Debug, Default)]
+pub(crate) struct Ledger {
+    holdings: HashMap<AssetId, Holding>,
+    /// Resting orders, in placement order.
+    orders: Vec<PaperOrder>,
+    fills: Vec<PaperFill>,
+    next_id: u64,
+    /// Rates charged on a market instead of its defaults.
+    fees: HashMap<MarketId, FeeRates>,
+}
+
+#[derive(Debug)]
+struct Holding {
+    symbol: String,
+    unlocked: u128,
+    locked: u128,
+}
+
+#[derive(Debug)]
+struct PaperOrder {
+    market: MarketSymbol,
+    order: Order,
+    /// Worst price the order fills at, in chain units.
+    limit: u64,
+    remaining: u64,
+    /// Funds still held for the order: quote for a buy, base for a sell.
+    locked: u128,
+    locked_asset: AssetId,
+    /// Sum of `price × quantity` over fills, for the average fill price.
+    filled_value: u128,
+    placed_at: Timestamp,
+}
+
+impl Ledger {
+    pub(crate) fn fills(&self) -> &[PaperFill] {
+        &self.fills
+    }
+
+    /// Charge `fees` on `market` instead of its default rates.
+    pub(crate) fn set_fees(&mut self, market: &Market, fees: FeeRates) {
+        self.fees.insert(market.market_id.clone(), fees);
+    }
+
+    /// The rates charged on `market`.
+    fn fees(&self, market: &Market) -> FeeRates {
+        self.fees
+            .get(&market.market_id)
+            .copied()
+            .unwrap_or_else(|| FeeRates::from_market(market))
+    }
+
+    /// Locked and unlocked balance of `asset`.
+    pub(crate) fn total(&self, asset: &AssetId) -> u128 {
+        self.holdings
+            .get(asset)
+            .map_or(0, |h| h.unlocked + h.locked)
+    }
+
+    pub(crate) fn credit(&mut self, asset: &AssetId, symbol: &str, amount: u128) {
+        self.holdings
+            .entry(asset.clone())
+            .or_insert_with(|| Holding {
+                symbol: symbol.to_string(),
+                unlocked: 0,
+                locked: 0,
+            })
+            .unlocked += amount;
+    }
+
+    /// Move `amount` of `asset` from unlocked to locked.
+    fn lock(&mut self, asset: &MarketAsset, amount: u128) -> Result<(), O2Error> {
+        let have = self.holdings.get(&asset.asset).map_or(0, |h| h.unlocked);
+        if have < amount {
+            return Err(O2Error::InvalidOrderParams(format!(
+                "Insufficient paper {} balance: order needs {}, {} available",
+                asset.symbol,
+                asset.format_amount(amount)?,
+                asset.format_amount(have)?
+            )));
+        }
+        if let Some(holding) = self.holdings.get_mut(&asset.asset) {
+            holding.unlocked -= amount;
+            holding.locked += amount;
+        }
+        Ok(())
+    }
+
+    /// Spend `amount` of the funds held for `order`, drawing any shortfall
+    /// from the unlocked balance.
+    fn spend(&mut self, order: &mut PaperOrder, amount: u128) {
+        let from_locked = amount.min(order.locked);
+        order.locked -= from_locked;
+        if let Some(holding) = self.holdings.get_mut(&order.locked_asset) {
+            holding.locked -= from_locked;
+            holding.unlocked = holding.unlocked.saturating_sub(amount - from_locked);
+        }
+    }
+
+    /// Return what is still held for `order` to the unlocked balance.
+    fn release(&mut self, order: &mut PaperOrder) {
+        if let Some(holding) = self.holdings.get_mut(&order.locked_asset) {
+            holding.locked -= order.locked;
+            holding.unlocked += order.locked;
+        }
+        order.locked = 0;
+    }
+
+    /// Place `order` at `now`, taking liquidity from `book`.
+    pub(crate) fn place(
+        &mut self,
+        market: &Market,
+        book: &OrderBook,
+        order: ValidatedOrder,
+        now: Timestamp,
+    ) -> Result<Order, O2Error> {
+        let ValidatedOrder {
+            side,
+            order_type,
+            chain_price,
+            chain_quantity,
+            ..
+        } = order;
+        let limit = match (&order_type, side) {
+            (OrderType::BoundedMarket { max_price, .. }, Side::Buy) => {
+                chain_price.min(market.scale_price(max_price)?)
+            }
+            (OrderType::BoundedMarket { min_price, .. }, Side::Sell) => {
+                chain_price.max(market.scale_price(min_price)?)
+            }
+            _ => chain_price,
+        };
+        let crossing: Vec<DepthLevel> = match side {
+            Side::Buy => book.asks().take_while(|l| l.price <= limit).collect(),
+            Side::Sell => book.bids().take_while(|l| l.price >= limit).collect(),
+        };
+        let available = crossing
+            .iter()
+            .fold(0u64, |sum, l| sum.saturating_add(l.quantity));
+        match order_type {
+            OrderType::PostOnly if !crossing.is_empty() => {
+                return Err(O2Error::InvalidOrderParams(format!(
+                    "PostOnly order would cross the book at {}",
+                    market.format_price(crossing[0].price)?
+                )));
+            }
+            OrderType::FillOrKill if available < chain_quantity => {
+                return Err(O2Error::InvalidOrderParams(format!(
+                    "FillOrKill order for {} can only fill {}",
+                    market.format_quantity(chain_quantity)?,
+                    market.format_quantity(available)?
+                )));
+            }
+            _ => {}
+        }
+
+        let (lock_asset, lock) = match side {
+            Side::Buy => {
+                // Hold the higher fee unless the order can only make.
+                let fees = self.fees(market);
+                let is_maker = matches!(order_type, OrderType::PostOnly) || fees.maker > fees.taker;
+                let (notional, fee) =
+                    fees.chain_fee_units(market, limit, chain_quantity, is_maker)?;
+                (&market.quote, notional + fee)
+            }
+            Side::Sell => (&market.base, u128::from(chain_quantity)),
+        };
+        self.lock(lock_asset, lock)?;
+
+        self.next_id += 1;
+        let mut paper = PaperOrder {
+            market: market.symbol_pair(),
+            order: Order::local(
+                OrderId::new(format!("0x{:064x}", self.next_id)),
+                market,
+                side,
+                &order_type,
+                chain_price,
+                chain_quantity,
+                now,
+            )?,
+            limit,
+            remaining: chain_quantity,
+            locked: lock,
+            locked_asset: lock_asset.asset.clone(),
+            filled_value: 0,
+            placed_at: now,
+        };
+        debug!(
+            "paper.place market={} order_id={} side={} price={} quantity={}",
+            paper.market,
+            paper.order.order_id,
+            side.as_str(),
+            chain_price,
+            chain_quantity
+        );
+
+        for level in crossing {
+            if paper.remaining == 0 {
+                break;
+            }
+            let quantity = level.quantity.min(paper.remaining);
+            self.fill(market, &mut paper, level.price, quantity, false, now)?;
+        }
+
+        let rests = matches!(
+            order_type,
+            OrderType::Spot | OrderType::PostOnly | OrderType::Limit { .. }
+        );
+        if paper.remaining > 0 && !rests {
+            Self::mark_cancelled(&mut paper);
+            self.release(&mut paper);
+        }
+        let placed = paper.order.clone();
+        if paper.remaining > 0 && rests {
+            self.orders.push(paper);
+        }
+        Ok(placed)
+    }
+
+    /// Settle a fill of `quantity` at `price` against `order`.
+    fn fill(
+        &mut self,
+        market: &Market,
+        order: &mut PaperOrder,
+        price: u64,
+        quantity: u64,
+        is_maker: bool,
+        at: Timestamp,
+    ) -> Result<(), O2Error> {
+        let (notional, fee) = self
+            .fees(market)
+            .chain_fee_units(market, price, quantity, is_maker)?;
+        match order.order.side {
+            Side::Buy => {
+                self.spend(order, notional + fee);
+                self.credit(
+                    &market.base.asset,
+                    &market.base.symbol,
+                    u128::from(quantity),
+                );
+            }
+            Side::Sell => {
+                self.spend(order, u128::from(quantity));
+                self.credit(
+                    &market.quote.asset,
+                    &market.quote.symbol,
+                    notional.saturating_sub(fee),
+                );
+            }
+        }
+
+        order.remaining -= quantity;
+        order.filled_value += u128::from(price) * u128::from(quantity);
+        let filled = order.order.quantity - order.remaining;
+        order.order.quantity_fill = Some(filled);
+        order.order.price_fill = Some((order.filled_value / u128::from(filled)) as u64);
+        order.order.partially_filled = order.remaining > 0;
+        if order.remaining == 0 {
+            order.order.close = true;
+            self.release(order);
+        }
+
+        self.fills.push(PaperFill {
+            order_id: order.order.order_id.clone(),
+            market: order.market.clone(),
+            side: order.order.side,
+            price,
+            quantity,
+            fee,
+            is_maker,
+            timestamp: at,
+        });
+        Ok(())
+    }
+
+    fn mark_cancelled(order: &mut PaperOrder) {
+        order.order.close = true;
+        order.order.cancel = true;
+    }
+
+    /// Fill resting orders on `market` from public `trades`, best price
+    /// first and then in placement order.
+    pub(crate) fn apply_trades(
+        &mut self,
+        market: &Market,
+        trades: &[Trade],
+    ) -> Result<(), O2Error> {
+        let symbol = market.symbol_pair();
+        let mut orders = std::mem::take(&mut self.orders);
+        let result = trades.iter().try_for_each(|trade| {
+            let mut matched: Vec<usize> = (0..orders.len())
+                .filter(|&i| {
+                    let o = &orders[i];
+                    o.market == symbol
+                        && o.remaining > 0
+                        && trade.timestamp >= o.placed_at
+                        && match o.order.side {
+                            Side::Buy => trade.price <= o.limit,
+                            Side::Sell => trade.price >= o.limit,
+                        }
+                })
+                .collect();
+            matched.sort_by_key(|&i| Reverse(orders[i].limit.abs_diff(trade.price)));
+            let mut left = trade.quantity;
+            for i in matched {
+                if left == 0 {
+                    break;
+                }
+                let quantity = left.min(orders[i].remaining);
+                let price = orders[i].limit;
+                self.fill(
+                    market,
+                    &mut orders[i],
+                    price,
+                    quantity,
+                    true,
+                    trade.timestamp,
+                )?;
+                left -= quantity;
+            }
+            Ok(())
+        });
+        orders.retain(|o| o.remaining > 0);
+        self.orders = orders;
+        result
+    }
+
+    /// Cancel `order_id` on `market`, or every order on `market` if `None`.
+    pub(crate) fn cancel(
+        &mut self,
+        market: &MarketSymbol,
+        order_id: Option<&OrderId>,
+    ) -> Result<Vec<Order>, O2Error> {
+        let (cancelled, kept): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.orders).into_iter().partition(|o| {
+                &o.market == market && order_id.map_or(true, |id| &o.order.order_id == id)
+            });
+        self.orders = kept;
+        if let (Some(id), true) = (order_id, cancelled.is_empty()) {
+            return Err(O2Error::OrderNotFound(format!(
+                "No open paper order {id} on {market}"
+            )));
+        }
+        Ok(cancelled
+            .into_iter()
+            .map(|mut order| {
+                Self::mark_cancelled(&mut order);
+                self.release(&mut order);
+                order.order
+            })
+            .collect())
+    }
+
+    pub(crate) fn open_orders(&self, market: &MarketSymbol) -> Vec<Order> {
+        self.orders
+            .iter()
+            .filter(|o| &o.market == market)
+            .map(|o| o.order.clone())
+            .collect()
+    }
+
+    pub(crate) fn balances(&self) -> HashMap<String, BalanceResponse> {
+        self.holdings
+            .values()
+            .map(|h| {
+                (
+                    h.symbol.clone(),
+                    BalanceResponse {
+                        order_books: HashMap::new(),
+                        total_locked: h.locked,
+                        total_unlocked: h.unlocked,
+                        trading_account_balance: h.unlocked,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ContractId, DepthSnapshot, MarketId};
+
+    fn market() -> Market {
+        Market {
+            contract_id: ContractId::new("0x01"),
+            market_id: MarketId::new("0x02"),
+            whitelist_id: None,
+            blacklist_id: None,
+            // 0.01%.
+            maker_fee: 0,
+            taker_fee: 100,
+            min_order: 1_000_000,
+            dust: 0,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "fFUEL".into(),
+                asset: AssetId::new("0xaa"),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "fUSDC".into(),
+                asset: AssetId::new("0xbb"),
+                decimals: 6,
+                max_precision: 4,
+            },
+        }
+    }
+
+    fn book(bids: &[(u64, u64)], asks: &[(u64, u64)]) -> OrderBook {
+        let levels = |side: &[(u64, u64)]| {
+            side.iter()
+                .map(|&(price, quantity)| DepthLevel { price, quantity })
+                .collect()
+        };
+        let mut book = OrderBook::new(MarketId::new("0x02"));
+        book.apply_snapshot(&DepthSnapshot {
+            bids: levels(bids),
+            asks: levels(asks),
+        });
+        book
+    }
+
+    fn trade(price: u64, quantity: u64, at: Timestamp) -> Trade {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": "1",
+            "side": "Sell",
+            "total": "0",
+            "quantity": quantity.to_string(),
+            "price": price.to_string(),
+            "timestamp": at.as_unix_millis() as u64,
+        }))
+        .unwrap()
+    }
+
+    fn order(side: Side, price: &str, quantity: &str, order_type: OrderType) -> ValidatedOrder {
+        check_order(&market(), side, price, quantity, order_type).unwrap()
+    }
+
+    fn balance(ledger: &Ledger, symbol: &str) -> (u128, u128) {
+        let b = &ledger.balances()[symbol];
+        (b.total_unlocked, b.total_locked)
+    }
+
+    #[test]
+    fn buys_take_the_book_then_rest_until_trades_reach_them() {
+        let market = market();
+        let mut ledger = Ledger::default();
+        ledger.credit(&market.quote.asset, "fUSDC", 10_000_000);
+        let book = book(&[], &[(250_000, 10_000_000_000), (260_000, 10_000_000_000)]);
+
+        let placed = ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Buy, "0.26", "25", OrderType::Spot),
+                Timestamp::now(),
+            )
+            .unwrap();
+        assert_eq!(placed.quantity_fill, Some(20_000_000_000));
+        assert_eq!(placed.price_fill, Some(255_000));
+        assert!(placed.status().is_open());
+        // Locked for 25 at 0.26 plus the taker fee, less two taker fills.
+        assert_eq!(balance(&ledger, "fUSDC"), (3_499_350, 1_400_140));
+        assert_eq!(balance(&ledger, "fFUEL"), (20_000_000_000, 0));
+
+        // Trades from before the order was placed are ignored.
+        ledger
+            .apply_trades(
+                &market,
+                &[trade(250_000, 100_000_000_000, Timestamp::UNIX_EPOCH)],
+            )
+            .unwrap();
+        assert_eq!(ledger.open_orders(&market.symbol_pair()).len(), 1);
+
+        let later = Timestamp::from_unix_millis(Timestamp::now().as_unix_millis() as u64 + 1_000);
+        ledger
+            .apply_trades(&market, &[trade(255_000, 100_000_000_000, later)])
+            .unwrap();
+        assert!(ledger.open_orders(&market.symbol_pair()).is_empty());
+        // The rest filled at the order's own price with no maker fee, and the
+        // unspent lock came back.
+        assert_eq!(balance(&ledger, "fUSDC"), (3_599_490, 0));
+        assert_eq!(balance(&ledger, "fFUEL"), (25_000_000_000, 0));
+        let fills: Vec<_> = ledger.fills.iter().map(|f| (f.price, f.is_maker)).collect();
+        assert_eq!(fills, [(250_000, false), (260_000, false), (260_000, true)]);
+    }
+
+    #[test]
+    fn account_fee_rates_replace_the_market_defaults() {
+        let market = market();
+        let mut ledger = Ledger::default();
+        ledger.set_fees(&market, FeeRates::new(0, 0));
+        ledger.credit(&market.quote.asset, "fUSDC", 10_000_000);
+        let book = book(&[], &[(250_000, 10_000_000_000), (260_000, 10_000_000_000)]);
+
+        ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Buy, "0.26", "25", OrderType::Spot),
+                Timestamp::now(),
+            )
+            .unwrap();
+        // As above, without the taker fee.
+        assert_eq!(balance(&ledger, "fUSDC"), (3_500_000, 1_400_000));
+        assert!(ledger.fills().iter().all(|f| f.fee == 0));
+    }
+
+    #[test]
+    fn order_types_and_balances_are_enforced() {
+        let market = market();
+        let symbol = market.symbol_pair();
+        let mut ledger = Ledger::default();
+        ledger.credit(&market.base.asset, "fFUEL", 30_000_000_000);
+        ledger.credit(&market.quote.asset, "fUSDC", 1_000_000);
+        let book = book(&[(240_000, 10_000_000_000)], &[(250_000, 10_000_000_000)]);
+
+        let err = ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Buy, "0.25", "4", OrderType::PostOnly),
+                Timestamp::now(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, O2Error::InvalidOrderParams(_)), "{err}");
+        let err = ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Sell, "0.24", "20", OrderType::FillOrKill),
+                Timestamp::now(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, O2Error::InvalidOrderParams(_)), "{err}");
+        let err = ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Buy, "0.2", "10", OrderType::Spot),
+                Timestamp::now(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Insufficient paper fUSDC"),
+            "{err}"
+        );
+
+        // A market order's unfilled remainder is cancelled and unlocked.
+        let sold = ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Sell, "0.24", "30", OrderType::Market),
+                Timestamp::now(),
+            )
+            .unwrap();
+        assert_eq!(sold.status(), crate::models::OrderStatus::Cancelled);
+        assert_eq!(balance(&ledger, "fFUEL"), (20_000_000_000, 0));
+        assert_eq!(balance(&ledger, "fUSDC"), (1_000_000 + 2_399_760, 0));
+
+        ledger
+            .place(
+                &market,
+                &book,
+                order(Side::Buy, "0.2", "5", OrderType::PostOnly),
+                Timestamp::now(),
+            )
+            .unwrap();
+        assert_eq!(balance(&ledger, "fUSDC"), (2_399_760, 1_000_000));
+        let missing = OrderId::new("0x09");
+        assert!(matches!(
+            ledger.cancel(&symbol, Some(&missing)),
+            Err(O2Error::OrderNotFound(_))
+        ));
+        let cancelled = ledger.cancel(&symbol, None).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert!(cancelled[0].cancel);
+        assert_eq!(balance(&ledger, "fUSDC"), (3_399_760, 0));
+    }
+}
@@ -0,0 +1,342 @@
+//! Checking an order against a market's rules without submitting it.
+//!
+//! [`O2Client::create_order`](crate::O2Client::create_order) truncates the
+//! price and quantity to the market's precision, trims the quantity with
+//! [`Market::adjust_quantity`], and only then checks the minimum order size.
+//! A strategy screening candidate orders wants to know all of that before it
+//! spends a nonce. [`check_order`] runs the same steps locally and reports
+//! every rule the order breaks, with amounts in human units;
+//! [`O2Client::validate_order`](crate::O2Client::validate_order) does the same
+//! against a cached market.
+//!
+//! ```rust,ignore
+//! match client.validate_order("fFUEL/fUSDC", Side::Sell, "0.02514", "100", OrderType::Spot).await {
+//!     Ok(order) => println!("would submit {} @ {}", order.quantity, order.price),
+//!     Err(OrderValidationError::Violations(violations)) => {
+//!         for v in &violations {
+//!             println!("rejected: {v}");
+//!         }
+//!     }
+//!     Err(OrderValidationError::Market(e)) => return Err(e),
+//! }
+//! ```
+//!
+//! The market's `price_window` is not checked: the range it bounds is
+//! relative to on-chain book state the SDK does not see.
+use thiserror::Error;
+
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, Market, MarketSymbol, OrderPriceInput, OrderQuantityInput, OrderType, Side,
+};
+
+/// An order that passed [`check_order`], as it would be submitted.
+#[derive(Debug, Clone)]
+pub struct ValidatedOrder {
+    pub market: MarketSymbol,
+    pub side: Side,
+    pub order_type: OrderType,
+    /// Price after truncation to the market's tick.
+    pub price: UnsignedDecimal,
+    /// Quantity after truncation to the lot and [`Market::adjust_quantity`].
+    pub quantity: UnsignedDecimal,
+    pub chain_price: u64,
+    pub chain_quantity: u64,
+    /// `price × quantity`, in quote units.
+    pub quote_value: UnsignedDecimal,
+}
+
+impl ValidatedOrder {
+    /// The order as a `CreateOrder` action for
+    /// [`O2Client::batch_actions`](crate::O2Client::batch_actions).
+    pub fn into_action(self) -> Action {
+        Action::CreateOrder {
+            side: self.side,
+            price: self.price,
+            quantity: self.quantity,
+            order_type: self.order_type,
+        }
+    }
+}
+
+/// One market rule an order breaks.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OrderViolation {
+    /// The price could not be read, or a [`Price`](crate::Price) was built
+    /// for another market or under different precision.
+    #[error("invalid price: {0}")]
+    InvalidPrice(String),
+    /// As `InvalidPrice`, for the quantity.
+    #[error("invalid quantity: {0}")]
+    InvalidQuantity(String),
+    #[error("price is zero after rounding to the tick size {tick_size}")]
+    ZeroPrice { tick_size: UnsignedDecimal },
+    /// Nothing is left of the quantity after rounding to the lot size and
+    /// adjusting it so `price × quantity` is whole in quote units.
+    #[error("quantity {requested} rounds to zero at price {price}")]
+    ZeroQuantity {
+        requested: UnsignedDecimal,
+        price: UnsignedDecimal,
+    },
+    #[error("order value {value} is below the market minimum {min_order}")]
+    BelowMinOrder {
+        value: UnsignedDecimal,
+        min_order: UnsignedDecimal,
+    },
+    #[error("quantity {quantity} is below the market's dust threshold {dust}")]
+    BelowDust {
+        quantity: UnsignedDecimal,
+        dust: UnsignedDecimal,
+    },
+    /// A `Limit` or `BoundedMarket` price could not be scaled, or a
+    /// `BoundedMarket` minimum exceeds its maximum.
+    #[error("invalid order type: {0}")]
+    InvalidOrderType(String),
+}
+
+/// Why [`O2Client::validate_order`](crate::O2Client::validate_order) did not
+/// accept an order.
+#[derive(Error, Debug)]
+pub enum OrderValidationError {
+    /// The market could not be resolved.
+    #[error(transparent)]
+    Market(#[from] O2Error),
+    /// The order breaks these rules, in the order they were checked.
+    #[error("{}", join_violations(.0))]
+    Violations(Vec<OrderViolation>),
+}
+
+fn join_violations(violations: &[OrderViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl From<OrderValidationError> for O2Error {
+    fn from(err: OrderValidationError) -> Self {
+        match err {
+            OrderValidationError::Market(e) => e,
+            OrderValidationError::Violations(v) => O2Error::InvalidOrderParams(join_violations(&v)),
+        }
+    }
+}
+
+/// Check an order against `market` the way submitting it would, collecting
+/// every violation rather than stopping at the first.
+pub fn check_order<P, Q>(
+    market: &Market,
+    side: Side,
+    price: P,
+    quantity: Q,
+    order_type: OrderType,
+) -> Result<ValidatedOrder, OrderValidationError>
+where
+    P: TryInto<OrderPriceInput, Error = O2Error>,
+    Q: TryInto<OrderQuantityInput, Error = O2Error>,
+{
+    let mut violations = Vec::new();
+
+    let price = match price.try_into() {
+        Ok(OrderPriceInput::Unchecked(v)) => Ok(v),
+        Ok(OrderPriceInput::Checked(v)) => market.validate_price_binding(&v).map(|()| v.value()),
+        Err(e) => Err(e),
+    };
+    let quantity = match quantity.try_into() {
+        Ok(OrderQuantityInput::Unchecked(v)) => Ok(v),
+        Ok(OrderQuantityInput::Checked(v)) => {
+            market.validate_quantity_binding(&v).map(|()| v.value())
+        }
+        Err(e) => Err(e),
+    };
+
+    let chain_price = match price.and_then(|p| market.scale_price(&p)) {
+        Ok(0) => {
+            let tick_size = market.tick_size().unwrap_or(UnsignedDecimal::ONE);
+            violations.push(OrderViolation::ZeroPrice { tick_size });
+            None
+        }
+        Ok(scaled) => Some(scaled),
+        Err(e) => {
+            violations.push(OrderViolation::InvalidPrice(e.to_string()));
+            None
+        }
+    };
+    let scaled_quantity = match quantity.and_then(|q| Ok((q, market.scale_quantity(&q)?))) {
+        Ok(scaled) => Some(scaled),
+        Err(e) => {
+            violations.push(OrderViolation::InvalidQuantity(e.to_string()));
+            None
+        }
+    };
+
+    if let Err(e) = order_type.to_encoding(market) {
+        violations.push(OrderViolation::InvalidOrderType(e.to_string()));
+    } else if let OrderType::BoundedMarket {
+        max_price,
+        min_price,
+    } = &order_type
+    {
+        if min_price > max_price {
+            violations.push(OrderViolation::InvalidOrderType(format!(
+                "BoundedMarket min_price {min_price} is above max_price {max_price}"
+            )));
+        }
+    }
+
+    let (Some(chain_price), Some((requested, chain_quantity))) = (chain_price, scaled_quantity)
+    else {
+        return Err(OrderValidationError::Violations(violations));
+    };
+    let sized = market
+        .adjust_quantity(chain_price, chain_quantity)
+        .and_then(|adjusted| {
+            // Whole quote units: adjust_quantity removed any remainder.
+            let base_factor = 10u128.pow(market.base.decimals);
+            let chain_value = u128::from(chain_price) * u128::from(adjusted) / base_factor;
+            Ok((
+                adjusted,
+                market.format_price(chain_price)?,
+                market.format_quantity(adjusted)?,
+                chain_value,
+                market.quote.format_amount(chain_value)?,
+            ))
+        });
+    let (adjusted, submitted_price, submitted_quantity, chain_value, quote_value) = match sized {
+        Ok(sized) => sized,
+        Err(e) => {
+            violations.push(OrderViolation::InvalidQuantity(e.to_string()));
+            return Err(OrderValidationError::Violations(violations));
+        }
+    };
+
+    if adjusted == 0 {
+        violations.push(OrderViolation::ZeroQuantity {
+            requested,
+            price: submitted_price,
+        });
+    } else {
+        if adjusted < market.dust {
+            violations.push(OrderViolation::BelowDust {
+                quantity: submitted_quantity,
+                dust: market.base.format_amount(u128::from(market.dust))?,
+            });
+        }
+        if chain_value < u128::from(market.min_order) {
+            violations.push(OrderViolation::BelowMinOrder {
+                value: quote_value,
+                min_order: market.quote.format_amount(u128::from(market.min_order))?,
+            });
+        }
+    }
+
+    if !violations.is_empty() {
+        return Err(OrderValidationError::Violations(violations));
+    }
+    Ok(ValidatedOrder {
+        market: market.symbol_pair(),
+        side,
+        order_type,
+        price: submitted_price,
+        quantity: submitted_quantity,
+        chain_price,
+        chain_quantity: adjusted,
+        quote_value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, MarketAsset, MarketId};
+
+    fn market() -> Market {
+        Market {
+            contract_id: ContractId::new("0x01"),
+            market_id: MarketId::new("0x02"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            // 1 quote unit.
+            min_order: 1_000_000,
+            // 0.01 base.
+            dust: 10_000_000,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "fFUEL".into(),
+                asset: AssetId::new("0xaa"),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "fUSDC".into(),
+                asset: AssetId::new("0xbb"),
+                decimals: 6,
+                max_precision: 4,
+            },
+        }
+    }
+
+    fn violations(err: OrderValidationError) -> Vec<OrderViolation> {
+        match err {
+            OrderValidationError::Violations(v) => v,
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_orders_report_what_would_be_submitted() {
+        let order =
+            check_order(&market(), Side::Sell, "0.25019", "40.0009", OrderType::Spot).unwrap();
+        assert_eq!(order.price.to_string(), "0.2501");
+        assert_eq!(order.quantity.to_string(), "40");
+        assert_eq!(order.chain_price, 250_100);
+        assert_eq!(order.quote_value.to_string(), "10.004");
+        assert!(matches!(order.into_action(), Action::CreateOrder { .. }));
+    }
+
+    #[test]
+    fn every_violation_is_reported_in_human_units() {
+        let found = violations(
+            check_order(&market(), Side::Buy, "0.5", "0.005", OrderType::Spot).unwrap_err(),
+        );
+        assert_eq!(
+            found,
+            [
+                OrderViolation::BelowDust {
+                    quantity: "0.005".parse().unwrap(),
+                    dust: "0.01".parse().unwrap(),
+                },
+                OrderViolation::BelowMinOrder {
+                    value: "0.0025".parse().unwrap(),
+                    min_order: "1".parse().unwrap(),
+                },
+            ]
+        );
+
+        let found = violations(
+            check_order(
+                &market(),
+                Side::Buy,
+                "0.00001",
+                "abc",
+                OrderType::BoundedMarket {
+                    max_price: "1".parse().unwrap(),
+                    min_price: "2".parse().unwrap(),
+                },
+            )
+            .unwrap_err(),
+        );
+        assert_eq!(found.len(), 3, "{found:?}");
+        assert!(matches!(found[0], OrderViolation::ZeroPrice { .. }));
+        assert!(matches!(found[1], OrderViolation::InvalidQuantity(_)));
+        assert!(matches!(found[2], OrderViolation::InvalidOrderType(_)));
+
+        let err: O2Error = OrderValidationError::Violations(found).into();
+        assert!(matches!(err, O2Error::InvalidOrderParams(_)));
+    }
+}
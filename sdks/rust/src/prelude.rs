@@ -0,0 +1,25 @@
+//! The types most programs need, in one import.
+//!
+//! ```rust,no_run
+//! use o2_sdk::prelude::*;
+//!
+//! # async fn run() -> Result<(), O2Error> {
+//! let mut client = O2Client::new(Network::Testnet);
+//! let market: Market = client.get_market("fFUEL/fUSDC").await?;
+//! let mut depth = client.stream_depth(&market.market_id, 1).await?;
+//! while let Some(update) = depth.next().await {
+//!     println!("{:?}", update?.changes);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `StreamExt` is the `futures` extension trait, so `.next()` works on every
+//! stream the SDK returns without depending on `futures` directly.
+pub use futures_util::StreamExt;
+
+pub use crate::{
+    Action, EvmWallet, Identity, IntoMarketSymbol, Market, MarketId, MarketSymbol, Network,
+    NetworkConfig, O2Client, O2Error, Order, OrderId, OrderType, Session, Side, SignableWallet,
+    Timestamp, TradeAccountId, TypedStream, UnsignedDecimal, Wallet,
+};
@@ -0,0 +1,489 @@
+//! Typed Fuel receipts and O2 revert reasons.
+//!
+//! [`O2Error::OnChainRevert`] carries the failed transaction's receipts as
+//! opaque JSON, and [`TxStatus`](crate::TxStatus) carries them as the Fuel node
+//! reported them. [`decode_receipts`] turns either form into [`Receipt`]s, and
+//! [`O2RevertReason`] names the O2 contract error behind a revert, so retry
+//! logic can match on it instead of on the reason string:
+//!
+//! ```rust,ignore
+//! match err.revert_reason() {
+//!     Some(O2RevertReason::TraderNotWhiteListed) => client.whitelist_account(&account).await?,
+//!     Some(O2RevertReason::InvalidNonce) => { client.refresh_nonce(&mut session).await?; }
+//!     _ => return Err(err),
+//! }
+//! ```
+//!
+//! Checks the API makes before submitting, such as the minimum order size,
+//! fail with coded errors like [`O2Error::InvalidOrderParams`] instead and
+//! never reach the chain.
+use serde_json::{Map, Value};
+
+use crate::errors::O2Error;
+use crate::models::{AssetId, ContractId};
+use crate::onchain_revert::{abi_error, abi_error_by_name};
+
+/// A receipt of a Fuel transaction.
+///
+/// Contract ids are `None` for receipts produced by the script itself.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Receipt {
+    Call {
+        id: Option<ContractId>,
+        to: Option<ContractId>,
+        amount: u64,
+        asset_id: Option<AssetId>,
+    },
+    Return {
+        id: Option<ContractId>,
+        val: u64,
+    },
+    ReturnData {
+        id: Option<ContractId>,
+        data: Vec<u8>,
+    },
+    /// A VM panic. `reason` is the panic reason as reported, e.g.
+    /// `NotEnoughBalance`.
+    Panic {
+        id: Option<ContractId>,
+        reason: String,
+    },
+    /// A revert. `ra` is the revert code, or a signal such as
+    /// `REVERT_WITH_LOG` (`0xffffffffffff0006`).
+    Revert {
+        id: Option<ContractId>,
+        ra: u64,
+    },
+    Log {
+        id: Option<ContractId>,
+        ra: u64,
+        rb: u64,
+    },
+    /// Logged data. `rb` identifies the logged type; for O2 errors the first
+    /// 8 bytes of `data` are the variant index.
+    LogData {
+        id: Option<ContractId>,
+        ra: u64,
+        rb: u64,
+        data: Vec<u8>,
+    },
+    Transfer {
+        id: Option<ContractId>,
+        to: Option<ContractId>,
+        amount: u64,
+        asset_id: Option<AssetId>,
+    },
+    TransferOut {
+        id: Option<ContractId>,
+        to: Option<String>,
+        amount: u64,
+        asset_id: Option<AssetId>,
+    },
+    ScriptResult {
+        success: bool,
+        gas_used: u64,
+    },
+    /// A receipt type not decoded here, with its JSON.
+    Other {
+        kind: String,
+        raw: Value,
+    },
+}
+
+/// Decode receipts from JSON.
+///
+/// Accepts the Fuel GraphQL form (`{"receiptType": "LOG_DATA", ...}`, as in
+/// [`TxStatus`](crate::TxStatus)) and the externally tagged form
+/// (`{"LogData": {...}}`). Anything that is not a list of receipts decodes to
+/// an empty list.
+pub fn decode_receipts(receipts: &Value) -> Vec<Receipt> {
+    match receipts {
+        Value::Array(items) => items.iter().map(decode_receipt).collect(),
+        Value::String(text) => serde_json::from_str::<Value>(text)
+            .ok()
+            .filter(Value::is_array)
+            .map(|v| decode_receipts(&v))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn decode_receipt(value: &Value) -> Receipt {
+    let other = || Receipt::Other {
+        kind: String::new(),
+        raw: value.clone(),
+    };
+    let Value::Object(map) = value else {
+        return other();
+    };
+    let (kind, fields) = match map.get("receiptType").and_then(Value::as_str) {
+        Some(kind) => (kind, map),
+        None => match map.iter().next() {
+            Some((kind, Value::Object(fields))) if map.len() == 1 => (kind.as_str(), fields),
+            _ => return other(),
+        },
+    };
+    let f = Fields(fields);
+    match kind.replace('_', "").to_ascii_lowercase().as_str() {
+        "call" => Receipt::Call {
+            id: f.contract("id"),
+            to: f.contract("to"),
+            amount: f.u64("amount"),
+            asset_id: f.asset(),
+        },
+        "return" => Receipt::Return {
+            id: f.contract("id"),
+            val: f.u64("val"),
+        },
+        "returndata" => Receipt::ReturnData {
+            id: f.contract("id"),
+            data: f.bytes("data"),
+        },
+        "panic" => Receipt::Panic {
+            id: f.contract("id"),
+            reason: f.panic_reason(),
+        },
+        "revert" => Receipt::Revert {
+            id: f.contract("id"),
+            ra: f.u64("ra"),
+        },
+        "log" => Receipt::Log {
+            id: f.contract("id"),
+            ra: f.u64("ra"),
+            rb: f.u64("rb"),
+        },
+        "logdata" => Receipt::LogData {
+            id: f.contract("id"),
+            ra: f.u64("ra"),
+            rb: f.u64("rb"),
+            data: f.bytes("data"),
+        },
+        "transfer" => Receipt::Transfer {
+            id: f.contract("id"),
+            to: f.contract("to"),
+            amount: f.u64("amount"),
+            asset_id: f.asset(),
+        },
+        "transferout" => Receipt::TransferOut {
+            id: f.contract("id"),
+            to: f
+                .get(&["to", "toAddress"])
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            amount: f.u64("amount"),
+            asset_id: f.asset(),
+        },
+        "scriptresult" => Receipt::ScriptResult {
+            success: match f.get(&["result"]) {
+                Some(Value::String(s)) => s == "Success" || s == "0",
+                Some(v) => v.as_u64() == Some(0),
+                None => false,
+            },
+            gas_used: f.u64("gasUsed"),
+        },
+        _ => Receipt::Other {
+            kind: kind.to_string(),
+            raw: value.clone(),
+        },
+    }
+}
+
+/// Field access that accepts both camelCase and snake_case names.
+struct Fields<'a>(&'a Map<String, Value>);
+
+impl Fields<'_> {
+    fn get(&self, names: &[&str]) -> Option<&Value> {
+        names.iter().find_map(|name| self.0.get(*name))
+    }
+
+    fn named(&self, name: &str) -> Option<&Value> {
+        let snake: String = name
+            .chars()
+            .flat_map(|c| {
+                let lower = c.to_ascii_lowercase();
+                (c.is_ascii_uppercase().then_some('_').into_iter()).chain([lower])
+            })
+            .collect();
+        self.get(&[name, &snake])
+    }
+
+    fn u64(&self, name: &str) -> u64 {
+        match self.named(name) {
+            Some(Value::Number(n)) => n.as_u64().unwrap_or_default(),
+            Some(Value::String(s)) => match s.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).unwrap_or_default(),
+                None => s.parse().unwrap_or_default(),
+            },
+            _ => 0,
+        }
+    }
+
+    fn bytes(&self, name: &str) -> Vec<u8> {
+        match self.named(name) {
+            Some(Value::String(s)) => hex::decode(s.trim_start_matches("0x")).unwrap_or_default(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn contract(&self, name: &str) -> Option<ContractId> {
+        let value = match name {
+            "id" => self.get(&["id", "contractId", "contract_id"]),
+            _ => self.named(name),
+        };
+        value
+            .and_then(Value::as_str)
+            .filter(|s| !s.trim_start_matches("0x").chars().all(|c| c == '0'))
+            .map(ContractId::new)
+    }
+
+    fn asset(&self) -> Option<AssetId> {
+        self.named("assetId")
+            .and_then(Value::as_str)
+            .map(AssetId::new)
+    }
+
+    fn panic_reason(&self) -> String {
+        match self.named("reason") {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Object(inner)) => match inner.get("reason") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            },
+            Some(other) => other.to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+/// The O2 contract error that made a transaction revert.
+///
+/// Errors a trading client can act on have their own variants; the rest are
+/// reported as [`O2RevertReason::Contract`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum O2RevertReason {
+    // Order creation
+    InvalidOrderArgs,
+    /// The order's input amount does not match price times quantity, usually
+    /// for lack of balance.
+    InvalidInputAmount,
+    InvalidAsset,
+    PriceExceedsRange,
+    PricePrecision,
+    /// Internal order book state error; resubmitting may succeed.
+    InvalidHeapPrices,
+    /// `price * quantity` is not a multiple of `10^base_decimals`.
+    FractionalPrice,
+    /// A FillOrKill order could not be filled completely.
+    OrderNotFilled,
+    /// A PostOnly order would have crossed the spread.
+    OrderPartiallyFilled,
+    TraderNotWhiteListed,
+    TraderBlackListed,
+    InvalidMarketOrder,
+    InvalidMarketOrderArgs,
+    // Order cancellation
+    NotOrderOwner,
+    // Trade account
+    /// The nonce was stale; refresh it and resubmit.
+    InvalidNonce,
+    SessionInThePast,
+    NoApprovedContractIdsProvided,
+    InvalidSigner,
+    InvalidCaller,
+    AmountIsZero,
+    NotEnoughBalance,
+    TradeAccountNotRegistered,
+    // Market
+    MarketPaused,
+    /// Another O2 contract error, by short enum name and variant.
+    Contract {
+        error: &'static str,
+        variant: &'static str,
+    },
+    /// A Fuel VM panic, such as `NotEnoughBalance` or `OutOfGas`.
+    Panic(String),
+    /// Nothing in the receipts or reason identified the error.
+    Unknown,
+}
+
+impl O2RevertReason {
+    fn from_abi(enum_name: &'static str, variant: &'static str) -> Self {
+        let error = enum_name.rsplit("::").next().unwrap_or(enum_name);
+        match (error, variant) {
+            ("OrderCreationError", "InvalidOrderArgs") => Self::InvalidOrderArgs,
+            ("OrderCreationError", "InvalidInputAmount") => Self::InvalidInputAmount,
+            ("OrderCreationError", "InvalidAsset") => Self::InvalidAsset,
+            ("OrderCreationError", "PriceExceedsRange") => Self::PriceExceedsRange,
+            ("OrderCreationError", "PricePrecision") => Self::PricePrecision,
+            ("OrderCreationError", "InvalidHeapPrices") => Self::InvalidHeapPrices,
+            ("OrderCreationError", "FractionalPrice") => Self::FractionalPrice,
+            ("OrderCreationError", "OrderNotFilled") => Self::OrderNotFilled,
+            ("OrderCreationError", "OrderPartiallyFilled") => Self::OrderPartiallyFilled,
+            ("OrderCreationError", "TraderNotWhiteListed") => Self::TraderNotWhiteListed,
+            ("OrderCreationError", "TraderBlackListed") => Self::TraderBlackListed,
+            ("OrderCreationError", "InvalidMarketOrder") => Self::InvalidMarketOrder,
+            ("OrderCreationError", "InvalidMarketOrderArgs") => Self::InvalidMarketOrderArgs,
+            ("OrderCancelError", "NotOrderOwner") => Self::NotOrderOwner,
+            ("NonceError", "InvalidNonce") => Self::InvalidNonce,
+            ("SessionError", "SessionInThePast") => Self::SessionInThePast,
+            ("SessionError", "NoApprovedContractIdsProvided") => {
+                Self::NoApprovedContractIdsProvided
+            }
+            ("SignerError", "InvalidSigner") => Self::InvalidSigner,
+            ("CallerError", "InvalidCaller") => Self::InvalidCaller,
+            ("WithdrawError", "AmountIsZero") => Self::AmountIsZero,
+            ("WithdrawError", "NotEnoughBalance") => Self::NotEnoughBalance,
+            ("TradeAccountRegistryError", "TradeAccountNotRegistered") => {
+                Self::TradeAccountNotRegistered
+            }
+            ("PauseError", "Paused") => Self::MarketPaused,
+            _ => Self::Contract { error, variant },
+        }
+    }
+
+    /// The error logged just before the last revert, or failing that, the
+    /// first VM panic.
+    pub fn from_receipts(receipts: &[Receipt]) -> Self {
+        let revert = receipts
+            .iter()
+            .rposition(|r| matches!(r, Receipt::Revert { .. }))
+            .unwrap_or(receipts.len());
+        let logged = receipts[..revert].iter().rev().find_map(|r| match r {
+            Receipt::LogData { rb, data, .. } if data.len() >= 8 => {
+                let discriminant = u64::from_be_bytes(data[..8].try_into().ok()?);
+                abi_error(*rb, discriminant)
+            }
+            _ => None,
+        });
+        if let Some((enum_name, variant)) = logged {
+            return Self::from_abi(enum_name, variant);
+        }
+        receipts
+            .iter()
+            .find_map(|r| match r {
+                Receipt::Panic { reason, .. } => Some(Self::Panic(reason.clone())),
+                _ => None,
+            })
+            .unwrap_or(Self::Unknown)
+    }
+
+    /// Decode the error of an [`O2Error::OnChainRevert`] from its receipts,
+    /// falling back to its (already decoded) reason.
+    pub fn from_revert(reason: &str, receipts: Option<&Value>) -> Self {
+        let decoded = receipts.map(decode_receipts).unwrap_or_default();
+        match Self::from_receipts(&decoded) {
+            Self::Unknown => abi_error_by_name(reason)
+                .map(|(enum_name, variant)| Self::from_abi(enum_name, variant))
+                .unwrap_or(Self::Unknown),
+            known => known,
+        }
+    }
+
+    /// Returns true if resubmitting may succeed: after refreshing the nonce
+    /// for [`O2RevertReason::InvalidNonce`], or as is for
+    /// [`O2RevertReason::InvalidHeapPrices`].
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::InvalidNonce | Self::InvalidHeapPrices)
+    }
+}
+
+impl O2Error {
+    /// The O2 contract error behind an [`O2Error::OnChainRevert`]; `None` for
+    /// other errors.
+    pub fn revert_reason(&self) -> Option<O2RevertReason> {
+        match self {
+            O2Error::OnChainRevert {
+                reason, receipts, ..
+            } => Some(O2RevertReason::from_revert(reason, receipts.as_ref())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_graphql_and_tagged_receipts() {
+        let graphql = json!([
+            { "receiptType": "CALL", "id": "0x0000", "to": "0xab", "amount": "5", "assetId": "0xcd" },
+            { "receiptType": "LOG_DATA", "id": "0xab", "ra": "0", "rb": "14888260448086063780",
+              "data": "0x0000000000000001" },
+            { "receiptType": "REVERT", "id": "0xab", "ra": "18446744073709486086" },
+            { "receiptType": "SCRIPT_RESULT", "result": "1", "gasUsed": "900" },
+            { "receiptType": "MINT", "subId": "0x01" },
+        ]);
+        let receipts = decode_receipts(&graphql);
+        assert_eq!(
+            receipts[0],
+            Receipt::Call {
+                id: None,
+                to: Some(ContractId::new("0xab")),
+                amount: 5,
+                asset_id: Some(AssetId::new("0xcd")),
+            }
+        );
+        assert_eq!(
+            receipts[3],
+            Receipt::ScriptResult {
+                success: false,
+                gas_used: 900,
+            }
+        );
+        assert!(matches!(&receipts[4], Receipt::Other { kind, .. } if kind == "MINT"));
+        assert_eq!(
+            O2RevertReason::from_receipts(&receipts),
+            O2RevertReason::NotEnoughBalance
+        );
+
+        let tagged = json!([
+            { "LogData": { "id": "0xab", "ra": 0, "rb": 12033795032676640771u64,
+                           "data": [0, 0, 0, 0, 0, 0, 0, 9] } },
+            { "Revert": { "id": "0xab", "ra": 18446744073709486086u64 } },
+        ]);
+        assert_eq!(
+            O2RevertReason::from_receipts(&decode_receipts(&tagged)),
+            O2RevertReason::TraderNotWhiteListed
+        );
+    }
+
+    #[test]
+    fn falls_back_to_panics_and_the_reason_string() {
+        let panicked = decode_receipts(&json!([
+            { "Panic": { "id": "0xab", "reason": { "reason": "OutOfGas", "instruction": 0 } } },
+        ]));
+        assert_eq!(
+            O2RevertReason::from_receipts(&panicked),
+            O2RevertReason::Panic("OutOfGas".into())
+        );
+
+        let err = O2Error::OnChainRevert {
+            message: "Failed to process transaction".into(),
+            reason: "OrderCreationError::FractionalPrice \u{2014} price \u{00d7} quantity ..."
+                .into(),
+            receipts: None,
+        };
+        assert_eq!(err.revert_reason(), Some(O2RevertReason::FractionalPrice));
+        assert_eq!(
+            O2RevertReason::from_revert("OrderBookInitializationError::InvalidAsset", None),
+            O2RevertReason::Contract {
+                error: "OrderBookInitializationError",
+                variant: "InvalidAsset",
+            }
+        );
+        assert_eq!(
+            O2RevertReason::from_revert("Revert(42)", None),
+            O2RevertReason::Unknown
+        );
+        assert!(O2Error::Other("x".into()).revert_reason().is_none());
+    }
+}
@@ -0,0 +1,447 @@
+//! Cross-checking local bookkeeping against the exchange.
+//!
+//! A bot that tracks its own resting orders and balances drifts from the
+//! exchange sooner or later: a fill is missed across a reconnect, an order is
+//! cancelled out from under it, or trade proceeds are left unlocked in an
+//! order-book contract until someone settles them. [`reconcile`] compares what
+//! the bot believes, an [`Expected`], with the account's open orders and
+//! balances on the exchange and lists every [`Discrepancy`].
+//!
+//! [`O2Client::reconcile`](crate::O2Client::reconcile) fetches the exchange
+//! side, and [`O2Client::heal`](crate::O2Client::heal) submits a
+//! `SettleBalance` for every market a report finds unsettled.
+//!
+//! Prices, sizes and balances are chain-scaled integers.
+//!
+//! ```rust,ignore
+//! let expected = Expected::new()
+//!     .with_book(mine.snapshot())
+//!     .with_balance("fUSDC", usdc_total);
+//! let report = client.reconcile(&session.trade_account_id, &expected).await?;
+//! for discrepancy in &report.discrepancies {
+//!     println!("{discrepancy}");
+//! }
+//! if !report.unsettled_markets().is_empty() {
+//!     client.heal(&mut session, &report).await?;
+//! }
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::models::{BalanceResponse, Market, MarketId, Order, OrderId, Side};
+use crate::my_book::MyBook;
+
+/// What the local bookkeeping believes about an account.
+#[derive(Debug, Clone, Default)]
+pub struct Expected {
+    books: Vec<MyBook>,
+    balances: BTreeMap<String, u128>,
+}
+
+impl Expected {
+    /// Expect nothing: only unsettled balances are reported.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect exactly `book`'s orders to be open on its market.
+    ///
+    /// An empty book checks that the account has no open orders there.
+    pub fn with_book(mut self, book: MyBook) -> Self {
+        self.books.retain(|b| b.market_id() != book.market_id());
+        self.books.push(book);
+        self
+    }
+
+    /// Expect the account to hold `total` of `asset` (locked plus unlocked).
+    pub fn with_balance(mut self, asset: impl Into<String>, total: u128) -> Self {
+        self.balances.insert(asset.into(), total);
+        self
+    }
+
+    /// The books whose markets are checked for open orders.
+    pub fn books(&self) -> &[MyBook] {
+        &self.books
+    }
+
+    /// Expected totals by asset symbol.
+    pub fn balances(&self) -> &BTreeMap<String, u128> {
+        &self.balances
+    }
+}
+
+/// One way the local bookkeeping disagrees with the exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The exchange shows a different unfilled quantity than the local book,
+    /// almost always a fill the local book never saw.
+    MissedFill {
+        market_id: MarketId,
+        order_id: OrderId,
+        local_remaining: u64,
+        exchange_remaining: u64,
+    },
+    /// The local book holds an order the exchange no longer has open. It was
+    /// filled, cancelled or closed without the local book noticing.
+    StaleOrder {
+        market_id: MarketId,
+        order_id: OrderId,
+        local_remaining: u64,
+    },
+    /// The exchange has an open order the local book does not know about.
+    UnknownOrder {
+        market_id: MarketId,
+        order_id: OrderId,
+        side: Side,
+        price: u64,
+        remaining: u64,
+    },
+    /// The account's total of an asset differs from the expected total.
+    BalanceDrift {
+        asset: String,
+        expected: u128,
+        actual: u128,
+    },
+    /// Funds left unlocked in an order-book contract. A `SettleBalance` on the
+    /// market moves them back to the trading account.
+    Unsettled {
+        asset: String,
+        /// The order-book contract as the balance endpoint names it.
+        order_book: String,
+        /// The market of that contract, if it is a known market.
+        market_id: Option<MarketId>,
+        amount: u128,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissedFill {
+                market_id,
+                order_id,
+                local_remaining,
+                exchange_remaining,
+            } => write!(
+                f,
+                "missed fill on {order_id} ({market_id}): {local_remaining} remaining locally, {exchange_remaining} on the exchange"
+            ),
+            Self::StaleOrder {
+                market_id,
+                order_id,
+                local_remaining,
+            } => write!(
+                f,
+                "stale order {order_id} ({market_id}): {local_remaining} remaining locally, no longer open"
+            ),
+            Self::UnknownOrder {
+                market_id,
+                order_id,
+                side,
+                price,
+                remaining,
+            } => write!(
+                f,
+                "unknown order {order_id} ({market_id}): {side:?} {remaining} at {price}"
+            ),
+            Self::BalanceDrift {
+                asset,
+                expected,
+                actual,
+            } => write!(f, "{asset} balance drift: expected {expected}, actual {actual}"),
+            Self::Unsettled {
+                asset,
+                order_book,
+                amount,
+                ..
+            } => write!(f, "{amount} {asset} unsettled in {order_book}"),
+        }
+    }
+}
+
+/// Every [`Discrepancy`] found by one reconciliation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Order discrepancies market by market, then balance drift and
+    /// unsettled funds by asset.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl ReconcileReport {
+    /// True if the local bookkeeping matches the exchange and nothing is
+    /// waiting to be settled.
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    /// Known markets holding unsettled funds, each listed once.
+    pub fn unsettled_markets(&self) -> Vec<MarketId> {
+        let mut markets: Vec<MarketId> = Vec::new();
+        for d in &self.discrepancies {
+            if let Discrepancy::Unsettled {
+                market_id: Some(market_id),
+                ..
+            } = d
+            {
+                if !markets.contains(market_id) {
+                    markets.push(market_id.clone());
+                }
+            }
+        }
+        markets
+    }
+}
+
+/// Compare `expected` with the exchange.
+///
+/// `open_orders` holds the account's open orders on each market of
+/// `expected`; a market missing from it is skipped. `balances` is keyed by
+/// asset symbol, as [`O2Client::get_balances`](crate::O2Client::get_balances)
+/// returns it, and `markets` names the order-book contracts in it.
+pub fn reconcile(
+    expected: &Expected,
+    open_orders: &HashMap<MarketId, Vec<Order>>,
+    balances: &HashMap<String, BalanceResponse>,
+    markets: &[Market],
+) -> ReconcileReport {
+    let mut discrepancies = Vec::new();
+
+    for local in &expected.books {
+        let market_id = local.market_id();
+        let Some(orders) = open_orders.get(market_id) else {
+            continue;
+        };
+        let mut exchange = MyBook::new(market_id.clone());
+        exchange.reconcile(orders);
+
+        let mut local_orders: Vec<_> = local.orders().collect();
+        local_orders.sort_by(|a, b| a.order_id.as_str().cmp(b.order_id.as_str()));
+        for order in local_orders {
+            match exchange.order(&order.order_id) {
+                Some(open) if open.remaining != order.remaining => {
+                    discrepancies.push(Discrepancy::MissedFill {
+                        market_id: market_id.clone(),
+                        order_id: order.order_id.clone(),
+                        local_remaining: order.remaining,
+                        exchange_remaining: open.remaining,
+                    });
+                }
+                Some(_) => {}
+                None => discrepancies.push(Discrepancy::StaleOrder {
+                    market_id: market_id.clone(),
+                    order_id: order.order_id.clone(),
+                    local_remaining: order.remaining,
+                }),
+            }
+        }
+
+        let mut unknown: Vec<_> = exchange
+            .orders()
+            .filter(|o| local.order(&o.order_id).is_none())
+            .collect();
+        unknown.sort_by(|a, b| a.order_id.as_str().cmp(b.order_id.as_str()));
+        for order in unknown {
+            discrepancies.push(Discrepancy::UnknownOrder {
+                market_id: market_id.clone(),
+                order_id: order.order_id.clone(),
+                side: order.side,
+                price: order.price,
+                remaining: order.remaining,
+            });
+        }
+    }
+
+    for (asset, &expected_total) in &expected.balances {
+        let actual = balances
+            .get(asset)
+            .map(|b| b.total_locked.saturating_add(b.total_unlocked))
+            .unwrap_or(0);
+        if actual != expected_total {
+            discrepancies.push(Discrepancy::BalanceDrift {
+                asset: asset.clone(),
+                expected: expected_total,
+                actual,
+            });
+        }
+    }
+
+    let sorted: BTreeMap<_, _> = balances.iter().collect();
+    for (asset, balance) in sorted {
+        let books: BTreeMap<_, _> = balance.order_books.iter().collect();
+        for (order_book, held) in books {
+            if held.unlocked == 0 {
+                continue;
+            }
+            discrepancies.push(Discrepancy::Unsettled {
+                asset: asset.clone(),
+                order_book: order_book.clone(),
+                market_id: market_of(markets, order_book),
+                amount: held.unlocked,
+            });
+        }
+    }
+
+    ReconcileReport { discrepancies }
+}
+
+/// The market whose order-book contract (or market id) is `order_book`.
+fn market_of(markets: &[Market], order_book: &str) -> Option<MarketId> {
+    markets
+        .iter()
+        .find(|m| {
+            m.contract_id.as_str().eq_ignore_ascii_case(order_book)
+                || m.market_id.as_str().eq_ignore_ascii_case(order_book)
+        })
+        .map(|m| m.market_id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{AssetId, ContractId, MarketAsset};
+
+    fn order(id: &str, side: &str, price: u64, qty: u64, filled: u64) -> Order {
+        serde_json::from_value(json!({
+            "order_id": id,
+            "side": side,
+            "order_type": "Spot",
+            "quantity": qty.to_string(),
+            "quantity_fill": filled.to_string(),
+            "price": price.to_string(),
+            "market_id": "0xaa",
+        }))
+        .unwrap()
+    }
+
+    fn balance(locked: u128, unlocked: u128, books: serde_json::Value) -> BalanceResponse {
+        serde_json::from_value(json!({
+            "order_books": books,
+            "total_locked": locked.to_string(),
+            "total_unlocked": unlocked.to_string(),
+            "trading_account_balance": "0",
+        }))
+        .unwrap()
+    }
+
+    fn market() -> Market {
+        let asset = |symbol: &str, id: &str| MarketAsset {
+            symbol: symbol.into(),
+            asset: AssetId::new(id),
+            decimals: 9,
+            max_precision: 3,
+        };
+        Market {
+            contract_id: ContractId::new("0xB00C"),
+            market_id: MarketId::new("0xaa"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 0,
+            dust: 0,
+            price_window: 0,
+            base: asset("fFUEL", "0x01"),
+            quote: asset("fUSDC", "0x02"),
+        }
+    }
+
+    #[test]
+    fn reports_order_and_balance_discrepancies() {
+        let mut local = MyBook::new(MarketId::new("0xaa"));
+        local.apply_order(&order("0x01", "Buy", 100, 10, 0));
+        local.apply_order(&order("0x02", "Buy", 99, 5, 0));
+        local.apply_order(&order("0x03", "Sell", 101, 4, 0));
+        let expected = Expected::new()
+            .with_book(local)
+            .with_balance("fUSDC", 1_000)
+            .with_balance("fFUEL", 50);
+
+        // 0x01 partly filled, 0x02 gone, 0x03 matches, 0x04 unknown.
+        let open = HashMap::from([(
+            MarketId::new("0xaa"),
+            vec![
+                order("0x01", "Buy", 100, 10, 4),
+                order("0x03", "Sell", 101, 4, 0),
+                order("0x04", "Sell", 102, 2, 0),
+            ],
+        )]);
+        let balances = HashMap::from([
+            ("fUSDC".to_string(), balance(600, 400, json!({}))),
+            (
+                "fFUEL".to_string(),
+                balance(
+                    4,
+                    40,
+                    json!({"0xb00c": {"locked": "4", "unlocked": "6", "fee": "0"}}),
+                ),
+            ),
+        ]);
+
+        let report = reconcile(&expected, &open, &balances, &[market()]);
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::MissedFill {
+                    market_id: MarketId::new("0xaa"),
+                    order_id: OrderId::new("0x01"),
+                    local_remaining: 10,
+                    exchange_remaining: 6,
+                },
+                Discrepancy::StaleOrder {
+                    market_id: MarketId::new("0xaa"),
+                    order_id: OrderId::new("0x02"),
+                    local_remaining: 5,
+                },
+                Discrepancy::UnknownOrder {
+                    market_id: MarketId::new("0xaa"),
+                    order_id: OrderId::new("0x04"),
+                    side: Side::Sell,
+                    price: 102,
+                    remaining: 2,
+                },
+                Discrepancy::BalanceDrift {
+                    asset: "fFUEL".into(),
+                    expected: 50,
+                    actual: 44,
+                },
+                Discrepancy::Unsettled {
+                    asset: "fFUEL".into(),
+                    order_book: "0xb00c".into(),
+                    market_id: Some(MarketId::new("0xaa")),
+                    amount: 6,
+                },
+            ]
+        );
+        assert_eq!(report.unsettled_markets(), vec![MarketId::new("0xaa")]);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn matching_state_is_clean_and_untracked_markets_are_skipped() {
+        let mut local = MyBook::new(MarketId::new("0xaa"));
+        local.apply_order(&order("0x01", "Buy", 100, 10, 2));
+        let other = MyBook::new(MarketId::new("0xbb"));
+        let expected = Expected::new()
+            .with_book(local)
+            .with_book(other)
+            .with_balance("fUSDC", 1_000);
+        let open = HashMap::from([(
+            MarketId::new("0xaa"),
+            vec![order("0x01", "Buy", 100, 10, 2)],
+        )]);
+        let balances = HashMap::from([(
+            "fUSDC".to_string(),
+            balance(
+                800,
+                200,
+                json!({"0xb00c": {"locked": "800", "unlocked": "0", "fee": "0"}}),
+            ),
+        )]);
+
+        let report = reconcile(&expected, &open, &balances, &[market()]);
+        assert!(report.is_clean(), "{:?}", report.discrepancies);
+        assert!(report.unsettled_markets().is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+//! Recording WebSocket sessions and replaying them offline.
+//!
+//! A [`Recorder`] attached with [`O2WebSocket::record`] (or
+//! [`O2Client::record_ws`](crate::O2Client::record_ws)) appends every text
+//! frame sent or received to a newline-delimited JSON file, one
+//! [`RecordedFrame`] per line, with the raw frame text and the time it passed
+//! through the client:
+//!
+//! ```text
+//! {"timestamp":1718000000123,"direction":"out","frame":"{\"action\":\"subscribe_trades\",...}"}
+//! {"timestamp":1718000000345,"direction":"in","frame":"{\"action\":\"subscribe_trades\",...}"}
+//! ```
+//!
+//! [`ReplayWebSocket`] plays the received frames of such a file back through
+//! the same routing as a live connection, so the `stream_*` methods yield
+//! what the recorded session saw: to reproduce a production incident, or to
+//! test a strategy against a fixed, repeatable feed.
+//!
+//! ```rust,ignore
+//! client.record_ws(Recorder::create("session.ndjson")?).await?;
+//! // ... later, offline:
+//! let mut replay = ReplayWebSocket::open("session.ndjson", ReplaySpeed::Accelerated(10.0))?;
+//! let mut trades = replay.stream_trades(market_id).await?;
+//! replay.start();
+//! while let Some(update) = trades.next().await {
+//!     strategy.on_trades(update?);
+//! }
+//! ```
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMsg};
+
+use crate::errors::O2Error;
+use crate::models::{BalanceUpdate, DepthUpdate, Identity, NonceUpdate, OrderUpdate, TradeUpdate};
+use crate::runtime;
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::{DepthPrecision, O2WebSocket, TypedStream};
+
+/// Whether a frame was sent to or received from the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// When the frame was sent or received.
+    #[serde(with = "crate::timestamp::unix_millis")]
+    pub timestamp: Timestamp,
+    pub direction: Direction,
+    /// The frame's text, exactly as on the wire.
+    pub frame: String,
+}
+
+/// Appends frames to a recording file. Clones share the file.
+///
+/// Each line is flushed as it is written, so a recording survives the
+/// process crashing. Write errors are logged and the frame is dropped; they
+/// never disturb the connection.
+#[derive(Clone)]
+pub struct Recorder {
+    out: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl Recorder {
+    /// Record to `path`, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| {
+            O2Error::Other(format!("cannot create recording {}: {e}", path.display()))
+        })?;
+        Ok(Self::from_file(file))
+    }
+
+    /// Record to the end of `path`, creating it if needed.
+    pub fn append(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                O2Error::Other(format!("cannot open recording {}: {e}", path.display()))
+            })?;
+        Ok(Self::from_file(file))
+    }
+
+    fn from_file(file: File) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(BufWriter::new(file))),
+        }
+    }
+
+    pub(crate) fn record(&self, direction: Direction, frame: &str) {
+        let line = RecordedFrame {
+            timestamp: Timestamp::now(),
+            direction,
+            frame: frame.to_string(),
+        };
+        let mut out = self.out.lock().unwrap_or_else(|p| p.into_inner());
+        let written = serde_json::to_writer(&mut *out, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|()| out.write_all(b"\n"))
+            .and_then(|()| out.flush());
+        if let Err(e) = written {
+            debug!("recorder.write error={}", e);
+        }
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Recorder")
+    }
+}
+
+/// Read every frame of a recording, in file order. Blank lines are skipped.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedFrame>, O2Error> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .map_err(|e| O2Error::Other(format!("cannot read recording {}: {e}", path.display())))?;
+    let mut frames = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| {
+            O2Error::Other(format!("cannot read recording {}: {e}", path.display()))
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = serde_json::from_str(&line)
+            .map_err(|e| O2Error::ParseError(format!("{} line {}: {e}", path.display(), n + 1)))?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// How fast a [`ReplayWebSocket`] plays its frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// With the recorded gaps between frames.
+    RealTime,
+    /// With the recorded gaps divided by the factor, e.g. `10.0` for ten
+    /// times faster. A factor of zero or less plays as [`Instant`](Self::Instant).
+    Accelerated(f64),
+    /// Back to back, without waiting.
+    Instant,
+}
+
+impl ReplaySpeed {
+    fn scale(self, gap: Duration) -> Duration {
+        match self {
+            Self::RealTime => gap,
+            Self::Accelerated(factor) if factor > 0.0 => gap.div_f64(factor),
+            Self::Accelerated(_) | Self::Instant => Duration::ZERO,
+        }
+    }
+}
+
+/// Replays the received frames of a recording through the streams of an
+/// [`O2WebSocket`], without a server.
+///
+/// Open the streams, then [`start`](Self::start) playback: frames played
+/// before a stream is opened are not delivered to it. Messages are routed as
+/// on a live connection, by market or identity, so each stream only sees the
+/// frames it subscribed to. Every stream ends after the last frame.
+pub struct ReplayWebSocket {
+    ws: O2WebSocket,
+    frames: Vec<RecordedFrame>,
+    speed: ReplaySpeed,
+}
+
+impl ReplayWebSocket {
+    /// Replay the recording at `path`.
+    pub fn open(path: impl AsRef<Path>, speed: ReplaySpeed) -> Result<Self, O2Error> {
+        Ok(Self::from_frames(read_recording(path)?, speed))
+    }
+
+    /// Replay `frames`. Sent frames are skipped.
+    pub fn from_frames(frames: Vec<RecordedFrame>, speed: ReplaySpeed) -> Self {
+        let frames = frames
+            .into_iter()
+            .filter(|f| f.direction == Direction::In)
+            .collect();
+        Self {
+            ws: O2WebSocket::offline(),
+            frames,
+            speed,
+        }
+    }
+
+    /// Begin playback. Calling it again does nothing.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn start(&mut self) {
+        let frames = std::mem::take(&mut self.frames);
+        let Some(first) = frames.first().map(|f| f.timestamp) else {
+            if !self.ws.is_terminated() && !self.ws.is_connected() {
+                // Nothing to play: end the streams at once.
+                self.ws
+                    .replay(futures_util::stream::empty::<Result<WsMsg, WsError>>());
+            }
+            return;
+        };
+        debug!(
+            "recorder.replay frames={} speed={:?}",
+            frames.len(),
+            self.speed
+        );
+        let speed = self.speed;
+        let started = runtime::now();
+        let frames = futures_util::stream::iter(frames)
+            .then(move |frame| async move {
+                let gap = Duration::from_millis(
+                    u64::try_from(
+                        frame
+                            .timestamp
+                            .as_unix_millis()
+                            .saturating_sub(first.as_unix_millis()),
+                    )
+                    .unwrap_or(u64::MAX),
+                );
+                runtime::sleep_until(started + speed.scale(gap)).await;
+                Ok::<_, WsError>(WsMsg::Text(frame.frame))
+            })
+            .boxed();
+        self.ws.replay(frames);
+    }
+
+    /// True once every frame has been played.
+    pub fn is_finished(&self) -> bool {
+        self.ws.is_terminated()
+    }
+
+    /// See [`O2WebSocket::stream_depth`].
+    pub async fn stream_depth(
+        &self,
+        market_id: &str,
+        precision: &DepthPrecision,
+    ) -> Result<TypedStream<DepthUpdate>, O2Error> {
+        self.ws.stream_depth(market_id, precision).await
+    }
+
+    /// See [`O2WebSocket::stream_orders`].
+    pub async fn stream_orders(
+        &self,
+        identities: &[Identity],
+    ) -> Result<TypedStream<OrderUpdate>, O2Error> {
+        self.ws.stream_orders(identities).await
+    }
+
+    /// See [`O2WebSocket::stream_trades`].
+    pub async fn stream_trades(
+        &self,
+        market_id: &str,
+    ) -> Result<TypedStream<TradeUpdate>, O2Error> {
+        self.ws.stream_trades(market_id).await
+    }
+
+    /// See [`O2WebSocket::stream_balances`].
+    pub async fn stream_balances(
+        &self,
+        identities: &[Identity],
+    ) -> Result<TypedStream<BalanceUpdate>, O2Error> {
+        self.ws.stream_balances(identities).await
+    }
+
+    /// See [`O2WebSocket::stream_nonce`].
+    pub async fn stream_nonce(
+        &self,
+        identities: &[Identity],
+    ) -> Result<TypedStream<NonceUpdate>, O2Error> {
+        self.ws.stream_nonce(identities).await
+    }
+
+    /// See [`O2WebSocket::subscribe_raw`].
+    pub async fn subscribe_raw(
+        &self,
+        subscription: serde_json::Value,
+    ) -> Result<TypedStream<serde_json::Value>, O2Error> {
+        self.ws.subscribe_raw(subscription).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn frame(millis: u64, direction: Direction, value: serde_json::Value) -> RecordedFrame {
+        RecordedFrame {
+            timestamp: Timestamp::from_unix_millis(millis),
+            direction,
+            frame: value.to_string(),
+        }
+    }
+
+    fn trades(market_id: &str, price: u64) -> serde_json::Value {
+        json!({
+            "action": "subscribe_trades",
+            "market_id": market_id,
+            "seen_timestamp": 1,
+            "trades": [{
+                "trade_id": "0x01", "side": "Buy", "total": "0",
+                "quantity": "1", "price": price.to_string(), "timestamp": 1,
+            }],
+        })
+    }
+
+    #[test]
+    fn recordings_round_trip_through_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("o2-recorder-test-{}.ndjson", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(Direction::Out, r#"{"action":"subscribe_trades"}"#);
+        recorder.clone().record(Direction::In, "not json");
+        Recorder::append(&path).unwrap().record(Direction::In, "{}");
+
+        let frames = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = frames
+            .iter()
+            .map(|f| (f.direction, f.frame.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (Direction::Out, r#"{"action":"subscribe_trades"}"#),
+                (Direction::In, "not json"),
+                (Direction::In, "{}"),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_routes_received_frames_at_the_recorded_pace() {
+        let mut replay = ReplayWebSocket::from_frames(
+            vec![
+                frame(1_000, Direction::Out, json!({"action": "subscribe_trades"})),
+                frame(1_000, Direction::In, trades("0xaa", 100)),
+                frame(2_000, Direction::In, trades("0xbb", 500)),
+                frame(5_000, Direction::In, trades("0xaa", 101)),
+            ],
+            ReplaySpeed::Accelerated(2.0),
+        );
+        let mut stream = replay.stream_trades("0xaa").await.unwrap();
+        let started = tokio::time::Instant::now();
+        replay.start();
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.trades[0].price, 100);
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.trades[0].price, 101);
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+        assert!(stream.next().await.is_none());
+        assert!(replay.is_finished());
+    }
+}
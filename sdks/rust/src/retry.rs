@@ -0,0 +1,312 @@
+//! Transport-level retries for idempotent requests.
+//!
+//! [`O2Api`](crate::api::O2Api) sends every read endpoint (`GET`) through a
+//! [`RetryPolicy`]. Failures classified as transient by [`RetryOn`] are retried
+//! with exponential backoff and jitter; a `Retry-After` hint from the server
+//! replaces the computed delay. Writes (`POST`/`PUT`) are never retried here,
+//! since resubmitting them is not safe in general.
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use reqwest::{Request, Response, StatusCode};
+
+use crate::api::retry_after_from_headers;
+use crate::runtime;
+use crate::trace::debug;
+use crate::transport::Transport;
+
+/// Which failures are worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryOn {
+    /// HTTP 500, 502, 503 and 504 responses.
+    pub server_errors: bool,
+    /// HTTP 429 and 503 responses. A `Retry-After` hint sets the delay.
+    pub rate_limited: bool,
+    /// Requests that hit the client's timeout.
+    pub timeouts: bool,
+    /// Connection failures: refused, reset, or closed before a response.
+    pub connection_errors: bool,
+}
+
+impl RetryOn {
+    /// Retry on every transient failure.
+    pub const ALL: RetryOn = RetryOn {
+        server_errors: true,
+        rate_limited: true,
+        timeouts: true,
+        connection_errors: true,
+    };
+}
+
+impl Default for RetryOn {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Retry behaviour for idempotent HTTP requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first (default: 3, 1 = no retries).
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles on each further retry (default: 200ms).
+    pub base_delay: Duration,
+    /// Upper bound on any single delay (default: 5s). A server `Retry-After`
+    /// longer than this is not waited out; the error is returned instead.
+    pub max_delay: Duration,
+    /// Fraction of each delay that is randomized, in `0.0..=1.0` (default: 0.5).
+    /// A delay `d` becomes a uniform value in `[d * (1 - jitter), d]`.
+    pub jitter: f64,
+    /// Failures that are retried.
+    pub retry_on: RetryOn,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.5,
+            retry_on: RetryOn::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based), before jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    fn jittered(&self, delay: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 || delay.is_zero() {
+            return delay;
+        }
+        let cut = rand::thread_rng().gen_range(0.0..=jitter);
+        delay.mul_f64(1.0 - cut)
+    }
+
+    /// Whether a response status should be retried.
+    fn classify_status(&self, status: StatusCode) -> bool {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => self.retry_on.rate_limited,
+            StatusCode::SERVICE_UNAVAILABLE => {
+                self.retry_on.server_errors || self.retry_on.rate_limited
+            }
+            StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::GATEWAY_TIMEOUT => self.retry_on.server_errors,
+            _ => false,
+        }
+    }
+
+    fn classify_error(&self, err: &reqwest::Error) -> bool {
+        if err.is_timeout() {
+            self.retry_on.timeouts
+        } else {
+            (err.is_connect() || err.is_request()) && self.retry_on.connection_errors
+        }
+    }
+}
+
+/// Send `request` through `transport`, retrying transient failures under
+/// `policy`.
+///
+/// Returns the last response (which may be an error status) or the last
+/// transport error once attempts run out.
+pub(crate) async fn send_retrying(
+    transport: &dyn Transport,
+    request: Request,
+    policy: &RetryPolicy,
+) -> Result<Response, reqwest::Error> {
+    let mut attempt = 1;
+    loop {
+        // Bodiless requests always clone; anything else goes out once.
+        let Some(attempt_request) = request.try_clone() else {
+            return transport.send(request).await;
+        };
+        let last = attempt >= policy.max_attempts;
+        let (hint, reason) = match transport.send(attempt_request).await {
+            Ok(resp) if !last && policy.classify_status(resp.status()) => {
+                let hint = retry_after_from_headers(resp.headers(), SystemTime::now());
+                if hint.is_some_and(|h| h > policy.max_delay) {
+                    // Not worth waiting out; the caller sees the hint in the error.
+                    debug!(
+                        "retry.give_up attempt={} status={} retry_after={:?}",
+                        attempt,
+                        resp.status(),
+                        hint
+                    );
+                    return Ok(resp);
+                }
+                (hint, resp.status().to_string())
+            }
+            Ok(resp) => return Ok(resp),
+            Err(e) if !last && policy.classify_error(&e) => (None, e.to_string()),
+            Err(e) => return Err(e),
+        };
+        let delay = hint.unwrap_or_else(|| policy.jittered(policy.backoff(attempt)));
+        debug!(
+            "retry.retrying attempt={} delay_ms={} reason={}",
+            attempt,
+            delay.as_millis(),
+            reason
+        );
+        runtime::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(100), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = RetryPolicy::default();
+        let d = Duration::from_millis(1000);
+        for _ in 0..100 {
+            let j = policy.jittered(d);
+            assert!(j >= Duration::from_millis(500) && j <= d, "{j:?}");
+        }
+        let none = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(none.jittered(d), d);
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        let policy = RetryPolicy::default();
+        assert!(policy.classify_status(StatusCode::BAD_GATEWAY));
+        assert!(policy.classify_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!policy.classify_status(StatusCode::BAD_REQUEST));
+        assert!(!policy.classify_status(StatusCode::NOT_FOUND));
+
+        let only_rate_limits = RetryPolicy {
+            retry_on: RetryOn {
+                server_errors: false,
+                rate_limited: true,
+                timeouts: false,
+                connection_errors: false,
+            },
+            ..RetryPolicy::default()
+        };
+        assert!(!only_rate_limits.classify_status(StatusCode::BAD_GATEWAY));
+        assert!(only_rate_limits.classify_status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    /// Serve one canned HTTP response per connection, in order, repeating the last.
+    async fn http_server(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let status = responses[n.min(responses.len() - 1)];
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let resp = format!(
+                    "HTTP/1.1 {status}\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok"
+                );
+                let _ = stream.write_all(resp.as_bytes()).await;
+            }
+        });
+        (format!("http://{addr}"), hits)
+    }
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let (url, hits) = http_server(vec!["502 Bad Gateway", "500 Oops", "200 OK"]).await;
+        let client = reqwest::Client::new();
+        let resp = send_retrying(&client, client.get(&url).build().unwrap(), &fast())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_when_attempts_run_out() {
+        let (url, hits) = http_server(vec!["500 Oops"]).await;
+        let client = reqwest::Client::new();
+        let resp = send_retrying(&client, client.get(&url).build().unwrap(), &fast())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors_or_when_disabled() {
+        let (url, hits) = http_server(vec!["404 Not Found"]).await;
+        let client = reqwest::Client::new();
+        let resp = send_retrying(&client, client.get(&url).build().unwrap(), &fast())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (url, hits) = http_server(vec!["500 Oops"]).await;
+        send_retrying(
+            &client,
+            client.get(&url).build().unwrap(),
+            &RetryPolicy::none(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_connection_errors() {
+        // Nothing listens on port 9.
+        let client = reqwest::Client::new();
+        let err = send_retrying(
+            &client,
+            client.get("http://127.0.0.1:9").build().unwrap(),
+            &fast(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_connect());
+    }
+}
@@ -0,0 +1,304 @@
+//! The async executor and timer the SDK runs its background work on.
+//!
+//! Background helpers ([`LiveOrderBook`](crate::LiveOrderBook),
+//! [`NonceManager`](crate::NonceManager), [`Scheduler`](crate::Scheduler), the
+//! dead-man's switch and the rest), polling loops, retries and timeouts spawn
+//! and sleep through the process's [`Runtime`]. The default is
+//! [`TokioRuntime`]; another executor plugs in with [`set_runtime`]:
+//!
+//! ```rust,ignore
+//! struct SmolRuntime;
+//!
+//! impl Runtime for SmolRuntime {
+//!     fn spawn(&self, task: BoxFuture<'static, ()>) {
+//!         smol::spawn(task).detach();
+//!     }
+//!
+//!     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+//!         Box::pin(async move {
+//!             smol::Timer::after(duration).await;
+//!         })
+//!     }
+//! }
+//!
+//! o2_sdk::runtime::set_runtime(SmolRuntime);
+//! ```
+//!
+//! The SDK's channels and locks are `tokio::sync` types, which work on any
+//! executor. The I/O is not covered: the default HTTP transport (`reqwest`)
+//! and the WebSocket client (`tokio-tungstenite`) need a tokio reactor. Outside
+//! tokio, send REST requests through a custom
+//! [`Transport`](crate::transport::Transport) and leave WebSocket features
+//! unused, or enter a tokio runtime for them.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{AbortHandle, Abortable, BoxFuture, Either};
+use futures_util::FutureExt;
+use tokio::sync::oneshot;
+
+/// An async executor with a timer.
+pub trait Runtime: Send + Sync + 'static {
+    /// Run `task` in the background until it completes.
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+
+    /// Complete after `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// The runtime's clock. Override it if the runtime can pause or advance
+    /// time, so deadlines agree with [`sleep`](Self::sleep).
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Whether [`spawn`](Self::spawn) can be called on this thread. Cleanup
+    /// spawned from `Drop` is skipped when it returns `false`.
+    fn can_spawn(&self) -> bool {
+        true
+    }
+}
+
+/// Runs on the ambient tokio runtime. Spawning panics outside one, as
+/// `tokio::spawn` does. Follows tokio's clock, including paused time in tests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+impl Runtime for TokioRuntime {
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn can_spawn(&self) -> bool {
+        tokio::runtime::Handle::try_current().is_ok()
+    }
+}
+
+fn global() -> &'static RwLock<Arc<dyn Runtime>> {
+    static RUNTIME: OnceLock<RwLock<Arc<dyn Runtime>>> = OnceLock::new();
+    RUNTIME.get_or_init(|| RwLock::new(Arc::new(TokioRuntime)))
+}
+
+fn current() -> Arc<dyn Runtime> {
+    global().read().unwrap_or_else(|p| p.into_inner()).clone()
+}
+
+/// Replace the process's runtime. Tasks and timers already started stay on
+/// the previous one, so call this before using the SDK.
+pub fn set_runtime(runtime: impl Runtime) {
+    *global().write().unwrap_or_else(|p| p.into_inner()) = Arc::new(runtime);
+}
+
+/// Spawn `future` on the process's runtime.
+pub(crate) fn spawn<F>(future: F) -> Task<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (abort, registration) = AbortHandle::new_pair();
+    let (tx, rx) = oneshot::channel();
+    let finished = Arc::new(AtomicBool::new(false));
+    let done = SetOnDrop(finished.clone());
+    current().spawn(Box::pin(async move {
+        // Set even if the future panics.
+        let _done = done;
+        if let Ok(output) = Abortable::new(future, registration).await {
+            let _ = tx.send(output);
+        }
+    }));
+    Task {
+        abort,
+        finished,
+        output: rx,
+    }
+}
+
+struct SetOnDrop(Arc<AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Whether [`spawn`] can be called on this thread.
+pub(crate) fn can_spawn() -> bool {
+    current().can_spawn()
+}
+
+/// Handle to a task started with [`spawn`]. Dropping it detaches the task;
+/// awaiting it yields the task's output.
+#[derive(Debug)]
+pub(crate) struct Task<T> {
+    abort: AbortHandle,
+    finished: Arc<AtomicBool>,
+    output: oneshot::Receiver<T>,
+}
+
+impl<T> Task<T> {
+    /// Stop the task at its next suspension point.
+    pub(crate) fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Whether the task has completed, been aborted or panicked.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = Result<T, TaskFailed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.output)
+            .poll(cx)
+            .map(|output| output.map_err(|_| TaskFailed))
+    }
+}
+
+/// A task was aborted or panicked before producing its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TaskFailed;
+
+impl std::fmt::Display for TaskFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("task aborted or panicked")
+    }
+}
+
+/// The current time on the runtime's clock.
+pub(crate) fn now() -> Instant {
+    current().now()
+}
+
+/// Complete after `duration`.
+pub(crate) fn sleep(duration: Duration) -> BoxFuture<'static, ()> {
+    current().sleep(duration)
+}
+
+/// Complete at `deadline`.
+pub(crate) fn sleep_until(deadline: Instant) -> BoxFuture<'static, ()> {
+    let runtime = current();
+    runtime.sleep(deadline.saturating_duration_since(runtime.now()))
+}
+
+/// `future` did not complete within its [`timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+/// Run `future`, giving up after `duration`.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let future = std::pin::pin!(future);
+    match futures_util::future::select(future, sleep(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(((), _)) => Err(Elapsed),
+    }
+}
+
+/// How late a tick may complete before the next one is pushed back, as in
+/// `tokio::time::MissedTickBehavior::Delay`.
+const TICK_TOLERANCE: Duration = Duration::from_millis(5);
+
+/// Ticks every `period`, the first immediately. A tick that completes late
+/// delays the following ones instead of bursting to catch up.
+pub(crate) fn interval(period: Duration) -> Ticker {
+    assert!(!period.is_zero(), "interval period must be non-zero");
+    Ticker {
+        period,
+        next: now(),
+    }
+}
+
+/// See [`interval`].
+#[derive(Debug)]
+pub(crate) struct Ticker {
+    period: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    /// Wait for the next tick and return its scheduled time.
+    pub(crate) async fn tick(&mut self) -> Instant {
+        let deadline = self.next;
+        sleep_until(deadline).await;
+        let now = now();
+        self.next = if now > deadline + TICK_TOLERANCE {
+            now + self.period
+        } else {
+            deadline + self.period
+        };
+        deadline
+    }
+
+    /// Make the next tick complete immediately.
+    pub(crate) fn reset_immediately(&mut self) {
+        self.next = now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_are_delayed_after_a_late_one() {
+        let start = now();
+        let mut ticker = interval(Duration::from_secs(1));
+        assert_eq!(ticker.tick().await, start);
+        assert_eq!(ticker.tick().await, start + Duration::from_secs(1));
+        tokio::time::advance(Duration::from_millis(2500)).await;
+        // Late: fires now and schedules the next one a period after it.
+        ticker.tick().await;
+        let late = now();
+        assert_eq!(late, start + Duration::from_millis(3500));
+        assert_eq!(ticker.tick().await, late + Duration::from_secs(1));
+
+        ticker.reset_immediately();
+        let before = now();
+        ticker.tick().await;
+        assert_eq!(now(), before);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_report_output_abort_and_timeouts() {
+        let task = spawn(async { 7 });
+        assert_eq!(task.await, Ok(7));
+
+        let task = spawn(std::future::pending::<()>());
+        assert!(!task.is_finished());
+        task.abort();
+        tokio::task::yield_now().await;
+        assert!(task.is_finished());
+        assert_eq!(task.await, Err(TaskFailed));
+
+        let slow = timeout(Duration::from_secs(1), sleep(Duration::from_secs(2))).await;
+        assert_eq!(slow, Err(Elapsed));
+        let fast = timeout(Duration::from_secs(2), sleep(Duration::from_secs(1))).await;
+        assert_eq!(fast, Ok(()));
+    }
+
+    #[test]
+    fn tokio_can_only_spawn_inside_a_runtime() {
+        assert!(!TokioRuntime.can_spawn());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let _guard = rt.enter();
+        assert!(TokioRuntime.can_spawn());
+    }
+}
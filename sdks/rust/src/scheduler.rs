@@ -0,0 +1,607 @@
+//! Recurring jobs on a cron-like schedule.
+//!
+//! A [`Scheduler`] runs named jobs, such as an end-of-day
+//! [`settle_balance`](crate::O2Client::settle_balance) sweep, a withdrawal
+//! sweep above a threshold or a report, at the times a [`Schedule`] gives. A
+//! job is any closure returning a future; it captures whatever client and
+//! session it needs.
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use tokio::sync::Mutex;
+//! use o2_sdk::{Schedule, Scheduler};
+//!
+//! let state = Arc::new(Mutex::new((client, session)));
+//! let mut scheduler = Scheduler::new().with_state_file("o2-jobs.json");
+//! scheduler.add("eod-settle", "0 0 * * *".parse()?, move || {
+//!     let state = state.clone();
+//!     async move {
+//!         let (client, session) = &mut *state.lock().await;
+//!         client.settle_balance(session, "fFUEL/fUSDC").await.map(|_| ())
+//!     }
+//! })?;
+//! let running = scheduler.start()?;
+//! let mut events = running.subscribe();
+//! ```
+//!
+//! Jobs run one at a time, in the order they fall due, so jobs sharing a
+//! session never race for its nonce. A job that overruns delays the others,
+//! and occurrences missed while it ran are skipped rather than queued.
+//!
+//! With a state file, the time of each job's last successful run is saved
+//! after every success. On the next start, a job whose next occurrence after
+//! that marker is already past runs once right away. This covers runs missed
+//! while the process was down and runs that failed. Every outcome is reported
+//! as a [`JobEvent`]; subscribe to be told about failures.
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use tokio::sync::broadcast;
+
+use crate::errors::O2Error;
+use crate::runtime::{self, Task};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+
+/// How far ahead a cron schedule is searched for its next occurrence. A
+/// schedule with none in this window (e.g. February 30th) is rejected.
+const CRON_SEARCH_YEARS: i64 = 5;
+
+/// When a job runs: a cron expression in UTC, or a fixed interval.
+///
+/// Cron expressions have five fields: minute (0-59), hour (0-23), day of
+/// month (1-31), month (1-12) and day of week (0-7, 0 and 7 are Sunday).
+/// Each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a
+/// comma-separated list of those. As in cron, when both day fields are
+/// restricted a day matching either one qualifies. `@hourly`, `@daily`,
+/// `@weekly` and `@monthly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule(Kind);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Cron(Cron),
+    Every(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    /// Parse a cron expression. See [`Schedule`] for the syntax.
+    pub fn cron(expr: &str) -> Result<Self, O2Error> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid(expr, "expected 5 fields"));
+        };
+        let weekdays = parse_field(weekday, 0, 7).map_err(|e| invalid(expr, &e))?;
+        // 7 is another name for Sunday.
+        let weekdays = (weekdays | (weekdays >> 7)) & 0x7f;
+        let schedule = Schedule(Kind::Cron(Cron {
+            minutes: parse_field(minute, 0, 59).map_err(|e| invalid(expr, &e))?,
+            hours: parse_field(hour, 0, 23).map_err(|e| invalid(expr, &e))?,
+            days: parse_field(day, 1, 31).map_err(|e| invalid(expr, &e))?,
+            months: parse_field(month, 1, 12).map_err(|e| invalid(expr, &e))?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        }));
+        if schedule.next_after(Timestamp::now()).is_none() {
+            return Err(invalid(expr, "never fires"));
+        }
+        Ok(schedule)
+    }
+
+    /// Run every `interval`, counted from the previous run. Fails on a zero
+    /// interval.
+    pub fn every(interval: Duration) -> Result<Self, O2Error> {
+        if interval.is_zero() {
+            return Err(O2Error::InvalidRequest(
+                "schedule interval must be greater than zero".into(),
+            ));
+        }
+        Ok(Schedule(Kind::Every(interval)))
+    }
+
+    /// The first occurrence strictly after `after`. `None` if a cron schedule
+    /// has none in the next few years.
+    pub fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        match &self.0 {
+            Kind::Every(interval) => Some(Timestamp::from_unix_nanos(
+                u64::try_from(after.since_epoch().checked_add(*interval)?.as_nanos()).ok()?,
+            )),
+            Kind::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = O2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Schedule::cron(s)
+    }
+}
+
+fn invalid(expr: &str, why: &str) -> O2Error {
+    O2Error::InvalidRequest(format!("invalid cron expression \"{expr}\": {why}"))
+}
+
+/// Parse one cron field into a bitmask of the values it allows.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("bad step in \"{part}\""))?;
+                if step == 0 {
+                    return Err(format!("zero step in \"{part}\""));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (parse_value(lo, part)?, parse_value(hi, part)?)
+        } else {
+            let value = parse_value(range, part)?;
+            // `5/15` means from 5 to the end in steps of 15.
+            (value, if step > 1 { max } else { value })
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(format!("\"{part}\" is outside {min}-{max}"));
+        }
+        for value in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, part: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("bad value in \"{part}\""))
+}
+
+impl Cron {
+    fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        let has = |mask: u64, value: i64| mask & (1 << value) != 0;
+        // Work in whole minutes since the epoch, starting at the next minute.
+        let mut minute = i64::try_from(after.as_unix_secs() / 60).ok()? + 1;
+        let (start_year, _, _) = civil_from_days(minute.div_euclid(1440));
+        loop {
+            let day = minute.div_euclid(1440);
+            let (year, month, dom) = civil_from_days(day);
+            if year > start_year + CRON_SEARCH_YEARS {
+                return None;
+            }
+            if !has(self.months, month) {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(year, month, 1) * 1440;
+                continue;
+            }
+            // 1970-01-01 was a Thursday.
+            let weekday = (day + 4).rem_euclid(7);
+            let day_ok = match (self.days_restricted, self.weekdays_restricted) {
+                (true, true) => has(self.days, dom) || has(self.weekdays, weekday),
+                _ => has(self.days, dom) && has(self.weekdays, weekday),
+            };
+            if !day_ok {
+                minute = (day + 1) * 1440;
+                continue;
+            }
+            if !has(self.hours, minute.rem_euclid(1440) / 60) {
+                minute = (minute.div_euclid(60) + 1) * 60;
+                continue;
+            }
+            if !has(self.minutes, minute.rem_euclid(60)) {
+                minute += 1;
+                continue;
+            }
+            return Some(Timestamp::from_unix_secs(u64::try_from(minute * 60).ok()?));
+        }
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The (year, month, day) of a count of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A job outcome, broadcast by a running scheduler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JobEvent {
+    /// A job started its run for the occurrence at `scheduled_for`.
+    Started {
+        job: String,
+        scheduled_for: Timestamp,
+    },
+    /// A job finished without error.
+    Succeeded { job: String, took: Duration },
+    /// A job returned an error. It runs again at its next occurrence.
+    Failed {
+        job: String,
+        error: String,
+        took: Duration,
+    },
+    /// The state file could not be written; last-run markers were not saved.
+    StateNotSaved { error: String },
+}
+
+type JobFn = Box<dyn FnMut() -> BoxFuture<'static, Result<(), O2Error>> + Send>;
+
+struct Job {
+    name: String,
+    schedule: Schedule,
+    run: JobFn,
+}
+
+/// A set of jobs to run on schedule. Add jobs, then [`start`](Self::start).
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    state_file: Option<PathBuf>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field(
+                "jobs",
+                &self.jobs.iter().map(|j| &j.name).collect::<Vec<_>>(),
+            )
+            .field("state_file", &self.state_file)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep each job's last successful run time in `path` (JSON), so that
+    /// runs missed while the process was down happen on the next start.
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Add a job. `name` identifies it in events and in the state file and
+    /// must be unique.
+    pub fn add<F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        schedule: Schedule,
+        mut job: F,
+    ) -> Result<&mut Self, O2Error>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), O2Error>> + Send + 'static,
+    {
+        let name = name.into();
+        if self.jobs.iter().any(|j| j.name == name) {
+            return Err(O2Error::InvalidRequest(format!(
+                "a job named \"{name}\" is already scheduled"
+            )));
+        }
+        self.jobs.push(Job {
+            name,
+            schedule,
+            run: Box::new(move || Box::pin(job())),
+        });
+        Ok(self)
+    }
+
+    /// Load the state file, if any, and start running jobs in the
+    /// background. A missing state file is not an error; an unreadable one
+    /// is. Must be called from within a Tokio runtime.
+    pub fn start(self) -> Result<SchedulerHandle, O2Error> {
+        let markers = match &self.state_file {
+            Some(path) => load_markers(path)?,
+            None => HashMap::new(),
+        };
+        let (events, _) = broadcast::channel(64);
+        let task = runtime::spawn(run(self, markers, events.clone()));
+        Ok(SchedulerHandle { events, task })
+    }
+}
+
+/// A running [`Scheduler`]. Dropping it stops the scheduler, abandoning a
+/// job that is mid-run.
+pub struct SchedulerHandle {
+    events: broadcast::Sender<JobEvent>,
+    task: Task<()>,
+}
+
+impl SchedulerHandle {
+    /// Receive job outcomes from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    /// True until the handle is dropped, or every schedule has run out.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for SchedulerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Last successful run of each job, as Unix milliseconds.
+type Markers = HashMap<String, u64>;
+
+fn load_markers(path: &PathBuf) -> Result<Markers, O2Error> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            O2Error::Other(format!(
+                "scheduler state file {} is corrupt: {e}",
+                path.display()
+            ))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(O2Error::Other(format!(
+            "cannot read scheduler state file {}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+/// Write `markers` next to `path` and rename it into place, so a crash
+/// mid-write leaves the previous markers intact.
+async fn save_markers(path: &PathBuf, markers: &Markers) -> std::io::Result<()> {
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(markers)?).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+async fn run(mut scheduler: Scheduler, mut markers: Markers, events: broadcast::Sender<JobEvent>) {
+    let now = Timestamp::now();
+    let mut due: Vec<Option<Timestamp>> = scheduler
+        .jobs
+        .iter()
+        .map(|job| {
+            let last = markers
+                .get(&job.name)
+                .map(|&ms| Timestamp::from_unix_millis(ms));
+            job.schedule.next_after(last.unwrap_or(now))
+        })
+        .collect();
+
+    loop {
+        let Some((index, at)) = due
+            .iter()
+            .enumerate()
+            .filter_map(|(i, at)| at.map(|at| (i, at)))
+            .min_by_key(|&(_, at)| at)
+        else {
+            return;
+        };
+        let wait = at.saturating_duration_since(Timestamp::now());
+        if !wait.is_zero() {
+            runtime::sleep(wait).await;
+        }
+
+        let job = &mut scheduler.jobs[index];
+        debug!("scheduler.run job={} scheduled_for={:?}", job.name, at);
+        let _ = events.send(JobEvent::Started {
+            job: job.name.clone(),
+            scheduled_for: at,
+        });
+        let started = Instant::now();
+        let result = (job.run)().await;
+        let took = started.elapsed();
+        match result {
+            Ok(()) => {
+                // Save the marker before reporting, so a subscriber that
+                // stops the scheduler on success doesn't lose it.
+                let marker = u64::try_from(at.as_unix_millis()).unwrap_or(u64::MAX);
+                markers.insert(job.name.clone(), marker);
+                if let Some(path) = &scheduler.state_file {
+                    if let Err(e) = save_markers(path, &markers).await {
+                        let _ = events.send(JobEvent::StateNotSaved {
+                            error: e.to_string(),
+                        });
+                    }
+                }
+                let _ = events.send(JobEvent::Succeeded {
+                    job: job.name.clone(),
+                    took,
+                });
+            }
+            Err(e) => {
+                let _ = events.send(JobEvent::Failed {
+                    job: job.name.clone(),
+                    error: e.to_string(),
+                    took,
+                });
+            }
+        }
+        // Skip occurrences that passed while the job ran.
+        due[index] = job.schedule.next_after(at.max(Timestamp::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn at(year: i64, month: i64, day: i64, hour: u64, minute: u64) -> Timestamp {
+        let secs = days_from_civil(year, month, day) as u64 * 86_400 + hour * 3600 + minute * 60;
+        Timestamp::from_unix_secs(secs)
+    }
+
+    #[test]
+    fn cron_finds_next_occurrence() {
+        let daily = Schedule::cron("@daily").unwrap();
+        assert_eq!(
+            daily.next_after(at(2026, 2, 28, 13, 5)),
+            Some(at(2026, 3, 1, 0, 0))
+        );
+        let quarter_hours = Schedule::cron("*/15 9-17 * * 1-5").unwrap();
+        // 2026-10-16 is a Friday; the next slot after 17:50 is Monday 09:00.
+        assert_eq!(
+            quarter_hours.next_after(at(2026, 10, 16, 17, 50)),
+            Some(at(2026, 10, 19, 9, 0))
+        );
+        assert_eq!(
+            quarter_hours.next_after(at(2026, 10, 16, 9, 0)),
+            Some(at(2026, 10, 16, 9, 15))
+        );
+        // Either day field may match when both are restricted.
+        let first_or_sunday = Schedule::cron("30 6 1 * 7").unwrap();
+        assert_eq!(
+            first_or_sunday.next_after(at(2026, 10, 16, 0, 0)),
+            Some(at(2026, 10, 18, 6, 30))
+        );
+        let leap_day = Schedule::cron("0 12 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(at(2026, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 12, 0))
+        );
+    }
+
+    #[test]
+    fn cron_rejects_bad_expressions() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "0 0 30 2 *",
+            "a * * * *",
+        ] {
+            let err = Schedule::cron(expr).unwrap_err();
+            assert!(matches!(err, O2Error::InvalidRequest(_)), "{expr}");
+        }
+        assert!(Schedule::every(Duration::ZERO).is_err());
+    }
+
+    #[tokio::test]
+    async fn jobs_report_failures_and_persist_markers() {
+        let path = std::env::temp_dir().join(format!(
+            "o2-scheduler-test-{}-{:?}.json",
+            std::process::id(),
+            Instant::now()
+        ));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let mut scheduler = Scheduler::new().with_state_file(&path);
+        scheduler
+            .add(
+                "sweep",
+                Schedule::every(Duration::from_millis(20)).unwrap(),
+                move || {
+                    let n = counter.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if n == 0 {
+                            Err(O2Error::Other("node down".into()))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                },
+            )
+            .unwrap();
+        assert!(scheduler
+            .add(
+                "sweep",
+                Schedule::every(Duration::from_secs(1)).unwrap(),
+                || async { Ok(()) }
+            )
+            .is_err());
+
+        let running = scheduler.start().unwrap();
+        let mut events = running.subscribe();
+        let mut outcomes = Vec::new();
+        while outcomes.len() < 2 {
+            match events.recv().await.unwrap() {
+                JobEvent::Failed { job, error, .. } => outcomes.push(format!("{job}: {error}")),
+                JobEvent::Succeeded { job, .. } => outcomes.push(format!("{job}: ok")),
+                _ => {}
+            }
+        }
+        drop(running);
+        assert_eq!(outcomes, ["sweep: node down", "sweep: ok"]);
+
+        let markers = load_markers(&path).unwrap();
+        assert!(markers.contains_key("sweep"));
+
+        // A job whose next run after its marker is past runs right away.
+        let mut stale = Markers::new();
+        stale.insert("sweep".into(), 0);
+        save_markers(&path, &stale).await.unwrap();
+        let mut scheduler = Scheduler::new().with_state_file(&path);
+        scheduler
+            .add("sweep", Schedule::cron("@daily").unwrap(), || async {
+                Ok(())
+            })
+            .unwrap();
+        let running = scheduler.start().unwrap();
+        let mut events = running.subscribe();
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            matches!(event, JobEvent::Started { scheduled_for, .. } if scheduled_for == Timestamp::from_unix_secs(86_400))
+        );
+        drop(running);
+        let _ = std::fs::remove_file(&path);
+    }
+}
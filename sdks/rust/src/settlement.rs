@@ -0,0 +1,277 @@
+//! Automatic `SettleBalance`.
+//!
+//! Trade proceeds stay unlocked in a market's order-book contract until a
+//! `SettleBalance` on that market moves them back to the trading account, and
+//! nothing announces that they are piling up. A [`SettlementPolicy`] set with
+//! [`O2Client::set_settlement_policy`](crate::O2Client::set_settlement_policy)
+//! has the client append `Action::SettleBalance` to a market's actions in an
+//! outgoing batch once the market is due:
+//!
+//! - [`SettlementPolicy::EveryActions`] after a number of actions on the
+//!   market since it last settled;
+//! - [`SettlementPolicy::UnsettledAbove`] once the account's balance stream
+//!   shows more than a threshold of an asset unlocked in the market's
+//!   order-book contract. The client subscribes to the stream on the shared
+//!   WebSocket with the first batch it submits for the account.
+//!
+//! The settle rides along in a batch that is being sent anyway, so it costs no
+//! extra nonce. A batch that already settles the market, or is already at
+//! [`MAX_ACTIONS_PER_BATCH`](crate::MAX_ACTIONS_PER_BATCH), is left alone and
+//! the market settles with its next batch. A market that sees no further
+//! batches is not settled; pair a policy with
+//! [`O2Client::heal`](crate::O2Client::heal) or a scheduled settle for those.
+//!
+//! ```rust,ignore
+//! client.set_settlement_policy(SettlementPolicy::unsettled_above([
+//!     ("fUSDC", 100_000_000), // chain units
+//!     ("fFUEL", 1_000_000_000_000),
+//! ]));
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use futures_util::StreamExt;
+
+use crate::models::{AssetId, BalanceUpdate, Market, MarketId, TradeAccountId};
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// When an [`O2Client`](crate::O2Client) adds a `SettleBalance` to a batch on
+/// its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SettlementPolicy {
+    /// Only settle when asked. The default.
+    #[default]
+    Manual,
+    /// Settle a market in the batch that brings the number of actions
+    /// submitted on it since its last settle to at least this many.
+    EveryActions(u32),
+    /// Settle a market once the balance stream shows more than the threshold
+    /// of its base or quote asset unlocked in its order-book contract.
+    /// Thresholds are chain integers keyed by asset symbol; assets without
+    /// one are ignored.
+    UnsettledAbove(BTreeMap<String, u128>),
+}
+
+impl SettlementPolicy {
+    /// [`SettlementPolicy::UnsettledAbove`] from `(symbol, threshold)` pairs.
+    pub fn unsettled_above<S: Into<String>>(
+        thresholds: impl IntoIterator<Item = (S, u128)>,
+    ) -> Self {
+        Self::UnsettledAbove(
+            thresholds
+                .into_iter()
+                .map(|(symbol, threshold)| (symbol.into(), threshold))
+                .collect(),
+        )
+    }
+}
+
+/// Unlocked amounts by order-book contract (lowercase) and asset, as last
+/// reported by the balance stream.
+type Unsettled = Arc<RwLock<HashMap<(String, AssetId), u128>>>;
+
+/// A client's settlement policy and what it has seen since each market's last
+/// settle.
+#[derive(Default)]
+pub(crate) struct Settlement {
+    policy: SettlementPolicy,
+    actions_since: HashMap<MarketId, u32>,
+    unsettled: Unsettled,
+    feed: Option<(TradeAccountId, Task<()>)>,
+}
+
+impl Settlement {
+    pub(crate) fn policy(&self) -> &SettlementPolicy {
+        &self.policy
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: SettlementPolicy) {
+        self.policy = policy;
+        self.actions_since.clear();
+    }
+
+    /// Whether `market` should be settled in a batch carrying `pending` of
+    /// its actions.
+    pub(crate) fn is_due(&self, market: &Market, pending: usize) -> bool {
+        match &self.policy {
+            SettlementPolicy::Manual => false,
+            SettlementPolicy::EveryActions(n) => {
+                let since = self
+                    .actions_since
+                    .get(&market.market_id)
+                    .copied()
+                    .unwrap_or(0);
+                u64::from(since) + pending as u64 >= u64::from(*n)
+            }
+            SettlementPolicy::UnsettledAbove(thresholds) => {
+                let unsettled = self.unsettled.read().unwrap_or_else(|p| p.into_inner());
+                let book = market.contract_id.as_str().to_ascii_lowercase();
+                [&market.base, &market.quote].into_iter().any(|asset| {
+                    let held = unsettled
+                        .get(&(book.clone(), asset.asset.clone()))
+                        .copied()
+                        .unwrap_or(0);
+                    thresholds
+                        .get(&asset.symbol)
+                        .is_some_and(|&threshold| held > threshold)
+                })
+            }
+        }
+    }
+
+    /// Record `actions` actions submitted on `market`, `settled` if one of
+    /// them was a `SettleBalance`.
+    pub(crate) fn record(&mut self, market: &Market, actions: usize, settled: bool) {
+        if settled {
+            self.actions_since.remove(&market.market_id);
+            let book = market.contract_id.as_str().to_ascii_lowercase();
+            self.unsettled
+                .write()
+                .unwrap_or_else(|p| p.into_inner())
+                .retain(|(b, _), _| *b != book);
+        } else if matches!(self.policy, SettlementPolicy::EveryActions(_)) {
+            let since = self
+                .actions_since
+                .entry(market.market_id.clone())
+                .or_insert(0);
+            *since = since.saturating_add(u32::try_from(actions).unwrap_or(u32::MAX));
+        }
+    }
+
+    /// Whether the policy needs a balance stream for `account` that is not
+    /// being followed yet.
+    pub(crate) fn needs_feed(&self, account: &TradeAccountId) -> bool {
+        matches!(self.policy, SettlementPolicy::UnsettledAbove(_))
+            && !self
+                .feed
+                .as_ref()
+                .is_some_and(|(followed, task)| followed == account && !task.is_finished())
+    }
+
+    /// Follow `account`'s balance stream, replacing any earlier one.
+    pub(crate) fn follow(
+        &mut self,
+        account: TradeAccountId,
+        mut stream: TypedStream<BalanceUpdate>,
+    ) {
+        debug!("settlement.follow trade_account_id={}", account);
+        let unsettled = self.unsettled.clone();
+        unsettled.write().unwrap_or_else(|p| p.into_inner()).clear();
+        let task = runtime::spawn(async move {
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(update) => apply(&unsettled, &update),
+                    Err(e) => debug!("settlement.follow stream_error error={}", e),
+                }
+            }
+            debug!("settlement.follow stream_ended");
+        });
+        if let Some((_, previous)) = self.feed.replace((account, task)) {
+            previous.abort();
+        }
+    }
+}
+
+impl Drop for Settlement {
+    fn drop(&mut self) {
+        if let Some((_, task)) = &self.feed {
+            task.abort();
+        }
+    }
+}
+
+/// Record the unlocked amounts in a balance update.
+fn apply(unsettled: &Unsettled, update: &BalanceUpdate) {
+    let mut unsettled = unsettled.write().unwrap_or_else(|p| p.into_inner());
+    for entry in &update.balance {
+        for (book, held) in &entry.order_books {
+            unsettled.insert(
+                (book.to_ascii_lowercase(), entry.asset_id.clone()),
+                held.unlocked,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{ContractId, MarketAsset};
+
+    fn market() -> Market {
+        let asset = |symbol: &str, id: &str| MarketAsset {
+            symbol: symbol.into(),
+            asset: AssetId::new(id),
+            decimals: 9,
+            max_precision: 3,
+        };
+        Market {
+            contract_id: ContractId::new("0xB00C"),
+            market_id: MarketId::new("0xaa"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 0,
+            dust: 0,
+            price_window: 0,
+            base: asset("fFUEL", "0x01"),
+            quote: asset("fUSDC", "0x02"),
+        }
+    }
+
+    #[test]
+    fn every_actions_counts_until_a_settle() {
+        let market = market();
+        let mut settlement = Settlement::default();
+        assert!(!settlement.is_due(&market, 5));
+
+        settlement.set_policy(SettlementPolicy::EveryActions(6));
+        assert!(!settlement.is_due(&market, 4));
+        settlement.record(&market, 4, false);
+        assert!(!settlement.is_due(&market, 1));
+        assert!(settlement.is_due(&market, 2));
+        settlement.record(&market, 2, true);
+        assert!(!settlement.is_due(&market, 5));
+    }
+
+    #[test]
+    fn unsettled_above_follows_the_balance_stream() {
+        let market = market();
+        let mut settlement = Settlement::default();
+        settlement.set_policy(SettlementPolicy::unsettled_above([("fUSDC", 100)]));
+        let account = TradeAccountId::new("0xacc");
+        assert!(settlement.needs_feed(&account));
+
+        let update = |asset: &str, unlocked: u128| -> BalanceUpdate {
+            serde_json::from_value(json!({
+                "action": "subscribe_balances",
+                "balance": [{
+                    "identity": {"ContractId": "0xacc"},
+                    "asset_id": asset,
+                    "total_locked": "0",
+                    "total_unlocked": unlocked.to_string(),
+                    "trading_account_balance": "0",
+                    "order_books": {
+                        "0xb00c": {"locked": "0", "unlocked": unlocked.to_string(), "fee": "0"}
+                    },
+                }],
+                "seen_timestamp": 1,
+            }))
+            .unwrap()
+        };
+        // fFUEL has no threshold, so any amount of it is left alone.
+        apply(&settlement.unsettled, &update("0x01", 1_000));
+        apply(&settlement.unsettled, &update("0x02", 100));
+        assert!(!settlement.is_due(&market, 1));
+        apply(&settlement.unsettled, &update("0x02", 101));
+        assert!(settlement.is_due(&market, 1));
+
+        settlement.record(&market, 1, true);
+        assert!(!settlement.is_due(&market, 1));
+    }
+}
@@ -0,0 +1,314 @@
+//! Process-wide protection for the setup-time endpoints.
+//!
+//! Account creation, the faucet and the whitelist are called once per account,
+//! usually by [`O2Client::setup_account`](crate::O2Client::setup_account) at
+//! startup. When many CI jobs start together they all call them at once, and
+//! their retries turn a brief outage into a sustained one. Every client in the
+//! process shares one guard for these endpoints:
+//!
+//! - at most [`SetupGuardConfig::max_concurrent`] requests are in flight;
+//! - each waits a random delay of up to [`SetupGuardConfig::jitter`] first, so
+//!   jobs that start together do not arrive together;
+//! - [`SetupGuardConfig::failure_threshold`] consecutive transient failures on
+//!   an endpoint open its circuit for [`SetupGuardConfig::open_for`]. Requests
+//!   then fail at once with [`O2Error::CircuitOpen`], and the setup retries stop
+//!   instead of waiting. After that, a single request probes the endpoint: a
+//!   success closes the circuit, a failure opens it again.
+//!
+//! Transient failures are those [`O2Error::is_retryable`] reports, plus
+//! transport errors. Configure the guard once, before the first setup call:
+//!
+//! ```rust,ignore
+//! o2_sdk::setup_guard::configure(SetupGuardConfig {
+//!     max_concurrent: 2,
+//!     ..SetupGuardConfig::default()
+//! });
+//! ```
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tokio::sync::Semaphore;
+
+use crate::capabilities::Endpoint;
+use crate::errors::O2Error;
+use crate::runtime;
+use crate::trace::debug;
+
+/// Limits shared by every client's setup-time requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupGuardConfig {
+    /// Setup requests in flight at once, across the process (default: 4).
+    pub max_concurrent: usize,
+    /// Upper bound of the random delay before each request (default: 250ms).
+    pub jitter: Duration,
+    /// Consecutive transient failures that open an endpoint's circuit
+    /// (default: 5).
+    pub failure_threshold: u32,
+    /// How long an open circuit fails requests before probing (default: 30s).
+    pub open_for: Duration,
+}
+
+impl Default for SetupGuardConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            jitter: Duration::from_millis(250),
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of an endpoint's circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through.
+    Closed,
+    /// Requests fail at once for `retry_in`.
+    Open { retry_in: Duration },
+    /// One request is probing the endpoint; the others fail at once.
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// When the current probe started. A probe that never reports back, e.g.
+    /// because its future was dropped, is given up after `open_for`.
+    probe_started: Option<Instant>,
+}
+
+impl Circuit {
+    fn probing(&self, now: Instant, open_for: Duration) -> bool {
+        self.probe_started.is_some_and(|t| now < t + open_for)
+    }
+}
+
+struct Guard {
+    config: SetupGuardConfig,
+    permits: Arc<Semaphore>,
+    circuits: Mutex<HashMap<Endpoint, Circuit>>,
+}
+
+impl Guard {
+    fn new(config: SetupGuardConfig) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn circuits(&self) -> std::sync::MutexGuard<'_, HashMap<Endpoint, Circuit>> {
+        self.circuits.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn state(&self, endpoint: Endpoint, now: Instant) -> CircuitState {
+        match self.circuits().get(&endpoint) {
+            Some(c) if c.probing(now, self.config.open_for) => CircuitState::HalfOpen,
+            Some(Circuit {
+                open_until: Some(until),
+                ..
+            }) if *until > now => CircuitState::Open {
+                retry_in: *until - now,
+            },
+            _ => CircuitState::Closed,
+        }
+    }
+
+    /// Admit a request, or fail it if the circuit is open. `Ok(true)` if it
+    /// is the probe of a circuit whose open period has passed.
+    fn admit(&self, endpoint: Endpoint, now: Instant) -> Result<bool, O2Error> {
+        let mut circuits = self.circuits();
+        let circuit = circuits.entry(endpoint).or_default();
+        let Some(until) = circuit.open_until else {
+            return Ok(false);
+        };
+        if until > now {
+            return Err(O2Error::CircuitOpen {
+                endpoint,
+                retry_after: until - now,
+            });
+        }
+        if circuit.probing(now, self.config.open_for) {
+            return Err(O2Error::CircuitOpen {
+                endpoint,
+                retry_after: Duration::ZERO,
+            });
+        }
+        circuit.probe_started = Some(now);
+        Ok(true)
+    }
+
+    async fn run<T, F>(&self, endpoint: Endpoint, request: F) -> Result<T, O2Error>
+    where
+        F: Future<Output = Result<T, O2Error>>,
+    {
+        let _permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| O2Error::Other("setup guard closed".into()))?;
+        if !self.config.jitter.is_zero() {
+            let millis = self.config.jitter.as_millis() as u64;
+            let delay = rand::thread_rng().gen_range(0..=millis);
+            runtime::sleep(Duration::from_millis(delay)).await;
+        }
+        // Checked once admitted, so requests queued behind a failing burst
+        // fail at once instead of joining it.
+        self.admit(endpoint, Instant::now())?;
+        let result = request.await;
+        let failed = result.as_ref().err().is_some_and(is_transient);
+        self.record(endpoint, failed, Instant::now());
+        result
+    }
+
+    fn record(&self, endpoint: Endpoint, transient_failure: bool, now: Instant) {
+        let mut circuits = self.circuits();
+        let circuit = circuits.entry(endpoint).or_default();
+        circuit.probe_started = None;
+        if !transient_failure {
+            circuit.failures = 0;
+            circuit.open_until = None;
+            return;
+        }
+        circuit.failures += 1;
+        if circuit.failures >= self.config.failure_threshold.max(1) {
+            debug!(
+                "setup_guard.open endpoint={} failures={}",
+                endpoint, circuit.failures
+            );
+            circuit.open_until = Some(now + self.config.open_for);
+        }
+    }
+}
+
+fn global() -> &'static RwLock<Arc<Guard>> {
+    static GUARD: OnceLock<RwLock<Arc<Guard>>> = OnceLock::new();
+    GUARD.get_or_init(|| RwLock::new(Arc::new(Guard::new(SetupGuardConfig::default()))))
+}
+
+fn current() -> Arc<Guard> {
+    global().read().unwrap_or_else(|p| p.into_inner()).clone()
+}
+
+/// Replace the process's setup guard. Circuits start closed again; requests
+/// already waiting finish under the previous limits.
+pub fn configure(config: SetupGuardConfig) {
+    debug!("setup_guard.configure config={:?}", config);
+    *global().write().unwrap_or_else(|p| p.into_inner()) = Arc::new(Guard::new(config));
+}
+
+/// The limits in effect.
+pub fn config() -> SetupGuardConfig {
+    current().config.clone()
+}
+
+/// The state of `endpoint`'s circuit.
+pub fn circuit_state(endpoint: Endpoint) -> CircuitState {
+    current().state(endpoint, Instant::now())
+}
+
+fn is_transient(err: &O2Error) -> bool {
+    err.is_retryable() || matches!(err, O2Error::HttpError(_))
+}
+
+/// Run a request to `endpoint` under the process's setup guard.
+pub(crate) async fn guarded<T, F>(endpoint: Endpoint, request: F) -> Result<T, O2Error>
+where
+    F: Future<Output = Result<T, O2Error>>,
+{
+    current().run(endpoint, request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(threshold: u32) -> Guard {
+        Guard::new(SetupGuardConfig {
+            failure_threshold: threshold,
+            open_for: Duration::from_secs(10),
+            ..SetupGuardConfig::default()
+        })
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures_and_probes_once() {
+        let guard = guard(2);
+        let t0 = Instant::now();
+        let faucet = Endpoint::Faucet;
+
+        guard.record(faucet, true, t0);
+        guard.record(faucet, false, t0);
+        guard.record(faucet, true, t0);
+        assert_eq!(guard.state(faucet, t0), CircuitState::Closed);
+        guard.record(faucet, true, t0);
+        assert!(matches!(
+            guard.admit(faucet, t0 + Duration::from_secs(4)),
+            Err(O2Error::CircuitOpen { retry_after, .. }) if retry_after == Duration::from_secs(6)
+        ));
+        // Other endpoints are unaffected.
+        assert!(!guard.admit(Endpoint::Whitelist, t0).unwrap());
+
+        let later = t0 + Duration::from_secs(10);
+        assert!(guard.admit(faucet, later).unwrap());
+        assert_eq!(guard.state(faucet, later), CircuitState::HalfOpen);
+        assert!(guard.admit(faucet, later).is_err());
+        // A failed probe reopens at once.
+        guard.record(faucet, true, later);
+        assert!(matches!(
+            guard.state(faucet, later),
+            CircuitState::Open { .. }
+        ));
+
+        let again = later + Duration::from_secs(10);
+        assert!(guard.admit(faucet, again).unwrap());
+        guard.record(faucet, false, again);
+        assert_eq!(guard.state(faucet, again), CircuitState::Closed);
+        assert!(!guard.admit(faucet, again).unwrap());
+    }
+
+    #[tokio::test]
+    async fn limits_requests_in_flight_and_stops_queued_ones_once_open() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let guard = Guard::new(SetupGuardConfig {
+            max_concurrent: 2,
+            jitter: Duration::ZERO,
+            failure_threshold: 2,
+            open_for: Duration::from_secs(60),
+        });
+        let (in_flight, peak, calls) = (
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+        );
+        let request = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            Err::<(), _>(O2Error::HttpError("connection reset".into()))
+        };
+        let results = futures_util::future::join_all(
+            (0..6).map(|_| guard.run(Endpoint::Whitelist, request())),
+        )
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        // The first two failures open the circuit; the queued four never go out.
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let open = results
+            .iter()
+            .filter(|r| matches!(r, Err(O2Error::CircuitOpen { .. })))
+            .count();
+        assert_eq!(open, 4);
+    }
+}
@@ -0,0 +1,577 @@
+//! A structured event loop for live trading bots.
+//!
+//! A [`LiveStrategy`] reacts to depth, public trades, its own fills and a timer
+//! by returning [`Intent`]s, each an action on a market.
+//! [`O2Client::run_strategy`](crate::O2Client::run_strategy) starts a
+//! [`StrategyRunner`] that subscribes to those streams for the markets in
+//! [`RunnerConfig::markets`], calls the strategy and submits what it returns:
+//!
+//! - the intents from one callback go out together, grouped by market in
+//!   order of first appearance and split into batches of at most
+//!   [`MAX_ACTIONS_PER_BATCH`] actions;
+//! - batches go through a [`NonceManager`], so the runner can share the
+//!   account with other submitters;
+//! - a batch that certainly did not land, because it was rate limited or
+//!   reverted for a nonce another writer took, is retried with backoff up to
+//!   [`RunnerConfig::max_attempts`] times. Any other failure or rejection,
+//!   including a timeout that may have landed, is handed to
+//!   [`LiveStrategy::on_error`] and the loop carries on;
+//! - a market's book is cleared when the WebSocket drops, and
+//!   [`LiveStrategy::on_depth`] resumes once a fresh snapshot arrives.
+//!
+//! Callbacks are plain functions. Keep them quick: streams are not read while
+//! one runs or while its intents are being submitted.
+//!
+//! ```rust,ignore
+//! struct Fader;
+//!
+//! impl LiveStrategy for Fader {
+//!     fn on_depth(&mut self, market: &Market, book: &OrderBook) -> Vec<Intent> {
+//!         // Quote around book.mid_price() ...
+//!         Vec::new()
+//!     }
+//! }
+//!
+//! let nonces = client.nonce_manager(&session).await?;
+//! let config = RunnerConfig::new([MarketSymbol::new("fFUEL/fUSDC")]);
+//! let runner = client.run_strategy(&session, &nonces, Fader, config).await?;
+//! ```
+//!
+//! This trait is for live trading. Backtests replay data through
+//! [`backtest::Strategy`](crate::backtest::Strategy), which trades through a
+//! [`Trader`](crate::Trader) instead of returning intents.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::stream::SelectAll;
+use futures_util::StreamExt;
+use tokio::sync::watch;
+
+use crate::client::{split_action_batches, O2Client};
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, DepthUpdate, Identity, Market, MarketId, MarketSymbol, Order, OrderId, OrderType,
+    OrderUpdate, Session, Side, Trade, TradeUpdate, MAX_ACTIONS_PER_BATCH,
+};
+use crate::nonce::NonceManager;
+use crate::orderbook::OrderBook;
+use crate::runtime::{self, Task};
+use crate::timestamp::Timestamp;
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// An action a [`LiveStrategy`] wants taken on a market.
+#[derive(Debug, Clone)]
+pub struct Intent {
+    pub market: MarketSymbol,
+    pub action: Action,
+}
+
+impl Intent {
+    pub fn new(market: MarketSymbol, action: Action) -> Self {
+        Self { market, action }
+    }
+
+    /// Place an order. Prices and quantities are human-readable.
+    pub fn create_order(
+        market: MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+    ) -> Self {
+        Self::new(
+            market,
+            Action::CreateOrder {
+                side,
+                price,
+                quantity,
+                order_type,
+            },
+        )
+    }
+
+    pub fn cancel_order(market: MarketSymbol, order_id: OrderId) -> Self {
+        Self::new(market, Action::CancelOrder { order_id })
+    }
+
+    pub fn settle_balance(market: MarketSymbol) -> Self {
+        Self::new(market, Action::SettleBalance)
+    }
+}
+
+/// Quantity newly filled on one of the account's orders.
+#[derive(Debug, Clone)]
+pub struct Fill {
+    /// The order as last reported.
+    pub order: Order,
+    /// Quantity filled since the previous report, chain-scaled.
+    pub quantity: u64,
+}
+
+/// Trading logic driven by a [`StrategyRunner`].
+///
+/// Every callback has a default that does nothing, so a strategy implements
+/// only the events it needs.
+pub trait LiveStrategy: Send {
+    /// The book of `market` changed. Only called while the book is synced.
+    fn on_depth(&mut self, market: &Market, book: &OrderBook) -> Vec<Intent> {
+        let _ = (market, book);
+        Vec::new()
+    }
+
+    /// A public trade printed on `market`.
+    fn on_trade(&mut self, market: &Market, trade: &Trade) -> Vec<Intent> {
+        let _ = (market, trade);
+        Vec::new()
+    }
+
+    /// One of the account's orders on `market` filled some quantity.
+    fn on_fill(&mut self, market: &Market, fill: &Fill) -> Vec<Intent> {
+        let _ = (market, fill);
+        Vec::new()
+    }
+
+    /// Called every [`RunnerConfig::timer_interval`].
+    fn on_timer(&mut self, now: Timestamp) -> Vec<Intent> {
+        let _ = now;
+        Vec::new()
+    }
+
+    /// A batch was accepted. `orders` holds the orders it created.
+    fn on_submitted(&mut self, orders: &[Order]) {
+        let _ = orders;
+    }
+
+    /// A batch of `intents` failed or was rejected, after any retries.
+    fn on_error(&mut self, error: &O2Error, intents: &[Intent]) {
+        let _ = (error, intents);
+    }
+}
+
+/// Configuration for a [`StrategyRunner`].
+#[derive(Debug, Clone)]
+pub struct RunnerConfig {
+    /// Markets whose depth, trades and fills reach the strategy.
+    pub markets: Vec<MarketSymbol>,
+    /// Depth stream precision (default: 1).
+    pub depth_precision: u64,
+    /// How often [`LiveStrategy::on_timer`] runs; `None` never (default: 1s).
+    pub timer_interval: Option<Duration>,
+    /// Attempts at a batch that did not land, including the first
+    /// (default: 3).
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles on each further retry
+    /// (default: 200ms). A server `Retry-After` hint replaces it.
+    pub retry_delay: Duration,
+}
+
+impl RunnerConfig {
+    pub fn new(markets: impl IntoIterator<Item = MarketSymbol>) -> Self {
+        Self {
+            markets: markets.into_iter().collect(),
+            depth_precision: 1,
+            timer_interval: Some(Duration::from_secs(1)),
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+/// What a [`StrategyRunner`] has submitted so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunnerStats {
+    /// Batches accepted.
+    pub batches: u64,
+    /// Actions in accepted batches.
+    pub actions: u64,
+    /// Batches that failed or were rejected after any retries.
+    pub failed: u64,
+    /// Retries of batches that did not land.
+    pub retries: u64,
+    /// Most recent failure.
+    pub last_error: Option<String>,
+}
+
+/// A background task running a [`LiveStrategy`].
+///
+/// Dropping the runner stops it. Orders it placed stay on the book.
+pub struct StrategyRunner {
+    stats_rx: watch::Receiver<RunnerStats>,
+    task: Task<()>,
+}
+
+impl StrategyRunner {
+    /// Subscribe to the account's orders and to depth and trades on every
+    /// configured market, and start running `strategy`.
+    pub(crate) async fn start<S: LiveStrategy + 'static>(
+        mut worker: Worker,
+        strategy: S,
+        config: RunnerConfig,
+    ) -> Result<Self, O2Error> {
+        let orders = worker
+            .client
+            .stream_orders(&[Identity::ContractId(
+                worker.session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let mut depth = SelectAll::new();
+        let mut trades = SelectAll::new();
+        for symbol in &config.markets {
+            let market = worker.client.get_market(symbol).await?;
+            depth.push(
+                worker
+                    .client
+                    .stream_depth(&market.market_id, config.depth_precision)
+                    .await?,
+            );
+            trades.push(worker.client.stream_trades(&market.market_id).await?);
+            worker.books.insert(
+                market.market_id.clone(),
+                OrderBook::new(market.market_id.clone()),
+            );
+            worker.markets.insert(market.market_id.clone(), market);
+        }
+        debug!("strategy.start markets={}", worker.markets.len());
+        let (stats_tx, stats_rx) = watch::channel(RunnerStats::default());
+        let streams = Streams {
+            depth,
+            trades,
+            orders,
+        };
+        let task = runtime::spawn(run(worker, strategy, streams, config, stats_tx));
+        Ok(Self { stats_rx, task })
+    }
+
+    /// What has been submitted so far.
+    pub fn stats(&self) -> RunnerStats {
+        self.stats_rx.borrow().clone()
+    }
+
+    /// Receive every change to the stats.
+    pub fn subscribe(&self) -> watch::Receiver<RunnerStats> {
+        self.stats_rx.clone()
+    }
+
+    /// True until the account's order stream ends or the runner is stopped.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop running the strategy. A batch in flight may or may not land.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for StrategyRunner {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The client side of a [`StrategyRunner`]: books, fill tracking and
+/// submission.
+pub(crate) struct Worker {
+    client: O2Client,
+    session: Session,
+    nonces: NonceManager,
+    markets: HashMap<MarketId, Market>,
+    books: HashMap<MarketId, OrderBook>,
+    fills: FillTracker,
+    stats: RunnerStats,
+}
+
+impl Worker {
+    pub(crate) fn new(client: O2Client, session: Session, nonces: NonceManager) -> Self {
+        Self {
+            client,
+            session,
+            nonces,
+            markets: HashMap::new(),
+            books: HashMap::new(),
+            fills: FillTracker::default(),
+            stats: RunnerStats::default(),
+        }
+    }
+
+    fn on_depth<S: LiveStrategy>(&mut self, strategy: &mut S, update: &DepthUpdate) -> Vec<Intent> {
+        let (Some(market), Some(book)) = (
+            self.markets.get(&update.market_id),
+            self.books.get_mut(&update.market_id),
+        ) else {
+            return Vec::new();
+        };
+        if !book.apply(update) || !book.is_synced() {
+            return Vec::new();
+        }
+        strategy.on_depth(market, book)
+    }
+
+    fn on_trades<S: LiveStrategy>(
+        &mut self,
+        strategy: &mut S,
+        update: &TradeUpdate,
+    ) -> Vec<Intent> {
+        let Some(market) = self.markets.get(&update.market_id) else {
+            return Vec::new();
+        };
+        update
+            .trades
+            .iter()
+            .flat_map(|trade| strategy.on_trade(market, trade))
+            .collect()
+    }
+
+    fn on_orders<S: LiveStrategy>(
+        &mut self,
+        strategy: &mut S,
+        update: &OrderUpdate,
+    ) -> Vec<Intent> {
+        let mut intents = Vec::new();
+        for order in &update.orders {
+            let Some(market) = order.market_id.as_ref().and_then(|id| self.markets.get(id)) else {
+                continue;
+            };
+            if let Some(fill) = self.fills.apply(order) {
+                intents.extend(strategy.on_fill(market, &fill));
+            }
+        }
+        intents
+    }
+
+    /// Submit `intents`, retrying batches that did not land.
+    async fn submit<S: LiveStrategy>(
+        &mut self,
+        strategy: &mut S,
+        intents: Vec<Intent>,
+        config: &RunnerConfig,
+    ) {
+        for batch in split_action_batches(&group(intents), MAX_ACTIONS_PER_BATCH) {
+            let actions: usize = batch.iter().map(|(_, actions)| actions.len()).sum();
+            let mut attempt = 1;
+            let error = loop {
+                let result = self
+                    .nonces
+                    .batch_actions_multi(&mut self.client, &mut self.session, &batch, true)
+                    .await;
+                match result {
+                    Ok(resp) if resp.is_success() => {
+                        self.stats.batches += 1;
+                        self.stats.actions += actions as u64;
+                        strategy.on_submitted(resp.orders.as_deref().unwrap_or_default());
+                        break None;
+                    }
+                    Ok(resp) => {
+                        break Some(O2Error::Other(format!(
+                            "Batch rejected: {}",
+                            resp.message.as_deref().unwrap_or("no message")
+                        )))
+                    }
+                    Err(e) if attempt < config.max_attempts && did_not_land(&e) => {
+                        let delay = e.retry_after().unwrap_or_else(|| {
+                            config
+                                .retry_delay
+                                .saturating_mul(2u32.saturating_pow(attempt - 1))
+                        });
+                        debug!(
+                            "strategy.retry attempt={} delay_ms={} error={}",
+                            attempt,
+                            delay.as_millis(),
+                            e
+                        );
+                        self.stats.retries += 1;
+                        runtime::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    Err(e) => break Some(e),
+                }
+            };
+            if let Some(error) = error {
+                debug!("strategy.batch_failed actions={} error={}", actions, error);
+                self.stats.failed += 1;
+                self.stats.last_error = Some(error.to_string());
+                let intents: Vec<Intent> = batch
+                    .into_iter()
+                    .flat_map(|(market, actions)| {
+                        actions
+                            .into_iter()
+                            .map(move |action| Intent::new(market.clone(), action))
+                    })
+                    .collect();
+                strategy.on_error(&error, &intents);
+            }
+        }
+    }
+}
+
+/// Whether a failed submission certainly did not land, so sending it again
+/// cannot double it.
+fn did_not_land(error: &O2Error) -> bool {
+    matches!(
+        error,
+        O2Error::RateLimited { .. }
+            | O2Error::RateLimitExceeded(_)
+            | O2Error::NonceContention { .. }
+    )
+}
+
+/// Actions grouped by market, markets in order of first appearance.
+fn group(intents: Vec<Intent>) -> Vec<(MarketSymbol, Vec<Action>)> {
+    let mut grouped: Vec<(MarketSymbol, Vec<Action>)> = Vec::new();
+    for intent in intents {
+        match grouped
+            .iter_mut()
+            .find(|(market, _)| *market == intent.market)
+        {
+            Some((_, actions)) => actions.push(intent.action),
+            None => grouped.push((intent.market, vec![intent.action])),
+        }
+    }
+    grouped
+}
+
+/// How much of each open order has been reported filled.
+#[derive(Debug, Default)]
+struct FillTracker {
+    seen: HashMap<OrderId, u64>,
+}
+
+impl FillTracker {
+    /// The quantity `order` filled since it was last seen, if any.
+    fn apply(&mut self, order: &Order) -> Option<Fill> {
+        let filled = order.quantity_fill.unwrap_or(0);
+        let seen = self.seen.entry(order.order_id.clone()).or_insert(0);
+        let delta = filled.saturating_sub(*seen);
+        *seen = (*seen).max(filled);
+        if order.close || order.cancel {
+            self.seen.remove(&order.order_id);
+        }
+        (delta > 0).then(|| Fill {
+            order: order.clone(),
+            quantity: delta,
+        })
+    }
+}
+
+struct Streams {
+    depth: SelectAll<TypedStream<DepthUpdate>>,
+    trades: SelectAll<TypedStream<TradeUpdate>>,
+    orders: TypedStream<OrderUpdate>,
+}
+
+async fn run<S: LiveStrategy>(
+    mut worker: Worker,
+    mut strategy: S,
+    mut streams: Streams,
+    config: RunnerConfig,
+    stats_tx: watch::Sender<RunnerStats>,
+) {
+    let mut timer = runtime::interval(config.timer_interval.unwrap_or(Duration::from_secs(3600)));
+    let timer_enabled = config.timer_interval.is_some();
+    loop {
+        let intents = tokio::select! {
+            Some(item) = streams.depth.next() => match item {
+                Ok(update) => worker.on_depth(&mut strategy, &update),
+                Err(e) => {
+                    debug!("strategy.run depth_stream_error={}", e);
+                    if matches!(e, O2Error::WebSocketDisconnected(_)) {
+                        worker.books.values_mut().for_each(OrderBook::clear);
+                    }
+                    Vec::new()
+                }
+            },
+            Some(item) = streams.trades.next() => match item {
+                Ok(update) => worker.on_trades(&mut strategy, &update),
+                Err(e) => {
+                    debug!("strategy.run trade_stream_error={}", e);
+                    Vec::new()
+                }
+            },
+            item = streams.orders.next() => match item {
+                None => break,
+                Some(Ok(update)) => worker.on_orders(&mut strategy, &update),
+                Some(Err(e)) => {
+                    debug!("strategy.run order_stream_error={}", e);
+                    Vec::new()
+                }
+            },
+            _ = timer.tick(), if timer_enabled => strategy.on_timer(Timestamp::now()),
+        };
+        if intents.is_empty() {
+            continue;
+        }
+        worker.submit(&mut strategy, intents, &config).await;
+        let _ = stats_tx.send(worker.stats.clone());
+    }
+    debug!(
+        "strategy.run finished batches={} failed={}",
+        worker.stats.batches, worker.stats.failed
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: &str, filled: u64, close: bool) -> Order {
+        serde_json::from_value(serde_json::json!({
+            "order_id": id,
+            "side": "Buy",
+            "order_type": "Spot",
+            "quantity": "100",
+            "quantity_fill": filled.to_string(),
+            "price": "1",
+            "close": close,
+            "market_id": "0xaa",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn intents_are_grouped_by_market_in_order() {
+        let fuel = MarketSymbol::new("fFUEL/fUSDC");
+        let eth = MarketSymbol::new("fETH/fUSDC");
+        let cancel = |market: &MarketSymbol, id: &str| {
+            Intent::cancel_order(market.clone(), OrderId::new(id))
+        };
+        let grouped = group(vec![
+            cancel(&fuel, "0x01"),
+            cancel(&eth, "0x02"),
+            Intent::settle_balance(fuel.clone()),
+        ]);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].0, fuel);
+        assert!(matches!(
+            grouped[0].1.as_slice(),
+            [Action::CancelOrder { order_id }, Action::SettleBalance] if order_id.as_str() == "0x01"
+        ));
+        assert_eq!(grouped[1].0, eth);
+        assert_eq!(grouped[1].1.len(), 1);
+
+        assert!(did_not_land(&O2Error::RateLimitExceeded(
+            "slow down".into()
+        )));
+        assert!(!did_not_land(&O2Error::HttpError("timed out".into())));
+        assert!(did_not_land(&O2Error::NonceContention {
+            nonce: 3,
+            current: 6,
+            source: Box::new(O2Error::OnChainRevert {
+                message: "Failed to process transaction".into(),
+                reason: "NonceError::InvalidNonce".into(),
+                receipts: None,
+            }),
+        }));
+    }
+
+    #[test]
+    fn fills_are_reported_once_per_increment() {
+        let mut fills = FillTracker::default();
+        assert!(fills.apply(&order("0x01", 0, false)).is_none());
+        assert_eq!(fills.apply(&order("0x01", 30, false)).unwrap().quantity, 30);
+        assert!(fills.apply(&order("0x01", 30, false)).is_none());
+        assert_eq!(fills.apply(&order("0x01", 100, true)).unwrap().quantity, 70);
+        assert!(fills.seen.is_empty());
+    }
+}
@@ -0,0 +1,15 @@
+//! Helpers for downstream integration tests. Requires the `test-util` feature.
+//!
+//! - [`testnet`]: persistent wallets, account setup and faucet funding against
+//!   the public testnet, as used by this crate's own integration tests.
+//! - [`mock_api`]: a local REST server with scripted responses, latency and
+//!   failures, for deterministic tests of retry and reconciliation logic.
+//! - [`mock_transport`]: the same scripts served in-process through a
+//!   [`Transport`](crate::transport::Transport), with canned JSON fixtures, for
+//!   unit tests of bot logic.
+//! - [`fill_sim`]: replays trade prints against a strategy's resting orders
+//!   with O2's price-time priority and maker fees, for PnL assertions.
+pub mod fill_sim;
+pub mod mock_api;
+pub mod mock_transport;
+pub mod testnet;
@@ -0,0 +1,374 @@
+//! Deterministic fills for strategy unit tests, without a live venue.
+//!
+//! A [`FillSimulator`] holds a strategy's resting orders on one market and
+//! replays the market's trade prints against them the way O2's book would
+//! match them: better prices first, then earlier orders, each fill at the
+//! resting order's own price and charged the maker fee. It keeps the
+//! position, quote flow and fees of the fills, so a test can assert a PnL
+//! path from a recorded or hand-written tape.
+//!
+//! ```rust,ignore
+//! let mut sim = FillSimulator::new(market)?;
+//! let ask = sim.place(Side::Sell, 2_000_000_000, 5_000_000_000);
+//! // A buyer lifts the book through 2.01.
+//! let fills = sim.apply(Print::new(Side::Sell, 2_010_000_000, 3_000_000_000));
+//! assert_eq!(fills[0].order_id, ask);
+//! assert_eq!(sim.position(), -3_000_000_000);
+//! ```
+//!
+//! A print only says that its quantity traded at its price, not where the
+//! strategy's order sat in the queue at that price; [`QueuePosition`] picks
+//! the assumption. All amounts are chain units.
+use crate::errors::O2Error;
+use crate::fees::FeeRates;
+use crate::models::{Market, Order, OrderId, Side, Trade};
+use crate::timestamp::Timestamp;
+
+/// Where the strategy's orders sit among others resting at the same price.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePosition {
+    /// Behind everyone else: an order fills only when a print trades through
+    /// its price.
+    #[default]
+    Back,
+    /// Ahead of everyone else: a print at an order's price fills it too.
+    Front,
+}
+
+/// One trade on the market's tape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Print {
+    /// The resting side of the trade, as in [`Trade::side`]: `Sell` when a
+    /// buyer took liquidity.
+    pub maker_side: Side,
+    pub price: u64,
+    pub quantity: u64,
+    pub timestamp: Option<Timestamp>,
+}
+
+impl Print {
+    pub fn new(maker_side: Side, price: u64, quantity: u64) -> Self {
+        Self {
+            maker_side,
+            price,
+            quantity,
+            timestamp: None,
+        }
+    }
+}
+
+impl From<&Trade> for Print {
+    fn from(trade: &Trade) -> Self {
+        Self {
+            maker_side: trade.side,
+            price: trade.price,
+            quantity: trade.quantity,
+            timestamp: Some(trade.timestamp),
+        }
+    }
+}
+
+/// A resting order of the strategy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimOrder {
+    pub order_id: OrderId,
+    pub side: Side,
+    pub price: u64,
+    /// Quantity not yet filled.
+    pub remaining: u64,
+    seq: u64,
+}
+
+/// A fill of one of the strategy's orders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimFill {
+    pub order_id: OrderId,
+    pub side: Side,
+    /// The resting order's price.
+    pub price: u64,
+    pub quantity: u64,
+    /// Quote units, `price * quantity` scaled by the base decimals.
+    pub notional: u128,
+    /// Maker fee in quote units.
+    pub fee: u128,
+    /// Quantity of the order left after this fill.
+    pub remaining: u64,
+    /// Timestamp of the print that caused the fill, if it had one.
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Replays trade prints against a set of resting orders on one market.
+#[derive(Debug, Clone)]
+pub struct FillSimulator {
+    market: Market,
+    fees: FeeRates,
+    queue: QueuePosition,
+    orders: Vec<SimOrder>,
+    fills: Vec<SimFill>,
+    next_seq: u64,
+    position: i128,
+    quote: i128,
+    fees_paid: u128,
+}
+
+impl FillSimulator {
+    /// A simulator charging the market's own fees, with orders at the back
+    /// of their price level.
+    pub fn new(market: Market) -> Result<Self, O2Error> {
+        // Fails here rather than on the first fill if the decimals are unusable.
+        let fees = FeeRates::from_market(&market);
+        fees.chain_fee_units(&market, 0, 0, true)?;
+        Ok(Self {
+            market,
+            fees,
+            queue: QueuePosition::default(),
+            orders: Vec::new(),
+            fills: Vec::new(),
+            next_seq: 0,
+            position: 0,
+            quote: 0,
+            fees_paid: 0,
+        })
+    }
+
+    /// Charge `fees` instead of the market's, e.g. an account override.
+    pub fn with_fees(mut self, fees: FeeRates) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_queue_position(mut self, queue: QueuePosition) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    /// Rest a new order behind every order already resting. Returns its
+    /// simulated id.
+    pub fn place(&mut self, side: Side, price: u64, quantity: u64) -> OrderId {
+        let order_id = OrderId::new(format!("sim-{}", self.next_seq + 1));
+        self.push(order_id.clone(), side, price, quantity);
+        order_id
+    }
+
+    /// Rest an open order fetched from the API, with what is left of it.
+    pub fn rest(&mut self, order: &Order) {
+        let remaining = order
+            .quantity
+            .saturating_sub(order.quantity_fill.unwrap_or(0));
+        self.push(order.order_id.clone(), order.side, order.price, remaining);
+    }
+
+    /// Remove a resting order. Returns false if it is not resting.
+    pub fn cancel(&mut self, order_id: &OrderId) -> bool {
+        let before = self.orders.len();
+        self.orders.retain(|o| &o.order_id != order_id);
+        self.orders.len() != before
+    }
+
+    /// Match one print against the resting orders and return the fills it
+    /// caused, in matching order.
+    pub fn apply(&mut self, print: impl Into<Print>) -> Vec<SimFill> {
+        let print = print.into();
+        let queue = self.queue;
+        let mut matched: Vec<usize> = (0..self.orders.len())
+            .filter(|&i| {
+                let o = &self.orders[i];
+                o.side == print.maker_side && crosses(o, &print, queue)
+            })
+            .collect();
+        // Price-time priority: best price for the taker, then earliest.
+        matched.sort_by(|&a, &b| {
+            let (a, b) = (&self.orders[a], &self.orders[b]);
+            let by_price = match print.maker_side {
+                Side::Sell => a.price.cmp(&b.price),
+                Side::Buy => b.price.cmp(&a.price),
+            };
+            by_price.then(a.seq.cmp(&b.seq))
+        });
+
+        let mut left = print.quantity;
+        let mut fills = Vec::new();
+        for i in matched {
+            if left == 0 {
+                break;
+            }
+            let order = &mut self.orders[i];
+            let quantity = order.remaining.min(left);
+            left -= quantity;
+            order.remaining -= quantity;
+            let (notional, fee) = self
+                .fees
+                .chain_fee_units(&self.market, order.price, quantity, true)
+                .expect("base decimals checked in FillSimulator::new");
+            fills.push(SimFill {
+                order_id: order.order_id.clone(),
+                side: order.side,
+                price: order.price,
+                quantity,
+                notional,
+                fee,
+                remaining: order.remaining,
+                timestamp: print.timestamp,
+            });
+        }
+        self.orders.retain(|o| o.remaining > 0);
+        for fill in &fills {
+            self.record(fill);
+        }
+        self.fills.extend(fills.iter().cloned());
+        fills
+    }
+
+    /// Apply each print in order and return all the fills.
+    pub fn replay<P: Into<Print>>(&mut self, prints: impl IntoIterator<Item = P>) -> Vec<SimFill> {
+        prints.into_iter().flat_map(|p| self.apply(p)).collect()
+    }
+
+    /// Resting orders, in the order they were placed.
+    pub fn resting(&self) -> &[SimOrder] {
+        &self.orders
+    }
+
+    /// Every fill so far.
+    pub fn fills(&self) -> &[SimFill] {
+        &self.fills
+    }
+
+    /// Net base quantity bought.
+    pub fn position(&self) -> i128 {
+        self.position
+    }
+
+    /// Net quote received, after fees.
+    pub fn quote_flow(&self) -> i128 {
+        self.quote
+    }
+
+    pub fn fees_paid(&self) -> u128 {
+        self.fees_paid
+    }
+
+    /// Quote flow plus the position valued at `mark_price`, rounded toward
+    /// zero.
+    pub fn pnl(&self, mark_price: u64) -> i128 {
+        let base_factor = 10i128.pow(self.market.base.decimals);
+        self.quote + self.position * mark_price as i128 / base_factor
+    }
+
+    fn push(&mut self, order_id: OrderId, side: Side, price: u64, remaining: u64) {
+        self.next_seq += 1;
+        if remaining == 0 {
+            return;
+        }
+        self.orders.push(SimOrder {
+            order_id,
+            side,
+            price,
+            remaining,
+            seq: self.next_seq,
+        });
+    }
+
+    fn record(&mut self, fill: &SimFill) {
+        let (quantity, notional) = (fill.quantity as i128, fill.notional as i128);
+        match fill.side {
+            Side::Buy => {
+                self.position += quantity;
+                self.quote -= notional;
+            }
+            Side::Sell => {
+                self.position -= quantity;
+                self.quote += notional;
+            }
+        }
+        self.quote -= fill.fee as i128;
+        self.fees_paid += fill.fee;
+    }
+}
+
+fn crosses(order: &SimOrder, print: &Print, queue: QueuePosition) -> bool {
+    if order.price == print.price {
+        return queue == QueuePosition::Front;
+    }
+    match order.side {
+        Side::Sell => order.price < print.price,
+        Side::Buy => order.price > print.price,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: u64 = 1_000_000_000;
+
+    fn market() -> Market {
+        serde_json::from_value(serde_json::json!({
+            "contract_id": "0x01",
+            "market_id": "0xaa",
+            "whitelist_id": null,
+            "blacklist_id": null,
+            "maker_fee": "100",
+            "taker_fee": "200",
+            "min_order": "0",
+            "dust": "0",
+            "price_window": "0",
+            "base": { "symbol": "fFUEL", "asset": "0x02", "decimals": 9, "max_precision": 3 },
+            "quote": { "symbol": "fUSDC", "asset": "0x03", "decimals": 9, "max_precision": 9 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn prints_fill_by_price_then_time() {
+        let mut sim = FillSimulator::new(market()).unwrap();
+        let first = sim.place(Side::Sell, 2 * UNIT, 5 * UNIT);
+        let worse = sim.place(Side::Sell, 3 * UNIT, 5 * UNIT);
+        let second = sim.place(Side::Sell, 2 * UNIT, 5 * UNIT);
+        let bid = sim.place(Side::Buy, UNIT, 5 * UNIT);
+
+        // A buyer lifts 8 through 3.0: both 2.0 asks fill ahead of the 3.0
+        // ask, the earlier one first; the print at 3.0 itself does not reach
+        // an order at the back of that level.
+        let fills = sim.apply(Print::new(Side::Sell, 3 * UNIT, 8 * UNIT));
+        let filled: Vec<_> = fills.iter().map(|f| (&f.order_id, f.quantity)).collect();
+        assert_eq!(filled, vec![(&first, 5 * UNIT), (&second, 3 * UNIT)]);
+        assert_eq!(fills[0].notional, 10 * UNIT as u128);
+        // 0.01% maker fee.
+        assert_eq!(fills[0].fee, 1_000_000);
+        assert_eq!(fills[1].remaining, 2 * UNIT);
+        let resting: Vec<_> = sim.resting().iter().map(|o| &o.order_id).collect();
+        assert_eq!(resting, vec![&worse, &second, &bid]);
+
+        // A seller hits the bid.
+        let fills = sim.apply(Print::new(Side::Buy, UNIT / 2, UNIT));
+        assert_eq!(fills[0].order_id, bid);
+
+        assert_eq!(sim.position(), -7 * UNIT as i128);
+        assert_eq!(sim.fees_paid(), 1_700_000);
+        assert_eq!(sim.quote_flow(), (16 * UNIT - UNIT - 1_700_000) as i128);
+        // Marked at 2.0, the 7 short are worth -14.
+        assert_eq!(sim.pnl(2 * UNIT), (UNIT - 1_700_000) as i128);
+    }
+
+    #[test]
+    fn queue_position_decides_fills_at_the_print_price() {
+        let print = Print::new(Side::Buy, 2 * UNIT, UNIT);
+
+        let mut back = FillSimulator::new(market()).unwrap();
+        back.place(Side::Buy, 2 * UNIT, UNIT);
+        assert!(back.apply(print.clone()).is_empty());
+
+        let mut front = FillSimulator::new(market())
+            .unwrap()
+            .with_queue_position(QueuePosition::Front)
+            .with_fees(FeeRates::new(0, 0));
+        let bid = front.place(Side::Buy, 2 * UNIT, UNIT);
+        let fills = front.replay([print.clone(), print]);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, bid);
+        assert_eq!(fills[0].fee, 0);
+        assert!(front.resting().is_empty());
+        assert_eq!(front.pnl(3 * UNIT), UNIT as i128);
+    }
+}
@@ -0,0 +1,483 @@
+//! An in-process O2 REST server with scripted responses, for deterministic
+//! tests of retry and reconciliation logic.
+//!
+//! [`MockO2Api`] listens on a local port and answers each route from a script
+//! of [`MockResponse`]s, consumed one per request with the last one repeating.
+//! Responses can be delayed, and can fail the way a real deployment does: an
+//! error status, a rate limit with `Retry-After`, a connection dropped after
+//! the request was read, or no answer at all. Routes without a script get a
+//! bare 404, which the client reports as
+//! [`O2Error::Unsupported`](crate::O2Error::Unsupported). Every request is
+//! recorded, so a test can tell whether a write reached the server before the
+//! client saw it fail.
+//!
+//! ```rust,ignore
+//! let mock = MockO2Api::start().await;
+//! mock.script("GET", "/v1/markets", [
+//!     MockResponse::rate_limited(Duration::from_secs(1)),
+//!     MockResponse::json(markets_json),
+//! ]);
+//! // Accepted by the server, but the client never hears back.
+//! mock.script("POST", "/v1/session/actions", [MockResponse::hang()]);
+//!
+//! let mut client = mock.client();
+//! client.get_markets().await?;
+//! assert_eq!(mock.requests_to("GET", "/v1/markets").len(), 2);
+//! ```
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::api::O2Api;
+use crate::client::O2Client;
+use crate::config::NetworkConfig;
+
+/// Request timeout of the clients built by [`MockO2Api::api`], so that
+/// [`MockResponse::hang`] fails fast.
+pub const MOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the mock does with one request.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub(super) delay: Duration,
+    pub(super) kind: MockKind,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum MockKind {
+    Reply {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String,
+    },
+    Disconnect,
+    Hang,
+}
+
+impl MockResponse {
+    /// `200 OK` with a JSON body.
+    pub fn json(body: Value) -> Self {
+        Self::status_json(200, body)
+    }
+
+    /// `status` with a JSON body, e.g. an API error
+    /// `{"code": 4002, "message": "..."}`.
+    pub fn status_json(status: u16, body: Value) -> Self {
+        Self::reply(status, body.to_string())
+    }
+
+    /// `status` with an empty body, like a proxy or load balancer error.
+    pub fn status(status: u16) -> Self {
+        Self::reply(status, String::new())
+    }
+
+    /// `429 Too Many Requests` with a `Retry-After` of `retry_after`, in
+    /// whole seconds.
+    pub fn rate_limited(retry_after: Duration) -> Self {
+        Self::status(429).with_header("retry-after", retry_after.as_secs().to_string())
+    }
+
+    /// Read the request, then close the connection without answering.
+    pub fn disconnect() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            kind: MockKind::Disconnect,
+        }
+    }
+
+    /// Read the request and never answer, so the client times out. For a
+    /// write, the request is recorded: the outcome is ambiguous to the client.
+    pub fn hang() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            kind: MockKind::Hang,
+        }
+    }
+
+    /// Wait `delay` after reading the request before acting on it.
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Add a response header. Ignored for [`disconnect`](Self::disconnect) and
+    /// [`hang`](Self::hang).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        if let MockKind::Reply { headers, .. } = &mut self.kind {
+            headers.push((name.into(), value.into()));
+        }
+        self
+    }
+
+    fn reply(status: u16, body: String) -> Self {
+        Self {
+            delay: Duration::ZERO,
+            kind: MockKind::Reply {
+                status,
+                headers: Vec::new(),
+                body,
+            },
+        }
+    }
+}
+
+/// A request the mock received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    pub query: Option<String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// The body parsed as JSON, or `Value::Null` if it is not JSON.
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+/// Scripts and recorded requests, shared with
+/// [`MockTransport`](super::mock_transport::MockTransport).
+#[derive(Debug, Default)]
+pub(super) struct MockState {
+    routes: HashMap<(String, String), Vec<MockResponse>>,
+    latency: Duration,
+    requests: Vec<RecordedRequest>,
+}
+
+impl MockState {
+    pub(super) fn script(&mut self, method: &str, path: &str, responses: Vec<MockResponse>) {
+        assert!(!responses.is_empty(), "a mock script needs a response");
+        self.routes
+            .insert((method.to_ascii_uppercase(), path.to_string()), responses);
+    }
+
+    pub(super) fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    pub(super) fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.clone()
+    }
+
+    pub(super) fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests
+            .iter()
+            .filter(|r| r.method.eq_ignore_ascii_case(method) && r.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// Record `request` and pick its response: the script's next one, or a
+    /// bare 404 for an unscripted route. The delay includes the latency.
+    pub(super) fn answer(&mut self, request: RecordedRequest) -> (MockResponse, Duration) {
+        let response = self
+            .next(&request.method, &request.path, request.query.as_deref())
+            .unwrap_or_else(|| MockResponse::status(404));
+        self.requests.push(request);
+        let delay = self.latency + response.delay;
+        (response, delay)
+    }
+
+    /// The next scripted response for a request; the last one repeats.
+    fn next(&mut self, method: &str, path: &str, query: Option<&str>) -> Option<MockResponse> {
+        let params: Vec<&str> = query.map(|q| q.split('&').collect()).unwrap_or_default();
+        // A route with a query wins when the request carries all its
+        // parameters; the one naming the most parameters is picked.
+        let mut key = self
+            .routes
+            .keys()
+            .filter(|(m, p)| {
+                m == method
+                    && p.split_once('?').is_some_and(|(route_path, route_query)| {
+                        route_path == path
+                            && route_query.split('&').all(|pair| params.contains(&pair))
+                    })
+            })
+            .max_by_key(|(_, p)| p.matches('&').count())
+            .cloned()
+            .unwrap_or_else(|| (method.to_string(), path.to_string()));
+        if !self.routes.contains_key(&key) {
+            // Fall back to the longest `prefix*` route.
+            key = self
+                .routes
+                .keys()
+                .filter(|(m, p)| {
+                    m == method && p.strip_suffix('*').is_some_and(|pre| path.starts_with(pre))
+                })
+                .max_by_key(|(_, p)| p.len())?
+                .clone();
+        }
+        let script = self.routes.get_mut(&key)?;
+        if script.len() > 1 {
+            Some(script.remove(0))
+        } else {
+            script.first().cloned()
+        }
+    }
+}
+
+/// A scripted O2 REST server on a local port. Stops when dropped.
+#[derive(Debug)]
+pub struct MockO2Api {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockO2Api {
+    /// Start listening on a free local port.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock O2 API");
+        let addr = listener.local_addr().expect("mock O2 API address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, shared.clone()));
+            }
+        });
+        Self { addr, state, task }
+    }
+
+    /// Answer `method` requests to `path` with `responses`, in order, repeating
+    /// the last. A path ending in `*` matches every path with that prefix; an
+    /// exact route wins over a prefix. A path with a query, such as
+    /// `/v1/balance?asset_id=0x01`, matches requests carrying those
+    /// parameters and wins over the bare path. Replaces any earlier script for
+    /// the route.
+    pub fn script(
+        &self,
+        method: &str,
+        path: &str,
+        responses: impl IntoIterator<Item = MockResponse>,
+    ) {
+        self.lock()
+            .script(method, path, responses.into_iter().collect());
+    }
+
+    /// Delay every response by `latency`, on top of its own delay.
+    pub fn set_latency(&self, latency: Duration) {
+        self.lock().set_latency(latency);
+    }
+
+    /// Every request received so far, in arrival order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests()
+    }
+
+    /// Requests received for one route, ignoring the query string.
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.lock().requests_to(method, path)
+    }
+
+    /// Base URL of the mock, e.g. `http://127.0.0.1:40123`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A network configuration pointing REST at the mock. WebSocket and RPC
+    /// point at a closed port.
+    pub fn config(&self) -> NetworkConfig {
+        NetworkConfig {
+            api_base: self.url(),
+            ws_url: "ws://127.0.0.1:9".into(),
+            fuel_rpc: "http://127.0.0.1:9".into(),
+            faucet_url: None,
+            whitelist_required: false,
+            chain_id: None,
+            api_base_fallbacks: Vec::new(),
+            ws_url_fallbacks: Vec::new(),
+            failover: Default::default(),
+        }
+    }
+
+    /// A REST client for the mock, timing out after [`MOCK_REQUEST_TIMEOUT`].
+    /// The retry policy is the default; set one to suit the test.
+    pub fn api(&self) -> O2Api {
+        O2Api::builder(self.config())
+            .timeout(MOCK_REQUEST_TIMEOUT)
+            .build()
+            .expect("build mock O2 API client")
+    }
+
+    /// An [`O2Client`] around [`api`](Self::api).
+    pub fn client(&self) -> O2Client {
+        O2Client::with_api(self.api())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl Drop for MockO2Api {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    let (response, delay) = state
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .answer(request);
+    tokio::time::sleep(delay).await;
+    match response.kind {
+        MockKind::Reply {
+            status,
+            headers,
+            body,
+        } => {
+            let mut head = format!("HTTP/1.1 {status} {}\r\n", reason(status));
+            for (name, value) in headers {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            if !body.is_empty() {
+                head.push_str("content-type: application/json\r\n");
+            }
+            head.push_str(&format!(
+                "content-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            ));
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(body.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+        MockKind::Disconnect => {}
+        MockKind::Hang => std::future::pending::<()>().await,
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = stream.read(&mut buf).await.ok()?;
+        raw.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&raw);
+        let Some(split) = text.find("\r\n\r\n") else {
+            if n == 0 {
+                return None;
+            }
+            continue;
+        };
+        let length = text[..split]
+            .lines()
+            .find_map(|l| {
+                l.to_ascii_lowercase()
+                    .strip_prefix("content-length:")
+                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+            })
+            .unwrap_or(0);
+        if n != 0 && raw.len() < split + 4 + length {
+            continue;
+        }
+        let mut request_line = text[..split].lines().next()?.split(' ');
+        let method = request_line.next()?.to_string();
+        let target = request_line.next()?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (target.to_string(), None),
+        };
+        return Some(RecordedRequest {
+            method,
+            path,
+            query,
+            body: text[split + 4..].to_string(),
+        });
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Status",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::O2Error;
+    use crate::retry::RetryPolicy;
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn scripts_are_consumed_in_order_and_the_last_repeats() {
+        let mock = MockO2Api::start().await;
+        mock.script(
+            "GET",
+            "/v1/markets",
+            [
+                MockResponse::rate_limited(Duration::ZERO),
+                MockResponse::status(503),
+                MockResponse::status_json(500, serde_json::json!({"message": "boom"})),
+            ],
+        );
+        let api = mock.api().with_retry_policy(fast_retries());
+        assert!(api.get_markets().await.is_err());
+        assert_eq!(mock.requests_to("GET", "/v1/markets").len(), 3);
+        assert!(api.get_markets().await.is_err());
+        assert_eq!(mock.requests_to("GET", "/v1/markets").len(), 6);
+
+        mock.script(
+            "GET",
+            "/v1/*",
+            [MockResponse::disconnect(), MockResponse::status(502)],
+        );
+        assert!(api.get_depth("0xaa", 1, None).await.is_err());
+        let depth = mock.requests_to("GET", "/v1/depth");
+        assert_eq!(depth.len(), 3);
+        assert!(depth[0]
+            .query
+            .as_deref()
+            .unwrap()
+            .contains("market_id=0xaa"));
+    }
+
+    #[tokio::test]
+    async fn hung_writes_are_recorded_and_unscripted_routes_are_missing() {
+        let mock = MockO2Api::start().await;
+        mock.script("PUT", "/v1/session", [MockResponse::hang()]);
+        let sent = reqwest::Client::new()
+            .put(format!("{}/v1/session", mock.url()))
+            .body(r#"{"nonce":"1"}"#)
+            .timeout(Duration::from_millis(100))
+            .send()
+            .await;
+        assert!(sent.unwrap_err().is_timeout());
+        let recorded = mock.requests_to("PUT", "/v1/session");
+        assert_eq!(recorded[0].json()["nonce"], "1");
+
+        let err = mock.api().get_market_summary("0xaa").await.unwrap_err();
+        assert!(matches!(err, O2Error::Unsupported(_)), "{err:?}");
+    }
+}
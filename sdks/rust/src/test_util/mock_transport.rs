@@ -0,0 +1,309 @@
+//! An in-memory [`Transport`] serving canned JSON, for unit tests of bot logic
+//! without a server, a port or the network.
+//!
+//! [`MockTransport`] answers the requests of an [`O2Api`] directly, scripted
+//! like [`MockO2Api`](super::mock_api::MockO2Api): each route gets a list of
+//! [`MockResponse`]s, consumed one per request with the last one repeating, and
+//! unscripted routes get a bare 404. [`fixture`](MockTransport::fixture) is
+//! the one-line form for a route that always answers with the same JSON.
+//! Every request is recorded.
+//!
+//! ```rust,ignore
+//! let mock = MockTransport::new();
+//! mock.fixture("GET", "/v1/markets", serde_json::from_str(MARKETS_JSON)?);
+//! mock.fixture_file("GET", "/v1/depth", "tests/fixtures/depth.json");
+//!
+//! let mut client = mock.client();
+//! run_strategy_tick(&mut client).await?;
+//! assert_eq!(mock.requests_to("POST", "/v1/session/actions").len(), 1);
+//! ```
+//!
+//! Without a socket, [`MockResponse::disconnect`] fails the request with a
+//! refused local connection, and [`MockResponse::hang`] never completes: wrap
+//! the call in `tokio::time::timeout`, as client timeouts do not apply.
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use reqwest::{Request, Response};
+use serde_json::Value;
+
+use super::mock_api::{MockKind, MockResponse, MockState, RecordedRequest};
+use crate::api::O2Api;
+use crate::client::O2Client;
+use crate::config::NetworkConfig;
+use crate::transport::Transport;
+
+/// Base URL of [`MockTransport::config`]. Requests to it never leave the
+/// process.
+pub const MOCK_API_BASE: &str = "http://o2.mock.invalid";
+
+/// A scripted [`Transport`]. Clones share scripts and recorded requests, so a
+/// test keeps one while the client owns another.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `method` requests to `path` with `responses`, in order, repeating
+    /// the last. A path ending in `*` matches every path with that prefix; an
+    /// exact route wins over a prefix. A path with a query, such as
+    /// `/v1/balance?asset_id=0x01`, matches requests carrying those
+    /// parameters and wins over the bare path. Replaces any earlier script for
+    /// the route.
+    pub fn script(
+        &self,
+        method: &str,
+        path: &str,
+        responses: impl IntoIterator<Item = MockResponse>,
+    ) {
+        self.lock()
+            .script(method, path, responses.into_iter().collect());
+    }
+
+    /// Answer every `method` request to `path` with `200 OK` and `body`.
+    pub fn fixture(&self, method: &str, path: &str, body: Value) {
+        self.script(method, path, [MockResponse::json(body)]);
+    }
+
+    /// [`fixture`](Self::fixture) with the body read from a JSON file.
+    ///
+    /// # Panics
+    ///
+    /// If the file cannot be read or is not JSON.
+    pub fn fixture_file(&self, method: &str, path: &str, file: impl AsRef<Path>) {
+        let file = file.as_ref();
+        let text = std::fs::read_to_string(file)
+            .unwrap_or_else(|e| panic!("read fixture {}: {e}", file.display()));
+        let body = serde_json::from_str(&text)
+            .unwrap_or_else(|e| panic!("parse fixture {}: {e}", file.display()));
+        self.fixture(method, path, body);
+    }
+
+    /// Delay every response by `latency`, on top of its own delay.
+    pub fn set_latency(&self, latency: std::time::Duration) {
+        self.lock().set_latency(latency);
+    }
+
+    /// Every request received so far, in arrival order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests()
+    }
+
+    /// Requests received for one route, ignoring the query string.
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.lock().requests_to(method, path)
+    }
+
+    /// A network configuration with REST at [`MOCK_API_BASE`]. WebSocket and
+    /// RPC point at a closed port.
+    pub fn config(&self) -> NetworkConfig {
+        NetworkConfig {
+            api_base: MOCK_API_BASE.into(),
+            ws_url: "ws://127.0.0.1:9".into(),
+            fuel_rpc: "http://127.0.0.1:9".into(),
+            faucet_url: None,
+            whitelist_required: false,
+            chain_id: None,
+            api_base_fallbacks: Vec::new(),
+            ws_url_fallbacks: Vec::new(),
+            failover: Default::default(),
+        }
+    }
+
+    /// A REST client answered by this transport. The retry policy is the
+    /// default; set one to suit the test.
+    pub fn api(&self) -> O2Api {
+        O2Api::with_transport(self.config(), self.clone())
+    }
+
+    /// An [`O2Client`] around [`api`](Self::api).
+    pub fn client(&self) -> O2Client {
+        O2Client::with_api(self.api())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+        let recorded = RecordedRequest {
+            method: request.method().as_str().to_string(),
+            path: request.url().path().to_string(),
+            query: request.url().query().map(str::to_string),
+            body: request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+                .unwrap_or_default(),
+        };
+        let (response, delay) = self.lock().answer(recorded);
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            match response.kind {
+                MockKind::Reply {
+                    status,
+                    headers,
+                    body,
+                } => {
+                    let mut builder = http::Response::builder().status(status);
+                    for (name, value) in headers {
+                        builder = builder.header(name, value);
+                    }
+                    if !body.is_empty() {
+                        builder = builder.header("content-type", "application/json");
+                    }
+                    Ok(builder.body(body).expect("valid mock response").into())
+                }
+                // reqwest errors cannot be built directly; nothing listens on
+                // port 9, so this fails as a connection error.
+                MockKind::Disconnect => {
+                    reqwest::Client::new()
+                        .get("http://127.0.0.1:9")
+                        .send()
+                        .await
+                }
+                MockKind::Hang => std::future::pending().await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::errors::{ApiErrorCode, O2Error};
+    use crate::retry::RetryPolicy;
+
+    #[tokio::test]
+    async fn fixtures_answer_the_api_and_requests_are_recorded() {
+        let mock = MockTransport::new();
+        mock.fixture(
+            "GET",
+            "/v1/depth",
+            json!({"orders": {"buys": [{"price": "100", "quantity": "10"}], "sells": []}}),
+        );
+        let api = mock.api();
+        let depth = api.get_depth("0xaa", 1, Some(5)).await.unwrap();
+        assert_eq!(depth.bids[0].price, 100);
+        assert!(depth.asks.is_empty());
+        api.get_depth("0xbb", 1, None).await.unwrap();
+
+        let recorded = mock.requests_to("GET", "/v1/depth");
+        assert_eq!(recorded.len(), 2);
+        let query = recorded[0].query.as_deref().unwrap();
+        assert!(query.contains("market_id=0xaa") && query.contains("limit=5"));
+
+        let err = api.get_market_summary("0xaa").await.unwrap_err();
+        assert!(matches!(err, O2Error::Unsupported(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn scripts_drive_retries_and_errors() {
+        let mock = MockTransport::new();
+        mock.script(
+            "GET",
+            "/v1/*",
+            [
+                MockResponse::disconnect(),
+                MockResponse::rate_limited(Duration::ZERO),
+                MockResponse::status_json(400, json!({"code": 1000, "message": "bad"})),
+            ],
+        );
+        let api = mock.api().with_retry_policy(RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        });
+        let err = api.get_depth("0xaa", 1, None).await.unwrap_err();
+        assert!(!matches!(err, O2Error::Unsupported(_)), "{err:?}");
+        assert_eq!(mock.requests_to("GET", "/v1/depth").len(), 3);
+
+        mock.script("GET", "/v1/depth", [MockResponse::hang()]);
+        let hung =
+            tokio::time::timeout(Duration::from_millis(20), api.get_depth("0xaa", 1, None)).await;
+        assert!(hung.is_err());
+    }
+
+    #[tokio::test]
+    async fn query_routes_win_over_the_bare_path() {
+        let balance = |locked: &str| {
+            json!({
+                "order_books": {},
+                "total_locked": locked,
+                "total_unlocked": "0",
+                "trading_account_balance": "0",
+            })
+        };
+        let mock = MockTransport::new();
+        mock.fixture("GET", "/v1/balance", balance("1"));
+        mock.fixture("GET", "/v1/balance?asset_id=0xbb", balance("2"));
+        let api = mock.api();
+
+        let other = api.get_balance("0xaa", Some("0xacc"), None).await.unwrap();
+        let scripted = api.get_balance("0xbb", Some("0xacc"), None).await.unwrap();
+        assert_eq!(other.total_locked, 1);
+        assert_eq!(scripted.total_locked, 2);
+        assert_eq!(mock.requests_to("GET", "/v1/balance").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn service_unavailable_keeps_api_error_codes() {
+        let mock = MockTransport::new();
+        let api = mock.api().with_retry_policy(RetryPolicy::none());
+
+        mock.script("GET", "/v1/depth", [MockResponse::status(503)]);
+        let err = api.get_depth("0xaa", 1, None).await.unwrap_err();
+        assert!(matches!(err, O2Error::RateLimited { .. }), "{err:?}");
+
+        mock.script(
+            "GET",
+            "/v1/depth",
+            [MockResponse::status_json(
+                503,
+                json!({"code": 2001, "message": "paused"}),
+            )],
+        );
+        let err = api.get_depth("0xaa", 1, None).await.unwrap_err();
+        assert!(matches!(err, O2Error::MarketPaused(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn rate_limits_keep_api_error_codes_and_retry_after() {
+        let mock = MockTransport::new();
+        let api = mock.api().with_retry_policy(RetryPolicy::none());
+
+        mock.script(
+            "GET",
+            "/v1/depth",
+            [
+                MockResponse::status_json(429, json!({"code": 1003, "message": "slow down"}))
+                    .with_header("retry-after", "2"),
+            ],
+        );
+        let err = api.get_depth("0xaa", 1, None).await.unwrap_err();
+        assert!(matches!(err, O2Error::RateLimited { .. }), "{err:?}");
+        assert_eq!(err.api_code(), Some(ApiErrorCode::RateLimitExceeded));
+        assert_eq!(err.error_code(), Some(1003));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(2)));
+
+        mock.script("GET", "/v1/depth", [MockResponse::status(429)]);
+        let err = api.get_depth("0xaa", 1, None).await.unwrap_err();
+        assert!(
+            matches!(err, O2Error::RateLimited { code: None, .. }),
+            "{err:?}"
+        );
+        assert_eq!(err.api_code(), None);
+    }
+}
@@ -0,0 +1,430 @@
+//! Wallet, account and funding setup for integration tests against testnet.
+//!
+//! Testnet accounts are slow to create and the faucet is rate limited, so the
+//! helpers here reuse as much as possible between runs:
+//!
+//! - maker/taker wallets are persisted to a local file (keep it out of version
+//!   control) by [`load_or_create_integration_wallets`]
+//! - accounts are looked up before being created, by [`ensure_account_with_retry`]
+//! - the faucet is only used when a balance is below a threshold, by [`ensure_funded`]
+//!
+//! [`setup_maker_taker`] does all of it for a maker/taker pair. Call it once per
+//! test binary, e.g. behind a `tokio::sync::OnceCell`, and run the tests with
+//! `--test-threads=1` so they do not race on nonces.
+//!
+//! These are test helpers: failures that leave a test without a usable account
+//! panic with a description rather than returning an error.
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::O2Api;
+use crate::client::O2Client;
+use crate::config::Network;
+use crate::crypto::{self, Wallet};
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    AccountResponse, Market, MarketSymbol, OrderType, Session, SessionActionsResponse, Side,
+    TradeAccountId,
+};
+use crate::receipts::O2RevertReason;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PersistedIntegrationWallets {
+    maker_private_key: String,
+    taker_private_key: String,
+}
+
+/// A funded maker/taker pair with their trade accounts.
+pub struct TestAccounts {
+    pub maker_wallet: Wallet,
+    pub maker_trade_account_id: TradeAccountId,
+    pub taker_wallet: Wallet,
+    pub taker_trade_account_id: TradeAccountId,
+}
+
+fn is_private_key_hex(value: &str) -> bool {
+    value.starts_with("0x")
+        && value.len() == 66
+        && value
+            .as_bytes()
+            .iter()
+            .skip(2)
+            .all(|b| b.is_ascii_hexdigit())
+}
+
+/// Load the maker and taker wallets from `path`, or generate them and write
+/// them there if the file is missing or invalid.
+///
+/// The file holds private keys in plain text.
+pub fn load_or_create_integration_wallets(path: impl AsRef<Path>) -> (Wallet, Wallet) {
+    let path = path.as_ref();
+    if let Ok(raw) = fs::read_to_string(path) {
+        if let Ok(persisted) = serde_json::from_str::<PersistedIntegrationWallets>(&raw) {
+            if is_private_key_hex(&persisted.maker_private_key)
+                && is_private_key_hex(&persisted.taker_private_key)
+            {
+                let client = O2Client::new(Network::Testnet);
+                if let (Ok(maker), Ok(taker)) = (
+                    client.load_wallet(&persisted.maker_private_key),
+                    client.load_wallet(&persisted.taker_private_key),
+                ) {
+                    return (maker, taker);
+                }
+            }
+        }
+        eprintln!(
+            "[integration] wallet cache at {} invalid, regenerating",
+            path.display()
+        );
+    }
+
+    let client = O2Client::new(Network::Testnet);
+    let maker = client.generate_wallet().expect("generate maker wallet");
+    let taker = client.generate_wallet().expect("generate taker wallet");
+
+    let persisted = PersistedIntegrationWallets {
+        maker_private_key: crypto::to_hex_string(maker.private_key.expose_secret()),
+        taker_private_key: crypto::to_hex_string(taker.private_key.expose_secret()),
+    };
+
+    if let Ok(serialized) = serde_json::to_string_pretty(&persisted) {
+        let _ = fs::write(path, format!("{serialized}\n"));
+        eprintln!("[integration] wrote wallet cache to {}", path.display());
+    }
+
+    (maker, taker)
+}
+
+fn is_cooldown_message(message: &str) -> bool {
+    let lower = message.to_ascii_lowercase();
+    lower.contains("cooldown") || lower.contains("rate limit") || lower.contains("too many")
+}
+
+/// Mint from the faucet to a trade account, waiting out faucet cooldowns.
+///
+/// Gives up silently after `max_retries` attempts; check the balance afterwards.
+pub async fn mint_with_retry(api: &O2Api, trade_account_id: &str, max_retries: usize) {
+    let mut wait_secs = 5u64;
+    for attempt in 0..max_retries {
+        match api.mint_to_contract(trade_account_id).await {
+            Ok(resp) if resp.error.is_none() => return,
+            Ok(resp) => {
+                let err = resp
+                    .error
+                    .unwrap_or_else(|| "unknown faucet error".to_string());
+                if attempt < max_retries - 1 {
+                    wait_secs = if is_cooldown_message(&err) {
+                        65
+                    } else {
+                        wait_secs.min(20)
+                    };
+                    tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+                    wait_secs = (wait_secs * 2).min(20);
+                }
+            }
+            Err(e) => {
+                if attempt < max_retries - 1 {
+                    let delay = if is_cooldown_message(&e.to_string()) {
+                        65
+                    } else {
+                        wait_secs
+                    };
+                    tokio::time::sleep(Duration::from_secs(delay)).await;
+                    wait_secs = (wait_secs * 2).min(20);
+                }
+            }
+        }
+    }
+}
+
+/// Mint until the trade account holds at least `min_balance` of
+/// `asset_symbol` (in chain units), up to five mints.
+///
+/// Returns the final balance, which is below `min_balance` if the faucet did
+/// not deliver.
+pub async fn ensure_funded(
+    client: &mut O2Client,
+    trade_account_id: &TradeAccountId,
+    asset_symbol: &str,
+    min_balance: u128,
+) -> Result<u128, O2Error> {
+    const MAX_MINTS: usize = 5;
+    let mut mint_count = 0;
+
+    loop {
+        let balances = client.get_balances(trade_account_id).await?;
+        let balance = balances
+            .get(asset_symbol)
+            .map(|b| b.trading_account_balance)
+            .unwrap_or(0);
+
+        if balance >= min_balance {
+            if mint_count > 0 {
+                eprintln!(
+                    "Balance for {} is now {} (needed {})",
+                    asset_symbol, balance, min_balance
+                );
+            }
+            return Ok(balance);
+        }
+
+        if mint_count >= MAX_MINTS {
+            eprintln!(
+                "Warning: Balance for {} is {} after {} mints (need {})",
+                asset_symbol, balance, mint_count, min_balance
+            );
+            return Ok(balance);
+        }
+
+        eprintln!(
+            "Balance for {} is {} (need {}), minting... (attempt {}/{})",
+            asset_symbol,
+            balance,
+            min_balance,
+            mint_count + 1,
+            MAX_MINTS
+        );
+
+        mint_with_retry(&client.api, trade_account_id.as_str(), 3).await;
+        mint_count += 1;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
+/// Whitelist a trade account, retrying a failed request.
+pub async fn whitelist_with_retry(api: &O2Api, trade_account_id: &str, max_retries: usize) {
+    for attempt in 0..max_retries {
+        if api.whitelist_account(trade_account_id).await.is_ok() {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            return;
+        }
+        if attempt < max_retries - 1 {
+            tokio::time::sleep(Duration::from_secs(15)).await;
+        }
+    }
+}
+
+/// Whether `err` is the testnet rate limiting or a faucet cooldown.
+pub fn is_rate_limited_error(err: &O2Error) -> bool {
+    match err {
+        O2Error::RateLimitExceeded(_) => true,
+        O2Error::OnChainRevert {
+            message, reason, ..
+        } => is_cooldown_message(&format!("{message} {reason}")),
+        other => is_cooldown_message(&other.to_string()),
+    }
+}
+
+/// [`O2Client::setup_account`], retried with backoff while rate limited.
+///
+/// # Panics
+///
+/// On any other error, or when the retries run out.
+pub async fn setup_account_with_retry(
+    client: &mut O2Client,
+    wallet: &Wallet,
+    max_retries: usize,
+) -> AccountResponse {
+    let mut wait_secs = 5u64;
+    for attempt in 0..max_retries {
+        match client.setup_account(wallet).await {
+            Ok(account) => return account,
+            Err(e) => {
+                let retryable = is_rate_limited_error(&e);
+                if retryable && attempt < max_retries - 1 {
+                    eprintln!(
+                        "[integration] setup_account attempt {}/{} rate-limited: {} (retrying in {}s)",
+                        attempt + 1,
+                        max_retries,
+                        e,
+                        wait_secs
+                    );
+                    tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+                    wait_secs = (wait_secs * 2).min(65);
+                    continue;
+                }
+                panic!(
+                    "setup_account failed after {}/{} attempts: {}",
+                    attempt + 1,
+                    max_retries,
+                    e
+                );
+            }
+        }
+    }
+    panic!("setup_account failed: no attempts made");
+}
+
+/// Find or create the wallet's trade account and whitelist it, without
+/// minting. Cheaper than [`setup_account_with_retry`] when funding is handled
+/// by [`ensure_funded`].
+///
+/// # Panics
+///
+/// When the retries run out.
+pub async fn ensure_account_with_retry(
+    client: &mut O2Client,
+    wallet: &Wallet,
+    max_retries: usize,
+) -> TradeAccountId {
+    let owner_hex = crypto::to_hex_string(&wallet.b256_address);
+
+    for attempt in 0..max_retries {
+        let result = async {
+            let existing = client.api.get_account_by_owner(&owner_hex).await?;
+            let trade_account_id = if let Some(id) = existing.trade_account_id {
+                id
+            } else {
+                client
+                    .api
+                    .create_account(&owner_hex)
+                    .await?
+                    .trade_account_id
+            };
+            whitelist_with_retry(&client.api, trade_account_id.as_str(), 2).await;
+            Ok::<TradeAccountId, O2Error>(trade_account_id)
+        }
+        .await;
+
+        match result {
+            Ok(id) => return id,
+            Err(e) => {
+                eprintln!(
+                    "[integration] ensure_account attempt {}/{} failed for {}...: {}",
+                    attempt + 1,
+                    max_retries,
+                    &owner_hex[..12.min(owner_hex.len())],
+                    e
+                );
+                if attempt < max_retries - 1 {
+                    tokio::time::sleep(Duration::from_secs(15)).await;
+                }
+            }
+        }
+    }
+
+    panic!("ensure_account failed after retries")
+}
+
+/// Place an order, re-whitelisting and retrying on `TraderNotWhiteListed`.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_order_with_whitelist_retry(
+    client: &mut O2Client,
+    session: &mut Session,
+    trade_account_id: &TradeAccountId,
+    market_pair: &MarketSymbol,
+    market: &Market,
+    side: Side,
+    price: UnsignedDecimal,
+    quantity: UnsignedDecimal,
+    order_type: OrderType,
+    settle_first: bool,
+    collect_orders: bool,
+    max_retries: usize,
+) -> Result<SessionActionsResponse, O2Error> {
+    let typed_price = market.price_from_decimal(price)?;
+    let typed_quantity = market.quantity_from_decimal(quantity)?;
+    let mut last_error = None;
+    for attempt in 0..max_retries {
+        match client
+            .create_order(
+                session,
+                market_pair,
+                side,
+                typed_price.clone(),
+                typed_quantity.clone(),
+                order_type.clone(),
+                settle_first,
+                collect_orders,
+            )
+            .await
+        {
+            Ok(resp) => return Ok(resp),
+            Err(e) => {
+                let is_whitelist_err = match e.revert_reason() {
+                    Some(reason) => reason == O2RevertReason::TraderNotWhiteListed,
+                    None => format!("{e}").contains("TraderNotWhiteListed"),
+                };
+                if is_whitelist_err && attempt < max_retries - 1 {
+                    whitelist_with_retry(&client.api, trade_account_id.as_str(), 2).await;
+                    tokio::time::sleep(Duration::from_secs(5 * (attempt as u64 + 1))).await;
+                    last_error = Some(e);
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| O2Error::Other("create_order: no attempts made".into())))
+}
+
+/// Cancel the wallet's open orders on a market and settle its balance,
+/// ignoring failures. Uses a fresh session.
+pub async fn cleanup_open_orders(
+    client: &mut O2Client,
+    wallet: &Wallet,
+    market_pair: &MarketSymbol,
+) {
+    if let Ok(mut session) = client
+        .create_session(wallet, &[market_pair], Duration::from_secs(30 * 24 * 3600))
+        .await
+    {
+        let _ = client.cancel_all_orders(&mut session, market_pair).await;
+        let _ = client.settle_balance(&mut session, market_pair).await;
+    }
+}
+
+/// Load or create the maker/taker wallets at `wallets_file`, make sure both
+/// have whitelisted trade accounts with no open orders on the first market,
+/// and fund each with at least `min_balance` of its base and quote assets.
+///
+/// # Panics
+///
+/// When the markets cannot be fetched or an account cannot be set up.
+pub async fn setup_maker_taker(wallets_file: impl AsRef<Path>, min_balance: u128) -> TestAccounts {
+    let (maker_wallet, taker_wallet) = load_or_create_integration_wallets(wallets_file);
+
+    let maker_fut = async {
+        let mut c = O2Client::new(Network::Testnet);
+        ensure_account_with_retry(&mut c, &maker_wallet, 4).await
+    };
+    let taker_fut = async {
+        let mut c = O2Client::new(Network::Testnet);
+        ensure_account_with_retry(&mut c, &taker_wallet, 4).await
+    };
+    let (maker_trade_account_id, taker_trade_account_id) = tokio::join!(maker_fut, taker_fut);
+
+    let mut client = O2Client::new(Network::Testnet);
+    let markets = client.get_markets().await.expect("fetch testnet markets");
+    let market = markets.first().expect("testnet lists at least one market");
+    let market_pair = market.symbol_pair();
+    let base_symbol = market.base.symbol.clone();
+    let quote_symbol = market.quote.symbol.clone();
+
+    cleanup_open_orders(&mut client, &maker_wallet, &market_pair).await;
+    cleanup_open_orders(&mut client, &taker_wallet, &market_pair).await;
+
+    let fund = |account: TradeAccountId, symbol: String| async move {
+        let mut c = O2Client::new(Network::Testnet);
+        if let Err(e) = ensure_funded(&mut c, &account, &symbol, min_balance).await {
+            eprintln!("[integration] funding {symbol} for {account} failed: {e}");
+        }
+    };
+    tokio::join!(
+        fund(maker_trade_account_id.clone(), quote_symbol.clone()),
+        fund(maker_trade_account_id.clone(), base_symbol.clone()),
+        fund(taker_trade_account_id.clone(), quote_symbol),
+        fund(taker_trade_account_id.clone(), base_symbol),
+    );
+
+    TestAccounts {
+        maker_wallet,
+        maker_trade_account_id,
+        taker_wallet,
+        taker_trade_account_id,
+    }
+}
@@ -0,0 +1,570 @@
+//! An in-memory exchange for unit tests. Requires the `test-util` feature.
+//!
+//! [`MockExchange`] implements [`MarketDataProvider`] and [`OrderGateway`]
+//! from data the test sets up, with no network. Submitted batches are
+//! checked the way the exchange would check them, recorded, and applied to
+//! an in-memory list of orders; nothing fills unless the test calls
+//! [`MockExchange::fill`].
+//!
+//! ```rust,ignore
+//! let mut exchange = MockExchange::new();
+//! exchange.add_market(market.clone());
+//! exchange.set_depth(&symbol, depth);
+//!
+//! join_best_bid(&mut exchange, &mut session, &symbol, "10".parse()?).await?;
+//!
+//! assert_eq!(exchange.submitted().len(), 1);
+//! assert_eq!(exchange.open_orders(&symbol).len(), 1);
+//! ```
+use std::collections::{HashMap, VecDeque};
+
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::gateway::{MarketDataProvider, OrderGateway};
+use crate::models::{
+    Action, BalanceResponse, Bar, DepthSnapshot, Identity, Market, MarketSymbol, Order, OrderId,
+    OrderType, OrdersResponse, Session, SessionActionsResponse, Side, Trade, TradeAccountId,
+    TradeId, TradesResponse, TxId, MAX_ACTIONS_PER_BATCH,
+};
+use crate::preflight::check_order;
+use crate::timestamp::Timestamp;
+
+/// One batch accepted by a [`MockExchange`].
+#[derive(Debug, Clone)]
+pub struct SubmittedBatch {
+    pub market: MarketSymbol,
+    pub trade_account_id: TradeAccountId,
+    /// The session nonce the batch was signed with.
+    pub nonce: u64,
+    pub actions: Vec<Action>,
+}
+
+/// An exchange held in memory. See the [module docs](self).
+#[derive(Debug, Default)]
+pub struct MockExchange {
+    markets: Vec<Market>,
+    depth: HashMap<MarketSymbol, DepthSnapshot>,
+    trades: HashMap<MarketSymbol, Vec<Trade>>,
+    bars: HashMap<MarketSymbol, Vec<Bar>>,
+    balances: HashMap<String, BalanceResponse>,
+    /// Every order placed, oldest first.
+    orders: Vec<Order>,
+    submitted: Vec<SubmittedBatch>,
+    failures: VecDeque<O2Error>,
+    next_order_id: u64,
+}
+
+impl MockExchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_market(&mut self, market: Market) {
+        self.markets.push(market);
+    }
+
+    /// Served by `get_depth` for `market`, whatever the precision and limit.
+    pub fn set_depth(&mut self, market: &MarketSymbol, depth: DepthSnapshot) {
+        self.depth.insert(market.clone(), depth);
+    }
+
+    /// Served by `get_trades` for `market`, in this order, up to `count`.
+    pub fn set_trades(&mut self, market: &MarketSymbol, trades: Vec<Trade>) {
+        self.trades.insert(market.clone(), trades);
+    }
+
+    /// Served by `get_bars` for `market`, filtered to the requested range.
+    pub fn set_bars(&mut self, market: &MarketSymbol, bars: Vec<Bar>) {
+        self.bars.insert(market.clone(), bars);
+    }
+
+    /// Served by `get_balances` for every account.
+    pub fn set_balance(&mut self, symbol: &str, balance: BalanceResponse) {
+        self.balances.insert(symbol.to_string(), balance);
+    }
+
+    /// Fail the next submission (`create_order`, `cancel_order`,
+    /// `cancel_all_orders` or `batch_actions`) with `error`. Failures queue
+    /// up in the order given.
+    pub fn fail_next(&mut self, error: O2Error) {
+        self.failures.push_back(error);
+    }
+
+    /// Every accepted batch, oldest first.
+    pub fn submitted(&self) -> &[SubmittedBatch] {
+        &self.submitted
+    }
+
+    /// Every order placed on `market`, oldest first.
+    pub fn orders(&self, market: &MarketSymbol) -> Vec<Order> {
+        self.orders_on(market).cloned().collect()
+    }
+
+    /// Orders on `market` that are neither filled nor cancelled.
+    pub fn open_orders(&self, market: &MarketSymbol) -> Vec<Order> {
+        self.orders_on(market)
+            .filter(|o| !o.close)
+            .cloned()
+            .collect()
+    }
+
+    /// Fill `quantity` more of an open order, closing it once fully filled.
+    pub fn fill(&mut self, order_id: &OrderId, quantity: u64) -> Result<(), O2Error> {
+        let order = self
+            .orders
+            .iter_mut()
+            .find(|o| &o.order_id == order_id && !o.close)
+            .ok_or_else(|| O2Error::OrderNotFound(format!("No open order {order_id}")))?;
+        let filled = (order.quantity_fill.unwrap_or(0) + quantity).min(order.quantity);
+        order.quantity_fill = Some(filled);
+        order.price_fill = Some(order.price);
+        order.partially_filled = filled < order.quantity;
+        order.close = filled == order.quantity;
+        Ok(())
+    }
+
+    fn orders_on<'a>(
+        &'a self,
+        market: &MarketSymbol,
+    ) -> impl DoubleEndedIterator<Item = &'a Order> + 'a {
+        let market_id = self.market(market).ok().map(|m| m.market_id.clone());
+        self.orders
+            .iter()
+            .filter(move |o| market_id.is_some() && o.market_id == market_id)
+    }
+
+    fn market(&self, market: &MarketSymbol) -> Result<&Market, O2Error> {
+        self.markets
+            .iter()
+            .find(|m| m.symbol_pair() == *market)
+            .ok_or_else(|| O2Error::MarketNotFound(format!("No mock market {market}")))
+    }
+
+    /// Check and apply one batch atomically, as the exchange would.
+    fn execute(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        if let Some(error) = self.failures.pop_front() {
+            return Err(error);
+        }
+        if actions.is_empty() {
+            return Err(O2Error::NoActionsProvided("Empty batch".into()));
+        }
+        if actions.len() > MAX_ACTIONS_PER_BATCH {
+            return Err(O2Error::TooManyActions(format!(
+                "{} actions, at most {MAX_ACTIONS_PER_BATCH} per batch",
+                actions.len()
+            )));
+        }
+        let info = self.market(market)?.clone();
+        let account = Identity::ContractId(session.trade_account_id.to_string());
+
+        let mut orders = self.orders.clone();
+        let mut created = Vec::new();
+        let mut next_order_id = self.next_order_id;
+        for action in &actions {
+            match action {
+                Action::CreateOrder {
+                    side,
+                    price,
+                    quantity,
+                    order_type,
+                } => {
+                    let valid = check_order(&info, *side, *price, *quantity, order_type.clone())?;
+                    next_order_id += 1;
+                    let mut order = Order::local(
+                        OrderId::new(format!("0x{next_order_id:064x}")),
+                        &info,
+                        *side,
+                        order_type,
+                        valid.chain_price,
+                        valid.chain_quantity,
+                        Timestamp::now(),
+                    )?;
+                    order.account = Some(account.clone());
+                    created.push(order.clone());
+                    orders.push(order);
+                }
+                Action::CancelOrder { order_id } => {
+                    let order = orders
+                        .iter_mut()
+                        .find(|o| {
+                            &o.order_id == order_id && o.market_id.as_ref() == Some(&info.market_id)
+                        })
+                        .ok_or_else(|| {
+                            O2Error::OrderNotFound(format!("No order {order_id} on {market}"))
+                        })?;
+                    if order.close {
+                        return Err(O2Error::OrderNotActive(format!(
+                            "Order {order_id} is already closed"
+                        )));
+                    }
+                    order.close = true;
+                    order.cancel = true;
+                }
+                Action::SettleBalance | Action::RegisterReferer { .. } => {}
+            }
+        }
+
+        self.orders = orders;
+        self.next_order_id = next_order_id;
+        self.submitted.push(SubmittedBatch {
+            market: market.clone(),
+            trade_account_id: session.trade_account_id.clone(),
+            nonce: session.nonce,
+            actions,
+        });
+        session.nonce += 1;
+        Ok(SessionActionsResponse {
+            tx_id: Some(TxId::new(format!("0x{:064x}", self.submitted.len()))),
+            orders: collect_orders.then_some(created),
+            code: None,
+            message: None,
+            reason: None,
+            receipts: None,
+            annotations: None,
+            dry_run: None,
+        })
+    }
+}
+
+impl MarketDataProvider for MockExchange {
+    async fn get_markets(&mut self) -> Result<Vec<Market>, O2Error> {
+        Ok(self.markets.clone())
+    }
+
+    async fn get_market(&mut self, market: &MarketSymbol) -> Result<Market, O2Error> {
+        self.market(market).cloned()
+    }
+
+    async fn get_depth(
+        &mut self,
+        market: &MarketSymbol,
+        _precision: u64,
+        limit: Option<usize>,
+    ) -> Result<DepthSnapshot, O2Error> {
+        self.market(market)?;
+        let mut depth = self.depth.get(market).cloned().unwrap_or(DepthSnapshot {
+            bids: Vec::new(),
+            asks: Vec::new(),
+        });
+        if let Some(limit) = limit {
+            depth.bids.truncate(limit);
+            depth.asks.truncate(limit);
+        }
+        Ok(depth)
+    }
+
+    async fn get_trades(
+        &mut self,
+        market: &MarketSymbol,
+        count: u32,
+        _start_timestamp: Option<u64>,
+        _start_trade_id: Option<&TradeId>,
+    ) -> Result<TradesResponse, O2Error> {
+        let market_id = self.market(market)?.market_id.clone();
+        let trades = self.trades.get(market).map_or(&[][..], Vec::as_slice);
+        Ok(TradesResponse {
+            trades: trades.iter().take(count as usize).cloned().collect(),
+            market_id,
+        })
+    }
+
+    async fn get_bars(
+        &mut self,
+        market: &MarketSymbol,
+        _resolution: &str,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> Result<Vec<Bar>, O2Error> {
+        self.market(market)?;
+        let range = Timestamp::from_unix_millis(from_ts)..=Timestamp::from_unix_millis(to_ts);
+        Ok(self
+            .bars
+            .get(market)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .filter(|bar| range.contains(&bar.timestamp))
+            .cloned()
+            .collect())
+    }
+}
+
+impl OrderGateway for MockExchange {
+    async fn create_order(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        side: Side,
+        price: UnsignedDecimal,
+        quantity: UnsignedDecimal,
+        order_type: OrderType,
+        settle_first: bool,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        let mut actions = Vec::new();
+        if settle_first {
+            actions.push(Action::SettleBalance);
+        }
+        actions.push(Action::CreateOrder {
+            side,
+            price,
+            quantity,
+            order_type,
+        });
+        self.execute(session, market, actions, collect_orders)
+    }
+
+    async fn cancel_order(
+        &mut self,
+        session: &mut Session,
+        order_id: &OrderId,
+        market: &MarketSymbol,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        let actions = vec![Action::CancelOrder {
+            order_id: order_id.clone(),
+        }];
+        self.execute(session, market, actions, false)
+    }
+
+    async fn cancel_all_orders(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+    ) -> Result<Vec<SessionActionsResponse>, O2Error> {
+        let account = Identity::ContractId(session.trade_account_id.to_string());
+        let ids: Vec<OrderId> = self
+            .open_orders(market)
+            .into_iter()
+            .filter(|o| o.account.as_ref() == Some(&account))
+            .map(|o| o.order_id)
+            .collect();
+        let mut results = Vec::new();
+        for chunk in ids.chunks(MAX_ACTIONS_PER_BATCH) {
+            let actions = chunk
+                .iter()
+                .map(|order_id| Action::CancelOrder {
+                    order_id: order_id.clone(),
+                })
+                .collect();
+            results.push(self.execute(session, market, actions, false)?);
+        }
+        Ok(results)
+    }
+
+    async fn batch_actions(
+        &mut self,
+        session: &mut Session,
+        market: &MarketSymbol,
+        actions: Vec<Action>,
+        collect_orders: bool,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        self.execute(session, market, actions, collect_orders)
+    }
+
+    /// Newest first, as the API returns them. The cursor is ignored.
+    async fn get_orders(
+        &mut self,
+        market: &MarketSymbol,
+        trade_account_id: &TradeAccountId,
+        is_open: Option<bool>,
+        count: u32,
+        _start_timestamp: Option<u64>,
+        _start_order_id: Option<&OrderId>,
+    ) -> Result<OrdersResponse, O2Error> {
+        let market_id = self.market(market)?.market_id.clone();
+        let identity = Identity::ContractId(trade_account_id.to_string());
+        let orders = self
+            .orders_on(market)
+            .rev()
+            .filter(|o| o.account.as_ref() == Some(&identity))
+            .filter(|o| is_open != Some(o.close))
+            .take(count as usize)
+            .cloned()
+            .collect();
+        Ok(OrdersResponse {
+            identity,
+            market_id,
+            orders,
+        })
+    }
+
+    async fn get_balances(
+        &mut self,
+        _trade_account_id: &TradeAccountId,
+    ) -> Result<HashMap<String, BalanceResponse>, O2Error> {
+        Ok(self.balances.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, DepthLevel, MarketAsset, MarketId};
+
+    fn market() -> Market {
+        Market {
+            contract_id: ContractId::new("0x01"),
+            market_id: MarketId::new("0x02"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 1_000_000,
+            dust: 0,
+            price_window: 0,
+            base: MarketAsset {
+                symbol: "fFUEL".into(),
+                asset: AssetId::new("0xaa"),
+                decimals: 9,
+                max_precision: 3,
+            },
+            quote: MarketAsset {
+                symbol: "fUSDC".into(),
+                asset: AssetId::new("0xbb"),
+                decimals: 6,
+                max_precision: 4,
+            },
+        }
+    }
+
+    fn session() -> Session {
+        Session {
+            owner_address: [0u8; 32],
+            session_private_key: [1u8; 32].into(),
+            session_address: [2u8; 32],
+            trade_account_id: TradeAccountId::new("0xacc"),
+            contract_ids: vec![],
+            inactive_contract_ids: vec![],
+            expiry: u64::MAX,
+            nonce: 4,
+        }
+    }
+
+    /// Code under test, written against the traits.
+    async fn join_best_bid(
+        gateway: &mut (impl MarketDataProvider + OrderGateway),
+        session: &mut Session,
+        market: &MarketSymbol,
+    ) -> Result<SessionActionsResponse, O2Error> {
+        let info = gateway.get_market(market).await?;
+        let depth = gateway.get_depth(market, 1, Some(1)).await?;
+        let price = info.format_price(depth.bids[0].price)?;
+        gateway
+            .create_order(
+                session,
+                market,
+                Side::Buy,
+                price,
+                "10".parse()?,
+                OrderType::PostOnly,
+                true,
+                true,
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn orders_are_recorded_and_cancelled() {
+        let symbol = market().symbol_pair();
+        let mut exchange = MockExchange::new();
+        exchange.add_market(market());
+        exchange.set_depth(
+            &symbol,
+            DepthSnapshot {
+                bids: vec![DepthLevel {
+                    price: 240_000,
+                    quantity: 1,
+                }],
+                asks: vec![],
+            },
+        );
+        let mut session = session();
+
+        let resp = join_best_bid(&mut exchange, &mut session, &symbol)
+            .await
+            .unwrap();
+        assert!(resp.is_success());
+        let placed = &resp.orders.unwrap()[0];
+        assert_eq!((placed.price, placed.quantity), (240_000, 10_000_000_000));
+        assert_eq!(session.nonce, 5);
+        let batch = &exchange.submitted()[0];
+        assert_eq!(batch.nonce, 4);
+        assert!(matches!(batch.actions[0], Action::SettleBalance));
+
+        let open = exchange
+            .get_orders(
+                &symbol,
+                &session.trade_account_id,
+                Some(true),
+                10,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(open.orders.len(), 1);
+
+        exchange.fail_next(O2Error::RateLimitExceeded("slow down".into()));
+        let err = exchange
+            .cancel_all_orders(&mut session, &symbol)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, O2Error::RateLimitExceeded(_)));
+        assert_eq!(exchange.open_orders(&symbol).len(), 1);
+
+        exchange
+            .cancel_all_orders(&mut session, &symbol)
+            .await
+            .unwrap();
+        assert!(exchange.open_orders(&symbol).is_empty());
+        assert!(exchange.orders(&symbol)[0].cancel);
+        let err = exchange
+            .cancel_order(&mut session, &placed.order_id, &symbol)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, O2Error::OrderNotActive(_)));
+    }
+
+    #[tokio::test]
+    async fn rejected_batches_change_nothing() {
+        let symbol = market().symbol_pair();
+        let mut exchange = MockExchange::new();
+        exchange.add_market(market());
+        let mut session = session();
+
+        // The second order is below the minimum, so neither is placed.
+        let actions = vec![
+            Action::CreateOrder {
+                side: Side::Sell,
+                price: "0.3".parse().unwrap(),
+                quantity: "10".parse().unwrap(),
+                order_type: OrderType::Spot,
+            },
+            Action::CreateOrder {
+                side: Side::Sell,
+                price: "0.3".parse().unwrap(),
+                quantity: "1".parse().unwrap(),
+                order_type: OrderType::Spot,
+            },
+        ];
+        let err = exchange
+            .batch_actions(&mut session, &symbol, actions, true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, O2Error::InvalidOrderParams(_)), "{err}");
+        assert!(exchange.orders(&symbol).is_empty());
+        assert!(exchange.submitted().is_empty());
+        assert_eq!(session.nonce, 4);
+
+        let unknown = MarketSymbol::new("fETH/fUSDC");
+        assert!(matches!(
+            exchange.get_market(&unknown).await,
+            Err(O2Error::MarketNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+//! Typed timestamps.
+//!
+//! The O2 API reports times in several units: trades and bars in Unix
+//! milliseconds, sessions and limit orders in Unix seconds, and WebSocket
+//! `onchain_timestamp` / `seen_timestamp` as bare strings. [`Timestamp`] is a
+//! single point-in-time type for all of them, with explicit constructors per unit
+//! and conversions to [`SystemTime`] (and `chrono::DateTime<Utc>` with the
+//! `chrono` feature).
+//!
+//! Fields with a documented unit deserialize through that unit. Fields without
+//! one (the WebSocket timestamps) infer it from magnitude; see
+//! [`Timestamp::from_unix_auto`]. All timestamps serialize as Unix milliseconds.
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Offset of Unix time zero in TAI64 labels, as used by Fuel block timestamps.
+const TAI64_UNIX_EPOCH: u128 = (1 << 62) + 10;
+
+/// A point in time, stored as a duration since the Unix epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(Duration);
+
+impl Timestamp {
+    /// The Unix epoch.
+    pub const UNIX_EPOCH: Timestamp = Timestamp(Duration::ZERO);
+
+    /// The current time.
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// From seconds since the Unix epoch.
+    pub fn from_unix_secs(secs: u64) -> Self {
+        Self(Duration::from_secs(secs))
+    }
+
+    /// From milliseconds since the Unix epoch.
+    pub fn from_unix_millis(millis: u64) -> Self {
+        Self(Duration::from_millis(millis))
+    }
+
+    /// From microseconds since the Unix epoch.
+    pub fn from_unix_micros(micros: u64) -> Self {
+        Self(Duration::from_micros(micros))
+    }
+
+    /// From nanoseconds since the Unix epoch.
+    pub fn from_unix_nanos(nanos: u64) -> Self {
+        Self(Duration::from_nanos(nanos))
+    }
+
+    /// Interpret a raw integer whose unit is not known.
+    ///
+    /// The unit is inferred from magnitude, which is unambiguous for any date
+    /// between 1973 and 2116: below 10^11 is seconds, below 10^14 milliseconds,
+    /// below 10^17 microseconds, and below 2^62 nanoseconds. Values at or above
+    /// 2^62 are TAI64 labels (seconds).
+    pub fn from_unix_auto(value: u128) -> Self {
+        if value >= 1 << 62 {
+            let secs = value.saturating_sub(TAI64_UNIX_EPOCH);
+            return Self(Duration::from_secs(secs.min(u64::MAX as u128) as u64));
+        }
+        // Below 2^62, so it fits in u64.
+        let v = value as u64;
+        if v < 100_000_000_000 {
+            Self::from_unix_secs(v)
+        } else if v < 100_000_000_000_000 {
+            Self::from_unix_millis(v)
+        } else if v < 100_000_000_000_000_000 {
+            Self::from_unix_micros(v)
+        } else {
+            Self::from_unix_nanos(v)
+        }
+    }
+
+    /// Name of the unit [`from_unix_auto`](Self::from_unix_auto) would infer for `value`.
+    pub(crate) fn inferred_unit(value: u128) -> &'static str {
+        if value >= 1 << 62 {
+            "TAI64 seconds"
+        } else if value < 100_000_000_000 {
+            "seconds"
+        } else if value < 100_000_000_000_000 {
+            "milliseconds"
+        } else if value < 100_000_000_000_000_000 {
+            "microseconds"
+        } else {
+            "nanoseconds"
+        }
+    }
+
+    /// From a [`SystemTime`]. Times before the epoch clamp to the epoch.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        Self(time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO))
+    }
+
+    /// Whole seconds since the Unix epoch.
+    pub fn as_unix_secs(&self) -> u64 {
+        self.0.as_secs()
+    }
+
+    /// Milliseconds since the Unix epoch.
+    pub fn as_unix_millis(&self) -> u128 {
+        self.0.as_millis()
+    }
+
+    /// Nanoseconds since the Unix epoch.
+    pub fn as_unix_nanos(&self) -> u128 {
+        self.0.as_nanos()
+    }
+
+    /// Duration since the Unix epoch.
+    pub fn since_epoch(&self) -> Duration {
+        self.0
+    }
+
+    /// As a [`SystemTime`].
+    pub fn to_system_time(&self) -> SystemTime {
+        UNIX_EPOCH + self.0
+    }
+
+    /// Time elapsed from `earlier` to `self`, or zero if `earlier` is later.
+    pub fn saturating_duration_since(&self, earlier: Timestamp) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// As a UTC `chrono::DateTime`.
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::<chrono::Utc>::from(self.to_system_time())
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        Self::from_system_time(time)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(ts: Timestamp) -> Self {
+        ts.to_system_time()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(ts: Timestamp) -> Self {
+        ts.to_datetime()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    /// Times before the epoch clamp to the epoch.
+    fn from(dt: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::from_system_time(dt.into())
+    }
+}
+
+impl std::ops::Add<Duration> for Timestamp {
+    type Output = Timestamp;
+    fn add(self, rhs: Duration) -> Timestamp {
+        Timestamp(self.0 + rhs)
+    }
+}
+
+impl std::ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+    fn sub(self, rhs: Duration) -> Timestamp {
+        Timestamp(self.0.saturating_sub(rhs))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Formats as Unix milliseconds.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_unix_millis())
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u128(self.as_unix_millis())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    /// Accepts a number or numeric string in any unit; see [`Timestamp::from_unix_auto`].
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        raw_u128(deserializer).map(Timestamp::from_unix_auto)
+    }
+}
+
+fn raw_u128<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    use serde::de;
+
+    struct RawTimestamp;
+    impl<'de> de::Visitor<'de> for RawTimestamp {
+        type Value = u128;
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a non-negative integer timestamp or a string containing one")
+        }
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<u128, E> {
+            Ok(v as u128)
+        }
+        fn visit_u128<E: de::Error>(self, v: u128) -> Result<u128, E> {
+            Ok(v)
+        }
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<u128, E> {
+            u128::try_from(v).map_err(de::Error::custom)
+        }
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<u128, E> {
+            if v.is_finite() && v >= 0.0 {
+                Ok(v as u128)
+            } else {
+                Err(de::Error::custom(format!("invalid timestamp {v}")))
+            }
+        }
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<u128, E> {
+            v.trim().parse().map_err(de::Error::custom)
+        }
+    }
+    deserializer.deserialize_any(RawTimestamp)
+}
+
+/// Serde adapter for fields documented as Unix milliseconds.
+pub(crate) mod unix_millis {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(ts: &Timestamp, serializer: S) -> Result<S::Ok, S::Error> {
+        ts.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        let millis = raw_u128(deserializer)?;
+        let millis = u64::try_from(millis).map_err(serde::de::Error::custom)?;
+        Ok(Timestamp::from_unix_millis(millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_detects_units() {
+        let secs = 1_700_000_000u64;
+        let expected = Timestamp::from_unix_secs(secs);
+        assert_eq!(Timestamp::from_unix_auto(secs as u128), expected);
+        assert_eq!(Timestamp::from_unix_auto(secs as u128 * 1_000), expected);
+        assert_eq!(
+            Timestamp::from_unix_auto(secs as u128 * 1_000_000),
+            expected
+        );
+        assert_eq!(
+            Timestamp::from_unix_auto(secs as u128 * 1_000_000_000),
+            expected
+        );
+        assert_eq!(
+            Timestamp::from_unix_auto(TAI64_UNIX_EPOCH + secs as u128),
+            expected
+        );
+    }
+
+    #[test]
+    fn deserializes_strings_and_numbers() {
+        let a: Timestamp = serde_json::from_str("\"1700000000123\"").unwrap();
+        let b: Timestamp = serde_json::from_str("1700000000123").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.as_unix_millis(), 1_700_000_000_123);
+        assert_eq!(serde_json::to_string(&a).unwrap(), "1700000000123");
+    }
+
+    #[test]
+    fn unix_millis_adapter_does_not_guess() {
+        #[derive(Deserialize)]
+        struct Row {
+            #[serde(with = "unix_millis")]
+            at: Timestamp,
+        }
+        // 5 seconds after the epoch, expressed in ms; auto-detection would read seconds.
+        let row: Row = serde_json::from_str(r#"{"at": 5000}"#).unwrap();
+        assert_eq!(row.at.as_unix_secs(), 5);
+    }
+
+    #[test]
+    fn converts_to_system_time() {
+        let ts = Timestamp::from_unix_millis(1_500);
+        assert_eq!(
+            SystemTime::from(ts),
+            UNIX_EPOCH + Duration::from_millis(1_500)
+        );
+        assert_eq!(Timestamp::from(SystemTime::from(ts)), ts);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn converts_to_chrono() {
+        let ts = Timestamp::from_unix_millis(1_700_000_000_123);
+        let dt = ts.to_datetime();
+        assert_eq!(dt.timestamp_millis(), 1_700_000_000_123);
+        assert_eq!(Timestamp::from(dt), ts);
+    }
+}
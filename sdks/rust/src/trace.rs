@@ -0,0 +1,145 @@
+//! Diagnostics backend: `log` by default, `tracing` with the `tracing` feature.
+//!
+//! The SDK's debug lines are `tracing` events under the feature, and REST
+//! calls, batch submissions and transaction waits open debug-level spans
+//! carrying `market_id`, `trade_account_id`, `nonce` and `tx_id` where known.
+//! Each HTTP request runs in an `http.request` span, which is the current span
+//! while the [`Transport`](crate::transport::Transport) sends it; a transport
+//! can read it to inject trace context headers. Without a `tracing` subscriber
+//! the events still reach `log`.
+use std::fmt::Display;
+use std::future::Future;
+
+use reqwest::{Request, Response};
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, warn};
+
+/// Send `request` with `send` inside an `http.request` span, recording the
+/// response status.
+pub(crate) async fn http<F, Fut>(request: Request, send: F) -> Result<Response, reqwest::Error>
+where
+    F: FnOnce(Request) -> Fut,
+    Fut: Future<Output = Result<Response, reqwest::Error>>,
+{
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+
+        let span = tracing::debug_span!(
+            "http.request",
+            http.request.method = %request.method(),
+            url.path = request.url().path(),
+            http.response.status_code = tracing::field::Empty,
+        );
+        let result = send(request).instrument(span.clone()).await;
+        if let Ok(resp) = &result {
+            span.record("http.response.status_code", resp.status().as_u16());
+        }
+        result
+    }
+    #[cfg(not(feature = "tracing"))]
+    send(request).await
+}
+
+/// Fill in a field declared empty on the current span.
+pub(crate) fn record(field: &'static str, value: impl Display) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record(field, tracing::field::display(value));
+    #[cfg(not(feature = "tracing"))]
+    let _ = (field, value);
+}
+
+#[cfg(all(test, feature = "tracing", feature = "test-util"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::test_util::mock_transport::MockTransport;
+
+    /// A span's name and its fields as `name=value`.
+    type SpanFields = (&'static str, Vec<String>);
+
+    #[derive(Clone, Default)]
+    struct Spans {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<HashMap<u64, SpanFields>>>,
+    }
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut fields = Vec::new();
+            attrs.record(&mut Fields(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(id, (attrs.metadata().name(), fields));
+            Id::from_u64(id)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            if let Some((_, fields)) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+                values.record(&mut Fields(fields));
+            }
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    impl Spans {
+        fn fields(&self, name: &str) -> Vec<String> {
+            let spans = self.spans.lock().unwrap();
+            let (_, fields) = spans.values().find(|(n, _)| *n == name).unwrap();
+            fields.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_run_in_spans_with_their_fields() {
+        let spans = Spans::default();
+        let _guard = tracing::subscriber::set_default(spans.clone());
+        let mock = MockTransport::new();
+        mock.fixture(
+            "GET",
+            "/v1/depth",
+            json!({"orders": {"buys": [], "sells": []}}),
+        );
+        mock.api().get_depth("0xaa", 1, None).await.unwrap();
+
+        assert_eq!(spans.fields("api.get_depth"), ["market_id=0xaa"]);
+        let http = spans.fields("http.request");
+        assert!(
+            http.contains(&"url.path=\"/v1/depth\"".to_string()),
+            "{http:?}"
+        );
+        assert!(
+            http.contains(&"http.response.status_code=200".to_string()),
+            "{http:?}"
+        );
+    }
+}
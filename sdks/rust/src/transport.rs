@@ -0,0 +1,46 @@
+//! The HTTP layer under [`O2Api`](crate::api::O2Api).
+//!
+//! Every REST request the API client makes, retries included, goes out through
+//! a [`Transport`]. The default is the client's own [`reqwest::Client`]; a
+//! custom transport can serve responses without a network, record traffic or
+//! add request signing at the HTTP level:
+//!
+//! ```rust,ignore
+//! let api = O2Api::with_transport(config, MyTransport::new());
+//! let client = O2Client::with_api(api);
+//! ```
+//!
+//! With the `test-util` feature,
+//! [`MockTransport`](crate::test_util::mock_transport::MockTransport) answers
+//! from canned JSON fixtures.
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::{Client, Request, Response};
+
+/// Sends a built HTTP request and returns the response.
+///
+/// Error statuses are returned as responses; the API client turns them into
+/// [`O2Error`](crate::O2Error)s. Return `Err` only when no response arrived,
+/// such as on a connection failure or timeout. Read requests whose body can be
+/// cloned are retried under the client's
+/// [`RetryPolicy`](crate::retry::RetryPolicy), so `send` may be called more
+/// than once per API call.
+///
+/// With the `tracing` feature, `send` runs inside the request's `http.request`
+/// span, so a transport can inject trace context headers from
+/// `tracing::Span::current()` for distributed tracing.
+pub trait Transport: Send + Sync + 'static {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>>;
+}
+
+impl Transport for Client {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<Response, reqwest::Error>> {
+        self.execute(request).boxed()
+    }
+}
+
+impl std::fmt::Debug for dyn Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Transport")
+    }
+}
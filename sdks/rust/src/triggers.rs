@@ -0,0 +1,1229 @@
+//! Stop and trigger orders.
+//!
+//! O2 has no native stop order. A [`TriggerEngine`] emulates them: each
+//! [`Trigger`] waits for the market's trade price to cross its
+//! [`TriggerCondition`] and then submits a pre-configured [`TriggerOrder`] —
+//! a market order, a `BoundedMarket` order around the crossing price, or a
+//! resting limit order. Prices are read from each market's trade stream, with
+//! a REST poll of the ticker's last price as a fallback for missed messages.
+//!
+//! A trigger fires at most once. It moves from [`TriggerStatus::Pending`] to
+//! [`TriggerStatus::Firing`] before its order is sent, and with
+//! [`TriggerConfig::persist_path`] set that state is on disk first: a trigger
+//! that cannot be persisted is failed instead of fired. Whatever happens to
+//! the submission, it is never retried. A rejection or error ends the trigger
+//! [`TriggerStatus::Failed`], and a trigger still `Firing` when a saved book
+//! is loaded again (the process died mid-submission) is left alone; look at
+//! the account's orders to learn whether it landed.
+//!
+//! A [`TriggerCondition::Trailing`] stop follows the price instead of waiting
+//! at a fixed level. Once armed, at its activation price or on the first trade
+//! if it has none, it tracks the best price since (the high for a sell, the
+//! low for a buy) and fires when the price retraces from there by a
+//! [`TrailDistance`], absolute or in percent.
+//!
+//! [`TriggerEngine::subscribe_events`] reports each step as a
+//! [`TriggerEvent`]: armed, trailing, triggered, submitted and filled, or
+//! failed. Fills are read from the account's order stream, with a REST poll of
+//! submitted orders as a fallback.
+//!
+//! ```rust,ignore
+//! let mut book = TriggerBook::new();
+//! book.add(Trigger::new(
+//!     "fuel-stop",
+//!     MarketSymbol::new("fFUEL/fUSDC"),
+//!     Side::Sell,
+//!     "100".parse()?,
+//!     TriggerCondition::AtOrBelow("0.018".parse()?),
+//!     TriggerOrder::BoundedMarket { max_slippage_bps: 50 },
+//! ))?;
+//! let config = TriggerConfig {
+//!     persist_path: Some("triggers.json".into()),
+//!     ..TriggerConfig::default()
+//! };
+//! let engine = client.start_triggers(&session, &nonces, book, config).await?;
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures_util::stream::SelectAll;
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+
+use crate::client::O2Client;
+use crate::decimal::UnsignedDecimal;
+use crate::errors::O2Error;
+use crate::models::{
+    Action, Identity, Market, MarketId, MarketSymbol, Order, OrderId, OrderStatus, OrderType,
+    OrderUpdate, Session, Side, TradeUpdate,
+};
+use crate::nonce::NonceManager;
+use crate::runtime::{self, Task};
+use crate::trace::debug;
+use crate::websocket::TypedStream;
+
+/// Configuration for a [`TriggerEngine`].
+#[derive(Debug, Clone)]
+pub struct TriggerConfig {
+    /// How often the last price of every market with a pending trigger is
+    /// re-read over REST.
+    pub poll_interval: Duration,
+    /// Write the book to this file on every change.
+    pub persist_path: Option<PathBuf>,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            persist_path: None,
+        }
+    }
+}
+
+/// The price move that fires a trigger. Prices are human-readable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerCondition {
+    /// A trade at or above the price, e.g. a buy stop.
+    AtOrAbove(UnsignedDecimal),
+    /// A trade at or below the price, e.g. a sell stop-loss.
+    AtOrBelow(UnsignedDecimal),
+    /// A trade that retraces from the best price since the stop was armed.
+    Trailing(TrailingStop),
+}
+
+impl TriggerCondition {
+    /// True if a trade at `price` fires a trigger whose order is on `side`.
+    /// An unarmed trailing stop never fires.
+    pub fn is_met(&self, side: Side, price: &UnsignedDecimal) -> bool {
+        match self {
+            Self::AtOrAbove(level) => price >= level,
+            Self::AtOrBelow(level) => price <= level,
+            Self::Trailing(trailing) => match (side, trailing.stop_price(side)) {
+                (Side::Sell, Some(stop)) => *price <= stop,
+                (Side::Buy, Some(stop)) => *price >= stop,
+                (_, None) => false,
+            },
+        }
+    }
+}
+
+/// How far the price may retrace from its best level before a trailing stop
+/// fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailDistance {
+    /// In price units.
+    Absolute(UnsignedDecimal),
+    /// In percent of the best level, e.g. `2.5` for 2.5%. Must be below 100.
+    Percent(UnsignedDecimal),
+}
+
+/// A stop that trails the price. A sell stop trails below the high since it
+/// was armed and a buy stop above the low.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrailingStop {
+    pub distance: TrailDistance,
+    /// Arm once a trade reaches this price: at or above it for a sell, at or
+    /// below it for a buy. `None` arms on the first trade seen.
+    #[serde(default)]
+    pub activation: Option<UnsignedDecimal>,
+    /// Best price since the stop was armed; `None` until then.
+    #[serde(default)]
+    pub extreme: Option<UnsignedDecimal>,
+}
+
+/// What a trade did to a trailing stop.
+#[derive(Debug, PartialEq, Eq)]
+enum Trail {
+    Armed,
+    Moved,
+}
+
+impl TrailingStop {
+    /// A stop that arms on the first trade seen.
+    pub fn new(distance: TrailDistance) -> Self {
+        Self {
+            distance,
+            activation: None,
+            extreme: None,
+        }
+    }
+
+    /// Arm only once a trade reaches `price`.
+    pub fn activate_at(mut self, price: UnsignedDecimal) -> Self {
+        self.activation = Some(price);
+        self
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.extreme.is_some()
+    }
+
+    /// The price that fires the stop for an order on `side`, once armed.
+    pub fn stop_price(&self, side: Side) -> Option<UnsignedDecimal> {
+        let extreme = *self.extreme?.inner();
+        let hundred = Decimal::ONE_HUNDRED;
+        let stop = match (side, self.distance) {
+            (Side::Sell, TrailDistance::Absolute(d)) => extreme - d.inner().min(&extreme),
+            (Side::Buy, TrailDistance::Absolute(d)) => extreme + d.inner(),
+            (Side::Sell, TrailDistance::Percent(p)) => {
+                extreme * (hundred - p.inner().min(&hundred)) / hundred
+            }
+            (Side::Buy, TrailDistance::Percent(p)) => extreme * (hundred + p.inner()) / hundred,
+        };
+        UnsignedDecimal::new(stop).ok()
+    }
+
+    fn validate(&self) -> Result<(), O2Error> {
+        let valid = match self.distance {
+            TrailDistance::Absolute(d) => !d.inner().is_zero(),
+            TrailDistance::Percent(p) => !p.inner().is_zero() && *p.inner() < Decimal::ONE_HUNDRED,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(O2Error::InvalidOrderParams(format!(
+                "Invalid trailing distance {:?}: must be above zero, and a percentage below 100",
+                self.distance
+            )))
+        }
+    }
+
+    /// Track a trade at `price` for an order on `side`.
+    fn observe(&mut self, side: Side, price: UnsignedDecimal) -> Option<Trail> {
+        let Some(extreme) = self.extreme else {
+            let reached = match (side, self.activation) {
+                (_, None) => true,
+                (Side::Sell, Some(activation)) => price >= activation,
+                (Side::Buy, Some(activation)) => price <= activation,
+            };
+            if !reached {
+                return None;
+            }
+            self.extreme = Some(price);
+            return Some(Trail::Armed);
+        };
+        let better = match side {
+            Side::Sell => price > extreme,
+            Side::Buy => price < extreme,
+        };
+        if !better {
+            return None;
+        }
+        self.extreme = Some(price);
+        Some(Trail::Moved)
+    }
+}
+
+/// The order a trigger submits when it fires.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerOrder {
+    /// A `Market` order with `price` as its order price.
+    Market { price: UnsignedDecimal },
+    /// A `BoundedMarket` order accepting at most `max_slippage_bps` from the
+    /// trade price that fired the trigger; see [`Market::slippage_bounds`].
+    BoundedMarket { max_slippage_bps: u32 },
+    /// A `Spot` order resting at `price`.
+    Limit { price: UnsignedDecimal },
+}
+
+/// Where a [`Trigger`] is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TriggerStatus {
+    /// Waiting for the price to cross.
+    Pending,
+    /// A trade at `price` fired the trigger and its order is being submitted.
+    Firing { price: UnsignedDecimal },
+    /// The order was accepted.
+    Fired {
+        price: UnsignedDecimal,
+        order_id: Option<OrderId>,
+    },
+    /// The order filled completely.
+    Filled {
+        price: UnsignedDecimal,
+        order_id: OrderId,
+    },
+    /// The order was not placed, or may not have been: a transport error
+    /// during submission leaves the outcome unknown. Never retried.
+    Failed { reason: String },
+    /// Withdrawn through [`TriggerBook::cancel`] or [`TriggerEngine::cancel`]
+    /// before it fired.
+    Cancelled,
+}
+
+/// An order held back until the price crosses a level.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    /// Unique within a [`TriggerBook`].
+    pub id: String,
+    pub market: MarketSymbol,
+    pub side: Side,
+    pub quantity: UnsignedDecimal,
+    pub condition: TriggerCondition,
+    pub order: TriggerOrder,
+    pub status: TriggerStatus,
+}
+
+impl Trigger {
+    /// A pending trigger.
+    pub fn new(
+        id: impl Into<String>,
+        market: MarketSymbol,
+        side: Side,
+        quantity: UnsignedDecimal,
+        condition: TriggerCondition,
+        order: TriggerOrder,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            market,
+            side,
+            quantity,
+            condition,
+            order,
+            status: TriggerStatus::Pending,
+        }
+    }
+
+    /// True while the trigger can still fire.
+    pub fn is_pending(&self) -> bool {
+        self.status == TriggerStatus::Pending
+    }
+
+    /// The order of a fired trigger, while it may still fill.
+    fn fired_order(&self) -> Option<&OrderId> {
+        match &self.status {
+            TriggerStatus::Fired {
+                order_id: Some(order_id),
+                ..
+            } => Some(order_id),
+            _ => None,
+        }
+    }
+
+    /// Fails if the trailing distance is invalid.
+    pub(crate) fn validate(&self) -> Result<(), O2Error> {
+        match &self.condition {
+            TriggerCondition::Trailing(trailing) => trailing.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply a trade at `price` on this trigger's market while it is pending:
+    /// arm or move a trailing stop, and report [`TriggerEvent::Triggered`] if
+    /// the trade fires it.
+    pub(crate) fn observe(&mut self, price: UnsignedDecimal) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        if !self.is_pending() {
+            return events;
+        }
+        let side = self.side;
+        if let TriggerCondition::Trailing(trailing) = &mut self.condition {
+            let id = self.id.clone();
+            match trailing.observe(side, price) {
+                Some(Trail::Armed) => events.push(TriggerEvent::Armed { id, price }),
+                Some(Trail::Moved) => {
+                    if let Some(stop) = trailing.stop_price(side) {
+                        events.push(TriggerEvent::Trailing {
+                            id,
+                            extreme: price,
+                            stop,
+                        });
+                    }
+                }
+                None => {}
+            }
+        }
+        if self.condition.is_met(side, &price) {
+            events.push(TriggerEvent::Triggered {
+                id: self.id.clone(),
+                price,
+            });
+        }
+        events
+    }
+
+    /// The order to submit after a trade at `chain_price` fired the trigger.
+    pub(crate) fn to_action(&self, market: &Market, chain_price: u64) -> Result<Action, O2Error> {
+        let (price, order_type) = match &self.order {
+            TriggerOrder::Market { price } => (*price, OrderType::Market),
+            TriggerOrder::BoundedMarket { max_slippage_bps } => {
+                let (min_price, max_price) =
+                    market.slippage_bounds(self.side, chain_price, *max_slippage_bps)?;
+                let worst = match self.side {
+                    Side::Buy => max_price,
+                    Side::Sell => min_price,
+                };
+                (
+                    market.format_price(worst)?,
+                    OrderType::BoundedMarket {
+                        max_price: market.format_price(max_price)?,
+                        min_price: market.format_price(min_price)?,
+                    },
+                )
+            }
+            TriggerOrder::Limit { price } => (*price, OrderType::Spot),
+        };
+        Ok(Action::CreateOrder {
+            side: self.side,
+            price,
+            quantity: self.quantity,
+            order_type,
+        })
+    }
+}
+
+/// A step in a trigger's lifecycle, broadcast by a running [`TriggerEngine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TriggerEvent {
+    /// A trailing stop reached its activation price and started trailing
+    /// from `price`.
+    Armed { id: String, price: UnsignedDecimal },
+    /// A trailing stop's best price moved to `extreme`, and its stop to
+    /// `stop`.
+    Trailing {
+        id: String,
+        extreme: UnsignedDecimal,
+        stop: UnsignedDecimal,
+    },
+    /// A trade at `price` fired the trigger.
+    Triggered { id: String, price: UnsignedDecimal },
+    /// The trigger's order was accepted.
+    Submitted {
+        id: String,
+        order_id: Option<OrderId>,
+    },
+    /// The trigger's order filled completely.
+    Filled { id: String, order_id: OrderId },
+    /// The trigger's order was not placed; see [`TriggerStatus::Failed`].
+    Failed { id: String, reason: String },
+}
+
+/// Every trigger a [`TriggerEngine`] watches, pending or not.
+///
+/// This is the persisted state of an engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerBook {
+    pub triggers: Vec<Trigger>,
+}
+
+impl TriggerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a book written by a [`TriggerEngine`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, O2Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            O2Error::Other(format!(
+                "Failed to read trigger book {}: {e}",
+                path.display()
+            ))
+        })?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Write the book atomically (temporary file, then rename).
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), O2Error> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                O2Error::Other(format!(
+                    "Failed to write trigger book {}: {e}",
+                    path.display()
+                ))
+            })
+    }
+
+    /// Add `trigger`. Fails if its id is taken or its trailing distance is
+    /// invalid.
+    pub fn add(&mut self, trigger: Trigger) -> Result<(), O2Error> {
+        if self.get(&trigger.id).is_some() {
+            return Err(O2Error::InvalidRequest(format!(
+                "Trigger '{}' already exists",
+                trigger.id
+            )));
+        }
+        trigger.validate()?;
+        self.triggers.push(trigger);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Trigger> {
+        self.triggers.iter().find(|t| t.id == id)
+    }
+
+    /// Triggers that can still fire.
+    pub fn pending(&self) -> impl Iterator<Item = &Trigger> {
+        self.triggers.iter().filter(|t| t.is_pending())
+    }
+
+    /// Withdraw a pending trigger. Fails if it is unknown or no longer
+    /// pending.
+    pub fn cancel(&mut self, id: &str) -> Result<(), O2Error> {
+        let trigger = self
+            .triggers
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| O2Error::InvalidRequest(format!("Unknown trigger '{id}'")))?;
+        if !trigger.is_pending() {
+            return Err(O2Error::InvalidRequest(format!(
+                "Trigger '{id}' is no longer pending: {:?}",
+                trigger.status
+            )));
+        }
+        trigger.status = TriggerStatus::Cancelled;
+        Ok(())
+    }
+
+    /// Ids of the pending triggers on `market` that a trade at `price` fires,
+    /// with trailing stops where they stand.
+    pub fn due(&self, market: &MarketSymbol, price: &UnsignedDecimal) -> Vec<String> {
+        self.pending()
+            .filter(|t| t.market == *market && t.condition.is_met(t.side, price))
+            .map(|t| t.id.clone())
+            .collect()
+    }
+
+    /// Apply a trade at `price` on `market`: arm and move trailing stops, then
+    /// report every pending trigger it fires as [`TriggerEvent::Triggered`].
+    ///
+    /// Triggered triggers stay pending; the engine fires them.
+    pub fn observe(&mut self, market: &MarketSymbol, price: UnsignedDecimal) -> Vec<TriggerEvent> {
+        self.triggers
+            .iter_mut()
+            .filter(|t| t.market == *market)
+            .flat_map(|t| t.observe(price))
+            .collect()
+    }
+
+    /// Apply an update to a fired trigger's order. Returns
+    /// [`TriggerEvent::Filled`] once it has filled completely.
+    pub fn record_fill(&mut self, order: &Order) -> Option<TriggerEvent> {
+        let trigger = self
+            .triggers
+            .iter_mut()
+            .find(|t| t.fired_order() == Some(&order.order_id))?;
+        if order.status() != OrderStatus::Filled {
+            return None;
+        }
+        let TriggerStatus::Fired { price, .. } = trigger.status else {
+            return None;
+        };
+        trigger.status = TriggerStatus::Filled {
+            price,
+            order_id: order.order_id.clone(),
+        };
+        Some(TriggerEvent::Filled {
+            id: trigger.id.clone(),
+            order_id: order.order_id.clone(),
+        })
+    }
+
+    /// Markets with a pending trigger, each listed once.
+    fn pending_markets(&self) -> Vec<MarketSymbol> {
+        let mut markets: Vec<MarketSymbol> = Vec::new();
+        for trigger in self.pending() {
+            if !markets.contains(&trigger.market) {
+                markets.push(trigger.market.clone());
+            }
+        }
+        markets
+    }
+
+    /// Move `id` from pending to firing at `price`. Returns the trigger, or
+    /// `None` if it is not pending: this is the at-most-once gate.
+    fn begin_firing(&mut self, id: &str, price: UnsignedDecimal) -> Option<Trigger> {
+        let trigger = self
+            .triggers
+            .iter_mut()
+            .find(|t| t.id == id && t.is_pending())?;
+        trigger.status = TriggerStatus::Firing { price };
+        Some(trigger.clone())
+    }
+
+    fn set_status(&mut self, id: &str, status: TriggerStatus) {
+        if let Some(trigger) = self.triggers.iter_mut().find(|t| t.id == id) {
+            trigger.status = status;
+        }
+    }
+}
+
+enum Command {
+    Add(Trigger, oneshot::Sender<Result<(), O2Error>>),
+    Cancel(String, oneshot::Sender<Result<(), O2Error>>),
+}
+
+/// A background task firing the triggers of a [`TriggerBook`].
+///
+/// Dropping the engine stops the task; pending triggers stay in the
+/// persisted book and can be started again.
+pub struct TriggerEngine {
+    book_rx: watch::Receiver<TriggerBook>,
+    events: broadcast::Sender<TriggerEvent>,
+    commands: mpsc::UnboundedSender<Command>,
+    task: Task<()>,
+}
+
+impl TriggerEngine {
+    /// Subscribe to the account's orders and to the trade stream of every
+    /// market with a pending trigger, and start watching.
+    pub(crate) async fn start(mut worker: Worker, config: TriggerConfig) -> Result<Self, O2Error> {
+        let orders = worker
+            .client
+            .stream_orders(&[Identity::ContractId(
+                worker.session.trade_account_id.as_str().to_string(),
+            )])
+            .await?;
+        let mut trades = SelectAll::new();
+        for market in worker.book.pending_markets() {
+            worker.watch(&market, &mut trades).await?;
+        }
+        let (book_tx, book_rx) = watch::channel(worker.book.clone());
+        let (events, _) = broadcast::channel(64);
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let publisher = Publisher {
+            path: config.persist_path.clone(),
+            book_tx,
+            events: events.clone(),
+        };
+        let task = runtime::spawn(run(worker, trades, orders, config, publisher, commands_rx));
+        Ok(Self {
+            book_rx,
+            events,
+            commands,
+            task,
+        })
+    }
+
+    /// Current state of every trigger.
+    pub fn book(&self) -> TriggerBook {
+        self.book_rx.borrow().clone()
+    }
+
+    /// Current state of one trigger.
+    pub fn trigger(&self, id: &str) -> Option<Trigger> {
+        self.book_rx.borrow().get(id).cloned()
+    }
+
+    /// Receive every change to the book.
+    pub fn subscribe(&self) -> watch::Receiver<TriggerBook> {
+        self.book_rx.clone()
+    }
+
+    /// Receive lifecycle events from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<TriggerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start watching `trigger`, subscribing to its market's trades if no
+    /// other trigger is on that market. Fails if its id is taken.
+    pub async fn add(&self, trigger: Trigger) -> Result<(), O2Error> {
+        self.request(|reply| Command::Add(trigger, reply)).await
+    }
+
+    /// Withdraw a pending trigger. Fails if it is unknown or has already
+    /// fired.
+    pub async fn cancel(&self, id: &str) -> Result<(), O2Error> {
+        let id = id.to_string();
+        self.request(|reply| Command::Cancel(id, reply)).await
+    }
+
+    async fn request(
+        &self,
+        command: impl FnOnce(oneshot::Sender<Result<(), O2Error>>) -> Command,
+    ) -> Result<(), O2Error> {
+        let stopped = || O2Error::Other("Trigger engine has stopped".into());
+        let (reply, rx) = oneshot::channel();
+        self.commands.send(command(reply)).map_err(|_| stopped())?;
+        rx.await.map_err(|_| stopped())?
+    }
+
+    /// True until the engine is stopped or the account's order stream ends.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop watching. A trigger that is firing may or may not be submitted.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for TriggerEngine {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The client side of a [`TriggerEngine`]: reads prices, submits orders and
+/// follows them until they close.
+pub(crate) struct Worker {
+    client: O2Client,
+    session: Session,
+    nonces: NonceManager,
+    book: TriggerBook,
+    markets: HashMap<MarketId, Market>,
+    /// Fired orders known to be closed, which no longer need polling.
+    closed: HashSet<OrderId>,
+}
+
+impl Worker {
+    pub(crate) fn new(
+        client: O2Client,
+        session: Session,
+        nonces: NonceManager,
+        book: TriggerBook,
+    ) -> Self {
+        Self {
+            client,
+            session,
+            nonces,
+            book,
+            markets: HashMap::new(),
+            closed: HashSet::new(),
+        }
+    }
+
+    /// Subscribe to `symbol`'s trades unless already subscribed.
+    async fn watch(
+        &mut self,
+        symbol: &MarketSymbol,
+        trades: &mut SelectAll<TypedStream<TradeUpdate>>,
+    ) -> Result<(), O2Error> {
+        if self.markets.values().any(|m| m.symbol_pair() == *symbol) {
+            return Ok(());
+        }
+        let market = self.client.get_market(symbol).await?;
+        debug!("triggers.watch market={}", symbol);
+        trades.push(self.client.stream_trades(&market.market_id).await?);
+        self.markets.insert(market.market_id.clone(), market);
+        Ok(())
+    }
+
+    /// Apply a trade at `chain_price` on `market_id`, firing every pending
+    /// trigger it crosses. Returns true if the book changed.
+    async fn observe(
+        &mut self,
+        market_id: &MarketId,
+        chain_price: u64,
+        publisher: &Publisher,
+    ) -> bool {
+        let Some(market) = self.markets.get(market_id).cloned() else {
+            return false;
+        };
+        let price = match market.format_price(chain_price) {
+            Ok(price) => price,
+            Err(e) => {
+                debug!("triggers.observe bad_price={} error={}", chain_price, e);
+                return false;
+            }
+        };
+        let events = self.book.observe(&market.symbol_pair(), price);
+        let changed = !events.is_empty();
+        for event in events {
+            let fire = match &event {
+                TriggerEvent::Triggered { id, .. } => Some(id.clone()),
+                _ => None,
+            };
+            publisher.emit(event);
+            if let Some(id) = fire {
+                self.fire(&id, &market, chain_price, price, publisher).await;
+            }
+        }
+        changed
+    }
+
+    async fn fire(
+        &mut self,
+        id: &str,
+        market: &Market,
+        chain_price: u64,
+        price: UnsignedDecimal,
+        publisher: &Publisher,
+    ) {
+        let Some(trigger) = self.book.begin_firing(id, price) else {
+            return;
+        };
+        debug!(
+            "triggers.fire id={} market={} price={}",
+            id, trigger.market, price
+        );
+        if let Err(e) = publisher.publish(&self.book) {
+            self.fail(
+                id,
+                format!("not fired, state could not be saved: {e}"),
+                publisher,
+            );
+            return;
+        }
+        let result = match trigger.to_action(market, chain_price) {
+            Ok(action) => self
+                .nonces
+                .batch_actions(
+                    &mut self.client,
+                    &mut self.session,
+                    &trigger.market,
+                    vec![action],
+                    true,
+                )
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(resp) if resp.is_success() => {
+                let order = resp.orders.and_then(|orders| orders.into_iter().next());
+                let order_id = order.as_ref().map(|o| o.order_id.clone());
+                debug!("triggers.fired id={} order_id={:?}", id, order_id);
+                self.book.set_status(
+                    id,
+                    TriggerStatus::Fired {
+                        price,
+                        order_id: order_id.clone(),
+                    },
+                );
+                publisher.emit(TriggerEvent::Submitted {
+                    id: id.to_string(),
+                    order_id,
+                });
+                // A market order has usually filled by the time it is returned.
+                if let Some(order) = order {
+                    self.track(&order, publisher);
+                }
+            }
+            Ok(resp) => self.fail(
+                id,
+                resp.message.unwrap_or_else(|| "no message".into()),
+                publisher,
+            ),
+            Err(reason) => self.fail(id, reason, publisher),
+        }
+    }
+
+    fn fail(&mut self, id: &str, reason: String, publisher: &Publisher) {
+        debug!("triggers.failed id={} reason={}", id, reason);
+        self.book.set_status(
+            id,
+            TriggerStatus::Failed {
+                reason: reason.clone(),
+            },
+        );
+        publisher.emit(TriggerEvent::Failed {
+            id: id.to_string(),
+            reason,
+        });
+    }
+
+    /// Apply an update to a fired trigger's order. Returns true if the book
+    /// changed.
+    fn track(&mut self, order: &Order, publisher: &Publisher) -> bool {
+        let event = self.book.record_fill(order);
+        if order.status().is_terminal() {
+            self.closed.insert(order.order_id.clone());
+        }
+        match event {
+            Some(event) => {
+                publisher.emit(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-read the last price of every market with a pending trigger, and
+    /// every fired order that may still fill.
+    async fn poll(&mut self, publisher: &Publisher) -> bool {
+        let mut changed = false;
+        for symbol in self.book.pending_markets() {
+            let Some(market_id) = self
+                .markets
+                .values()
+                .find(|m| m.symbol_pair() == symbol)
+                .map(|m| m.market_id.clone())
+            else {
+                continue;
+            };
+            match self.client.get_ticker(&symbol).await {
+                Ok(ticker) => {
+                    if let Some(last) = ticker.last {
+                        changed |= self.observe(&market_id, last, publisher).await;
+                    }
+                }
+                Err(e) => debug!("triggers.poll_failed market={} error={}", symbol, e),
+            }
+        }
+        let fired: Vec<(MarketSymbol, OrderId)> = self
+            .book
+            .triggers
+            .iter()
+            .filter_map(|t| Some((t.market.clone(), t.fired_order()?.clone())))
+            .filter(|(_, order_id)| !self.closed.contains(order_id))
+            .collect();
+        for (market, order_id) in fired {
+            match self.client.get_order(&market, &order_id).await {
+                Ok(order) => changed |= self.track(&order, publisher),
+                Err(e) => debug!("triggers.poll_failed order_id={} error={}", order_id, e),
+            }
+        }
+        changed
+    }
+
+    async fn handle(
+        &mut self,
+        command: Command,
+        trades: &mut SelectAll<TypedStream<TradeUpdate>>,
+    ) -> bool {
+        match command {
+            Command::Add(trigger, reply) => {
+                let result = match self.watch(&trigger.market, trades).await {
+                    Ok(()) => self.book.add(trigger),
+                    Err(e) => Err(e),
+                };
+                let changed = result.is_ok();
+                let _ = reply.send(result);
+                changed
+            }
+            Command::Cancel(id, reply) => {
+                let result = self.book.cancel(&id);
+                let changed = result.is_ok();
+                let _ = reply.send(result);
+                changed
+            }
+        }
+    }
+}
+
+/// Where changes go: the book to its watch channel and file, if configured,
+/// and lifecycle events to subscribers.
+struct Publisher {
+    path: Option<PathBuf>,
+    book_tx: watch::Sender<TriggerBook>,
+    events: broadcast::Sender<TriggerEvent>,
+}
+
+impl Publisher {
+    /// Publish `book`. Fails only if it could not be written to disk.
+    fn publish(&self, book: &TriggerBook) -> Result<(), O2Error> {
+        let _ = self.book_tx.send(book.clone());
+        match &self.path {
+            Some(path) => book.save(path),
+            None => Ok(()),
+        }
+    }
+
+    fn publish_logged(&self, book: &TriggerBook) {
+        if let Err(e) = self.publish(book) {
+            debug!("triggers.persist_failed error={}", e);
+        }
+    }
+
+    fn emit(&self, event: TriggerEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+async fn run(
+    mut worker: Worker,
+    mut trades: SelectAll<TypedStream<TradeUpdate>>,
+    mut orders: TypedStream<OrderUpdate>,
+    config: TriggerConfig,
+    publisher: Publisher,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    publisher.publish_logged(&worker.book);
+
+    let mut ticker = runtime::interval(config.poll_interval);
+    loop {
+        let changed = tokio::select! {
+            Some(item) = trades.next() => match item {
+                Err(e) => {
+                    debug!("triggers.run stream_error={}", e);
+                    false
+                }
+                Ok(update) => {
+                    let mut changed = false;
+                    for trade in &update.trades {
+                        changed |= worker.observe(&update.market_id, trade.price, &publisher).await;
+                    }
+                    changed
+                }
+            },
+            item = orders.next() => match item {
+                None => break,
+                Some(Err(e)) => {
+                    debug!("triggers.run order_stream_error={}", e);
+                    false
+                }
+                Some(Ok(update)) => {
+                    let mut changed = false;
+                    for order in &update.orders {
+                        changed |= worker.track(order, &publisher);
+                    }
+                    changed
+                }
+            },
+            _ = ticker.tick() => worker.poll(&publisher).await,
+            command = commands.recv() => match command {
+                None => break,
+                Some(command) => worker.handle(command, &mut trades).await,
+            },
+        };
+        if changed {
+            publisher.publish_logged(&worker.book);
+        }
+    }
+    debug!(
+        "triggers.run finished pending={}",
+        worker.book.pending().count()
+    );
+    publisher.publish_logged(&worker.book);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AssetId, ContractId, MarketAsset};
+
+    fn dec(s: &str) -> UnsignedDecimal {
+        s.parse().unwrap()
+    }
+
+    fn stop(id: &str, condition: TriggerCondition) -> Trigger {
+        Trigger::new(
+            id,
+            MarketSymbol::new("fFUEL/fUSDC"),
+            Side::Sell,
+            dec("100"),
+            condition,
+            TriggerOrder::BoundedMarket {
+                max_slippage_bps: 500,
+            },
+        )
+    }
+
+    fn market() -> Market {
+        let asset = |symbol: &str, id: &str, decimals: u32| MarketAsset {
+            symbol: symbol.into(),
+            asset: AssetId::new(id),
+            decimals,
+            max_precision: 3,
+        };
+        Market {
+            contract_id: ContractId::new("0xb00c"),
+            market_id: MarketId::new("0xaa"),
+            whitelist_id: None,
+            blacklist_id: None,
+            maker_fee: 0,
+            taker_fee: 0,
+            min_order: 0,
+            dust: 0,
+            price_window: 0,
+            base: asset("fFUEL", "0x01", 9),
+            quote: asset("fUSDC", "0x02", 6),
+        }
+    }
+
+    #[test]
+    fn crossing_prices_are_due_and_fire_at_most_once() {
+        let mut book = TriggerBook::new();
+        book.add(stop("sl", TriggerCondition::AtOrBelow(dec("0.02"))))
+            .unwrap();
+        book.add(stop("tp", TriggerCondition::AtOrAbove(dec("0.03"))))
+            .unwrap();
+        assert!(book
+            .add(stop("sl", TriggerCondition::AtOrAbove(dec("1"))))
+            .is_err());
+
+        let fuel = MarketSymbol::new("fFUEL/fUSDC");
+        assert!(book.due(&fuel, &dec("0.025")).is_empty());
+        assert_eq!(book.due(&fuel, &dec("0.02")), ["sl"]);
+        assert_eq!(book.due(&fuel, &dec("0.031")), ["tp"]);
+        assert!(book
+            .due(&MarketSymbol::new("fETH/fUSDC"), &dec("0.01"))
+            .is_empty());
+
+        assert!(book.begin_firing("sl", dec("0.019")).is_some());
+        assert!(book.begin_firing("sl", dec("0.018")).is_none());
+        assert!(book.due(&fuel, &dec("0.01")).is_empty());
+        assert!(book.cancel("sl").is_err());
+
+        book.cancel("tp").unwrap();
+        assert_eq!(book.get("tp").unwrap().status, TriggerStatus::Cancelled);
+        assert!(book.begin_firing("tp", dec("0.04")).is_none());
+        assert_eq!(book.pending().count(), 0);
+    }
+
+    #[test]
+    fn orders_are_built_from_the_crossing_price() {
+        let market = market();
+        // 0.020 fUSDC, chain-scaled at 6 decimals; 5% below it is 0.019.
+        let action = stop("sl", TriggerCondition::AtOrBelow(dec("0.02")))
+            .to_action(&market, 20_000)
+            .unwrap();
+        let Action::CreateOrder {
+            side,
+            price,
+            order_type: OrderType::BoundedMarket { min_price, .. },
+            ..
+        } = action
+        else {
+            panic!("expected a bounded market order: {action:?}");
+        };
+        assert_eq!(side, Side::Sell);
+        assert_eq!(price, dec("0.019"));
+        assert_eq!(min_price, dec("0.019"));
+
+        let mut limit = stop("tp", TriggerCondition::AtOrAbove(dec("0.03")));
+        limit.order = TriggerOrder::Limit {
+            price: dec("0.031"),
+        };
+        let Action::CreateOrder {
+            price, order_type, ..
+        } = limit.to_action(&market, 30_000).unwrap()
+        else {
+            panic!("expected an order");
+        };
+        assert_eq!(price, dec("0.031"));
+        assert!(matches!(order_type, OrderType::Spot));
+    }
+
+    #[test]
+    fn trailing_stops_arm_follow_the_best_price_and_fire_on_a_retrace() {
+        let fuel = MarketSymbol::new("fFUEL/fUSDC");
+        let trailing = TrailingStop::new(TrailDistance::Percent(dec("10"))).activate_at(dec("1.0"));
+        let mut book = TriggerBook::new();
+        book.add(stop("trail", TriggerCondition::Trailing(trailing)))
+            .unwrap();
+        let mut buy = stop(
+            "cover",
+            TriggerCondition::Trailing(TrailingStop::new(TrailDistance::Absolute(dec("0.1")))),
+        );
+        buy.side = Side::Buy;
+        book.add(buy).unwrap();
+        assert!(book
+            .add(stop(
+                "bad",
+                TriggerCondition::Trailing(TrailingStop::new(TrailDistance::Percent(dec("100"))))
+            ))
+            .is_err());
+
+        // Below activation only the buy stop arms, at 0.9; its stop is 1.0.
+        assert_eq!(
+            book.observe(&fuel, dec("0.9")),
+            [TriggerEvent::Armed {
+                id: "cover".into(),
+                price: dec("0.9")
+            }]
+        );
+        assert!(book.observe(&fuel, dec("0.95")).is_empty());
+
+        let events = book.observe(&fuel, dec("1.0"));
+        assert_eq!(
+            events,
+            [
+                TriggerEvent::Armed {
+                    id: "trail".into(),
+                    price: dec("1.0")
+                },
+                TriggerEvent::Triggered {
+                    id: "cover".into(),
+                    price: dec("1.0")
+                },
+            ]
+        );
+        book.begin_firing("cover", dec("1.0"));
+
+        assert_eq!(
+            book.observe(&fuel, dec("1.2")),
+            [TriggerEvent::Trailing {
+                id: "trail".into(),
+                extreme: dec("1.2"),
+                stop: dec("1.08")
+            }]
+        );
+        assert!(book.observe(&fuel, dec("1.1")).is_empty());
+        assert_eq!(book.due(&fuel, &dec("1.08")), ["trail"]);
+        assert_eq!(
+            book.observe(&fuel, dec("1.07")),
+            [TriggerEvent::Triggered {
+                id: "trail".into(),
+                price: dec("1.07")
+            }]
+        );
+    }
+
+    #[test]
+    fn fired_orders_are_followed_until_filled() {
+        let order = |filled: u64| -> Order {
+            serde_json::from_value(serde_json::json!({
+                "order_id": "0x0f",
+                "side": "Sell",
+                "order_type": "Spot",
+                "quantity": "100",
+                "quantity_fill": filled.to_string(),
+                "price": "1",
+                "close": filled == 100,
+            }))
+            .unwrap()
+        };
+        let mut book = TriggerBook::new();
+        book.add(stop("sl", TriggerCondition::AtOrBelow(dec("0.02"))))
+            .unwrap();
+        assert_eq!(book.record_fill(&order(100)), None);
+
+        book.set_status(
+            "sl",
+            TriggerStatus::Fired {
+                price: dec("0.02"),
+                order_id: Some(OrderId::new("0x0f")),
+            },
+        );
+        assert_eq!(book.record_fill(&order(40)), None);
+        assert_eq!(
+            book.record_fill(&order(100)),
+            Some(TriggerEvent::Filled {
+                id: "sl".into(),
+                order_id: OrderId::new("0x0f")
+            })
+        );
+        assert_eq!(
+            book.get("sl").unwrap().status,
+            TriggerStatus::Filled {
+                price: dec("0.02"),
+                order_id: OrderId::new("0x0f")
+            }
+        );
+        assert_eq!(book.record_fill(&order(100)), None);
+    }
+
+    #[test]
+    fn persists_round_trip() {
+        let mut book = TriggerBook::new();
+        book.add(stop("sl", TriggerCondition::AtOrBelow(dec("0.02"))))
+            .unwrap();
+        book.add(stop("tp", TriggerCondition::AtOrAbove(dec("0.03"))))
+            .unwrap();
+        book.begin_firing("tp", dec("0.03"));
+        let path =
+            std::env::temp_dir().join(format!("o2-triggers-test-{}.json", std::process::id()));
+        book.save(&path).unwrap();
+        let loaded = TriggerBook::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, book);
+        assert_eq!(loaded.pending_markets(), [MarketSymbol::new("fFUEL/fUSDC")]);
+    }
+}
@@ -4,33 +4,38 @@
 /// - Auto-reconnect with exponential backoff
 /// - Subscription tracking and automatic re-subscribe on reconnect
 /// - Per-subscription channels (no race condition on concurrent stream calls)
+/// - Messages routed by market or identity to the streams that asked for them
+/// - Identical subscriptions share one server subscription
 /// - Heartbeat ping/pong with configurable intervals
-/// - Graceful shutdown signaling
+/// - Graceful shutdown signaling, and draining ahead of a planned shutdown
 use futures_util::{SinkExt, StreamExt};
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex;
 use tokio_stream::Stream;
+use tokio_tungstenite::tungstenite::Error as WsError;
 use tokio_tungstenite::tungstenite::Message as WsMsg;
 
 use crate::errors::O2Error;
+use crate::failover::{EndpointHealth, Endpoints, FailoverPolicy};
 use crate::models::*;
+use crate::orderbook::OrderBook;
+use crate::recorder::{Direction, Recorder};
+use crate::runtime;
 
 type WsSink = futures_util::stream::SplitSink<
     tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
     WsMsg,
 >;
 
-type WsStream = futures_util::stream::SplitStream<
-    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
->;
-
 /// Configuration for WebSocket reconnection behavior.
 #[derive(Debug, Clone)]
 pub struct WsConfig {
@@ -44,6 +49,11 @@ pub struct WsConfig {
     pub ping_interval: Duration,
     /// Timeout for pong response before triggering reconnect (default: 60s).
     pub pong_timeout: Duration,
+    /// URLs tried in order when the one passed to `connect` is failing
+    /// (default: none). See [`crate::failover`].
+    pub fallback_urls: Vec<String>,
+    /// When to move between the URL and its fallbacks.
+    pub failover: FailoverPolicy,
 }
 
 impl Default for WsConfig {
@@ -54,6 +64,8 @@ impl Default for WsConfig {
             max_attempts: 10,
             ping_interval: Duration::from_secs(30),
             pong_timeout: Duration::from_secs(60),
+            fallback_urls: Vec::new(),
+            failover: FailoverPolicy::default(),
         }
     }
 }
@@ -68,6 +80,142 @@ impl Default for WsConfig {
 /// [`O2WebSocket::subscribe_lifecycle`].
 pub struct TypedStream<T> {
     rx: mpsc::UnboundedReceiver<Result<T, O2Error>>,
+    handle: Option<SubscriptionHandle>,
+}
+
+impl<T> TypedStream<T> {
+    /// Wrap a channel receiver. Used by stream adapters that forward filtered items.
+    pub(crate) fn from_receiver(rx: mpsc::UnboundedReceiver<Result<T, O2Error>>) -> Self {
+        Self { rx, handle: None }
+    }
+
+    /// The server subscription behind this stream, if it has one.
+    pub fn subscription(&self) -> Option<&SubscriptionHandle> {
+        self.handle.as_ref()
+    }
+
+    /// End the stream and release its subscription now. See
+    /// [`SubscriptionHandle::close`]. Dropping the stream does the same in
+    /// the background.
+    pub async fn close(mut self) -> Result<(), O2Error> {
+        match self.handle.take() {
+            Some(handle) => handle.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// A stream's claim on a server subscription.
+///
+/// Streams subscribing with the same message share one server subscription.
+/// When the last of them is closed or dropped, the matching `unsubscribe_*`
+/// is sent and the subscription is no longer re-sent on reconnect. The
+/// unsubscribe is spawned on the SDK's [`Runtime`](crate::runtime::Runtime);
+/// dropping where it cannot spawn (outside a Tokio runtime, by default) leaves
+/// the subscription to the connection.
+pub struct SubscriptionHandle {
+    inner: Weak<Mutex<WsInner>>,
+    subscription: serde_json::Value,
+    holder: u64,
+    released: bool,
+}
+
+impl SubscriptionHandle {
+    /// The subscribe message this handle holds.
+    pub fn subscription(&self) -> &serde_json::Value {
+        &self.subscription
+    }
+
+    /// Release the subscription, sending the unsubscribe if no other stream
+    /// holds it.
+    pub async fn close(mut self) -> Result<(), O2Error> {
+        self.released = true;
+        match self.inner.upgrade() {
+            Some(inner) => release(&inner, &self.subscription, self.holder).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        let Some(inner) = self.inner.upgrade() else {
+            return;
+        };
+        if !runtime::can_spawn() {
+            return;
+        }
+        let subscription = std::mem::take(&mut self.subscription);
+        let holder = self.holder;
+        runtime::spawn(async move {
+            let _ = release(&inner, &subscription, holder).await;
+        });
+    }
+}
+
+impl fmt::Debug for SubscriptionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriptionHandle")
+            .field("subscription", &self.subscription)
+            .finish()
+    }
+}
+
+/// A tracked server subscription and the streams sharing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveStream {
+    /// The subscribe message sent to the server.
+    pub subscription: serde_json::Value,
+    /// Open streams holding it.
+    pub streams: usize,
+}
+
+/// Drop `holder`'s claim on `subscription` and, if it was the last one, stop
+/// tracking it and unsubscribe.
+async fn release(
+    inner: &Mutex<WsInner>,
+    subscription: &serde_json::Value,
+    holder: u64,
+) -> Result<(), O2Error> {
+    let mut guard = inner.lock().await;
+    let key = subscription.to_string();
+    let Some(holders) = guard.holders.get_mut(&key) else {
+        return Ok(());
+    };
+    holders.retain(|h| *h != holder);
+    if !holders.is_empty() {
+        return Ok(());
+    }
+    guard.holders.remove(&key);
+    let Some(pos) = guard.subscriptions.iter().position(|s| s == subscription) else {
+        return Ok(());
+    };
+    guard.subscriptions.remove(pos);
+    let Some(unsub) = unsubscribe_message(subscription) else {
+        return Ok(());
+    };
+    // Depth at another precision unsubscribes the same way; leave it be.
+    if guard
+        .subscriptions
+        .iter()
+        .any(|s| unsubscribe_message(s).as_ref() == Some(&unsub))
+    {
+        return Ok(());
+    }
+    for kind in [FeedKind::Depth, FeedKind::Trades] {
+        if unsub["action"] == kind.unsubscribe_action() {
+            if let Some(market_id) = unsub["market_id"].as_str() {
+                guard.feeds.remove(&feed_key(kind, market_id));
+                if kind == FeedKind::Depth {
+                    guard.remove_depth_book(market_id);
+                }
+            }
+        }
+    }
+    guard.send_or_queue(unsub).await
 }
 
 impl<T> Stream for TypedStream<T> {
@@ -110,20 +258,288 @@ impl DepthPrecision {
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WsLifecycleEvent {
-    Reconnecting { attempt: usize, delay: Duration },
-    Reconnected { attempts: usize },
-    Disconnected { reason: String, final_: bool },
+    Reconnecting {
+        attempt: usize,
+        delay: Duration,
+    },
+    Reconnected {
+        attempts: usize,
+    },
+    Disconnected {
+        reason: String,
+        final_: bool,
+    },
+    /// A market data feed has been silent for longer than the monitor's window.
+    /// See [`crate::feed_monitor`].
+    FeedStalled {
+        kind: FeedKind,
+        market_id: String,
+        silent_for: Duration,
+    },
+    /// A stalled feed was re-subscribed without reconnecting the socket.
+    FeedResubscribed {
+        kind: FeedKind,
+        market_id: String,
+    },
+    /// The socket is draining ahead of a planned shutdown. No new
+    /// subscriptions are accepted, and the socket closes with a final
+    /// `Disconnected` once in-flight messages have arrived, or at `deadline`.
+    /// See [`O2WebSocket::drain`].
+    Draining {
+        deadline: Instant,
+    },
+    /// A reconnect landed on a different endpoint than the last connection,
+    /// because earlier ones were failing or one recovered. See
+    /// [`crate::failover`].
+    FailedOver {
+        from: String,
+        to: String,
+    },
+}
+
+/// A market-scoped data feed whose message gaps are tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeedKind {
+    Depth,
+    Trades,
+}
+
+impl FeedKind {
+    fn subscribe_action(&self) -> &'static str {
+        match self {
+            FeedKind::Depth => "subscribe_depth",
+            FeedKind::Trades => "subscribe_trades",
+        }
+    }
+
+    fn unsubscribe_action(&self) -> &'static str {
+        match self {
+            FeedKind::Depth => "unsubscribe_depth",
+            FeedKind::Trades => "unsubscribe_trades",
+        }
+    }
+}
+
+/// Inter-message timing for one depth or trades subscription.
+#[derive(Debug, Clone)]
+pub struct FeedStats {
+    pub kind: FeedKind,
+    pub market_id: String,
+    /// Messages received since subscribing.
+    pub messages: u64,
+    pub subscribed_at: Instant,
+    pub last_message_at: Option<Instant>,
+    /// Longest gap between consecutive messages (or between subscribing and
+    /// the first message).
+    pub max_gap: Duration,
+}
+
+impl FeedStats {
+    fn new(kind: FeedKind, market_id: String) -> Self {
+        Self {
+            kind,
+            market_id,
+            messages: 0,
+            subscribed_at: Instant::now(),
+            last_message_at: None,
+            max_gap: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        let gap = now.saturating_duration_since(self.last_message_at.unwrap_or(self.subscribed_at));
+        self.max_gap = self.max_gap.max(gap);
+        self.last_message_at = Some(now);
+        self.messages += 1;
+    }
+
+    /// Time since the last message, or since subscribing if none has arrived.
+    pub fn silent_for(&self) -> Duration {
+        self.last_message_at.unwrap_or(self.subscribed_at).elapsed()
+    }
+}
+
+type FeedKey = (FeedKind, String);
+
+fn feed_key(kind: FeedKind, market_id: &str) -> FeedKey {
+    (kind, market_id.to_ascii_lowercase())
+}
+
+/// The `subscribe_*` action of a raw subscription message.
+fn raw_action(subscription: &serde_json::Value) -> Result<&str, O2Error> {
+    subscription
+        .get("action")
+        .and_then(|a| a.as_str())
+        .filter(|a| a.starts_with("subscribe_"))
+        .ok_or_else(|| {
+            O2Error::SubscriptionError(
+                "raw subscription must be an object with a subscribe_* action".into(),
+            )
+        })
+}
+
+/// The message undoing `subscription`: its action as `unsubscribe_*`, without
+/// the depth precision.
+fn unsubscribe_message(subscription: &serde_json::Value) -> Option<serde_json::Value> {
+    let action = subscription.get("action")?.as_str()?;
+    let mut unsub = subscription.clone();
+    unsub["action"] = json!(action.replacen("subscribe_", "unsubscribe_", 1));
+    if let Some(fields) = unsub.as_object_mut() {
+        fields.remove("precision");
+    }
+    Some(unsub)
+}
+
+type Sender<T> = mpsc::UnboundedSender<Result<T, O2Error>>;
+
+/// Stream senders indexed by the key their messages are routed on: the market
+/// id for depth and trades, the identity address for account streams. Keys
+/// compare case-insensitively. A stream subscribed with several identities is
+/// registered under each with one id, so it gets every message once.
+struct Routes<T> {
+    by_key: HashMap<String, Vec<(u64, Sender<T>)>>,
+    next_id: u64,
+}
+
+impl<T: Clone> Routes<T> {
+    fn new() -> Self {
+        Self {
+            by_key: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn add<'a>(&mut self, keys: impl IntoIterator<Item = &'a str>, tx: Sender<T>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        for key in keys {
+            self.by_key
+                .entry(key.to_ascii_lowercase())
+                .or_default()
+                .push((id, tx.clone()));
+        }
+    }
+
+    /// Send `item` to the streams registered under `key`.
+    fn send(&self, key: &str, item: &T) {
+        if let Some(senders) = self.by_key.get(&key.to_ascii_lowercase()) {
+            for (_, tx) in senders {
+                let _ = tx.send(Ok(item.clone()));
+            }
+        }
+    }
+
+    /// Every registered stream, once.
+    fn streams(&self) -> BTreeMap<u64, &Sender<T>> {
+        self.by_key
+            .values()
+            .flatten()
+            .map(|(id, tx)| (*id, tx))
+            .collect()
+    }
+
+    /// Split `items` among the streams registered under the keys each item
+    /// names, and send each stream one message built from its share. Items
+    /// naming no key cannot be routed and go to every stream, as does a
+    /// message with no items at all.
+    fn fan_out<I: Clone>(
+        &self,
+        items: Vec<I>,
+        keys_of: impl Fn(&I) -> Vec<&str>,
+        build: impl Fn(Vec<I>) -> T,
+    ) {
+        let mut shares: BTreeMap<u64, (&Sender<T>, Vec<I>)> = BTreeMap::new();
+        if items.is_empty() {
+            shares = self
+                .streams()
+                .into_iter()
+                .map(|(id, tx)| (id, (tx, Vec::new())))
+                .collect();
+        }
+        for item in items {
+            let keys = keys_of(&item);
+            let targets: BTreeMap<u64, &Sender<T>> = if keys.is_empty() {
+                self.streams()
+            } else {
+                keys.iter()
+                    .filter_map(|k| self.by_key.get(&k.to_ascii_lowercase()))
+                    .flatten()
+                    .map(|(id, tx)| (*id, tx))
+                    .collect()
+            };
+            for (id, tx) in targets {
+                shares
+                    .entry(id)
+                    .or_insert((tx, Vec::new()))
+                    .1
+                    .push(item.clone());
+            }
+        }
+        for (tx, share) in shares.into_values() {
+            let _ = tx.send(Ok(build(share)));
+        }
+    }
+
+    fn prune_closed(&mut self) {
+        for senders in self.by_key.values_mut() {
+            senders.retain(|(_, tx)| !tx.is_closed());
+        }
+        self.by_key.retain(|_, senders| !senders.is_empty());
+    }
+
+    fn fail_all(&self, msg: &str) {
+        for tx in self.streams().into_values() {
+            let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.by_key.clear();
+    }
+}
+
+/// Keys an order update is routed on: the account and owner it names.
+fn order_keys(order: &Order) -> Vec<&str> {
+    [&order.account, &order.owner]
+        .into_iter()
+        .flatten()
+        .map(Identity::address_value)
+        .collect()
+}
+
+fn is_subscribe(msg: &serde_json::Value) -> bool {
+    msg.get("action")
+        .and_then(|a| a.as_str())
+        .is_some_and(|a| a.starts_with("subscribe_"))
 }
 
 /// Shared inner state for the WebSocket connection.
 struct WsInner {
     sink: Option<WsSink>,
     subscriptions: Vec<serde_json::Value>,
-    depth_senders: Vec<mpsc::UnboundedSender<Result<DepthUpdate, O2Error>>>,
-    orders_senders: Vec<mpsc::UnboundedSender<Result<OrderUpdate, O2Error>>>,
-    trades_senders: Vec<mpsc::UnboundedSender<Result<TradeUpdate, O2Error>>>,
-    balances_senders: Vec<mpsc::UnboundedSender<Result<BalanceUpdate, O2Error>>>,
-    nonce_senders: Vec<mpsc::UnboundedSender<Result<NonceUpdate, O2Error>>>,
+    /// Live [`SubscriptionHandle`]s of each tracked subscription, by its JSON.
+    holders: HashMap<String, Vec<u64>>,
+    next_holder: u64,
+    /// Messages that could not be sent, flushed on the next connection.
+    pending: Vec<serde_json::Value>,
+    /// Set once the socket is closed for good; nothing is queued after that.
+    closed: bool,
+    depth_senders: Routes<DepthUpdate>,
+    orders_senders: Routes<OrderUpdate>,
+    trades_senders: Routes<TradeUpdate>,
+    balances_senders: Routes<BalanceUpdate>,
+    nonce_senders: Routes<NonceUpdate>,
+    /// Raw subscribers, keyed by the action their messages carry.
+    raw_senders: Vec<(
+        String,
+        mpsc::UnboundedSender<Result<serde_json::Value, O2Error>>,
+    )>,
+    feeds: HashMap<FeedKey, FeedStats>,
+    /// Book of each subscribed depth market, so that a stream joining an
+    /// existing subscription starts from a snapshot.
+    depth_books: Vec<OrderBook>,
+    last_message_at: Instant,
+    recorder: Option<Recorder>,
 }
 
 impl WsInner {
@@ -131,21 +547,107 @@ impl WsInner {
         Self {
             sink: None,
             subscriptions: Vec::new(),
-            depth_senders: Vec::new(),
-            orders_senders: Vec::new(),
-            trades_senders: Vec::new(),
-            balances_senders: Vec::new(),
-            nonce_senders: Vec::new(),
+            holders: HashMap::new(),
+            next_holder: 0,
+            pending: Vec::new(),
+            closed: false,
+            depth_senders: Routes::new(),
+            orders_senders: Routes::new(),
+            trades_senders: Routes::new(),
+            balances_senders: Routes::new(),
+            nonce_senders: Routes::new(),
+            raw_senders: Vec::new(),
+            feeds: HashMap::new(),
+            depth_books: Vec::new(),
+            last_message_at: Instant::now(),
+            recorder: None,
+        }
+    }
+
+    fn track_feed(&mut self, kind: FeedKind, market_id: &str) {
+        self.feeds
+            .entry(feed_key(kind, market_id))
+            .or_insert_with(|| FeedStats::new(kind, market_id.to_string()));
+    }
+
+    fn record_feed_message(&mut self, kind: FeedKind, market_id: &str) {
+        if let Some(stats) = self.feeds.get_mut(&feed_key(kind, market_id)) {
+            stats.record(Instant::now());
+        }
+    }
+
+    fn depth_book(&self, market_id: &str) -> Option<&OrderBook> {
+        self.depth_books
+            .iter()
+            .find(|b| b.market_id().as_str().eq_ignore_ascii_case(market_id))
+    }
+
+    fn remove_depth_book(&mut self, market_id: &str) {
+        self.depth_books
+            .retain(|b| !b.market_id().as_str().eq_ignore_ascii_case(market_id));
+    }
+
+    /// Queue `value` for the next connection, unless an identical message is
+    /// already waiting.
+    fn queue(&mut self, value: serde_json::Value) {
+        if !self.pending.contains(&value) {
+            self.pending.push(value);
+        }
+    }
+
+    /// Send `value`, or queue it if the connection is down. See
+    /// [`O2WebSocket::send_on`].
+    async fn send_or_queue(&mut self, value: serde_json::Value) -> Result<(), O2Error> {
+        let text = serde_json::to_string(&value)?;
+        if self.closed {
+            return Err(O2Error::WebSocketError("Not connected".into()));
+        }
+        if let Some(ref mut sink) = self.sink {
+            if let Some(recorder) = &self.recorder {
+                recorder.record(Direction::Out, &text);
+            }
+            if sink.send(WsMsg::Text(text)).await.is_ok() {
+                return Ok(());
+            }
+            // The reader notices the broken connection and reconnects.
+            self.sink = None;
+        }
+        self.queue(value);
+        Ok(())
+    }
+
+    /// Send queued messages, then every tracked subscription, on a fresh sink.
+    ///
+    /// Queued subscribes are skipped: the replay covers the ones still
+    /// tracked, and the rest were unsubscribed while disconnected. Everything
+    /// else goes first so that a queued unsubscribe cannot undo a replayed
+    /// subscription.
+    async fn flush_on_connect(&mut self) {
+        // The replayed depth subscriptions bring fresh snapshots.
+        for book in &mut self.depth_books {
+            book.clear();
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let subs = self.subscriptions.clone();
+        if let Some(ref mut sink) = self.sink {
+            for msg in pending.iter().filter(|m| !is_subscribe(m)).chain(&subs) {
+                let text = serde_json::to_string(msg).unwrap_or_default();
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(Direction::Out, &text);
+                }
+                let _ = sink.send(WsMsg::Text(text)).await;
+            }
         }
     }
 
     /// Remove closed senders from all sender lists.
     fn prune_closed_senders(&mut self) {
-        self.depth_senders.retain(|s| !s.is_closed());
-        self.orders_senders.retain(|s| !s.is_closed());
-        self.trades_senders.retain(|s| !s.is_closed());
-        self.balances_senders.retain(|s| !s.is_closed());
-        self.nonce_senders.retain(|s| !s.is_closed());
+        self.depth_senders.prune_closed();
+        self.orders_senders.prune_closed();
+        self.trades_senders.prune_closed();
+        self.balances_senders.prune_closed();
+        self.nonce_senders.prune_closed();
+        self.raw_senders.retain(|(_, s)| !s.is_closed());
     }
 
     /// Close all sender channels (signals receivers to terminate).
@@ -155,23 +657,17 @@ impl WsInner {
         self.trades_senders.clear();
         self.balances_senders.clear();
         self.nonce_senders.clear();
+        self.raw_senders.clear();
     }
 
     /// Send disconnect error to all subscribers, then clear.
     fn close_all_senders_with_error(&mut self, msg: &str) {
-        for tx in &self.depth_senders {
-            let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
-        }
-        for tx in &self.orders_senders {
-            let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
-        }
-        for tx in &self.trades_senders {
-            let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
-        }
-        for tx in &self.balances_senders {
-            let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
-        }
-        for tx in &self.nonce_senders {
+        self.depth_senders.fail_all(msg);
+        self.orders_senders.fail_all(msg);
+        self.trades_senders.fail_all(msg);
+        self.balances_senders.fail_all(msg);
+        self.nonce_senders.fail_all(msg);
+        for (_, tx) in &self.raw_senders {
             let _ = tx.send(Err(O2Error::WebSocketDisconnected(msg.to_string())));
         }
         self.close_all_senders();
@@ -182,12 +678,19 @@ impl WsInner {
 ///
 /// Supports auto-reconnect, subscription tracking, heartbeat,
 /// and per-subscription channels for safe concurrent access.
+///
+/// Subscribe and unsubscribe calls made while the connection is down are
+/// queued and sent once it is back, so `stream_*` calls do not fail during a
+/// reconnect.
 pub struct O2WebSocket {
-    url: String,
+    endpoints: Endpoints,
+    /// The URL of the current or most recent connection.
+    url: Arc<std::sync::Mutex<String>>,
     config: WsConfig,
     inner: Arc<Mutex<WsInner>>,
     connected: Arc<AtomicBool>,
     should_run: Arc<AtomicBool>,
+    draining: Arc<AtomicBool>,
     last_pong: Arc<Mutex<Instant>>,
     lifecycle_tx: Arc<broadcast::Sender<WsLifecycleEvent>>,
     reader_handle: Option<tokio::task::JoinHandle<()>>,
@@ -208,12 +711,15 @@ impl O2WebSocket {
         let last_pong = Arc::new(Mutex::new(Instant::now()));
         let lifecycle_tx = Arc::new(broadcast::channel(64).0);
 
+        let endpoints = Endpoints::new(url, &config.fallback_urls, config.failover.clone());
         let mut ws = Self {
-            url: url.to_string(),
+            url: Arc::new(std::sync::Mutex::new(endpoints.active())),
+            endpoints,
             config,
             inner,
             connected,
             should_run,
+            draining: Arc::new(AtomicBool::new(false)),
             last_pong,
             lifecycle_tx,
             reader_handle: None,
@@ -224,8 +730,26 @@ impl O2WebSocket {
         Ok(ws)
     }
 
+    /// The first connect: each endpoint once, in order.
     async fn do_connect(&mut self) -> Result<(), O2Error> {
-        let (ws_stream, _) = tokio_tungstenite::connect_async(&self.url).await?;
+        let mut attempts = self.endpoints.len();
+        let ws_stream = loop {
+            let url = self.endpoints.active();
+            match tokio_tungstenite::connect_async(&url).await {
+                Ok((ws_stream, _)) => {
+                    self.endpoints.record(&url, true);
+                    *self.url.lock().unwrap_or_else(|p| p.into_inner()) = url;
+                    break ws_stream;
+                }
+                Err(e) => {
+                    attempts -= 1;
+                    if attempts == 0 {
+                        return Err(e.into());
+                    }
+                    self.endpoints.skip(&url);
+                }
+            }
+        };
         let (sink, stream) = ws_stream.split();
 
         {
@@ -236,24 +760,16 @@ impl O2WebSocket {
         self.connected.store(true, Ordering::SeqCst);
         *self.last_pong.lock().await = Instant::now();
 
-        // Re-send all tracked subscriptions
-        {
-            let mut guard = self.inner.lock().await;
-            let subs = guard.subscriptions.clone();
-            if let Some(ref mut sink) = guard.sink {
-                // We need to drop guard to send, so collect first
-                for sub in &subs {
-                    let text = serde_json::to_string(sub).unwrap_or_default();
-                    let _ = sink.send(WsMsg::Text(text)).await;
-                }
-            }
-        }
+        // Flush queued messages and re-send all tracked subscriptions
+        self.inner.lock().await.flush_on_connect().await;
 
         // Spawn read loop
         let inner_clone = self.inner.clone();
         let connected_clone = self.connected.clone();
         let should_run_clone = self.should_run.clone();
+        let draining_clone = self.draining.clone();
         let last_pong_clone = self.last_pong.clone();
+        let endpoints_clone = self.endpoints.clone();
         let url_clone = self.url.clone();
         let config_clone = self.config.clone();
         let lifecycle_tx_clone = self.lifecycle_tx.clone();
@@ -268,15 +784,19 @@ impl O2WebSocket {
             )
             .await;
 
-            // If we should still be running, attempt reconnect
-            if should_run_clone.load(Ordering::SeqCst) {
-                connected_clone.store(false, Ordering::SeqCst);
+            // If we should still be running, attempt reconnect. A draining
+            // socket is about to close anyway.
+            connected_clone.store(false, Ordering::SeqCst);
+            inner_clone.lock().await.sink = None;
+            if should_run_clone.load(Ordering::SeqCst) && !draining_clone.load(Ordering::SeqCst) {
                 Self::reconnect_loop(
+                    &endpoints_clone,
                     &url_clone,
                     &config_clone,
                     inner_clone,
                     connected_clone,
                     should_run_clone,
+                    draining_clone,
                     last_pong_clone,
                     lifecycle_tx_clone,
                 )
@@ -309,13 +829,15 @@ impl O2WebSocket {
         Ok(())
     }
 
-    async fn read_loop(
-        mut stream: WsStream,
+    async fn read_loop<S>(
+        mut stream: S,
         inner: Arc<Mutex<WsInner>>,
         connected: Arc<AtomicBool>,
         should_run: Arc<AtomicBool>,
         last_pong: Arc<Mutex<Instant>>,
-    ) {
+    ) where
+        S: Stream<Item = Result<WsMsg, WsError>> + Unpin,
+    {
         while should_run.load(Ordering::SeqCst) {
             let msg = match stream.next().await {
                 Some(Ok(m)) => m,
@@ -326,7 +848,17 @@ impl O2WebSocket {
             match msg {
                 WsMsg::Text(text) => {
                     let text = text.to_string();
-                    let parsed: serde_json::Value = match serde_json::from_str(&text) {
+                    let recorder = inner.lock().await.recorder.clone();
+                    if let Some(recorder) = recorder {
+                        recorder.record(Direction::In, &text);
+                    }
+                    #[cfg(feature = "alloc-audit")]
+                    let (parsed, frame_allocs) = crate::alloc_audit::measure(|| {
+                        serde_json::from_str::<serde_json::Value>(&text)
+                    });
+                    #[cfg(not(feature = "alloc-audit"))]
+                    let parsed = serde_json::from_str::<serde_json::Value>(&text);
+                    let parsed = match parsed {
                         Ok(v) => v,
                         Err(_) => continue,
                     };
@@ -334,42 +866,78 @@ impl O2WebSocket {
                     let action = parsed.get("action").and_then(|a| a.as_str()).unwrap_or("");
 
                     let mut guard = inner.lock().await;
+                    guard.last_message_at = Instant::now();
                     guard.prune_closed_senders();
+                    for (raw_action, tx) in &guard.raw_senders {
+                        if raw_action == action {
+                            let _ = tx.send(Ok(parsed.clone()));
+                        }
+                    }
 
                     match action {
                         "subscribe_depth" | "subscribe_depth_update" => {
-                            if let Ok(update) = serde_json::from_value::<DepthUpdate>(parsed) {
-                                for tx in &guard.depth_senders {
-                                    let _ = tx.send(Ok(update.clone()));
+                            #[cfg(feature = "alloc-audit")]
+                            let update = {
+                                let (update, allocs) = crate::alloc_audit::measure(|| {
+                                    serde_json::from_value::<DepthUpdate>(parsed)
+                                });
+                                crate::alloc_audit::record("depth_update", frame_allocs + allocs);
+                                update
+                            };
+                            #[cfg(not(feature = "alloc-audit"))]
+                            let update = serde_json::from_value::<DepthUpdate>(parsed);
+                            if let Ok(update) = update {
+                                guard.record_feed_message(
+                                    FeedKind::Depth,
+                                    update.market_id.as_str(),
+                                );
+                                for book in &mut guard.depth_books {
+                                    book.apply(&update);
                                 }
+                                guard.depth_senders.send(update.market_id.as_str(), &update);
                             }
                         }
                         "subscribe_orders" => {
-                            if let Ok(update) = serde_json::from_value::<OrderUpdate>(parsed) {
-                                for tx in &guard.orders_senders {
-                                    let _ = tx.send(Ok(update.clone()));
-                                }
+                            if let Ok(mut update) = serde_json::from_value::<OrderUpdate>(parsed) {
+                                let orders = std::mem::take(&mut update.orders);
+                                guard.orders_senders.fan_out(orders, order_keys, |orders| {
+                                    OrderUpdate {
+                                        orders,
+                                        ..update.clone()
+                                    }
+                                });
                             }
                         }
                         "subscribe_trades" => {
                             if let Ok(update) = serde_json::from_value::<TradeUpdate>(parsed) {
-                                for tx in &guard.trades_senders {
-                                    let _ = tx.send(Ok(update.clone()));
-                                }
+                                guard.record_feed_message(
+                                    FeedKind::Trades,
+                                    update.market_id.as_str(),
+                                );
+                                guard
+                                    .trades_senders
+                                    .send(update.market_id.as_str(), &update);
                             }
                         }
                         "subscribe_balances" => {
-                            if let Ok(update) = serde_json::from_value::<BalanceUpdate>(parsed) {
-                                for tx in &guard.balances_senders {
-                                    let _ = tx.send(Ok(update.clone()));
-                                }
+                            if let Ok(mut update) = serde_json::from_value::<BalanceUpdate>(parsed)
+                            {
+                                let entries = std::mem::take(&mut update.balance);
+                                guard.balances_senders.fan_out(
+                                    entries,
+                                    |entry| vec![entry.identity.address_value()],
+                                    |balance| BalanceUpdate {
+                                        balance,
+                                        ..update.clone()
+                                    },
+                                );
                             }
                         }
                         "subscribe_nonce" => {
                             if let Ok(update) = serde_json::from_value::<NonceUpdate>(parsed) {
-                                for tx in &guard.nonce_senders {
-                                    let _ = tx.send(Ok(update.clone()));
-                                }
+                                guard
+                                    .nonce_senders
+                                    .send(update.contract_id.as_str(), &update);
                             }
                         }
                         _ => {}
@@ -432,12 +1000,15 @@ impl O2WebSocket {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn reconnect_loop(
-        url: &str,
+        endpoints: &Endpoints,
+        connected_url: &std::sync::Mutex<String>,
         config: &WsConfig,
         inner: Arc<Mutex<WsInner>>,
         connected: Arc<AtomicBool>,
         should_run: Arc<AtomicBool>,
+        draining: Arc<AtomicBool>,
         last_pong: Arc<Mutex<Instant>>,
         lifecycle_tx: Arc<broadcast::Sender<WsLifecycleEvent>>,
     ) {
@@ -449,6 +1020,8 @@ impl O2WebSocket {
                 // Max attempts reached — signal all subscribers and stop
                 should_run.store(false, Ordering::SeqCst);
                 let mut guard = inner.lock().await;
+                guard.closed = true;
+                guard.pending.clear();
                 let reason = "Connection lost after max retries".to_string();
                 guard.close_all_senders_with_error(&reason);
                 let _ = lifecycle_tx.send(WsLifecycleEvent::Disconnected {
@@ -465,9 +1038,19 @@ impl O2WebSocket {
             tokio::time::sleep(delay).await;
             attempts += 1;
 
-            match tokio_tungstenite::connect_async(url).await {
+            let url = endpoints.active();
+            let result = tokio_tungstenite::connect_async(&url).await;
+            endpoints.record(&url, result.is_ok());
+            match result {
                 Ok((ws_stream, _)) => {
                     let (sink, stream) = ws_stream.split();
+                    let from = std::mem::replace(
+                        &mut *connected_url.lock().unwrap_or_else(|p| p.into_inner()),
+                        url.clone(),
+                    );
+                    if from != url {
+                        let _ = lifecycle_tx.send(WsLifecycleEvent::FailedOver { from, to: url });
+                    }
 
                     {
                         let mut guard = inner.lock().await;
@@ -477,17 +1060,9 @@ impl O2WebSocket {
                     connected.store(true, Ordering::SeqCst);
                     *last_pong.lock().await = Instant::now();
 
-                    // Re-send all tracked subscriptions and signal reconnect
-                    {
-                        let mut guard = inner.lock().await;
-                        let subs = guard.subscriptions.clone();
-                        if let Some(ref mut sink) = guard.sink {
-                            for sub in &subs {
-                                let text = serde_json::to_string(sub).unwrap_or_default();
-                                let _ = sink.send(WsMsg::Text(text)).await;
-                            }
-                        }
-                    }
+                    // Flush queued messages, re-send all tracked subscriptions
+                    // and signal reconnect
+                    inner.lock().await.flush_on_connect().await;
                     let _ = lifecycle_tx.send(WsLifecycleEvent::Reconnected { attempts });
 
                     // Spawn new read loop (recursive via reconnect)
@@ -501,8 +1076,9 @@ impl O2WebSocket {
                     .await;
 
                     // If read loop exited and we should still run, reset delay and retry
-                    if should_run.load(Ordering::SeqCst) {
-                        connected.store(false, Ordering::SeqCst);
+                    connected.store(false, Ordering::SeqCst);
+                    inner.lock().await.sink = None;
+                    if should_run.load(Ordering::SeqCst) && !draining.load(Ordering::SeqCst) {
                         delay = config.base_delay;
                         attempts = 0;
                         continue;
@@ -521,22 +1097,129 @@ impl O2WebSocket {
         self.lifecycle_tx.subscribe()
     }
 
+    /// Tee every text frame sent and received, across reconnects, to
+    /// `recorder`. Replaces any earlier recorder. See [`crate::recorder`].
+    pub async fn record(&self, recorder: Recorder) {
+        self.inner.lock().await.recorder = Some(recorder);
+    }
+
+    /// Stop recording frames.
+    pub async fn stop_recording(&self) {
+        self.inner.lock().await.recorder = None;
+    }
+
+    /// A client with no connection, for feeding recorded frames through
+    /// [`replay`](Self::replay). Subscriptions are tracked but never sent.
+    pub(crate) fn offline() -> Self {
+        Self {
+            endpoints: Endpoints::new("", &[], FailoverPolicy::default()),
+            url: Arc::new(std::sync::Mutex::new(String::new())),
+            config: WsConfig::default(),
+            inner: Arc::new(Mutex::new(WsInner::new())),
+            connected: Arc::new(AtomicBool::new(false)),
+            should_run: Arc::new(AtomicBool::new(true)),
+            draining: Arc::new(AtomicBool::new(false)),
+            last_pong: Arc::new(Mutex::new(Instant::now())),
+            lifecycle_tx: Arc::new(broadcast::channel(64).0),
+            reader_handle: None,
+            ping_handle: None,
+        }
+    }
+
+    /// Route `frames` to the streams as if they had been received. The
+    /// client counts as connected until they run out; then every stream
+    /// ends and the client is terminated.
+    pub(crate) fn replay<S>(&mut self, frames: S)
+    where
+        S: Stream<Item = Result<WsMsg, WsError>> + Unpin + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let connected = self.connected.clone();
+        let should_run = self.should_run.clone();
+        let last_pong = self.last_pong.clone();
+        connected.store(true, Ordering::SeqCst);
+        self.reader_handle = Some(tokio::spawn(async move {
+            Self::read_loop(
+                frames,
+                inner.clone(),
+                connected.clone(),
+                should_run.clone(),
+                last_pong,
+            )
+            .await;
+            connected.store(false, Ordering::SeqCst);
+            should_run.store(false, Ordering::SeqCst);
+            inner.lock().await.close_all_senders();
+        }));
+    }
+
     async fn send_json(&self, value: serde_json::Value) -> Result<(), O2Error> {
-        let text = serde_json::to_string(&value)?;
-        let mut guard = self.inner.lock().await;
-        if let Some(ref mut sink) = guard.sink {
-            sink.send(WsMsg::Text(text))
-                .await
-                .map_err(|e| O2Error::WebSocketError(e.to_string()))
-        } else {
-            Err(O2Error::WebSocketError("Not connected".into()))
+        Self::send_on(&self.inner, value).await
+    }
+
+    /// Send `value`, or queue it if the connection is down (including a send
+    /// that fails mid-reconnect). Queued messages are flushed once the
+    /// connection is re-established. Fails only once the socket is closed for
+    /// good.
+    async fn send_on(inner: &Mutex<WsInner>, value: serde_json::Value) -> Result<(), O2Error> {
+        inner.lock().await.send_or_queue(value).await
+    }
+
+    fn check_accepting(&self) -> Result<(), O2Error> {
+        if self.draining.load(Ordering::SeqCst) {
+            return Err(O2Error::SubscriptionError(
+                "WebSocket is draining and not accepting new subscriptions".into(),
+            ));
         }
+        Ok(())
     }
 
-    fn add_subscription(inner: &mut WsInner, sub: serde_json::Value) {
-        if !inner.subscriptions.contains(&sub) {
+    /// Track `sub` and register a new holder of it. Returns the holder's id
+    /// and whether `sub` was already tracked, in which case the server
+    /// subscription is shared and must not be sent again.
+    fn add_subscription(inner: &mut WsInner, sub: serde_json::Value) -> (u64, bool) {
+        let holder = inner.next_holder;
+        inner.next_holder += 1;
+        let key = sub.to_string();
+        let shared = inner.subscriptions.contains(&sub);
+        if shared {
+            inner.holders.entry(key).or_default().push(holder);
+        } else {
+            // Holders of an earlier, since unsubscribed copy no longer count.
+            inner.holders.insert(key, vec![holder]);
             inner.subscriptions.push(sub);
         }
+        (holder, shared)
+    }
+
+    /// Send `sub` unless the server already has it, then wrap `rx`.
+    async fn finish_subscribe<T>(
+        &self,
+        rx: mpsc::UnboundedReceiver<Result<T, O2Error>>,
+        sub: serde_json::Value,
+        (holder, shared): (u64, bool),
+    ) -> Result<TypedStream<T>, O2Error> {
+        if !shared {
+            self.send_json(sub.clone()).await?;
+        }
+        Ok(self.subscribed_stream(rx, sub, holder))
+    }
+
+    fn subscribed_stream<T>(
+        &self,
+        rx: mpsc::UnboundedReceiver<Result<T, O2Error>>,
+        subscription: serde_json::Value,
+        holder: u64,
+    ) -> TypedStream<T> {
+        TypedStream {
+            rx,
+            handle: Some(SubscriptionHandle {
+                inner: Arc::downgrade(&self.inner),
+                subscription,
+                holder,
+                released: false,
+            }),
+        }
     }
 
     /// Check if the WebSocket is currently connected.
@@ -544,6 +1227,24 @@ impl O2WebSocket {
         self.connected.load(Ordering::SeqCst)
     }
 
+    /// The URL of the current connection, or of the last one while
+    /// reconnecting: the one passed to `connect` or one of
+    /// [`WsConfig::fallback_urls`].
+    pub fn active_url(&self) -> String {
+        self.url.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// Health of the URL passed to `connect` and each of its fallbacks, in
+    /// order. The [`active_url`](Self::active_url) is the one marked active.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        let active = self.active_url();
+        let mut health = self.endpoints.health();
+        for endpoint in &mut health {
+            endpoint.active = endpoint.url == active;
+        }
+        health
+    }
+
     /// Subscribe to order book depth. Returns a stream of `Result<DepthUpdate, O2Error>`.
     ///
     /// `precision` is a level index as a string (e.g. `"1"` for finest).
@@ -558,6 +1259,7 @@ impl O2WebSocket {
         market_id: &str,
         precision: &DepthPrecision,
     ) -> Result<TypedStream<DepthUpdate>, O2Error> {
+        self.check_accepting()?;
         let (tx, rx) = mpsc::unbounded_channel();
         let sub = json!({
             "action": "subscribe_depth",
@@ -565,14 +1267,32 @@ impl O2WebSocket {
             "precision": precision.as_str()
         });
 
-        {
+        let added = {
             let mut guard = self.inner.lock().await;
-            guard.depth_senders.push(tx);
-            Self::add_subscription(&mut guard, sub.clone());
-        }
+            let added = Self::add_subscription(&mut guard, sub.clone());
+            match guard.depth_book(market_id) {
+                // Joining: the server will not send this stream a snapshot.
+                Some(book) if added.1 && book.is_synced() => {
+                    let _ = tx.send(Ok(DepthUpdate {
+                        action: "subscribe_depth".into(),
+                        changes: None,
+                        view: Some(book.to_snapshot(usize::MAX)),
+                        market_id: book.market_id().clone(),
+                        onchain_timestamp: book.last_onchain_timestamp(),
+                        seen_timestamp: None,
+                    }));
+                }
+                Some(_) => {}
+                None => guard
+                    .depth_books
+                    .push(OrderBook::new(MarketId::new(market_id))),
+            }
+            guard.depth_senders.add([market_id], tx);
+            guard.track_feed(FeedKind::Depth, market_id);
+            added
+        };
 
-        self.send_json(sub).await?;
-        Ok(TypedStream { rx })
+        self.finish_subscribe(rx, sub, added).await
     }
 
     /// Subscribe to order updates. Returns a stream of `Result<OrderUpdate, O2Error>`.
@@ -580,20 +1300,22 @@ impl O2WebSocket {
         &self,
         identities: &[Identity],
     ) -> Result<TypedStream<OrderUpdate>, O2Error> {
+        self.check_accepting()?;
         let (tx, rx) = mpsc::unbounded_channel();
         let sub = json!({
             "action": "subscribe_orders",
             "identities": identities
         });
 
-        {
+        let added = {
             let mut guard = self.inner.lock().await;
-            guard.orders_senders.push(tx);
-            Self::add_subscription(&mut guard, sub.clone());
-        }
+            guard
+                .orders_senders
+                .add(identities.iter().map(Identity::address_value), tx);
+            Self::add_subscription(&mut guard, sub.clone())
+        };
 
-        self.send_json(sub).await?;
-        Ok(TypedStream { rx })
+        self.finish_subscribe(rx, sub, added).await
     }
 
     /// Subscribe to trades. Returns a stream of `Result<TradeUpdate, O2Error>`.
@@ -601,20 +1323,21 @@ impl O2WebSocket {
         &self,
         market_id: &str,
     ) -> Result<TypedStream<TradeUpdate>, O2Error> {
+        self.check_accepting()?;
         let (tx, rx) = mpsc::unbounded_channel();
         let sub = json!({
             "action": "subscribe_trades",
             "market_id": market_id
         });
 
-        {
+        let added = {
             let mut guard = self.inner.lock().await;
-            guard.trades_senders.push(tx);
-            Self::add_subscription(&mut guard, sub.clone());
-        }
+            guard.trades_senders.add([market_id], tx);
+            guard.track_feed(FeedKind::Trades, market_id);
+            Self::add_subscription(&mut guard, sub.clone())
+        };
 
-        self.send_json(sub).await?;
-        Ok(TypedStream { rx })
+        self.finish_subscribe(rx, sub, added).await
     }
 
     /// Subscribe to balance updates. Returns a stream of `Result<BalanceUpdate, O2Error>`.
@@ -622,20 +1345,22 @@ impl O2WebSocket {
         &self,
         identities: &[Identity],
     ) -> Result<TypedStream<BalanceUpdate>, O2Error> {
+        self.check_accepting()?;
         let (tx, rx) = mpsc::unbounded_channel();
         let sub = json!({
             "action": "subscribe_balances",
             "identities": identities
         });
 
-        {
+        let added = {
             let mut guard = self.inner.lock().await;
-            guard.balances_senders.push(tx);
-            Self::add_subscription(&mut guard, sub.clone());
-        }
+            guard
+                .balances_senders
+                .add(identities.iter().map(Identity::address_value), tx);
+            Self::add_subscription(&mut guard, sub.clone())
+        };
 
-        self.send_json(sub).await?;
-        Ok(TypedStream { rx })
+        self.finish_subscribe(rx, sub, added).await
     }
 
     /// Subscribe to nonce updates. Returns a stream of `Result<NonceUpdate, O2Error>`.
@@ -643,20 +1368,67 @@ impl O2WebSocket {
         &self,
         identities: &[Identity],
     ) -> Result<TypedStream<NonceUpdate>, O2Error> {
+        self.check_accepting()?;
         let (tx, rx) = mpsc::unbounded_channel();
         let sub = json!({
             "action": "subscribe_nonce",
             "identities": identities
         });
 
-        {
+        let added = {
             let mut guard = self.inner.lock().await;
-            guard.nonce_senders.push(tx);
-            Self::add_subscription(&mut guard, sub.clone());
-        }
+            guard
+                .nonce_senders
+                .add(identities.iter().map(Identity::address_value), tx);
+            Self::add_subscription(&mut guard, sub.clone())
+        };
 
-        self.send_json(sub).await?;
-        Ok(TypedStream { rx })
+        self.finish_subscribe(rx, sub, added).await
+    }
+
+    /// Subscribe with a message of your own, for channels the SDK has no
+    /// typed wrapper for yet. Returns every message whose `action` matches
+    /// the subscription's, as raw JSON.
+    ///
+    /// `subscription` must be an object with a `subscribe_*` action, e.g.
+    /// `{"action": "subscribe_ticker", "market_id": "0x.."}`. Like the typed
+    /// streams, it is tracked and re-sent after a reconnect.
+    pub async fn subscribe_raw(
+        &self,
+        subscription: serde_json::Value,
+    ) -> Result<TypedStream<serde_json::Value>, O2Error> {
+        self.check_accepting()?;
+        let action = raw_action(&subscription)?.to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let added = {
+            let mut guard = self.inner.lock().await;
+            guard.raw_senders.push((action, tx));
+            Self::add_subscription(&mut guard, subscription.clone())
+        };
+
+        self.finish_subscribe(rx, subscription, added).await
+    }
+
+    /// Undo [`O2WebSocket::subscribe_raw`]: send `subscription` with its
+    /// action renamed to `unsubscribe_*` and stop re-sending it on reconnect.
+    ///
+    /// Streams for the same action end once no tracked subscription uses it.
+    pub async fn unsubscribe_raw(&self, subscription: &serde_json::Value) -> Result<(), O2Error> {
+        let action = raw_action(subscription)?.to_string();
+        let mut unsub = subscription.clone();
+        unsub["action"] = json!(action.replacen("subscribe_", "unsubscribe_", 1));
+        self.send_json(unsub).await?;
+        let mut guard = self.inner.lock().await;
+        guard.subscriptions.retain(|s| s != subscription);
+        let still_used = guard
+            .subscriptions
+            .iter()
+            .any(|s| s.get("action").and_then(|a| a.as_str()) == Some(action.as_str()));
+        if !still_used {
+            guard.raw_senders.retain(|(a, _)| a != &action);
+        }
+        Ok(())
     }
 
     /// Unsubscribe from depth updates.
@@ -671,6 +1443,8 @@ impl O2WebSocket {
             !(s.get("action").and_then(|a| a.as_str()) == Some("subscribe_depth")
                 && s.get("market_id").and_then(|m| m.as_str()) == Some(market_id))
         });
+        guard.feeds.remove(&feed_key(FeedKind::Depth, market_id));
+        guard.remove_depth_book(market_id);
         Ok(())
     }
 
@@ -701,6 +1475,7 @@ impl O2WebSocket {
             !(s.get("action").and_then(|a| a.as_str()) == Some("subscribe_trades")
                 && s.get("market_id").and_then(|m| m.as_str()) == Some(market_id))
         });
+        guard.feeds.remove(&feed_key(FeedKind::Trades, market_id));
         Ok(())
     }
 
@@ -736,19 +1511,160 @@ impl O2WebSocket {
         Ok(())
     }
 
+    /// Every tracked server subscription, with the number of streams sharing
+    /// it. Subscribing again with an identical message joins the existing
+    /// subscription instead of sending another.
+    pub async fn active_streams(&self) -> Vec<ActiveStream> {
+        let guard = self.inner.lock().await;
+        guard
+            .subscriptions
+            .iter()
+            .map(|sub| ActiveStream {
+                subscription: sub.clone(),
+                streams: guard.holders.get(&sub.to_string()).map_or(0, Vec::len),
+            })
+            .collect()
+    }
+
+    /// Message-gap statistics for every depth and trades subscription.
+    pub async fn feed_stats(&self) -> Vec<FeedStats> {
+        self.feed_handle().stats().await
+    }
+
+    /// Re-send the subscription for one feed without touching the others.
+    ///
+    /// Useful when a feed has stalled while the socket itself is healthy.
+    pub async fn resubscribe_feed(&self, kind: FeedKind, market_id: &str) -> Result<(), O2Error> {
+        self.feed_handle().resubscribe(kind, market_id).await
+    }
+
+    /// Watch depth and trades feeds for stalls. See [`crate::feed_monitor`].
+    ///
+    /// `api` is used to confirm market activity over REST. Must be called from
+    /// within a Tokio runtime.
+    pub fn monitor_feeds(
+        &self,
+        api: crate::api::O2Api,
+        config: crate::feed_monitor::FeedMonitorConfig,
+    ) -> crate::feed_monitor::FeedMonitor {
+        crate::feed_monitor::FeedMonitor::spawn(self.feed_handle(), api, config)
+    }
+
+    pub(crate) fn feed_handle(&self) -> FeedHandle {
+        FeedHandle {
+            inner: self.inner.clone(),
+            lifecycle_tx: self.lifecycle_tx.clone(),
+            should_run: self.should_run.clone(),
+        }
+    }
+
+    pub(crate) fn drain_handle(&self) -> DrainHandle {
+        DrainHandle {
+            inner: self.inner.clone(),
+            connected: self.connected.clone(),
+            should_run: self.should_run.clone(),
+            draining: self.draining.clone(),
+            lifecycle_tx: self.lifecycle_tx.clone(),
+        }
+    }
+
     /// Check if the WebSocket has been permanently terminated
     /// (max reconnect attempts exhausted or explicitly stopped).
     pub fn is_terminated(&self) -> bool {
         !self.should_run.load(Ordering::SeqCst)
     }
 
+    /// Check if the WebSocket is draining or has drained. See [`O2WebSocket::drain`].
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Drain the connection ahead of a planned shutdown, such as a rolling deploy.
+    ///
+    /// Emits [`WsLifecycleEvent::Draining`], rejects new `stream_*` calls with
+    /// [`O2Error::SubscriptionError`], and unsubscribes every tracked
+    /// subscription. Messages already in flight are still delivered; once the
+    /// socket has been quiet for a short while, or `deadline` has passed, it is
+    /// closed as by [`O2WebSocket::disconnect`]. Open streams end after their
+    /// buffered messages. A dropped connection is not re-established while
+    /// draining.
+    pub async fn drain(&self, deadline: Duration) -> Result<(), O2Error> {
+        self.drain_handle().drain(deadline).await
+    }
+
     /// Close the WebSocket connection and stop all tasks.
     pub async fn disconnect(&self) -> Result<(), O2Error> {
+        self.drain_handle().close("Explicit disconnect").await;
+        Ok(())
+    }
+}
+
+/// How long a draining socket must go without messages before it is closed early.
+const DRAIN_QUIET: Duration = Duration::from_millis(500);
+
+const DRAIN_POLL: Duration = Duration::from_millis(50);
+
+/// Shared access to the connection for draining and closing it without
+/// holding the [`O2WebSocket`] itself.
+#[derive(Clone)]
+pub(crate) struct DrainHandle {
+    inner: Arc<Mutex<WsInner>>,
+    connected: Arc<AtomicBool>,
+    should_run: Arc<AtomicBool>,
+    draining: Arc<AtomicBool>,
+    lifecycle_tx: Arc<broadcast::Sender<WsLifecycleEvent>>,
+}
+
+impl DrainHandle {
+    pub(crate) async fn drain(&self, deadline: Duration) -> Result<(), O2Error> {
+        if !self.should_run.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return Err(O2Error::WebSocketError(
+                "WebSocket is already draining".into(),
+            ));
+        }
+        let started = Instant::now();
+        let deadline = started + deadline;
+        let _ = self
+            .lifecycle_tx
+            .send(WsLifecycleEvent::Draining { deadline });
+
+        let subscriptions = self.inner.lock().await.subscriptions.clone();
+        for sub in subscriptions {
+            let Some(unsub) = unsubscribe_message(&sub) else {
+                continue;
+            };
+            // The socket closes shortly anyway; a failed unsubscribe only
+            // means more messages to flush.
+            let _ = O2WebSocket::send_on(&self.inner, unsub).await;
+        }
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline || !self.connected.load(Ordering::SeqCst) {
+                break;
+            }
+            let last = self.inner.lock().await.last_message_at.max(started);
+            if now.saturating_duration_since(last) >= DRAIN_QUIET {
+                break;
+            }
+            tokio::time::sleep(DRAIN_POLL.min(deadline - now)).await;
+        }
+
+        self.close("Drained").await;
+        Ok(())
+    }
+
+    pub(crate) async fn close(&self, reason: &str) {
         self.should_run.store(false, Ordering::SeqCst);
         self.connected.store(false, Ordering::SeqCst);
 
         // Send close frame
         let mut guard = self.inner.lock().await;
+        guard.closed = true;
+        guard.pending.clear();
         if let Some(ref mut sink) = guard.sink {
             let _ = sink.send(WsMsg::Close(None)).await;
         }
@@ -756,13 +1672,66 @@ impl O2WebSocket {
         // Emit lifecycle event BEFORE closing data channels, so consumers
         // see "Disconnected" before their data streams terminate.
         let _ = self.lifecycle_tx.send(WsLifecycleEvent::Disconnected {
-            reason: "Explicit disconnect".to_string(),
+            reason: reason.to_string(),
             final_: true,
         });
 
         // Close all sender channels
         guard.close_all_senders();
+    }
+}
+
+/// Shared access to feed tracking for background monitors.
+#[derive(Clone)]
+pub(crate) struct FeedHandle {
+    inner: Arc<Mutex<WsInner>>,
+    lifecycle_tx: Arc<broadcast::Sender<WsLifecycleEvent>>,
+    should_run: Arc<AtomicBool>,
+}
 
+impl FeedHandle {
+    pub(crate) async fn stats(&self) -> Vec<FeedStats> {
+        self.inner.lock().await.feeds.values().cloned().collect()
+    }
+
+    pub(crate) fn is_terminated(&self) -> bool {
+        !self.should_run.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn emit(&self, event: WsLifecycleEvent) {
+        let _ = self.lifecycle_tx.send(event);
+    }
+
+    pub(crate) async fn resubscribe(&self, kind: FeedKind, market_id: &str) -> Result<(), O2Error> {
+        let subs: Vec<serde_json::Value> = {
+            let guard = self.inner.lock().await;
+            guard
+                .subscriptions
+                .iter()
+                .filter(|s| {
+                    s.get("action").and_then(|a| a.as_str()) == Some(kind.subscribe_action())
+                        && s.get("market_id")
+                            .and_then(|m| m.as_str())
+                            .is_some_and(|m| m.eq_ignore_ascii_case(market_id))
+                })
+                .cloned()
+                .collect()
+        };
+        if subs.is_empty() {
+            return Err(O2Error::SubscriptionError(format!(
+                "No {} subscription for market {}",
+                kind.subscribe_action(),
+                market_id
+            )));
+        }
+        O2WebSocket::send_on(
+            &self.inner,
+            json!({ "action": kind.unsubscribe_action(), "market_id": market_id }),
+        )
+        .await?;
+        for sub in subs {
+            O2WebSocket::send_on(&self.inner, sub).await?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+#![cfg(feature = "alloc-audit")]
+//! Tests for the allocation audit counters. Run with `--features alloc-audit`.
+
+use o2_sdk::alloc_audit::{self, CountingAllocator};
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_measure_counts_this_threads_allocations() {
+    let (v, stats) = alloc_audit::measure(|| vec![1u64; 16]);
+    assert_eq!(v.len(), 16);
+    assert_eq!(stats.allocs, 1);
+    assert_eq!(stats.bytes, 128);
+
+    let ((), stats) = alloc_audit::measure(|| {});
+    assert_eq!(stats.allocs, 0);
+}
+
+#[tokio::test]
+async fn test_track_future_records_site() {
+    for n in 1..=3 {
+        alloc_audit::track_future("test_future", async move {
+            tokio::task::yield_now().await;
+            (0..n).map(|i| vec![i; 4]).collect::<Vec<_>>()
+        })
+        .await;
+    }
+    let report = alloc_audit::site_report("test_future").unwrap();
+    assert_eq!(report.calls, 3);
+    // One outer Vec plus one per element.
+    assert_eq!(report.max_allocs, 4);
+    assert_eq!(report.last.allocs, 4);
+    alloc_audit::assert_max_allocs("test_future", 4);
+}
+
+#[test]
+#[should_panic(expected = "test_over made up to 2 allocations per call (limit 1")]
+fn test_assert_max_allocs_fails_over_budget() {
+    alloc_audit::track("test_over", || (Box::new(1u8), Box::new(2u8)));
+    alloc_audit::assert_max_allocs("test_over", 1);
+}
+
+#[test]
+#[should_panic(expected = "was never called")]
+fn test_assert_max_allocs_fails_for_unknown_site() {
+    alloc_audit::assert_max_allocs("test_never", 100);
+}
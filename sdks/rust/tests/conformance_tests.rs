@@ -0,0 +1,82 @@
+#![cfg(feature = "low-level")]
+/// Cross-SDK conformance tests.
+///
+/// Runs the shared golden vectors in `conformance/vectors/` at the repository
+/// root against this SDK's encoders and signers.
+use std::path::PathBuf;
+
+use o2_sdk::conformance::{self, VectorInput};
+
+fn vectors_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../conformance/vectors")
+}
+
+#[test]
+fn test_shared_vectors_pass() {
+    let vectors = conformance::load(vectors_dir()).unwrap();
+    let report = conformance::run(&vectors);
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.passed(), vectors.len());
+}
+
+#[test]
+fn test_shared_vectors_cover_every_payload() {
+    let vectors = conformance::load(vectors_dir()).unwrap();
+    let count = |f: fn(&VectorInput) -> bool| vectors.iter().filter(|v| f(&v.input)).count();
+    assert!(count(|i| matches!(i, VectorInput::Session { .. })) > 0);
+    assert!(count(|i| matches!(i, VectorInput::Actions { .. })) > 0);
+    assert!(count(|i| matches!(i, VectorInput::Withdraw { .. })) > 0);
+    assert!(vectors
+        .iter()
+        .all(|v| v.signer.is_none() || v.expected.signature.is_some()));
+}
+
+#[test]
+fn test_mismatch_is_reported() {
+    let mut vectors = conformance::load(vectors_dir().join("withdraw.json")).unwrap();
+    let vector = &mut vectors[0];
+    // Flip the last byte of the expected amount.
+    let bytes = &mut vector.expected.signing_bytes;
+    let last = bytes.pop().unwrap();
+    bytes.push(if last == '0' { '1' } else { '0' });
+
+    let report = conformance::run(&vectors[..1]);
+    assert!(!report.is_success());
+    let failure = report.failures().next().unwrap();
+    assert_eq!(failure.name, vectors[0].name);
+    assert!(failure
+        .failure
+        .as_deref()
+        .unwrap()
+        .contains("signing bytes differ at byte"));
+}
+
+#[test]
+fn test_numbers_accepted_as_json_numbers() {
+    let json = r#"{
+        "version": 1,
+        "vectors": [{
+            "name": "withdraw/numeric",
+            "input": {
+                "kind": "withdraw", "nonce": 1, "chain_id": 0,
+                "to": {"Address": "0x0000000000000000000000000000000000000000000000000000000000000001"},
+                "asset_id": "0x0000000000000000000000000000000000000000000000000000000000000002",
+                "amount": "0x10"
+            },
+            "expected": {
+                "signing_bytes": "0x00000000000000010000000000000000000000000000000877697468647261770000000000000000000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000020000000000000010"
+            }
+        }]
+    }"#;
+    let file = conformance::from_json(json).unwrap();
+    let report = conformance::run(&file.vectors);
+    assert!(report.is_success(), "{report}");
+}
+
+#[test]
+fn test_future_format_version_rejected() {
+    let err = conformance::from_json(r#"{"version": 99, "vectors": []}"#).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Unsupported conformance vector version"));
+}
@@ -7,7 +7,7 @@ use sha2::{Digest, Sha256};
 #[test]
 fn test_generate_keypair() {
     let wallet = generate_keypair().unwrap();
-    assert_eq!(wallet.private_key.len(), 32);
+    assert_eq!(wallet.private_key.expose_secret().len(), 32);
     assert_eq!(wallet.public_key.len(), 65);
     assert_eq!(wallet.public_key[0], 0x04); // uncompressed prefix
     assert_eq!(wallet.b256_address.len(), 32);
@@ -20,7 +20,7 @@ fn test_generate_keypair() {
 #[test]
 fn test_generate_evm_keypair() {
     let wallet = generate_evm_keypair().unwrap();
-    assert_eq!(wallet.private_key.len(), 32);
+    assert_eq!(wallet.private_key.expose_secret().len(), 32);
     assert_eq!(wallet.public_key.len(), 65);
     assert_eq!(wallet.evm_address.len(), 20);
     assert_eq!(wallet.b256_address.len(), 32);
@@ -46,7 +46,7 @@ fn test_fuel_compact_sign_64_bytes() {
     let digest = Sha256::digest(b"test message");
     let digest_arr: [u8; 32] = digest.into();
 
-    let sig = fuel_compact_sign(&wallet.private_key, &digest_arr).unwrap();
+    let sig = fuel_compact_sign(wallet.private_key.expose_secret(), &digest_arr).unwrap();
     assert_eq!(sig.len(), 64);
 }
 
@@ -57,7 +57,7 @@ fn test_recovery_id_in_msb() {
 
     for i in 0u8..20 {
         let digest: [u8; 32] = Sha256::digest([i]).into();
-        let sig = fuel_compact_sign(&wallet.private_key, &digest).unwrap();
+        let sig = fuel_compact_sign(wallet.private_key.expose_secret(), &digest).unwrap();
 
         // MSB of byte 32 should be 0 or 1 (recovery ID)
         let recovery_id = (sig[32] >> 7) & 1;
@@ -76,8 +76,8 @@ fn test_personal_sign_deterministic() {
     let wallet = load_wallet(&private_key).unwrap();
 
     let message = b"hello world";
-    let sig1 = personal_sign(&wallet.private_key, message).unwrap();
-    let sig2 = personal_sign(&wallet.private_key, message).unwrap();
+    let sig1 = personal_sign(wallet.private_key.expose_secret(), message).unwrap();
+    let sig2 = personal_sign(wallet.private_key.expose_secret(), message).unwrap();
 
     assert_eq!(sig1, sig2, "personalSign should be deterministic");
     assert_eq!(sig1.len(), 64);
@@ -89,8 +89,8 @@ fn test_raw_sign_deterministic() {
     let wallet = load_wallet(&private_key).unwrap();
 
     let message = b"hello world";
-    let sig1 = raw_sign(&wallet.private_key, message).unwrap();
-    let sig2 = raw_sign(&wallet.private_key, message).unwrap();
+    let sig1 = raw_sign(wallet.private_key.expose_secret(), message).unwrap();
+    let sig2 = raw_sign(wallet.private_key.expose_secret(), message).unwrap();
 
     assert_eq!(sig1, sig2, "rawSign should be deterministic");
     assert_eq!(sig1.len(), 64);
@@ -181,7 +181,7 @@ fn test_low_s_normalization() {
 
     for i in 0u8..50 {
         let digest: [u8; 32] = Sha256::digest([i]).into();
-        let sig = fuel_compact_sign(&wallet.private_key, &digest).unwrap();
+        let sig = fuel_compact_sign(wallet.private_key.expose_secret(), &digest).unwrap();
 
         // Extract s (bytes 32..64) with recovery ID cleared
         let mut s = [0u8; 32];
@@ -206,3 +206,148 @@ fn test_low_s_normalization() {
         );
     }
 }
+
+// ---------------------------------------------------------------------------
+// Mnemonics
+// ---------------------------------------------------------------------------
+
+use o2_sdk::mnemonic::*;
+
+#[test]
+fn test_bip39_trezor_vector() {
+    let phrase = entropy_to_mnemonic(&[0u8; 16]).unwrap();
+    assert_eq!(
+        phrase,
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+    );
+    let seed = mnemonic_to_seed(&phrase, "TREZOR").unwrap();
+    assert_eq!(
+        hex::encode(seed),
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+    );
+    assert_eq!(mnemonic_to_entropy(&phrase).unwrap(), vec![0u8; 16]);
+}
+
+#[test]
+fn test_bip32_derivation_vector() {
+    // BIP-32 test vector 1.
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    assert_eq!(
+        hex::encode(derive_private_key(&seed, "m").unwrap()),
+        "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+    );
+    assert_eq!(
+        hex::encode(derive_private_key(&seed, "m/0'").unwrap()),
+        "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+    );
+    assert_eq!(
+        hex::encode(derive_private_key(&seed, "m/0'/1/2'/2/1000000000").unwrap()),
+        "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"
+    );
+}
+
+#[test]
+fn test_evm_wallet_from_mnemonic_matches_browser_wallets() {
+    // The well-known development mnemonic used by Hardhat and Anvil.
+    let phrase = "test test test test test test test test test test test junk";
+    let wallet = EvmWallet::from_mnemonic(phrase, EVM_DERIVATION_PATH).unwrap();
+    assert_eq!(
+        hex::encode(wallet.private_key.expose_secret()),
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+    );
+    assert_eq!(
+        hex::encode(wallet.evm_address),
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+    );
+    let second = EvmWallet::from_mnemonic(phrase, &evm_derivation_path(1)).unwrap();
+    assert_eq!(
+        hex::encode(second.evm_address),
+        "70997970c51812dc3a010c7d01b50e0d17dc79c8"
+    );
+}
+
+#[test]
+fn test_fuel_wallet_from_generated_mnemonic() {
+    let phrase = generate_mnemonic(24).unwrap();
+    assert_eq!(phrase.split(' ').count(), 24);
+    let a = Wallet::from_mnemonic(&phrase, FUEL_DERIVATION_PATH).unwrap();
+    let b = Wallet::from_mnemonic(&phrase.to_uppercase(), &fuel_derivation_path(0)).unwrap();
+    assert_eq!(a.b256_address, b.b256_address);
+    let other = Wallet::from_mnemonic(&phrase, &fuel_derivation_path(1)).unwrap();
+    assert_ne!(a.b256_address, other.b256_address);
+}
+
+#[test]
+fn test_invalid_mnemonics_are_rejected() {
+    let words = |n| vec!["abandon"; n].join(" ");
+    // Twelve "abandon"s fail the checksum; the last word must be "about".
+    assert!(mnemonic_to_entropy(&words(12)).is_err());
+    assert!(mnemonic_to_entropy(&words(11)).is_err());
+    assert!(Wallet::from_mnemonic(&format!("{} abaft", words(11)), FUEL_DERIVATION_PATH).is_err());
+    assert!(generate_mnemonic(13).is_err());
+    assert!(mnemonic_to_seed(&format!("{} about", words(11)), "pässword").is_err());
+}
+
+#[test]
+fn test_verify_personal_sign_fuel_and_evm() {
+    let message = b"session signing bytes";
+
+    let fuel = generate_keypair().unwrap();
+    let signature = personal_sign(fuel.private_key.expose_secret(), message).unwrap();
+    assert_eq!(
+        verify_personal_sign(message, &signature, &fuel.b256_address).unwrap(),
+        PersonalSignScheme::Fuel
+    );
+    assert_eq!(
+        recover_fuel_compact(
+            &Sha256::digest([&b"\x19Fuel Signed Message:\n21"[..], &message[..]].concat()).into(),
+            &signature
+        )
+        .unwrap(),
+        fuel.public_key
+    );
+
+    let evm = generate_evm_keypair().unwrap();
+    let signature = evm_personal_sign(evm.private_key.expose_secret(), message).unwrap();
+    assert_eq!(
+        verify_personal_sign(message, &signature, &evm.b256_address).unwrap(),
+        PersonalSignScheme::Evm
+    );
+}
+
+#[test]
+fn test_verify_personal_sign_names_the_recovered_signer() {
+    let message = b"withdraw signing bytes";
+    let owner = generate_keypair().unwrap();
+    let other = generate_keypair().unwrap();
+
+    // Signed by a different key.
+    let signature = personal_sign(other.private_key.expose_secret(), message).unwrap();
+    let err = verify_personal_sign(message, &signature, &owner.b256_address)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&to_hex_string(&owner.b256_address)), "{err}");
+    assert!(err.contains(&to_hex_string(&other.b256_address)), "{err}");
+
+    // Right key, wrong scheme: raw SHA-256 instead of personalSign.
+    let signature = raw_sign(owner.private_key.expose_secret(), message).unwrap();
+    assert!(verify_personal_sign(message, &signature, &owner.b256_address).is_err());
+
+    // Signature over different bytes.
+    let signature = personal_sign(owner.private_key.expose_secret(), b"other bytes").unwrap();
+    assert!(verify_personal_sign(message, &signature, &owner.b256_address).is_err());
+}
+
+#[test]
+fn test_private_key_is_redacted_and_exportable() {
+    let wallet = load_wallet(&[7u8; 32]).unwrap();
+    let debug = format!("{wallet:?}");
+    assert!(debug.contains("PrivateKey(<redacted>)"), "{debug}");
+    assert!(!debug.contains(&hex::encode([7u8; 32])), "{debug}");
+
+    let exported = wallet.private_key.expose_hex();
+    assert_eq!(exported.as_str(), to_hex_string(&[7u8; 32]));
+    let reloaded = PrivateKey::from_hex(&exported).unwrap();
+    assert_eq!(reloaded.expose_secret(), &[7u8; 32]);
+    assert!(PrivateKey::from_hex("0x1234").is_err());
+}
@@ -1,9 +1,10 @@
+#![cfg(feature = "low-level")]
 /// Unit tests for O2 SDK encoding module.
 ///
 /// Tests Fuel ABI encoding primitives, function selectors, session signing bytes,
 /// and action signing bytes.
 use o2_sdk::encoding::*;
-use o2_sdk::models::{IntoValidId, Market, MarketAsset};
+use o2_sdk::models::{Identity, IntoValidId, Market, MarketAsset, Side};
 use o2_sdk::UnsignedDecimal;
 
 #[test]
@@ -75,7 +76,7 @@ fn test_function_selector_set_session() {
 #[test]
 fn test_encode_identity_address() {
     let addr = [0xAA; 32];
-    let encoded = encode_identity(0, &addr);
+    let encoded = encode_identity(IdentityKind::Address, &addr);
     assert_eq!(encoded.len(), 40);
     assert_eq!(&encoded[..8], &u64_be(0)); // Address discriminant
     assert_eq!(&encoded[8..], &addr);
@@ -84,12 +85,27 @@ fn test_encode_identity_address() {
 #[test]
 fn test_encode_identity_contract_id() {
     let addr = [0xBB; 32];
-    let encoded = encode_identity(1, &addr);
+    let encoded = encode_identity(IdentityKind::ContractId, &addr);
     assert_eq!(encoded.len(), 40);
     assert_eq!(&encoded[..8], &u64_be(1)); // ContractId discriminant
     assert_eq!(&encoded[8..], &addr);
 }
 
+#[test]
+fn test_identity_parts_keep_kind_and_parse_hex() {
+    let contract = Identity::ContractId(format!("0x{}", "bb".repeat(32)));
+    assert_eq!(
+        identity_parts(&contract).unwrap(),
+        (IdentityKind::ContractId, [0xBB; 32])
+    );
+    assert_eq!(
+        IdentityKind::from(&Identity::Address("0x01".into())),
+        IdentityKind::Address
+    );
+    assert!(identity_parts(&Identity::Address("0xnothex".into())).is_err());
+    assert_eq!(u64::from(IdentityKind::ContractId), 1);
+}
+
 #[test]
 fn test_encode_option_none() {
     let encoded = encode_option_none();
@@ -378,12 +394,14 @@ fn test_settle_balance_to_call() {
     let contract_id = [0x11; 32];
     let to_address = [0x22; 32];
 
-    let call = settle_balance_to_call(&contract_id, 1, &to_address);
+    let call = settle_balance_to_call(&contract_id, IdentityKind::ContractId, &to_address);
 
     assert_eq!(call.amount, 0);
     assert_eq!(call.asset_id, [0u8; 32]);
-    // call_data should be encode_identity(1, to_address) = 40 bytes
-    assert_eq!(call.call_data.as_ref().unwrap().len(), 40);
+    assert_eq!(
+        call.call_data.as_deref(),
+        Some(encode_identity(IdentityKind::ContractId, &to_address).as_slice())
+    );
 }
 
 #[test]
@@ -492,6 +510,51 @@ fn test_adjust_quantity_needs_adjustment() {
     assert_eq!(adjusted, 2);
 }
 
+#[test]
+fn test_quantity_for_quote() {
+    let market = test_market();
+    // 10 USDC at 0.25 → 40 FUEL.
+    let quantity = market
+        .quantity_for_quote(250_000_000, 10_000_000_000)
+        .unwrap();
+    assert_eq!(quantity, 40_000_000_000);
+
+    // 10 USDC at 0.3 → 33.333... FUEL, truncated to 3 decimals.
+    let quantity = market
+        .quantity_for_quote(300_000_000, 10_000_000_000)
+        .unwrap();
+    assert_eq!(quantity, 33_333_000_000);
+    market.validate_order(300_000_000, quantity).unwrap();
+
+    // Below min_order.
+    let err = market.quantity_for_quote(250_000_000, 500_000).unwrap_err();
+    assert!(err.to_string().contains("below min_order"));
+    assert!(market.quantity_for_quote(0, 1).is_err());
+}
+
+#[test]
+fn test_slippage_bounds() {
+    let market = test_market();
+    // 0.25 with 50 bps: buys capped at 0.25125, truncated to 0.251.
+    assert_eq!(
+        market.slippage_bounds(Side::Buy, 250_000_000, 50).unwrap(),
+        (0, 251_000_000)
+    );
+    // Sells floored at 0.24875, rounded up to 0.249.
+    let (min, max) = market.slippage_bounds(Side::Sell, 250_000_000, 50).unwrap();
+    assert_eq!(min, 249_000_000);
+    assert_eq!(max % 1_000_000, 0);
+    assert!(max > 250_000_000);
+    assert_eq!(
+        market
+            .slippage_bounds(Side::Sell, 250_000_000, 20_000)
+            .unwrap()
+            .0,
+        0
+    );
+    assert!(market.slippage_bounds(Side::Buy, 0, 50).is_err());
+}
+
 // ---------------------------------------------------------------------------
 // OrderType::to_encoding tests
 // ---------------------------------------------------------------------------
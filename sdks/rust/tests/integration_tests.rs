@@ -8,394 +8,24 @@
 /// - maker/taker wallets are persisted to a gitignored local file
 /// - accounts are reused between runs
 /// - faucet minting is only triggered when balances are below thresholds
-use std::fs;
-
+///
+/// The setup helpers live in `o2_sdk::test_util::testnet`.
 use futures_util::StreamExt;
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
 use serial_test::serial;
 use tokio::sync::OnceCell;
 
-use o2_sdk::api::O2Api;
+use o2_sdk::test_util::testnet::*;
 use o2_sdk::*;
 
 const INTEGRATION_WALLETS_FILE: &str =
     concat!(env!("CARGO_MANIFEST_DIR"), "/.integration-wallets.json");
 
-#[derive(Debug, Deserialize, Serialize)]
-struct PersistedIntegrationWallets {
-    maker_private_key: String,
-    taker_private_key: String,
-}
-
-struct SharedSetup {
-    maker_wallet: Wallet,
-    maker_trade_account_id: TradeAccountId,
-    taker_wallet: Wallet,
-    taker_trade_account_id: TradeAccountId,
-}
-
-static SHARED: OnceCell<SharedSetup> = OnceCell::const_new();
-
-fn is_private_key_hex(value: &str) -> bool {
-    value.starts_with("0x")
-        && value.len() == 66
-        && value
-            .as_bytes()
-            .iter()
-            .skip(2)
-            .all(|b| b.is_ascii_hexdigit())
-}
-
-fn private_key_to_hex(private_key: &[u8; 32]) -> String {
-    format!(
-        "0x{}",
-        private_key
-            .iter()
-            .map(|b| format!("{b:02x}"))
-            .collect::<String>()
-    )
-}
-
-fn load_or_create_integration_wallets() -> (Wallet, Wallet) {
-    if let Ok(raw) = fs::read_to_string(INTEGRATION_WALLETS_FILE) {
-        if let Ok(persisted) = serde_json::from_str::<PersistedIntegrationWallets>(&raw) {
-            if is_private_key_hex(&persisted.maker_private_key)
-                && is_private_key_hex(&persisted.taker_private_key)
-            {
-                let client = O2Client::new(Network::Testnet);
-                if let (Ok(maker), Ok(taker)) = (
-                    client.load_wallet(&persisted.maker_private_key),
-                    client.load_wallet(&persisted.taker_private_key),
-                ) {
-                    return (maker, taker);
-                }
-            }
-        }
-        eprintln!(
-            "[integration] wallet cache at {} invalid, regenerating",
-            INTEGRATION_WALLETS_FILE
-        );
-    }
-
-    let client = O2Client::new(Network::Testnet);
-    let maker = client.generate_wallet().expect("generate maker wallet");
-    let taker = client.generate_wallet().expect("generate taker wallet");
-
-    let persisted = PersistedIntegrationWallets {
-        maker_private_key: private_key_to_hex(&maker.private_key),
-        taker_private_key: private_key_to_hex(&taker.private_key),
-    };
-
-    if let Ok(serialized) = serde_json::to_string_pretty(&persisted) {
-        let _ = fs::write(INTEGRATION_WALLETS_FILE, format!("{serialized}\n"));
-        eprintln!(
-            "[integration] wrote wallet cache to {}",
-            INTEGRATION_WALLETS_FILE
-        );
-    }
-
-    (maker, taker)
-}
-
-async fn mint_with_retry(api: &O2Api, trade_account_id: &str, max_retries: usize) {
-    let mut wait_secs = 5u64;
-    for attempt in 0..max_retries {
-        match api.mint_to_contract(trade_account_id).await {
-            Ok(resp) if resp.error.is_none() => return,
-            Ok(resp) => {
-                let err = resp
-                    .error
-                    .unwrap_or_else(|| "unknown faucet error".to_string());
-                if attempt < max_retries - 1 {
-                    let lower = err.to_ascii_lowercase();
-                    let cooldown = lower.contains("cooldown")
-                        || lower.contains("rate limit")
-                        || lower.contains("too many");
-                    wait_secs = if cooldown { 65 } else { wait_secs.min(20) };
-                    tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
-                    wait_secs = (wait_secs * 2).min(20);
-                }
-            }
-            Err(e) => {
-                if attempt < max_retries - 1 {
-                    let lower = e.to_string().to_ascii_lowercase();
-                    let cooldown = lower.contains("cooldown")
-                        || lower.contains("rate limit")
-                        || lower.contains("too many");
-                    let delay = if cooldown { 65 } else { wait_secs };
-                    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
-                    wait_secs = (wait_secs * 2).min(20);
-                }
-            }
-        }
-    }
-}
-
-async fn ensure_funded(
-    client: &mut O2Client,
-    trade_account_id: &TradeAccountId,
-    asset_symbol: &str,
-    min_balance: u128,
-) {
-    let mut mint_count = 0;
-    const MAX_MINTS: usize = 5;
-
-    loop {
-        let balances = client.get_balances(trade_account_id).await.unwrap();
-        let balance = balances
-            .get(asset_symbol)
-            .map(|b| b.trading_account_balance)
-            .unwrap_or(0);
-
-        if balance >= min_balance {
-            if mint_count > 0 {
-                eprintln!(
-                    "Balance for {} is now {} (needed {})",
-                    asset_symbol, balance, min_balance
-                );
-            }
-            break;
-        }
-
-        if mint_count >= MAX_MINTS {
-            eprintln!(
-                "Warning: Balance for {} is {} after {} mints (need {})",
-                asset_symbol, balance, mint_count, min_balance
-            );
-            break;
-        }
-
-        eprintln!(
-            "Balance for {} is {} (need {}), minting... (attempt {}/{})",
-            asset_symbol,
-            balance,
-            min_balance,
-            mint_count + 1,
-            MAX_MINTS
-        );
-
-        mint_with_retry(&client.api, trade_account_id.as_str(), 3).await;
-        mint_count += 1;
-        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-    }
-}
-
-async fn whitelist_with_retry(api: &O2Api, trade_account_id: &str, max_retries: usize) {
-    for attempt in 0..max_retries {
-        if api.whitelist_account(trade_account_id).await.is_ok() {
-            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-            return;
-        }
-        if attempt < max_retries - 1 {
-            tokio::time::sleep(std::time::Duration::from_secs(15)).await;
-        }
-    }
-}
-
-fn is_rate_limited_error(err: &O2Error) -> bool {
-    match err {
-        O2Error::RateLimitExceeded(_) => true,
-        O2Error::OnChainRevert {
-            message, reason, ..
-        } => {
-            let combined = format!("{message} {reason}").to_ascii_lowercase();
-            combined.contains("rate limit")
-                || combined.contains("cooldown")
-                || combined.contains("too many")
-        }
-        other => {
-            let msg = other.to_string().to_ascii_lowercase();
-            msg.contains("rate limit") || msg.contains("cooldown") || msg.contains("too many")
-        }
-    }
-}
-
-async fn setup_account_with_retry(
-    client: &mut O2Client,
-    wallet: &Wallet,
-    max_retries: usize,
-) -> AccountResponse {
-    let mut wait_secs = 5u64;
-    for attempt in 0..max_retries {
-        match client.setup_account(wallet).await {
-            Ok(account) => return account,
-            Err(e) => {
-                let retryable = is_rate_limited_error(&e);
-                if retryable && attempt < max_retries - 1 {
-                    eprintln!(
-                        "[integration] setup_account attempt {}/{} rate-limited: {} (retrying in {}s)",
-                        attempt + 1,
-                        max_retries,
-                        e,
-                        wait_secs
-                    );
-                    tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
-                    wait_secs = (wait_secs * 2).min(65);
-                    continue;
-                }
-                panic!(
-                    "setup_account failed after {}/{} attempts: {}",
-                    attempt + 1,
-                    max_retries,
-                    e
-                );
-            }
-        }
-    }
-    unreachable!()
-}
+static SHARED: OnceCell<TestAccounts> = OnceCell::const_new();
 
-async fn ensure_account_with_retry(
-    client: &mut O2Client,
-    wallet: &Wallet,
-    max_retries: usize,
-) -> TradeAccountId {
-    let owner_hex = crypto::to_hex_string(&wallet.b256_address);
-
-    for attempt in 0..max_retries {
-        let result = async {
-            let existing = client.api.get_account_by_owner(&owner_hex).await?;
-            let trade_account_id = if let Some(id) = existing.trade_account_id {
-                id
-            } else {
-                client
-                    .api
-                    .create_account(&owner_hex)
-                    .await?
-                    .trade_account_id
-            };
-            whitelist_with_retry(&client.api, trade_account_id.as_str(), 2).await;
-            Ok::<TradeAccountId, O2Error>(trade_account_id)
-        }
-        .await;
-
-        match result {
-            Ok(id) => return id,
-            Err(e) => {
-                eprintln!(
-                    "[integration] ensure_account attempt {}/{} failed for {}...: {}",
-                    attempt + 1,
-                    max_retries,
-                    &owner_hex[..12.min(owner_hex.len())],
-                    e
-                );
-                if attempt < max_retries - 1 {
-                    tokio::time::sleep(std::time::Duration::from_secs(15)).await;
-                }
-            }
-        }
-    }
-
-    panic!("ensure_account failed after retries")
-}
-
-/// Place an order, retrying with re-whitelist on TraderNotWhiteListed errors.
-#[allow(clippy::too_many_arguments)]
-async fn create_order_with_whitelist_retry(
-    client: &mut O2Client,
-    session: &mut Session,
-    trade_account_id: &TradeAccountId,
-    market_pair: &MarketSymbol,
-    market: &o2_sdk::Market,
-    side: Side,
-    price: UnsignedDecimal,
-    quantity: UnsignedDecimal,
-    order_type: OrderType,
-    settle_first: bool,
-    collect_orders: bool,
-    max_retries: usize,
-) -> Result<o2_sdk::models::SessionActionsResponse, o2_sdk::O2Error> {
-    let typed_price = market.price_from_decimal(price)?;
-    let typed_quantity = market.quantity_from_decimal(quantity)?;
-    for attempt in 0..max_retries {
-        match client
-            .create_order(
-                session,
-                market_pair,
-                side,
-                typed_price.clone(),
-                typed_quantity.clone(),
-                order_type.clone(),
-                settle_first,
-                collect_orders,
-            )
-            .await
-        {
-            Ok(resp) => return Ok(resp),
-            Err(e) => {
-                let is_whitelist_err = match &e {
-                    o2_sdk::O2Error::OnChainRevert { reason, .. } => {
-                        reason.contains("TraderNotWhiteListed")
-                    }
-                    other => format!("{other}").contains("TraderNotWhiteListed"),
-                };
-                if is_whitelist_err && attempt < max_retries - 1 {
-                    whitelist_with_retry(&client.api, trade_account_id.as_str(), 2).await;
-                    tokio::time::sleep(std::time::Duration::from_secs(5 * (attempt as u64 + 1)))
-                        .await;
-                    continue;
-                }
-                return Err(e);
-            }
-        }
-    }
-    unreachable!()
-}
-
-async fn get_shared_setup() -> &'static SharedSetup {
+async fn get_shared_setup() -> &'static TestAccounts {
     SHARED
-        .get_or_init(|| async {
-            let (maker_wallet, taker_wallet) = load_or_create_integration_wallets();
-
-            let maker_fut = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_account_with_retry(&mut c, &maker_wallet, 4).await
-            };
-            let taker_fut = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_account_with_retry(&mut c, &taker_wallet, 4).await
-            };
-
-            let (maker_trade_account_id, taker_trade_account_id) =
-                tokio::join!(maker_fut, taker_fut);
-
-            let mut client = O2Client::new(Network::Testnet);
-            let markets = client.get_markets().await.unwrap();
-            let market = &markets[0];
-            let market_pair = market.symbol_pair();
-            let base_symbol = market.base.symbol.clone();
-            let quote_symbol = market.quote.symbol.clone();
-
-            cleanup_open_orders(&mut client, &maker_wallet, &market_pair).await;
-            cleanup_open_orders(&mut client, &taker_wallet, &market_pair).await;
-
-            let maker_quote = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_funded(&mut c, &maker_trade_account_id, &quote_symbol, 50_000_000).await;
-            };
-            let maker_base = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_funded(&mut c, &maker_trade_account_id, &base_symbol, 50_000_000).await;
-            };
-            let taker_quote = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_funded(&mut c, &taker_trade_account_id, &quote_symbol, 50_000_000).await;
-            };
-            let taker_base = async {
-                let mut c = O2Client::new(Network::Testnet);
-                ensure_funded(&mut c, &taker_trade_account_id, &base_symbol, 50_000_000).await;
-            };
-
-            let _ = tokio::join!(maker_quote, maker_base, taker_quote, taker_base);
-
-            SharedSetup {
-                maker_wallet,
-                maker_trade_account_id,
-                taker_wallet,
-                taker_trade_account_id,
-            }
-        })
+        .get_or_init(|| setup_maker_taker(INTEGRATION_WALLETS_FILE, 50_000_000))
         .await
 }
 
@@ -669,14 +299,14 @@ async fn conservative_post_only_buy_params(
     // the chosen price to accidentally cross the actual best ask.
     if let Ok(depth) = client.get_depth(market_pair, 1, None).await {
         if let Some(best_ask) = depth.asks.first() {
-            let best_ask_human = market.format_price(best_ask.price);
+            let best_ask_human = market.format_price(best_ask.price).unwrap();
             let best_ask_dec = *best_ask_human.inner();
             let just_below_ask = floor_to_step(best_ask_dec - step_dec, step_dec);
             if just_below_ask > Decimal::ZERO {
                 chosen = just_below_ask;
             }
         } else if let Some(best_bid) = depth.bids.first() {
-            let best_bid_human = market.format_price(best_bid.price);
+            let best_bid_human = market.format_price(best_bid.price).unwrap();
             let best_bid_dec = *best_bid_human.inner();
             let bid_floor = floor_to_step(best_bid_dec, step_dec);
             if bid_floor > Decimal::ZERO {
@@ -703,20 +333,6 @@ async fn conservative_post_only_buy_params(
     (price, quantity, maker_quote_min, taker_base_min)
 }
 
-async fn cleanup_open_orders(client: &mut O2Client, wallet: &Wallet, market_pair: &MarketSymbol) {
-    if let Ok(mut session) = client
-        .create_session(
-            wallet,
-            &[market_pair],
-            std::time::Duration::from_secs(30 * 24 * 3600),
-        )
-        .await
-    {
-        let _ = client.cancel_all_orders(&mut session, market_pair).await;
-        let _ = client.settle_balance(&mut session, market_pair).await;
-    }
-}
-
 #[tokio::test]
 #[serial]
 async fn test_order_placement_and_cancellation() {
@@ -740,7 +356,8 @@ async fn test_order_placement_and_cancellation() {
         &market.quote.symbol,
         50_000_000,
     )
-    .await;
+    .await
+    .unwrap();
 
     let mut session = client
         .create_session(
@@ -815,14 +432,16 @@ async fn test_cross_account_fill() {
         &market.quote.symbol,
         maker_quote_min,
     )
-    .await;
+    .await
+    .unwrap();
     ensure_funded(
         &mut client,
         &shared.taker_trade_account_id,
         &market.base.symbol,
         taker_base_min,
     )
-    .await;
+    .await
+    .unwrap();
 
     let mut maker_session = client
         .create_session(
@@ -995,14 +614,16 @@ async fn test_websocket_trades() {
         &market.quote.symbol,
         maker_quote_min,
     )
-    .await;
+    .await
+    .unwrap();
     ensure_funded(
         &mut client,
         &shared.taker_trade_account_id,
         &market.base.symbol,
         taker_base_min,
     )
-    .await;
+    .await
+    .unwrap();
 
     let mut maker_session = client
         .create_session(
@@ -1482,14 +1103,16 @@ async fn test_websocket_mixed_with_fill() {
         &market.quote.symbol,
         maker_quote_min,
     )
-    .await;
+    .await
+    .unwrap();
     ensure_funded(
         &mut client,
         &shared.taker_trade_account_id,
         &market.base.symbol,
         taker_base_min,
     )
-    .await;
+    .await
+    .unwrap();
 
     let mut maker_session = client
         .create_session(
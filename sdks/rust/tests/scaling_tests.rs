@@ -0,0 +1,141 @@
+/// Property tests for market scaling math.
+///
+/// Exercises the `scale_*` / `format_*` round-trip across the full range of
+/// decimals/max_precision configurations a market can advertise, including
+/// degenerate ones (zero decimals) and values that cannot be represented.
+use o2_sdk::models::{IntoValidId, Market, MarketAsset};
+use o2_sdk::UnsignedDecimal;
+use proptest::prelude::*;
+
+fn market_with(decimals: u32, max_precision: u32) -> Market {
+    Market {
+        contract_id: "0x0000000000000000000000000000000000000000000000000000000000000001"
+            .into_valid()
+            .unwrap(),
+        market_id: "0x0000000000000000000000000000000000000000000000000000000000000002"
+            .into_valid()
+            .unwrap(),
+        whitelist_id: None,
+        blacklist_id: None,
+        maker_fee: 0,
+        taker_fee: 0,
+        min_order: 0,
+        dust: 0,
+        price_window: 0,
+        base: MarketAsset {
+            symbol: "BASE".into(),
+            asset: "0x0000000000000000000000000000000000000000000000000000000000000003"
+                .into_valid()
+                .unwrap(),
+            decimals,
+            max_precision,
+        },
+        quote: MarketAsset {
+            symbol: "QUOTE".into(),
+            asset: "0x0000000000000000000000000000000000000000000000000000000000000004"
+                .into_valid()
+                .unwrap(),
+            decimals,
+            max_precision,
+        },
+    }
+}
+
+/// (decimals, max_precision) pairs with max_precision <= decimals <= 19.
+fn precision_config() -> impl Strategy<Value = (u32, u32)> {
+    (0u32..=19).prop_flat_map(|decimals| (Just(decimals), 0..=decimals))
+}
+
+proptest! {
+    #[test]
+    fn format_then_scale_round_trips_aligned_values(
+        (decimals, max_precision) in precision_config(),
+        raw in any::<u64>(),
+    ) {
+        let market = market_with(decimals, max_precision);
+        let step = 10u64.pow(decimals - max_precision);
+        let chain_value = (raw / step) * step;
+
+        let price = market.format_price(chain_value).unwrap();
+        prop_assert_eq!(market.scale_price(&price).unwrap(), chain_value);
+
+        let quantity = market.format_quantity(chain_value).unwrap();
+        prop_assert_eq!(market.scale_quantity(&quantity).unwrap(), chain_value);
+    }
+
+    #[test]
+    fn scale_truncates_to_max_precision(
+        (decimals, max_precision) in precision_config(),
+        raw in any::<u64>(),
+    ) {
+        let market = market_with(decimals, max_precision);
+        let step = 10u64.pow(decimals - max_precision);
+
+        let human = market.format_price(raw).unwrap();
+        let scaled = market.scale_price(&human).unwrap();
+        prop_assert_eq!(scaled % step, 0);
+        prop_assert!(scaled <= raw);
+        prop_assert!(raw - scaled < step);
+    }
+
+    #[test]
+    fn typed_wrappers_accept_formatted_values(
+        (decimals, max_precision) in precision_config(),
+        raw in any::<u64>(),
+    ) {
+        let market = market_with(decimals, max_precision);
+        let step = 10u64.pow(decimals - max_precision);
+        let chain_value = (raw / step) * step;
+
+        let human = market.format_quantity(chain_value).unwrap();
+        let quantity = market.quantity_from_decimal(human).unwrap();
+        prop_assert_eq!(market.scale_quantity(&quantity.value()).unwrap(), chain_value);
+    }
+
+    #[test]
+    fn scale_never_panics_on_large_inputs(
+        decimals in 0u32..=40,
+        mantissa in any::<u64>(),
+        exponent in 0u32..=9,
+    ) {
+        let market = market_with(decimals, 0);
+        let human = UnsignedDecimal::from(mantissa) * UnsignedDecimal::from(10u64.pow(exponent));
+        // Only the absence of a panic matters; overflow must surface as Err.
+        let _ = market.scale_price(&human);
+        let _ = market.scale_quantity(&human);
+        let _ = market.format_price(mantissa);
+        let _ = market.format_quantity(mantissa);
+    }
+}
+
+#[test]
+fn zero_decimals_scale_whole_units() {
+    let market = market_with(0, 0);
+    let human: UnsignedDecimal = "42.9".parse().unwrap();
+    assert_eq!(market.scale_price(&human).unwrap(), 42);
+    assert_eq!(market.format_price(42).unwrap(), "42".parse().unwrap());
+    assert!(market.price("42").is_ok());
+    assert!(market.price("42.5").is_err());
+}
+
+#[test]
+fn decimals_beyond_u64_range_error() {
+    let market = market_with(20, 0);
+    let human: UnsignedDecimal = "1".parse().unwrap();
+    assert!(market.scale_price(&human).is_err());
+    assert!(market.scale_quantity(&human).is_err());
+}
+
+#[test]
+fn decimals_beyond_decimal_range_error() {
+    let market = market_with(29, 0);
+    assert!(market.format_price(1).is_err());
+    assert!(market.format_quantity(1).is_err());
+}
+
+#[test]
+fn scale_overflow_is_an_error() {
+    let market = market_with(9, 9);
+    let human = UnsignedDecimal::from(u64::MAX);
+    assert!(market.scale_price(&human).is_err());
+}
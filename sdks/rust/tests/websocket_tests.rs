@@ -14,8 +14,12 @@ use tokio::sync::Mutex;
 use tokio_tungstenite::accept_async;
 use tokio_tungstenite::tungstenite::Message as WsMsg;
 
+use o2_sdk::api::O2Api;
+use o2_sdk::feed_monitor::FeedMonitorConfig;
 use o2_sdk::models::*;
-use o2_sdk::websocket::{DepthPrecision, O2WebSocket, WsConfig, WsLifecycleEvent};
+use o2_sdk::orderbook::LiveOrderBook;
+use o2_sdk::websocket::{DepthPrecision, FeedKind, O2WebSocket, WsConfig, WsLifecycleEvent};
+use o2_sdk::{NetworkConfig, O2Error};
 
 /// Create a mock server that sends specific messages on connection.
 async fn create_messaging_mock_server(messages: Vec<serde_json::Value>) -> String {
@@ -181,6 +185,453 @@ async fn test_ws_depth_stream_receives_messages() {
     let _ = ws.disconnect().await;
 }
 
+#[tokio::test]
+async fn test_live_order_book_applies_snapshot_and_deltas() {
+    let market = "0x0000000000000000000000000000000000000000000000000000000000000001";
+    let messages = vec![
+        json!({
+            "action": "subscribe_depth",
+            "market_id": market,
+            "view": {
+                "buys": [{"price": "100", "quantity": "10"}, {"price": "99", "quantity": "4"}],
+                "sells": [{"price": "102", "quantity": "3"}]
+            }
+        }),
+        json!({
+            "action": "subscribe_depth_update",
+            "market_id": market,
+            "changes": {
+                "buys": [{"price": "100", "quantity": "0"}],
+                "sells": [{"price": "101", "quantity": "6"}]
+            }
+        }),
+    ];
+
+    let url = create_messaging_mock_server(messages).await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let stream = ws
+        .stream_depth(market, &DepthPrecision::new(1).unwrap())
+        .await
+        .unwrap();
+
+    let book = LiveOrderBook::spawn(market.into_valid().unwrap(), stream);
+    let mut version = book.subscribe();
+    tokio::time::timeout(Duration::from_secs(2), version.wait_for(|v| *v >= 2))
+        .await
+        .expect("book should apply both updates")
+        .unwrap();
+
+    assert!(book.is_synced());
+    assert_eq!(book.best_bid().unwrap().price, 99);
+    assert_eq!(book.best_ask().unwrap().price, 101);
+    assert_eq!(book.spread(), Some(2));
+
+    let _ = ws.disconnect().await;
+}
+
+/// Mock server that records every action it receives and answers the first
+/// `subscribe_depth` with `replies` depth messages, then goes quiet.
+async fn create_recording_depth_server(replies: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let actions = Arc::new(Mutex::new(Vec::new()));
+    let actions_clone = actions.clone();
+
+    tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            if let Ok(ws_stream) = accept_async(stream).await {
+                let (mut sender, mut receiver) = ws_stream.split();
+                let mut replied = false;
+                while let Some(Ok(msg)) = receiver.next().await {
+                    match msg {
+                        WsMsg::Text(text) => {
+                            let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                            let action = json["action"].as_str().unwrap_or("").to_string();
+                            actions_clone.lock().await.push(action.clone());
+                            if action == "subscribe_depth" && !replied {
+                                replied = true;
+                                for _ in 0..replies {
+                                    let reply = json!({
+                                        "action": "subscribe_depth_update",
+                                        "market_id": json["market_id"],
+                                        "changes": { "buys": [], "sells": [] }
+                                    });
+                                    let _ = sender.send(WsMsg::Text(reply.to_string())).await;
+                                }
+                            }
+                        }
+                        WsMsg::Ping(data) => {
+                            let _ = sender.send(WsMsg::Pong(data)).await;
+                        }
+                        WsMsg::Close(_) => break,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (format!("ws://{}", addr), actions)
+}
+
+/// Mock server that records every action it receives and answers each
+/// `subscribe_ticker` with a ticker message and an unrelated depth message.
+async fn create_recording_ticker_server() -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+
+    tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            if let Ok(ws_stream) = accept_async(stream).await {
+                let (mut sender, mut receiver) = ws_stream.split();
+                while let Some(Ok(msg)) = receiver.next().await {
+                    match msg {
+                        WsMsg::Text(text) => {
+                            let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                            received_clone.lock().await.push(json.clone());
+                            if json["action"] == "subscribe_ticker" {
+                                let replies = [
+                                    json!({
+                                        "action": "subscribe_depth_update",
+                                        "market_id": json["market_id"],
+                                        "changes": { "buys": [], "sells": [] }
+                                    }),
+                                    json!({
+                                        "action": "subscribe_ticker",
+                                        "market_id": json["market_id"],
+                                        "last": "105"
+                                    }),
+                                ];
+                                for reply in replies {
+                                    let _ = sender.send(WsMsg::Text(reply.to_string())).await;
+                                }
+                            }
+                        }
+                        WsMsg::Ping(data) => {
+                            let _ = sender.send(WsMsg::Pong(data)).await;
+                        }
+                        WsMsg::Close(_) => break,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (format!("ws://{}", addr), received)
+}
+
+#[tokio::test]
+async fn test_ws_raw_subscription_routes_by_action() {
+    let (url, received) = create_recording_ticker_server().await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+
+    let err = ws.subscribe_raw(json!({ "action": "ticker" })).await;
+    assert!(matches!(err, Err(O2Error::SubscriptionError(_))));
+
+    let sub = json!({ "action": "subscribe_ticker", "market_id": "0xab" });
+    let mut stream = ws.subscribe_raw(sub.clone()).await.unwrap();
+    let msg = tokio::time::timeout(Duration::from_secs(2), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg["action"], "subscribe_ticker");
+    assert_eq!(msg["last"], "105");
+
+    ws.unsubscribe_raw(&sub).await.unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(2), stream.next())
+        .await
+        .unwrap();
+    assert!(next.is_none(), "stream should end after unsubscribe");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        received.lock().await.last().unwrap(),
+        &json!({ "action": "unsubscribe_ticker", "market_id": "0xab" })
+    );
+
+    let _ = ws.disconnect().await;
+}
+
+#[tokio::test]
+async fn test_ws_dropping_last_stream_unsubscribes() {
+    let (url, actions) = create_recording_depth_server(0).await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let precision = DepthPrecision::new(1).unwrap();
+    let first = ws.stream_depth("0xab", &precision).await.unwrap();
+    let second = ws.stream_depth("0xab", &precision).await.unwrap();
+    let trades = ws.stream_trades("0xab").await.unwrap();
+    assert_eq!(
+        first.subscription().unwrap().subscription()["action"],
+        "subscribe_depth"
+    );
+
+    // Another stream still holds the depth subscription.
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!actions
+        .lock()
+        .await
+        .contains(&"unsubscribe_depth".to_string()));
+    assert_eq!(ws.feed_stats().await.len(), 2);
+
+    drop(second);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(actions
+        .lock()
+        .await
+        .contains(&"unsubscribe_depth".to_string()));
+    let stats = ws.feed_stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].kind, FeedKind::Trades);
+
+    trades.close().await.unwrap();
+    assert!(ws.feed_stats().await.is_empty());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        actions.lock().await.last().map(String::as_str),
+        Some("unsubscribe_trades")
+    );
+
+    let _ = ws.disconnect().await;
+}
+
+/// Mock server that records every action it receives and answers each
+/// `subscribe_depth` with a snapshot and then a delta.
+async fn create_snapshot_depth_server() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let actions = Arc::new(Mutex::new(Vec::new()));
+    let actions_clone = actions.clone();
+
+    tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            if let Ok(ws_stream) = accept_async(stream).await {
+                let (mut sender, mut receiver) = ws_stream.split();
+                while let Some(Ok(msg)) = receiver.next().await {
+                    match msg {
+                        WsMsg::Text(text) => {
+                            let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                            let action = json["action"].as_str().unwrap_or("").to_string();
+                            actions_clone.lock().await.push(action.clone());
+                            if action == "subscribe_depth" {
+                                let replies = [
+                                    json!({
+                                        "action": "subscribe_depth",
+                                        "market_id": json["market_id"],
+                                        "view": {
+                                            "buys": [{"price": "100", "quantity": "10"}],
+                                            "sells": [{"price": "110", "quantity": "4"}]
+                                        }
+                                    }),
+                                    json!({
+                                        "action": "subscribe_depth_update",
+                                        "market_id": json["market_id"],
+                                        "changes": {
+                                            "buys": [{"price": "101", "quantity": "5"}],
+                                            "sells": []
+                                        }
+                                    }),
+                                ];
+                                for reply in replies {
+                                    let _ = sender.send(WsMsg::Text(reply.to_string())).await;
+                                }
+                            }
+                        }
+                        WsMsg::Ping(data) => {
+                            let _ = sender.send(WsMsg::Pong(data)).await;
+                        }
+                        WsMsg::Close(_) => break,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (format!("ws://{}", addr), actions)
+}
+
+#[tokio::test]
+async fn test_ws_duplicate_streams_share_subscription() {
+    let (url, actions) = create_snapshot_depth_server().await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let precision = DepthPrecision::new(1).unwrap();
+    let mut first = ws.stream_depth("0xab", &precision).await.unwrap();
+    for _ in 0..2 {
+        tokio::time::timeout(Duration::from_secs(2), first.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    // The second stream joins without a new server subscription and starts
+    // from the book as the first stream has it.
+    let mut second = ws.stream_depth("0xab", &precision).await.unwrap();
+    let snapshot = tokio::time::timeout(Duration::from_secs(2), second.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let view = snapshot.view.unwrap();
+    let bids: Vec<_> = view.bids.iter().map(|l| (l.price, l.quantity)).collect();
+    assert_eq!(bids, vec![(101, 5), (100, 10)]);
+    assert_eq!(view.asks.len(), 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(*actions.lock().await, vec!["subscribe_depth".to_string()]);
+
+    let active = ws.active_streams().await;
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].subscription["market_id"], "0xab");
+    assert_eq!(active[0].streams, 2);
+
+    first.close().await.unwrap();
+    assert_eq!(ws.active_streams().await[0].streams, 1);
+    second.close().await.unwrap();
+    assert!(ws.active_streams().await.is_empty());
+
+    let _ = ws.disconnect().await;
+}
+
+#[tokio::test]
+async fn test_ws_feed_stats_count_messages() {
+    let (url, _actions) = create_recording_depth_server(3).await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let mut stream = ws
+        .stream_depth("0xAB", &DepthPrecision::new(1).unwrap())
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        tokio::time::timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    let stats = ws.feed_stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].kind, FeedKind::Depth);
+    assert_eq!(stats[0].messages, 3);
+    assert!(stats[0].last_message_at.is_some());
+
+    ws.unsubscribe_depth("0xAB").await.unwrap();
+    assert!(ws.feed_stats().await.is_empty());
+    let _ = ws.disconnect().await;
+}
+
+#[tokio::test]
+async fn test_ws_drain_unsubscribes_flushes_and_closes() {
+    let (url, actions) = create_recording_depth_server(3).await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let mut lifecycle = ws.subscribe_lifecycle();
+    let mut stream = ws
+        .stream_depth("0xAB", &DepthPrecision::new(1).unwrap())
+        .await
+        .unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), ws.drain(Duration::from_secs(3)))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(ws.is_draining());
+    assert!(ws.is_terminated());
+
+    // In-flight updates are delivered before the stream ends.
+    let mut received = 0;
+    while let Some(item) = stream.next().await {
+        item.unwrap();
+        received += 1;
+    }
+    assert_eq!(received, 3);
+    assert!(actions
+        .lock()
+        .await
+        .contains(&"unsubscribe_depth".to_string()));
+
+    assert!(matches!(
+        lifecycle.recv().await.unwrap(),
+        WsLifecycleEvent::Draining { .. }
+    ));
+    assert_eq!(
+        lifecycle.recv().await.unwrap(),
+        WsLifecycleEvent::Disconnected {
+            reason: "Drained".into(),
+            final_: true
+        }
+    );
+    assert!(matches!(
+        ws.stream_trades("0xAB").await,
+        Err(O2Error::SubscriptionError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_ws_feed_monitor_reports_and_resubscribes_stalled_feed() {
+    let (url, actions) = create_recording_depth_server(0).await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let mut lifecycle = ws.subscribe_lifecycle();
+    let _stream = ws
+        .stream_depth("0xab", &DepthPrecision::new(1).unwrap())
+        .await
+        .unwrap();
+
+    let api = O2Api::new(NetworkConfig {
+        api_base: "http://127.0.0.1:9".into(),
+        ws_url: url.clone(),
+        fuel_rpc: "http://127.0.0.1:9".into(),
+        faucet_url: None,
+        whitelist_required: false,
+        chain_id: None,
+        api_base_fallbacks: Vec::new(),
+        ws_url_fallbacks: Vec::new(),
+        failover: Default::default(),
+    });
+    let _monitor = ws.monitor_feeds(
+        api,
+        FeedMonitorConfig {
+            stall_window: Duration::from_millis(200),
+            check_interval: Duration::from_millis(50),
+            require_rest_activity: false,
+            resubscribe: true,
+        },
+    );
+
+    let stalled = tokio::time::timeout(Duration::from_secs(2), lifecycle.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        stalled,
+        WsLifecycleEvent::FeedStalled { kind: FeedKind::Depth, ref market_id, .. } if market_id == "0xab"
+    ));
+    let resubscribed = tokio::time::timeout(Duration::from_secs(2), lifecycle.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        resubscribed,
+        WsLifecycleEvent::FeedResubscribed {
+            kind: FeedKind::Depth,
+            ..
+        }
+    ));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let seen = actions.lock().await.clone();
+    assert_eq!(
+        seen,
+        vec!["subscribe_depth", "unsubscribe_depth", "subscribe_depth"]
+    );
+    let _ = ws.disconnect().await;
+}
+
 #[tokio::test]
 async fn test_ws_orders_stream_receives_messages() {
     let messages = vec![json!({
@@ -291,7 +742,7 @@ async fn test_ws_nonce_stream_receives_messages() {
 
     let url = create_messaging_mock_server(messages).await;
     let ws = O2WebSocket::connect(&url).await.unwrap();
-    let identity = Identity::Address("test_address".to_string());
+    let identity = Identity::ContractId("contract123".to_string());
     let mut stream = ws.stream_nonce(&[identity]).await.unwrap();
 
     let update = tokio::time::timeout(Duration::from_secs(2), stream.next())
@@ -307,6 +758,192 @@ async fn test_ws_nonce_stream_receives_messages() {
     let _ = ws.disconnect().await;
 }
 
+/// Create a mock server that sends `messages` once it has received
+/// `subscribes` subscribe requests, so that every stream is registered first.
+async fn create_subscribed_server(subscribes: usize, messages: Vec<serde_json::Value>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let Ok((stream, _)) = listener.accept().await else {
+            return;
+        };
+        let Ok(ws_stream) = accept_async(stream).await else {
+            return;
+        };
+        let (mut sender, mut receiver) = ws_stream.split();
+        let mut seen = 0;
+        while let Some(Ok(msg)) = receiver.next().await {
+            match msg {
+                WsMsg::Text(text) if text.contains("\"subscribe_") => {
+                    seen += 1;
+                    if seen == subscribes {
+                        for msg in &messages {
+                            let text = serde_json::to_string(msg).unwrap();
+                            let _ = sender.send(WsMsg::Text(text)).await;
+                        }
+                    }
+                }
+                WsMsg::Ping(data) => {
+                    let _ = sender.send(WsMsg::Pong(data)).await;
+                }
+                WsMsg::Close(_) => break,
+                _ => {}
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    format!("ws://{}", addr)
+}
+
+/// Collect whatever a stream yields until it has been quiet for a moment.
+async fn drain_quiet<T>(stream: &mut o2_sdk::TypedStream<T>) -> Vec<T> {
+    let mut items = Vec::new();
+    while let Ok(Some(item)) = tokio::time::timeout(Duration::from_millis(300), stream.next()).await
+    {
+        items.push(item.unwrap());
+    }
+    items
+}
+
+#[tokio::test]
+async fn test_ws_trades_and_depth_route_by_market() {
+    let trade = |market: &str, id: &str| {
+        json!({
+            "action": "subscribe_trades",
+            "trades": [{"trade_id": id, "side": "Buy", "total": "500", "price": "100", "quantity": "5", "timestamp": "1"}],
+            "market_id": market,
+            "seen_timestamp": "1"
+        })
+    };
+    let depth = |market: &str| {
+        json!({
+            "action": "subscribe_depth",
+            "market_id": market,
+            "view": {"buys": [{"price": "100", "quantity": "10"}], "sells": []}
+        })
+    };
+    let url = create_subscribed_server(
+        4,
+        vec![
+            trade("0xaaaa", "t1"),
+            trade("0xbbbb", "t2"),
+            depth("0xbbbb"),
+            trade("0xAAAA", "t3"),
+            depth("0xaaaa"),
+        ],
+    )
+    .await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let precision = DepthPrecision::new(1).unwrap();
+    let mut trades_a = ws.stream_trades("0xAAAA").await.unwrap();
+    let mut trades_b = ws.stream_trades("0xbbbb").await.unwrap();
+    let mut depth_a = ws.stream_depth("0xaaaa", &precision).await.unwrap();
+    let mut depth_b = ws.stream_depth("0xbbbb", &precision).await.unwrap();
+
+    let ids = |updates: Vec<TradeUpdate>| -> Vec<String> {
+        updates
+            .iter()
+            .flat_map(|u| u.trades.iter().map(|t| t.trade_id.to_string()))
+            .collect()
+    };
+    assert_eq!(ids(drain_quiet(&mut trades_a).await), ["t1", "t3"]);
+    assert_eq!(ids(drain_quiet(&mut trades_b).await), ["t2"]);
+    let depth_a = drain_quiet(&mut depth_a).await;
+    let depth_b = drain_quiet(&mut depth_b).await;
+    assert_eq!(depth_a.len(), 1);
+    assert_eq!(depth_a[0].market_id.as_str(), "0xaaaa");
+    assert_eq!(depth_b.len(), 1);
+    assert_eq!(depth_b[0].market_id.as_str(), "0xbbbb");
+
+    let _ = ws.disconnect().await;
+}
+
+#[tokio::test]
+async fn test_ws_account_streams_route_by_identity() {
+    let balance = |entries: Vec<(&str, &str)>| {
+        let balance: Vec<serde_json::Value> = entries
+            .into_iter()
+            .map(|(account, asset)| {
+                json!({
+                    "identity": {"ContractId": account},
+                    "asset_id": asset,
+                    "total_locked": "0",
+                    "total_unlocked": "1",
+                    "trading_account_balance": "1",
+                    "order_books": {}
+                })
+            })
+            .collect();
+        json!({"action": "subscribe_balances", "balance": balance, "seen_timestamp": "1"})
+    };
+    let order = |id: &str, account: Option<&str>| {
+        let mut order = json!({
+            "order_id": id, "side": "Buy", "order_type": "Spot",
+            "price": "100", "quantity": "10", "close": false,
+            "partially_filled": false, "cancel": false
+        });
+        if let Some(account) = account {
+            order["account"] = json!({ "ContractId": account });
+        }
+        order
+    };
+    let url = create_subscribed_server(
+        6,
+        vec![
+        balance(vec![("0xa1", "asset1"), ("0xb2", "asset2")]),
+        json!({
+            "action": "subscribe_orders",
+            "orders": [order("o1", Some("0xA1")), order("o2", Some("0xb2")), order("o3", None)],
+            "seen_timestamp": "1"
+        }),
+        json!({"action": "subscribe_nonce", "contract_id": "0xb2", "nonce": "7", "seen_timestamp": "1"}),
+    ],
+    )
+    .await;
+    let ws = O2WebSocket::connect(&url).await.unwrap();
+    let a = [Identity::ContractId("0xa1".into())];
+    let b = [Identity::ContractId("0xb2".into())];
+    let both = [a[0].clone(), b[0].clone()];
+    let mut balances_a = ws.stream_balances(&a).await.unwrap();
+    let mut balances_both = ws.stream_balances(&both).await.unwrap();
+    let mut orders_a = ws.stream_orders(&a).await.unwrap();
+    let mut orders_b = ws.stream_orders(&b).await.unwrap();
+    let mut nonce_a = ws.stream_nonce(&a).await.unwrap();
+    let mut nonce_b = ws.stream_nonce(&b).await.unwrap();
+
+    let assets = |updates: Vec<BalanceUpdate>| -> Vec<Vec<String>> {
+        updates
+            .iter()
+            .map(|u| u.balance.iter().map(|e| e.asset_id.to_string()).collect())
+            .collect()
+    };
+    assert_eq!(assets(drain_quiet(&mut balances_a).await), [["asset1"]]);
+    // A stream over both identities gets the message once, whole.
+    assert_eq!(
+        assets(drain_quiet(&mut balances_both).await),
+        [["asset1", "asset2"]]
+    );
+
+    let order_ids = |updates: Vec<OrderUpdate>| -> Vec<String> {
+        updates
+            .iter()
+            .flat_map(|u| u.orders.iter().map(|o| o.order_id.to_string()))
+            .collect()
+    };
+    // Orders naming no account cannot be routed and reach every stream.
+    assert_eq!(order_ids(drain_quiet(&mut orders_a).await), ["o1", "o3"]);
+    assert_eq!(order_ids(drain_quiet(&mut orders_b).await), ["o2", "o3"]);
+
+    assert!(drain_quiet(&mut nonce_a).await.is_empty());
+    let nonces = drain_quiet(&mut nonce_b).await;
+    assert_eq!(nonces.len(), 1);
+    assert_eq!(nonces[0].nonce, 7);
+
+    let _ = ws.disconnect().await;
+}
+
 #[tokio::test]
 async fn test_ws_reconnect_on_server_disconnect() {
     let first_messages = vec![json!({
@@ -329,6 +966,7 @@ async fn test_ws_reconnect_on_server_disconnect() {
         max_attempts: 3,
         ping_interval: Duration::from_secs(1),
         pong_timeout: Duration::from_secs(2),
+        ..WsConfig::default()
     };
 
     let ws = O2WebSocket::connect_with_config(&url, config)
@@ -441,6 +1079,7 @@ async fn test_ws_reconnect_resubscribes() {
         max_attempts: 5,
         ping_interval: Duration::from_secs(10),
         pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
     };
 
     let ws = O2WebSocket::connect_with_config(&url, config)
@@ -474,6 +1113,192 @@ async fn test_ws_reconnect_resubscribes() {
     let _ = ws.disconnect().await;
 }
 
+#[tokio::test]
+async fn test_ws_reconnect_resubscribes_raw_subscriptions() {
+    // This test verifies that raw subscriptions are re-sent on reconnect
+    // alongside typed ones.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let url = format!("ws://{}", addr);
+    let subscriptions_received = Arc::new(Mutex::new(Vec::new()));
+    let subs_clone = subscriptions_received.clone();
+
+    tokio::spawn(async move {
+        let mut connection_num = 0;
+        loop {
+            if let Ok((stream, _)) = listener.accept().await {
+                connection_num += 1;
+                let is_first = connection_num == 1;
+                let subs = subs_clone.clone();
+
+                if let Ok(ws_stream) = accept_async(stream).await {
+                    let (mut sender, mut receiver) = ws_stream.split();
+
+                    // Collect subscription messages
+                    let mut message_count = 0;
+                    while let Some(Ok(msg)) = receiver.next().await {
+                        match msg {
+                            WsMsg::Text(text) => {
+                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+                                    if let Some(action) =
+                                        json.get("action").and_then(|a| a.as_str())
+                                    {
+                                        subs.lock().await.push(action.to_string());
+                                        message_count += 1;
+
+                                        // After receiving 3 subscription messages on first connection, close it
+                                        if is_first && message_count >= 3 {
+                                            tokio::time::sleep(Duration::from_millis(100)).await;
+                                            let _ = sender.send(WsMsg::Close(None)).await;
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                            WsMsg::Ping(data) => {
+                                let _ = sender.send(WsMsg::Pong(data)).await;
+                            }
+                            WsMsg::Close(_) => break,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let config = WsConfig {
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(500),
+        max_attempts: 5,
+        ping_interval: Duration::from_secs(10),
+        pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
+    };
+
+    let ws = O2WebSocket::connect_with_config(&url, config)
+        .await
+        .unwrap();
+    let _stream1 = ws
+        .stream_depth("market1", &DepthPrecision::new(1).unwrap())
+        .await
+        .unwrap();
+    let _stream2 = ws.stream_trades("market1").await.unwrap();
+    let _stream3 = ws
+        .subscribe_raw(json!({ "action": "subscribe_ticker", "market_id": "market1" }))
+        .await
+        .unwrap();
+
+    // Wait for initial subscriptions and reconnection
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Verify all subscriptions were re-sent after reconnect
+    let subs = subscriptions_received.lock().await;
+    let depth_count = subs.iter().filter(|s| *s == "subscribe_depth").count();
+    let trades_count = subs.iter().filter(|s| *s == "subscribe_trades").count();
+    let ticker_count = subs.iter().filter(|s| *s == "subscribe_ticker").count();
+
+    assert!(
+        depth_count >= 2,
+        "Depth subscription should be sent twice (initial + reconnect), got {}",
+        depth_count
+    );
+    assert!(
+        trades_count >= 2,
+        "Trades subscription should be sent twice (initial + reconnect), got {}",
+        trades_count
+    );
+    assert!(
+        ticker_count >= 2,
+        "Raw subscription should be sent twice (initial + reconnect), got {}",
+        ticker_count
+    );
+
+    let _ = ws.disconnect().await;
+}
+
+#[tokio::test]
+async fn test_ws_subscribe_while_reconnecting_is_queued() {
+    // The first connection is dropped right away; the client subscribes while
+    // it waits to reconnect, and the subscription reaches the second one.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let url = format!("ws://{}", addr);
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = received.clone();
+
+    tokio::spawn(async move {
+        let mut connection_num = 0;
+        while let Ok((stream, _)) = listener.accept().await {
+            connection_num += 1;
+            let Ok(ws_stream) = accept_async(stream).await else {
+                continue;
+            };
+            let (mut sender, mut receiver) = ws_stream.split();
+            if connection_num == 1 {
+                let _ = sender.send(WsMsg::Close(None)).await;
+                continue;
+            }
+            while let Some(Ok(msg)) = receiver.next().await {
+                match msg {
+                    WsMsg::Text(text) => {
+                        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+                        received_clone
+                            .lock()
+                            .await
+                            .push(json["action"].as_str().unwrap_or("").to_string());
+                    }
+                    WsMsg::Ping(data) => {
+                        let _ = sender.send(WsMsg::Pong(data)).await;
+                    }
+                    WsMsg::Close(_) => break,
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    let config = WsConfig {
+        base_delay: Duration::from_millis(300),
+        max_delay: Duration::from_millis(500),
+        max_attempts: 5,
+        ping_interval: Duration::from_secs(10),
+        pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
+    };
+    let ws = O2WebSocket::connect_with_config(&url, config)
+        .await
+        .unwrap();
+    let mut lifecycle = ws.subscribe_lifecycle();
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), lifecycle.recv()).await {
+            Ok(Ok(WsLifecycleEvent::Reconnecting { .. })) => break,
+            Ok(Ok(_)) => continue,
+            other => panic!("expected a reconnect, got {:?}", other),
+        }
+    }
+    assert!(!ws.is_connected());
+
+    let _orders = ws
+        .stream_orders(&[Identity::ContractId("0x01".into())])
+        .await
+        .expect("subscribing while reconnecting should be queued");
+
+    loop {
+        match tokio::time::timeout(Duration::from_secs(2), lifecycle.recv()).await {
+            Ok(Ok(WsLifecycleEvent::Reconnected { .. })) => break,
+            Ok(Ok(_)) => continue,
+            other => panic!("expected to reconnect, got {:?}", other),
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(*received.lock().await, vec!["subscribe_orders".to_string()]);
+
+    let _ = ws.disconnect().await;
+}
+
 #[tokio::test]
 async fn test_ws_max_reconnect_attempts_exhausted() {
     let url = create_refusing_mock_server().await;
@@ -484,6 +1309,7 @@ async fn test_ws_max_reconnect_attempts_exhausted() {
         max_attempts: 2,
         ping_interval: Duration::from_secs(10),
         pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
     };
 
     // Connection will fail because server refuses connections
@@ -620,6 +1446,7 @@ async fn test_ws_unsubscribe_removes_tracking() {
         max_attempts: 5,
         ping_interval: Duration::from_secs(10),
         pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
     };
 
     let ws = O2WebSocket::connect_with_config(&url, config)
@@ -694,6 +1521,7 @@ async fn test_ws_is_connected_state() {
         max_attempts: 5,
         ping_interval: Duration::from_secs(10),
         pong_timeout: Duration::from_secs(20),
+        ..WsConfig::default()
     };
 
     let ws = O2WebSocket::connect_with_config(&url, config)